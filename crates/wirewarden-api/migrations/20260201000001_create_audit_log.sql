@@ -0,0 +1,13 @@
+-- Append-only record of administrative actions
+CREATE TABLE audit_log (
+    id          UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    actor_id    UUID REFERENCES users(id) ON DELETE SET NULL,
+    action      TEXT NOT NULL,
+    network_id  UUID,
+    target_id   UUID,
+    details     JSONB NOT NULL DEFAULT '{}',
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_audit_log_created ON audit_log(created_at DESC);
+CREATE INDEX idx_audit_log_network ON audit_log(network_id, created_at DESC);
@@ -0,0 +1,14 @@
+-- Destructive operations awaiting a second approval
+CREATE TABLE pending_changes (
+    id           UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    change       JSONB NOT NULL,
+    requested_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status       TEXT NOT NULL DEFAULT 'pending',
+    decided_by   UUID REFERENCES users(id) ON DELETE SET NULL,
+    decided_at   TIMESTAMPTZ,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT valid_status CHECK (status IN ('pending', 'approved', 'rejected')),
+    CONSTRAINT decision_pair CHECK ((status = 'pending') = (decided_at IS NULL))
+);
+
+CREATE INDEX idx_pending_changes_status ON pending_changes(status, created_at);
@@ -0,0 +1,20 @@
+ALTER TABLE networks ADD COLUMN enabled BOOL NOT NULL DEFAULT true;
+
+-- Mutations queued to run at a future time by the scheduler
+CREATE TABLE scheduled_changes (
+    id          UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    change      JSONB NOT NULL,
+    run_at      TIMESTAMPTZ NOT NULL,
+    created_by  UUID REFERENCES users(id) ON DELETE SET NULL,
+    status      TEXT NOT NULL DEFAULT 'pending',
+    error       TEXT,
+    -- When a scheduler last moved the row to 'running'; one that stays
+    -- running past its lease belonged to a scheduler that died
+    claimed_at  TIMESTAMPTZ,
+    executed_at TIMESTAMPTZ,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT valid_status CHECK (status IN ('pending', 'running', 'done', 'failed', 'cancelled'))
+);
+
+CREATE INDEX idx_scheduled_changes_due ON scheduled_changes(run_at) WHERE status = 'pending';
+CREATE INDEX idx_scheduled_changes_claimed ON scheduled_changes(claimed_at) WHERE status = 'running';
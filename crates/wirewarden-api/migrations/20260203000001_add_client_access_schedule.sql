@@ -0,0 +1,6 @@
+-- Optional weekly access windows; the scheduler keeps access_allowed in sync
+ALTER TABLE wg_clients
+    ADD COLUMN access_schedule JSONB,
+    ADD COLUMN access_allowed  BOOL NOT NULL DEFAULT true;
+
+CREATE INDEX idx_wg_clients_scheduled ON wg_clients(id) WHERE access_schedule IS NOT NULL;
@@ -0,0 +1,4 @@
+-- Track daemon check-ins so the scheduler can detect offline servers
+ALTER TABLE wg_servers
+    ADD COLUMN last_seen_at TIMESTAMPTZ,
+    ADD COLUMN offline      BOOL NOT NULL DEFAULT false;
@@ -0,0 +1,10 @@
+-- Users opted in to a weekly summary email per network
+CREATE TABLE digest_subscriptions (
+    user_id        UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    network_id     UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    next_digest_at TIMESTAMPTZ NOT NULL,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, network_id)
+);
+
+CREATE INDEX idx_digest_subscriptions_due ON digest_subscriptions(next_digest_at);
@@ -0,0 +1,3 @@
+-- Bumped whenever a change outside the admin UI (such as a dynamic endpoint
+-- update) alters the generated configs for a network.
+ALTER TABLE networks ADD COLUMN config_serial BIGINT NOT NULL DEFAULT 1;
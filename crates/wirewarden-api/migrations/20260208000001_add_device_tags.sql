@@ -0,0 +1,5 @@
+ALTER TABLE wg_servers ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE wg_clients ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX wg_servers_tags_idx ON wg_servers USING GIN (tags);
+CREATE INDEX wg_clients_tags_idx ON wg_clients USING GIN (tags);
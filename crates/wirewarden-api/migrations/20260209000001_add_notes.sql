@@ -0,0 +1,3 @@
+ALTER TABLE networks ADD COLUMN notes TEXT;
+ALTER TABLE wg_servers ADD COLUMN notes TEXT;
+ALTER TABLE wg_clients ADD COLUMN notes TEXT;
@@ -0,0 +1,52 @@
+-- Names become unique regardless of case and surrounding whitespace.
+ALTER TABLE networks DROP CONSTRAINT networks_name_key;
+ALTER TABLE wg_servers DROP CONSTRAINT wg_servers_network_id_name_key;
+ALTER TABLE wg_clients DROP CONSTRAINT wg_clients_network_id_name_key;
+
+UPDATE networks SET name = btrim(name) WHERE name <> btrim(name);
+UPDATE wg_servers SET name = btrim(name) WHERE name <> btrim(name);
+UPDATE wg_clients SET name = btrim(name) WHERE name <> btrim(name);
+
+-- Names that now collide keep the oldest as-is. Each of the others takes the
+-- first `-N` suffix, counting from 2, that nothing in its scope already uses
+-- in any case, so a renamed row cannot run into an existing `home-2`.
+CREATE FUNCTION pg_temp.dedupe_names(tbl regclass, scope text) RETURNS void AS $$
+DECLARE
+    dup record;
+    n int;
+    candidate text;
+    taken bool;
+BEGIN
+    FOR dup IN EXECUTE format(
+        'SELECT id, name, scope FROM (
+             SELECT id, name, %1$s AS scope, row_number() OVER (
+                 PARTITION BY %1$s, lower(name) ORDER BY created_at, id
+             ) AS rank
+             FROM %2$s
+         ) ranked
+         WHERE rank > 1
+         ORDER BY scope, lower(name), rank', scope, tbl)
+    LOOP
+        n := 2;
+        LOOP
+            candidate := dup.name || '-' || n;
+            EXECUTE format(
+                'SELECT EXISTS (SELECT 1 FROM %s
+                 WHERE %s IS NOT DISTINCT FROM $1 AND lower(name) = lower($2))', tbl, scope)
+                INTO taken USING dup.scope, candidate;
+            EXIT WHEN NOT taken;
+            n := n + 1;
+        END LOOP;
+        EXECUTE format('UPDATE %s SET name = $1 WHERE id = $2', tbl) USING candidate, dup.id;
+    END LOOP;
+END
+$$ LANGUAGE plpgsql;
+
+SELECT pg_temp.dedupe_names('networks', 'NULL::uuid');
+SELECT pg_temp.dedupe_names('wg_servers', 'network_id');
+SELECT pg_temp.dedupe_names('wg_clients', 'network_id');
+DROP FUNCTION pg_temp.dedupe_names(regclass, text);
+
+CREATE UNIQUE INDEX networks_name_ci_key ON networks (lower(name));
+CREATE UNIQUE INDEX wg_servers_network_id_name_ci_key ON wg_servers (network_id, lower(name));
+CREATE UNIQUE INDEX wg_clients_network_id_name_ci_key ON wg_clients (network_id, lower(name));
@@ -0,0 +1,51 @@
+-- Keep networks.config_serial in step with everything that feeds a daemon
+-- config, so rendered configs can be cached by serial. Heartbeat columns
+-- (last_seen_at, offline) and metadata (tags, notes) are deliberately left
+-- out.
+
+CREATE FUNCTION bump_own_config_serial() RETURNS trigger AS $$
+BEGIN
+    NEW.config_serial := OLD.config_serial + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER networks_config_serial
+    BEFORE UPDATE OF name, cidr_ip, dns_servers, persistent_keepalive, enabled ON networks
+    FOR EACH ROW EXECUTE FUNCTION bump_own_config_serial();
+
+CREATE FUNCTION bump_peer_network_config_serial() RETURNS trigger AS $$
+BEGIN
+    UPDATE networks SET config_serial = config_serial + 1
+    WHERE id IN (OLD.network_id, NEW.network_id);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER wg_servers_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        forwards_internet_traffic, endpoint_host, endpoint_port ON wg_servers
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
+
+CREATE TRIGGER wg_clients_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        access_allowed ON wg_clients
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
+
+CREATE FUNCTION bump_server_network_config_serial() RETURNS trigger AS $$
+BEGIN
+    UPDATE networks SET config_serial = config_serial + 1
+    WHERE id IN (
+        SELECT network_id FROM wg_servers WHERE id IN (OLD.server_id, NEW.server_id)
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER wg_server_routes_config_serial
+    AFTER INSERT OR DELETE OR UPDATE ON wg_server_routes
+    FOR EACH ROW EXECUTE FUNCTION bump_server_network_config_serial();
+
+CREATE TRIGGER wg_peer_psks_config_serial
+    AFTER INSERT OR DELETE OR UPDATE ON wg_peer_psks
+    FOR EACH ROW EXECUTE FUNCTION bump_server_network_config_serial();
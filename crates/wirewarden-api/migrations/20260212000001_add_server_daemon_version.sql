@@ -0,0 +1 @@
+ALTER TABLE wg_servers ADD COLUMN daemon_version TEXT;
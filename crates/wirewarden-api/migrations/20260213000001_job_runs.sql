@@ -0,0 +1,6 @@
+-- Last start of each background job, so replicas sharing a database run a
+-- job once per interval instead of once per replica.
+CREATE TABLE job_runs (
+    name       TEXT PRIMARY KEY,
+    started_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,10 @@
+-- Log filter and request log sampling set at runtime through
+-- /api/tools/logging. A single row; absent means the RUST_LOG defaults.
+CREATE TABLE log_settings (
+    id                  BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    filter              TEXT NOT NULL,
+    request_sample_rate DOUBLE PRECISION NOT NULL
+        CHECK (request_sample_rate >= 0 AND request_sample_rate <= 1),
+    updated_by          UUID REFERENCES users (id) ON DELETE SET NULL,
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
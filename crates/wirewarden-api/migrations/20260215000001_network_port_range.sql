@@ -0,0 +1,10 @@
+-- Listen ports servers on the network may use; new servers get the lowest
+-- one not taken on their endpoint host. Both NULL means unrestricted.
+ALTER TABLE networks
+    ADD COLUMN port_range_start INTEGER,
+    ADD COLUMN port_range_end INTEGER,
+    ADD CONSTRAINT networks_port_range_check CHECK (
+        (port_range_start IS NULL AND port_range_end IS NULL)
+        OR (port_range_start BETWEEN 1 AND 65535
+            AND port_range_end BETWEEN port_range_start AND 65535)
+    );
@@ -0,0 +1,8 @@
+-- IPv6-only networks: a unique local /64 or narrower, checked by the API.
+ALTER TABLE networks DROP CONSTRAINT valid_prefix;
+ALTER TABLE networks ADD CONSTRAINT valid_prefix CHECK (
+    CASE family(cidr_ip)
+        WHEN 4 THEN masklen(cidr_ip) BETWEEN 8 AND 30
+        ELSE masklen(cidr_ip) BETWEEN 64 AND 126
+    END
+);
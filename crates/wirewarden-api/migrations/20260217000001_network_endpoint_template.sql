@@ -0,0 +1,3 @@
+-- Hostname for servers created without an endpoint, e.g.
+-- '{server}.vpn.example.com'. NULL means servers get no endpoint by default.
+ALTER TABLE networks ADD COLUMN endpoint_template TEXT;
@@ -0,0 +1,11 @@
+-- Servers that forward internet traffic can have their daemon manage the
+-- masquerade rules. The flag is part of the server's daemon config, so it
+-- bumps the config serial like the other columns rendered there.
+ALTER TABLE wg_servers ADD COLUMN manage_nat BOOL NOT NULL DEFAULT false;
+
+DROP TRIGGER wg_servers_config_serial ON wg_servers;
+
+CREATE TRIGGER wg_servers_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        forwards_internet_traffic, manage_nat, endpoint_host, endpoint_port ON wg_servers
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
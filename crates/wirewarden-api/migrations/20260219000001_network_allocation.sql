@@ -0,0 +1,4 @@
+-- How new servers and clients are given addresses in a network.
+ALTER TABLE networks ADD COLUMN allocation TEXT NOT NULL DEFAULT 'lowest-free'
+    CONSTRAINT valid_allocation
+    CHECK (allocation IN ('lowest-free', 'random-in-range', 'sequential-from-high'));
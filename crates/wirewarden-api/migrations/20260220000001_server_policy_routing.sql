@@ -0,0 +1,20 @@
+-- Optional fwmark and policy routing for a server's interface: the mark
+-- WireGuard puts on its own packets, the table its routes go in, and the
+-- priority of the rule that sends traffic to that table. All three are
+-- unsigned 32-bit values in the kernel, hence BIGINT.
+ALTER TABLE wg_servers
+    ADD COLUMN fwmark BIGINT CONSTRAINT valid_fwmark CHECK (fwmark BETWEEN 1 AND 4294967295),
+    ADD COLUMN route_table BIGINT
+        CONSTRAINT valid_route_table CHECK (route_table BETWEEN 1 AND 4294967295),
+    ADD COLUMN rule_priority BIGINT
+        CONSTRAINT valid_rule_priority CHECK (rule_priority BETWEEN 1 AND 4294967295),
+    ADD CONSTRAINT rule_priority_needs_table
+        CHECK (rule_priority IS NULL OR route_table IS NOT NULL);
+
+DROP TRIGGER wg_servers_config_serial ON wg_servers;
+
+CREATE TRIGGER wg_servers_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        forwards_internet_traffic, manage_nat, fwmark, route_table, rule_priority,
+        endpoint_host, endpoint_port ON wg_servers
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
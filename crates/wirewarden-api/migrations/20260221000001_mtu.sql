@@ -0,0 +1,24 @@
+-- Interface MTU for a network's peers, with overrides per server and per
+-- client. NULL leaves the default: the kernel's on servers, wg-quick's on
+-- clients. Client MTUs only reach the client's own config, so only the
+-- network and server columns feed the config serial.
+ALTER TABLE networks
+    ADD COLUMN mtu INTEGER CONSTRAINT valid_mtu CHECK (mtu BETWEEN 576 AND 65535);
+ALTER TABLE wg_servers
+    ADD COLUMN mtu INTEGER CONSTRAINT valid_mtu CHECK (mtu BETWEEN 576 AND 65535);
+ALTER TABLE wg_clients
+    ADD COLUMN mtu INTEGER CONSTRAINT valid_mtu CHECK (mtu BETWEEN 576 AND 65535);
+
+DROP TRIGGER networks_config_serial ON networks;
+
+CREATE TRIGGER networks_config_serial
+    BEFORE UPDATE OF name, cidr_ip, dns_servers, persistent_keepalive, enabled, mtu ON networks
+    FOR EACH ROW EXECUTE FUNCTION bump_own_config_serial();
+
+DROP TRIGGER wg_servers_config_serial ON wg_servers;
+
+CREATE TRIGGER wg_servers_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        forwards_internet_traffic, manage_nat, fwmark, route_table, rule_priority, mtu,
+        endpoint_host, endpoint_port ON wg_servers
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
@@ -0,0 +1,14 @@
+-- Port the daemon listens on, when it differs from the endpoint_port peers
+-- dial, as behind a NAT that forwards another external port. NULL listens on
+-- endpoint_port.
+ALTER TABLE wg_servers
+    ADD COLUMN listen_port INTEGER
+        CONSTRAINT valid_listen_port CHECK (listen_port BETWEEN 1 AND 65535);
+
+DROP TRIGGER wg_servers_config_serial ON wg_servers;
+
+CREATE TRIGGER wg_servers_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        forwards_internet_traffic, manage_nat, fwmark, route_table, rule_priority, mtu,
+        endpoint_host, endpoint_port, listen_port ON wg_servers
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
@@ -0,0 +1,12 @@
+-- Order in which a network's servers claim overlapping AllowedIPs: higher
+-- first, ties by created_at. Peer order also decides which server a daemon
+-- routes an overlapping prefix to, so it feeds the config serial.
+ALTER TABLE wg_servers ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+
+DROP TRIGGER wg_servers_config_serial ON wg_servers;
+
+CREATE TRIGGER wg_servers_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        forwards_internet_traffic, manage_nat, fwmark, route_table, rule_priority, mtu,
+        endpoint_host, endpoint_port, listen_port, priority ON wg_servers
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
@@ -0,0 +1,16 @@
+-- Per-client AllowedIPs overrides. An `extra` route sends one more CIDR
+-- through the given server; `only` routes narrow every server's AllowedIPs in
+-- the client's config down to the CIDRs listed.
+CREATE TABLE wg_client_routes (
+    id         UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    client_id  UUID NOT NULL REFERENCES wg_clients(id) ON DELETE CASCADE,
+    kind       TEXT NOT NULL CHECK (kind IN ('extra', 'only')),
+    server_id  UUID REFERENCES wg_servers(id) ON DELETE CASCADE,
+    route_cidr CIDR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK ((kind = 'extra') = (server_id IS NOT NULL)),
+    UNIQUE (client_id, kind, route_cidr)
+);
+
+CREATE INDEX idx_wg_client_routes_client ON wg_client_routes(client_id);
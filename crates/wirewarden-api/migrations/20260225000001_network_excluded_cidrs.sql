@@ -0,0 +1,4 @@
+-- CIDRs that full-tunnel clients keep off the VPN on top of the private
+-- ranges, e.g. the office LAN or a streaming provider. Only client configs
+-- use them, so they do not feed the config serial.
+ALTER TABLE networks ADD COLUMN excluded_cidrs TEXT[] NOT NULL DEFAULT '{}';
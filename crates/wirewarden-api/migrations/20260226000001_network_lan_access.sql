@@ -0,0 +1,4 @@
+-- Whether full-tunnel clients keep reaching their local LAN, by leaving the
+-- private ranges off the tunnel. On by default; a config request can override
+-- it for one download.
+ALTER TABLE networks ADD COLUMN lan_access BOOLEAN NOT NULL DEFAULT true;
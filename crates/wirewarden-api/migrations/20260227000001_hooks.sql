@@ -0,0 +1,12 @@
+-- wg-quick PostUp/PreDown/PostDown commands for client configs, set on a
+-- network and overridden per client. A client column left NULL inherits the
+-- network's command; there is no way to clear a network hook for one client
+-- short of setting a no-op such as `true`.
+ALTER TABLE networks
+    ADD COLUMN post_up TEXT,
+    ADD COLUMN pre_down TEXT,
+    ADD COLUMN post_down TEXT;
+ALTER TABLE wg_clients
+    ADD COLUMN post_up TEXT,
+    ADD COLUMN pre_down TEXT,
+    ADD COLUMN post_down TEXT;
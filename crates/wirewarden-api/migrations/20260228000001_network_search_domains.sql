@@ -0,0 +1,4 @@
+-- DNS search domains handed to clients alongside the network's DNS servers,
+-- so short hostnames resolve inside the VPN. Written after the servers on the
+-- client's `DNS =` line; empty by default, which leaves that line as it was.
+ALTER TABLE networks ADD COLUMN search_domains TEXT[] NOT NULL DEFAULT '{}';
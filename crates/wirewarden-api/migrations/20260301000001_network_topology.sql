@@ -0,0 +1,14 @@
+-- Whether a network's servers peer with each other. `mesh`, what every
+-- network did before, gives each server the others with their routes, for
+-- site-to-site links; `hub` leaves each server to its own clients. It feeds
+-- daemon configs, so it joins the config serial.
+ALTER TABLE networks ADD COLUMN topology TEXT NOT NULL DEFAULT 'mesh'
+    CONSTRAINT valid_topology
+    CHECK (topology IN ('hub', 'mesh'));
+
+DROP TRIGGER networks_config_serial ON networks;
+
+CREATE TRIGGER networks_config_serial
+    BEFORE UPDATE OF name, cidr_ip, dns_servers, persistent_keepalive, enabled, mtu, topology
+    ON networks
+    FOR EACH ROW EXECUTE FUNCTION bump_own_config_serial();
@@ -0,0 +1,26 @@
+-- Persistent keepalive overrides per server and per client, e.g. for NAT'd
+-- peers on a network that otherwise leaves keepalive off. NULL falls back to
+-- the network's; zero turns keepalive off. Both reach daemon configs, the
+-- client's through the servers' peer entries for it.
+ALTER TABLE wg_servers
+    ADD COLUMN persistent_keepalive INTEGER
+        CONSTRAINT valid_keepalive CHECK (persistent_keepalive BETWEEN 0 AND 65535);
+ALTER TABLE wg_clients
+    ADD COLUMN persistent_keepalive INTEGER
+        CONSTRAINT valid_keepalive CHECK (persistent_keepalive BETWEEN 0 AND 65535);
+
+DROP TRIGGER wg_servers_config_serial ON wg_servers;
+
+CREATE TRIGGER wg_servers_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        forwards_internet_traffic, manage_nat, fwmark, route_table, rule_priority, mtu,
+        endpoint_host, endpoint_port, listen_port, priority, persistent_keepalive
+        ON wg_servers
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
+
+DROP TRIGGER wg_clients_config_serial ON wg_clients;
+
+CREATE TRIGGER wg_clients_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        access_allowed, persistent_keepalive ON wg_clients
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
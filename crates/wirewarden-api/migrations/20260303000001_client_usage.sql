@@ -0,0 +1,25 @@
+-- Transfer counters daemons last reported, per server and client. Counters
+-- run from when the server's interface added the peer, so usage is the
+-- difference between reports; a counter below the last one was reset.
+CREATE TABLE wg_peer_counters (
+    server_id  UUID   NOT NULL REFERENCES wg_servers(id) ON DELETE CASCADE,
+    client_id  UUID   NOT NULL REFERENCES wg_clients(id) ON DELETE CASCADE,
+    rx_bytes   BIGINT NOT NULL,
+    tx_bytes   BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (server_id, client_id)
+);
+
+-- Bytes each client moved, summed over its servers, in hourly buckets that
+-- are rolled up into daily ones as they age. rx is what the servers
+-- received from the client, tx what they sent it.
+CREATE TABLE client_usage (
+    client_id  UUID   NOT NULL REFERENCES wg_clients(id) ON DELETE CASCADE,
+    resolution TEXT   NOT NULL CHECK (resolution IN ('hour', 'day')),
+    bucket     TIMESTAMPTZ NOT NULL,
+    rx_bytes   BIGINT NOT NULL DEFAULT 0,
+    tx_bytes   BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (client_id, resolution, bucket)
+);
+
+CREATE INDEX idx_client_usage_age ON client_usage(resolution, bucket);
@@ -0,0 +1,14 @@
+-- Monthly transfer limits per client. Once a client's usage this UTC month
+-- reaches its quota it is marked over quota and left out of daemon configs
+-- until the next month, or until the quota is raised or cleared.
+ALTER TABLE wg_clients
+    ADD COLUMN monthly_quota_bytes BIGINT
+        CONSTRAINT positive_quota CHECK (monthly_quota_bytes > 0),
+    ADD COLUMN quota_exceeded BOOLEAN NOT NULL DEFAULT false;
+
+DROP TRIGGER wg_clients_config_serial ON wg_clients;
+
+CREATE TRIGGER wg_clients_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        access_allowed, persistent_keepalive, quota_exceeded ON wg_clients
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
@@ -0,0 +1,14 @@
+-- Bandwidth caps per client in kilobits per second, shaped by the daemons
+-- on every server the client connects through. Upload is what the client
+-- sends, download what it receives.
+ALTER TABLE wg_clients
+    ADD COLUMN upload_kbps INTEGER CONSTRAINT positive_upload CHECK (upload_kbps > 0),
+    ADD COLUMN download_kbps INTEGER CONSTRAINT positive_download CHECK (download_kbps > 0);
+
+DROP TRIGGER wg_clients_config_serial ON wg_clients;
+
+CREATE TRIGGER wg_clients_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        access_allowed, persistent_keepalive, quota_exceeded, upload_kbps, download_kbps
+        ON wg_clients
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
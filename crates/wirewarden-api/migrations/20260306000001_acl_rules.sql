@@ -0,0 +1,32 @@
+-- Forwarding policy between a network's peers, compiled into firewall rules
+-- on every server. Rules are checked in position order and the first match
+-- decides; traffic no rule matches is allowed, so a network without rules
+-- behaves as before. Each side is `*`, `tag:<tag>` for the clients carrying
+-- the tag, or a CIDR.
+CREATE TABLE wg_acl_rules (
+    id          UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    network_id  UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    position    INTEGER NOT NULL CHECK (position > 0),
+    action      TEXT NOT NULL CHECK (action IN ('allow', 'deny')),
+    source      TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- Deferred so inserting or deleting can shift the rules after it.
+    CONSTRAINT unique_acl_position UNIQUE (network_id, position)
+        DEFERRABLE INITIALLY DEFERRED
+);
+
+CREATE TRIGGER wg_acl_rules_config_serial
+    AFTER INSERT OR DELETE OR UPDATE ON wg_acl_rules
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
+
+-- Tag selectors resolve to addresses, so tags now feed daemon configs too.
+DROP TRIGGER wg_clients_config_serial ON wg_clients;
+
+CREATE TRIGGER wg_clients_config_serial
+    AFTER INSERT OR DELETE OR UPDATE OF network_id, name, key_id, address_offset,
+        access_allowed, persistent_keepalive, quota_exceeded, upload_kbps, download_kbps,
+        tags
+        ON wg_clients
+    FOR EACH ROW EXECUTE FUNCTION bump_peer_network_config_serial();
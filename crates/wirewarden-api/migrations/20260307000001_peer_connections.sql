@@ -0,0 +1,28 @@
+-- Whether each peer of a server has a live session, judged from the
+-- handshakes daemons report: one in the last three minutes counts as
+-- connected. Peers are clients or other servers of the network.
+CREATE TABLE peer_connections (
+    server_id      UUID NOT NULL REFERENCES wg_servers(id) ON DELETE CASCADE,
+    peer_id        UUID NOT NULL,
+    connected      BOOLEAN NOT NULL,
+    last_handshake TIMESTAMPTZ,
+    changed_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (server_id, peer_id)
+);
+
+-- Every change in peer_connections, newest read first. Peer names are kept
+-- so the history still reads after a peer is deleted.
+CREATE TABLE connection_events (
+    id             UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    network_id     UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    server_id      UUID NOT NULL REFERENCES wg_servers(id) ON DELETE CASCADE,
+    peer_id        UUID NOT NULL,
+    peer_kind      TEXT NOT NULL CHECK (peer_kind IN ('server', 'client')),
+    peer_name      TEXT NOT NULL,
+    connected      BOOLEAN NOT NULL,
+    last_handshake TIMESTAMPTZ,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_connection_events_network ON connection_events(network_id, created_at DESC);
+CREATE INDEX idx_connection_events_peer ON connection_events(peer_id, created_at DESC);
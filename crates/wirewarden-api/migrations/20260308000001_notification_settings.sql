@@ -0,0 +1,8 @@
+-- Which events each user is emailed about. Users without a row get nothing.
+CREATE TABLE notification_settings (
+    user_id              UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    server_offline       BOOL NOT NULL DEFAULT false,
+    client_created       BOOL NOT NULL DEFAULT false,
+    server_token_rotated BOOL NOT NULL DEFAULT false,
+    updated_at           TIMESTAMPTZ NOT NULL DEFAULT now()
+);
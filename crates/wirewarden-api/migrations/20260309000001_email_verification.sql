@@ -0,0 +1,11 @@
+-- Accounts confirm their address through a mailed token. Existing accounts
+-- predate verification and count as verified.
+ALTER TABLE users
+    ADD COLUMN email_verified_at       TIMESTAMPTZ,
+    ADD COLUMN verify_token            TEXT,
+    ADD COLUMN verify_token_expires_at TIMESTAMPTZ,
+    ADD CONSTRAINT verify_token_pair CHECK (
+        (verify_token IS NULL) = (verify_token_expires_at IS NULL)
+    );
+
+UPDATE users SET email_verified_at = created_at;
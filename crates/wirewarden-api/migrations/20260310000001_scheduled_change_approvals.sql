@@ -0,0 +1,7 @@
+-- A destructive scheduled change runs only once this approval is granted
+-- while the two-person rule is on
+ALTER TABLE scheduled_changes
+    ADD COLUMN approval_id UUID REFERENCES pending_changes(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_scheduled_changes_approval ON scheduled_changes(approval_id)
+    WHERE approval_id IS NOT NULL;
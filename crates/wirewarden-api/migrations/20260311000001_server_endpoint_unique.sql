@@ -0,0 +1,6 @@
+-- Two servers on one endpoint host cannot share a port. The API checks this
+-- before writing; the index closes the race between two concurrent writes.
+-- Servers that already collide must be moved to another port before this
+-- migration can apply.
+CREATE UNIQUE INDEX wg_servers_endpoint_key ON wg_servers (lower(endpoint_host), endpoint_port)
+    WHERE endpoint_host IS NOT NULL;
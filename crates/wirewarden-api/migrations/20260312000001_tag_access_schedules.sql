@@ -0,0 +1,10 @@
+-- Access schedules for every client in a network carrying a tag. A client's
+-- own schedule takes precedence over these.
+CREATE TABLE tag_access_schedules (
+    network_id  UUID        NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    tag         TEXT        NOT NULL,
+    schedule    JSONB       NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (network_id, tag)
+);
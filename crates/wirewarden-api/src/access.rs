@@ -0,0 +1,178 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Weekly time windows during which a client may connect, set on the
+//! client itself or on a tag for every client carrying it.
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::vpn::TagAccessSchedule;
+
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessSchedule {
+    /// IANA zone that window times are expressed in, e.g.
+    /// `Europe/Berlin`. Windows follow its daylight saving changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Tz>,
+    /// Fixed offset from UTC, in minutes, for schedules without a
+    /// `timezone`. It does not follow daylight saving changes.
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    pub windows: Vec<AccessWindow>,
+}
+
+/// A daily window on the listed days. If `end` is before `start` the window
+/// runs past midnight into the following day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessWindow {
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl AccessSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+            return Err("utc_offset_minutes must be within ±14 hours".into());
+        }
+        if self.timezone.is_some() && self.utc_offset_minutes != 0 {
+            return Err("set timezone or utc_offset_minutes, not both".into());
+        }
+        if self.windows.is_empty() {
+            return Err("schedule must contain at least one window".into());
+        }
+        for window in &self.windows {
+            if window.days.is_empty() {
+                return Err("each window must list at least one day".into());
+            }
+            if window.start == window.end {
+                return Err("window start and end must differ".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether access is allowed at `now`.
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        let local = match self.timezone {
+            Some(tz) => now.with_timezone(&tz).naive_local(),
+            None => now.naive_utc() + Duration::minutes(self.utc_offset_minutes.into()),
+        };
+        let (day, time) = (local.weekday(), local.time());
+        self.windows.iter().any(|w| {
+            if w.start < w.end {
+                w.days.contains(&day) && w.start <= time && time < w.end
+            } else {
+                (w.days.contains(&day) && time >= w.start)
+                    || (w.days.contains(&day.pred()) && time < w.end)
+            }
+        })
+    }
+}
+
+/// Whether a client may connect at `now`: under its own schedule when it
+/// has one, otherwise while any schedule on one of its tags is open.
+/// Clients with neither may always connect.
+pub fn is_allowed(
+    own: Option<&AccessSchedule>,
+    tagged: &[&AccessSchedule],
+    now: DateTime<Utc>,
+) -> bool {
+    match own {
+        Some(schedule) => schedule.is_open(now),
+        None => tagged.is_empty() || tagged.iter().any(|s| s.is_open(now)),
+    }
+}
+
+/// The schedules in `tag_schedules` for a client in `network_id` carrying
+/// `tags`.
+pub fn tagged<'a>(
+    tag_schedules: &'a [TagAccessSchedule],
+    network_id: Uuid,
+    tags: &[String],
+) -> Vec<&'a AccessSchedule> {
+    tag_schedules
+        .iter()
+        .filter(|t| t.network_id == network_id && tags.contains(&t.tag))
+        .map(|t| &t.schedule.0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn schedule(json: &str) -> AccessSchedule {
+        serde_json::from_str(json).unwrap()
+    }
+
+    const DAYTIME: &str = r#"{"windows":[{"days":["Mon","Tue","Wed","Thu","Fri","Sat","Sun"],"start":"07:00:00","end":"21:00:00"}]}"#;
+    const OVERNIGHT: &str = r#"{"windows":[{"days":["Fri"],"start":"22:00:00","end":"02:00:00"}]}"#;
+    const OFFSET: &str = r#"{"utc_offset_minutes":-300,"windows":[{"days":["Mon"],"start":"09:00:00","end":"17:00:00"}]}"#;
+    const ZONED: &str = r#"{"timezone":"America/New_York","windows":[{"days":["Mon"],"start":"09:00:00","end":"17:00:00"}]}"#;
+
+    // 2026-02-02 is a Monday.
+    #[test_case(DAYTIME, "2026-02-02T06:59:00Z", false ; "before window")]
+    #[test_case(DAYTIME, "2026-02-02T07:00:00Z", true ; "window start inclusive")]
+    #[test_case(DAYTIME, "2026-02-02T21:00:00Z", false ; "window end exclusive")]
+    #[test_case(OVERNIGHT, "2026-02-06T23:00:00Z", true ; "overnight same day")]
+    #[test_case(OVERNIGHT, "2026-02-07T01:00:00Z", true ; "overnight next day")]
+    #[test_case(OVERNIGHT, "2026-02-07T23:00:00Z", false ; "overnight wrong day")]
+    #[test_case(OFFSET, "2026-02-02T13:59:00Z", false ; "offset before")]
+    #[test_case(OFFSET, "2026-02-02T14:00:00Z", true ; "offset inside")]
+    // New York is UTC-5 in February and UTC-4 from March 8, 2026.
+    #[test_case(ZONED, "2026-02-02T13:59:00Z", false ; "zone before in winter")]
+    #[test_case(ZONED, "2026-02-02T14:00:00Z", true ; "zone inside in winter")]
+    #[test_case(ZONED, "2026-07-06T13:00:00Z", true ; "zone inside in summer")]
+    #[test_case(ZONED, "2026-07-06T21:30:00Z", false ; "zone after in summer")]
+    fn test_is_open(json: &str, now: &str, expected: bool) {
+        let now: DateTime<Utc> = now.parse().unwrap();
+        assert_eq!(schedule(json).is_open(now), expected);
+    }
+
+    #[test_case(r#"{"windows":[]}"# ; "no windows")]
+    #[test_case(r#"{"windows":[{"days":[],"start":"07:00:00","end":"21:00:00"}]}"# ; "no days")]
+    #[test_case(r#"{"windows":[{"days":["Mon"],"start":"07:00:00","end":"07:00:00"}]}"# ; "empty window")]
+    #[test_case(r#"{"utc_offset_minutes":900,"windows":[{"days":["Mon"],"start":"07:00:00","end":"21:00:00"}]}"# ; "offset too large")]
+    #[test_case(r#"{"timezone":"Europe/Berlin","utc_offset_minutes":60,"windows":[{"days":["Mon"],"start":"07:00:00","end":"21:00:00"}]}"# ; "zone and offset")]
+    fn test_validate_rejects(json: &str) {
+        assert!(schedule(json).validate().is_err());
+    }
+
+    #[test]
+    fn test_unknown_timezone_is_rejected() {
+        let json = r#"{"timezone":"Mars/Olympus_Mons","windows":[]}"#;
+        assert!(serde_json::from_str::<AccessSchedule>(json).is_err());
+    }
+
+    // 2026-02-02 is a Monday; DAYTIME is open at noon, OVERNIGHT is not.
+    #[test_case(Some(DAYTIME), &[], true ; "own open")]
+    #[test_case(Some(OVERNIGHT), &[DAYTIME], false ; "own wins over tags")]
+    #[test_case(None, &[], true ; "unscheduled")]
+    #[test_case(None, &[OVERNIGHT], false ; "tag closed")]
+    #[test_case(None, &[OVERNIGHT, DAYTIME], true ; "any tag open")]
+    fn test_is_allowed(own: Option<&str>, tagged: &[&str], expected: bool) {
+        let now: DateTime<Utc> = "2026-02-02T12:00:00Z".parse().unwrap();
+        let own = own.map(schedule);
+        let tagged: Vec<_> = tagged.iter().map(|json| schedule(json)).collect();
+        let tagged: Vec<_> = tagged.iter().collect();
+        assert_eq!(is_allowed(own.as_ref(), &tagged, now), expected);
+    }
+}
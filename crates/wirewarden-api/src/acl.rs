@@ -0,0 +1,197 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Forwarding rules between a network's peers. Each side of a rule is `*`,
+//! `tag:<tag>` for the clients carrying the tag, or a CIDR; the daemons
+//! enforce them on traffic their servers forward, the first match deciding.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+use wirewarden_types::daemon::{DaemonAclAction, DaemonAclRule};
+
+use crate::db::vpn::{self, AclAction, Network, WgAclRule, WgClient};
+use crate::tags;
+
+/// One side of a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    Any,
+    Tag(String),
+    Cidr(IpNetwork),
+}
+
+impl Selector {
+    /// Parse a selector, normalizing tags as [`tags::normalize`] does.
+    /// `field` names it in the error.
+    pub fn parse(s: &str, field: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s == "*" {
+            return Ok(Self::Any);
+        }
+        if let Some(tag) = s.strip_prefix("tag:") {
+            let mut tag = tags::normalize(&[tag.to_string()])
+                .map_err(|e| format!("{field}: {e}"))?;
+            return Ok(Self::Tag(tag.remove(0)));
+        }
+        s.parse().map(Self::Cidr).map_err(|_| {
+            format!("{field} must be \"*\", \"tag:<tag>\", or a CIDR")
+        })
+    }
+
+    /// The CIDRs matched, or `None` for any address.
+    fn resolve(&self, members: &HashMap<String, Vec<IpAddr>>) -> Option<Vec<String>> {
+        match self {
+            Self::Any => None,
+            Self::Tag(tag) => Some(
+                members
+                    .get(tag)
+                    .into_iter()
+                    .flatten()
+                    .map(|ip| IpNetwork::from(*ip).to_string())
+                    .collect(),
+            ),
+            Self::Cidr(cidr) => Some(vec![cidr.to_string()]),
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => f.write_str("*"),
+            Self::Tag(tag) => write!(f, "tag:{tag}"),
+            Self::Cidr(cidr) => write!(f, "{cidr}"),
+        }
+    }
+}
+
+/// The addresses of `clients` under each tag they carry.
+pub fn tag_members(network: &Network, clients: &[WgClient]) -> HashMap<String, Vec<IpAddr>> {
+    let mut members: HashMap<String, Vec<IpAddr>> = HashMap::new();
+    for client in clients {
+        let ip = vpn::compute_address(network, client.address_offset);
+        for tag in &client.tags {
+            members.entry(tag.clone()).or_default().push(ip);
+        }
+    }
+    members
+}
+
+/// `rules`, in order, as the daemons take them. A rule whose tag has no
+/// clients can match nothing, so it is left out rather than sent with an
+/// empty side, which would match anything.
+pub fn compile(
+    rules: &[WgAclRule],
+    members: &HashMap<String, Vec<IpAddr>>,
+) -> Vec<DaemonAclRule> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let side = |s: &str| match Selector::parse(s, "selector") {
+                Ok(selector) => match selector.resolve(members) {
+                    Some(cidrs) if cidrs.is_empty() => None,
+                    resolved => Some(resolved.unwrap_or_default()),
+                },
+                Err(e) => {
+                    tracing::warn!(rule_id = %rule.id, error = %e, "skipping unparseable ACL rule");
+                    None
+                }
+            };
+            Some(DaemonAclRule {
+                action: match rule.action {
+                    AclAction::Allow => DaemonAclAction::Allow,
+                    AclAction::Deny => DaemonAclAction::Deny,
+                },
+                sources: side(&rule.source)?,
+                destinations: side(&rule.destination)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use test_case::test_case;
+    use uuid::Uuid;
+
+    #[test_case("*", Selector::Any ; "any")]
+    #[test_case(" tag:Kids ", Selector::Tag("kids".into()) ; "tag")]
+    #[test_case("10.0.0.0/24", Selector::Cidr("10.0.0.0/24".parse().unwrap()) ; "cidr")]
+    #[test_case("fd00::5", Selector::Cidr("fd00::5/128".parse().unwrap()) ; "bare address")]
+    fn test_parse(input: &str, expected: Selector) {
+        assert_eq!(Selector::parse(input, "source").unwrap(), expected);
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("tag:" ; "empty tag")]
+    #[test_case("tag:two words" ; "bad tag")]
+    #[test_case("any" ; "word")]
+    #[test_case("10.0.0.0/33" ; "bad prefix")]
+    fn test_parse_rejects(input: &str) {
+        assert!(Selector::parse(input, "source").is_err());
+    }
+
+    #[test_case("*" ; "any")]
+    #[test_case("tag:kids" ; "tag")]
+    #[test_case("10.0.0.0/24" ; "cidr")]
+    fn test_display_round_trips(input: &str) {
+        assert_eq!(Selector::parse(input, "source").unwrap().to_string(), input);
+    }
+
+    fn rule(action: AclAction, source: &str, destination: &str) -> WgAclRule {
+        WgAclRule {
+            id: Uuid::nil(),
+            network_id: Uuid::nil(),
+            position: 1,
+            action,
+            source: source.into(),
+            destination: destination.into(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        let members = HashMap::from([(
+            "kids".to_string(),
+            vec!["10.0.0.2".parse().unwrap(), "10.0.0.3".parse().unwrap()],
+        )]);
+        let rules = [
+            rule(AclAction::Allow, "tag:kids", "10.0.0.1/32"),
+            rule(AclAction::Deny, "tag:iot", "*"),
+            rule(AclAction::Deny, "tag:kids", "*"),
+        ];
+        assert_eq!(
+            compile(&rules, &members),
+            [
+                DaemonAclRule {
+                    action: DaemonAclAction::Allow,
+                    sources: vec!["10.0.0.2/32".into(), "10.0.0.3/32".into()],
+                    destinations: vec!["10.0.0.1/32".into()],
+                },
+                DaemonAclRule {
+                    action: DaemonAclAction::Deny,
+                    sources: vec!["10.0.0.2/32".into(), "10.0.0.3/32".into()],
+                    destinations: Vec::new(),
+                },
+            ]
+        );
+    }
+}
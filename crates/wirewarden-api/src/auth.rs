@@ -18,22 +18,32 @@ use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::db::user::User;
 use crate::error::ApiError;
+use crate::i18n::Locale;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid,
     pub exp: i64,
     pub iat: i64,
+    #[serde(default)]
+    pub locale: Locale,
+    /// The user had not confirmed their email address when the token was
+    /// issued. Tokens from before verification existed lack it.
+    #[serde(default)]
+    pub unverified: bool,
 }
 
-#[tracing::instrument(skip(secret))]
-pub fn create_token(user_id: Uuid, secret: &str) -> Result<String, ApiError> {
+#[tracing::instrument(skip_all, fields(user_id = %user.id))]
+pub fn create_token(user: &User, secret: &str) -> Result<String, ApiError> {
     let now = chrono::Utc::now().timestamp();
     let claims = Claims {
-        sub: user_id,
+        sub: user.id,
         exp: now + 86_400, // 24h
         iat: now,
+        locale: user.locale,
+        unverified: user.email_verified_at.is_none(),
     };
 
     jsonwebtoken::encode(
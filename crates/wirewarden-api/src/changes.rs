@@ -0,0 +1,174 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Deferred mutations that can be stored, approved, and applied later.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::vpn::{VpnStore, VpnStoreError};
+use crate::events::{EventBus, EventKind};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    DeleteNetwork { network_id: Uuid },
+    DeleteServer { network_id: Uuid, server_id: Uuid },
+    /// Replace the preshared key of every server-client pair. Client configs
+    /// embed their keys, so every client is cut off until it downloads its
+    /// config again.
+    RotateNetworkPsks { network_id: Uuid },
+    SetNetworkEnabled { network_id: Uuid, enabled: bool },
+}
+
+impl Change {
+    /// Audit log action name recorded when the change is applied.
+    pub fn action(&self) -> &'static str {
+        match self {
+            Self::DeleteNetwork { .. } => "network.delete",
+            Self::DeleteServer { .. } => "server.delete",
+            Self::RotateNetworkPsks { .. } => "network.rotate_psks",
+            Self::SetNetworkEnabled { enabled: true, .. } => "network.enable",
+            Self::SetNetworkEnabled { enabled: false, .. } => "network.disable",
+        }
+    }
+
+    /// What applying the change breaks beyond its target, for approvers.
+    pub fn impact(&self) -> Option<&'static str> {
+        match self {
+            Self::RotateNetworkPsks { .. } => Some(
+                "client configs embed their preshared keys; every client in the network \
+                 loses its connection until its config is downloaded again",
+            ),
+            Self::DeleteNetwork { .. }
+            | Self::DeleteServer { .. }
+            | Self::SetNetworkEnabled { .. } => None,
+        }
+    }
+
+    /// Whether the two-person rule applies to this change.
+    pub fn is_destructive(&self) -> bool {
+        !matches!(self, Self::SetNetworkEnabled { .. })
+    }
+
+    pub fn network_id(&self) -> Uuid {
+        match self {
+            Self::DeleteNetwork { network_id }
+            | Self::DeleteServer { network_id, .. }
+            | Self::RotateNetworkPsks { network_id }
+            | Self::SetNetworkEnabled { network_id, .. } => *network_id,
+        }
+    }
+
+    pub fn target_id(&self) -> Uuid {
+        match self {
+            Self::DeleteNetwork { network_id }
+            | Self::RotateNetworkPsks { network_id }
+            | Self::SetNetworkEnabled { network_id, .. } => *network_id,
+            Self::DeleteServer { server_id, .. } => *server_id,
+        }
+    }
+
+    fn event(&self) -> EventKind {
+        match self {
+            Self::DeleteNetwork { .. } => EventKind::NetworkDeleted,
+            Self::DeleteServer { .. } => EventKind::ServerDeleted,
+            Self::RotateNetworkPsks { .. } | Self::SetNetworkEnabled { .. } => {
+                EventKind::NetworkUpdated
+            }
+        }
+    }
+
+    /// Fail unless the network, and for a server deletion the server in it,
+    /// exists, so nothing is queued or applied against a missing target.
+    pub async fn check_target(&self, store: &VpnStore) -> Result<(), VpnStoreError> {
+        store
+            .get_network(self.network_id())
+            .await?
+            .ok_or(VpnStoreError::NetworkNotFound)?;
+        if let Self::DeleteServer {
+            network_id,
+            server_id,
+        } = self
+        {
+            let server = store
+                .get_server(*server_id)
+                .await?
+                .ok_or(VpnStoreError::ServerNotFound)?;
+            if server.network_id != *network_id {
+                return Err(VpnStoreError::ServerNotFound);
+            }
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(store, events))]
+    pub async fn apply(&self, store: &VpnStore, events: &EventBus) -> Result<(), VpnStoreError> {
+        self.check_target(store).await?;
+        self.apply_inner(store).await?;
+        events.publish(self.event(), self.network_id(), self.target_id());
+        Ok(())
+    }
+
+    async fn apply_inner(&self, store: &VpnStore) -> Result<(), VpnStoreError> {
+        match self {
+            Self::DeleteNetwork { network_id } => store.delete_network(*network_id).await,
+            Self::DeleteServer { server_id, .. } => {
+                let server = store
+                    .get_server(*server_id)
+                    .await?
+                    .ok_or(VpnStoreError::ServerNotFound)?;
+                store.delete_server(server.id).await?;
+                store.delete_key(server.key_id).await
+            }
+            Self::RotateNetworkPsks { network_id } => {
+                let (servers, clients) = futures::future::try_join(
+                    store.list_servers_by_network(*network_id),
+                    store.list_clients_by_network(*network_id),
+                )
+                .await?;
+                let server_ids: Vec<_> = servers.iter().map(|s| s.id).collect();
+                let client_ids: Vec<_> = clients.iter().map(|c| c.id).collect();
+                store.rotate_psks(&client_ids, &server_ids).await
+            }
+            Self::SetNetworkEnabled {
+                network_id,
+                enabled,
+            } => store
+                .set_network_enabled(*network_id, *enabled)
+                .await?
+                .map(|_| ())
+                .ok_or(VpnStoreError::NetworkNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(r#"{"kind":"delete_network","network_id":"00000000-0000-0000-0000-000000000001"}"#, "network.delete" ; "delete network")]
+    #[test_case(r#"{"kind":"delete_server","network_id":"00000000-0000-0000-0000-000000000001","server_id":"00000000-0000-0000-0000-000000000002"}"#, "server.delete" ; "delete server")]
+    #[test_case(r#"{"kind":"rotate_network_psks","network_id":"00000000-0000-0000-0000-000000000001"}"#, "network.rotate_psks" ; "rotate psks")]
+    #[test_case(r#"{"kind":"set_network_enabled","network_id":"00000000-0000-0000-0000-000000000001","enabled":false}"#, "network.disable" ; "disable network")]
+    fn parse_change(json: &str, action: &str) {
+        let change: Change = serde_json::from_str(json).unwrap();
+        assert_eq!(change.action(), action);
+        assert_eq!(change.network_id(), Uuid::from_u128(1));
+        let round_trip: Change =
+            serde_json::from_str(&serde_json::to_string(&change).unwrap()).unwrap();
+        assert_eq!(round_trip, change);
+    }
+}
@@ -13,19 +13,109 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use std::env;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
 use thiserror::Error;
 use url::Url;
+use wirewarden_types::redact::{Redacted, redact_opt};
+use wirewarden_types::version::Version;
 
-#[derive(Debug)]
 pub struct Config {
     pub database_url: String,
     pub bind_addr: String,
+    /// Listener for the daemon gRPC endpoint; disabled when unset.
+    pub grpc_bind_addr: Option<SocketAddr>,
+    /// Certificate the gRPC endpoint serves; plaintext when unset, which is
+    /// only allowed on a loopback address.
+    pub grpc_tls: Option<GrpcTls>,
     pub jwt_secret: String,
     pub webauthn_rp_id: String,
     pub webauthn_rp_origin: String,
     pub wg_key_secret: [u8; 32],
     pub public_url: String,
+    pub require_approval: bool,
+    /// Refuse changes from users who have not confirmed their email.
+    pub require_email_verification: bool,
+    pub approval_cooldown_secs: i64,
+    pub server_offline_secs: i64,
+    pub key_cache_capacity: usize,
+    /// How long a daemon token lookup is cached per process, and so how long
+    /// other replicas may accept a revoked token; 0 (the default) disables
+    /// the cache.
+    pub server_token_cache_secs: u64,
+    /// Daemons reporting an older release are flagged as outdated.
+    pub min_daemon_version: Option<Version>,
+    pub smtp: Option<SmtpConfig>,
+    pub mail_from: String,
+    /// Log the bodies of unsent mail at debug level when SMTP is not
+    /// configured. They carry reset and verification links, so this is for
+    /// development only.
+    pub mail_log_bodies: bool,
+    /// Accept hooks on networks, which run on every client of the network.
+    /// Per-client hooks are always accepted.
+    pub allow_network_hooks: bool,
+    /// Token for the public status page; the page is disabled when unset.
+    pub status_page_token: Option<String>,
+    /// Days of daily client usage history kept.
+    pub usage_retention_days: i32,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("database_url", &self.database_url)
+            .field("bind_addr", &self.bind_addr)
+            .field("grpc_bind_addr", &self.grpc_bind_addr)
+            .field("grpc_tls", &self.grpc_tls)
+            .field("jwt_secret", &Redacted)
+            .field("webauthn_rp_id", &self.webauthn_rp_id)
+            .field("webauthn_rp_origin", &self.webauthn_rp_origin)
+            .field("wg_key_secret", &Redacted)
+            .field("public_url", &self.public_url)
+            .field("require_approval", &self.require_approval)
+            .field("require_email_verification", &self.require_email_verification)
+            .field("approval_cooldown_secs", &self.approval_cooldown_secs)
+            .field("server_offline_secs", &self.server_offline_secs)
+            .field("key_cache_capacity", &self.key_cache_capacity)
+            .field("server_token_cache_secs", &self.server_token_cache_secs)
+            .field("min_daemon_version", &self.min_daemon_version)
+            .field("smtp", &self.smtp)
+            .field("mail_from", &self.mail_from)
+            .field("mail_log_bodies", &self.mail_log_bodies)
+            .field("allow_network_hooks", &self.allow_network_hooks)
+            .field("status_page_token", &redact_opt(&self.status_page_token))
+            .field("usage_retention_days", &self.usage_retention_days)
+            .finish()
+    }
+}
+
+/// PEM files for the gRPC endpoint's TLS identity.
+#[derive(Debug, Clone)]
+pub struct GrpcTls {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+/// Outbound mail relay. Without it, mail is not sent and only its recipient
+/// and subject are logged.
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl fmt::Debug for SmtpConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmtpConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &redact_opt(&self.password))
+            .finish()
+    }
 }
 
 #[derive(Debug, Error)]
@@ -38,12 +128,129 @@ pub enum ConfigError {
 
     #[error("PUBLIC_URL is not a valid URL")]
     InvalidPublicUrl,
+
+    #[error("invalid value for environment variable: {var}")]
+    InvalidValue { var: &'static str },
+
+    #[error("unknown argument: {0}")]
+    UnknownArgument(String),
+
+    #[error("GRPC_TLS_CERT and GRPC_TLS_KEY must be set together")]
+    IncompleteGrpcTls,
+
+    #[error(
+        "GRPC_BIND_ADDR {0} is not a loopback address; set GRPC_TLS_CERT and GRPC_TLS_KEY \
+         or bind to loopback behind a TLS proxy"
+    )]
+    PlaintextGrpc(SocketAddr),
+}
+
+/// What startup does about pending migrations, chosen on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrateMode {
+    /// Apply pending migrations, then serve. The default.
+    Run,
+    /// `--migrate-only`: apply pending migrations and exit.
+    Only,
+    /// `--no-migrate`: serve against the schema as it is.
+    Skip,
+    /// `--migrate-dry-run`: list pending migrations and exit.
+    DryRun,
+}
+
+impl MigrateMode {
+    pub fn from_args<I>(args: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut mode = Self::Run;
+        for arg in args {
+            mode = match arg.as_str() {
+                "--migrate-only" => Self::Only,
+                "--no-migrate" => Self::Skip,
+                "--migrate-dry-run" => Self::DryRun,
+                _ => return Err(ConfigError::UnknownArgument(arg)),
+            };
+        }
+        Ok(mode)
+    }
+}
+
+/// The database URL alone, for modes that only touch the schema.
+pub fn database_url() -> Result<String, ConfigError> {
+    require_env("DATABASE_URL")
 }
 
 fn require_env(var: &'static str) -> Result<String, ConfigError> {
     env::var(var).map_err(|_| ConfigError::MissingEnvVar { var })
 }
 
+fn env_flag(var: &'static str) -> Result<bool, ConfigError> {
+    match env::var(var).as_deref() {
+        Err(_) | Ok("") | Ok("0") | Ok("false") | Ok("no") => Ok(false),
+        Ok("1") | Ok("true") | Ok("yes") => Ok(true),
+        Ok(_) => Err(ConfigError::InvalidValue { var }),
+    }
+}
+
+fn env_or<T>(var: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: std::str::FromStr,
+{
+    match env::var(var) {
+        Ok(v) => v.trim().parse().map_err(|_| ConfigError::InvalidValue { var }),
+        Err(_) => Ok(default),
+    }
+}
+
+fn env_opt<T>(var: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: std::str::FromStr,
+{
+    match env::var(var) {
+        Ok(v) if !v.trim().is_empty() => v
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| ConfigError::InvalidValue { var }),
+        _ => Ok(None),
+    }
+}
+
+fn smtp_from_env() -> Result<Option<SmtpConfig>, ConfigError> {
+    let Ok(host) = env::var("SMTP_HOST") else {
+        return Ok(None);
+    };
+    Ok(Some(SmtpConfig {
+        host,
+        port: env_or("SMTP_PORT", 587)?,
+        username: env::var("SMTP_USERNAME").ok(),
+        password: env::var("SMTP_PASSWORD").ok(),
+    }))
+}
+
+fn grpc_tls_from_env() -> Result<Option<GrpcTls>, ConfigError> {
+    match (env_opt("GRPC_TLS_CERT")?, env_opt("GRPC_TLS_KEY")?) {
+        (Some(cert_path), Some(key_path)) => Ok(Some(GrpcTls {
+            cert_path,
+            key_path,
+        })),
+        (None, None) => Ok(None),
+        _ => Err(ConfigError::IncompleteGrpcTls),
+    }
+}
+
+/// Daemon tokens cross the gRPC listener, so it speaks plaintext only where
+/// nothing else can listen in.
+fn check_grpc(addr: Option<SocketAddr>, tls: Option<&GrpcTls>) -> Result<(), ConfigError> {
+    match addr {
+        Some(addr) if tls.is_none() && !addr.ip().is_loopback() => {
+            Err(ConfigError::PlaintextGrpc(addr))
+        }
+        _ => Ok(()),
+    }
+}
+
 fn parse_hex_32(hex: &str) -> Result<[u8; 32], ConfigError> {
     let hex = hex.trim();
     if hex.len() != 64 {
@@ -66,14 +273,63 @@ impl Config {
         let public_url_parsed =
             Url::parse(&public_url).map_err(|_| ConfigError::InvalidPublicUrl)?;
 
+        let grpc_bind_addr = env_opt("GRPC_BIND_ADDR")?;
+        let grpc_tls = grpc_tls_from_env()?;
+        check_grpc(grpc_bind_addr, grpc_tls.as_ref())?;
+
         Ok(Self {
             database_url: require_env("DATABASE_URL")?,
             bind_addr: env::var("BIND_ADDR").unwrap_or_else(|_| "127.0.0.1:8080".to_string()),
+            grpc_bind_addr,
+            grpc_tls,
             jwt_secret: require_env("JWT_SECRET")?,
             wg_key_secret,
             public_url: public_url.clone(),
             webauthn_rp_id: public_url_parsed.host_str().unwrap().to_string(),
             webauthn_rp_origin: public_url.trim_end_matches('/').to_string(),
+            require_approval: env_flag("REQUIRE_APPROVAL")?,
+            require_email_verification: env_flag("REQUIRE_EMAIL_VERIFICATION")?,
+            approval_cooldown_secs: env_or("APPROVAL_COOLDOWN_SECS", 86_400)?,
+            server_offline_secs: env_or("SERVER_OFFLINE_SECS", 300)?,
+            key_cache_capacity: env_or("KEY_CACHE_CAPACITY", 4096)?,
+            server_token_cache_secs: env_or("SERVER_TOKEN_CACHE_SECS", 0)?,
+            min_daemon_version: env_opt("MIN_DAEMON_VERSION")?,
+            smtp: smtp_from_env()?,
+            mail_from: env::var("MAIL_FROM").unwrap_or_else(|_| "wirewarden@localhost".to_string()),
+            mail_log_bodies: env_flag("MAIL_LOG_BODIES")?,
+            allow_network_hooks: env_flag("ALLOW_NETWORK_HOOKS")?,
+            status_page_token: env_opt("STATUS_PAGE_TOKEN")?,
+            usage_retention_days: env_or("USAGE_RETENTION_DAYS", 366)?,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(&[], Some(MigrateMode::Run) ; "default")]
+    #[test_case(&["--migrate-only"], Some(MigrateMode::Only) ; "only")]
+    #[test_case(&["--no-migrate"], Some(MigrateMode::Skip) ; "skip")]
+    #[test_case(&["--migrate-dry-run"], Some(MigrateMode::DryRun) ; "dry run")]
+    #[test_case(&["--migrate"], None ; "unknown")]
+    fn test_migrate_mode(args: &[&str], expected: Option<MigrateMode>) {
+        let mode = MigrateMode::from_args(args.iter().map(|a| a.to_string()));
+        assert_eq!(mode.ok(), expected);
+    }
+
+    #[test_case(None, false, true ; "disabled")]
+    #[test_case(Some("127.0.0.1:50051"), false, true ; "loopback plaintext")]
+    #[test_case(Some("[::1]:50051"), false, true ; "v6 loopback plaintext")]
+    #[test_case(Some("0.0.0.0:50051"), false, false ; "public plaintext")]
+    #[test_case(Some("0.0.0.0:50051"), true, true ; "public tls")]
+    fn test_check_grpc(addr: Option<&str>, tls: bool, ok: bool) {
+        let addr = addr.map(|a| a.parse().unwrap());
+        let tls = tls.then(|| GrpcTls {
+            cert_path: "cert.pem".into(),
+            key_path: "key.pem".into(),
+        });
+        assert_eq!(check_grpc(addr, tls.as_ref()).is_ok(), ok);
+    }
+}
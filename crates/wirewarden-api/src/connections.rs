@@ -0,0 +1,67 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Whether peers are connected, from the handshakes daemons report.
+//! WireGuard re-handshakes every two minutes while a session carries
+//! traffic or keepalives, so a peer whose last handshake is older than
+//! [`HANDSHAKE_TIMEOUT`] has dropped off.
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::db::connection::ConnectionEvent;
+use crate::events::{EventBus, EventKind};
+
+pub const HANDSHAKE_TIMEOUT: TimeDelta = TimeDelta::minutes(3);
+
+/// Whether a peer whose latest handshake was `last_handshake` counts as
+/// connected at `now`.
+pub fn is_connected(last_handshake: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    last_handshake.is_some_and(|at| now - at <= HANDSHAKE_TIMEOUT)
+}
+
+/// Publish each change on the event bus.
+pub fn announce(changes: &[ConnectionEvent], events: &EventBus) {
+    for change in changes {
+        let kind = if change.connected {
+            EventKind::PeerConnected
+        } else {
+            EventKind::PeerDisconnected
+        };
+        tracing::info!(
+            server_id = %change.server_id,
+            peer_id = %change.peer_id,
+            peer = %change.peer_name,
+            event = kind.as_str(),
+            "peer connection changed"
+        );
+        events.publish(kind, change.network_id, change.peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(None, false ; "never")]
+    #[test_case(Some(30), true ; "recent")]
+    #[test_case(Some(180), true ; "at the limit")]
+    #[test_case(Some(181), false ; "stale")]
+    #[test_case(Some(-5), true ; "daemon clock ahead")]
+    fn test_is_connected(age_secs: Option<i64>, expected: bool) {
+        let now = Utc::now();
+        let last = age_secs.map(|s| now - TimeDelta::seconds(s));
+        assert_eq!(is_connected(last, now), expected);
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Minimal CSV writer for list exports.
+//!
+//! Fields are quoted per RFC 4180 when needed. Values starting with a
+//! character spreadsheets treat as a formula are prefixed with `'`, since
+//! names, tags, and notes are user-controlled.
+
+use actix_web::HttpResponse;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use serde::Deserialize;
+
+/// `?format=` on list endpoints that can export.
+#[derive(Debug, Deserialize)]
+pub struct FormatQuery {
+    #[serde(default)]
+    pub format: Format,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Default)]
+pub struct CsvWriter {
+    out: String,
+}
+
+impl CsvWriter {
+    pub fn row<I, S>(&mut self, fields: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            write_field(&mut self.out, field.as_ref());
+        }
+        self.out.push_str("\r\n");
+    }
+
+    /// Respond with the document as a download named `filename`.
+    pub fn respond(self, filename: &str) -> HttpResponse {
+        HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(filename.to_string())],
+            })
+            .body(self.out)
+    }
+}
+
+fn write_field(out: &mut String, field: &str) {
+    let formula = field.starts_with(['=', '+', '-', '@', '\t', '\r']);
+    let quote = field.contains([',', '"', '\n', '\r']);
+    if quote {
+        out.push('"');
+    }
+    if formula {
+        out.push('\'');
+    }
+    for c in field.chars() {
+        if c == '"' {
+            out.push('"');
+        }
+        out.push(c);
+    }
+    if quote {
+        out.push('"');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("relay", "relay" ; "plain")]
+    #[test_case("a,b", "\"a,b\"" ; "comma")]
+    #[test_case("say \"hi\"", "\"say \"\"hi\"\"\"" ; "quotes")]
+    #[test_case("line\nbreak", "\"line\nbreak\"" ; "newline")]
+    #[test_case("=HYPERLINK(1)", "'=HYPERLINK(1)" ; "formula")]
+    #[test_case("=1,2", "\"'=1,2\"" ; "formula with comma")]
+    #[test_case("", "" ; "empty")]
+    fn test_write_field(input: &str, expected: &str) {
+        let mut out = String::new();
+        write_field(&mut out, input);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut csv = CsvWriter::default();
+        csv.row(["id", "name"]);
+        csv.row(["1", "a,b"]);
+        assert_eq!(csv.out, "id,name\r\n1,\"a,b\"\r\n");
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Rendered daemon configs, keyed by server, its network's config serial,
+//! and the capabilities of the daemon they were rendered for.
+//!
+//! Daemons poll every 30 seconds and almost always find nothing changed. A
+//! hit skips the snapshot load, key decryption, and per-client PSK lookups;
+//! the serial is bumped by database triggers on any change that could alter
+//! a config, so entries never need explicit invalidation.
+//!
+//! Each body carries an ETag derived from its content, so a daemon holding
+//! the current config gets a 304 instead of the body.
+//!
+//! The last few configs of each server are also kept whole, so a daemon can
+//! be sent only the peers changed since the serial it holds. A daemon further
+//! behind, or that last polled another replica, is sent every peer.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use actix_web::http::header::EntityTag;
+use actix_web::web::Bytes;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use wirewarden_types::daemon::{Capabilities, DaemonConfig};
+
+/// Configs kept per server for deltas. Daemons poll far more often than
+/// configs change, so they are rarely more than one serial behind.
+const HISTORY: usize = 4;
+
+/// A config serial, the capabilities it was rendered for, and the config.
+type Remembered = (i64, Capabilities, Arc<DaemonConfig>);
+
+/// A serialized config and its ETag.
+#[derive(Debug, Clone)]
+pub struct RenderedConfig {
+    pub body: Bytes,
+    pub etag: EntityTag,
+}
+
+impl RenderedConfig {
+    pub fn new(body: Bytes) -> Self {
+        let digest = Sha256::digest(&body);
+        let etag = EntityTag::new_strong(format!("{digest:x}"));
+        Self { body, etag }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DaemonConfigCache {
+    /// Server ID to (config serial, capabilities, rendered config).
+    entries: Mutex<HashMap<Uuid, (i64, Capabilities, RenderedConfig)>>,
+    /// Server ID to its latest configs, oldest first.
+    history: Mutex<HashMap<Uuid, VecDeque<Remembered>>>,
+}
+
+impl DaemonConfigCache {
+    /// The cached config for `server_id`, if it was rendered at `serial` for
+    /// a daemon with `caps`.
+    pub fn get(&self, server_id: Uuid, serial: i64, caps: Capabilities) -> Option<RenderedConfig> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries
+            .get(&server_id)
+            .filter(|(cached, cached_caps, _)| *cached == serial && *cached_caps == caps)
+            .map(|(_, _, rendered)| rendered.clone())
+    }
+
+    pub fn insert(
+        &self,
+        server_id: Uuid,
+        serial: i64,
+        caps: Capabilities,
+        rendered: RenderedConfig,
+    ) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(server_id, (serial, caps, rendered));
+    }
+
+    /// The config rendered for `server_id` at `serial` for a daemon with
+    /// `caps`, if it is among the last few.
+    pub fn config_at(
+        &self,
+        server_id: Uuid,
+        serial: i64,
+        caps: Capabilities,
+    ) -> Option<Arc<DaemonConfig>> {
+        let history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        history
+            .get(&server_id)?
+            .iter()
+            .find(|(cached, cached_caps, _)| *cached == serial && *cached_caps == caps)
+            .map(|(_, _, config)| config.clone())
+    }
+
+    pub fn remember(
+        &self,
+        server_id: Uuid,
+        serial: i64,
+        caps: Capabilities,
+        config: Arc<DaemonConfig>,
+    ) {
+        let mut history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        let configs = history.entry(server_id).or_default();
+        configs.retain(|(cached, cached_caps, _)| (*cached, *cached_caps) != (serial, caps));
+        if configs.len() == HISTORY {
+            configs.pop_front();
+        }
+        configs.push_back((serial, caps, config));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_serial_misses() {
+        let cache = DaemonConfigCache::default();
+        let id = Uuid::from_u128(1);
+        let caps = Capabilities::CURRENT;
+        cache.insert(id, 3, caps, RenderedConfig::new(Bytes::from_static(b"{}")));
+
+        assert_eq!(cache.get(id, 3, caps).unwrap().body, &b"{}"[..]);
+        assert!(cache.get(id, 4, caps).is_none());
+        assert!(cache.get(Uuid::from_u128(2), 3, caps).is_none());
+
+        let legacy = Capabilities::from_header(Some(""));
+        assert!(cache.get(id, 3, legacy).is_none());
+    }
+
+    #[test]
+    fn test_history_keeps_the_latest() {
+        use wirewarden_types::daemon::{CONFIG_VERSION, DaemonNetworkInfo, DaemonServerInfo};
+
+        let cache = DaemonConfigCache::default();
+        let id = Uuid::from_u128(1);
+        let caps = Capabilities::CURRENT;
+        let config = Arc::new(DaemonConfig {
+            version: CONFIG_VERSION,
+            server: DaemonServerInfo {
+                id,
+                name: "relay".into(),
+                private_key: "priv".into(),
+                public_key: "pub".into(),
+                address: "10.0.0.1/24".into(),
+                listen_port: 51820,
+                manage_nat: false,
+                fwmark: None,
+                route_table: None,
+                rule_priority: None,
+                mtu: None,
+            },
+            network: DaemonNetworkInfo {
+                id: Uuid::nil(),
+                name: "home".into(),
+                cidr: "10.0.0.0/24".into(),
+                persistent_keepalive: 25,
+                dns_servers: Vec::new(),
+                dns_zone: None,
+                acl: Vec::new(),
+            },
+            peers: Vec::new(),
+        });
+        for serial in 1..=HISTORY as i64 + 1 {
+            cache.remember(id, serial, caps, config.clone());
+        }
+
+        assert!(cache.config_at(id, 1, caps).is_none());
+        assert!(cache.config_at(id, 2, caps).is_some());
+        assert!(cache.config_at(id, HISTORY as i64 + 1, caps).is_some());
+        assert!(cache.config_at(id, 2, Capabilities::from_header(Some(""))).is_none());
+    }
+
+    #[test]
+    fn test_etag_follows_content() {
+        let a = RenderedConfig::new(Bytes::from_static(b"{\"peers\":[]}"));
+        let b = RenderedConfig::new(Bytes::from_static(b"{\"peers\":[]}"));
+        let c = RenderedConfig::new(Bytes::from_static(b"{\"peers\":[1]}"));
+
+        assert!(a.etag.strong_eq(&b.etag));
+        assert!(!a.etag.strong_eq(&c.etag));
+        assert_eq!(a.etag.tag().len(), 64);
+    }
+}
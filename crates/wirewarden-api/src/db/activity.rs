@@ -0,0 +1,127 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::audit::AuditEntry;
+use crate::db::connection::{ConnectionEvent, PeerKind};
+
+/// One entry in the activity feed.
+#[derive(Debug)]
+pub enum Activity {
+    Audit(AuditEntry),
+    Connection(ConnectionEvent),
+}
+
+/// A row of either source, with the other's columns null.
+#[derive(Debug, sqlx::FromRow)]
+struct ActivityRow {
+    source: String,
+    id: Uuid,
+    network_id: Option<Uuid>,
+    created_at: DateTime<Utc>,
+    actor_id: Option<Uuid>,
+    action: Option<String>,
+    target_id: Option<Uuid>,
+    details: Option<serde_json::Value>,
+    server_id: Option<Uuid>,
+    peer_id: Option<Uuid>,
+    peer_kind: Option<PeerKind>,
+    peer_name: Option<String>,
+    connected: Option<bool>,
+    last_handshake: Option<DateTime<Utc>>,
+}
+
+impl ActivityRow {
+    fn into_activity(self) -> Result<Activity, sqlx::Error> {
+        let missing = |column: &str| sqlx::Error::ColumnNotFound(column.into());
+        Ok(match self.source.as_str() {
+            "audit" => Activity::Audit(AuditEntry {
+                id: self.id,
+                actor_id: self.actor_id,
+                action: self.action.ok_or_else(|| missing("action"))?,
+                network_id: self.network_id,
+                target_id: self.target_id,
+                details: self.details.unwrap_or_default(),
+                created_at: self.created_at,
+            }),
+            _ => Activity::Connection(ConnectionEvent {
+                id: self.id,
+                network_id: self.network_id.ok_or_else(|| missing("network_id"))?,
+                server_id: self.server_id.ok_or_else(|| missing("server_id"))?,
+                peer_id: self.peer_id.ok_or_else(|| missing("peer_id"))?,
+                peer_kind: self.peer_kind.ok_or_else(|| missing("peer_kind"))?,
+                peer_name: self.peer_name.ok_or_else(|| missing("peer_name"))?,
+                connected: self.connected.ok_or_else(|| missing("connected"))?,
+                last_handshake: self.last_handshake,
+                created_at: self.created_at,
+            }),
+        })
+    }
+}
+
+const FEED: &str = "SELECT 'audit' AS source, id, network_id, created_at,
+                           actor_id, action, target_id, details,
+                           NULL::uuid AS server_id, NULL::uuid AS peer_id,
+                           NULL::text AS peer_kind, NULL::text AS peer_name,
+                           NULL::bool AS connected, NULL::timestamptz AS last_handshake
+                    FROM audit_log
+                    WHERE $1::uuid IS NULL OR network_id = $1
+                    UNION ALL
+                    SELECT 'connection', id, network_id, created_at,
+                           NULL, NULL, NULL, NULL,
+                           server_id, peer_id, peer_kind, peer_name, connected, last_handshake
+                    FROM connection_events
+                    WHERE $1::uuid IS NULL OR network_id = $1";
+
+/// The audit log and connection events merged into one timeline.
+#[derive(Debug, Clone)]
+pub struct ActivityStore {
+    pool: PgPool,
+}
+
+impl ActivityStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// One page of the feed, newest first, optionally for one network,
+    /// with the total across pages.
+    #[tracing::instrument(skip(self))]
+    pub async fn page(
+        &self,
+        network_id: Option<Uuid>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<Activity>, i64), sqlx::Error> {
+        let select = format!("{FEED} ORDER BY created_at DESC, id LIMIT $2 OFFSET $3");
+        let count = format!("SELECT count(*) FROM ({FEED}) feed");
+        let rows = sqlx::query_as::<_, ActivityRow>(&select)
+            .bind(network_id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool);
+        let total = sqlx::query_scalar::<_, i64>(&count)
+            .bind(network_id)
+            .fetch_one(&self.pool);
+        let (rows, total) = futures::future::try_join(rows, total).await?;
+        let items = rows
+            .into_iter()
+            .map(ActivityRow::into_activity)
+            .collect::<Result<_, _>>()?;
+        Ok((items, total))
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use sqlx::types::Json;
+use uuid::Uuid;
+
+use crate::changes::Change;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct PendingChange {
+    pub id: Uuid,
+    pub change: Json<Change>,
+    pub requested_by: Uuid,
+    pub status: ChangeStatus,
+    pub decided_by: Option<Uuid>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Destructive changes held back until a second admin signs off.
+#[derive(Debug, Clone)]
+pub struct ApprovalStore {
+    pool: PgPool,
+}
+
+impl ApprovalStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn create(
+        &self,
+        change: &Change,
+        requested_by: Uuid,
+    ) -> Result<PendingChange, sqlx::Error> {
+        sqlx::query_as::<_, PendingChange>(
+            "INSERT INTO pending_changes (change, requested_by)
+             VALUES ($1, $2)
+             RETURNING *",
+        )
+        .bind(Json(change))
+        .bind(requested_by)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn get(&self, id: Uuid) -> Result<Option<PendingChange>, sqlx::Error> {
+        sqlx::query_as::<_, PendingChange>("SELECT * FROM pending_changes WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list(
+        &self,
+        status: Option<ChangeStatus>,
+    ) -> Result<Vec<PendingChange>, sqlx::Error> {
+        sqlx::query_as::<_, PendingChange>(
+            "SELECT * FROM pending_changes
+             WHERE $1::text IS NULL OR status = $1
+             ORDER BY created_at DESC",
+        )
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Atomically move a pending change to `status`, returning `None` if it
+    /// was already decided by someone else.
+    #[tracing::instrument(skip(self))]
+    pub async fn decide(
+        &self,
+        id: Uuid,
+        decided_by: Uuid,
+        status: ChangeStatus,
+    ) -> Result<Option<PendingChange>, sqlx::Error> {
+        sqlx::query_as::<_, PendingChange>(
+            "UPDATE pending_changes SET status = $3, decided_by = $2, decided_at = now()
+             WHERE id = $1 AND status = 'pending'
+             RETURNING *",
+        )
+        .bind(id)
+        .bind(decided_by)
+        .bind(status)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Return an approved change to the queue after it failed to apply.
+    #[tracing::instrument(skip(self))]
+    pub async fn reopen(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE pending_changes SET status = 'pending', decided_by = NULL, decided_at = NULL
+             WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
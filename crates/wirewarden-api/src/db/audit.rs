@@ -0,0 +1,137 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub network_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The fields an update changed, recorded as `{field: {"from", "to"}}` so
+/// one audit entry covers the whole update.
+#[derive(Debug, Default)]
+pub struct FieldChanges(serde_json::Map<String, serde_json::Value>);
+
+impl FieldChanges {
+    /// Note `name` if it went from `from` to `to`.
+    pub fn field<T: Serialize + PartialEq>(&mut self, name: &str, from: &T, to: &T) -> &mut Self {
+        if from != to {
+            self.0.insert(
+                name.to_string(),
+                serde_json::json!({ "from": from, "to": to }),
+            );
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn into_details(self) -> serde_json::Value {
+        serde_json::Value::Object(self.0)
+    }
+}
+
+/// Append-only log of administrative actions.
+#[derive(Debug, Clone)]
+pub struct AuditStore {
+    pool: PgPool,
+}
+
+impl AuditStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(skip(self, details))]
+    pub async fn record(
+        &self,
+        actor_id: Option<Uuid>,
+        action: &str,
+        network_id: Option<Uuid>,
+        target_id: Option<Uuid>,
+        details: serde_json::Value,
+    ) -> Result<AuditEntry, sqlx::Error> {
+        sqlx::query_as::<_, AuditEntry>(
+            "INSERT INTO audit_log (actor_id, action, network_id, target_id, details)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING *",
+        )
+        .bind(actor_id)
+        .bind(action)
+        .bind(network_id)
+        .bind(target_id)
+        .bind(&details)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Most recent entries first, optionally restricted to one network.
+    #[tracing::instrument(skip(self))]
+    pub async fn list(
+        &self,
+        network_id: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        sqlx::query_as::<_, AuditEntry>(
+            "SELECT * FROM audit_log
+             WHERE $1::uuid IS NULL OR network_id = $1
+             ORDER BY created_at DESC
+             LIMIT $2",
+        )
+        .bind(network_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_changes_keeps_only_changed_fields() {
+        let mut changes = FieldChanges::default();
+        changes
+            .field("mtu", &Some(1420), &None::<i32>)
+            .field("priority", &0, &0)
+            .field("notes", &None::<String>, &Some("rack 2".to_string()));
+        assert_eq!(
+            changes.into_details(),
+            serde_json::json!({
+                "mtu": { "from": 1420, "to": null },
+                "notes": { "from": null, "to": "rack 2" },
+            })
+        );
+    }
+
+    #[test]
+    fn test_field_changes_empty() {
+        let mut changes = FieldChanges::default();
+        changes.field("priority", &3, &3);
+        assert!(changes.is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+use wirewarden_types::daemon::DaemonPeerTelemetry;
+
+use crate::connections;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum PeerKind {
+    Server,
+    Client,
+}
+
+impl PeerKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Server => "server",
+            Self::Client => "client",
+        }
+    }
+}
+
+/// A peer connecting to or dropping off a server.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ConnectionEvent {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub server_id: Uuid,
+    pub peer_id: Uuid,
+    pub peer_kind: PeerKind,
+    pub peer_name: String,
+    pub connected: bool,
+    pub last_handshake: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A reported peer, resolved to a client or server of the network.
+#[derive(Debug, sqlx::FromRow)]
+struct Observed {
+    peer_id: Uuid,
+    peer_kind: PeerKind,
+    peer_name: String,
+    last_handshake: Option<DateTime<Utc>>,
+    was_connected: Option<bool>,
+}
+
+/// Peer connection state per server, and its history.
+#[derive(Debug, Clone)]
+pub struct ConnectionStore {
+    pool: PgPool,
+}
+
+impl ConnectionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Update each reported peer's state as of `now` and record the ones
+    /// that changed, which are returned. A peer seen for the first time
+    /// counts as having been disconnected. Keys that are not peers of the
+    /// network are skipped.
+    #[tracing::instrument(skip(self, peers), fields(peers = peers.len()))]
+    pub async fn record(
+        &self,
+        server_id: Uuid,
+        network_id: Uuid,
+        peers: &[DaemonPeerTelemetry],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<ConnectionEvent>, sqlx::Error> {
+        let keys: Vec<&str> = peers.iter().map(|p| p.public_key.as_str()).collect();
+        let handshakes: Vec<Option<DateTime<Utc>>> =
+            peers.iter().map(|p| p.last_handshake).collect();
+
+        let mut tx = self.pool.begin().await?;
+        let observed = sqlx::query_as::<_, Observed>(
+            "WITH sample AS (
+                 SELECT * FROM UNNEST($3::text[], $4::timestamptz[])
+                     AS s(public_key, last_handshake)
+             ),
+             peers AS (
+                 SELECT c.id AS peer_id, 'client' AS peer_kind, c.name::text AS peer_name,
+                     s.last_handshake
+                 FROM sample s
+                 JOIN wg_keys k ON k.public_key = s.public_key
+                 JOIN wg_clients c ON c.key_id = k.id AND c.network_id = $2
+                 UNION ALL
+                 SELECT v.id, 'server', v.name::text, s.last_handshake
+                 FROM sample s
+                 JOIN wg_keys k ON k.public_key = s.public_key
+                 JOIN wg_servers v ON v.key_id = k.id AND v.network_id = $2 AND v.id <> $1
+             )
+             SELECT p.*, pc.connected AS was_connected
+             FROM peers p
+             LEFT JOIN peer_connections pc ON pc.server_id = $1 AND pc.peer_id = p.peer_id",
+        )
+        .bind(server_id)
+        .bind(network_id)
+        .bind(&keys)
+        .bind(&handshakes)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let connected: Vec<bool> = observed
+            .iter()
+            .map(|o| connections::is_connected(o.last_handshake, now))
+            .collect();
+        let ids: Vec<Uuid> = observed.iter().map(|o| o.peer_id).collect();
+        let handshakes: Vec<Option<DateTime<Utc>>> =
+            observed.iter().map(|o| o.last_handshake).collect();
+        sqlx::query(
+            "INSERT INTO peer_connections (server_id, peer_id, connected, last_handshake)
+             SELECT $1, * FROM UNNEST($2::uuid[], $3::bool[], $4::timestamptz[])
+             ON CONFLICT (server_id, peer_id) DO UPDATE
+             SET last_handshake = EXCLUDED.last_handshake,
+                 changed_at = CASE WHEN peer_connections.connected = EXCLUDED.connected
+                     THEN peer_connections.changed_at ELSE now() END,
+                 connected = EXCLUDED.connected",
+        )
+        .bind(server_id)
+        .bind(&ids)
+        .bind(&connected)
+        .bind(&handshakes)
+        .execute(&mut *tx)
+        .await?;
+
+        let changed: Vec<(&Observed, bool)> = observed
+            .iter()
+            .zip(connected)
+            .filter(|(o, now)| o.was_connected.unwrap_or(false) != *now)
+            .collect();
+        let events = sqlx::query_as::<_, ConnectionEvent>(
+            "INSERT INTO connection_events
+                 (network_id, server_id, peer_id, peer_kind, peer_name, connected,
+                  last_handshake)
+             SELECT $1, $2, * FROM UNNEST($3::uuid[], $4::text[], $5::text[], $6::bool[],
+                 $7::timestamptz[])
+             RETURNING *",
+        )
+        .bind(network_id)
+        .bind(server_id)
+        .bind(changed.iter().map(|(o, _)| o.peer_id).collect::<Vec<_>>())
+        .bind(
+            changed
+                .iter()
+                .map(|(o, _)| o.peer_kind.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            changed
+                .iter()
+                .map(|(o, _)| o.peer_name.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .bind(changed.iter().map(|(_, c)| *c).collect::<Vec<_>>())
+        .bind(
+            changed
+                .iter()
+                .map(|(o, _)| o.last_handshake)
+                .collect::<Vec<_>>(),
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(events)
+    }
+
+    /// The latest changes, newest first, optionally for one network or
+    /// peer.
+    #[tracing::instrument(skip(self))]
+    pub async fn list(
+        &self,
+        network_id: Option<Uuid>,
+        peer_id: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<ConnectionEvent>, sqlx::Error> {
+        sqlx::query_as::<_, ConnectionEvent>(
+            "SELECT * FROM connection_events
+             WHERE ($1::uuid IS NULL OR network_id = $1)
+               AND ($2::uuid IS NULL OR peer_id = $2)
+             ORDER BY created_at DESC
+             LIMIT $3",
+        )
+        .bind(network_id)
+        .bind(peer_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
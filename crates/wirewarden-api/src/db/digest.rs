@@ -0,0 +1,115 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::i18n::Locale;
+
+/// A due digest, joined with the recipient's address.
+#[derive(Debug, sqlx::FromRow)]
+pub struct DueDigest {
+    pub user_id: Uuid,
+    pub network_id: Uuid,
+    pub email: String,
+    pub locale: Locale,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct DigestSubscription {
+    pub network_id: Uuid,
+    pub next_digest_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Weekly per-network digest opt-ins.
+#[derive(Debug, Clone)]
+pub struct DigestStore {
+    pool: PgPool,
+}
+
+impl DigestStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn subscribe(
+        &self,
+        user_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<DigestSubscription, sqlx::Error> {
+        sqlx::query_as::<_, DigestSubscription>(
+            "INSERT INTO digest_subscriptions (user_id, network_id, next_digest_at)
+             VALUES ($1, $2, now() + interval '7 days')
+             ON CONFLICT (user_id, network_id) DO UPDATE SET user_id = EXCLUDED.user_id
+             RETURNING *",
+        )
+        .bind(user_id)
+        .bind(network_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn get(
+        &self,
+        user_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<Option<DigestSubscription>, sqlx::Error> {
+        sqlx::query_as::<_, DigestSubscription>(
+            "SELECT * FROM digest_subscriptions WHERE user_id = $1 AND network_id = $2",
+        )
+        .bind(user_id)
+        .bind(network_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn unsubscribe(&self, user_id: Uuid, network_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM digest_subscriptions WHERE user_id = $1 AND network_id = $2")
+            .bind(user_id)
+            .bind(network_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Claim due digests by advancing them a week past now, so each is sent
+    /// once and missed weeks are not replayed.
+    #[tracing::instrument(skip(self))]
+    pub async fn claim_due(&self, limit: i64) -> Result<Vec<DueDigest>, sqlx::Error> {
+        sqlx::query_as::<_, DueDigest>(
+            "WITH due AS (
+                 UPDATE digest_subscriptions
+                 SET next_digest_at = GREATEST(next_digest_at, now()) + interval '7 days'
+                 WHERE (user_id, network_id) IN (
+                     SELECT user_id, network_id FROM digest_subscriptions
+                     WHERE next_digest_at <= now()
+                     ORDER BY next_digest_at
+                     LIMIT $1
+                     FOR UPDATE SKIP LOCKED
+                 )
+                 RETURNING user_id, network_id
+             )
+             SELECT due.user_id, due.network_id, u.email, u.locale
+             FROM due JOIN users u ON u.id = due.user_id",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Coordination for background jobs when several API replicas share one
+//! database.
+
+use std::time::Duration;
+
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// Ticks on different replicas drift; a run that started this much short of
+/// a full interval ago still counts for it.
+const SLACK: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct JobStore {
+    pool: PgPool,
+}
+
+/// The right to run a job for the current interval. The job's advisory lock
+/// is held until the claim is finished or dropped; dropping it (say, on a
+/// panic) also forgets the run, so the next tick retries.
+#[derive(Debug)]
+pub struct JobClaim {
+    tx: Transaction<'static, Postgres>,
+}
+
+impl JobStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Claim `job` unless another replica is running it or it already ran
+    /// within the last `every`.
+    #[tracing::instrument(skip(self))]
+    pub async fn try_claim(
+        &self,
+        job: &str,
+        every: Duration,
+    ) -> Result<Option<JobClaim>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let (locked,): (bool,) = sqlx::query_as(
+            "SELECT pg_try_advisory_xact_lock(hashtext('wirewarden.job'), hashtext($1))",
+        )
+        .bind(job)
+        .fetch_one(&mut *tx)
+        .await?;
+        if !locked {
+            return Ok(None);
+        }
+
+        let due = sqlx::query(
+            "INSERT INTO job_runs (name, started_at) VALUES ($1, now())
+             ON CONFLICT (name) DO UPDATE SET started_at = now()
+             WHERE job_runs.started_at <= now() - make_interval(secs => $2)
+             RETURNING name",
+        )
+        .bind(job)
+        .bind(every.saturating_sub(SLACK).as_secs_f64())
+        .fetch_optional(&mut *tx)
+        .await?;
+        Ok(due.map(|_| JobClaim { tx }))
+    }
+}
+
+impl JobClaim {
+    /// Record the run and release the lock.
+    pub async fn finish(self) -> Result<(), sqlx::Error> {
+        self.tx.commit().await
+    }
+}
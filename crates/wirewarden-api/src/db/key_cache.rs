@@ -0,0 +1,192 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Bounded cache of decrypted WireGuard key pairs.
+//!
+//! Every snapshot and daemon config render needs the key of each server in
+//! the network, and decrypting them one by one dominates CPU on large
+//! networks. Key rows are immutable: replacing a key pair means creating a
+//! new row and deleting the old one, so deletion is the only invalidation.
+//! Cached private keys are zeroized when evicted, invalidated, or dropped.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use wirewarden_types::redact::Redacted;
+use zeroize::Zeroizing;
+
+use super::vpn::WgKey;
+
+struct Entry {
+    private_key: Zeroizing<String>,
+    public_key: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    last_used: u64,
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entry")
+            .field("private_key", &Redacted)
+            .field("public_key", &self.public_key)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("last_used", &self.last_used)
+            .finish()
+    }
+}
+
+impl Entry {
+    fn to_key(&self, id: Uuid) -> WgKey {
+        WgKey {
+            id,
+            private_key: self.private_key.to_string(),
+            public_key: self.public_key.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<Uuid, Entry>,
+    /// Monotonic counter used to find the least recently used entry.
+    clock: u64,
+}
+
+#[derive(Debug)]
+pub struct KeyCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+impl KeyCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::default(),
+        }
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<WgKey> {
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        inner.clock += 1;
+        let clock = inner.clock;
+        let entry = inner.entries.get_mut(&id)?;
+        entry.last_used = clock;
+        Some(entry.to_key(id))
+    }
+
+    /// Cached keys among `ids`, plus the IDs that still need decrypting.
+    pub fn get_many(&self, ids: &[Uuid]) -> (HashMap<Uuid, WgKey>, Vec<Uuid>) {
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        inner.clock += 1;
+        let clock = inner.clock;
+        let mut found = HashMap::with_capacity(ids.len());
+        let mut missing = Vec::new();
+        for &id in ids {
+            match inner.entries.get_mut(&id) {
+                Some(entry) => {
+                    entry.last_used = clock;
+                    found.insert(id, entry.to_key(id));
+                }
+                None => missing.push(id),
+            }
+        }
+        (found, missing)
+    }
+
+    pub fn insert(&self, key: &WgKey) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(&key.id) {
+            // Linear scan; eviction only happens once the working set
+            // outgrows the cache, where it is still far cheaper than AES-GCM.
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+        inner.clock += 1;
+        let last_used = inner.clock;
+        inner.entries.insert(
+            key.id,
+            Entry {
+                private_key: Zeroizing::new(key.private_key.clone()),
+                public_key: key.public_key.clone(),
+                created_at: key.created_at,
+                updated_at: key.updated_at,
+                last_used,
+            },
+        );
+    }
+
+    pub fn invalidate(&self, id: Uuid) {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entries
+            .remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: u128) -> WgKey {
+        WgKey {
+            id: Uuid::from_u128(n),
+            private_key: format!("priv-{n}"),
+            public_key: format!("pub-{n}"),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = KeyCache::new(2);
+        cache.insert(&key(1));
+        cache.insert(&key(2));
+        assert!(cache.get(Uuid::from_u128(1)).is_some());
+
+        cache.insert(&key(3));
+
+        let ids = [1, 2, 3].map(Uuid::from_u128);
+        let (found, missing) = cache.get_many(&ids);
+        assert_eq!(missing, vec![Uuid::from_u128(2)]);
+        assert_eq!(found[&Uuid::from_u128(1)].private_key, "priv-1");
+        assert_eq!(found[&Uuid::from_u128(3)].public_key, "pub-3");
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let cache = KeyCache::new(4);
+        cache.insert(&key(1));
+        cache.invalidate(Uuid::from_u128(1));
+        assert!(cache.get(Uuid::from_u128(1)).is_none());
+    }
+}
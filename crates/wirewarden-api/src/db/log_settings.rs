@@ -0,0 +1,81 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct LogSettings {
+    /// An `EnvFilter` directive string, e.g. `info,wirewarden_api=debug`.
+    pub filter: String,
+    /// Fraction of successful requests the request logger records.
+    pub request_sample_rate: f64,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The log overrides set through the tools API, shared by all replicas.
+#[derive(Debug, Clone)]
+pub struct LogSettingsStore {
+    pool: PgPool,
+}
+
+impl LogSettingsStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn get(&self) -> Result<Option<LogSettings>, sqlx::Error> {
+        sqlx::query_as::<_, LogSettings>(
+            "SELECT filter, request_sample_rate, updated_by, updated_at FROM log_settings",
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set(
+        &self,
+        filter: &str,
+        request_sample_rate: f64,
+        updated_by: Uuid,
+    ) -> Result<LogSettings, sqlx::Error> {
+        sqlx::query_as::<_, LogSettings>(
+            "INSERT INTO log_settings (filter, request_sample_rate, updated_by)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (id) DO UPDATE SET
+                 filter = EXCLUDED.filter,
+                 request_sample_rate = EXCLUDED.request_sample_rate,
+                 updated_by = EXCLUDED.updated_by,
+                 updated_at = now()
+             RETURNING filter, request_sample_rate, updated_by, updated_at",
+        )
+        .bind(filter)
+        .bind(request_sample_rate)
+        .bind(updated_by)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Drop the overrides. Returns whether there were any.
+    #[tracing::instrument(skip(self))]
+    pub async fn clear(&self) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM log_settings")
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -12,13 +12,32 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+pub mod activity;
+pub mod approval;
+pub mod audit;
+pub mod connection;
+pub mod digest;
+pub mod job;
+pub mod key_cache;
+pub mod log_settings;
+pub mod notification;
+pub mod report;
+pub mod schedule;
+pub mod token_cache;
+pub mod usage;
 pub mod user;
 pub mod vpn;
 pub mod webauthn;
+pub mod webhook;
+
+use std::collections::HashMap;
 
 use sqlx::PgPool;
+use sqlx::migrate::{Migration, Migrator};
 use sqlx::postgres::PgPoolOptions;
 
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
 pub async fn create_pool(database_url: &str) -> PgPool {
     PgPoolOptions::new()
         .max_connections(8)
@@ -28,8 +47,82 @@ pub async fn create_pool(database_url: &str) -> PgPool {
 }
 
 pub async fn migrate(pool: &PgPool) {
-    sqlx::migrate!("./migrations")
+    MIGRATOR
         .run(pool)
         .await
         .expect("failed to run database migrations");
 }
+
+/// Every migration this build ships, oldest first, for tests that replay
+/// part of the history against data of their own.
+pub fn migrations() -> impl Iterator<Item = &'static Migration> {
+    MIGRATOR.iter()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the file has changed since; `migrate` will refuse to run.
+    Modified,
+    /// Applied by a newer build that ships a migration this one lacks.
+    Unknown,
+}
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+/// Compare this build's migrations with those recorded in the database,
+/// without applying anything.
+pub async fn migration_status(pool: &PgPool) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+    let table_exists: bool =
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(pool)
+            .await?;
+    let rows: Vec<(i64, String, Vec<u8>)> = if table_exists {
+        sqlx::query_as(
+            "SELECT version, description, checksum FROM _sqlx_migrations
+             WHERE success ORDER BY version",
+        )
+        .fetch_all(pool)
+        .await?
+    } else {
+        Vec::new()
+    };
+    let mut applied: HashMap<i64, (String, Vec<u8>)> = rows
+        .into_iter()
+        .map(|(version, description, checksum)| (version, (description, checksum)))
+        .collect();
+
+    let mut status: Vec<_> = MIGRATOR
+        .iter()
+        .map(|m| {
+            let state = match applied.remove(&m.version) {
+                None => MigrationState::Pending,
+                Some((_, checksum)) if *checksum != *m.checksum => MigrationState::Modified,
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                state,
+            }
+        })
+        .collect();
+    status.extend(
+        applied
+            .into_iter()
+            .map(|(version, (description, _))| MigrationStatus {
+                version,
+                description,
+                state: MigrationState::Unknown,
+            }),
+    );
+    status.sort_by_key(|m| m.version);
+    Ok(status)
+}
@@ -0,0 +1,131 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::events::EventKind;
+use crate::i18n::Locale;
+
+/// Events a user can be emailed about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    ServerOffline,
+    ClientCreated,
+    ServerTokenRotated,
+}
+
+impl NotificationKind {
+    /// The kind an event is mailed as, if any.
+    pub fn for_event(kind: EventKind) -> Option<Self> {
+        match kind {
+            EventKind::ServerOffline => Some(Self::ServerOffline),
+            EventKind::ClientCreated => Some(Self::ClientCreated),
+            EventKind::ServerTokenRotated => Some(Self::ServerTokenRotated),
+            _ => None,
+        }
+    }
+
+    /// The `notification_settings` column holding the toggle.
+    fn column(self) -> &'static str {
+        match self {
+            Self::ServerOffline => "server_offline",
+            Self::ClientCreated => "client_created",
+            Self::ServerTokenRotated => "server_token_rotated",
+        }
+    }
+}
+
+/// A user's per-event toggles; everything is off until set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct NotificationSettings {
+    pub server_offline: bool,
+    pub client_created: bool,
+    pub server_token_rotated: bool,
+}
+
+/// Someone to email about an event.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Recipient {
+    pub user_id: Uuid,
+    pub email: String,
+    pub locale: Locale,
+}
+
+/// Per-user email notification preferences.
+#[derive(Debug, Clone)]
+pub struct NotificationStore {
+    pool: PgPool,
+}
+
+impl NotificationStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn get(&self, user_id: Uuid) -> Result<NotificationSettings, sqlx::Error> {
+        let settings = sqlx::query_as::<_, NotificationSettings>(
+            "SELECT server_offline, client_created, server_token_rotated
+             FROM notification_settings WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(settings.unwrap_or_default())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set(
+        &self,
+        user_id: Uuid,
+        settings: NotificationSettings,
+    ) -> Result<NotificationSettings, sqlx::Error> {
+        sqlx::query_as::<_, NotificationSettings>(
+            "INSERT INTO notification_settings
+                 (user_id, server_offline, client_created, server_token_rotated)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (user_id) DO UPDATE
+             SET server_offline = EXCLUDED.server_offline,
+                 client_created = EXCLUDED.client_created,
+                 server_token_rotated = EXCLUDED.server_token_rotated,
+                 updated_at = now()
+             RETURNING server_offline, client_created, server_token_rotated",
+        )
+        .bind(user_id)
+        .bind(settings.server_offline)
+        .bind(settings.client_created)
+        .bind(settings.server_token_rotated)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Users who asked to be emailed about `kind`.
+    #[tracing::instrument(skip(self))]
+    pub async fn recipients(&self, kind: NotificationKind) -> Result<Vec<Recipient>, sqlx::Error> {
+        // The column comes from a static list.
+        let query = format!(
+            "SELECT u.id AS user_id, u.email, u.locale
+             FROM notification_settings n
+             JOIN users u ON u.id = n.user_id
+             WHERE n.{}
+             ORDER BY u.username",
+            kind.column()
+        );
+        sqlx::query_as::<_, Recipient>(&query)
+            .fetch_all(&self.pool)
+            .await
+    }
+}
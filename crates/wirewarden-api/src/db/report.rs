@@ -0,0 +1,254 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Aggregate reports over networks, devices and keys. Each is one query, so
+//! admins get the numbers without database access and without the API
+//! loading every row.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Totals across every network, for the dashboard.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Summary {
+    pub networks: i64,
+    pub servers: i64,
+    /// Servers that have checked in and not since been marked offline.
+    pub servers_online: i64,
+    pub clients: i64,
+    /// Clients that handshook with any server since the cutoff.
+    pub clients_seen: i64,
+    /// Assignable addresses summed over networks, as in [`NetworkPeers`].
+    pub capacity: i64,
+}
+
+/// Servers and clients in a network, against its address space.
+#[derive(Debug, sqlx::FromRow)]
+pub struct NetworkPeers {
+    pub network_id: Uuid,
+    pub name: String,
+    pub enabled: bool,
+    pub servers: i64,
+    pub clients: i64,
+    /// Assignable addresses, as `Network::usable_addresses` counts them.
+    pub capacity: i64,
+}
+
+/// How finely [`ReportStore::growth`] buckets creation times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Day,
+    Week,
+    Month,
+}
+
+impl Bucket {
+    /// The `date_trunc` field and `generate_series` step.
+    fn as_sql(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Day => ("day", "1 day"),
+            Self::Week => ("week", "1 week"),
+            Self::Month => ("month", "1 month"),
+        }
+    }
+}
+
+/// Servers and clients created in one bucket, and how many existed at its
+/// end. Devices deleted since are not counted.
+#[derive(Debug, sqlx::FromRow)]
+pub struct GrowthPoint {
+    pub period_start: DateTime<Utc>,
+    pub servers_added: i64,
+    pub clients_added: i64,
+    pub servers_total: i64,
+    pub clients_total: i64,
+}
+
+/// Preshared key age per network. Each server-client pair has its own key;
+/// a pair without one has never been rendered or rotated.
+#[derive(Debug, sqlx::FromRow)]
+pub struct RotationCompliance {
+    pub network_id: Uuid,
+    pub name: String,
+    pub pairs: i64,
+    /// Pairs whose key was set or rotated within the allowed age.
+    pub compliant: i64,
+    pub stale: i64,
+    pub missing: i64,
+    /// When the least recently rotated key was last set.
+    pub oldest_rotation: Option<DateTime<Utc>>,
+}
+
+/// Devices in the networks a user owns. Unowned networks are reported with
+/// no user.
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserDevices {
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub networks: i64,
+    pub servers: i64,
+    pub clients: i64,
+}
+
+/// Read-only reporting queries.
+#[derive(Debug, Clone)]
+pub struct ReportStore {
+    pool: PgPool,
+}
+
+impl ReportStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Totals for the dashboard, counting clients seen since `seen_since`.
+    #[tracing::instrument(skip(self))]
+    pub async fn summary(&self, seen_since: DateTime<Utc>) -> Result<Summary, sqlx::Error> {
+        sqlx::query_as::<_, Summary>(
+            "SELECT (SELECT count(*) FROM networks) AS networks,
+                    (SELECT count(*) FROM wg_servers) AS servers,
+                    (SELECT count(*) FROM wg_servers
+                     WHERE last_seen_at IS NOT NULL AND NOT offline) AS servers_online,
+                    (SELECT count(*) FROM wg_clients) AS clients,
+                    (SELECT count(DISTINCT c.id) FROM wg_clients c
+                     JOIN peer_connections pc ON pc.peer_id = c.id
+                     WHERE pc.last_handshake >= $1) AS clients_seen,
+                    (SELECT coalesce(sum(CASE family(cidr_ip)
+                         WHEN 4 THEN (2 ^ (32 - masklen(cidr_ip)))::bigint - 2
+                         ELSE least(2 ^ (128 - masklen(cidr_ip)) - 1, 2147483647)::bigint
+                     END), 0)::bigint FROM networks) AS capacity",
+        )
+        .bind(seen_since)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn peers_per_network(&self) -> Result<Vec<NetworkPeers>, sqlx::Error> {
+        sqlx::query_as::<_, NetworkPeers>(
+            "SELECT n.id AS network_id, n.name, n.enabled,
+                    (SELECT count(*) FROM wg_servers s WHERE s.network_id = n.id) AS servers,
+                    (SELECT count(*) FROM wg_clients c WHERE c.network_id = n.id) AS clients,
+                    CASE family(n.cidr_ip)
+                        WHEN 4 THEN (2 ^ (32 - masklen(n.cidr_ip)))::bigint - 2
+                        ELSE least(2 ^ (128 - masklen(n.cidr_ip)) - 1, 2147483647)::bigint
+                    END AS capacity
+             FROM networks n
+             ORDER BY n.name",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// One point per bucket from `from` to `to`, including empty ones,
+    /// optionally limited to one network.
+    #[tracing::instrument(skip(self))]
+    pub async fn growth(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        bucket: Bucket,
+        network_id: Option<Uuid>,
+    ) -> Result<Vec<GrowthPoint>, sqlx::Error> {
+        let (field, step) = bucket.as_sql();
+        sqlx::query_as::<_, GrowthPoint>(
+            "WITH periods AS (
+                 SELECT p AS period_start, p + $4::interval AS period_end
+                 FROM generate_series(date_trunc($3, $1), $2, $4::interval) p
+             ),
+             devices AS (
+                 SELECT created_at, true AS is_server FROM wg_servers
+                 WHERE $5::uuid IS NULL OR network_id = $5
+                 UNION ALL
+                 SELECT created_at, false FROM wg_clients
+                 WHERE $5::uuid IS NULL OR network_id = $5
+             )
+             SELECT p.period_start,
+                    count(*) FILTER (WHERE d.is_server AND d.created_at >= p.period_start)
+                        AS servers_added,
+                    count(*) FILTER (WHERE NOT d.is_server AND d.created_at >= p.period_start)
+                        AS clients_added,
+                    count(*) FILTER (WHERE d.is_server) AS servers_total,
+                    count(*) FILTER (WHERE NOT d.is_server) AS clients_total
+             FROM periods p
+             LEFT JOIN devices d ON d.created_at < p.period_end
+             GROUP BY p.period_start
+             ORDER BY p.period_start",
+        )
+        .bind(from)
+        .bind(to)
+        .bind(field)
+        .bind(step)
+        .bind(network_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Keys set or rotated more than `max_age_days` ago count as stale.
+    #[tracing::instrument(skip(self))]
+    pub async fn rotation_compliance(
+        &self,
+        max_age_days: i32,
+        network_id: Option<Uuid>,
+    ) -> Result<Vec<RotationCompliance>, sqlx::Error> {
+        sqlx::query_as::<_, RotationCompliance>(
+            "SELECT n.id AS network_id, n.name,
+                    count(pair.server_id) AS pairs,
+                    count(*) FILTER (WHERE pair.rotated_at >= now() - make_interval(days => $1))
+                        AS compliant,
+                    count(*) FILTER (WHERE pair.rotated_at < now() - make_interval(days => $1))
+                        AS stale,
+                    count(*) FILTER (WHERE pair.server_id IS NOT NULL AND pair.rotated_at IS NULL)
+                        AS missing,
+                    min(pair.rotated_at) AS oldest_rotation
+             FROM networks n
+             LEFT JOIN (
+                 SELECT s.network_id, s.id AS server_id, p.updated_at AS rotated_at
+                 FROM wg_servers s
+                 JOIN wg_clients c ON c.network_id = s.network_id
+                 LEFT JOIN wg_peer_psks p ON p.server_id = s.id AND p.client_id = c.id
+             ) pair ON pair.network_id = n.id
+             WHERE $2::uuid IS NULL OR n.id = $2
+             GROUP BY n.id, n.name
+             ORDER BY n.name",
+        )
+        .bind(max_age_days)
+        .bind(network_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn devices_per_user(&self) -> Result<Vec<UserDevices>, sqlx::Error> {
+        sqlx::query_as::<_, UserDevices>(
+            "SELECT u.id AS user_id, u.username,
+                    count(n.id) AS networks,
+                    coalesce(sum(n.servers), 0)::bigint AS servers,
+                    coalesce(sum(n.clients), 0)::bigint AS clients
+             FROM (
+                 SELECT n.id, n.owner_id,
+                        (SELECT count(*) FROM wg_servers s WHERE s.network_id = n.id) AS servers,
+                        (SELECT count(*) FROM wg_clients c WHERE c.network_id = n.id) AS clients
+                 FROM networks n
+             ) n
+             FULL JOIN users u ON u.id = n.owner_id
+             GROUP BY u.id, u.username
+             ORDER BY u.username NULLS LAST",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use sqlx::types::Json;
+use uuid::Uuid;
+
+use crate::changes::Change;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ScheduledChange {
+    pub id: Uuid,
+    pub change: Json<Change>,
+    pub run_at: DateTime<Utc>,
+    pub created_by: Option<Uuid>,
+    pub status: ScheduleStatus,
+    pub error: Option<String>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub executed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// The pending change that must be approved before this one runs.
+    pub approval_id: Option<Uuid>,
+}
+
+/// Changes queued for the scheduler to apply at `run_at`.
+#[derive(Debug, Clone)]
+pub struct ScheduleStore {
+    pool: PgPool,
+}
+
+impl ScheduleStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn create(
+        &self,
+        change: &Change,
+        run_at: DateTime<Utc>,
+        created_by: Uuid,
+        approval_id: Option<Uuid>,
+    ) -> Result<ScheduledChange, sqlx::Error> {
+        sqlx::query_as::<_, ScheduledChange>(
+            "INSERT INTO scheduled_changes (change, run_at, created_by, approval_id)
+             VALUES ($1, $2, $3, $4)
+             RETURNING *",
+        )
+        .bind(Json(change))
+        .bind(run_at)
+        .bind(created_by)
+        .bind(approval_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// The change waiting on `approval_id`, if one is.
+    #[tracing::instrument(skip(self))]
+    pub async fn by_approval(
+        &self,
+        approval_id: Uuid,
+    ) -> Result<Option<ScheduledChange>, sqlx::Error> {
+        sqlx::query_as::<_, ScheduledChange>(
+            "SELECT * FROM scheduled_changes WHERE approval_id = $1",
+        )
+        .bind(approval_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Return a claimed change to `pending` until `approval_id` is granted.
+    #[tracing::instrument(skip(self))]
+    pub async fn await_approval(&self, id: Uuid, approval_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE scheduled_changes
+             SET status = 'pending', claimed_at = NULL, approval_id = $2
+             WHERE id = $1 AND status = 'running'",
+        )
+        .bind(id)
+        .bind(approval_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Cancel the change waiting on `approval_id` once it is rejected.
+    #[tracing::instrument(skip(self))]
+    pub async fn cancel_for_approval(
+        &self,
+        approval_id: Uuid,
+    ) -> Result<Option<ScheduledChange>, sqlx::Error> {
+        sqlx::query_as::<_, ScheduledChange>(
+            "UPDATE scheduled_changes SET status = 'cancelled', error = 'approval rejected'
+             WHERE approval_id = $1 AND status = 'pending'
+             RETURNING *",
+        )
+        .bind(approval_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn get(&self, id: Uuid) -> Result<Option<ScheduledChange>, sqlx::Error> {
+        sqlx::query_as::<_, ScheduledChange>("SELECT * FROM scheduled_changes WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list(
+        &self,
+        status: Option<ScheduleStatus>,
+    ) -> Result<Vec<ScheduledChange>, sqlx::Error> {
+        sqlx::query_as::<_, ScheduledChange>(
+            "SELECT * FROM scheduled_changes
+             WHERE $1::text IS NULL OR status = $1
+             ORDER BY run_at",
+        )
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Pending changes for `network_id` due to run before `until`.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_pending_for_network(
+        &self,
+        network_id: Uuid,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<ScheduledChange>, sqlx::Error> {
+        sqlx::query_as::<_, ScheduledChange>(
+            "SELECT * FROM scheduled_changes
+             WHERE status = 'pending' AND change->>'network_id' = $1::text AND run_at <= $2
+             ORDER BY run_at",
+        )
+        .bind(network_id)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Cancel a change that has not started yet. Returns `None` if it is no
+    /// longer pending.
+    #[tracing::instrument(skip(self))]
+    pub async fn cancel(&self, id: Uuid) -> Result<Option<ScheduledChange>, sqlx::Error> {
+        sqlx::query_as::<_, ScheduledChange>(
+            "UPDATE scheduled_changes SET status = 'cancelled'
+             WHERE id = $1 AND status = 'pending'
+             RETURNING *",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Claim due changes by moving them to `running`. Rows locked by another
+    /// claimer are skipped so each change runs once. A change still running
+    /// `lease_secs` after it was claimed belonged to a scheduler that died
+    /// before finishing it, and is claimed again. With `require_approval`,
+    /// a change waiting on an approval stays put until it is granted.
+    #[tracing::instrument(skip(self))]
+    pub async fn claim_due(
+        &self,
+        limit: i64,
+        lease_secs: i64,
+        require_approval: bool,
+    ) -> Result<Vec<ScheduledChange>, sqlx::Error> {
+        sqlx::query_as::<_, ScheduledChange>(
+            "UPDATE scheduled_changes SET status = 'running', claimed_at = now()
+             WHERE id IN (
+                 SELECT s.id FROM scheduled_changes s
+                 LEFT JOIN pending_changes p ON p.id = s.approval_id
+                 WHERE (s.status = 'pending' AND s.run_at <= now()
+                        AND (NOT $3 OR p.id IS NULL OR p.status = 'approved'))
+                    OR (s.status = 'running'
+                        AND s.claimed_at < now() - make_interval(secs => $2))
+                 ORDER BY s.run_at
+                 LIMIT $1
+                 FOR UPDATE OF s SKIP LOCKED
+             )
+             RETURNING *",
+        )
+        .bind(limit)
+        .bind(lease_secs as f64)
+        .bind(require_approval)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn finish(&self, id: Uuid, error: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE scheduled_changes
+             SET status = CASE WHEN $2::text IS NULL THEN 'done' ELSE 'failed' END,
+                 error = $2, executed_at = now()
+             WHERE id = $1 AND status = 'running'",
+        )
+        .bind(id)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Short-lived cache of daemon API tokens to their servers.
+//!
+//! Every daemon request authenticates by token, and with many daemons
+//! polling that lookup is most of the read load on `wg_servers`. The cache
+//! is per process: a change made through this process drops its entries at
+//! once, but other replicas keep honouring a rotated or deleted token until
+//! their entry expires, so the TTL is how long a revoked token may still
+//! work. It is off unless `SERVER_TOKEN_CACHE_SECS` is set. Unknown tokens
+//! are never cached.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use super::vpn::WgServer;
+
+/// Expired entries are swept on insert once the map grows past this.
+const SWEEP_THRESHOLD: usize = 1024;
+
+#[derive(Debug)]
+pub struct ServerTokenCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, WgServer)>>,
+    /// Bumped by every invalidation, under the `entries` lock.
+    generation: AtomicU64,
+}
+
+impl ServerTokenCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Taken before reading a server to [`insert`](Self::insert), so a read
+    /// that raced an invalidation is not cached.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub fn get(&self, token: &str) -> Option<WgServer> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        match entries.get(token) {
+            Some((expires, server)) if *expires > Instant::now() => Some(server.clone()),
+            Some(_) => {
+                entries.remove(token);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache `server`, read after [`generation`](Self::generation) returned
+    /// `generation`. Dropped if anything was invalidated since, as the read
+    /// may predate that change.
+    pub fn insert(&self, server: &WgServer, generation: u64) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if entries.len() >= SWEEP_THRESHOLD {
+            entries.retain(|_, (expires, _)| *expires > now);
+        }
+        entries.insert(server.api_token.clone(), (now + self.ttl, server.clone()));
+    }
+
+    pub fn invalidate_server(&self, server_id: Uuid) {
+        self.retain(|s| s.id != server_id);
+    }
+
+    pub fn invalidate_network(&self, network_id: Uuid) {
+        self.retain(|s| s.network_id != network_id);
+    }
+
+    fn retain<F>(&self, keep: F)
+    where
+        F: Fn(&WgServer) -> bool,
+    {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.retain(|_, (_, server)| keep(server));
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn server(n: u128, network: u128) -> WgServer {
+        WgServer {
+            id: Uuid::from_u128(n),
+            network_id: Uuid::from_u128(network),
+            name: format!("srv-{n}"),
+            key_id: Uuid::from_u128(n),
+            api_token: format!("token-{n}"),
+            address_offset: n as i32,
+            forwards_internet_traffic: false,
+            manage_nat: false,
+            fwmark: None,
+            route_table: None,
+            rule_priority: None,
+            mtu: None,
+            persistent_keepalive: None,
+            endpoint_host: None,
+            endpoint_port: 51820,
+            tags: Vec::new(),
+            notes: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            last_seen_at: None,
+            offline: false,
+            daemon_version: None,
+            listen_port: None,
+            priority: 0,
+        }
+    }
+
+    #[test]
+    fn test_invalidation() {
+        let cache = ServerTokenCache::new(Duration::from_secs(60));
+        cache.insert(&server(1, 10), cache.generation());
+        cache.insert(&server(2, 10), cache.generation());
+        cache.insert(&server(3, 20), cache.generation());
+        assert_eq!(cache.get("token-1").unwrap().id, Uuid::from_u128(1));
+
+        cache.invalidate_server(Uuid::from_u128(1));
+        assert!(cache.get("token-1").is_none());
+        assert!(cache.get("token-2").is_some());
+
+        cache.invalidate_network(Uuid::from_u128(10));
+        assert!(cache.get("token-2").is_none());
+        assert!(cache.get("token-3").is_some());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let cache = ServerTokenCache::new(Duration::from_nanos(1));
+        cache.insert(&server(1, 10), cache.generation());
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(cache.get("token-1").is_none());
+    }
+
+    #[test]
+    fn test_reads_racing_an_invalidation_are_not_cached() {
+        let cache = ServerTokenCache::new(Duration::from_secs(60));
+        let generation = cache.generation();
+        // The row is changed and invalidated while the read is in flight.
+        cache.invalidate_server(Uuid::from_u128(1));
+        cache.insert(&server(1, 10), generation);
+        assert!(cache.get("token-1").is_none());
+
+        cache.insert(&server(1, 10), cache.generation());
+        assert!(cache.get("token-1").is_some());
+    }
+}
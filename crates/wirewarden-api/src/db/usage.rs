@@ -0,0 +1,202 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use wirewarden_types::daemon::DaemonPeerTelemetry;
+
+use crate::usage::{HOURLY_RETENTION_DAYS, Step};
+
+/// A client whose quota state changed.
+#[derive(Debug, sqlx::FromRow)]
+pub struct QuotaChange {
+    pub client_id: Uuid,
+    pub network_id: Uuid,
+    pub quota_exceeded: bool,
+    pub used_bytes: i64,
+    pub monthly_quota_bytes: Option<i64>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct UsagePoint {
+    pub at: DateTime<Utc>,
+    pub rx_bytes: i64,
+    pub tx_bytes: i64,
+}
+
+/// Per-client usage history, from the transfer counters daemons report.
+#[derive(Debug, Clone)]
+pub struct UsageStore {
+    pool: PgPool,
+}
+
+impl UsageStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Add what moved since the server's last report to the clients' current
+    /// hour. Keys that are not clients of the network, such as other
+    /// servers, are skipped. A client's first report only sets the baseline,
+    /// since its counters may predate usage history.
+    #[tracing::instrument(skip(self, peers), fields(peers = peers.len()))]
+    pub async fn record(
+        &self,
+        server_id: Uuid,
+        network_id: Uuid,
+        peers: &[DaemonPeerTelemetry],
+    ) -> Result<(), sqlx::Error> {
+        let keys: Vec<&str> = peers.iter().map(|p| p.public_key.as_str()).collect();
+        let rx: Vec<i64> = peers.iter().map(|p| saturating_i64(p.rx_bytes)).collect();
+        let tx: Vec<i64> = peers.iter().map(|p| saturating_i64(p.tx_bytes)).collect();
+        sqlx::query(
+            "WITH sample AS (
+                 SELECT DISTINCT ON (c.id) c.id AS client_id, s.rx_bytes, s.tx_bytes
+                 FROM UNNEST($3::text[], $4::int8[], $5::int8[])
+                     AS s(public_key, rx_bytes, tx_bytes)
+                 JOIN wg_keys k ON k.public_key = s.public_key
+                 JOIN wg_clients c ON c.key_id = k.id AND c.network_id = $2
+             ),
+             delta AS (
+                 SELECT s.client_id, s.rx_bytes, s.tx_bytes,
+                     CASE WHEN s.rx_bytes < p.rx_bytes THEN s.rx_bytes
+                          ELSE s.rx_bytes - p.rx_bytes END AS rx_delta,
+                     CASE WHEN s.tx_bytes < p.tx_bytes THEN s.tx_bytes
+                          ELSE s.tx_bytes - p.tx_bytes END AS tx_delta
+                 FROM sample s
+                 LEFT JOIN wg_peer_counters p
+                     ON p.server_id = $1 AND p.client_id = s.client_id
+             ),
+             counters AS (
+                 INSERT INTO wg_peer_counters (server_id, client_id, rx_bytes, tx_bytes)
+                 SELECT $1, client_id, rx_bytes, tx_bytes FROM delta
+                 ON CONFLICT (server_id, client_id) DO UPDATE
+                 SET rx_bytes = EXCLUDED.rx_bytes,
+                     tx_bytes = EXCLUDED.tx_bytes,
+                     updated_at = now()
+             )
+             INSERT INTO client_usage (client_id, resolution, bucket, rx_bytes, tx_bytes)
+             SELECT client_id, 'hour', date_trunc('hour', now()), rx_delta, tx_delta
+             FROM delta
+             WHERE rx_delta > 0 OR tx_delta > 0
+             ON CONFLICT (client_id, resolution, bucket) DO UPDATE
+             SET rx_bytes = client_usage.rx_bytes + EXCLUDED.rx_bytes,
+                 tx_bytes = client_usage.tx_bytes + EXCLUDED.tx_bytes",
+        )
+        .bind(server_id)
+        .bind(network_id)
+        .bind(&keys)
+        .bind(&rx)
+        .bind(&tx)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Mark clients over quota whose usage this UTC month has reached it,
+    /// and clear the mark on those no longer over, e.g. once the month turns
+    /// or the quota is raised. With `network_id` only that network's clients
+    /// are checked. Returns the clients that changed.
+    #[tracing::instrument(skip(self))]
+    pub async fn sync_quotas(
+        &self,
+        network_id: Option<Uuid>,
+    ) -> Result<Vec<QuotaChange>, sqlx::Error> {
+        sqlx::query_as::<_, QuotaChange>(
+            "WITH used AS (
+                 SELECT c.id, COALESCE(SUM(u.rx_bytes + u.tx_bytes), 0)::BIGINT AS used_bytes
+                 FROM wg_clients c
+                 LEFT JOIN client_usage u
+                     ON u.client_id = c.id AND u.bucket >= date_trunc('month', now(), 'UTC')
+                 WHERE (c.monthly_quota_bytes IS NOT NULL OR c.quota_exceeded)
+                   AND ($1::uuid IS NULL OR c.network_id = $1)
+                 GROUP BY c.id
+             )
+             UPDATE wg_clients c
+             SET quota_exceeded = COALESCE(used.used_bytes >= c.monthly_quota_bytes, false),
+                 updated_at = now()
+             FROM used
+             WHERE c.id = used.id
+               AND c.quota_exceeded <> COALESCE(used.used_bytes >= c.monthly_quota_bytes, false)
+             RETURNING c.id AS client_id, c.network_id, c.quota_exceeded, used.used_bytes,
+                 c.monthly_quota_bytes",
+        )
+        .bind(network_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Usage since `since`, summed per `step` in UTC. Buckets with no
+    /// traffic are left out.
+    #[tracing::instrument(skip(self))]
+    pub async fn series(
+        &self,
+        client_id: Uuid,
+        since: DateTime<Utc>,
+        step: Step,
+    ) -> Result<Vec<UsagePoint>, sqlx::Error> {
+        sqlx::query_as::<_, UsagePoint>(
+            "SELECT date_trunc($3, bucket, 'UTC') AS at,
+                 SUM(rx_bytes)::BIGINT AS rx_bytes,
+                 SUM(tx_bytes)::BIGINT AS tx_bytes
+             FROM client_usage
+             WHERE client_id = $1 AND bucket >= date_trunc($3, $2, 'UTC')
+             GROUP BY 1
+             ORDER BY 1",
+        )
+        .bind(client_id)
+        .bind(since)
+        .bind(step.as_str())
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Fold hourly buckets older than [`HOURLY_RETENTION_DAYS`] into daily
+    /// ones and drop daily buckets older than `retention_days`.
+    #[tracing::instrument(skip(self))]
+    pub async fn rollup(&self, retention_days: i32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "WITH aged AS (
+                 DELETE FROM client_usage
+                 WHERE resolution = 'hour'
+                   AND bucket < date_trunc('day', now(), 'UTC') - make_interval(days => $1)
+                 RETURNING client_id, bucket, rx_bytes, tx_bytes
+             )
+             INSERT INTO client_usage (client_id, resolution, bucket, rx_bytes, tx_bytes)
+             SELECT client_id, 'day', date_trunc('day', bucket, 'UTC'),
+                 SUM(rx_bytes), SUM(tx_bytes)
+             FROM aged
+             GROUP BY 1, 3
+             ON CONFLICT (client_id, resolution, bucket) DO UPDATE
+             SET rx_bytes = client_usage.rx_bytes + EXCLUDED.rx_bytes,
+                 tx_bytes = client_usage.tx_bytes + EXCLUDED.tx_bytes",
+        )
+        .bind(HOURLY_RETENTION_DAYS)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM client_usage
+             WHERE resolution = 'day' AND bucket < now() - make_interval(days => $1)",
+        )
+        .bind(retention_days)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+}
+
+fn saturating_i64(n: u64) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
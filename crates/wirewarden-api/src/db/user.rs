@@ -12,14 +12,19 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use std::fmt;
+
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
 use argon2::Argon2;
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
+use wirewarden_types::redact::{Redacted, redact_opt};
 
-#[derive(Debug, sqlx::FromRow)]
+use crate::i18n::Locale;
+
+#[derive(sqlx::FromRow)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
@@ -28,8 +33,32 @@ pub struct User {
     pub password_hash: String,
     pub reset_token: Option<String>,
     pub reset_token_expires_at: Option<DateTime<Utc>>,
+    pub locale: Locale,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub verify_token: Option<String>,
+    pub verify_token_expires_at: Option<DateTime<Utc>>,
+}
+
+impl fmt::Debug for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("User")
+            .field("id", &self.id)
+            .field("username", &self.username)
+            .field("display_name", &self.display_name)
+            .field("email", &self.email)
+            .field("password_hash", &Redacted)
+            .field("reset_token", &redact_opt(&self.reset_token))
+            .field("reset_token_expires_at", &self.reset_token_expires_at)
+            .field("locale", &self.locale)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("email_verified_at", &self.email_verified_at)
+            .field("verify_token", &redact_opt(&self.verify_token))
+            .field("verify_token_expires_at", &self.verify_token_expires_at)
+            .finish()
+    }
 }
 
 #[derive(Debug, sqlx::FromRow)]
@@ -61,6 +90,9 @@ pub enum UserStoreError {
 
     #[error("reset token expired")]
     TokenExpired,
+
+    #[error("verification token expired")]
+    VerifyTokenExpired,
 }
 
 type Result<T> = std::result::Result<T, UserStoreError>;
@@ -178,6 +210,18 @@ impl UserStore {
         Ok(())
     }
 
+    #[tracing::instrument(skip(self))]
+    pub async fn set_locale(&self, id: Uuid, locale: Locale) -> Result<Option<User>> {
+        sqlx::query_as::<_, User>(
+            "UPDATE users SET locale = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(locale)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn set_reset_token(&self, id: Uuid) -> Result<String> {
         let token = Uuid::new_v4().to_string();
@@ -209,10 +253,10 @@ impl UserStore {
             return Ok(None);
         };
 
-        if let Some(expires_at) = user.reset_token_expires_at {
-            if expires_at < Utc::now() {
-                return Err(UserStoreError::TokenExpired);
-            }
+        if let Some(expires_at) = user.reset_token_expires_at
+            && expires_at < Utc::now()
+        {
+            return Err(UserStoreError::TokenExpired);
         }
 
         sqlx::query(
@@ -226,6 +270,61 @@ impl UserStore {
         Ok(Some(user))
     }
 
+    /// Issue a fresh email verification token, valid for a day.
+    #[tracing::instrument(skip(self))]
+    pub async fn set_verify_token(&self, id: Uuid) -> Result<String> {
+        let token = Uuid::new_v4().to_string();
+        let expires_at = Utc::now() + chrono::Duration::days(1);
+
+        sqlx::query(
+            "UPDATE users SET verify_token = $1, verify_token_expires_at = $2, updated_at = now()
+             WHERE id = $3",
+        )
+        .bind(&token)
+        .bind(expires_at)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Mark the token's user verified, returning them updated.
+    #[tracing::instrument(skip(self, token))]
+    pub async fn consume_verify_token(&self, token: &str) -> Result<Option<User>> {
+        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE verify_token = $1")
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let Some(user) = user else {
+            return Ok(None);
+        };
+
+        if let Some(expires_at) = user.verify_token_expires_at
+            && expires_at < Utc::now()
+        {
+            return Err(UserStoreError::VerifyTokenExpired);
+        }
+
+        self.mark_verified(user.id).await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn mark_verified(&self, id: Uuid) -> Result<Option<User>> {
+        sqlx::query_as::<_, User>(
+            "UPDATE users
+             SET email_verified_at = coalesce(email_verified_at, now()),
+                 verify_token = NULL, verify_token_expires_at = NULL, updated_at = now()
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn delete(&self, id: Uuid) -> Result<()> {
         sqlx::query("DELETE FROM users WHERE id = $1")
@@ -237,6 +336,7 @@ impl UserStore {
 
     // --- Passkey operations ---
 
+    #[allow(clippy::too_many_arguments)]
     #[tracing::instrument(skip(self, credential_id, public_key))]
     pub async fn add_passkey(
         &self,
@@ -12,19 +12,38 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
-use std::fmt::Write as _;
-use std::net::Ipv4Addr;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as _};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
 
 use aes_gcm::aead::{Aead, OsRng, rand_core::RngCore};
 use aes_gcm::{AeadCore, Aes256Gcm, KeyInit, Nonce};
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
 use chrono::{DateTime, Utc};
-use ipnetwork::{IpNetwork, Ipv4Network};
-use sqlx::PgPool;
+use ipnetwork::IpNetwork;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
+use wirewarden_types::redact::Redacted;
 use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use super::key_cache::KeyCache;
+use super::token_cache::ServerTokenCache;
+
+use crate::access::AccessSchedule;
+use crate::hooks::Hooks;
+use crate::i18n::{Locale, Msg};
+use crate::keepalive;
+use crate::mtu;
+use crate::pagination::{ListOptions, escape_like};
+use crate::policy_routing::PolicyRouting;
 
 // ---------------------------------------------------------------------------
 // Model types
@@ -38,17 +57,145 @@ pub struct Network {
     pub owner_id: Option<Uuid>,
     pub dns_servers: Vec<String>,
     pub persistent_keepalive: i32,
+    pub enabled: bool,
+    pub config_serial: i64,
+    pub notes: Option<String>,
+    pub port_range_start: Option<i32>,
+    pub port_range_end: Option<i32>,
+    pub endpoint_template: Option<String>,
+    pub allocation: Allocation,
+    /// Interface MTU for the network's peers, unless they set their own.
+    pub mtu: Option<i32>,
+    /// CIDRs full-tunnel clients keep off the VPN, besides private ranges.
+    pub excluded_cidrs: Vec<String>,
+    /// Whether full-tunnel clients keep private ranges off the VPN, unless
+    /// the config request says otherwise.
+    pub lan_access: bool,
+    /// wg-quick hooks for client configs, unless clients set their own.
+    #[sqlx(flatten)]
+    pub hooks: Hooks,
+    /// Written after the DNS servers in client configs.
+    pub search_domains: Vec<String>,
+    pub topology: Topology,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// How a network picks the address of each new server or client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum Allocation {
+    /// The lowest free address, filling gaps left by deletions.
+    #[default]
+    LowestFree,
+    /// Any free address, so neighbours cannot be guessed by scanning.
+    RandomInRange,
+    /// The highest free address, keeping low ones for infrastructure.
+    SequentialFromHigh,
+}
+
+/// Which peers a network's servers have besides its clients.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum Topology {
+    /// None; each server serves only its clients.
+    Hub,
+    /// Every other server, with the routes it advertises.
+    #[default]
+    Mesh,
+}
+
 impl Network {
     pub fn prefix(&self) -> u8 {
         self.cidr_ip.prefix()
     }
+
+    /// Listen ports this network's servers may use; `None` if unrestricted.
+    pub fn port_range(&self) -> Option<RangeInclusive<i32>> {
+        Some(self.port_range_start?..=self.port_range_end?)
+    }
+
+    /// Number of assignable offsets, excluding the network and broadcast
+    /// addresses, or for IPv6 the subnet-router anycast address. Offsets are
+    /// stored as `i32`, so large IPv6 networks are capped there.
+    pub fn usable_addresses(&self) -> i64 {
+        match self.cidr_ip {
+            IpNetwork::V4(_) => (1i64 << (32 - self.prefix())) - 2,
+            IpNetwork::V6(_) => match 128 - u32::from(self.prefix()) {
+                bits @ 0..31 => (1i64 << bits) - 1,
+                _ => i32::MAX as i64,
+            },
+        }
+    }
+}
+
+/// Picks the offset for a new server or client.
+pub trait AllocationStrategy {
+    /// A free offset in `1..=usable`, given the offsets in use in ascending
+    /// order; `None` when every one is taken.
+    fn pick(&self, used: &[i32], usable: i64) -> Option<i32>;
+}
+
+struct LowestFree;
+
+impl AllocationStrategy for LowestFree {
+    fn pick(&self, used: &[i32], usable: i64) -> Option<i32> {
+        nth_free(used, 0).filter(|&offset| i64::from(offset) <= usable)
+    }
+}
+
+struct RandomInRange;
+
+impl AllocationStrategy for RandomInRange {
+    fn pick(&self, used: &[i32], usable: i64) -> Option<i32> {
+        let free = usable - used.len() as i64;
+        if free <= 0 {
+            return None;
+        }
+        nth_free(used, rand::thread_rng().gen_range(0..free))
+    }
+}
+
+struct SequentialFromHigh;
+
+impl AllocationStrategy for SequentialFromHigh {
+    fn pick(&self, used: &[i32], usable: i64) -> Option<i32> {
+        let mut candidate = usable;
+        for &offset in used.iter().rev() {
+            if i64::from(offset) != candidate {
+                break;
+            }
+            candidate -= 1;
+        }
+        i32::try_from(candidate).ok().filter(|&offset| offset >= 1)
+    }
+}
+
+/// The `n`th (from zero) offset not in `used`, counting up from 1. `used`
+/// must be ascending.
+fn nth_free(used: &[i32], n: i64) -> Option<i32> {
+    let mut candidate = n + 1;
+    for &offset in used {
+        if i64::from(offset) > candidate {
+            break;
+        }
+        candidate += 1;
+    }
+    i32::try_from(candidate).ok()
+}
+
+impl Allocation {
+    pub fn strategy(self) -> &'static dyn AllocationStrategy {
+        match self {
+            Self::LowestFree => &LowestFree,
+            Self::RandomInRange => &RandomInRange,
+            Self::SequentialFromHigh => &SequentialFromHigh,
+        }
+    }
 }
 
-#[derive(Debug)]
 pub struct WgKey {
     pub id: Uuid,
     pub private_key: String,
@@ -57,6 +204,18 @@ pub struct WgKey {
     pub updated_at: DateTime<Utc>,
 }
 
+impl fmt::Debug for WgKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WgKey")
+            .field("id", &self.id)
+            .field("private_key", &Redacted)
+            .field("public_key", &self.public_key)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
+
 #[derive(Debug, sqlx::FromRow)]
 struct WgKeyRow {
     id: Uuid,
@@ -67,15 +226,12 @@ struct WgKeyRow {
     updated_at: DateTime<Utc>,
 }
 
+/// The encrypted key of a `wg_peer_psks` row; the rest is not needed.
 #[derive(Debug, sqlx::FromRow)]
 struct WgPeerPskRow {
-    id: Uuid,
-    server_id: Uuid,
     client_id: Uuid,
     psk_enc: Vec<u8>,
     psk_nonce: Vec<u8>,
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, sqlx::FromRow)]
@@ -84,7 +240,7 @@ struct MissingPskPair {
     client_id: Uuid,
 }
 
-#[derive(Debug, sqlx::FromRow)]
+#[derive(Clone, sqlx::FromRow)]
 pub struct WgServer {
     pub id: Uuid,
     pub network_id: Uuid,
@@ -93,10 +249,87 @@ pub struct WgServer {
     pub api_token: String,
     pub address_offset: i32,
     pub forwards_internet_traffic: bool,
+    /// The daemon keeps the masquerade rules for forwarded traffic.
+    pub manage_nat: bool,
+    pub fwmark: Option<i64>,
+    pub route_table: Option<i64>,
+    pub rule_priority: Option<i64>,
+    pub mtu: Option<i32>,
+    /// Overrides the network's keepalive on the server's links.
+    pub persistent_keepalive: Option<i32>,
     pub endpoint_host: Option<String>,
     pub endpoint_port: i32,
+    /// Overrides `endpoint_port` as the port the daemon listens on.
+    pub listen_port: Option<i32>,
+    /// Servers with a higher priority claim overlapping AllowedIPs first.
+    pub priority: i32,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub offline: bool,
+    /// Build the daemon reported at its last check-in, if it reported one.
+    pub daemon_version: Option<String>,
+}
+
+impl fmt::Debug for WgServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WgServer")
+            .field("id", &self.id)
+            .field("network_id", &self.network_id)
+            .field("name", &self.name)
+            .field("key_id", &self.key_id)
+            .field("api_token", &Redacted)
+            .field("address_offset", &self.address_offset)
+            .field("forwards_internet_traffic", &self.forwards_internet_traffic)
+            .field("manage_nat", &self.manage_nat)
+            .field("fwmark", &self.fwmark)
+            .field("route_table", &self.route_table)
+            .field("rule_priority", &self.rule_priority)
+            .field("mtu", &self.mtu)
+            .field("persistent_keepalive", &self.persistent_keepalive)
+            .field("endpoint_host", &self.endpoint_host)
+            .field("endpoint_port", &self.endpoint_port)
+            .field("listen_port", &self.listen_port)
+            .field("priority", &self.priority)
+            .field("tags", &self.tags)
+            .field("notes", &self.notes)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("last_seen_at", &self.last_seen_at)
+            .field("offline", &self.offline)
+            .field("daemon_version", &self.daemon_version)
+            .finish()
+    }
+}
+
+impl WgServer {
+    /// The fwmark and policy routing columns as the kernel's unsigned values.
+    pub fn policy_routing(&self) -> PolicyRouting {
+        let field = |v: Option<i64>| v.and_then(|v| u32::try_from(v).ok());
+        PolicyRouting {
+            fwmark: field(self.fwmark),
+            route_table: field(self.route_table),
+            rule_priority: field(self.rule_priority),
+        }
+    }
+
+    /// The port the daemon listens on: its own, else the one peers dial.
+    pub fn effective_listen_port(&self) -> i32 {
+        self.listen_port.unwrap_or(self.endpoint_port)
+    }
+}
+
+/// A server with the network and key details needed to show it outside the
+/// context of its network.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ServerListing {
+    #[sqlx(flatten)]
+    pub server: WgServer,
+    pub network_name: String,
+    pub address: String,
+    pub public_key: String,
 }
 
 #[derive(Debug, sqlx::FromRow)]
@@ -106,10 +339,64 @@ pub struct WgClient {
     pub name: String,
     pub key_id: Uuid,
     pub address_offset: i32,
+    pub access_schedule: Option<Json<AccessSchedule>>,
+    pub access_allowed: bool,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    pub mtu: Option<i32>,
+    /// Overrides the network's and server's keepalive on the client's links.
+    pub persistent_keepalive: Option<i32>,
+    /// Bytes the client may move per UTC month, both directions together.
+    pub monthly_quota_bytes: Option<i64>,
+    /// Set while this month's usage is over the quota; keeps the client out
+    /// of daemon configs.
+    pub quota_exceeded: bool,
+    /// Kilobits per second the client may send through a server.
+    pub upload_kbps: Option<i32>,
+    /// Kilobits per second a server may send the client.
+    pub download_kbps: Option<i32>,
+    #[sqlx(flatten)]
+    pub hooks: Hooks,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Settings written by [`VpnStore::update_server`]. `None` leaves a setting
+/// as it is; values must already be validated.
+#[derive(Debug, Default, PartialEq)]
+pub struct ServerUpdate {
+    pub notes: Option<Option<String>>,
+    pub manage_nat: Option<bool>,
+    pub policy_routing: Option<PolicyRouting>,
+    pub mtu: Option<Option<i32>>,
+    pub persistent_keepalive: Option<Option<i32>>,
+    pub listen_port: Option<Option<i32>>,
+    pub priority: Option<i32>,
+}
+
+/// Settings written by [`VpnStore::update_client`]. `None` leaves a setting
+/// as it is; values must already be validated.
+#[derive(Debug, Default, PartialEq)]
+pub struct ClientUpdate {
+    pub notes: Option<Option<String>>,
+    pub mtu: Option<Option<i32>>,
+    pub persistent_keepalive: Option<Option<i32>>,
+    pub monthly_quota_bytes: Option<Option<i64>>,
+    pub upload_kbps: Option<Option<i32>>,
+    pub download_kbps: Option<Option<i32>>,
+    pub hooks: Option<Hooks>,
+}
+
+/// A client with its network name, address, and public key joined in.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ClientListing {
+    #[sqlx(flatten)]
+    pub client: WgClient,
+    pub network_name: String,
+    pub address: String,
+    pub public_key: String,
+}
+
 #[derive(Debug, sqlx::FromRow)]
 pub struct WgServerRoute {
     pub id: Uuid,
@@ -119,6 +406,116 @@ pub struct WgServerRoute {
     pub updated_at: DateTime<Utc>,
 }
 
+/// How a [`WgClientRoute`] changes the AllowedIPs of one client's config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ClientRouteKind {
+    /// Send one more CIDR through a particular server.
+    Extra,
+    /// Route nothing but these CIDRs, and each server's own address.
+    Only,
+}
+
+/// A per-client AllowedIPs override. Only `extra` routes name a server.
+#[derive(Debug, sqlx::FromRow)]
+pub struct WgClientRoute {
+    pub id: Uuid,
+    pub client_id: Uuid,
+    pub kind: ClientRouteKind,
+    pub server_id: Option<Uuid>,
+    pub route_cidr: IpNetwork,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What a [`WgAclRule`] does with the traffic it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AclAction {
+    Allow,
+    Deny,
+}
+
+/// A forwarding rule between a network's peers. `source` and `destination`
+/// hold [`crate::acl::Selector`]s in their stored form.
+#[derive(Debug, sqlx::FromRow)]
+pub struct WgAclRule {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    /// 1-based place in the network's rules; lower is checked first.
+    pub position: i32,
+    pub action: AclAction,
+    pub source: String,
+    pub destination: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An access schedule for every client in a network carrying `tag`.
+#[derive(Debug, sqlx::FromRow)]
+pub struct TagAccessSchedule {
+    pub network_id: Uuid,
+    pub tag: String,
+    pub schedule: Json<AccessSchedule>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Returned by [`VpnStore::touch_server`].
+#[derive(Debug, sqlx::FromRow)]
+pub struct CheckIn {
+    pub was_offline: bool,
+    pub config_serial: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum SearchKind {
+    Network,
+    Server,
+    Client,
+}
+
+/// A network, server, or client matched by [`VpnStore::search`]. Networks
+/// have no address or public key of their own.
+#[derive(Debug, sqlx::FromRow)]
+pub struct SearchHit {
+    pub kind: SearchKind,
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub name: String,
+    pub address: Option<String>,
+    pub public_key: Option<String>,
+}
+
+/// An enabled network as the public status page shows it.
+#[derive(Debug, sqlx::FromRow)]
+pub struct NetworkStatus {
+    pub id: Uuid,
+    pub name: String,
+    pub clients: i64,
+}
+
+/// A server as the public status page shows it. Servers that have never
+/// checked in count as offline.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ServerStatus {
+    pub network_id: Uuid,
+    pub name: String,
+    pub online: bool,
+}
+
+/// Keys no server or client refers to, by ID: left by creates that failed
+/// after generating their key, and deletes that did not get to the key.
+/// Routes and clients cannot be orphaned; their foreign keys cascade.
+#[derive(Debug, sqlx::FromRow)]
+pub struct OrphanReport {
+    pub keys: Vec<Uuid>,
+}
+
 // ---------------------------------------------------------------------------
 // Network snapshot (for config generation)
 // ---------------------------------------------------------------------------
@@ -167,6 +564,12 @@ pub enum VpnStoreError {
     #[error("no available address offsets in this network")]
     NetworkFull,
 
+    #[error("every port in this network's range is taken on the endpoint host")]
+    PortRangeFull,
+
+    #[error("port {port} is already used by another server on this endpoint host")]
+    PortInUse { port: i32 },
+
     #[error("key encryption/decryption failed")]
     KeyEncryption,
 }
@@ -196,11 +599,23 @@ macro_rules! batch_by_ids {
 pub struct VpnStore {
     pool: PgPool,
     encryption_key: [u8; 32],
+    keys: Arc<KeyCache>,
+    tokens: Arc<ServerTokenCache>,
 }
 
 impl VpnStore {
-    pub fn new(pool: PgPool, encryption_key: [u8; 32]) -> Self {
-        Self { pool, encryption_key }
+    pub fn new(
+        pool: PgPool,
+        encryption_key: [u8; 32],
+        key_cache_capacity: usize,
+        token_cache_ttl: Duration,
+    ) -> Self {
+        Self {
+            pool,
+            encryption_key,
+            keys: Arc::new(KeyCache::new(key_cache_capacity)),
+            tokens: Arc::new(ServerTokenCache::new(token_cache_ttl)),
+        }
     }
 
     // -- Encryption helpers --------------------------------------------------
@@ -237,14 +652,18 @@ impl VpnStore {
         bytes
     }
     fn decrypt_key_row(&self, row: WgKeyRow) -> Result<WgKey> {
-        let plaintext = self.decrypt_secret(&row.private_key_enc, &row.private_key_nonce)?;
-        Ok(WgKey {
+        let plaintext = Zeroizing::new(
+            self.decrypt_secret(&row.private_key_enc, &row.private_key_nonce)?,
+        );
+        let key = WgKey {
             id: row.id,
-            private_key: BASE64.encode(&plaintext),
+            private_key: BASE64.encode(&*plaintext),
             public_key: row.public_key,
             created_at: row.created_at,
             updated_at: row.updated_at,
-        })
+        };
+        self.keys.insert(&key);
+        Ok(key)
     }
 
     // -- Network CRUD --------------------------------------------------------
@@ -271,7 +690,7 @@ impl VpnStore {
         .fetch_one(&self.pool)
         .await
         .map_err(|e| match &e {
-            sqlx::Error::Database(db_err) if db_err.constraint() == Some("networks_name_key") => {
+            sqlx::Error::Database(db_err) if db_err.constraint() == Some("networks_name_ci_key") => {
                 VpnStoreError::DuplicateNetworkName
             }
             _ => VpnStoreError::Database(e),
@@ -297,6 +716,141 @@ impl VpnStore {
         .map_err(Into::into)
     }
 
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_notes(
+        &self,
+        id: Uuid,
+        notes: Option<&str>,
+    ) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET notes = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(notes)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_port_range(
+        &self,
+        id: Uuid,
+        range: Option<RangeInclusive<i32>>,
+    ) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET port_range_start = $2, port_range_end = $3, updated_at = now()
+             WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(range.as_ref().map(|r| *r.start()))
+        .bind(range.as_ref().map(|r| *r.end()))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_endpoint_template(
+        &self,
+        id: Uuid,
+        template: Option<&str>,
+    ) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET endpoint_template = $2, updated_at = now()
+             WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(template)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_mtu(&self, id: Uuid, mtu: Option<i32>) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET mtu = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(mtu)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_excluded_cidrs(
+        &self,
+        id: Uuid,
+        excluded_cidrs: &[String],
+    ) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET excluded_cidrs = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(excluded_cidrs)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_lan_access(&self, id: Uuid, lan_access: bool) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET lan_access = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(lan_access)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_hooks(&self, id: Uuid, hooks: &Hooks) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET post_up = $2, pre_down = $3, post_down = $4, updated_at = now()
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(id)
+        .bind(&hooks.post_up)
+        .bind(&hooks.pre_down)
+        .bind(&hooks.post_down)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_search_domains(
+        &self,
+        id: Uuid,
+        search_domains: &[String],
+    ) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET search_domains = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(search_domains)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_enabled(&self, id: Uuid, enabled: bool) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET enabled = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(enabled)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn get_network(&self, id: Uuid) -> Result<Option<Network>> {
         sqlx::query_as::<_, Network>("SELECT * FROM networks WHERE id = $1")
@@ -306,10 +860,25 @@ impl VpnStore {
             .map_err(Into::into)
     }
 
+    /// One page of networks and the total number matching the filter.
     #[tracing::instrument(skip(self))]
-    pub async fn list_networks(&self) -> Result<Vec<Network>> {
-        sqlx::query_as::<_, Network>("SELECT * FROM networks ORDER BY name")
-            .fetch_all(&self.pool)
+    pub async fn page_networks(&self, opts: &ListOptions) -> Result<(Vec<Network>, i64)> {
+        const WHERE: &str = "WHERE $1::text IS NULL OR name ILIKE $1";
+        let select = format!(
+            "SELECT * FROM networks {WHERE} {} LIMIT $2 OFFSET $3",
+            opts.order_by()
+        );
+        let count = format!("SELECT count(*) FROM networks {WHERE}");
+
+        let rows = sqlx::query_as::<_, Network>(&select)
+            .bind(&opts.filter)
+            .bind(opts.limit)
+            .bind(opts.offset)
+            .fetch_all(&self.pool);
+        let total = sqlx::query_scalar::<_, i64>(&count)
+            .bind(&opts.filter)
+            .fetch_one(&self.pool);
+        futures::future::try_join(rows, total)
             .await
             .map_err(Into::into)
     }
@@ -320,6 +889,7 @@ impl VpnStore {
             .bind(id)
             .execute(&self.pool)
             .await?;
+        self.tokens.invalidate_network(id);
         Ok(())
     }
 
@@ -327,7 +897,7 @@ impl VpnStore {
 
     #[tracing::instrument(skip(self))]
     pub async fn create_key(&self) -> Result<WgKey> {
-        let secret = StaticSecret::random_from_rng(&mut OsRng);
+        let secret = StaticSecret::random_from_rng(OsRng);
         let public = PublicKey::from(&secret);
 
         let private_bytes = secret.to_bytes();
@@ -350,6 +920,9 @@ impl VpnStore {
 
     #[tracing::instrument(skip(self))]
     pub async fn get_key(&self, id: Uuid) -> Result<WgKey> {
+        if let Some(key) = self.keys.get(id) {
+            return Ok(key);
+        }
         let row = sqlx::query_as::<_, WgKeyRow>("SELECT * FROM wg_keys WHERE id = $1")
             .bind(id)
             .fetch_optional(&self.pool)
@@ -364,8 +937,11 @@ impl VpnStore {
         if ids.is_empty() {
             return Ok(HashMap::new());
         }
-        let rows: Vec<WgKeyRow> = batch_by_ids!(&self.pool, "wg_keys", WgKeyRow, ids)?;
-        let mut map = HashMap::with_capacity(rows.len());
+        let (mut map, missing) = self.keys.get_many(ids);
+        if missing.is_empty() {
+            return Ok(map);
+        }
+        let rows: Vec<WgKeyRow> = batch_by_ids!(&self.pool, "wg_keys", WgKeyRow, &missing)?;
         for row in rows {
             let id = row.id;
             let key = self.decrypt_key_row(row)?;
@@ -380,19 +956,51 @@ impl VpnStore {
             .bind(id)
             .execute(&self.pool)
             .await?;
+        self.keys.invalidate(id);
         Ok(())
     }
 
     // -- Offset allocation ---------------------------------------------------
 
+    #[tracing::instrument(skip(self))]
+    pub async fn set_network_topology(
+        &self,
+        id: Uuid,
+        topology: Topology,
+    ) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET topology = $2, updated_at = now()
+             WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(topology)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    pub async fn set_network_allocation(
+        &self,
+        id: Uuid,
+        allocation: Allocation,
+    ) -> Result<Option<Network>> {
+        sqlx::query_as::<_, Network>(
+            "UPDATE networks SET allocation = $2, updated_at = now()
+             WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(allocation)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
     async fn next_offset(&self, network_id: Uuid) -> Result<i32> {
         let network = self
             .get_network(network_id)
             .await?
             .ok_or(VpnStoreError::NetworkNotFound)?;
 
-        let max = (1i64 << (32 - network.prefix())) - 1;
-
         let used: Vec<(i32,)> = sqlx::query_as(
             "SELECT address_offset FROM wg_servers WHERE network_id = $1
              UNION
@@ -403,23 +1011,78 @@ impl VpnStore {
         .fetch_all(&self.pool)
         .await?;
 
-        let mut candidate = 1i32;
-        for (offset,) in &used {
-            if *offset != candidate {
-                break;
-            }
-            candidate += 1;
-        }
-
-        if candidate as i64 >= max {
-            return Err(VpnStoreError::NetworkFull);
-        }
-
-        Ok(candidate)
+        let usable = network.usable_addresses();
+        let used: Vec<i32> = used
+            .into_iter()
+            .map(|(offset,)| offset)
+            .filter(|&offset| offset >= 1 && i64::from(offset) <= usable)
+            .collect();
+        network
+            .allocation
+            .strategy()
+            .pick(&used, usable)
+            .ok_or(VpnStoreError::NetworkFull)
     }
 
     // -- WgServer CRUD -------------------------------------------------------
 
+    /// The listen port for a new server: the lowest in the network's range
+    /// that no server on `endpoint_host` uses, whatever its network. Without
+    /// a host, the ports of this network's servers are avoided instead, as
+    /// they may share one. A network without a range counts up from
+    /// [`DEFAULT_LISTEN_PORT`] on a named host, and otherwise always gets it.
+    #[tracing::instrument(skip(self))]
+    pub async fn next_port(&self, network_id: Uuid, endpoint_host: Option<&str>) -> Result<i32> {
+        let network = self
+            .get_network(network_id)
+            .await?
+            .ok_or(VpnStoreError::NetworkNotFound)?;
+        let range = match (network.port_range(), endpoint_host) {
+            (Some(range), _) => range,
+            (None, Some(_)) => DEFAULT_LISTEN_PORT..=MAX_PORT,
+            (None, None) => return Ok(DEFAULT_LISTEN_PORT),
+        };
+
+        let used: Vec<i32> = sqlx::query_scalar(
+            "SELECT endpoint_port FROM wg_servers
+             WHERE lower(endpoint_host) = lower($2) OR ($2 IS NULL AND network_id = $1)",
+        )
+        .bind(network_id)
+        .bind(endpoint_host)
+        .fetch_all(&self.pool)
+        .await?;
+
+        first_free_port(range, &used).ok_or(VpnStoreError::PortRangeFull)
+    }
+
+    /// Fail with [`VpnStoreError::PortInUse`] if a server other than
+    /// `except` listens on `port` at `endpoint_host`. Hosts compare
+    /// case-insensitively, whatever network the servers are in. Writes that
+    /// race past this check run into [`ENDPOINT_KEY`].
+    async fn check_port_free(
+        &self,
+        endpoint_host: &str,
+        port: i32,
+        except: Option<Uuid>,
+    ) -> Result<()> {
+        let taken: bool = sqlx::query_scalar(
+            "SELECT EXISTS (
+                 SELECT 1 FROM wg_servers
+                 WHERE lower(endpoint_host) = lower($1) AND endpoint_port = $2
+                   AND id IS DISTINCT FROM $3
+             )",
+        )
+        .bind(endpoint_host)
+        .bind(port)
+        .bind(except)
+        .fetch_one(&self.pool)
+        .await?;
+        if taken {
+            return Err(VpnStoreError::PortInUse { port });
+        }
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn create_server(
         &self,
@@ -430,6 +1093,9 @@ impl VpnStore {
         endpoint_host: Option<&str>,
         endpoint_port: i32,
     ) -> Result<WgServer> {
+        if let Some(host) = endpoint_host {
+            self.check_port_free(host, endpoint_port, None).await?;
+        }
         let address_offset = self.next_offset(network_id).await?;
 
         let api_token = Uuid::new_v4().to_string();
@@ -452,10 +1118,11 @@ impl VpnStore {
         .map_err(|e| match &e {
             sqlx::Error::Database(db_err) => {
                 match db_err.constraint() {
-                    Some("wg_servers_network_id_name_key") => VpnStoreError::DuplicateName,
+                    Some("wg_servers_network_id_name_ci_key") => VpnStoreError::DuplicateName,
                     Some("wg_servers_network_id_address_offset_key") => {
                         VpnStoreError::AddressOffsetConflict { offset: address_offset }
                     }
+                    Some(ENDPOINT_KEY) => VpnStoreError::PortInUse { port: endpoint_port },
                     _ => VpnStoreError::Database(e),
                 }
             }
@@ -472,19 +1139,44 @@ impl VpnStore {
             .map_err(Into::into)
     }
 
+    /// Authenticate a daemon token. Served from a short-TTL, per-process
+    /// cache, when enabled, that the server-mutating methods below
+    /// invalidate.
     #[tracing::instrument(skip(self, api_token))]
     pub async fn get_server_by_token(&self, api_token: &str) -> Result<Option<WgServer>> {
-        sqlx::query_as::<_, WgServer>("SELECT * FROM wg_servers WHERE api_token = $1")
-            .bind(api_token)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(Into::into)
+        if let Some(server) = self.tokens.get(api_token) {
+            return Ok(Some(server));
+        }
+        let generation = self.tokens.generation();
+        let server =
+            sqlx::query_as::<_, WgServer>("SELECT * FROM wg_servers WHERE api_token = $1")
+                .bind(api_token)
+                .fetch_optional(&self.pool)
+                .await?;
+        if let Some(server) = &server {
+            self.tokens.insert(server, generation);
+        }
+        Ok(server)
+    }
+
+    /// Replace the server's API token, revoking the old one immediately.
+    #[tracing::instrument(skip(self))]
+    pub async fn rotate_server_token(&self, id: Uuid) -> Result<Option<WgServer>> {
+        let server = sqlx::query_as::<_, WgServer>(
+            "UPDATE wg_servers SET api_token = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(Uuid::new_v4().to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        self.tokens.invalidate_server(id);
+        Ok(server)
     }
 
     #[tracing::instrument(skip(self))]
     pub async fn list_servers_by_network(&self, network_id: Uuid) -> Result<Vec<WgServer>> {
         sqlx::query_as::<_, WgServer>(
-            "SELECT * FROM wg_servers WHERE network_id = $1 ORDER BY created_at",
+            "SELECT * FROM wg_servers WHERE network_id = $1 ORDER BY priority DESC, created_at",
         )
         .bind(network_id)
         .fetch_all(&self.pool)
@@ -492,15 +1184,221 @@ impl VpnStore {
         .map_err(Into::into)
     }
 
+    #[tracing::instrument(skip(self))]
+    pub async fn page_servers(
+        &self,
+        network_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<(Vec<WgServer>, i64)> {
+        const WHERE: &str = "WHERE network_id = $1 AND ($2::text IS NULL OR name ILIKE $2)
+                             AND tags @> $3";
+        let select = format!(
+            "SELECT * FROM wg_servers {WHERE} {} LIMIT $4 OFFSET $5",
+            opts.order_by()
+        );
+        let count = format!("SELECT count(*) FROM wg_servers {WHERE}");
+
+        let rows = sqlx::query_as::<_, WgServer>(&select)
+            .bind(network_id)
+            .bind(&opts.filter)
+            .bind(&opts.tags)
+            .bind(opts.limit)
+            .bind(opts.offset)
+            .fetch_all(&self.pool);
+        let total = sqlx::query_scalar::<_, i64>(&count)
+            .bind(network_id)
+            .bind(&opts.filter)
+            .bind(&opts.tags)
+            .fetch_one(&self.pool);
+        futures::future::try_join(rows, total)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Servers across every network, with network name, address, and public
+    /// key joined in so callers need no per-network follow-up queries.
+    #[tracing::instrument(skip(self))]
+    pub async fn page_all_servers(&self, opts: &ListOptions) -> Result<(Vec<ServerListing>, i64)> {
+        // Wrapped in a subquery so the shared ORDER BY resolves unambiguously.
+        const FROM: &str = "FROM (
+                SELECT s.*, n.name AS network_name,
+                       host(n.cidr_ip + s.address_offset::bigint) AS address, k.public_key
+                FROM wg_servers s
+                JOIN networks n ON n.id = s.network_id
+                JOIN wg_keys k ON k.id = s.key_id
+            ) listing
+            WHERE ($1::text IS NULL OR name ILIKE $1) AND tags @> $2";
+        let select = format!("SELECT * {FROM} {} LIMIT $3 OFFSET $4", opts.order_by());
+        let count = format!("SELECT count(*) {FROM}");
+
+        let rows = sqlx::query_as::<_, ServerListing>(&select)
+            .bind(&opts.filter)
+            .bind(&opts.tags)
+            .bind(opts.limit)
+            .bind(opts.offset)
+            .fetch_all(&self.pool);
+        let total = sqlx::query_scalar::<_, i64>(&count)
+            .bind(&opts.filter)
+            .bind(&opts.tags)
+            .fetch_one(&self.pool);
+        futures::future::try_join(rows, total)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Apply every setting in `update` in one statement, so a rejected or
+    /// failed update changes nothing.
+    #[tracing::instrument(skip(self))]
+    pub async fn update_server(&self, id: Uuid, update: &ServerUpdate) -> Result<Option<WgServer>> {
+        let mut query = QueryBuilder::<Postgres>::new("UPDATE wg_servers SET updated_at = now()");
+        if let Some(notes) = &update.notes {
+            query.push(", notes = ").push_bind(notes);
+        }
+        if let Some(manage_nat) = update.manage_nat {
+            query.push(", manage_nat = ").push_bind(manage_nat);
+        }
+        if let Some(routing) = update.policy_routing {
+            query
+                .push(", fwmark = ")
+                .push_bind(routing.fwmark.map(i64::from))
+                .push(", route_table = ")
+                .push_bind(routing.route_table.map(i64::from))
+                .push(", rule_priority = ")
+                .push_bind(routing.rule_priority.map(i64::from));
+        }
+        if let Some(mtu) = update.mtu {
+            query.push(", mtu = ").push_bind(mtu);
+        }
+        if let Some(keepalive) = update.persistent_keepalive {
+            query.push(", persistent_keepalive = ").push_bind(keepalive);
+        }
+        if let Some(port) = update.listen_port {
+            query.push(", listen_port = ").push_bind(port);
+        }
+        if let Some(priority) = update.priority {
+            query.push(", priority = ").push_bind(priority);
+        }
+        query
+            .push(" WHERE id = ")
+            .push_bind(id)
+            .push(" RETURNING *");
+        let server = query
+            .build_query_as::<WgServer>()
+            .fetch_optional(&self.pool)
+            .await?;
+        self.tokens.invalidate_server(id);
+        Ok(server)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_server_tags(&self, id: Uuid, tags: &[String]) -> Result<Option<WgServer>> {
+        let server = sqlx::query_as::<_, WgServer>(
+            "UPDATE wg_servers SET tags = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(tags)
+        .fetch_optional(&self.pool)
+        .await?;
+        self.tokens.invalidate_server(id);
+        Ok(server)
+    }
+
+    /// Point `server_id` at a new public endpoint. Returns `None` when the
+    /// endpoint is unchanged.
+    #[tracing::instrument(skip(self))]
+    pub async fn set_server_endpoint(
+        &self,
+        server_id: Uuid,
+        endpoint_host: &str,
+    ) -> Result<Option<WgServer>> {
+        let port: Option<i32> =
+            sqlx::query_scalar("SELECT endpoint_port FROM wg_servers WHERE id = $1")
+                .bind(server_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        let Some(port) = port else {
+            return Ok(None);
+        };
+        self.check_port_free(endpoint_host, port, Some(server_id))
+            .await?;
+        let server = sqlx::query_as::<_, WgServer>(
+            "UPDATE wg_servers SET endpoint_host = $2, updated_at = now()
+             WHERE id = $1 AND endpoint_host IS DISTINCT FROM $2
+             RETURNING *",
+        )
+        .bind(server_id)
+        .bind(endpoint_host)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_err) if db_err.constraint() == Some(ENDPOINT_KEY) => {
+                VpnStoreError::PortInUse { port }
+            }
+            _ => VpnStoreError::Database(e),
+        })?;
+        self.tokens.invalidate_server(server_id);
+        Ok(server)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn delete_server(&self, id: Uuid) -> Result<()> {
         sqlx::query("DELETE FROM wg_servers WHERE id = $1")
             .bind(id)
             .execute(&self.pool)
             .await?;
+        self.tokens.invalidate_server(id);
         Ok(())
     }
 
+    /// Record a daemon check-in, returning the server's previous offline
+    /// state and its network's current config serial.
+    #[tracing::instrument(skip(self))]
+    pub async fn touch_server(
+        &self,
+        id: Uuid,
+        daemon_version: Option<&str>,
+    ) -> Result<Option<CheckIn>> {
+        sqlx::query_as::<_, CheckIn>(
+            "UPDATE wg_servers s SET last_seen_at = now(), offline = false,
+                 daemon_version = COALESCE($2, s.daemon_version)
+             FROM (SELECT offline FROM wg_servers WHERE id = $1) prev, networks n
+             WHERE s.id = $1 AND n.id = s.network_id
+             RETURNING prev.offline AS was_offline, n.config_serial",
+        )
+        .bind(id)
+        .bind(daemon_version)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Mark a server offline, returning whether it was online before.
+    #[tracing::instrument(skip(self))]
+    pub async fn mark_server_offline(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE wg_servers SET offline = true WHERE id = $1 AND NOT offline",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mark servers that have not checked in for `after_secs` as offline,
+    /// returning only those that just transitioned.
+    #[tracing::instrument(skip(self))]
+    pub async fn mark_offline_servers(&self, after_secs: i64) -> Result<Vec<WgServer>> {
+        sqlx::query_as::<_, WgServer>(
+            "UPDATE wg_servers SET offline = true
+             WHERE NOT offline AND last_seen_at < now() - make_interval(secs => $1)
+             RETURNING *",
+        )
+        .bind(after_secs as f64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
     // -- WgClient CRUD -------------------------------------------------------
 
     #[tracing::instrument(skip(self))]
@@ -523,18 +1421,41 @@ impl VpnStore {
         .bind(address_offset)
         .fetch_one(&self.pool)
         .await
-        .map_err(|e| match &e {
-            sqlx::Error::Database(db_err) => {
-                match db_err.constraint() {
-                    Some("wg_clients_network_id_name_key") => VpnStoreError::DuplicateName,
-                    Some("wg_clients_network_id_address_offset_key") => {
-                        VpnStoreError::AddressOffsetConflict { offset: address_offset }
-                    }
-                    _ => VpnStoreError::Database(e),
-                }
-            }
-            _ => VpnStoreError::Database(e),
-        })
+        .map_err(|e| client_conflict(e, address_offset))
+    }
+
+    /// Move a client, keys and all, to `network_id` at the lowest free
+    /// address there. Its preshared keys with the old network's servers go
+    /// with the old membership; the new network's are created on demand.
+    #[tracing::instrument(skip(self))]
+    pub async fn move_client(&self, id: Uuid, network_id: Uuid) -> Result<Option<WgClient>> {
+        let address_offset = self.next_offset(network_id).await?;
+
+        let mut tx = self.pool.begin().await?;
+        let client = sqlx::query_as::<_, WgClient>(
+            "UPDATE wg_clients SET network_id = $2, address_offset = $3, updated_at = now()
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(id)
+        .bind(network_id)
+        .bind(address_offset)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| client_conflict(e, address_offset))?;
+        if client.is_some() {
+            sqlx::query("DELETE FROM wg_peer_psks WHERE client_id = $1")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            // Extra routes name servers of the old network.
+            sqlx::query("DELETE FROM wg_client_routes WHERE client_id = $1 AND server_id IS NOT NULL")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(client)
     }
 
     #[tracing::instrument(skip(self))]
@@ -557,6 +1478,225 @@ impl VpnStore {
         .map_err(Into::into)
     }
 
+    #[tracing::instrument(skip(self))]
+    pub async fn page_clients(
+        &self,
+        network_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<(Vec<WgClient>, i64)> {
+        const WHERE: &str = "WHERE network_id = $1 AND ($2::text IS NULL OR name ILIKE $2)
+                             AND tags @> $3";
+        let select = format!(
+            "SELECT * FROM wg_clients {WHERE} {} LIMIT $4 OFFSET $5",
+            opts.order_by()
+        );
+        let count = format!("SELECT count(*) FROM wg_clients {WHERE}");
+
+        let rows = sqlx::query_as::<_, WgClient>(&select)
+            .bind(network_id)
+            .bind(&opts.filter)
+            .bind(&opts.tags)
+            .bind(opts.limit)
+            .bind(opts.offset)
+            .fetch_all(&self.pool);
+        let total = sqlx::query_scalar::<_, i64>(&count)
+            .bind(network_id)
+            .bind(&opts.filter)
+            .bind(&opts.tags)
+            .fetch_one(&self.pool);
+        futures::future::try_join(rows, total)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Clients across every network; see [`VpnStore::page_all_servers`].
+    #[tracing::instrument(skip(self))]
+    pub async fn page_all_clients(&self, opts: &ListOptions) -> Result<(Vec<ClientListing>, i64)> {
+        const FROM: &str = "FROM (
+                SELECT c.*, n.name AS network_name,
+                       host(n.cidr_ip + c.address_offset::bigint) AS address, k.public_key
+                FROM wg_clients c
+                JOIN networks n ON n.id = c.network_id
+                JOIN wg_keys k ON k.id = c.key_id
+            ) listing
+            WHERE ($1::text IS NULL OR name ILIKE $1) AND tags @> $2";
+        let select = format!("SELECT * {FROM} {} LIMIT $3 OFFSET $4", opts.order_by());
+        let count = format!("SELECT count(*) {FROM}");
+
+        let rows = sqlx::query_as::<_, ClientListing>(&select)
+            .bind(&opts.filter)
+            .bind(&opts.tags)
+            .bind(opts.limit)
+            .bind(opts.offset)
+            .fetch_all(&self.pool);
+        let total = sqlx::query_scalar::<_, i64>(&count)
+            .bind(&opts.filter)
+            .bind(&opts.tags)
+            .fetch_one(&self.pool);
+        futures::future::try_join(rows, total)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Set or clear a client's access schedule, along with whether access is
+    /// currently allowed under it.
+    #[tracing::instrument(skip(self))]
+    pub async fn set_client_access_schedule(
+        &self,
+        id: Uuid,
+        schedule: Option<&AccessSchedule>,
+        allowed: bool,
+    ) -> Result<Option<WgClient>> {
+        sqlx::query_as::<_, WgClient>(
+            "UPDATE wg_clients SET access_schedule = $2, access_allowed = $3, updated_at = now()
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(id)
+        .bind(schedule.map(Json))
+        .bind(allowed)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Apply every setting in `update` in one statement, so a rejected or
+    /// failed update changes nothing. Whether a new quota puts the client
+    /// over it is left to
+    /// [`UsageStore::sync_quotas`](crate::db::usage::UsageStore::sync_quotas).
+    #[tracing::instrument(skip(self))]
+    pub async fn update_client(&self, id: Uuid, update: &ClientUpdate) -> Result<Option<WgClient>> {
+        let mut query = QueryBuilder::<Postgres>::new("UPDATE wg_clients SET updated_at = now()");
+        if let Some(notes) = &update.notes {
+            query.push(", notes = ").push_bind(notes);
+        }
+        if let Some(mtu) = update.mtu {
+            query.push(", mtu = ").push_bind(mtu);
+        }
+        if let Some(keepalive) = update.persistent_keepalive {
+            query.push(", persistent_keepalive = ").push_bind(keepalive);
+        }
+        if let Some(quota) = update.monthly_quota_bytes {
+            query.push(", monthly_quota_bytes = ").push_bind(quota);
+        }
+        if let Some(upload) = update.upload_kbps {
+            query.push(", upload_kbps = ").push_bind(upload);
+        }
+        if let Some(download) = update.download_kbps {
+            query.push(", download_kbps = ").push_bind(download);
+        }
+        if let Some(hooks) = &update.hooks {
+            query
+                .push(", post_up = ")
+                .push_bind(&hooks.post_up)
+                .push(", pre_down = ")
+                .push_bind(&hooks.pre_down)
+                .push(", post_down = ")
+                .push_bind(&hooks.post_down);
+        }
+        query
+            .push(" WHERE id = ")
+            .push_bind(id)
+            .push(" RETURNING *");
+        query
+            .build_query_as::<WgClient>()
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_client_tags(&self, id: Uuid, tags: &[String]) -> Result<Option<WgClient>> {
+        sqlx::query_as::<_, WgClient>(
+            "UPDATE wg_clients SET tags = $2, updated_at = now() WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(tags)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Clients whose access a schedule decides: those with their own, those
+    /// carrying a scheduled tag, and those shut out by a schedule that has
+    /// since been removed.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_scheduled_clients(&self) -> Result<Vec<WgClient>> {
+        sqlx::query_as::<_, WgClient>(
+            "SELECT * FROM wg_clients c
+             WHERE access_schedule IS NOT NULL
+                OR NOT access_allowed
+                OR EXISTS (
+                    SELECT 1 FROM tag_access_schedules t
+                    WHERE t.network_id = c.network_id AND t.tag = ANY(c.tags)
+                )",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Tag schedules in `network_id`, or in every network.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_tag_access_schedules(
+        &self,
+        network_id: Option<Uuid>,
+    ) -> Result<Vec<TagAccessSchedule>> {
+        sqlx::query_as::<_, TagAccessSchedule>(
+            "SELECT * FROM tag_access_schedules
+             WHERE $1::uuid IS NULL OR network_id = $1
+             ORDER BY network_id, tag",
+        )
+        .bind(network_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_tag_access_schedule(
+        &self,
+        network_id: Uuid,
+        tag: &str,
+        schedule: &AccessSchedule,
+    ) -> Result<TagAccessSchedule> {
+        sqlx::query_as::<_, TagAccessSchedule>(
+            "INSERT INTO tag_access_schedules (network_id, tag, schedule)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (network_id, tag)
+             DO UPDATE SET schedule = EXCLUDED.schedule, updated_at = now()
+             RETURNING *",
+        )
+        .bind(network_id)
+        .bind(tag)
+        .bind(Json(schedule))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Remove a tag schedule, returning whether there was one.
+    #[tracing::instrument(skip(self))]
+    pub async fn clear_tag_access_schedule(&self, network_id: Uuid, tag: &str) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM tag_access_schedules WHERE network_id = $1 AND tag = $2")
+                .bind(network_id)
+                .bind(tag)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_client_access_allowed(&self, id: Uuid, allowed: bool) -> Result<()> {
+        sqlx::query("UPDATE wg_clients SET access_allowed = $2 WHERE id = $1")
+            .bind(id)
+            .bind(allowed)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn delete_client(&self, id: Uuid) -> Result<()> {
         sqlx::query("DELETE FROM wg_clients WHERE id = $1")
@@ -647,65 +1787,321 @@ impl VpnStore {
         self.decrypt_psk_row(row)
     }
 
-    #[tracing::instrument(skip(self))]
-    pub async fn rotate_psks_for_client(
+    /// As [`ensure_psk`](Self::ensure_psk) for every client in `client_ids`
+    /// at once: one query loads the existing keys and one inserts the missing.
+    #[tracing::instrument(skip(self, client_ids), fields(clients = client_ids.len()))]
+    pub async fn ensure_psks(
         &self,
-        client_id: Uuid,
-        server_ids: &[Uuid],
-    ) -> Result<()> {
-        for server_id in server_ids {
-            let psk = Self::generate_psk();
-            let (enc, nonce) = self.encrypt_secret(&psk)?;
+        server_id: Uuid,
+        client_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, String>> {
+        let mut rows = self.get_psk_rows(server_id, client_ids).await?;
+        let found: HashSet<Uuid> = rows.iter().map(|row| row.client_id).collect();
+        let missing: Vec<Uuid> = client_ids
+            .iter()
+            .copied()
+            .filter(|id| !found.contains(id))
+            .collect();
+        if !missing.is_empty() {
+            let mut encs = Vec::with_capacity(missing.len());
+            let mut nonces = Vec::with_capacity(missing.len());
+            for _ in &missing {
+                let (enc, nonce) = self.encrypt_secret(&Self::generate_psk())?;
+                encs.push(enc);
+                nonces.push(nonce);
+            }
             sqlx::query(
                 "INSERT INTO wg_peer_psks (server_id, client_id, psk_enc, psk_nonce)
-                 VALUES ($1, $2, $3, $4)
-                 ON CONFLICT (server_id, client_id)
-                 DO UPDATE SET psk_enc = $3, psk_nonce = $4, updated_at = now()",
+                 SELECT $1, * FROM UNNEST($2::uuid[], $3::bytea[], $4::bytea[])
+                 ON CONFLICT (server_id, client_id) DO NOTHING",
             )
             .bind(server_id)
-            .bind(client_id)
-            .bind(&enc)
-            .bind(&nonce)
+            .bind(&missing)
+            .bind(&encs)
+            .bind(&nonces)
             .execute(&self.pool)
             .await?;
+            // Read back rather than trust our keys: a concurrent call may
+            // have inserted some of them first.
+            rows.extend(self.get_psk_rows(server_id, &missing).await?);
         }
+        rows.into_iter()
+            .map(|row| Ok((row.client_id, self.decrypt_psk_row(row)?)))
+            .collect()
+    }
+
+    #[tracing::instrument(skip(self, client_ids))]
+    async fn get_psk_rows(
+        &self,
+        server_id: Uuid,
+        client_ids: &[Uuid],
+    ) -> Result<Vec<WgPeerPskRow>> {
+        sqlx::query_as::<_, WgPeerPskRow>(
+            "SELECT * FROM wg_peer_psks WHERE server_id = $1 AND client_id = ANY($2)",
+        )
+        .bind(server_id)
+        .bind(client_ids)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Replace the preshared key of every pair of `client_ids` and
+    /// `server_ids` in one statement, so a failure leaves none replaced.
+    #[tracing::instrument(skip(self))]
+    pub async fn rotate_psks(&self, client_ids: &[Uuid], server_ids: &[Uuid]) -> Result<()> {
+        let pairs = client_ids.len() * server_ids.len();
+        let mut servers = Vec::with_capacity(pairs);
+        let mut clients = Vec::with_capacity(pairs);
+        let mut encs = Vec::with_capacity(pairs);
+        let mut nonces = Vec::with_capacity(pairs);
+        for &client_id in client_ids {
+            for &server_id in server_ids {
+                let (enc, nonce) = self.encrypt_secret(&Self::generate_psk())?;
+                servers.push(server_id);
+                clients.push(client_id);
+                encs.push(enc);
+                nonces.push(nonce);
+            }
+        }
+        sqlx::query(
+            "INSERT INTO wg_peer_psks (server_id, client_id, psk_enc, psk_nonce)
+             SELECT * FROM UNNEST($1::uuid[], $2::uuid[], $3::bytea[], $4::bytea[])
+             ON CONFLICT (server_id, client_id)
+             DO UPDATE SET psk_enc = EXCLUDED.psk_enc, psk_nonce = EXCLUDED.psk_nonce,
+                           updated_at = now()",
+        )
+        .bind(&servers)
+        .bind(&clients)
+        .bind(&encs)
+        .bind(&nonces)
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
-    // -- WgServerRoute CRUD --------------------------------------------------
-
+    // -- WgServerRoute CRUD --------------------------------------------------
+
+    #[tracing::instrument(skip(self))]
+    pub async fn add_route(&self, server_id: Uuid, route_cidr: IpNetwork) -> Result<WgServerRoute> {
+        sqlx::query_as::<_, WgServerRoute>(
+            "INSERT INTO wg_server_routes (server_id, route_cidr)
+             VALUES ($1, $2)
+             RETURNING *",
+        )
+        .bind(server_id)
+        .bind(route_cidr)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list_routes_by_server(&self, server_id: Uuid) -> Result<Vec<WgServerRoute>> {
+        sqlx::query_as::<_, WgServerRoute>(
+            "SELECT * FROM wg_server_routes WHERE server_id = $1 ORDER BY route_cidr",
+        )
+        .bind(server_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Routes are filtered by CIDR text rather than name.
+    #[tracing::instrument(skip(self))]
+    pub async fn page_routes(
+        &self,
+        server_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<(Vec<WgServerRoute>, i64)> {
+        const WHERE: &str =
+            "WHERE server_id = $1 AND ($2::text IS NULL OR route_cidr::text ILIKE $2)";
+        let select = format!(
+            "SELECT * FROM wg_server_routes {WHERE} {} LIMIT $3 OFFSET $4",
+            opts.order_by()
+        );
+        let count = format!("SELECT count(*) FROM wg_server_routes {WHERE}");
+
+        let rows = sqlx::query_as::<_, WgServerRoute>(&select)
+            .bind(server_id)
+            .bind(&opts.filter)
+            .bind(opts.limit)
+            .bind(opts.offset)
+            .fetch_all(&self.pool);
+        let total = sqlx::query_scalar::<_, i64>(&count)
+            .bind(server_id)
+            .bind(&opts.filter)
+            .fetch_one(&self.pool);
+        futures::future::try_join(rows, total)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_route(&self, id: Uuid) -> Result<Option<WgServerRoute>> {
+        sqlx::query_as::<_, WgServerRoute>(
+            "DELETE FROM wg_server_routes WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    // -- WgClientRoute CRUD --------------------------------------------------
+
+    #[tracing::instrument(skip(self))]
+    pub async fn add_client_route(
+        &self,
+        client_id: Uuid,
+        kind: ClientRouteKind,
+        server_id: Option<Uuid>,
+        route_cidr: IpNetwork,
+    ) -> Result<WgClientRoute> {
+        sqlx::query_as::<_, WgClientRoute>(
+            "INSERT INTO wg_client_routes (client_id, kind, server_id, route_cidr)
+             VALUES ($1, $2, $3, $4)
+             RETURNING *",
+        )
+        .bind(client_id)
+        .bind(kind)
+        .bind(server_id)
+        .bind(route_cidr)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list_routes_by_client(&self, client_id: Uuid) -> Result<Vec<WgClientRoute>> {
+        sqlx::query_as::<_, WgClientRoute>(
+            "SELECT * FROM wg_client_routes WHERE client_id = $1 ORDER BY route_cidr",
+        )
+        .bind(client_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Like [`Self::page_routes`], filtered by CIDR text.
+    #[tracing::instrument(skip(self))]
+    pub async fn page_client_routes(
+        &self,
+        client_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<(Vec<WgClientRoute>, i64)> {
+        const WHERE: &str =
+            "WHERE client_id = $1 AND ($2::text IS NULL OR route_cidr::text ILIKE $2)";
+        let select = format!(
+            "SELECT * FROM wg_client_routes {WHERE} {} LIMIT $3 OFFSET $4",
+            opts.order_by()
+        );
+        let count = format!("SELECT count(*) FROM wg_client_routes {WHERE}");
+
+        let rows = sqlx::query_as::<_, WgClientRoute>(&select)
+            .bind(client_id)
+            .bind(&opts.filter)
+            .bind(opts.limit)
+            .bind(opts.offset)
+            .fetch_all(&self.pool);
+        let total = sqlx::query_scalar::<_, i64>(&count)
+            .bind(client_id)
+            .bind(&opts.filter)
+            .fetch_one(&self.pool);
+        futures::future::try_join(rows, total)
+            .await
+            .map_err(Into::into)
+    }
+
     #[tracing::instrument(skip(self))]
-    pub async fn add_route(&self, server_id: Uuid, route_cidr: IpNetwork) -> Result<WgServerRoute> {
-        sqlx::query_as::<_, WgServerRoute>(
-            "INSERT INTO wg_server_routes (server_id, route_cidr)
-             VALUES ($1, $2)
-             RETURNING *",
+    pub async fn delete_client_route(&self, id: Uuid) -> Result<Option<WgClientRoute>> {
+        sqlx::query_as::<_, WgClientRoute>(
+            "DELETE FROM wg_client_routes WHERE id = $1 RETURNING *",
         )
-        .bind(server_id)
-        .bind(route_cidr)
-        .fetch_one(&self.pool)
+        .bind(id)
+        .fetch_optional(&self.pool)
         .await
         .map_err(Into::into)
     }
 
+    // -- WgAclRule CRUD ------------------------------------------------------
+
+    /// Insert a rule at `position`, moving the rules from there down one,
+    /// or with `None` after the last. Positions past the end append.
     #[tracing::instrument(skip(self))]
-    pub async fn list_routes_by_server(&self, server_id: Uuid) -> Result<Vec<WgServerRoute>> {
-        sqlx::query_as::<_, WgServerRoute>(
-            "SELECT * FROM wg_server_routes WHERE server_id = $1 ORDER BY route_cidr",
+    pub async fn add_acl_rule(
+        &self,
+        network_id: Uuid,
+        position: Option<i32>,
+        action: AclAction,
+        source: &str,
+        destination: &str,
+    ) -> Result<WgAclRule> {
+        let mut tx = self.pool.begin().await?;
+        let next: i32 = sqlx::query_scalar(
+            "SELECT COALESCE(max(position), 0) + 1 FROM wg_acl_rules WHERE network_id = $1",
         )
-        .bind(server_id)
+        .bind(network_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let position = position.map_or(next, |p| p.clamp(1, next));
+        sqlx::query(
+            "UPDATE wg_acl_rules SET position = position + 1, updated_at = now()
+             WHERE network_id = $1 AND position >= $2",
+        )
+        .bind(network_id)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+        let rule = sqlx::query_as::<_, WgAclRule>(
+            "INSERT INTO wg_acl_rules (network_id, position, action, source, destination)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING *",
+        )
+        .bind(network_id)
+        .bind(position)
+        .bind(action)
+        .bind(source)
+        .bind(destination)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(rule)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list_acl_rules_by_network(&self, network_id: Uuid) -> Result<Vec<WgAclRule>> {
+        sqlx::query_as::<_, WgAclRule>(
+            "SELECT * FROM wg_acl_rules WHERE network_id = $1 ORDER BY position",
+        )
+        .bind(network_id)
         .fetch_all(&self.pool)
         .await
         .map_err(Into::into)
     }
 
+    /// Delete a rule, moving the rules after it up one.
     #[tracing::instrument(skip(self))]
-    pub async fn delete_route(&self, id: Uuid) -> Result<()> {
-        sqlx::query("DELETE FROM wg_server_routes WHERE id = $1")
-            .bind(id)
-            .execute(&self.pool)
+    pub async fn delete_acl_rule(&self, id: Uuid) -> Result<Option<WgAclRule>> {
+        let mut tx = self.pool.begin().await?;
+        let rule = sqlx::query_as::<_, WgAclRule>(
+            "DELETE FROM wg_acl_rules WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(rule) = &rule {
+            sqlx::query(
+                "UPDATE wg_acl_rules SET position = position - 1, updated_at = now()
+                 WHERE network_id = $1 AND position > $2",
+            )
+            .bind(rule.network_id)
+            .bind(rule.position)
+            .execute(&mut *tx)
             .await?;
-        Ok(())
+        }
+        tx.commit().await?;
+        Ok(rule)
     }
 
     // -- Network snapshot ----------------------------------------------------
@@ -719,14 +2115,14 @@ impl VpnStore {
 
         let servers = self.list_servers_by_network(network_id).await?;
 
-        let mut keys = HashMap::new();
+        let key_ids: Vec<_> = servers.iter().map(|s| s.key_id).collect();
+        let keys = self.get_keys_batch(&key_ids).await?;
+        if !key_ids.iter().all(|id| keys.contains_key(id)) {
+            return Err(VpnStoreError::KeyNotFound);
+        }
         let mut server_routes = HashMap::new();
 
         for server in &servers {
-            if !keys.contains_key(&server.key_id) {
-                let key = self.get_key(server.key_id).await?;
-                keys.insert(key.id, key);
-            }
             let routes = self.list_routes_by_server(server.id).await?;
             server_routes.insert(server.id, routes);
         }
@@ -738,42 +2134,186 @@ impl VpnStore {
             server_routes,
         })
     }
+
+    /// The server or client using `public_key`, if any.
+    #[tracing::instrument(skip(self))]
+    pub async fn find_public_key_owner(&self, public_key: &str) -> Result<Option<SearchHit>> {
+        sqlx::query_as::<_, SearchHit>(
+            "SELECT 'server' AS kind, s.id, s.network_id, s.name,
+                    host(n.cidr_ip + s.address_offset::bigint) AS address, k.public_key
+             FROM wg_servers s
+             JOIN networks n ON n.id = s.network_id
+             JOIN wg_keys k ON k.id = s.key_id
+             WHERE k.public_key = $1
+             UNION ALL
+             SELECT 'client', c.id, c.network_id, c.name,
+                    host(n.cidr_ip + c.address_offset::bigint), k.public_key
+             FROM wg_clients c
+             JOIN networks n ON n.id = c.network_id
+             JOIN wg_keys k ON k.id = c.key_id
+             WHERE k.public_key = $1
+             LIMIT 1",
+        )
+        .bind(public_key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Match `query` against resource names (substring), public keys
+    /// (prefix), and, when `ip` is given, peer addresses and the networks
+    /// containing it.
+    #[tracing::instrument(skip(self))]
+    pub async fn search(
+        &self,
+        query: &str,
+        ip: Option<IpNetwork>,
+        limit: i64,
+    ) -> Result<Vec<SearchHit>> {
+        let escaped = escape_like(query);
+        sqlx::query_as::<_, SearchHit>(
+            "SELECT 'network' AS kind, n.id, n.id AS network_id, n.name,
+                    NULL::text AS address, NULL::text AS public_key
+             FROM networks n
+             WHERE n.name ILIKE $1 OR n.cidr_ip >>= $3
+             UNION ALL
+             SELECT 'server', s.id, s.network_id, s.name,
+                    host(n.cidr_ip + s.address_offset::bigint), k.public_key
+             FROM wg_servers s
+             JOIN networks n ON n.id = s.network_id
+             JOIN wg_keys k ON k.id = s.key_id
+             WHERE s.name ILIKE $1 OR k.public_key LIKE $2
+                OR host(n.cidr_ip + s.address_offset::bigint) = host($3)
+             UNION ALL
+             SELECT 'client', c.id, c.network_id, c.name,
+                    host(n.cidr_ip + c.address_offset::bigint), k.public_key
+             FROM wg_clients c
+             JOIN networks n ON n.id = c.network_id
+             JOIN wg_keys k ON k.id = c.key_id
+             WHERE c.name ILIKE $1 OR k.public_key LIKE $2
+                OR host(n.cidr_ip + c.address_offset::bigint) = host($3)
+             ORDER BY kind, name, id
+             LIMIT $4",
+        )
+        .bind(format!("%{escaped}%"))
+        .bind(format!("{escaped}%"))
+        .bind(ip)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    // -- Status page ---------------------------------------------------------
+
+    /// Enabled networks with their client counts, by name.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_network_status(&self) -> Result<Vec<NetworkStatus>> {
+        sqlx::query_as::<_, NetworkStatus>(
+            "SELECT n.id, n.name,
+                    (SELECT count(*) FROM wg_clients c WHERE c.network_id = n.id) AS clients
+             FROM networks n
+             WHERE n.enabled
+             ORDER BY n.name",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Servers in enabled networks with whether each is online, by name.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_server_status(&self) -> Result<Vec<ServerStatus>> {
+        sqlx::query_as::<_, ServerStatus>(
+            "SELECT s.network_id, s.name,
+                    (s.last_seen_at IS NOT NULL AND NOT s.offline) AS online
+             FROM wg_servers s
+             JOIN networks n ON n.id = s.network_id
+             WHERE n.enabled
+             ORDER BY s.name",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    // -- Orphan repair -------------------------------------------------------
+
+    /// Find orphaned keys. Keys younger than `key_grace_secs` are skipped,
+    /// since a create in progress holds its key before the row using it
+    /// exists.
+    #[tracing::instrument(skip(self))]
+    pub async fn find_orphans(&self, key_grace_secs: i64) -> Result<OrphanReport> {
+        sqlx::query_as::<_, OrphanReport>(&format!(
+            "SELECT ARRAY(SELECT id FROM wg_keys k WHERE {ORPHAN_KEYS} ORDER BY id) AS keys"
+        ))
+        .bind(key_grace_secs as f64)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Delete what [`VpnStore::find_orphans`] reports, in one statement. No
+    /// config refers to these keys, so nothing rendered changes and the
+    /// approval flow has nothing to review.
+    #[tracing::instrument(skip(self))]
+    pub async fn purge_orphans(&self, key_grace_secs: i64) -> Result<OrphanReport> {
+        let report = sqlx::query_as::<_, OrphanReport>(&format!(
+            "WITH keys AS (DELETE FROM wg_keys k WHERE {ORPHAN_KEYS} RETURNING id)
+            SELECT ARRAY(SELECT id FROM keys ORDER BY id) AS keys"
+        ))
+        .bind(key_grace_secs as f64)
+        .fetch_one(&self.pool)
+        .await?;
+        for id in &report.keys {
+            self.keys.invalidate(*id);
+        }
+        Ok(report)
+    }
 }
 
+const ORPHAN_KEYS: &str = "k.created_at < now() - make_interval(secs => $1)
+    AND NOT EXISTS (SELECT 1 FROM wg_servers s WHERE s.key_id = k.id)
+    AND NOT EXISTS (SELECT 1 FROM wg_clients c WHERE c.key_id = k.id)";
+
 // ---------------------------------------------------------------------------
 // CIDR math helpers
 // ---------------------------------------------------------------------------
 
-fn ip_to_u32(ip: Ipv4Addr) -> u32 {
-    u32::from(ip)
+/// Address width of `net`'s family, in bits.
+fn family_bits(net: IpNetwork) -> u8 {
+    if net.is_ipv4() { 32 } else { 128 }
 }
 
-fn u32_to_ip(n: u32) -> Ipv4Addr {
-    Ipv4Addr::from(n)
+/// The address `n` above `base`, in the same family.
+fn ip_add(base: IpAddr, n: u128) -> IpAddr {
+    match base {
+        IpAddr::V4(v4) => IpAddr::V4(Ipv4Addr::from(u32::from(v4) + n as u32)),
+        IpAddr::V6(v6) => IpAddr::V6(Ipv6Addr::from(u128::from(v6) + n)),
+    }
 }
 
-fn network_contains(net: Ipv4Network, other: Ipv4Network) -> bool {
-    net.prefix() <= other.prefix() && net.contains(other.ip())
+fn network_contains(net: IpNetwork, other: IpNetwork) -> bool {
+    net.is_ipv4() == other.is_ipv4() && net.prefix() <= other.prefix() && net.contains(other.ip())
 }
 
 /// Subtract `exclude` from `base`, returning the remaining CIDRs.
-fn cidr_subtract(base: Ipv4Network, exclude: Ipv4Network) -> Vec<Ipv4Network> {
+fn cidr_subtract(base: IpNetwork, exclude: IpNetwork) -> Vec<IpNetwork> {
     if !network_contains(base, exclude) && !network_contains(exclude, base) {
         return vec![base];
     }
     if network_contains(exclude, base) {
         return vec![];
     }
-    if base.prefix() >= 32 {
+    if base.prefix() >= family_bits(base) {
         return vec![];
     }
 
     let new_prefix = base.prefix() + 1;
-    let base_ip = ip_to_u32(base.network());
-    let half_size = 1u32 << (32 - new_prefix);
+    let half_size = 1u128 << (family_bits(base) - new_prefix);
 
-    let left = Ipv4Network::new(u32_to_ip(base_ip), new_prefix).unwrap();
-    let right = Ipv4Network::new(u32_to_ip(base_ip + half_size), new_prefix).unwrap();
+    let left = IpNetwork::new(base.network(), new_prefix).unwrap();
+    let right = IpNetwork::new(ip_add(base.network(), half_size), new_prefix).unwrap();
 
     let mut result = Vec::new();
     for half in [left, right] {
@@ -789,7 +2329,7 @@ fn cidr_subtract(base: Ipv4Network, exclude: Ipv4Network) -> Vec<Ipv4Network> {
 }
 
 /// Subtract multiple excludes from base.
-fn cidr_subtract_many(base: Ipv4Network, excludes: &[Ipv4Network]) -> Vec<Ipv4Network> {
+fn cidr_subtract_many(base: IpNetwork, excludes: &[IpNetwork]) -> Vec<IpNetwork> {
     let mut remaining = vec![base];
     for &exclude in excludes {
         let mut next = Vec::new();
@@ -801,93 +2341,235 @@ fn cidr_subtract_many(base: Ipv4Network, excludes: &[Ipv4Network]) -> Vec<Ipv4Ne
     remaining
 }
 
+/// The parts of `nets` that lie inside `within`.
+fn cidr_intersect(nets: &[IpNetwork], within: &[IpNetwork]) -> Vec<IpNetwork> {
+    let mut result: Vec<IpNetwork> = Vec::new();
+    for &net in nets {
+        for &w in within {
+            let part = if network_contains(w, net) {
+                net
+            } else if network_contains(net, w) {
+                w
+            } else {
+                continue;
+            };
+            if !result.iter().any(|r| network_contains(*r, part)) {
+                result.push(part);
+            }
+        }
+    }
+    result
+}
+
 const RFC1918: &[&str] = &["10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16"];
 
-fn rfc1918_networks() -> Vec<Ipv4Network> {
+fn rfc1918_networks() -> Vec<IpNetwork> {
     RFC1918
         .iter()
         .map(|s| s.parse().unwrap())
         .collect()
 }
 
-/// Compute the IP address for a given network + offset.
-pub fn compute_address(network: &Network, offset: i32) -> Ipv4Addr {
-    let base = match network.cidr_ip {
-        IpNetwork::V4(v4) => ip_to_u32(v4.ip()),
-        IpNetwork::V6(_) => panic!("IPv6 not supported"),
+/// IPv6 internet traffic: global unicast, plus the well-known NAT64 prefix
+/// that a DNS64 resolver maps IPv4-only hosts into.
+const IPV6_INTERNET: &[&str] = &["2000::/3", "64:ff9b::/96"];
+
+/// What a client forwarding internet traffic sends through a server on a
+/// network of `vpn`'s family. `excludes` stay local, and so do private IPv4
+/// ranges when the client keeps LAN access. Without it, everything goes.
+fn internet_ranges(vpn: IpNetwork, lan_access: bool, excludes: &[IpNetwork]) -> Vec<IpNetwork> {
+    let ranges = match (vpn, lan_access) {
+        (IpNetwork::V4(_), true) => {
+            let all: IpNetwork = "0.0.0.0/0".parse().unwrap();
+            cidr_subtract_many(all, &rfc1918_networks())
+        }
+        (IpNetwork::V4(_), false) => vec!["0.0.0.0/0".parse().unwrap()],
+        (IpNetwork::V6(_), true) => IPV6_INTERNET.iter().map(|s| s.parse().unwrap()).collect(),
+        (IpNetwork::V6(_), false) => vec!["::/0".parse().unwrap()],
     };
-    u32_to_ip(base + offset as u32)
+    ranges
+        .into_iter()
+        .flat_map(|range| cidr_subtract_many(range, excludes))
+        .collect()
+}
+
+/// Compute the IP address for a given network + offset.
+/// Check that `key` is a canonically encoded 32-byte WireGuard public key.
+pub fn validate_public_key(key: &str) -> std::result::Result<(), &'static str> {
+    let bytes = BASE64
+        .decode(key)
+        .map_err(|_| "public key is not valid base64")?;
+    if bytes.len() != 32 {
+        return Err("public key must decode to 32 bytes");
+    }
+    if BASE64.encode(&bytes) != key {
+        return Err("public key is not canonically encoded");
+    }
+    if bytes.iter().all(|b| *b == 0) {
+        return Err("public key must not be all zeros");
+    }
+    Ok(())
+}
+
+/// Name the unique constraint a client insert or move ran into.
+fn client_conflict(e: sqlx::Error, address_offset: i32) -> VpnStoreError {
+    match &e {
+        sqlx::Error::Database(db_err) => match db_err.constraint() {
+            Some("wg_clients_network_id_name_ci_key") => VpnStoreError::DuplicateName,
+            Some("wg_clients_network_id_address_offset_key") => {
+                VpnStoreError::AddressOffsetConflict {
+                    offset: address_offset,
+                }
+            }
+            _ => VpnStoreError::Database(e),
+        },
+        _ => VpnStoreError::Database(e),
+    }
+}
+
+/// Unique index over each server's endpoint host and port.
+const ENDPOINT_KEY: &str = "wg_servers_endpoint_key";
+
+/// WireGuard's customary port, for servers on networks without a range.
+pub const DEFAULT_LISTEN_PORT: i32 = 51820;
+
+const MAX_PORT: i32 = 65535;
+
+/// The lowest port in `range` missing from `used`.
+fn first_free_port(range: RangeInclusive<i32>, used: &[i32]) -> Option<i32> {
+    range.into_iter().find(|port| !used.contains(port))
+}
+
+pub fn compute_address(network: &Network, offset: i32) -> IpAddr {
+    ip_add(network.cidr_ip.ip(), offset as u128)
 }
 
 // ---------------------------------------------------------------------------
 // Config generation
 // ---------------------------------------------------------------------------
 
+/// How a client asked for its config.
+#[derive(Debug, Default)]
+pub struct ConfigOptions {
+    /// Send internet traffic through the network's exit servers.
+    pub forward_internet: bool,
+    /// CIDRs to keep off the tunnel when forwarding internet traffic, on top
+    /// of the network's own.
+    pub exclude: Vec<IpNetwork>,
+    /// Keep private ranges off the tunnel; `None` takes the network's choice.
+    pub lan_access: Option<bool>,
+}
+
 impl WgClient {
     pub fn wg_quick_config(
         &self,
         key: &WgKey,
         snapshot: &NetworkSnapshot,
-        forward_internet: bool,
+        client_routes: &[WgClientRoute],
+        options: &ConfigOptions,
         preshared_keys: &HashMap<Uuid, String>,
+        locale: Locale,
     ) -> String {
         let client_ip = compute_address(&snapshot.network, self.address_offset);
         let prefix = snapshot.network.prefix();
 
         let mut config = String::new();
+        writeln!(config, "# {}", locale.text(Msg::ConfigHeader)).unwrap();
         writeln!(config, "# {}", self.name).unwrap();
         writeln!(config, "[Interface]").unwrap();
         writeln!(config, "# PublicKey = {}", key.public_key).unwrap();
         writeln!(config, "PrivateKey = {}", key.private_key).unwrap();
         writeln!(config, "Address = {client_ip}/{prefix}").unwrap();
 
+        let forward_internet = options.forward_internet;
         if forward_internet && !snapshot.network.dns_servers.is_empty() {
-            writeln!(config, "DNS = {}", snapshot.network.dns_servers.join(", ")).unwrap();
+            let dns: Vec<&str> = snapshot
+                .network
+                .dns_servers
+                .iter()
+                .chain(&snapshot.network.search_domains)
+                .map(String::as_str)
+                .collect();
+            writeln!(config, "DNS = {}", dns.join(", ")).unwrap();
+        }
+        if let Some(mtu) = mtu::effective(self.mtu, snapshot.network.mtu) {
+            writeln!(config, "MTU = {mtu}").unwrap();
+        }
+        for (key, command) in self.hooks.effective(&snapshot.network.hooks).lines() {
+            writeln!(config, "{key} = {command}").unwrap();
         }
 
-        let vpn_cidr: Ipv4Network = match snapshot.network.cidr_ip {
-            IpNetwork::V4(v4) => {
-                Ipv4Network::new(v4.ip(), snapshot.network.prefix()).unwrap()
-            }
-            IpNetwork::V6(_) => panic!("IPv6 not supported"),
-        };
+        // Only the network's own family goes through the tunnel; a client on
+        // an IPv6-only network has no IPv4 address there to send from.
+        let vpn_cidr = snapshot.network.cidr_ip;
+
+        // Network excludes were validated on the way in.
+        let mut excludes: Vec<IpNetwork> = snapshot
+            .network
+            .excluded_cidrs
+            .iter()
+            .filter_map(|c| c.parse().ok())
+            .collect();
+        excludes.extend(&options.exclude);
+        let lan_access = options.lan_access.unwrap_or(snapshot.network.lan_access);
+
+        let same_family = |r: &&WgClientRoute| r.route_cidr.is_ipv4() == vpn_cidr.is_ipv4();
+        let only: Vec<IpNetwork> = client_routes
+            .iter()
+            .filter(same_family)
+            .filter(|r| r.kind == ClientRouteKind::Only)
+            .map(|r| r.route_cidr)
+            .collect();
+        let extra: Vec<&WgClientRoute> = client_routes
+            .iter()
+            .filter(same_family)
+            .filter(|r| r.kind == ClientRouteKind::Extra)
+            .collect();
 
-        // Build claimed set and assign AllowedIPs per server (first-server-wins)
-        let mut claimed: Vec<Ipv4Network> = Vec::new();
+        // Build claimed set and assign AllowedIPs per server (first-server-wins).
+        // The client's own extra routes are claimed up front, so the server
+        // they name gets them whatever its priority.
+        let mut claimed: Vec<IpNetwork> = extra.iter().map(|r| r.route_cidr).collect();
 
-        // Servers in created_at ASC order (already sorted from DB query)
+        // Servers by priority, then created_at (already sorted from DB query)
         for server in &snapshot.servers {
             let Some(ref endpoint_host) = server.endpoint_host else {
                 continue;
             };
 
             let server_ip = compute_address(&snapshot.network, server.address_offset);
-            let server_32: Ipv4Network = Ipv4Network::new(server_ip, 32).unwrap();
+            let server_32 = IpNetwork::from(server_ip);
 
             // Build candidate CIDRs
-            let mut candidates: Vec<Ipv4Network> = vec![vpn_cidr];
+            let mut candidates: Vec<IpNetwork> = vec![vpn_cidr];
 
             let routes = snapshot.server_routes.get(&server.id);
             if let Some(routes) = routes {
                 for route in routes {
-                    if let IpNetwork::V4(v4) = route.route_cidr {
-                        candidates.push(v4);
+                    if route.route_cidr.is_ipv4() == vpn_cidr.is_ipv4() {
+                        candidates.push(route.route_cidr);
                     }
                 }
             }
 
             if forward_internet && server.forwards_internet_traffic {
-                let all: Ipv4Network = "0.0.0.0/0".parse().unwrap();
-                let public_ranges = cidr_subtract_many(all, &rfc1918_networks());
-                candidates.extend(public_ranges);
+                candidates.extend(internet_ranges(vpn_cidr, lan_access, &excludes));
             }
 
             // Subtract already-claimed CIDRs from candidates
-            let mut allowed: Vec<Ipv4Network> = Vec::new();
+            let mut allowed: Vec<IpNetwork> = Vec::new();
             for candidate in &candidates {
                 let remaining = cidr_subtract_many(*candidate, &claimed);
                 allowed.extend(remaining);
             }
+            if !only.is_empty() {
+                allowed = cidr_intersect(&allowed, &only);
+            }
+            for route in &extra {
+                if route.server_id == Some(server.id) {
+                    allowed.push(route.route_cidr);
+                }
+            }
 
             // Always include the server's own /32
             if !allowed.iter().any(|a| network_contains(*a, server_32)) {
@@ -912,8 +2594,10 @@ impl WgClient {
             }
             writeln!(config, "Endpoint = {endpoint_host}:{}", server.endpoint_port).unwrap();
             writeln!(config, "AllowedIPs = {}", allowed_ips.join(", ")).unwrap();
-            if snapshot.network.persistent_keepalive > 0 {
-                writeln!(config, "PersistentKeepalive = {}", snapshot.network.persistent_keepalive).unwrap();
+            let keepalive = keepalive::link(self.persistent_keepalive, server.persistent_keepalive)
+                .unwrap_or(snapshot.network.persistent_keepalive);
+            if keepalive > 0 {
+                writeln!(config, "PersistentKeepalive = {keepalive}").unwrap();
             }
         }
 
@@ -930,16 +2614,16 @@ mod tests {
     use super::*;
     use test_case::test_case;
 
-    fn net(s: &str) -> Ipv4Network {
+    fn net(s: &str) -> IpNetwork {
         s.parse().unwrap()
     }
 
-    fn nets(strs: &[&str]) -> Vec<Ipv4Network> {
+    fn nets(strs: &[&str]) -> Vec<IpNetwork> {
         strs.iter().map(|s| net(s)).collect()
     }
 
-    fn sorted(mut v: Vec<Ipv4Network>) -> Vec<Ipv4Network> {
-        v.sort_by_key(|n| (ip_to_u32(n.ip()), n.prefix()));
+    fn sorted(mut v: Vec<IpNetwork>) -> Vec<IpNetwork> {
+        v.sort_by_key(|n| (n.ip(), n.prefix()));
         v
     }
 
@@ -951,15 +2635,28 @@ mod tests {
     #[test_case("10.0.0.0/24", "10.0.0.0/24", &[] ; "subtract self")]
     #[test_case("10.0.0.0/24", "10.0.0.0/16", &[] ; "subtract supernet")]
     #[test_case("10.0.0.0/24", "10.0.0.0/26", &["10.0.0.64/26", "10.0.0.128/25"] ; "subtract quarter")]
+    #[test_case("fd00::/64", "fd00::/65", &["fd00::8000:0:0:0/65"] ; "subtract ipv6 lower half")]
+    #[test_case("fd00::/126", "fd00::1/128", &["fd00::/128", "fd00::2/127"] ; "subtract ipv6 host")]
+    #[test_case("10.0.0.0/24", "fd00::/8", &["10.0.0.0/24"] ; "other family noop")]
     fn test_cidr_subtract(base: &str, exclude: &str, expected: &[&str]) {
         let result = sorted(cidr_subtract(net(base), net(exclude)));
         let expected = sorted(nets(expected));
         assert_eq!(result, expected);
     }
 
+    #[test_case(&["10.0.0.0/8"], &["10.1.0.0/16"], &["10.1.0.0/16"] ; "narrows to within")]
+    #[test_case(&["10.1.0.0/16"], &["10.0.0.0/8"], &["10.1.0.0/16"] ; "keeps nets inside")]
+    #[test_case(&["10.0.0.0/8"], &["192.168.0.0/16"], &[] ; "disjoint")]
+    #[test_case(&["10.0.0.0/8", "172.16.0.0/12"], &["10.2.0.0/16", "172.16.1.0/24"], &["10.2.0.0/16", "172.16.1.0/24"] ; "several")]
+    #[test_case(&["10.0.0.0/8"], &["fd00::/8"], &[] ; "other family")]
+    fn test_cidr_intersect(base: &[&str], within: &[&str], expected: &[&str]) {
+        let result = sorted(cidr_intersect(&nets(base), &nets(within)));
+        assert_eq!(result, sorted(nets(expected)));
+    }
+
     #[test]
     fn test_subtract_rfc1918_from_all() {
-        let all: Ipv4Network = "0.0.0.0/0".parse().unwrap();
+        let all: IpNetwork = "0.0.0.0/0".parse().unwrap();
         let result = cidr_subtract_many(all, &rfc1918_networks());
         // Should cover all public IP space. Verify none of the results overlap RFC1918.
         for r in &result {
@@ -979,17 +2676,40 @@ mod tests {
         assert_eq!(result_size + private_size, 1u64 << 32);
     }
 
+    // -- Port allocation -----------------------------------------------------
+
+    #[test_case(&[], Some(51820) ; "empty range start")]
+    #[test_case(&[51820, 51821], Some(51822) ; "skips taken")]
+    #[test_case(&[51821], Some(51820) ; "fills gap")]
+    #[test_case(&[51820, 51821, 51822], None ; "full")]
+    #[test_case(&[1194, 51822], Some(51820) ; "ignores ports outside")]
+    fn test_first_free_port(used: &[i32], expected: Option<i32>) {
+        assert_eq!(first_free_port(51820..=51822, used), expected);
+    }
+
     // -- Config generation helpers -------------------------------------------
 
     fn make_network(cidr: &str, dns: &[&str]) -> Network {
-        let v4: Ipv4Network = cidr.parse().unwrap();
         Network {
             id: Uuid::nil(),
             name: "test-net".to_string(),
-            cidr_ip: IpNetwork::V4(Ipv4Network::new(v4.ip(), v4.prefix()).unwrap()),
+            cidr_ip: cidr.parse().unwrap(),
             owner_id: None,
             dns_servers: dns.iter().map(|s| s.to_string()).collect(),
             persistent_keepalive: 25,
+            enabled: true,
+            config_serial: 1,
+            notes: None,
+            port_range_start: None,
+            port_range_end: None,
+            endpoint_template: None,
+            allocation: Allocation::LowestFree,
+            mtu: None,
+            excluded_cidrs: Vec::new(),
+            lan_access: true,
+            hooks: Hooks::default(),
+            search_domains: Vec::new(),
+            topology: Topology::default(),
             created_at: Utc::now(),
             updated_at: Utc::now(),
         }
@@ -1021,10 +2741,23 @@ mod tests {
             api_token: Uuid::new_v4().to_string(),
             address_offset: offset,
             forwards_internet_traffic: forwards,
+            manage_nat: false,
+            fwmark: None,
+            route_table: None,
+            rule_priority: None,
+            mtu: None,
+            persistent_keepalive: None,
             endpoint_host: host.map(str::to_string),
             endpoint_port: port,
+            tags: Vec::new(),
+            notes: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            last_seen_at: None,
+            offline: false,
+            daemon_version: None,
+            listen_port: None,
+            priority: 0,
         }
     }
 
@@ -1035,6 +2768,17 @@ mod tests {
             name: format!("client-{offset}"),
             key_id,
             address_offset: offset,
+            access_schedule: None,
+            access_allowed: true,
+            tags: Vec::new(),
+            notes: None,
+            mtu: None,
+            persistent_keepalive: None,
+            monthly_quota_bytes: None,
+            quota_exceeded: false,
+            upload_kbps: None,
+            download_kbps: None,
+            hooks: Hooks::default(),
             created_at: Utc::now(),
             updated_at: Utc::now(),
         }
@@ -1044,7 +2788,7 @@ mod tests {
         WgServerRoute {
             id: Uuid::new_v4(),
             server_id,
-            route_cidr: IpNetwork::V4(cidr.parse().unwrap()),
+            route_cidr: cidr.parse().unwrap(),
             created_at: Utc::now(),
             updated_at: Utc::now(),
         }
@@ -1072,7 +2816,44 @@ mod tests {
         forward_internet: bool,
     ) -> String {
         let preshared_keys = HashMap::new();
-        client.wg_quick_config(key, snapshot, forward_internet, &preshared_keys)
+        let options = ConfigOptions {
+            forward_internet,
+            ..Default::default()
+        };
+        client.wg_quick_config(key, snapshot, &[], &options, &preshared_keys, Locale::En)
+    }
+
+    #[test_case("10.0.0.0/24", 254 ; "slash 24")]
+    #[test_case("10.0.0.0/30", 2 ; "slash 30")]
+    #[test_case("10.0.0.0/16", 65_534 ; "slash 16")]
+    #[test_case("fd00::/120", 255 ; "ipv6 slash 120")]
+    #[test_case("fd00::/64", i32::MAX as i64 ; "ipv6 slash 64 capped")]
+    fn test_usable_addresses(cidr: &str, expected: i64) {
+        assert_eq!(make_network(cidr, &[]).usable_addresses(), expected);
+    }
+
+    #[test_case(Allocation::LowestFree, &[1, 2, 4], 6, Some(3) ; "lowest fills gaps")]
+    #[test_case(Allocation::LowestFree, &[1, 2, 3], 3, None ; "lowest full")]
+    #[test_case(Allocation::SequentialFromHigh, &[5, 6], 6, Some(4) ; "high counts down")]
+    #[test_case(Allocation::SequentialFromHigh, &[1, 6], 6, Some(5) ; "high ignores low")]
+    #[test_case(Allocation::SequentialFromHigh, &[1, 2], 2, None ; "high full")]
+    #[test_case(Allocation::RandomInRange, &[1, 2, 4], 4, Some(3) ; "random last free")]
+    #[test_case(Allocation::RandomInRange, &[1, 2], 2, None ; "random full")]
+    fn test_allocation_pick(
+        allocation: Allocation,
+        used: &[i32],
+        usable: i64,
+        expected: Option<i32>,
+    ) {
+        assert_eq!(allocation.strategy().pick(used, usable), expected);
+    }
+
+    #[test]
+    fn test_random_allocation_stays_free_and_in_range() {
+        for _ in 0..100 {
+            let offset = Allocation::RandomInRange.strategy().pick(&[2, 3], 5).unwrap();
+            assert!([1, 4, 5].contains(&offset), "{offset}");
+        }
     }
 
     // -- Config generation tests ---------------------------------------------
@@ -1116,7 +2897,14 @@ mod tests {
         let mut preshared_keys = HashMap::new();
         preshared_keys.insert(sid, "psk-base64".to_string());
 
-        let config = client.wg_quick_config(&ckey, &snapshot, false, &preshared_keys);
+        let config = client.wg_quick_config(
+            &ckey,
+            &snapshot,
+            &[],
+            &ConfigOptions::default(),
+            &preshared_keys,
+            Locale::En,
+        );
         assert!(config.contains("PresharedKey = psk-base64"));
     }
 
@@ -1144,6 +2932,66 @@ mod tests {
         assert!(!config.contains("AllowedIPs = 0.0.0.0/0"));
     }
 
+    #[test_case("10.0.1.0/24", true, None, "AllowedIPs = 10.0.1.0/24, 0.0.0.0/0\n" ; "network without lan access")]
+    #[test_case("10.0.1.0/24", false, Some(false), "AllowedIPs = 10.0.1.0/24, 0.0.0.0/0\n" ; "request without lan access")]
+    #[test_case("fd00:1::/64", true, None, "AllowedIPs = fd00:1::/64, ::/0\n" ; "ipv6 without lan access")]
+    #[test_case("10.0.1.0/24", true, Some(true), "AllowedIPs = 10.0.1.0/24, 0.0.0.0/5" ; "request keeps lan access")]
+    fn test_full_tunnel_lan_access(
+        cidr: &str,
+        network_off: bool,
+        requested: Option<bool>,
+        expected: &str,
+    ) {
+        let mut network = make_network(cidr, &[]);
+        network.lan_access = !network_off;
+        let sk = Uuid::new_v4();
+        let ck = Uuid::new_v4();
+        let sid = Uuid::new_v4();
+
+        let server = make_server(sid, sk, 1, true, Some("vpn.example.com"), 51820);
+        let skey = make_key(sk, "server-priv", "server-pub");
+        let ckey = make_key(ck, "client-priv", "client-pub");
+        let client = make_client(Uuid::new_v4(), ck, 2);
+
+        let snapshot = make_snapshot(network, vec![server], vec![skey], HashMap::new());
+        let options = ConfigOptions {
+            forward_internet: true,
+            lan_access: requested,
+            ..Default::default()
+        };
+        let config =
+            client.wg_quick_config(&ckey, &snapshot, &[], &options, &HashMap::new(), Locale::En);
+        assert!(config.contains(expected), "{config}");
+    }
+
+    #[test]
+    fn test_hooks_rendered_in_interface() {
+        let mut network = make_network("10.0.1.0/24", &[]);
+        network.hooks = Hooks {
+            post_up: Some("network up".into()),
+            post_down: Some("network down".into()),
+            ..Default::default()
+        };
+        let sk = Uuid::new_v4();
+        let ck = Uuid::new_v4();
+        let sid = Uuid::new_v4();
+
+        let server = make_server(sid, sk, 1, false, Some("vpn.example.com"), 51820);
+        let skey = make_key(sk, "server-priv", "server-pub");
+        let ckey = make_key(ck, "client-priv", "client-pub");
+        let mut client = make_client(Uuid::new_v4(), ck, 2);
+        client.hooks.post_up = Some("resolvectl dns %i 10.0.1.1".into());
+
+        let snapshot = make_snapshot(network, vec![server], vec![skey], HashMap::new());
+        let config = render_config(&client, &ckey, &snapshot, false);
+
+        let interface = "Address = 10.0.1.2/24\n\
+                         PostUp = resolvectl dns %i 10.0.1.1\n\
+                         PostDown = network down\n\n";
+        assert!(config.contains(interface), "{config}");
+        assert!(!config.contains("network up"));
+    }
+
     #[test]
     fn test_single_server_forward_internet_split_tunnel() {
         // forward_internet=false means even if server forwards, client doesn't request it
@@ -1305,6 +3153,88 @@ mod tests {
         assert!(!allowed_lines[1].contains("172.16.0.0/16"));
     }
 
+    fn make_client_route(kind: ClientRouteKind, server_id: Option<Uuid>, cidr: &str) -> WgClientRoute {
+        WgClientRoute {
+            id: Uuid::new_v4(),
+            client_id: Uuid::nil(),
+            kind,
+            server_id,
+            route_cidr: cidr.parse().unwrap(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_client_extra_route_goes_to_named_server() {
+        let network = make_network("10.0.1.0/24", &[]);
+        let sk1 = Uuid::new_v4();
+        let sk2 = Uuid::new_v4();
+        let ck = Uuid::new_v4();
+        let sid1 = Uuid::new_v4();
+        let sid2 = Uuid::new_v4();
+
+        let s1 = make_server(sid1, sk1, 1, false, Some("s1.example.com"), 51820);
+        let s2 = make_server(sid2, sk2, 2, false, Some("s2.example.com"), 51821);
+        let skey1 = make_key(sk1, "s1-priv", "s1-pub");
+        let skey2 = make_key(sk2, "s2-priv", "s2-pub");
+        let ckey = make_key(ck, "client-priv", "client-pub");
+        let client = make_client(Uuid::new_v4(), ck, 3);
+
+        // s1 would claim the route for everyone else
+        let mut routes = HashMap::new();
+        routes.insert(sid1, vec![make_route(sid1, "172.16.0.0/16")]);
+        let snapshot = make_snapshot(network, vec![s1, s2], vec![skey1, skey2], routes);
+        let client_routes = [make_client_route(ClientRouteKind::Extra, Some(sid2), "172.16.0.0/16")];
+        let config = client.wg_quick_config(
+            &ckey,
+            &snapshot,
+            &client_routes,
+            &ConfigOptions::default(),
+            &HashMap::new(),
+            Locale::En,
+        );
+
+        let allowed_lines: Vec<&str> = config
+            .lines()
+            .filter(|l| l.starts_with("AllowedIPs"))
+            .collect();
+        assert_eq!(allowed_lines[0], "AllowedIPs = 10.0.1.0/24");
+        assert_eq!(allowed_lines[1], "AllowedIPs = 172.16.0.0/16, 10.0.1.2/32");
+    }
+
+    #[test]
+    fn test_client_only_routes_restrict_allowed_ips() {
+        let network = make_network("10.0.1.0/24", &[]);
+        let sk = Uuid::new_v4();
+        let ck = Uuid::new_v4();
+        let sid = Uuid::new_v4();
+
+        let mut server = make_server(sid, sk, 1, false, Some("vpn.example.com"), 51820);
+        server.forwards_internet_traffic = true;
+        let skey = make_key(sk, "server-priv", "server-pub");
+        let ckey = make_key(ck, "client-priv", "client-pub");
+        let client = make_client(Uuid::new_v4(), ck, 2);
+
+        let mut routes = HashMap::new();
+        routes.insert(sid, vec![make_route(sid, "172.16.0.0/16")]);
+        let snapshot = make_snapshot(network, vec![server], vec![skey], routes);
+        let client_routes = [make_client_route(ClientRouteKind::Only, None, "172.16.4.0/24")];
+        let config = client.wg_quick_config(
+            &ckey,
+            &snapshot,
+            &client_routes,
+            &ConfigOptions {
+                forward_internet: true,
+                ..Default::default()
+            },
+            &HashMap::new(),
+            Locale::En,
+        );
+
+        assert!(config.contains("AllowedIPs = 172.16.4.0/24, 10.0.1.1/32\n"), "{config}");
+    }
+
     #[test]
     fn test_server_without_endpoint_skipped() {
         let network = make_network("10.0.1.0/24", &[]);
@@ -1323,6 +3253,36 @@ mod tests {
         assert!(!config.contains("[Peer]"));
     }
 
+    #[test]
+    fn test_ipv6_only_client_full_tunnel() {
+        let network = make_network("fd00:1::/64", &["2001:4860:4860::6464"]);
+        let sk = Uuid::new_v4();
+        let ck = Uuid::new_v4();
+        let sid = Uuid::new_v4();
+
+        let server = make_server(sid, sk, 1, true, Some("vpn.example.com"), 51820);
+        let skey = make_key(sk, "server-priv", "server-pub");
+        let ckey = make_key(ck, "client-priv", "client-pub");
+        let client = make_client(Uuid::new_v4(), ck, 2);
+        let mut routes = HashMap::new();
+        routes.insert(
+            sid,
+            vec![make_route(sid, "192.168.5.0/24"), make_route(sid, "fd00:2::/64")],
+        );
+
+        let snapshot = make_snapshot(network, vec![server], vec![skey], routes);
+        let config = render_config(&client, &ckey, &snapshot, true);
+
+        assert!(config.contains("Address = fd00:1::2/64"));
+        assert!(config.contains("DNS = 2001:4860:4860::6464"));
+        assert!(config.contains(
+            "AllowedIPs = fd00:1::/64, fd00:2::/64, 2000::/3, 64:ff9b::/96\n"
+        ));
+        // No IPv4 address to send from, so no IPv4 routes either
+        assert!(!config.contains("192.168.5.0/24"));
+        assert!(!config.contains("0.0.0.0"));
+    }
+
     #[test]
     fn test_dns_included_when_forwarding() {
         let network = make_network("10.0.1.0/24", &["1.1.1.1", "8.8.8.8"]);
@@ -1336,6 +3296,20 @@ mod tests {
         assert!(config.contains("DNS = 1.1.1.1, 8.8.8.8"));
     }
 
+    #[test]
+    fn test_search_domains_follow_dns_servers() {
+        let mut network = make_network("10.0.1.0/24", &["10.0.1.1"]);
+        network.search_domains = vec!["corp.example.com".into(), "lan".into()];
+        let ck = Uuid::new_v4();
+        let ckey = make_key(ck, "client-priv", "client-pub");
+        let client = make_client(Uuid::new_v4(), ck, 2);
+
+        let snapshot = make_snapshot(network, vec![], vec![], HashMap::new());
+        let config = render_config(&client, &ckey, &snapshot, true);
+
+        assert!(config.contains("DNS = 10.0.1.1, corp.example.com, lan\n"), "{config}");
+    }
+
     #[test]
     fn test_dns_excluded_without_forwarding() {
         let network = make_network("10.0.1.0/24", &["1.1.1.1", "8.8.8.8"]);
@@ -1361,4 +3335,13 @@ mod tests {
 
         assert!(!config.contains("DNS"));
     }
+
+    #[test_case("YmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmI=", true ; "valid")]
+    #[test_case("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=", false ; "all zeros")]
+    #[test_case("YmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJ=", false ; "non canonical")]
+    #[test_case("YmJiYg==", false ; "too short")]
+    #[test_case("not base64!", false ; "garbage")]
+    fn test_validate_public_key(key: &str, valid: bool) {
+        assert_eq!(validate_public_key(key).is_ok(), valid);
+    }
 }
@@ -0,0 +1,262 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use sqlx::types::Json;
+use uuid::Uuid;
+use wirewarden_types::redact::Redacted;
+
+use crate::events::Event;
+
+#[derive(sqlx::FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub secret: String,
+    /// Event kinds this webhook receives; empty means all.
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Webhook")
+            .field("id", &self.id)
+            .field("url", &self.url)
+            .field("secret", &Redacted)
+            .field("events", &self.events)
+            .field("enabled", &self.enabled)
+            .field("created_by", &self.created_by)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: Json<Event>,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookStore {
+    pool: PgPool,
+}
+
+impl WebhookStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // -- Webhooks ------------------------------------------------------------
+
+    #[tracing::instrument(skip(self, secret))]
+    pub async fn create(
+        &self,
+        url: &str,
+        secret: &str,
+        events: &[String],
+        created_by: Uuid,
+    ) -> Result<Webhook, sqlx::Error> {
+        sqlx::query_as::<_, Webhook>(
+            "INSERT INTO webhooks (url, secret, events, created_by)
+             VALUES ($1, $2, $3, $4)
+             RETURNING *",
+        )
+        .bind(url)
+        .bind(secret)
+        .bind(events)
+        .bind(created_by)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn get(&self, id: Uuid) -> Result<Option<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list(&self) -> Result<Vec<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn update(
+        &self,
+        id: Uuid,
+        url: &str,
+        events: &[String],
+        enabled: bool,
+    ) -> Result<Option<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, Webhook>(
+            "UPDATE webhooks SET url = $2, events = $3, enabled = $4, updated_at = now()
+             WHERE id = $1
+             RETURNING *",
+        )
+        .bind(id)
+        .bind(url)
+        .bind(events)
+        .bind(enabled)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn delete(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Enabled webhooks subscribed to `kind`.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_subscribed(&self, kind: &str) -> Result<Vec<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, Webhook>(
+            "SELECT * FROM webhooks
+             WHERE enabled AND (cardinality(events) = 0 OR $1 = ANY(events))",
+        )
+        .bind(kind)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // -- Deliveries ----------------------------------------------------------
+
+    #[tracing::instrument(skip(self))]
+    pub async fn enqueue(&self, webhook_id: Uuid, event: &Event) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO webhook_deliveries (webhook_id, event) VALUES ($1, $2)")
+            .bind(webhook_id)
+            .bind(Json(event))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Claim due deliveries by pushing their next attempt `lease_secs` into
+    /// the future, so a crashed worker's deliveries are retried later.
+    #[tracing::instrument(skip(self))]
+    pub async fn claim_due(
+        &self,
+        limit: i64,
+        lease_secs: i64,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookDelivery>(
+            "UPDATE webhook_deliveries
+             SET next_attempt_at = now() + make_interval(secs => $2)
+             WHERE id IN (
+                 SELECT id FROM webhook_deliveries
+                 WHERE status = 'pending' AND next_attempt_at <= now()
+                 ORDER BY next_attempt_at
+                 LIMIT $1
+                 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING *",
+        )
+        .bind(limit)
+        .bind(lease_secs as f64)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn mark_delivered(&self, id: Uuid, status_code: i32) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE webhook_deliveries
+             SET status = 'delivered', attempts = attempts + 1, last_status_code = $2,
+                 last_error = NULL, delivered_at = now()
+             WHERE id = $1",
+        )
+        .bind(id)
+        .bind(status_code)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Record a failed attempt. With no `retry_at` the delivery is given up.
+    #[tracing::instrument(skip(self))]
+    pub async fn mark_failed(
+        &self,
+        id: Uuid,
+        status_code: Option<i32>,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE webhook_deliveries
+             SET attempts = attempts + 1, last_status_code = $2, last_error = $3,
+                 status = CASE WHEN $4::timestamptz IS NULL THEN 'failed' ELSE 'pending' END,
+                 next_attempt_at = COALESCE($4, next_attempt_at)
+             WHERE id = $1",
+        )
+        .bind(id)
+        .bind(status_code)
+        .bind(error)
+        .bind(retry_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list_deliveries(
+        &self,
+        webhook_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookDelivery>(
+            "SELECT * FROM webhook_deliveries
+             WHERE webhook_id = $1
+             ORDER BY created_at DESC
+             LIMIT $2",
+        )
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Weekly per-network summary emails.
+
+use std::fmt::Write as _;
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::changes::Change;
+use crate::db::schedule::ScheduleStore;
+use crate::db::vpn::{VpnStore, VpnStoreError};
+use crate::i18n::{Locale, Msg};
+
+pub const PERIOD_DAYS: i64 = 7;
+
+#[derive(Debug)]
+pub struct NewDevice {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct OfflineServer {
+    pub name: String,
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub struct Digest {
+    pub network_name: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub new_devices: Vec<NewDevice>,
+    pub offline_servers: Vec<OfflineServer>,
+    pub upcoming_rotations: Vec<DateTime<Utc>>,
+}
+
+impl Digest {
+    /// Gather the digest for `network_id` covering the week up to `now`.
+    /// Returns `None` if the network no longer exists.
+    pub async fn build(
+        store: &VpnStore,
+        schedules: &ScheduleStore,
+        network_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Self>, VpnStoreError> {
+        let Some(network) = store.get_network(network_id).await? else {
+            return Ok(None);
+        };
+        let period_start = now - Duration::days(PERIOD_DAYS);
+
+        let (servers, clients) = futures::future::try_join(
+            store.list_servers_by_network(network_id),
+            store.list_clients_by_network(network_id),
+        )
+        .await?;
+        let scheduled = schedules
+            .list_pending_for_network(network_id, now + Duration::days(PERIOD_DAYS))
+            .await?;
+
+        Ok(Some(Self {
+            network_name: network.name,
+            period_start,
+            period_end: now,
+            new_devices: clients
+                .into_iter()
+                .filter(|c| c.created_at >= period_start)
+                .map(|c| NewDevice {
+                    name: c.name,
+                    created_at: c.created_at,
+                })
+                .collect(),
+            offline_servers: servers
+                .into_iter()
+                .filter(|s| s.offline)
+                .map(|s| OfflineServer {
+                    name: s.name,
+                    last_seen_at: s.last_seen_at,
+                })
+                .collect(),
+            upcoming_rotations: scheduled
+                .into_iter()
+                .filter(|s| matches!(s.change.0, Change::RotateNetworkPsks { .. }))
+                .map(|s| s.run_at)
+                .collect(),
+        }))
+    }
+
+    pub fn subject(&self, locale: Locale) -> String {
+        locale.format(Msg::DigestSubject, &[&self.network_name])
+    }
+
+    pub fn render(&self, locale: Locale) -> String {
+        const TS: &str = "%Y-%m-%d %H:%M UTC";
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}\n",
+            locale.format(
+                Msg::DigestHeading,
+                &[
+                    &self.network_name,
+                    &self.period_start.format("%Y-%m-%d"),
+                    &self.period_end.format("%Y-%m-%d"),
+                ],
+            )
+        );
+
+        let _ = writeln!(
+            out,
+            "{}",
+            locale.format(Msg::DigestNewDevices, &[&self.new_devices.len()])
+        );
+        for d in &self.new_devices {
+            let added = locale.format(Msg::DigestAdded, &[&d.created_at.format(TS)]);
+            let _ = writeln!(out, "  - {} ({added})", d.name);
+        }
+
+        let _ = writeln!(
+            out,
+            "\n{}",
+            locale.format(Msg::DigestOfflineServers, &[&self.offline_servers.len()])
+        );
+        for s in &self.offline_servers {
+            let seen = match s.last_seen_at {
+                Some(at) => locale.format(Msg::DigestLastSeen, &[&at.format(TS)]),
+                None => locale.text(Msg::DigestNeverSeen).to_string(),
+            };
+            let _ = writeln!(out, "  - {} ({seen})", s.name);
+        }
+
+        let _ = writeln!(
+            out,
+            "\n{}",
+            locale.format(
+                Msg::DigestUpcomingRotations,
+                &[&PERIOD_DAYS, &self.upcoming_rotations.len()],
+            )
+        );
+        for at in &self.upcoming_rotations {
+            let _ = writeln!(out, "  - {}", at.format(TS));
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let digest = Digest {
+            network_name: "home".into(),
+            period_start: ts("2026-02-01T00:00:00Z"),
+            period_end: ts("2026-02-08T00:00:00Z"),
+            new_devices: vec![NewDevice {
+                name: "laptop".into(),
+                created_at: ts("2026-02-03T10:30:00Z"),
+            }],
+            offline_servers: vec![OfflineServer {
+                name: "relay".into(),
+                last_seen_at: None,
+            }],
+            upcoming_rotations: vec![ts("2026-02-09T03:00:00Z")],
+        };
+
+        assert_eq!(
+            digest.render(Locale::En),
+            "Network \"home\": 2026-02-01 to 2026-02-08\n\n\
+             New devices (1):\n  - laptop (added 2026-02-03 10:30 UTC)\n\n\
+             Offline servers (1):\n  - relay (never seen)\n\n\
+             Preshared key rotations in the next 7 days (1):\n  - 2026-02-09 03:00 UTC\n"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! DNS records for a network's peers under `<network>.wirewarden.internal`,
+//! as JSON or as a zone file for CoreDNS, unbound, or BIND to serve.
+
+use std::fmt::Write as _;
+use std::net::IpAddr;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::vpn::{self, Network, WgClient, WgServer};
+use crate::endpoint_template;
+
+/// The parent of every network's zone.
+pub const ZONE_SUFFIX: &str = "wirewarden.internal";
+
+/// How long resolvers may cache answers, in seconds. Peers are renamed and
+/// moved rarely, but it should not take long to notice when they are.
+const TTL: u32 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerKind {
+    Server,
+    Client,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Record {
+    /// Fully qualified, without the trailing dot.
+    pub name: String,
+    /// `A` or `AAAA`.
+    #[serde(rename = "type")]
+    pub record_type: &'static str,
+    pub address: IpAddr,
+    pub peer: PeerKind,
+    pub peer_id: Uuid,
+}
+
+/// A network's records and what a zone file needs around them.
+#[derive(Debug, Serialize)]
+pub struct Zone {
+    pub origin: String,
+    /// The network's config serial, which moves whenever a peer's name or
+    /// address does.
+    pub serial: u32,
+    pub records: Vec<Record>,
+}
+
+impl Zone {
+    /// Records for `servers` and then `clients`. Peers whose names make no
+    /// valid hostname are left out.
+    pub fn build<'a>(
+        network: &Network,
+        servers: impl IntoIterator<Item = &'a WgServer>,
+        clients: impl IntoIterator<Item = &'a WgClient>,
+    ) -> Result<Zone, String> {
+        let network_label = hostname(&network.name).ok_or_else(|| {
+            format!("network name {:?} does not make a DNS label", network.name)
+        })?;
+        let origin = format!("{network_label}.{ZONE_SUFFIX}");
+
+        let servers = servers
+            .into_iter()
+            .map(|s| (PeerKind::Server, s.id, s.name.as_str(), s.address_offset));
+        let clients = clients
+            .into_iter()
+            .map(|c| (PeerKind::Client, c.id, c.name.as_str(), c.address_offset));
+        let records = servers
+            .chain(clients)
+            .filter_map(|(peer, peer_id, name, offset)| {
+                let address = vpn::compute_address(network, offset);
+                Some(Record {
+                    name: format!("{}.{origin}", hostname(name)?),
+                    record_type: if address.is_ipv4() { "A" } else { "AAAA" },
+                    address,
+                    peer,
+                    peer_id,
+                })
+            })
+            .collect();
+
+        Ok(Zone {
+            origin,
+            serial: network.config_serial as u32,
+            records,
+        })
+    }
+
+    /// The zone in RFC 1035 master file format. Its name server is the
+    /// first server, the one clients reach first.
+    pub fn to_zone_file(&self) -> String {
+        let origin = &self.origin;
+        let mut out = String::new();
+        writeln!(out, "$ORIGIN {origin}.").unwrap();
+        writeln!(out, "$TTL {TTL}").unwrap();
+        writeln!(
+            out,
+            "@\tIN\tSOA\tns.{origin}. hostmaster.{origin}. {} 3600 600 86400 {TTL}",
+            self.serial
+        )
+        .unwrap();
+        writeln!(out, "@\tIN\tNS\tns.{origin}.").unwrap();
+        if let Some(ns) = self.records.iter().find(|r| r.peer == PeerKind::Server) {
+            writeln!(out, "ns\tIN\t{}\t{}", ns.record_type, ns.address).unwrap();
+        }
+        for record in &self.records {
+            let host = record
+                .name
+                .strip_suffix(&format!(".{origin}"))
+                .unwrap_or(&record.name);
+            writeln!(out, "{host}\tIN\t{}\t{}", record.record_type, record.address).unwrap();
+        }
+        out
+    }
+}
+
+/// A name as a hostname, or `None` if its labels are not valid ones.
+fn hostname(name: &str) -> Option<String> {
+    let host = endpoint_template::label(name);
+    let valid = host.split('.').all(|label| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    valid.then_some(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("Alice Laptop", Some("alice-laptop") ; "spaces")]
+    #[test_case("edge.east", Some("edge.east") ; "dotted")]
+    #[test_case("phone_", None ; "trailing dash")]
+    #[test_case("a..b", None ; "empty label")]
+    fn test_hostname(name: &str, expected: Option<&str>) {
+        assert_eq!(hostname(name).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_zone_file() {
+        let record = |name: &str, address: &str, peer| Record {
+            name: format!("{name}.home.{ZONE_SUFFIX}"),
+            record_type: "A",
+            address: address.parse().unwrap(),
+            peer,
+            peer_id: Uuid::nil(),
+        };
+        let zone = Zone {
+            origin: format!("home.{ZONE_SUFFIX}"),
+            serial: 7,
+            records: vec![
+                record("gw", "10.0.0.1", PeerKind::Server),
+                record("laptop", "10.0.0.2", PeerKind::Client),
+            ],
+        };
+        let expected = "\
+$ORIGIN home.wirewarden.internal.
+$TTL 300
+@\tIN\tSOA\tns.home.wirewarden.internal. hostmaster.home.wirewarden.internal. 7 3600 600 86400 300
+@\tIN\tNS\tns.home.wirewarden.internal.
+ns\tIN\tA\t10.0.0.1
+gw\tIN\tA\t10.0.0.1
+laptop\tIN\tA\t10.0.0.2
+";
+        assert_eq!(zone.to_zone_file(), expected);
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Endpoint hostname templates on networks, e.g. `{server}.vpn.example.com`.
+//! Servers created without an endpoint get the template rendered with their
+//! name, and servers still on the old rendering follow the template when it
+//! changes, so DNS records and server names stay in step.
+
+const MAX_HOST_LEN: usize = 253;
+
+/// What placeholders stand in for when checking a template.
+const SAMPLE_LABEL: &str = "a";
+
+/// Trim `template` and check it renders to a hostname. Blank input maps to
+/// `None`, clearing the template.
+pub fn normalize(template: &str) -> Result<Option<String>, String> {
+    let template = template.trim();
+    if template.is_empty() {
+        return Ok(None);
+    }
+    if template.len() > MAX_HOST_LEN {
+        return Err(format!(
+            "endpoint template must be at most {MAX_HOST_LEN} characters"
+        ));
+    }
+    let host = render(template, SAMPLE_LABEL, SAMPLE_LABEL);
+    if host.contains(['{', '}']) {
+        return Err("endpoint template may only use {server} and {network}".into());
+    }
+    let valid_labels = host.split('.').all(|label| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    if !valid_labels {
+        return Err(format!(
+            "endpoint template must render to a hostname, e.g. {{server}}.vpn.example.com; got {host}"
+        ));
+    }
+    Ok(Some(template.to_string()))
+}
+
+/// `template` with `{network}` and `{server}` replaced by the names as DNS
+/// labels.
+pub fn render(template: &str, network: &str, server: &str) -> String {
+    template
+        .replace("{network}", &label(network))
+        .replace("{server}", &label(server))
+}
+
+/// A name as it appears in a hostname: lowercase, with the spaces and
+/// underscores names allow turned into dashes.
+pub fn label(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            ' ' | '_' => '-',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(" {server}.vpn.example.com ", Some("{server}.vpn.example.com") ; "trimmed")]
+    #[test_case("{server}-{network}.example.com", Some("{server}-{network}.example.com") ; "both")]
+    #[test_case("vpn.example.com", Some("vpn.example.com") ; "fixed host")]
+    #[test_case("  ", None ; "blank clears")]
+    fn test_normalize(input: &str, expected: Option<&str>) {
+        assert_eq!(normalize(input).unwrap().as_deref(), expected);
+    }
+
+    #[test_case("{host}.example.com" ; "unknown placeholder")]
+    #[test_case("{server.example.com" ; "unclosed")]
+    #[test_case("{server}..example.com" ; "empty label")]
+    #[test_case("-{server}.example.com" ; "leading dash")]
+    #[test_case("{server}.example.com:51820" ; "port")]
+    #[test_case("{server}/vpn" ; "slash")]
+    fn test_normalize_rejects(input: &str) {
+        assert!(normalize(input).is_err());
+    }
+
+    #[test_case("{server}.vpn.example.com", "home", "Relay 01", "relay-01.vpn.example.com" ; "spaces")]
+    #[test_case("{server}.{network}.example.com", "Home_Lab", "gw", "gw.home-lab.example.com" ; "network")]
+    #[test_case("{server}.example.com", "home", "edge.east", "edge.east.example.com" ; "dotted name")]
+    fn test_render(template: &str, network: &str, server: &str, expected: &str) {
+        assert_eq!(render(template, network, server), expected);
+    }
+}
@@ -17,6 +17,7 @@ use actix_web::{HttpResponse, ResponseError};
 
 use crate::db::user::UserStoreError;
 use crate::db::vpn::VpnStoreError;
+use crate::i18n::{Locale, Msg};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ApiError {
@@ -41,13 +42,22 @@ pub enum ApiError {
     #[error("reset token expired")]
     ResetTokenExpired,
 
+    #[error("invalid verification token")]
+    InvalidVerifyToken,
+
+    #[error("verification token expired")]
+    VerifyTokenExpired,
+
+    #[error("verify your email address first")]
+    EmailNotVerified,
+
     #[error("validation error: {0}")]
     Validation(String),
 
     #[error("not found")]
     NotFound,
 
-    #[error("name already taken")]
+    #[error("name already taken (names are case-insensitive)")]
     DuplicateName,
 
     #[error("address offset conflict")]
@@ -59,19 +69,79 @@ pub enum ApiError {
     #[error("no available addresses in this network")]
     NetworkFull,
 
+    #[error("no free port in this network's port range")]
+    PortRangeFull,
+
+    #[error("port already used by another server on this endpoint host")]
+    PortInUse,
+
+    #[error("approval requires a second admin or the cooling-off period to elapse")]
+    SelfApproval,
+
+    #[error("change has already been decided")]
+    ChangeAlreadyDecided,
+
+    #[error("the daemon cannot enforce this network's deny rules; upgrade it")]
+    AclUnsupported,
+
     #[error("internal server error")]
     Internal,
 }
 
+impl ApiError {
+    fn msg(&self) -> Msg {
+        match self {
+            Self::InvalidCredentials => Msg::InvalidCredentials,
+            Self::Unauthorized => Msg::Unauthorized,
+            Self::UserNotFound => Msg::UserNotFound,
+            Self::DuplicateUsername => Msg::DuplicateUsername,
+            Self::DuplicateEmail => Msg::DuplicateEmail,
+            Self::InvalidResetToken => Msg::InvalidResetToken,
+            Self::ResetTokenExpired => Msg::ResetTokenExpired,
+            Self::InvalidVerifyToken => Msg::InvalidVerifyToken,
+            Self::VerifyTokenExpired => Msg::VerifyTokenExpired,
+            Self::EmailNotVerified => Msg::EmailNotVerified,
+            Self::Validation(_) => Msg::ValidationError,
+            Self::NotFound => Msg::NotFound,
+            Self::DuplicateName => Msg::DuplicateName,
+            Self::OffsetConflict => Msg::OffsetConflict,
+            Self::OffsetOutOfRange => Msg::OffsetOutOfRange,
+            Self::NetworkFull => Msg::NetworkFull,
+            Self::PortRangeFull => Msg::PortRangeFull,
+            Self::PortInUse => Msg::PortInUse,
+            Self::SelfApproval => Msg::SelfApproval,
+            Self::ChangeAlreadyDecided => Msg::ChangeAlreadyDecided,
+            Self::AclUnsupported => Msg::AclUnsupported,
+            Self::Internal => Msg::Internal,
+        }
+    }
+
+    /// The error message in `locale`. Validation details are passed through
+    /// untranslated.
+    pub fn localized(&self, locale: Locale) -> String {
+        match self {
+            Self::Validation(detail) => locale.format(Msg::ValidationError, &[detail]),
+            _ => locale.text(self.msg()).to_string(),
+        }
+    }
+}
+
 impl ResponseError for ApiError {
     fn status_code(&self) -> StatusCode {
         match self {
             Self::InvalidCredentials | Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::SelfApproval | Self::EmailNotVerified => StatusCode::FORBIDDEN,
             Self::UserNotFound | Self::NotFound => StatusCode::NOT_FOUND,
             Self::DuplicateUsername | Self::DuplicateEmail | Self::DuplicateName
-            | Self::OffsetConflict => StatusCode::CONFLICT,
-            Self::InvalidResetToken | Self::ResetTokenExpired | Self::Validation(_)
-            | Self::OffsetOutOfRange | Self::NetworkFull => StatusCode::BAD_REQUEST,
+            | Self::OffsetConflict | Self::PortInUse | Self::ChangeAlreadyDecided => {
+                StatusCode::CONFLICT
+            }
+            Self::InvalidResetToken | Self::ResetTokenExpired | Self::InvalidVerifyToken
+            | Self::VerifyTokenExpired | Self::Validation(_) | Self::OffsetOutOfRange
+            | Self::NetworkFull | Self::PortRangeFull => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::AclUnsupported => StatusCode::UPGRADE_REQUIRED,
             Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -88,6 +158,7 @@ impl From<UserStoreError> for ApiError {
             UserStoreError::DuplicateUsername => Self::DuplicateUsername,
             UserStoreError::DuplicateEmail => Self::DuplicateEmail,
             UserStoreError::TokenExpired => Self::ResetTokenExpired,
+            UserStoreError::VerifyTokenExpired => Self::VerifyTokenExpired,
             UserStoreError::PasswordHash | UserStoreError::Database(_) => {
                 tracing::error!(error = %err, "store error");
                 Self::Internal
@@ -105,6 +176,8 @@ impl From<VpnStoreError> for ApiError {
             VpnStoreError::AddressOffsetConflict { .. } => Self::OffsetConflict,
             VpnStoreError::OffsetOutOfRange { .. } => Self::OffsetOutOfRange,
             VpnStoreError::NetworkFull => Self::NetworkFull,
+            VpnStoreError::PortRangeFull => Self::PortRangeFull,
+            VpnStoreError::PortInUse { .. } => Self::PortInUse,
             VpnStoreError::NetworkNotFound
             | VpnStoreError::KeyNotFound
             | VpnStoreError::ServerNotFound => Self::NotFound,
@@ -117,3 +190,10 @@ impl From<VpnStoreError> for ApiError {
         }
     }
 }
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        tracing::error!(error = %err, "database error");
+        Self::Internal
+    }
+}
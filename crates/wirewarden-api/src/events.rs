@@ -0,0 +1,252 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Broadcast of resource lifecycle events. Events are delivered in-process
+//! and, once [`EventBus::relay`] runs, shared with the other replicas on the
+//! same database through Postgres `NOTIFY`.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use tokio::sync::{broadcast, mpsc};
+use uuid::Uuid;
+
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Postgres channel events are relayed on between replicas.
+const NOTIFY_CHANNEL: &str = "wirewarden_events";
+
+/// How long to wait before listening again after the connection drops.
+const RELISTEN_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventKind {
+    #[serde(rename = "network.created")]
+    NetworkCreated,
+    #[serde(rename = "network.updated")]
+    NetworkUpdated,
+    #[serde(rename = "network.deleted")]
+    NetworkDeleted,
+    #[serde(rename = "server.created")]
+    ServerCreated,
+    #[serde(rename = "server.updated")]
+    ServerUpdated,
+    #[serde(rename = "server.deleted")]
+    ServerDeleted,
+    #[serde(rename = "server.online")]
+    ServerOnline,
+    #[serde(rename = "server.offline")]
+    ServerOffline,
+    #[serde(rename = "server.token_rotated")]
+    ServerTokenRotated,
+    #[serde(rename = "client.created")]
+    ClientCreated,
+    #[serde(rename = "client.updated")]
+    ClientUpdated,
+    #[serde(rename = "client.deleted")]
+    ClientDeleted,
+    #[serde(rename = "peer.connected")]
+    PeerConnected,
+    #[serde(rename = "peer.disconnected")]
+    PeerDisconnected,
+}
+
+impl EventKind {
+    pub const ALL: [Self; 14] = [
+        Self::NetworkCreated,
+        Self::NetworkUpdated,
+        Self::NetworkDeleted,
+        Self::ServerCreated,
+        Self::ServerUpdated,
+        Self::ServerDeleted,
+        Self::ServerOnline,
+        Self::ServerOffline,
+        Self::ServerTokenRotated,
+        Self::ClientCreated,
+        Self::ClientUpdated,
+        Self::ClientDeleted,
+        Self::PeerConnected,
+        Self::PeerDisconnected,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::NetworkCreated => "network.created",
+            Self::NetworkUpdated => "network.updated",
+            Self::NetworkDeleted => "network.deleted",
+            Self::ServerCreated => "server.created",
+            Self::ServerUpdated => "server.updated",
+            Self::ServerDeleted => "server.deleted",
+            Self::ServerOnline => "server.online",
+            Self::ServerOffline => "server.offline",
+            Self::ServerTokenRotated => "server.token_rotated",
+            Self::ClientCreated => "client.created",
+            Self::ClientUpdated => "client.updated",
+            Self::ClientDeleted => "client.deleted",
+            Self::PeerConnected => "peer.connected",
+            Self::PeerDisconnected => "peer.disconnected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub id: Uuid,
+    pub kind: EventKind,
+    pub network_id: Uuid,
+    pub resource_id: Uuid,
+    pub at: DateTime<Utc>,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, network_id: Uuid, resource_id: Uuid) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            kind,
+            network_id,
+            resource_id,
+            at: Utc::now(),
+        }
+    }
+}
+
+/// An event as sent over `NOTIFY`, tagged with the replica that published it.
+#[derive(Debug, Serialize, Deserialize)]
+struct Relayed {
+    origin: Uuid,
+    event: Event,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    /// Events published by this replica.
+    tx: broadcast::Sender<Event>,
+    /// Events published by this replica and relayed from the others.
+    all: broadcast::Sender<Event>,
+    origin: Uuid,
+    outbox: Arc<OnceLock<mpsc::UnboundedSender<Event>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let (all, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            tx,
+            all,
+            origin: Uuid::new_v4(),
+            outbox: Arc::default(),
+        }
+    }
+
+    /// Publish an event to every current subscriber. Events published while
+    /// nobody is listening are dropped.
+    pub fn publish(&self, kind: EventKind, network_id: Uuid, resource_id: Uuid) {
+        let event = Event::new(kind, network_id, resource_id);
+        tracing::debug!(kind = kind.as_str(), %resource_id, "event published");
+        if let Some(outbox) = self.outbox.get() {
+            let _ = outbox.send(event.clone());
+        }
+        let _ = self.all.send(event.clone());
+        let _ = self.tx.send(event);
+    }
+
+    /// Events published by this replica. Subscribers that act on an event,
+    /// such as webhook delivery, use this so each event is handled once.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+
+    /// Events published by any replica, for subscribers that only watch,
+    /// such as the event stream and daemon config watches.
+    pub fn subscribe_all(&self) -> broadcast::Receiver<Event> {
+        self.all.subscribe()
+    }
+
+    /// Share events with the other replicas on `pool`'s database: publish
+    /// ours with `NOTIFY` and feed theirs to [`subscribe_all`](Self::subscribe_all).
+    /// Returns once listening, so no event published afterwards is missed.
+    pub async fn relay(&self, pool: PgPool) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener.listen(NOTIFY_CHANNEL).await?;
+
+        let (outbox, mut rx) = mpsc::unbounded_channel();
+        if self.outbox.set(outbox).is_err() {
+            tracing::warn!("event relay already running");
+            return Ok(());
+        }
+        let origin = self.origin;
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let payload =
+                    serde_json::to_string(&Relayed { origin, event }).expect("event serializes");
+                let sent = sqlx::query("SELECT pg_notify($1, $2)")
+                    .bind(NOTIFY_CHANNEL)
+                    .bind(payload)
+                    .execute(&pool)
+                    .await;
+                if let Err(e) = sent {
+                    tracing::warn!(error = %e, "failed to relay event");
+                }
+            }
+        });
+
+        let all = self.all.clone();
+        tokio::spawn(async move {
+            loop {
+                let notification = match listener.recv().await {
+                    Ok(notification) => notification,
+                    // The listener reconnects on the next call; events sent
+                    // in between are lost, as with a lagging subscriber.
+                    Err(e) => {
+                        tracing::warn!(error = %e, "lost the event relay connection");
+                        tokio::time::sleep(RELISTEN_DELAY).await;
+                        continue;
+                    }
+                };
+                match serde_json::from_str::<Relayed>(notification.payload()) {
+                    Ok(relayed) if relayed.origin != origin => {
+                        let _ = all.send(relayed.event);
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = %e, "ignoring malformed relayed event"),
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_serializes_as_dotted_name() {
+        for kind in EventKind::ALL {
+            let json = serde_json::to_string(&kind).unwrap();
+            assert_eq!(json, format!("\"{}\"", kind.as_str()));
+        }
+    }
+}
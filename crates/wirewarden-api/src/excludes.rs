@@ -0,0 +1,58 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! CIDRs that full-tunnel clients keep off the VPN, set on a network or
+//! asked for with a config.
+
+use ipnetwork::IpNetwork;
+
+/// Parse `cidrs` into network form, so `192.168.1.7/24` reads back as
+/// `192.168.1.0/24`. Blank entries are skipped.
+pub fn parse<S: AsRef<str>>(cidrs: &[S]) -> Result<Vec<IpNetwork>, String> {
+    let mut parsed = Vec::new();
+    for cidr in cidrs {
+        let cidr = cidr.as_ref().trim();
+        if cidr.is_empty() {
+            continue;
+        }
+        let net = cidr
+            .parse::<IpNetwork>()
+            .map_err(|_| format!("invalid excluded CIDR: {cidr}"))?;
+        let net = IpNetwork::new(net.network(), net.prefix()).unwrap();
+        if !parsed.contains(&net) {
+            parsed.push(net);
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(&["203.0.113.0/24"], Ok(&["203.0.113.0/24"]) ; "plain")]
+    #[test_case(&["192.168.1.7/24"], Ok(&["192.168.1.0/24"]) ; "host bits cleared")]
+    #[test_case(&[" 2001:db8::/32 ", ""], Ok(&["2001:db8::/32"]) ; "trims and skips blanks")]
+    #[test_case(&["1.1.1.1", "1.1.1.1/32"], Ok(&["1.1.1.1/32"]) ; "deduplicates")]
+    #[test_case(&["not a cidr"], Err(()) ; "garbage")]
+    #[test_case(&["10.0.0.0/33"], Err(()) ; "prefix too long")]
+    fn test_parse(cidrs: &[&str], expected: Result<&[&str], ()>) {
+        let result = parse(cidrs)
+            .map(|nets| nets.iter().map(ToString::to_string).collect::<Vec<_>>())
+            .map_err(|_| ());
+        let expected = expected.map(|e| e.iter().map(ToString::to_string).collect::<Vec<_>>());
+        assert_eq!(result, expected);
+    }
+}
@@ -46,6 +46,9 @@ fn extract_auth(req: &HttpRequest) -> Result<AuthUser, ApiError> {
 
     let cookie = req.cookie("token").ok_or(ApiError::Unauthorized)?;
     let claims = validate_token(cookie.value(), &config.jwt_secret)?;
+    if config.require_email_verification && claims.unverified && changes_resources(req) {
+        return Err(ApiError::EmailNotVerified);
+    }
 
     Ok(AuthUser {
         user_id: claims.sub,
@@ -53,6 +56,13 @@ fn extract_auth(req: &HttpRequest) -> Result<AuthUser, ApiError> {
     })
 }
 
+/// Whether `req` may change something outside the caller's own account.
+/// Reads and `/api/auth` stay open to unverified users, so they can still
+/// look around and verify.
+fn changes_resources(req: &HttpRequest) -> bool {
+    !(req.method().is_safe() || req.path().starts_with("/api/auth/"))
+}
+
 #[derive(Debug)]
 pub struct AuthServer(pub WgServer);
 
@@ -0,0 +1,305 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! gRPC endpoint for the daemon protocol, served on its own listener next to
+//! the REST API. Serves the same configs as `GET /api/daemon/config`, from
+//! the same [`DaemonConfigCache`].
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::{self, BoxStream, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{Instant, Interval};
+use tonic::metadata::MetadataKey;
+use tonic::transport::{Identity, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+use wirewarden_types::daemon::{
+    CAPABILITIES_HEADER, Capabilities, DAEMON_VERSION_HEADER, MIN_DAEMON_VERSION_HEADER,
+};
+use wirewarden_types::grpc::daemon_server::{Daemon, DaemonServer};
+use wirewarden_types::grpc::{DaemonConfig, GetConfigRequest};
+use wirewarden_types::version::Version;
+
+use crate::config::GrpcTls;
+use crate::daemon_cache::{DaemonConfigCache, RenderedConfig};
+use crate::db::vpn::{VpnStore, WgServer};
+use crate::error::ApiError;
+use crate::events::{Event, EventBus};
+use crate::routes::daemon::{cached_config, check_in};
+use crate::signing::ConfigSigner;
+
+/// How often an open watch records a check-in and rechecks the config
+/// serial when no event has arrived. Matches the daemon's poll interval.
+const HEARTBEAT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct DaemonService {
+    pub store: VpnStore,
+    pub events: EventBus,
+    pub min_daemon_version: Option<Version>,
+    pub signer: ConfigSigner,
+    /// Shared with the REST endpoints.
+    pub cache: Arc<DaemonConfigCache>,
+}
+
+/// Load the endpoint's certificate chain and key.
+pub fn tls_config(tls: &GrpcTls) -> io::Result<ServerTlsConfig> {
+    let cert = std::fs::read(&tls.cert_path)?;
+    let key = std::fs::read(&tls.key_path)?;
+    Ok(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+}
+
+impl DaemonService {
+    /// Serve on `addr`, over TLS when `tls` is given.
+    pub fn spawn(self, addr: SocketAddr, tls: Option<ServerTlsConfig>) {
+        tokio::spawn(async move {
+            tracing::info!(%addr, tls = tls.is_some(), "starting daemon gRPC endpoint");
+            let mut builder = tonic::transport::Server::builder();
+            if let Some(tls) = tls {
+                builder = match builder.tls_config(tls) {
+                    Ok(builder) => builder,
+                    Err(e) => {
+                        tracing::error!(error = %e, "invalid daemon gRPC TLS configuration");
+                        return;
+                    }
+                };
+            }
+            let result = builder
+                .add_service(DaemonServer::new(self))
+                .serve(addr)
+                .await;
+            if let Err(e) = result {
+                tracing::error!(error = %e, "daemon gRPC endpoint stopped");
+            }
+        });
+    }
+
+    /// Wrap a reply, announcing the minimum daemon release when configured.
+    fn respond<T>(&self, message: T) -> Response<T> {
+        let mut resp = Response::new(message);
+        if let Some(min) = self.min_daemon_version {
+            let key =
+                MetadataKey::from_bytes(MIN_DAEMON_VERSION_HEADER.to_ascii_lowercase().as_bytes())
+                    .expect("header names are valid metadata keys");
+            let value = min
+                .to_string()
+                .parse()
+                .expect("versions are valid metadata");
+            resp.metadata_mut().insert(key, value);
+        }
+        resp
+    }
+
+    async fn authenticate<T>(&self, req: &Request<T>) -> Result<WgServer, Status> {
+        let token = req
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("missing bearer token"))?;
+        self.store
+            .get_server_by_token(token)
+            .await
+            .map_err(|e| status(e.into()))?
+            .ok_or_else(|| Status::unauthenticated("invalid token"))
+    }
+}
+
+/// A REST request header, read from metadata under its lowercased name.
+fn header<'a, T>(req: &'a Request<T>, name: &str) -> Option<&'a str> {
+    req.metadata()
+        .get(name.to_ascii_lowercase().as_str())
+        .and_then(|v| v.to_str().ok())
+}
+
+#[tonic::async_trait]
+impl Daemon for DaemonService {
+    #[tracing::instrument(skip_all)]
+    async fn get_config(
+        &self,
+        request: Request<GetConfigRequest>,
+    ) -> Result<Response<DaemonConfig>, Status> {
+        let server = self.authenticate(&request).await?;
+        let daemon_version = header(&request, DAEMON_VERSION_HEADER);
+        let serial = check_in(&self.store, &self.events, &server, daemon_version)
+            .await
+            .map_err(status)?
+            .config_serial;
+        let caps = Capabilities::from_header(header(&request, CAPABILITIES_HEADER));
+        let (config, rendered) = cached_config(&self.store, &self.cache, server, serial, caps)
+            .await
+            .map_err(status)?;
+        Ok(self.respond(signed(&self.signer, &config, rendered, serial)))
+    }
+
+    type WatchConfigStream = BoxStream<'static, Result<DaemonConfig, Status>>;
+
+    #[tracing::instrument(skip_all)]
+    async fn watch_config(
+        &self,
+        request: Request<GetConfigRequest>,
+    ) -> Result<Response<Self::WatchConfigStream>, Status> {
+        let server = self.authenticate(&request).await?;
+        tracing::info!(server_id = %server.id, "daemon watching config");
+        let watch = Watch {
+            store: self.store.clone(),
+            events: self.events.clone(),
+            signer: self.signer.clone(),
+            cache: self.cache.clone(),
+            rx: self.events.subscribe_all(),
+            heartbeat: tokio::time::interval_at(Instant::now() + HEARTBEAT, HEARTBEAT),
+            server_id: server.id,
+            network_id: server.network_id,
+            caps: Capabilities::from_header(header(&request, CAPABILITIES_HEADER)),
+            daemon_version: header(&request, DAEMON_VERSION_HEADER).map(str::to_string),
+            serial: None,
+            done: false,
+        };
+        let stream = stream::unfold(watch, Watch::next).boxed();
+        Ok(self.respond(stream))
+    }
+}
+
+/// State of one `WatchConfig` stream.
+struct Watch {
+    store: VpnStore,
+    events: EventBus,
+    signer: ConfigSigner,
+    cache: Arc<DaemonConfigCache>,
+    rx: broadcast::Receiver<Event>,
+    heartbeat: Interval,
+    server_id: Uuid,
+    network_id: Uuid,
+    caps: Capabilities,
+    daemon_version: Option<String>,
+    /// Serial of the last config sent.
+    serial: Option<i64>,
+    done: bool,
+}
+
+impl Watch {
+    async fn next(mut self) -> Option<(Result<DaemonConfig, Status>, Self)> {
+        if self.done {
+            return None;
+        }
+        loop {
+            if self.serial.is_some() {
+                self.wait().await;
+            }
+            match self.poll().await {
+                Ok(Some(config)) => return Some((Ok(config), self)),
+                Ok(None) => {}
+                Err(e) => {
+                    // Deleted servers and store failures end the stream; the
+                    // daemon reconnects and sees the error on a fresh call.
+                    self.done = true;
+                    return Some((Err(e), self));
+                }
+            }
+        }
+    }
+
+    /// Wait for the next heartbeat or an event in the server's network.
+    async fn wait(&mut self) {
+        loop {
+            tokio::select! {
+                _ = self.heartbeat.tick() => return,
+                event = self.rx.recv() => match event {
+                    Ok(e) if e.network_id == self.network_id => return,
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(_)) => return,
+                    Err(RecvError::Closed) => {
+                        self.heartbeat.tick().await;
+                        return;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Check in, returning a freshly rendered config if the serial moved.
+    async fn poll(&mut self) -> Result<Option<DaemonConfig>, Status> {
+        // Reloaded each time so renames and port changes are picked up.
+        let server = self
+            .store
+            .get_server(self.server_id)
+            .await
+            .map_err(|e| status(e.into()))?
+            .ok_or_else(|| status(ApiError::NotFound))?;
+        let serial = check_in(
+            &self.store,
+            &self.events,
+            &server,
+            self.daemon_version.as_deref(),
+        )
+        .await
+        .map_err(status)?
+        .config_serial;
+        if self.serial == Some(serial) {
+            return Ok(None);
+        }
+        let (config, rendered) = cached_config(&self.store, &self.cache, server, serial, self.caps)
+            .await
+            .map_err(status)?;
+        self.serial = Some(serial);
+        Ok(Some(signed(&self.signer, &config, rendered, serial)))
+    }
+}
+
+/// The message for `config`, carrying its JSON form and the signature over
+/// it, so daemons verify the same bytes they would over REST.
+fn signed(
+    signer: &ConfigSigner,
+    config: &wirewarden_types::daemon::DaemonConfig,
+    rendered: RenderedConfig,
+    serial: i64,
+) -> DaemonConfig {
+    let signature = signer.sign(config.server.id, serial, &rendered.body);
+    let mut msg = DaemonConfig::from_config(config.clone(), serial);
+    msg.signature = Some(signature);
+    msg.signed_config = Some(rendered.body.to_vec());
+    msg
+}
+
+fn status(e: ApiError) -> Status {
+    match e {
+        ApiError::NotFound => Status::not_found("server not found"),
+        ApiError::Unauthorized => Status::unauthenticated("unauthorized"),
+        ApiError::AclUnsupported => Status::failed_precondition(e.to_string()),
+        ApiError::Validation(msg) => Status::invalid_argument(msg),
+        e => {
+            tracing::error!(error = %e, "daemon gRPC request failed");
+            Status::internal("internal error")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use tonic::Code;
+
+    #[test_case(ApiError::NotFound, Code::NotFound ; "not found")]
+    #[test_case(ApiError::Unauthorized, Code::Unauthenticated ; "unauthorized")]
+    #[test_case(ApiError::AclUnsupported, Code::FailedPrecondition ; "acl unsupported")]
+    #[test_case(ApiError::Validation("too long".into()), Code::InvalidArgument ; "validation")]
+    fn test_status(err: ApiError, code: Code) {
+        assert_eq!(status(err).code(), code);
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! wg-quick hook commands, set on a network and overridden per client.
+//!
+//! wg-quick runs hooks as root on every machine that brings the config up,
+//! so whoever can set them can run commands on every client that installs
+//! its config. Every change is audited, and network-wide hooks, which reach
+//! every client of a network at once, are refused unless the operator sets
+//! `ALLOW_NETWORK_HOOKS`.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::audit::{AuditStore, FieldChanges};
+
+const MAX_HOOK_LEN: usize = 1024;
+
+/// `PostUp`, `PreDown`, and `PostDown` commands for a client's
+/// `[Interface]`. In requests, a present field replaces the current command
+/// and an empty one clears it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_up: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_down: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_down: Option<String>,
+}
+
+impl Hooks {
+    /// These hooks with the fields present in `changes` replaced.
+    pub fn apply(&self, changes: &Hooks) -> Result<Hooks, String> {
+        let field = |current: &Option<String>, change: &Option<String>, name: &str| match change {
+            Some(command) => normalize(command).map_err(|e| format!("{name} {e}")),
+            None => Ok(current.clone()),
+        };
+        Ok(Hooks {
+            post_up: field(&self.post_up, &changes.post_up, "post_up")?,
+            pre_down: field(&self.pre_down, &changes.pre_down, "pre_down")?,
+            post_down: field(&self.post_down, &changes.post_down, "post_down")?,
+        })
+    }
+
+    /// Whether these hooks, read as a request, set any command rather than
+    /// only clearing them.
+    pub fn sets_any(&self) -> bool {
+        [&self.post_up, &self.pre_down, &self.post_down]
+            .into_iter()
+            .flatten()
+            .any(|command| !command.trim().is_empty())
+    }
+
+    /// The hooks in effect for a client: each of its own, else its
+    /// network's.
+    pub fn effective(&self, network: &Hooks) -> Hooks {
+        Hooks {
+            post_up: self.post_up.clone().or_else(|| network.post_up.clone()),
+            pre_down: self.pre_down.clone().or_else(|| network.pre_down.clone()),
+            post_down: self.post_down.clone().or_else(|| network.post_down.clone()),
+        }
+    }
+
+    /// The `[Interface]` keys to write, in the order wg-quick runs them.
+    pub fn lines(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("PostUp", &self.post_up),
+            ("PreDown", &self.pre_down),
+            ("PostDown", &self.post_down),
+        ]
+        .into_iter()
+        .filter_map(|(key, command)| Some((key, command.as_deref()?)))
+    }
+}
+
+/// Record a change of `target_id`'s hooks from `before` to `after` under
+/// `action`. Nothing is recorded when they are equal.
+pub async fn audit_change(
+    audit: &AuditStore,
+    actor_id: Uuid,
+    action: &str,
+    network_id: Uuid,
+    target_id: Uuid,
+    before: &Hooks,
+    after: &Hooks,
+) -> Result<(), sqlx::Error> {
+    let mut changes = FieldChanges::default();
+    changes.field("hooks", before, after);
+    if changes.is_empty() {
+        return Ok(());
+    }
+    audit
+        .record(
+            Some(actor_id),
+            action,
+            Some(network_id),
+            Some(target_id),
+            changes.into_details(),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Trim `command`, mapping blank input to `None` so it clears the field. A
+/// command is one config line, so it cannot hold line breaks.
+fn normalize(command: &str) -> Result<Option<String>, String> {
+    let command = command.trim();
+    if command.chars().count() > MAX_HOOK_LEN {
+        return Err(format!("must be at most {MAX_HOOK_LEN} characters"));
+    }
+    if command.chars().any(char::is_control) {
+        return Err("must be a single line".into());
+    }
+    Ok((!command.is_empty()).then(|| command.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(" iptables -A FORWARD -i %i ", Ok(Some("iptables -A FORWARD -i %i")) ; "trimmed")]
+    #[test_case("  ", Ok(None) ; "blank clears")]
+    #[test_case("true\nrm -rf /", Err(()) ; "line break")]
+    #[test_case("echo\tup", Err(()) ; "control character")]
+    fn test_normalize(input: &str, expected: Result<Option<&str>, ()>) {
+        let expected = expected.map(|e| e.map(String::from));
+        assert_eq!(normalize(input).map_err(|_| ()), expected);
+    }
+
+    #[test]
+    fn test_normalize_rejects_long_commands() {
+        assert!(normalize(&"x".repeat(MAX_HOOK_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_apply_replaces_present_fields() {
+        let current = Hooks {
+            post_up: Some("up".into()),
+            pre_down: Some("pre".into()),
+            post_down: None,
+        };
+        let changes = Hooks {
+            post_up: Some(String::new()),
+            pre_down: None,
+            post_down: Some("down".into()),
+        };
+        let expected = Hooks {
+            post_up: None,
+            pre_down: Some("pre".into()),
+            post_down: Some("down".into()),
+        };
+        assert_eq!(current.apply(&changes).unwrap(), expected);
+    }
+
+    #[test_case(Hooks::default(), false ; "absent")]
+    #[test_case(Hooks { post_up: Some(" ".into()), ..Default::default() }, false ; "clears")]
+    #[test_case(Hooks { pre_down: Some("true".into()), ..Default::default() }, true ; "sets")]
+    fn test_sets_any(hooks: Hooks, expected: bool) {
+        assert_eq!(hooks.sets_any(), expected);
+    }
+
+    #[test]
+    fn test_effective_prefers_own() {
+        let own = Hooks {
+            post_up: Some("client".into()),
+            ..Default::default()
+        };
+        let network = Hooks {
+            post_up: Some("network".into()),
+            post_down: Some("network down".into()),
+            ..Default::default()
+        };
+        let lines: Vec<_> = own
+            .effective(&network)
+            .lines()
+            .map(|(key, command)| format!("{key} = {command}"))
+            .collect();
+        assert_eq!(lines, ["PostUp = client", "PostDown = network down"]);
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Translations of user-facing strings. English is the source language and
+//! the fallback for anything a catalog does not cover.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Es,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    // Errors
+    InvalidCredentials,
+    Unauthorized,
+    UserNotFound,
+    DuplicateUsername,
+    DuplicateEmail,
+    InvalidResetToken,
+    ResetTokenExpired,
+    InvalidVerifyToken,
+    VerifyTokenExpired,
+    EmailNotVerified,
+    ValidationError,
+    NotFound,
+    DuplicateName,
+    OffsetConflict,
+    OffsetOutOfRange,
+    NetworkFull,
+    PortRangeFull,
+    PortInUse,
+    SelfApproval,
+    ChangeAlreadyDecided,
+    AclUnsupported,
+    Internal,
+
+    // Digest email; `{0}`, `{1}`, ... are positional placeholders
+    DigestSubject,
+    DigestHeading,
+    DigestNewDevices,
+    DigestAdded,
+    DigestOfflineServers,
+    DigestLastSeen,
+    DigestNeverSeen,
+    DigestUpcomingRotations,
+
+    // Event notification emails
+    NotifyServerOfflineSubject,
+    NotifyServerOffline,
+    NotifyClientCreatedSubject,
+    NotifyClientCreated,
+    NotifyTokenRotatedSubject,
+    NotifyTokenRotated,
+    NotifyFooter,
+
+    // Password reset email
+    ResetSubject,
+    ResetBody,
+
+    // Email verification
+    VerifySubject,
+    VerifyBody,
+
+    // Generated client config
+    ConfigHeader,
+}
+
+impl Msg {
+    fn en(self) -> &'static str {
+        match self {
+            Self::InvalidCredentials => "invalid credentials",
+            Self::Unauthorized => "unauthorized",
+            Self::UserNotFound => "user not found",
+            Self::DuplicateUsername => "username already taken",
+            Self::DuplicateEmail => "email already taken",
+            Self::InvalidResetToken => "invalid reset token",
+            Self::ResetTokenExpired => "reset token expired",
+            Self::InvalidVerifyToken => "invalid verification token",
+            Self::VerifyTokenExpired => "verification token expired",
+            Self::EmailNotVerified => "verify your email address first",
+            Self::ValidationError => "validation error: {0}",
+            Self::NotFound => "not found",
+            Self::DuplicateName => "name already taken (names are case-insensitive)",
+            Self::OffsetConflict => "address offset conflict",
+            Self::OffsetOutOfRange => "offset out of range",
+            Self::NetworkFull => "no available addresses in this network",
+            Self::PortRangeFull => "no free port in this network's port range",
+            Self::PortInUse => "port already used by another server on this endpoint host",
+            Self::SelfApproval => {
+                "approval requires a second admin or the cooling-off period to elapse"
+            }
+            Self::ChangeAlreadyDecided => "change has already been decided",
+            Self::AclUnsupported => {
+                "the daemon cannot enforce this network's deny rules; upgrade it"
+            }
+            Self::Internal => "internal server error",
+            Self::DigestSubject => "Weekly summary for {0}",
+            Self::DigestHeading => "Network \"{0}\": {1} to {2}",
+            Self::DigestNewDevices => "New devices ({0}):",
+            Self::DigestAdded => "added {0}",
+            Self::DigestOfflineServers => "Offline servers ({0}):",
+            Self::DigestLastSeen => "last seen {0}",
+            Self::DigestNeverSeen => "never seen",
+            Self::DigestUpcomingRotations => "Preshared key rotations in the next {0} days ({1}):",
+            Self::NotifyServerOfflineSubject => "Server {0} is offline",
+            Self::NotifyServerOffline => "Server \"{0}\" in network \"{1}\" went offline at {2}.",
+            Self::NotifyClientCreatedSubject => "New client {0}",
+            Self::NotifyClientCreated => "Client \"{0}\" was added to network \"{1}\" at {2}.",
+            Self::NotifyTokenRotatedSubject => "API token rotated for {0}",
+            Self::NotifyTokenRotated => {
+                "The API token of server \"{0}\" in network \"{1}\" was rotated at {2}. \
+                 Its daemon must be reconnected with the new connect command."
+            }
+            Self::NotifyFooter => "You get this email because of your notification settings.",
+            Self::ResetSubject => "Reset your wirewarden password",
+            Self::ResetBody => {
+                "Someone asked to reset the password of {0}. Open this link within an hour \
+                 to choose a new one:\n\n{1}\n\nIf that was not you, ignore this email; \
+                 your password stays the same."
+            }
+            Self::VerifySubject => "Confirm your wirewarden email address",
+            Self::VerifyBody => {
+                "Welcome to wirewarden, {0}. Open this link within a day to confirm your \
+                 email address:\n\n{1}\n\nIf you did not create this account, ignore this \
+                 email."
+            }
+            Self::ConfigHeader => "Generated by wirewarden. Keep this file private.",
+        }
+    }
+
+    fn de(self) -> Option<&'static str> {
+        Some(match self {
+            Self::InvalidCredentials => "ungültige Anmeldedaten",
+            Self::Unauthorized => "nicht autorisiert",
+            Self::UserNotFound => "Benutzer nicht gefunden",
+            Self::DuplicateUsername => "Benutzername bereits vergeben",
+            Self::DuplicateEmail => "E-Mail-Adresse bereits vergeben",
+            Self::InvalidResetToken => "ungültiger Rücksetz-Token",
+            Self::ResetTokenExpired => "Rücksetz-Token abgelaufen",
+            Self::InvalidVerifyToken => "ungültiger Bestätigungs-Token",
+            Self::VerifyTokenExpired => "Bestätigungs-Token abgelaufen",
+            Self::EmailNotVerified => "bestätigen Sie zuerst Ihre E-Mail-Adresse",
+            Self::ValidationError => "Validierungsfehler: {0}",
+            Self::NotFound => "nicht gefunden",
+            Self::DuplicateName => "Name bereits vergeben (Groß-/Kleinschreibung wird ignoriert)",
+            Self::OffsetConflict => "Adress-Offset bereits belegt",
+            Self::OffsetOutOfRange => "Offset außerhalb des gültigen Bereichs",
+            Self::NetworkFull => "keine freien Adressen in diesem Netzwerk",
+            Self::PortRangeFull => "kein freier Port im Portbereich dieses Netzwerks",
+            Self::PortInUse => "Port wird auf diesem Endpunkt-Host bereits von einem anderen Server verwendet",
+            Self::SelfApproval => {
+                "Freigabe erfordert einen zweiten Admin oder den Ablauf der Wartezeit"
+            }
+            Self::ChangeAlreadyDecided => "über die Änderung wurde bereits entschieden",
+            Self::AclUnsupported => {
+                "der Daemon kann die Sperrregeln dieses Netzwerks nicht durchsetzen; bitte aktualisieren"
+            }
+            Self::Internal => "interner Serverfehler",
+            Self::DigestSubject => "Wochenübersicht für {0}",
+            Self::DigestHeading => "Netzwerk \"{0}\": {1} bis {2}",
+            Self::DigestNewDevices => "Neue Geräte ({0}):",
+            Self::DigestAdded => "hinzugefügt {0}",
+            Self::DigestOfflineServers => "Offline-Server ({0}):",
+            Self::DigestLastSeen => "zuletzt gesehen {0}",
+            Self::DigestNeverSeen => "nie gesehen",
+            Self::DigestUpcomingRotations => "PSK-Rotationen in den nächsten {0} Tagen ({1}):",
+            Self::NotifyServerOfflineSubject => "Server {0} ist offline",
+            Self::NotifyServerOffline => {
+                "Server \"{0}\" im Netzwerk \"{1}\" ist seit {2} offline."
+            }
+            Self::NotifyClientCreatedSubject => "Neuer Client {0}",
+            Self::NotifyClientCreated => {
+                "Client \"{0}\" wurde am {2} zum Netzwerk \"{1}\" hinzugefügt."
+            }
+            Self::NotifyTokenRotatedSubject => "API-Token für {0} erneuert",
+            Self::NotifyTokenRotated => {
+                "Das API-Token von Server \"{0}\" im Netzwerk \"{1}\" wurde am {2} erneuert. \
+                 Sein Daemon muss mit dem neuen Verbindungsbefehl neu verbunden werden."
+            }
+            Self::NotifyFooter => {
+                "Sie erhalten diese E-Mail aufgrund Ihrer Benachrichtigungseinstellungen."
+            }
+            Self::ResetSubject => "Ihr wirewarden-Passwort zurücksetzen",
+            Self::ResetBody => {
+                "Jemand hat angefordert, das Passwort von {0} zurückzusetzen. Öffnen Sie \
+                 innerhalb einer Stunde diesen Link, um ein neues zu wählen:\n\n{1}\n\n\
+                 Falls Sie das nicht waren, ignorieren Sie diese E-Mail; Ihr Passwort bleibt \
+                 unverändert."
+            }
+            Self::VerifySubject => "Bestätigen Sie Ihre E-Mail-Adresse für wirewarden",
+            Self::VerifyBody => {
+                "Willkommen bei wirewarden, {0}. Öffnen Sie innerhalb eines Tages diesen \
+                 Link, um Ihre E-Mail-Adresse zu bestätigen:\n\n{1}\n\nFalls Sie dieses \
+                 Konto nicht angelegt haben, ignorieren Sie diese E-Mail."
+            }
+            Self::ConfigHeader => "Erzeugt von wirewarden. Diese Datei vertraulich behandeln.",
+        })
+    }
+
+    fn es(self) -> Option<&'static str> {
+        Some(match self {
+            Self::InvalidCredentials => "credenciales no válidas",
+            Self::Unauthorized => "no autorizado",
+            Self::UserNotFound => "usuario no encontrado",
+            Self::DuplicateUsername => "el nombre de usuario ya está en uso",
+            Self::DuplicateEmail => "el correo electrónico ya está en uso",
+            Self::InvalidResetToken => "token de restablecimiento no válido",
+            Self::ResetTokenExpired => "el token de restablecimiento ha caducado",
+            Self::InvalidVerifyToken => "token de verificación no válido",
+            Self::VerifyTokenExpired => "el token de verificación ha caducado",
+            Self::EmailNotVerified => "primero verifique su dirección de correo electrónico",
+            Self::ValidationError => "error de validación: {0}",
+            Self::NotFound => "no encontrado",
+            Self::DuplicateName => "el nombre ya está en uso (sin distinguir mayúsculas)",
+            Self::OffsetConflict => "conflicto de desplazamiento de dirección",
+            Self::OffsetOutOfRange => "desplazamiento fuera de rango",
+            Self::NetworkFull => "no hay direcciones disponibles en esta red",
+            Self::PortRangeFull => "no hay puertos libres en el rango de puertos de esta red",
+            Self::PortInUse => "el puerto ya lo usa otro servidor en este host de endpoint",
+            Self::SelfApproval => {
+                "la aprobación requiere un segundo administrador o que termine el periodo de espera"
+            }
+            Self::ChangeAlreadyDecided => "el cambio ya fue decidido",
+            Self::AclUnsupported => {
+                "el daemon no puede aplicar las reglas de denegación de esta red; actualícelo"
+            }
+            Self::Internal => "error interno del servidor",
+            Self::DigestSubject => "Resumen semanal de {0}",
+            Self::DigestHeading => "Red \"{0}\": del {1} al {2}",
+            Self::DigestNewDevices => "Dispositivos nuevos ({0}):",
+            Self::DigestAdded => "añadido {0}",
+            Self::DigestOfflineServers => "Servidores desconectados ({0}):",
+            Self::DigestLastSeen => "visto por última vez {0}",
+            Self::DigestNeverSeen => "nunca visto",
+            Self::DigestUpcomingRotations => {
+                "Rotaciones de claves precompartidas en los próximos {0} días ({1}):"
+            }
+            Self::NotifyServerOfflineSubject => "El servidor {0} está desconectado",
+            Self::NotifyServerOffline => {
+                "El servidor \"{0}\" de la red \"{1}\" se desconectó el {2}."
+            }
+            Self::NotifyClientCreatedSubject => "Nuevo cliente {0}",
+            Self::NotifyClientCreated => {
+                "El cliente \"{0}\" se añadió a la red \"{1}\" el {2}."
+            }
+            Self::NotifyTokenRotatedSubject => "Token de API renovado para {0}",
+            Self::NotifyTokenRotated => {
+                "El token de API del servidor \"{0}\" de la red \"{1}\" se renovó el {2}. \
+                 Su daemon debe volver a conectarse con el nuevo comando de conexión."
+            }
+            Self::NotifyFooter => {
+                "Recibe este correo por su configuración de notificaciones."
+            }
+            Self::ResetSubject => "Restablezca su contraseña de wirewarden",
+            Self::ResetBody => {
+                "Alguien pidió restablecer la contraseña de {0}. Abra este enlace en menos \
+                 de una hora para elegir una nueva:\n\n{1}\n\nSi no fue usted, ignore este \
+                 correo; su contraseña no cambia."
+            }
+            Self::VerifySubject => "Confirme su correo electrónico de wirewarden",
+            Self::VerifyBody => {
+                "Bienvenido a wirewarden, {0}. Abra este enlace en menos de un día para \
+                 confirmar su dirección de correo electrónico:\n\n{1}\n\nSi no creó esta \
+                 cuenta, ignore este correo."
+            }
+            Self::ConfigHeader => "Generado por wirewarden. Mantenga este archivo en privado.",
+        })
+    }
+}
+
+impl Locale {
+    /// Look up `msg`, falling back to English.
+    pub fn text(self, msg: Msg) -> &'static str {
+        let translated = match self {
+            Self::En => None,
+            Self::De => msg.de(),
+            Self::Es => msg.es(),
+        };
+        translated.unwrap_or_else(|| msg.en())
+    }
+
+    /// Look up `msg` and substitute positional `{N}` placeholders.
+    pub fn format(self, msg: Msg, args: &[&dyn std::fmt::Display]) -> String {
+        args.iter()
+            .enumerate()
+            .fold(self.text(msg).to_string(), |out, (i, arg)| {
+                out.replace(&format!("{{{i}}}"), &arg.to_string())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(Locale::En, "Weekly summary for home" ; "english")]
+    #[test_case(Locale::De, "Wochenübersicht für home" ; "german")]
+    #[test_case(Locale::Es, "Resumen semanal de home" ; "spanish")]
+    fn test_format(locale: Locale, expected: &str) {
+        assert_eq!(locale.format(Msg::DigestSubject, &[&"home"]), expected);
+    }
+
+    #[test_case("\"de\"", Locale::De ; "german")]
+    #[test_case("\"en\"", Locale::En ; "english")]
+    fn test_parse(json: &str, expected: Locale) {
+        assert_eq!(serde_json::from_str::<Locale>(json).unwrap(), expected);
+    }
+}
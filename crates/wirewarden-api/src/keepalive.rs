@@ -0,0 +1,59 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Persistent keepalives, set on a network and overridden per server or
+//! client.
+
+const MAX_KEEPALIVE: i32 = 65535;
+
+/// Check a keepalive override in seconds. Zero turns keepalive off for the
+/// peer, so clearing the override takes a negative value, mapped to `None`.
+pub fn normalize(keepalive: i32) -> Result<Option<i32>, String> {
+    if keepalive < 0 {
+        return Ok(None);
+    }
+    if keepalive > MAX_KEEPALIVE {
+        return Err(format!("persistent_keepalive must be at most {MAX_KEEPALIVE}"));
+    }
+    Ok(Some(keepalive))
+}
+
+/// The override on the link between a peer and a server: the peer's own,
+/// else the server's. `None` leaves the network's keepalive.
+pub fn link(peer: Option<i32>, server: Option<i32>) -> Option<i32> {
+    peer.or(server)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(-1, Ok(None) ; "negative clears")]
+    #[test_case(0, Ok(Some(0)) ; "zero turns off")]
+    #[test_case(25, Ok(Some(25)) ; "typical")]
+    #[test_case(65535, Ok(Some(65535)) ; "maximum")]
+    #[test_case(65536, Err(()) ; "too large")]
+    fn test_normalize(keepalive: i32, expected: Result<Option<i32>, ()>) {
+        assert_eq!(normalize(keepalive).map_err(|_| ()), expected);
+    }
+
+    #[test_case(Some(25), Some(60), Some(25) ; "peer wins")]
+    #[test_case(None, Some(60), Some(60) ; "server default")]
+    #[test_case(Some(0), Some(60), Some(0) ; "peer turns off")]
+    #[test_case(None, None, None ; "unset")]
+    fn test_link(peer: Option<i32>, server: Option<i32>, expected: Option<i32>) {
+        assert_eq!(link(peer, server), expected);
+    }
+}
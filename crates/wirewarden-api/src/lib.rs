@@ -0,0 +1,200 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg_attr(test, feature(test))]
+
+//! The wirewarden management API. The binary in `main.rs` wires this up
+//! from the environment; [`AppState`] lets tests build the same app around
+//! their own database.
+
+pub mod access;
+pub mod acl;
+pub mod auth;
+pub mod changes;
+pub mod config;
+pub mod connections;
+pub mod csv;
+pub mod daemon_cache;
+pub mod db;
+pub mod digest;
+pub mod dns_zone;
+pub mod endpoint_template;
+pub mod error;
+pub mod events;
+pub mod excludes;
+pub mod extract;
+pub mod grpc;
+pub mod hooks;
+pub mod i18n;
+pub mod keepalive;
+pub mod logging;
+pub mod mailer;
+pub mod middleware;
+pub mod mtu;
+pub mod names;
+pub mod notes;
+pub mod notifier;
+pub mod pagination;
+pub mod policy_routing;
+pub mod rate_limit;
+pub mod routes;
+pub mod scheduler;
+pub mod search_domains;
+pub mod signing;
+pub mod tags;
+pub mod usage;
+pub mod webhooks;
+
+use std::time::Duration;
+
+use actix_web::{HttpResponse, web};
+use sqlx::PgPool;
+use webauthn_rs::Webauthn;
+
+use crate::config::Config;
+use crate::daemon_cache::DaemonConfigCache;
+use crate::db::activity::ActivityStore;
+use crate::db::approval::ApprovalStore;
+use crate::db::audit::AuditStore;
+use crate::db::connection::ConnectionStore;
+use crate::db::digest::DigestStore;
+use crate::db::log_settings::LogSettingsStore;
+use crate::db::notification::NotificationStore;
+use crate::db::report::ReportStore;
+use crate::db::schedule::ScheduleStore;
+use crate::db::usage::UsageStore;
+use crate::db::user::UserStore;
+use crate::db::vpn::VpnStore;
+use crate::db::webauthn::ChallengeStore;
+use crate::db::webhook::WebhookStore;
+use crate::events::EventBus;
+use crate::logging::LogControl;
+use crate::mailer::{MailError, Mailer};
+use crate::signing::ConfigSigner;
+
+/// Everything the HTTP handlers share. Cheap to clone; clones share stores,
+/// caches and the event bus.
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: web::Data<PgPool>,
+    pub config: web::Data<Config>,
+    pub users: web::Data<UserStore>,
+    pub webauthn: web::Data<Webauthn>,
+    pub challenges: web::Data<ChallengeStore>,
+    pub vpn: web::Data<VpnStore>,
+    pub audit: web::Data<AuditStore>,
+    pub approvals: web::Data<ApprovalStore>,
+    pub schedules: web::Data<ScheduleStore>,
+    pub webhooks: web::Data<WebhookStore>,
+    pub events: web::Data<EventBus>,
+    pub digests: web::Data<DigestStore>,
+    pub daemon_cache: web::Data<DaemonConfigCache>,
+    pub signer: web::Data<ConfigSigner>,
+    pub log_control: web::Data<LogControl>,
+    pub log_settings: web::Data<LogSettingsStore>,
+    pub reports: web::Data<ReportStore>,
+    pub usage: web::Data<UsageStore>,
+    pub connections: web::Data<ConnectionStore>,
+    pub activity: web::Data<ActivityStore>,
+    pub notifications: web::Data<NotificationStore>,
+    pub mailer: web::Data<Mailer>,
+}
+
+impl AppState {
+    pub fn new(pool: PgPool, config: Config, log_control: LogControl) -> Result<Self, MailError> {
+        let webauthn = db::webauthn::build_webauthn(&config);
+        let mailer = Mailer::from_config(&config)?;
+        let vpn = VpnStore::new(
+            pool.clone(),
+            config.wg_key_secret,
+            config.key_cache_capacity,
+            Duration::from_secs(config.server_token_cache_secs),
+        );
+        Ok(Self {
+            users: web::Data::new(UserStore::new(pool.clone())),
+            webauthn: web::Data::new(webauthn),
+            challenges: web::Data::new(ChallengeStore::new(pool.clone())),
+            vpn: web::Data::new(vpn),
+            audit: web::Data::new(AuditStore::new(pool.clone())),
+            approvals: web::Data::new(ApprovalStore::new(pool.clone())),
+            schedules: web::Data::new(ScheduleStore::new(pool.clone())),
+            webhooks: web::Data::new(WebhookStore::new(pool.clone())),
+            events: web::Data::new(EventBus::new()),
+            digests: web::Data::new(DigestStore::new(pool.clone())),
+            daemon_cache: web::Data::new(DaemonConfigCache::default()),
+            signer: web::Data::new(ConfigSigner::from_secret(&config.wg_key_secret)),
+            log_control: web::Data::new(log_control),
+            log_settings: web::Data::new(LogSettingsStore::new(pool.clone())),
+            reports: web::Data::new(ReportStore::new(pool.clone())),
+            usage: web::Data::new(UsageStore::new(pool.clone())),
+            connections: web::Data::new(ConnectionStore::new(pool.clone())),
+            activity: web::Data::new(ActivityStore::new(pool.clone())),
+            notifications: web::Data::new(NotificationStore::new(pool.clone())),
+            mailer: web::Data::new(mailer),
+            config: web::Data::new(config),
+            pool: web::Data::new(pool),
+        })
+    }
+
+    /// Register the shared state and every route. Middleware is left to the
+    /// caller's `App`.
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(self.pool.clone())
+            .app_data(self.config.clone())
+            .app_data(self.users.clone())
+            .app_data(self.webauthn.clone())
+            .app_data(self.challenges.clone())
+            .app_data(self.vpn.clone())
+            .app_data(self.audit.clone())
+            .app_data(self.approvals.clone())
+            .app_data(self.schedules.clone())
+            .app_data(self.webhooks.clone())
+            .app_data(self.events.clone())
+            .app_data(self.digests.clone())
+            .app_data(self.daemon_cache.clone())
+            .app_data(self.signer.clone())
+            .app_data(self.log_control.clone())
+            .app_data(self.log_settings.clone())
+            .app_data(self.reports.clone())
+            .app_data(self.usage.clone())
+            .app_data(self.connections.clone())
+            .app_data(self.activity.clone())
+            .app_data(self.notifications.clone())
+            .app_data(self.mailer.clone())
+            .route("/health", web::get().to(health))
+            .configure(routes::auth::configure)
+            .configure(routes::networks::configure)
+            .configure(routes::servers::configure)
+            .configure(routes::clients::configure)
+            .configure(routes::server_routes::configure)
+            .configure(routes::client_routes::configure)
+            .configure(routes::acl::configure)
+            .configure(routes::daemon::configure)
+            .configure(routes::approvals::configure)
+            .configure(routes::audit::configure)
+            .configure(routes::connections::configure)
+            .configure(routes::activity::configure)
+            .configure(routes::schedules::configure)
+            .configure(routes::reports::configure)
+            .configure(routes::search::configure)
+            .configure(routes::status_page::configure)
+            .configure(routes::tools::configure)
+            .configure(routes::webhooks::configure)
+            .configure(routes::events::configure);
+    }
+}
+
+async fn health() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
@@ -0,0 +1,198 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Runtime control of the log filter and request log sampling, so production
+//! can be debugged without a restart. The settings live in the database and
+//! every replica applies them on its scheduler tick.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rand::Rng;
+use tracing::{error, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry, fmt, reload};
+
+use crate::db::log_settings::LogSettings;
+
+/// Used when `RUST_LOG` is unset or invalid.
+const DEFAULT_FILTER: &str = "info";
+
+/// Longest filter accepted from the API; real directive lists are far shorter.
+const MAX_FILTER_LEN: usize = 1024;
+
+/// The filter and sample rate in effect.
+#[derive(Debug, Clone, PartialEq)]
+struct Active {
+    filter: String,
+    request_sample_rate: f64,
+}
+
+/// Handle to the process's tracing subscriber. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct LogControl {
+    handle: reload::Handle<EnvFilter, Registry>,
+    default_filter: String,
+    /// `f64` bits, read by the request logger on every request.
+    sample_rate: Arc<AtomicU64>,
+    active: Arc<Mutex<Active>>,
+}
+
+impl LogControl {
+    /// Install the global subscriber, filtered by `RUST_LOG` until settings
+    /// are applied.
+    pub fn init() -> Self {
+        let (filter, control) = Self::layer();
+
+        #[cfg(distribute)]
+        {
+            tracing_subscriber::registry()
+                .with(filter)
+                .with(fmt::layer().json())
+                .init();
+        }
+
+        #[cfg(not(distribute))]
+        {
+            tracing_subscriber::registry()
+                .with(filter)
+                .with(fmt::layer().pretty())
+                .init();
+        }
+
+        control
+    }
+
+    /// The reloadable filter and its control, for callers that install their
+    /// own subscriber, such as test harnesses. Settings applied after the
+    /// layer is dropped only log an error.
+    pub fn layer() -> (reload::Layer<EnvFilter, Registry>, Self) {
+        let default_filter = std::env::var("RUST_LOG")
+            .ok()
+            .filter(|f| EnvFilter::try_new(f).is_ok())
+            .unwrap_or_else(|| DEFAULT_FILTER.to_owned());
+        let (filter, handle) = reload::Layer::new(EnvFilter::new(&default_filter));
+        let control = Self {
+            handle,
+            active: Arc::new(Mutex::new(Active {
+                filter: default_filter.clone(),
+                request_sample_rate: 1.0,
+            })),
+            default_filter,
+            sample_rate: Arc::new(AtomicU64::new(1.0f64.to_bits())),
+        };
+        (filter, control)
+    }
+
+    /// The filter used when no settings are stored.
+    pub fn default_filter(&self) -> &str {
+        &self.default_filter
+    }
+
+    /// Switch to `settings`, or back to the defaults for `None`. Does nothing
+    /// if they are already in effect.
+    pub fn apply(&self, settings: Option<&LogSettings>) {
+        let next = match settings {
+            Some(s) => Active {
+                filter: s.filter.clone(),
+                request_sample_rate: s.request_sample_rate,
+            },
+            None => Active {
+                filter: self.default_filter.clone(),
+                request_sample_rate: 1.0,
+            },
+        };
+
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if *active == next {
+            return;
+        }
+        let filter = match EnvFilter::try_new(&next.filter) {
+            Ok(filter) => filter,
+            Err(e) => {
+                error!(filter = %next.filter, error = %e, "stored log filter is invalid");
+                return;
+            }
+        };
+        if let Err(e) = self.handle.reload(filter) {
+            error!(error = %e, "failed to change log filter");
+            return;
+        }
+        self.sample_rate
+            .store(next.request_sample_rate.to_bits(), Ordering::Relaxed);
+        warn!(
+            filter = %next.filter,
+            request_sample_rate = next.request_sample_rate,
+            "log settings changed"
+        );
+        *active = next;
+    }
+
+    /// Whether the request logger should record a request. Server errors are
+    /// always recorded.
+    pub fn sample_request(&self, status: u16) -> bool {
+        if status >= 500 {
+            return true;
+        }
+        let rate = f64::from_bits(self.sample_rate.load(Ordering::Relaxed));
+        rate >= 1.0 || (rate > 0.0 && rand::thread_rng().gen_bool(rate))
+    }
+}
+
+/// Check settings submitted through the API before they are stored, so a
+/// typo cannot leave every replica failing to apply them.
+pub fn validate(filter: &str, request_sample_rate: f64) -> Result<(), String> {
+    if filter.trim().is_empty() {
+        return Err("filter must not be empty".into());
+    }
+    if filter.len() > MAX_FILTER_LEN {
+        return Err(format!("filter must be at most {MAX_FILTER_LEN} characters"));
+    }
+    if let Err(e) = EnvFilter::try_new(filter) {
+        return Err(format!("invalid filter: {e}"));
+    }
+    if !(0.0..=1.0).contains(&request_sample_rate) {
+        return Err("request_sample_rate must be between 0 and 1".into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("info" ; "level")]
+    #[test_case("info,wirewarden_api=debug" ; "per target")]
+    #[test_case("warn,sqlx=trace,wirewarden_api::grpc=debug" ; "module path")]
+    fn test_validate(filter: &str) {
+        assert!(validate(filter, 0.5).is_ok());
+    }
+
+    #[test_case("", 1.0 ; "empty filter")]
+    #[test_case("  ", 1.0 ; "blank filter")]
+    #[test_case("wirewarden_api=loud", 1.0 ; "bad level")]
+    #[test_case("info", -0.1 ; "negative rate")]
+    #[test_case("info", 1.5 ; "rate above one")]
+    #[test_case("info", f64::NAN ; "nan rate")]
+    fn test_validate_rejects(filter: &str, rate: f64) {
+        assert!(validate(filter, rate).is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_long_filters() {
+        assert!(validate(&"a".repeat(MAX_FILTER_LEN + 1), 1.0).is_err());
+    }
+}
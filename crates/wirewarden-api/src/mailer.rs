@@ -0,0 +1,88 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Outbound email. Messages go through SMTP when configured. Otherwise only
+//! their recipient and subject are logged, so development setups need no
+//! mail server; `MAIL_LOG_BODIES` adds the body at debug level, reset and
+//! verification links included.
+
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use thiserror::Error;
+
+use crate::config::Config;
+
+#[derive(Debug, Error)]
+pub enum MailError {
+    #[error("invalid address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+
+    #[error("failed to build message: {0}")]
+    Message(#[from] lettre::error::Error),
+
+    #[error("smtp error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+    from: Mailbox,
+    log_bodies: bool,
+}
+
+impl Mailer {
+    pub fn from_config(config: &Config) -> Result<Self, MailError> {
+        let from = config.mail_from.parse()?;
+        let transport = match &config.smtp {
+            Some(smtp) => {
+                let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?
+                    .port(smtp.port);
+                if let (Some(user), Some(pass)) = (&smtp.username, &smtp.password) {
+                    builder = builder.credentials(Credentials::new(user.clone(), pass.clone()));
+                }
+                Some(builder.build())
+            }
+            None => None,
+        };
+        Ok(Self {
+            transport,
+            from,
+            log_bodies: config.mail_log_bodies,
+        })
+    }
+
+    #[tracing::instrument(skip(self, body))]
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), MailError> {
+        let Some(transport) = &self.transport else {
+            tracing::info!(%to, %subject, "smtp not configured; mail not sent");
+            if self.log_bodies {
+                tracing::debug!(%to, %body, "unsent mail body");
+            }
+            return Ok(());
+        };
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)?;
+        transport.send(message).await?;
+        tracing::info!(%to, %subject, "mail sent");
+        Ok(())
+    }
+}
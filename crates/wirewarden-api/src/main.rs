@@ -12,20 +12,14 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-mod auth;
-mod config;
-mod db;
-mod error;
-mod extract;
-mod middleware;
-mod routes;
-
-use actix_web::{App, HttpResponse, HttpServer, web};
+use actix_web::{App, HttpServer};
+use sqlx::PgPool;
 use tracing::{info, warn};
 
-use crate::config::Config;
-use crate::db::user::UserStore;
-use crate::db::vpn::VpnStore;
+use wirewarden_api::config::{self, Config, MigrateMode};
+use wirewarden_api::db::user::UserStore;
+use wirewarden_api::logging::LogControl;
+use wirewarden_api::{AppState, db, grpc, middleware, notifier, scheduler, webhooks};
 
 async fn seed_admin(store: &UserStore) {
     let empty = store.is_empty().await.expect("failed to check user table");
@@ -35,10 +29,15 @@ async fn seed_admin(store: &UserStore) {
 
     let password: String = uuid::Uuid::new_v4().to_string();
 
-    store
+    let admin = store
         .create("admin", "Administrator", "admin@localhost", &password)
         .await
         .expect("failed to create admin user");
+    // Nobody reads admin@localhost; the operator set this account up.
+    store
+        .mark_verified(admin.id)
+        .await
+        .expect("failed to verify admin user");
 
     std::fs::write(".admin_pw.txt", &password).expect("failed to write .admin_pw.txt");
 
@@ -46,85 +45,104 @@ async fn seed_admin(store: &UserStore) {
     warn!("change the admin password and delete .admin_pw.txt");
 }
 
-fn init_tracing() {
-    use tracing_subscriber::{EnvFilter, fmt};
-
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-
-    #[cfg(distribute)]
-    {
-        fmt().json().with_env_filter(filter).init();
-    }
-
-    #[cfg(not(distribute))]
-    {
-        fmt().pretty().with_env_filter(filter).init();
-    }
-}
-
-async fn health() -> HttpResponse {
-    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+async fn pending_migrations(pool: &PgPool) -> Vec<db::MigrationStatus> {
+    db::migration_status(pool)
+        .await
+        .expect("failed to read migration status")
+        .into_iter()
+        .filter(|m| m.state == db::MigrationState::Pending)
+        .collect()
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenvy::dotenv().ok();
-    init_tracing();
+    let log_control = LogControl::init();
+
+    let mode = MigrateMode::from_args(std::env::args().skip(1)).expect("invalid arguments");
+    match mode {
+        MigrateMode::Only => {
+            let url = config::database_url().expect("failed to load configuration");
+            db::migrate(&db::create_pool(&url).await).await;
+            info!("database migrations applied");
+            return Ok(());
+        }
+        MigrateMode::DryRun => {
+            let url = config::database_url().expect("failed to load configuration");
+            let pending = pending_migrations(&db::create_pool(&url).await).await;
+            for m in &pending {
+                println!("{} {}", m.version, m.description);
+            }
+            info!(count = pending.len(), "pending migrations listed");
+            return Ok(());
+        }
+        MigrateMode::Run | MigrateMode::Skip => {}
+    }
 
     let config = Config::from_env().expect("failed to load configuration");
     info!(addr = %config.bind_addr, "starting wirewarden-api");
 
     let pool = db::create_pool(&config.database_url).await;
-    db::migrate(&pool).await;
-    info!("database migrations applied");
-
-    let user_store = UserStore::new(pool.clone());
-    seed_admin(&user_store).await;
-    let webauthn = db::webauthn::build_webauthn(&config);
-    let challenge_store = db::webauthn::ChallengeStore::new(pool.clone());
-    let vpn_store = VpnStore::new(pool.clone(), config.wg_key_secret);
-    vpn_store
+    if mode == MigrateMode::Skip {
+        let pending = pending_migrations(&pool).await;
+        if !pending.is_empty() {
+            warn!(count = pending.len(), "skipping pending database migrations");
+        }
+    } else {
+        db::migrate(&pool).await;
+        info!("database migrations applied");
+    }
+
+    let state =
+        AppState::new(pool.clone(), config, log_control).expect("invalid mail configuration");
+    match state.log_settings.get().await {
+        Ok(settings) => state.log_control.apply(settings.as_ref()),
+        Err(e) => warn!(error = %e, "failed to load log settings"),
+    }
+
+    seed_admin(&state.users).await;
+    state
+        .vpn
         .backfill_psks()
         .await
         .expect("failed to backfill preshared keys");
 
-    {
-        let cs = challenge_store.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
-            loop {
-                interval.tick().await;
-                if let Err(e) = cs.cleanup().await {
-                    tracing::warn!(error = %e, "webauthn challenge cleanup failed");
-                }
-            }
+    let bind = state.config.bind_addr.clone();
+
+    state
+        .events
+        .relay(state.pool.get_ref().clone())
+        .await
+        .expect("failed to listen for events from other replicas");
+    webhooks::WebhookDispatcher::new(state.webhooks.get_ref().clone()).spawn(&state.events);
+    notifier::Notifier::new(
+        state.notifications.get_ref().clone(),
+        state.vpn.get_ref().clone(),
+        state.mailer.get_ref().clone(),
+    )
+    .spawn(&state.events);
+
+    scheduler::Scheduler::new(&state).spawn();
+
+    if let Some(addr) = state.config.grpc_bind_addr {
+        let tls = state.config.grpc_tls.as_ref().map(|tls| {
+            grpc::tls_config(tls).expect("failed to read the gRPC TLS certificate or key")
         });
+        grpc::DaemonService {
+            store: state.vpn.get_ref().clone(),
+            events: state.events.get_ref().clone(),
+            min_daemon_version: state.config.min_daemon_version,
+            signer: state.signer.get_ref().clone(),
+            cache: state.daemon_cache.clone().into_inner(),
+        }
+        .spawn(addr, tls);
     }
 
-    let bind = config.bind_addr.clone();
-
-    let config_data = web::Data::new(config);
-    let store_data = web::Data::new(user_store);
-    let webauthn_data = web::Data::new(webauthn);
-    let challenge_data = web::Data::new(challenge_store);
-    let vpn_data = web::Data::new(vpn_store);
-
     HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(pool.clone()))
-            .app_data(config_data.clone())
-            .app_data(store_data.clone())
-            .app_data(webauthn_data.clone())
-            .app_data(challenge_data.clone())
-            .app_data(vpn_data.clone())
+            .wrap(middleware::Localize)
             .wrap(middleware::RequestLogger)
-            .route("/health", web::get().to(health))
-            .configure(routes::auth::configure)
-            .configure(routes::networks::configure)
-            .configure(routes::servers::configure)
-            .configure(routes::clients::configure)
-            .configure(routes::server_routes::configure)
-            .configure(routes::daemon::configure)
+            .configure(|cfg| state.configure(cfg))
     })
     .bind(&bind)?
     .run()
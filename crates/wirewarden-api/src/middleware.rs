@@ -17,8 +17,42 @@ use std::pin::Pin;
 
 use actix_web::body::MessageBody;
 use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
-use actix_web::body::BodySize;
+use actix_web::body::{BodySize, EitherBody};
+use actix_web::http::Uri;
+use actix_web::web::Data;
+use actix_web::{HttpResponse, ResponseError};
 use tracing::info;
+use wirewarden_types::redact::Redacted;
+
+use crate::auth::validate_token;
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::i18n::Locale;
+use crate::logging::LogControl;
+
+/// Query parameters named with any of these carry credentials, and their
+/// values are left out of the request log.
+const SECRET_PARAMS: &[&str] = &["token", "secret", "password"];
+
+/// `uri` as the request log shows it, with secret query values redacted.
+fn loggable_uri(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.path().to_string();
+    };
+    let query: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) if is_secret_param(key) => format!("{key}={Redacted}"),
+            _ => pair.to_string(),
+        })
+        .collect();
+    format!("{}?{}", uri.path(), query.join("&"))
+}
+
+fn is_secret_param(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_PARAMS.iter().any(|secret| key.contains(secret))
+}
 
 pub struct RequestLogger;
 
@@ -62,7 +96,7 @@ where
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let method = req.method().clone();
-        let uri = req.uri().to_string();
+        let uri = loggable_uri(req.uri());
         let remote_ip = req
             .connection_info()
             .peer_addr()
@@ -75,6 +109,7 @@ where
             .and_then(|v| v.parse::<u64>().ok())
             .unwrap_or(0);
 
+        let log_control = req.app_data::<Data<LogControl>>().cloned();
         let start = std::time::Instant::now();
         let fut = self.service.call(req);
 
@@ -82,6 +117,9 @@ where
             let res = fut.await?;
             let elapsed = start.elapsed();
             let status = res.status().as_u16();
+            if log_control.is_some_and(|l| !l.sample_request(status)) {
+                return Ok(res);
+            }
             let response_size = match res.response().body().size() {
                 BodySize::Sized(n) => n,
                 _ => 0,
@@ -102,3 +140,94 @@ where
         })
     }
 }
+
+/// Rewrites `ApiError` responses in the language stored in the caller's
+/// session. Anonymous requests and English sessions pass through untouched.
+pub struct Localize;
+
+impl<S, B> Transform<S, ServiceRequest> for Localize
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = LocalizeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LocalizeMiddleware { service }))
+    }
+}
+
+pub struct LocalizeMiddleware<S> {
+    service: S,
+}
+
+fn session_locale(req: &ServiceRequest) -> Locale {
+    let Some(config) = req.app_data::<Data<Config>>() else {
+        return Locale::En;
+    };
+    req.cookie("token")
+        .and_then(|c| validate_token(c.value(), &config.jwt_secret).ok())
+        .map_or(Locale::En, |claims| claims.locale)
+}
+
+impl<S, B> Service<ServiceRequest> for LocalizeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(
+        &self,
+        ctx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let locale = session_locale(&req);
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            if locale == Locale::En {
+                return Ok(res.map_into_left_body());
+            }
+
+            let localized = res
+                .response()
+                .error()
+                .and_then(|e| e.as_error::<ApiError>())
+                .map(|e| {
+                    HttpResponse::build(e.status_code())
+                        .json(serde_json::json!({ "error": e.localized(locale) }))
+                });
+            Ok(match localized {
+                Some(resp) => res.into_response(resp).map_into_right_body(),
+                None => res.map_into_left_body(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("/api/networks", "/api/networks" ; "no query")]
+    #[test_case("/api/servers?page=2&filter=gw", "/api/servers?page=2&filter=gw" ; "plain query")]
+    #[test_case("/api/status-page?token=s3cret", "/api/status-page?token=<redacted>" ; "token")]
+    #[test_case("/x?a=1&API_TOKEN=s3cret&b", "/x?a=1&API_TOKEN=<redacted>&b" ; "any case among others")]
+    fn test_loggable_uri(uri: &str, expected: &str) {
+        assert_eq!(loggable_uri(&uri.parse().unwrap()), expected);
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Interface MTUs, set on a network and overridden per server or client.
+
+/// The least an IPv4 host must accept.
+const MIN_MTU_V4: u32 = 576;
+/// The least IPv6 allows on any link.
+const MIN_MTU_V6: u32 = 1280;
+const MAX_MTU: u32 = 65535;
+
+/// Check `mtu` for a network of the given family, mapping zero to `None` so
+/// it clears the field.
+pub fn normalize(mtu: u32, ipv6: bool) -> Result<Option<i32>, String> {
+    if mtu == 0 {
+        return Ok(None);
+    }
+    let min = if ipv6 { MIN_MTU_V6 } else { MIN_MTU_V4 };
+    if !(min..=MAX_MTU).contains(&mtu) {
+        return Err(format!("mtu must be between {min} and {MAX_MTU}"));
+    }
+    Ok(Some(mtu as i32))
+}
+
+/// The MTU in effect for a peer: its own, else its network's.
+pub fn effective(own: Option<i32>, network: Option<i32>) -> Option<u32> {
+    own.or(network).map(|mtu| mtu as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, false, Ok(None) ; "zero clears")]
+    #[test_case(1420, false, Ok(Some(1420)) ; "typical")]
+    #[test_case(576, false, Ok(Some(576)) ; "ipv4 minimum")]
+    #[test_case(575, false, Err(()) ; "below ipv4 minimum")]
+    #[test_case(1000, true, Err(()) ; "below ipv6 minimum")]
+    #[test_case(1280, true, Ok(Some(1280)) ; "ipv6 minimum")]
+    #[test_case(65536, false, Err(()) ; "too large")]
+    fn test_normalize(mtu: u32, ipv6: bool, expected: Result<Option<i32>, ()>) {
+        assert_eq!(normalize(mtu, ipv6).map_err(|_| ()), expected);
+    }
+
+    #[test_case(Some(1380), Some(1420), Some(1380) ; "own wins")]
+    #[test_case(None, Some(1420), Some(1420) ; "network default")]
+    #[test_case(None, None, None ; "unset")]
+    fn test_effective(own: Option<i32>, network: Option<i32>, expected: Option<u32>) {
+        assert_eq!(effective(own, network), expected);
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Names for networks, servers, and clients. They end up in hostnames and
+//! config comments, so they are restricted to a DNS-friendly character set
+//! and compared case-insensitively by the database.
+
+const MAX_NAME_LEN: usize = 63;
+
+/// Trim `name` and check its length and characters. Case is preserved for
+/// display; uniqueness ignores it.
+pub fn normalize(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!("name must be 1 to {MAX_NAME_LEN} characters"));
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return Err("name must start with a letter or digit".into());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.')))
+    {
+        return Err(format!(
+            "name may only contain letters, digits, spaces, '-', '_' and '.'; found {c:?}"
+        ));
+    }
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("  Laptop ", "Laptop" ; "trimmed")]
+    #[test_case("relay-01.home", "relay-01.home" ; "dns style")]
+    #[test_case("Alice laptop", "Alice laptop" ; "inner space")]
+    fn test_normalize(input: &str, expected: &str) {
+        assert_eq!(normalize(input).unwrap(), expected);
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("   " ; "blank")]
+    #[test_case("-relay" ; "leading dash")]
+    #[test_case("bob's phone" ; "apostrophe")]
+    #[test_case("café" ; "non ascii")]
+    fn test_normalize_rejects(input: &str) {
+        assert!(normalize(input).is_err());
+    }
+
+    #[test]
+    fn test_normalize_rejects_long_names() {
+        assert!(normalize(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Operator notes on networks, servers, and clients.
+
+const MAX_NOTES_LEN: usize = 4000;
+
+/// Trim `notes`, mapping blank input to `None` so it clears the field.
+pub fn normalize(notes: &str) -> Result<Option<String>, String> {
+    let notes = notes.trim();
+    if notes.chars().count() > MAX_NOTES_LEN {
+        return Err(format!("notes must be at most {MAX_NOTES_LEN} characters"));
+    }
+    Ok((!notes.is_empty()).then(|| notes.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("  Alice's laptop \n", Some("Alice's laptop") ; "trimmed")]
+    #[test_case("   ", None ; "blank clears")]
+    fn test_normalize(input: &str, expected: Option<&str>) {
+        assert_eq!(normalize(input).unwrap().as_deref(), expected);
+    }
+
+    #[test]
+    fn test_normalize_rejects_long_notes() {
+        assert!(normalize(&"x".repeat(MAX_NOTES_LEN + 1)).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Emails users about the events they opted into: servers going offline,
+//! new clients and rotated server tokens.
+//!
+//! Each event is published on the replica that caused it, so every replica
+//! runs a notifier and each mail goes out once.
+
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::db::notification::{NotificationKind, NotificationStore};
+use crate::db::vpn::{VpnStore, VpnStoreError};
+use crate::events::{Event, EventBus};
+use crate::i18n::{Locale, Msg};
+use crate::mailer::Mailer;
+
+/// Subject and body of the email about `resource` in `network`.
+pub fn render(
+    kind: NotificationKind,
+    locale: Locale,
+    resource: &str,
+    network: &str,
+    at: DateTime<Utc>,
+) -> (String, String) {
+    let (subject, body) = match kind {
+        NotificationKind::ServerOffline => {
+            (Msg::NotifyServerOfflineSubject, Msg::NotifyServerOffline)
+        }
+        NotificationKind::ClientCreated => {
+            (Msg::NotifyClientCreatedSubject, Msg::NotifyClientCreated)
+        }
+        NotificationKind::ServerTokenRotated => {
+            (Msg::NotifyTokenRotatedSubject, Msg::NotifyTokenRotated)
+        }
+    };
+    let at = at.format("%Y-%m-%d %H:%M UTC");
+    let body = format!(
+        "{}\n\n-- \n{}\n",
+        locale.format(body, &[&resource, &network, &at]),
+        locale.text(Msg::NotifyFooter)
+    );
+    (locale.format(subject, &[&resource]), body)
+}
+
+#[derive(Clone)]
+pub struct Notifier {
+    store: NotificationStore,
+    vpn: VpnStore,
+    mailer: Mailer,
+}
+
+impl Notifier {
+    pub fn new(store: NotificationStore, vpn: VpnStore, mailer: Mailer) -> Self {
+        Self { store, vpn, mailer }
+    }
+
+    pub fn spawn(self, events: &EventBus) {
+        let mut rx = events.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let event = match rx.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(n)) => {
+                        tracing::warn!(skipped = n, "notifier lagged; events dropped");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                };
+                let Some(kind) = NotificationKind::for_event(event.kind) else {
+                    continue;
+                };
+                // Mail can be slow; keep up with the bus meanwhile.
+                tokio::spawn(self.clone().notify(kind, event));
+            }
+        });
+    }
+
+    #[tracing::instrument(skip_all, fields(event = event.kind.as_str(), resource_id = %event.resource_id))]
+    async fn notify(self, kind: NotificationKind, event: Event) {
+        let recipients = match self.store.recipients(kind).await {
+            Ok(recipients) => recipients,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to load notification recipients");
+                return;
+            }
+        };
+        if recipients.is_empty() {
+            return;
+        }
+
+        let names = match self.names(kind, &event).await {
+            Ok(Some(names)) => names,
+            Ok(None) => {
+                tracing::debug!("resource deleted before notifying");
+                return;
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to load notification details");
+                return;
+            }
+        };
+        for recipient in recipients {
+            let (subject, body) =
+                render(kind, recipient.locale, &names.0, &names.1, event.at);
+            if let Err(e) = self.mailer.send(&recipient.email, &subject, body).await {
+                tracing::warn!(user_id = %recipient.user_id, error = %e, "failed to send notification");
+            }
+        }
+    }
+
+    /// The names of the event's resource and network, or `None` if either
+    /// is gone.
+    async fn names(
+        &self,
+        kind: NotificationKind,
+        event: &Event,
+    ) -> Result<Option<(String, String)>, VpnStoreError> {
+        let resource = match kind {
+            NotificationKind::ServerOffline | NotificationKind::ServerTokenRotated => self
+                .vpn
+                .get_server(event.resource_id)
+                .await?
+                .map(|s| s.name),
+            NotificationKind::ClientCreated => self
+                .vpn
+                .get_client(event.resource_id)
+                .await?
+                .map(|c| c.name),
+        };
+        let network = self.vpn.get_network(event.network_id).await?;
+        Ok(resource.zip(network.map(|n| n.name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let at = "2026-03-08T09:15:00Z".parse().unwrap();
+        let (subject, body) = render(
+            NotificationKind::ServerOffline,
+            Locale::En,
+            "gateway",
+            "home",
+            at,
+        );
+        assert_eq!(subject, "Server gateway is offline");
+        assert_eq!(
+            body,
+            "Server \"gateway\" in network \"home\" went offline at 2026-03-08 09:15 UTC.\n\n\
+             -- \nYou get this email because of your notification settings.\n"
+        );
+    }
+
+    #[test]
+    fn test_render_translates() {
+        let at = "2026-03-08T09:15:00Z".parse().unwrap();
+        let (subject, _) = render(
+            NotificationKind::ClientCreated,
+            Locale::De,
+            "laptop",
+            "home",
+            at,
+        );
+        assert_eq!(subject, "Neuer Client laptop");
+    }
+}
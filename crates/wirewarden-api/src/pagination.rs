@@ -0,0 +1,223 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `?page=&per_page=&sort=&filter=` handling shared by list endpoints.
+//!
+//! `sort` names a column, prefixed with `-` for descending order. `filter` is
+//! a case-insensitive substring match on the resource's name. `tag` takes a
+//! comma-separated list and keeps servers and clients carrying all of those
+//! tags; other resources ignore it. The total
+//! number of matching rows is returned in the `X-Total-Count` header so the
+//! response body stays a plain array.
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, web};
+use serde::{Deserialize, Serialize};
+use std::future::{Ready, ready};
+
+use crate::error::ApiError;
+use crate::tags;
+
+const DEFAULT_PER_PAGE: i64 = 100;
+const MAX_PER_PAGE: i64 = 500;
+
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+#[derive(Debug, Deserialize)]
+struct RawQuery {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    sort: Option<String>,
+    filter: Option<String>,
+    tag: Option<String>,
+}
+
+/// Validated list parameters. The sort column is checked against each
+/// endpoint's allow-list with [`ListQuery::options`].
+#[derive(Debug)]
+pub struct ListQuery {
+    pub page: i64,
+    pub per_page: i64,
+    /// Rows before this page, checked not to overflow.
+    pub offset: i64,
+    sort: Option<(String, bool)>,
+    filter: Option<String>,
+    tags: Vec<String>,
+}
+
+impl FromRequest for ListQuery {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(parse(req.query_string()))
+    }
+}
+
+fn parse(query: &str) -> Result<ListQuery, ApiError> {
+    let raw = web::Query::<RawQuery>::from_query(query)
+        .map_err(|e| ApiError::Validation(format!("invalid list query: {e}")))?
+        .into_inner();
+
+    let page = raw.page.unwrap_or(1);
+    if page < 1 {
+        return Err(ApiError::Validation("page must be at least 1".into()));
+    }
+    let per_page = raw.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(ApiError::Validation(format!(
+            "per_page must be between 1 and {MAX_PER_PAGE}"
+        )));
+    }
+    let offset = (page - 1)
+        .checked_mul(per_page)
+        .ok_or_else(|| ApiError::Validation("page is too large".into()))?;
+
+    let sort = raw
+        .sort
+        .filter(|s| !s.is_empty())
+        .map(|s| match s.strip_prefix('-') {
+            Some(col) => (col.to_string(), true),
+            None => (s, false),
+        });
+    let filter = raw.filter.filter(|f| !f.is_empty());
+    let tags: Vec<String> = raw
+        .tag
+        .iter()
+        .flat_map(|t| t.split(','))
+        .map(str::to_string)
+        .collect();
+    let tags = tags::normalize(&tags).map_err(ApiError::Validation)?;
+
+    Ok(ListQuery {
+        page,
+        per_page,
+        offset,
+        sort,
+        filter,
+        tags,
+    })
+}
+
+impl ListQuery {
+    /// Resolve the query against `columns`, the sortable columns for this
+    /// resource. The first column is the default sort.
+    pub fn options(&self, columns: &[&'static str]) -> Result<ListOptions, ApiError> {
+        let (column, desc) = match &self.sort {
+            None => (columns[0], false),
+            Some((name, desc)) => {
+                let column = columns.iter().find(|c| **c == name).ok_or_else(|| {
+                    ApiError::Validation(format!(
+                        "cannot sort by {name}; expected one of {}",
+                        columns.join(", ")
+                    ))
+                })?;
+                (*column, *desc)
+            }
+        };
+
+        Ok(ListOptions {
+            limit: self.per_page,
+            offset: self.offset,
+            sort_column: column,
+            sort_desc: desc,
+            filter: self.filter.as_deref().map(like_pattern),
+            tags: self.tags.clone(),
+        })
+    }
+
+    /// Build a 200 response carrying one page of `items`.
+    pub fn respond<T>(&self, items: Vec<T>, total: i64) -> HttpResponse
+    where
+        T: Serialize,
+    {
+        HttpResponse::Ok()
+            .insert_header((TOTAL_COUNT_HEADER, total.to_string()))
+            .insert_header(("X-Page", self.page.to_string()))
+            .insert_header(("X-Per-Page", self.per_page.to_string()))
+            .json(items)
+    }
+}
+
+/// Store-facing list options.
+#[derive(Debug)]
+pub struct ListOptions {
+    pub limit: i64,
+    pub offset: i64,
+    sort_column: &'static str,
+    sort_desc: bool,
+    /// An `ILIKE` pattern, already escaped.
+    pub filter: Option<String>,
+    /// Normalized tags that every row must carry.
+    pub tags: Vec<String>,
+}
+
+impl ListOptions {
+    /// `ORDER BY` clause. The column comes from a static allow-list, and `id`
+    /// breaks ties so pages are stable.
+    pub fn order_by(&self) -> String {
+        let dir = if self.sort_desc { "DESC" } else { "ASC" };
+        format!("ORDER BY {} {dir}, id", self.sort_column)
+    }
+}
+
+/// Escape `LIKE` wildcards so `s` matches literally.
+pub fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn like_pattern(filter: &str) -> String {
+    format!("%{}%", escape_like(filter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const COLUMNS: &[&str] = &["name", "created_at"];
+
+    #[test_case("", 100, 0, "ORDER BY name ASC, id" ; "defaults")]
+    #[test_case("page=3&per_page=20", 20, 40, "ORDER BY name ASC, id" ; "third page")]
+    #[test_case("sort=-created_at", 100, 0, "ORDER BY created_at DESC, id" ; "descending")]
+    fn test_options(query: &str, limit: i64, offset: i64, order: &str) {
+        let opts = parse(query).unwrap().options(COLUMNS).unwrap();
+        assert_eq!(opts.limit, limit);
+        assert_eq!(opts.offset, offset);
+        assert_eq!(opts.order_by(), order);
+    }
+
+    #[test_case("page=0" ; "zero page")]
+    #[test_case("per_page=1000" ; "per page too large")]
+    #[test_case("page=9223372036854775807&per_page=2" ; "offset overflows")]
+    #[test_case("sort=api_token" ; "unknown column")]
+    #[test_case("sort=name;drop" ; "injection attempt")]
+    fn test_rejects(query: &str) {
+        assert!(parse(query).and_then(|q| q.options(COLUMNS)).is_err());
+    }
+
+    #[test]
+    fn test_tags_split_and_normalize() {
+        let opts = parse("tag=Kids,iot").unwrap().options(COLUMNS).unwrap();
+        assert_eq!(opts.tags, ["kids", "iot"]);
+    }
+
+    #[test]
+    fn test_filter_escapes_wildcards() {
+        let opts = parse("filter=50%25_off").unwrap().options(COLUMNS).unwrap();
+        assert_eq!(opts.filter.as_deref(), Some("%50\\%\\_off%"));
+    }
+}
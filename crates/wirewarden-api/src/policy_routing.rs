@@ -0,0 +1,118 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! A server's fwmark and policy routing, applied by its daemon on Linux.
+
+/// Tables the kernel reserves: default, main and local.
+const RESERVED_TABLES: [u32; 3] = [253, 254, 255];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PolicyRouting {
+    /// Mark WireGuard puts on the packets it sends.
+    pub fwmark: Option<u32>,
+    /// Table the interface's routes go in instead of main.
+    pub route_table: Option<u32>,
+    /// Priority of the rule looking up `route_table`; the kernel picks one
+    /// when unset.
+    pub rule_priority: Option<u32>,
+}
+
+impl PolicyRouting {
+    /// `self` with the fields set in `update` replaced; zero clears a field.
+    pub fn merge(self, update: PolicyRouting) -> Self {
+        Self {
+            fwmark: update.fwmark.or(self.fwmark),
+            route_table: update.route_table.or(self.route_table),
+            rule_priority: update.rule_priority.or(self.rule_priority),
+        }
+    }
+
+    /// Map zeroes to unset and check the fields fit together.
+    pub fn normalize(self) -> Result<Self, String> {
+        let nonzero = |v: Option<u32>| v.filter(|&v| v != 0);
+        let routing = Self {
+            fwmark: nonzero(self.fwmark),
+            route_table: nonzero(self.route_table),
+            rule_priority: nonzero(self.rule_priority),
+        };
+        if let Some(table) = routing.route_table
+            && RESERVED_TABLES.contains(&table)
+        {
+            return Err(format!("route table {table} is reserved by the kernel"));
+        }
+        if routing.rule_priority.is_some() && routing.route_table.is_none() {
+            return Err("rule_priority requires a route_table".into());
+        }
+        Ok(routing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn routing(fwmark: u32, route_table: u32, rule_priority: u32) -> PolicyRouting {
+        PolicyRouting {
+            fwmark: Some(fwmark),
+            route_table: Some(route_table),
+            rule_priority: Some(rule_priority),
+        }
+    }
+
+    #[test]
+    fn test_normalize_clears_zeroes() {
+        assert_eq!(
+            routing(0, 0, 0).normalize().unwrap(),
+            PolicyRouting::default()
+        );
+        assert_eq!(
+            routing(51820, 1000, 0).normalize().unwrap(),
+            PolicyRouting {
+                fwmark: Some(51820),
+                route_table: Some(1000),
+                rule_priority: None,
+            }
+        );
+    }
+
+    #[test_case(253 ; "default")]
+    #[test_case(254 ; "main")]
+    #[test_case(255 ; "local")]
+    fn test_normalize_rejects_reserved_tables(table: u32) {
+        assert!(routing(1, table, 100).normalize().is_err());
+    }
+
+    #[test]
+    fn test_normalize_rejects_priority_without_table() {
+        assert!(routing(1, 0, 100).normalize().is_err());
+    }
+
+    #[test]
+    fn test_merge_keeps_unset_fields() {
+        let update = PolicyRouting {
+            route_table: Some(0),
+            rule_priority: Some(0),
+            ..PolicyRouting::default()
+        };
+        let merged = routing(51820, 1000, 100).merge(update).normalize().unwrap();
+        assert_eq!(
+            merged,
+            PolicyRouting {
+                fwmark: Some(51820),
+                ..PolicyRouting::default()
+            }
+        );
+    }
+}
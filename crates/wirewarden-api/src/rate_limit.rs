@@ -0,0 +1,47 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+//! Per-client bandwidth caps, enforced by the daemons on their servers.
+
+/// Below this, TCP barely gets a handshake through.
+const MIN_KBPS: u32 = 8;
+/// 100 Gbit/s.
+const MAX_KBPS: u32 = 100_000_000;
+
+/// Check a cap in kilobits per second, mapping zero to `None` so it clears
+/// the field. `field` names it in the error.
+pub fn normalize(kbps: u32, field: &str) -> Result<Option<i32>, String> {
+    if kbps == 0 {
+        return Ok(None);
+    }
+    if !(MIN_KBPS..=MAX_KBPS).contains(&kbps) {
+        return Err(format!("{field} must be between {MIN_KBPS} and {MAX_KBPS}"));
+    }
+    Ok(Some(kbps as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, Ok(None) ; "zero clears")]
+    #[test_case(8, Ok(Some(8)) ; "minimum")]
+    #[test_case(10_000, Ok(Some(10_000)) ; "typical")]
+    #[test_case(100_000_000, Ok(Some(100_000_000)) ; "maximum")]
+    #[test_case(7, Err(()) ; "too small")]
+    #[test_case(100_000_001, Err(()) ; "too large")]
+    fn test_normalize(kbps: u32, expected: Result<Option<i32>, ()>) {
+        assert_eq!(normalize(kbps, "upload_kbps").map_err(|_| ()), expected);
+    }
+}
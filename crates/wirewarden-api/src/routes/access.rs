@@ -0,0 +1,124 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Access schedules for every client in a network carrying a tag. Clients
+//! are let in or shut out on the scheduler's next tick.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::access::AccessSchedule;
+use crate::db::audit::AuditStore;
+use crate::db::vpn::{TagAccessSchedule, VpnStore};
+use crate::error::ApiError;
+use crate::extract::AuthUser;
+use crate::tags;
+
+#[derive(Debug, Serialize)]
+struct TagAccessScheduleResponse {
+    network_id: Uuid,
+    tag: String,
+    schedule: AccessSchedule,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<TagAccessSchedule> for TagAccessScheduleResponse {
+    fn from(t: TagAccessSchedule) -> Self {
+        Self {
+            network_id: t.network_id,
+            tag: t.tag,
+            schedule: t.schedule.0,
+            created_at: t.created_at,
+            updated_at: t.updated_at,
+        }
+    }
+}
+
+/// The one tag in a path, normalized as tags on clients are.
+fn parse_tag(tag: &str) -> Result<String, ApiError> {
+    let mut normalized = tags::normalize(&[tag.to_string()]).map_err(ApiError::Validation)?;
+    Ok(normalized.remove(0))
+}
+
+pub async fn list_tag_schedules(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let network_id = path.into_inner();
+    store
+        .get_network(network_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let schedules = store.list_tag_access_schedules(Some(network_id)).await?;
+    let resp: Vec<TagAccessScheduleResponse> = schedules.into_iter().map(Into::into).collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub async fn set_tag_schedule(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    path: web::Path<(Uuid, String)>,
+    body: web::Json<AccessSchedule>,
+) -> Result<HttpResponse, ApiError> {
+    let (network_id, tag) = path.into_inner();
+    let tag = parse_tag(&tag)?;
+    let schedule = body.into_inner();
+    schedule.validate().map_err(ApiError::Validation)?;
+    store
+        .get_network(network_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let saved = store
+        .set_tag_access_schedule(network_id, &tag, &schedule)
+        .await?;
+    audit
+        .record(
+            Some(auth.user_id),
+            "network.access_schedule.set",
+            Some(network_id),
+            Some(network_id),
+            serde_json::json!({ "tag": tag, "schedule": schedule }),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(TagAccessScheduleResponse::from(saved)))
+}
+
+pub async fn clear_tag_schedule(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    path: web::Path<(Uuid, String)>,
+) -> Result<HttpResponse, ApiError> {
+    let (network_id, tag) = path.into_inner();
+    let tag = parse_tag(&tag)?;
+    if !store.clear_tag_access_schedule(network_id, &tag).await? {
+        return Err(ApiError::NotFound);
+    }
+    audit
+        .record(
+            Some(auth.user_id),
+            "network.access_schedule.clear",
+            Some(network_id),
+            Some(network_id),
+            serde_json::json!({ "tag": tag }),
+        )
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
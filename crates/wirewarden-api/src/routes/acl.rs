@@ -0,0 +1,128 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::acl::Selector;
+use crate::db::vpn::{AclAction, VpnStore, WgAclRule};
+use crate::error::ApiError;
+use crate::events::{EventBus, EventKind};
+use crate::extract::AuthUser;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAclRuleRequest {
+    /// 1-based place to insert at; after the last rule when absent.
+    #[serde(default)]
+    position: Option<i32>,
+    action: AclAction,
+    source: String,
+    destination: String,
+}
+
+#[derive(Debug, Serialize)]
+struct AclRuleResponse {
+    id: Uuid,
+    network_id: Uuid,
+    position: i32,
+    action: AclAction,
+    source: String,
+    destination: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<WgAclRule> for AclRuleResponse {
+    fn from(r: WgAclRule) -> Self {
+        Self {
+            id: r.id,
+            network_id: r.network_id,
+            position: r.position,
+            action: r.action,
+            source: r.source,
+            destination: r.destination,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        }
+    }
+}
+
+/// The network's rules in the order they are checked. Not paginated, since
+/// the order is the point.
+pub async fn list_acl_rules(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let network_id = path.into_inner();
+    store
+        .get_network(network_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let rules = store.list_acl_rules_by_network(network_id).await?;
+    let resp: Vec<AclRuleResponse> = rules.into_iter().map(Into::into).collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub async fn add_acl_rule(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateAclRuleRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let network_id = path.into_inner();
+    let source = Selector::parse(&body.source, "source").map_err(ApiError::Validation)?;
+    let destination =
+        Selector::parse(&body.destination, "destination").map_err(ApiError::Validation)?;
+    if body.position.is_some_and(|p| p < 1) {
+        return Err(ApiError::Validation("position must be at least 1".into()));
+    }
+    store
+        .get_network(network_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let rule = store
+        .add_acl_rule(
+            network_id,
+            body.position,
+            body.action,
+            &source.to_string(),
+            &destination.to_string(),
+        )
+        .await?;
+    events.publish(EventKind::NetworkUpdated, network_id, network_id);
+    Ok(HttpResponse::Created().json(AclRuleResponse::from(rule)))
+}
+
+async fn delete_acl_rule(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    if let Some(rule) = store.delete_acl_rule(id).await? {
+        events.publish(EventKind::NetworkUpdated, rule.network_id, rule.network_id);
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Listing and adding live under the networks scope.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/api/acl-rules/{id}").route(web::delete().to(delete_acl_rule)));
+}
@@ -0,0 +1,66 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! A "what happened recently" feed: audit entries and peer connection
+//! events in one timeline, newest first. Paged like other lists; `sort`,
+//! `filter` and `tag` do not apply.
+
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::activity::{Activity, ActivityStore};
+use crate::error::ApiError;
+use crate::extract::AuthUser;
+use crate::pagination::ListQuery;
+use crate::routes::audit::AuditEntryResponse;
+use crate::routes::connections::ConnectionEventResponse;
+
+#[derive(Debug, Deserialize)]
+struct ActivityQuery {
+    network_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ActivityResponse {
+    Audit(AuditEntryResponse),
+    Connection(ConnectionEventResponse),
+}
+
+impl From<Activity> for ActivityResponse {
+    fn from(a: Activity) -> Self {
+        match a {
+            Activity::Audit(e) => Self::Audit(e.into()),
+            Activity::Connection(e) => Self::Connection(e.into()),
+        }
+    }
+}
+
+async fn list_activity(
+    _auth: AuthUser,
+    activity: web::Data<ActivityStore>,
+    list: ListQuery,
+    query: web::Query<ActivityQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let (items, total) = activity
+        .page(query.network_id, list.per_page, list.offset)
+        .await?;
+    let resp: Vec<_> = items.into_iter().map(ActivityResponse::from).collect();
+    Ok(list.respond(resp, total))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/api/activity").route(web::get().to(list_activity)));
+}
@@ -0,0 +1,278 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::changes::Change;
+use crate::config::Config;
+use crate::db::approval::{ApprovalStore, ChangeStatus, PendingChange};
+use crate::db::audit::AuditStore;
+use crate::db::schedule::ScheduleStore;
+use crate::db::vpn::VpnStore;
+use crate::error::ApiError;
+use crate::events::EventBus;
+use crate::extract::AuthUser;
+
+#[derive(Debug, Serialize)]
+struct PendingChangeResponse {
+    id: Uuid,
+    change: Change,
+    /// What the change breaks beyond its target, e.g. client configs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    impact: Option<&'static str>,
+    requested_by: Uuid,
+    status: ChangeStatus,
+    decided_by: Option<Uuid>,
+    decided_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    /// When the requester may approve their own change.
+    self_approvable_at: DateTime<Utc>,
+}
+
+impl PendingChangeResponse {
+    fn from_model(p: PendingChange, cooldown_secs: i64) -> Self {
+        Self {
+            id: p.id,
+            impact: p.change.0.impact(),
+            change: p.change.0,
+            requested_by: p.requested_by,
+            status: p.status,
+            decided_by: p.decided_by,
+            decided_at: p.decided_at,
+            self_approvable_at: p.created_at + chrono::Duration::seconds(cooldown_secs),
+            created_at: p.created_at,
+        }
+    }
+}
+
+/// Apply `change` immediately, or queue it for approval when the two-person
+/// rule is enabled.
+pub async fn submit_change(
+    auth: &AuthUser,
+    change: Change,
+    config: &Config,
+    store: &VpnStore,
+    approvals: &ApprovalStore,
+    audit: &AuditStore,
+    events: &EventBus,
+) -> Result<HttpResponse, ApiError> {
+    if !config.require_approval || !change.is_destructive() {
+        change.apply(store, events).await?;
+        audit
+            .record(
+                Some(auth.user_id),
+                change.action(),
+                Some(change.network_id()),
+                Some(change.target_id()),
+                serde_json::json!({ "change": change }),
+            )
+            .await?;
+        return Ok(HttpResponse::NoContent().finish());
+    }
+
+    change.check_target(store).await?;
+    let pending = approvals.create(&change, auth.user_id).await?;
+    audit
+        .record(
+            Some(auth.user_id),
+            "change.requested",
+            Some(change.network_id()),
+            Some(change.target_id()),
+            serde_json::json!({
+                "pending_change_id": pending.id,
+                "change": change,
+                "impact": change.impact(),
+            }),
+        )
+        .await?;
+    tracing::info!(change_id = %pending.id, action = change.action(), "change queued for approval");
+
+    Ok(HttpResponse::Accepted().json(PendingChangeResponse::from_model(
+        pending,
+        config.approval_cooldown_secs,
+    )))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    status: Option<ChangeStatus>,
+}
+
+async fn list_changes(
+    _auth: AuthUser,
+    approvals: web::Data<ApprovalStore>,
+    config: web::Data<Config>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let changes = approvals.list(query.status).await?;
+    let resp: Vec<_> = changes
+        .into_iter()
+        .map(|p| PendingChangeResponse::from_model(p, config.approval_cooldown_secs))
+        .collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+async fn get_change(
+    _auth: AuthUser,
+    approvals: web::Data<ApprovalStore>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let change = approvals
+        .get(path.into_inner())
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(HttpResponse::Ok().json(PendingChangeResponse::from_model(
+        change,
+        config.approval_cooldown_secs,
+    )))
+}
+
+/// Approve and apply a pending change. A change held by a schedule is only
+/// marked approved; the scheduler applies it once it is due.
+#[allow(clippy::too_many_arguments)]
+async fn approve_change(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    approvals: web::Data<ApprovalStore>,
+    schedules: web::Data<ScheduleStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let pending = approvals.get(id).await?.ok_or(ApiError::NotFound)?;
+    if pending.status != ChangeStatus::Pending {
+        return Err(ApiError::ChangeAlreadyDecided);
+    }
+
+    let cooldown_elapsed = Utc::now()
+        >= pending.created_at + chrono::Duration::seconds(config.approval_cooldown_secs);
+    if pending.requested_by == auth.user_id && !cooldown_elapsed {
+        return Err(ApiError::SelfApproval);
+    }
+
+    let decided = approvals
+        .decide(id, auth.user_id, ChangeStatus::Approved)
+        .await?
+        .ok_or(ApiError::ChangeAlreadyDecided)?;
+    let change = &decided.change.0;
+
+    if let Some(scheduled) = schedules.by_approval(id).await? {
+        audit
+            .record(
+                Some(auth.user_id),
+                "schedule.approved",
+                Some(change.network_id()),
+                Some(change.target_id()),
+                serde_json::json!({
+                    "pending_change_id": id,
+                    "scheduled_change_id": scheduled.id,
+                    "requested_by": decided.requested_by,
+                    "change": change,
+                }),
+            )
+            .await?;
+        tracing::info!(
+            change_id = %id,
+            scheduled_change_id = %scheduled.id,
+            "scheduled change approved"
+        );
+        return Ok(HttpResponse::Ok().json(PendingChangeResponse::from_model(
+            decided,
+            config.approval_cooldown_secs,
+        )));
+    }
+
+    if let Err(e) = change.apply(&store, &events).await {
+        approvals.reopen(id).await?;
+        return Err(e.into());
+    }
+
+    audit
+        .record(
+            Some(auth.user_id),
+            change.action(),
+            Some(change.network_id()),
+            Some(change.target_id()),
+            serde_json::json!({
+                "pending_change_id": id,
+                "requested_by": decided.requested_by,
+                "change": change,
+            }),
+        )
+        .await?;
+    tracing::info!(change_id = %id, action = change.action(), "pending change approved and applied");
+
+    Ok(HttpResponse::Ok().json(PendingChangeResponse::from_model(
+        decided,
+        config.approval_cooldown_secs,
+    )))
+}
+
+async fn reject_change(
+    auth: AuthUser,
+    approvals: web::Data<ApprovalStore>,
+    schedules: web::Data<ScheduleStore>,
+    audit: web::Data<AuditStore>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let decided = approvals
+        .decide(id, auth.user_id, ChangeStatus::Rejected)
+        .await?;
+    let Some(decided) = decided else {
+        return Err(match approvals.get(id).await? {
+            Some(_) => ApiError::ChangeAlreadyDecided,
+            None => ApiError::NotFound,
+        });
+    };
+
+    let cancelled = schedules.cancel_for_approval(id).await?;
+
+    let change = &decided.change.0;
+    audit
+        .record(
+            Some(auth.user_id),
+            "change.rejected",
+            Some(change.network_id()),
+            Some(change.target_id()),
+            serde_json::json!({
+                "pending_change_id": id,
+                "scheduled_change_id": cancelled.map(|s| s.id),
+                "change": change,
+            }),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(PendingChangeResponse::from_model(
+        decided,
+        config.approval_cooldown_secs,
+    )))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/approvals")
+            .route("", web::get().to(list_changes))
+            .route("/{id}", web::get().to(get_change))
+            .route("/{id}/approve", web::post().to(approve_change))
+            .route("/{id}/reject", web::post().to(reject_change)),
+    );
+}
@@ -0,0 +1,73 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::audit::{AuditEntry, AuditStore};
+use crate::error::ApiError;
+use crate::extract::AuthUser;
+
+#[derive(Debug, Deserialize)]
+struct AuditQuery {
+    network_id: Option<Uuid>,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AuditEntryResponse {
+    id: Uuid,
+    actor_id: Option<Uuid>,
+    action: String,
+    network_id: Option<Uuid>,
+    target_id: Option<Uuid>,
+    details: serde_json::Value,
+    created_at: DateTime<Utc>,
+}
+
+impl From<AuditEntry> for AuditEntryResponse {
+    fn from(e: AuditEntry) -> Self {
+        Self {
+            id: e.id,
+            actor_id: e.actor_id,
+            action: e.action,
+            network_id: e.network_id,
+            target_id: e.target_id,
+            details: e.details,
+            created_at: e.created_at,
+        }
+    }
+}
+
+async fn list_audit(
+    _auth: AuthUser,
+    audit: web::Data<AuditStore>,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let limit = query.limit.clamp(1, 1000);
+    let entries = audit.list(query.network_id, limit).await?;
+    let resp: Vec<_> = entries.into_iter().map(AuditEntryResponse::from).collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/api/audit").route(web::get().to(list_audit)));
+}
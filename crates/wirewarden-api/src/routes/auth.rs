@@ -12,18 +12,25 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use std::fmt;
+
 use actix_web::{web, HttpResponse};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use wirewarden_types::api::VerifyEmailRequest;
+use wirewarden_types::redact::Redacted;
 
 use crate::auth::{clear_auth_cookie, create_token, set_auth_cookie};
 use crate::config::Config;
+use crate::db::notification::{NotificationSettings, NotificationStore};
 use crate::db::user::{User, UserStore};
 use crate::error::ApiError;
 use crate::extract::AuthUser;
+use crate::i18n::{Locale, Msg};
+use crate::mailer::Mailer;
 
-#[derive(Debug, Deserialize)]
+#[derive(Deserialize)]
 pub struct RegisterRequest {
     pub username: String,
     pub display_name: String,
@@ -31,29 +38,65 @@ pub struct RegisterRequest {
     pub password: String,
 }
 
-#[derive(Debug, Deserialize)]
+impl fmt::Debug for RegisterRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegisterRequest")
+            .field("username", &self.username)
+            .field("display_name", &self.display_name)
+            .field("email", &self.email)
+            .field("password", &Redacted)
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
 }
 
+impl fmt::Debug for LoginRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginRequest")
+            .field("username", &self.username)
+            .field("password", &Redacted)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateLocaleRequest {
+    pub locale: Locale,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ForgotPasswordRequest {
     pub email: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Deserialize)]
 pub struct ResetPasswordRequest {
     pub token: String,
     pub password: String,
 }
 
+impl fmt::Debug for ResetPasswordRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResetPasswordRequest")
+            .field("token", &Redacted)
+            .field("password", &Redacted)
+            .finish()
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserResponse {
     pub id: Uuid,
     pub username: String,
     pub display_name: String,
     pub email: String,
+    pub locale: Locale,
+    pub email_verified: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -64,6 +107,8 @@ impl From<&User> for UserResponse {
             username: u.username.clone(),
             display_name: u.display_name.clone(),
             email: u.email.clone(),
+            locale: u.locale,
+            email_verified: u.email_verified_at.is_some(),
             created_at: u.created_at,
         }
     }
@@ -76,16 +121,54 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route("/login", web::post().to(login))
             .route("/logout", web::post().to(logout))
             .route("/me", web::get().to(me))
+            .route("/me/locale", web::put().to(update_locale))
+            .route("/me/notifications", web::get().to(get_notifications))
+            .route("/me/notifications", web::put().to(update_notifications))
+            .route("/verify", web::post().to(verify_email))
+            .route("/verify/resend", web::post().to(resend_verification))
             .route("/forgot-password", web::post().to(forgot_password))
             .route("/reset-password", web::post().to(reset_password))
             .configure(super::passkey::configure),
     );
 }
 
-#[tracing::instrument(skip(body, store))]
+/// A frontend page taking a mailed `token`.
+fn token_link(public_url: &str, page: &str, token: &str) -> String {
+    format!("{}/{page}?token={token}", public_url.trim_end_matches('/'))
+}
+
+/// Mail `user` in the background, so response time reveals neither whether
+/// an account exists nor how slow the relay is.
+fn send_later(mailer: &Mailer, user: &User, subject: &str, text: String) {
+    let mailer = mailer.clone();
+    let (user_id, email, subject) = (user.id, user.email.clone(), subject.to_string());
+    tokio::spawn(async move {
+        if let Err(e) = mailer.send(&email, &subject, text).await {
+            tracing::error!(%user_id, %subject, error = %e, "failed to send account email");
+        }
+    });
+}
+
+/// Issue a verification token and mail the user a link to confirm with.
+async fn send_verification(
+    store: &UserStore,
+    mailer: &Mailer,
+    config: &Config,
+    user: &User,
+) -> Result<(), ApiError> {
+    let token = store.set_verify_token(user.id).await?;
+    let link = token_link(&config.public_url, "verify-email", &token);
+    let text = user.locale.format(Msg::VerifyBody, &[&user.username, &link]);
+    send_later(mailer, user, user.locale.text(Msg::VerifySubject), text);
+    Ok(())
+}
+
+#[tracing::instrument(skip(body, store, mailer, config))]
 async fn register(
     body: web::Json<RegisterRequest>,
     store: web::Data<UserStore>,
+    mailer: web::Data<Mailer>,
+    config: web::Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
     if body.username.is_empty() || body.password.is_empty() || body.email.is_empty() {
         return Err(ApiError::Validation("missing required fields".into()));
@@ -96,6 +179,7 @@ async fn register(
         .await?;
 
     tracing::info!(user_id = %user.id, username = %user.username, "user registered");
+    send_verification(&store, &mailer, &config, &user).await?;
 
     Ok(HttpResponse::Created().json(UserResponse::from(&user)))
 }
@@ -116,7 +200,7 @@ async fn login(
         return Err(ApiError::InvalidCredentials);
     }
 
-    let token = create_token(user.id, &config.jwt_secret)?;
+    let token = create_token(&user, &config.jwt_secret)?;
     tracing::info!(user_id = %user.id, "login success");
 
     Ok(HttpResponse::Ok()
@@ -144,16 +228,103 @@ async fn me(
     Ok(HttpResponse::Ok().json(UserResponse::from(&user)))
 }
 
-#[tracing::instrument(skip(body, store))]
+/// Store the user's language and reissue the session cookie so the
+/// preference applies to subsequent responses.
+#[tracing::instrument(skip(store, config))]
+async fn update_locale(
+    auth: AuthUser,
+    body: web::Json<UpdateLocaleRequest>,
+    store: web::Data<UserStore>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    let user = store
+        .set_locale(auth.user_id, body.locale)
+        .await?
+        .ok_or(ApiError::UserNotFound)?;
+    let token = create_token(&user, &config.jwt_secret)?;
+
+    Ok(HttpResponse::Ok()
+        .cookie(set_auth_cookie(&token))
+        .json(UserResponse::from(&user)))
+}
+
+#[tracing::instrument(skip(store))]
+async fn get_notifications(
+    auth: AuthUser,
+    store: web::Data<NotificationStore>,
+) -> Result<HttpResponse, ApiError> {
+    let settings = store.get(auth.user_id).await?;
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+/// Replace which events the user is emailed about.
+#[tracing::instrument(skip(store))]
+async fn update_notifications(
+    auth: AuthUser,
+    body: web::Json<NotificationSettings>,
+    store: web::Data<NotificationStore>,
+) -> Result<HttpResponse, ApiError> {
+    let settings = store.set(auth.user_id, body.into_inner()).await?;
+    tracing::info!(user_id = %auth.user_id, ?settings, "notification settings updated");
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+/// Confirm the address a verification link was sent to. A session of the
+/// same user is reissued, so changes open up without logging in again.
+#[tracing::instrument(skip(body, store, config))]
+async fn verify_email(
+    auth: Option<AuthUser>,
+    body: web::Json<VerifyEmailRequest>,
+    store: web::Data<UserStore>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    let user = store
+        .consume_verify_token(&body.token)
+        .await?
+        .ok_or(ApiError::InvalidVerifyToken)?;
+    tracing::info!(user_id = %user.id, "email verified");
+
+    let mut resp = HttpResponse::Ok();
+    if auth.is_some_and(|a| a.user_id == user.id) {
+        resp.cookie(set_auth_cookie(&create_token(&user, &config.jwt_secret)?));
+    }
+    Ok(resp.json(UserResponse::from(&user)))
+}
+
+/// Mail a fresh verification link, replacing any earlier one.
+#[tracing::instrument(skip(store, mailer, config))]
+async fn resend_verification(
+    auth: AuthUser,
+    store: web::Data<UserStore>,
+    mailer: web::Data<Mailer>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    let user = store
+        .get_by_id(auth.user_id)
+        .await?
+        .ok_or(ApiError::UserNotFound)?;
+    if user.email_verified_at.is_some() {
+        return Err(ApiError::Validation("email already verified".into()));
+    }
+    send_verification(&store, &mailer, &config, &user).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[tracing::instrument(skip(body, store, mailer, config))]
 async fn forgot_password(
     body: web::Json<ForgotPasswordRequest>,
     store: web::Data<UserStore>,
+    mailer: web::Data<Mailer>,
+    config: web::Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
     // Always return 200 to prevent email enumeration
     if let Ok(Some(user)) = store.get_by_email(&body.email).await {
         match store.set_reset_token(user.id).await {
             Ok(token) => {
-                tracing::info!(user_id = %user.id, reset_token = %token, "password reset token generated");
+                tracing::info!(user_id = %user.id, "password reset token generated");
+                let link = token_link(&config.public_url, "reset-password", &token);
+                let text = user.locale.format(Msg::ResetBody, &[&user.username, &link]);
+                send_later(&mailer, &user, user.locale.text(Msg::ResetSubject), text);
             }
             Err(e) => {
                 tracing::error!(error = %e, "failed to set reset token");
@@ -185,3 +356,18 @@ async fn reset_password(
 
     Ok(HttpResponse::Ok().json(serde_json::json!({ "status": "ok" })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("https://vpn.example.com" ; "bare")]
+    #[test_case("https://vpn.example.com/" ; "trailing slash")]
+    fn test_token_link(public_url: &str) {
+        assert_eq!(
+            token_link(public_url, "reset-password", "abc"),
+            "https://vpn.example.com/reset-password?token=abc"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::vpn::{ClientRouteKind, VpnStore, WgClientRoute};
+use crate::error::ApiError;
+use crate::events::{EventBus, EventKind};
+use crate::extract::AuthUser;
+use crate::pagination::ListQuery;
+
+#[derive(Debug, Deserialize)]
+struct CreateClientRouteRequest {
+    kind: ClientRouteKind,
+    #[serde(default)]
+    server_id: Option<Uuid>,
+    route_cidr: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ClientRouteResponse {
+    id: Uuid,
+    client_id: Uuid,
+    kind: ClientRouteKind,
+    server_id: Option<Uuid>,
+    route_cidr: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<WgClientRoute> for ClientRouteResponse {
+    fn from(r: WgClientRoute) -> Self {
+        Self {
+            id: r.id,
+            client_id: r.client_id,
+            kind: r.kind,
+            server_id: r.server_id,
+            route_cidr: r.route_cidr.to_string(),
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        }
+    }
+}
+
+async fn list_client_routes(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    path: web::Path<Uuid>,
+    query: ListQuery,
+) -> Result<HttpResponse, ApiError> {
+    let client_id = path.into_inner();
+    let opts = query.options(&["route_cidr", "created_at"])?;
+    let (routes, total) = store.page_client_routes(client_id, &opts).await?;
+    let resp: Vec<ClientRouteResponse> = routes.into_iter().map(Into::into).collect();
+    Ok(query.respond(resp, total))
+}
+
+async fn add_client_route(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateClientRouteRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let client_id = path.into_inner();
+    let cidr: IpNetwork = body
+        .route_cidr
+        .parse()
+        .map_err(|_| ApiError::Validation("invalid CIDR".into()))?;
+
+    let client = store.get_client(client_id).await?.ok_or(ApiError::NotFound)?;
+    match (body.kind, body.server_id) {
+        (ClientRouteKind::Extra, Some(server_id)) => {
+            let server = store.get_server(server_id).await?;
+            if server.is_none_or(|s| s.network_id != client.network_id) {
+                return Err(ApiError::Validation(
+                    "server is not in the client's network".into(),
+                ));
+            }
+        }
+        (ClientRouteKind::Extra, None) => {
+            return Err(ApiError::Validation("extra routes need a server_id".into()));
+        }
+        (ClientRouteKind::Only, Some(_)) => {
+            return Err(ApiError::Validation("only routes apply to every server".into()));
+        }
+        (ClientRouteKind::Only, None) => {}
+    }
+
+    let route = store
+        .add_client_route(client.id, body.kind, body.server_id, cidr)
+        .await?;
+    events.publish(EventKind::ClientUpdated, client.network_id, client.id);
+    Ok(HttpResponse::Created().json(ClientRouteResponse::from(route)))
+}
+
+async fn delete_client_route(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    if let Some(route) = store.delete_client_route(id).await?
+        && let Some(client) = store.get_client(route.client_id).await?
+    {
+        events.publish(EventKind::ClientUpdated, client.network_id, client.id);
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/api/clients/{id}/routes")
+            .route(web::get().to(list_client_routes))
+            .route(web::post().to(add_client_route)),
+    )
+    .service(
+        web::resource("/api/client-routes/{id}")
+            .route(web::delete().to(delete_client_route)),
+    );
+}
@@ -17,14 +17,73 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::db::vpn::{self, VpnStore};
+use crate::access::{self, AccessSchedule};
+use crate::csv::{CsvWriter, Format, FormatQuery};
+use crate::db::audit::{AuditStore, FieldChanges};
+use crate::db::usage::UsageStore;
+use crate::db::vpn::{self, ConfigOptions, VpnStore};
 use crate::error::ApiError;
+use crate::events::{EventBus, EventKind};
+use crate::excludes;
+use crate::hooks::{self, Hooks};
 use crate::extract::AuthUser;
+use crate::keepalive;
+use crate::mtu;
+use crate::names;
+use crate::notes;
+use crate::pagination::ListQuery;
+use crate::rate_limit;
+use crate::tags;
+use crate::usage;
 
 #[derive(Debug, Deserialize)]
 struct CreateClientRequest {
     network_id: Uuid,
     name: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    notes: Option<String>,
+    /// Overrides the network's MTU in the client's config.
+    mtu: Option<u32>,
+    /// Overrides the network's and servers' keepalive on the client's links.
+    persistent_keepalive: Option<i32>,
+    /// Bytes the client may move per UTC month before it is cut off.
+    monthly_quota_bytes: Option<i64>,
+    /// Caps in kilobits per second on what the client sends and receives.
+    upload_kbps: Option<u32>,
+    download_kbps: Option<u32>,
+    /// Override the network's wg-quick hooks in the client's config.
+    #[serde(flatten)]
+    hooks: Hooks,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateClientRequest {
+    /// Replaces the notes when present; an empty string clears them.
+    notes: Option<String>,
+    /// Replaces the MTU override when present; zero clears it.
+    mtu: Option<u32>,
+    /// Replaces the keepalive override when present; zero turns keepalive
+    /// off and a negative value clears it.
+    persistent_keepalive: Option<i32>,
+    /// Replaces the monthly quota when present; zero clears it.
+    monthly_quota_bytes: Option<i64>,
+    /// Replace the bandwidth caps when present; zero clears them.
+    upload_kbps: Option<u32>,
+    download_kbps: Option<u32>,
+    /// Hooks present replace the current ones; empty ones clear them.
+    #[serde(flatten)]
+    hooks: Hooks,
+}
+
+#[derive(Debug, Deserialize)]
+struct MoveClientRequest {
+    network_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetTagsRequest {
+    tags: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -35,10 +94,54 @@ struct ClientResponse {
     public_key: String,
     address_offset: i32,
     address: String,
+    access_schedule: Option<AccessSchedule>,
+    access_allowed: bool,
+    tags: Vec<String>,
+    notes: Option<String>,
+    mtu: Option<u32>,
+    persistent_keepalive: Option<i32>,
+    monthly_quota_bytes: Option<i64>,
+    /// Over this month's quota, and so left out of daemon configs.
+    quota_exceeded: bool,
+    upload_kbps: Option<u32>,
+    download_kbps: Option<u32>,
+    #[serde(flatten)]
+    hooks: Hooks,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Serialize)]
+struct ClientListItem {
+    #[serde(flatten)]
+    client: ClientResponse,
+    network_name: String,
+}
+
+fn list_response(c: vpn::WgClient, public_key: String, address: String) -> ClientResponse {
+    ClientResponse {
+        id: c.id,
+        network_id: c.network_id,
+        name: c.name,
+        public_key,
+        address_offset: c.address_offset,
+        address,
+        access_schedule: c.access_schedule.map(|s| s.0),
+        access_allowed: c.access_allowed,
+        tags: c.tags,
+        notes: c.notes,
+        mtu: c.mtu.map(|mtu| mtu as u32),
+        persistent_keepalive: c.persistent_keepalive,
+        monthly_quota_bytes: c.monthly_quota_bytes,
+        quota_exceeded: c.quota_exceeded,
+        upload_kbps: c.upload_kbps.map(|kbps| kbps as u32),
+        download_kbps: c.download_kbps.map(|kbps| kbps as u32),
+        hooks: c.hooks,
+        created_at: c.created_at,
+        updated_at: c.updated_at,
+    }
+}
+
 async fn build_response(
     store: &VpnStore,
     client: vpn::WgClient,
@@ -57,26 +160,106 @@ async fn build_response(
         public_key: key.public_key,
         address_offset: client.address_offset,
         address: address.to_string(),
+        access_schedule: client.access_schedule.map(|s| s.0),
+        access_allowed: client.access_allowed,
+        tags: client.tags,
+        notes: client.notes,
+        mtu: client.mtu.map(|mtu| mtu as u32),
+        persistent_keepalive: client.persistent_keepalive,
+        monthly_quota_bytes: client.monthly_quota_bytes,
+        quota_exceeded: client.quota_exceeded,
+        upload_kbps: client.upload_kbps.map(|kbps| kbps as u32),
+        download_kbps: client.download_kbps.map(|kbps| kbps as u32),
+        hooks: client.hooks,
         created_at: client.created_at,
         updated_at: client.updated_at,
     })
 }
 
 async fn create_client(
-    _auth: AuthUser,
+    auth: AuthUser,
     store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
     body: web::Json<CreateClientRequest>,
 ) -> Result<HttpResponse, ApiError> {
+    let name = names::normalize(&body.name).map_err(ApiError::Validation)?;
+    let tags = tags::normalize(&body.tags).map_err(ApiError::Validation)?;
+    let notes = match &body.notes {
+        Some(notes) => notes::normalize(notes).map_err(ApiError::Validation)?,
+        None => None,
+    };
+    let mtu = match body.mtu {
+        Some(m) => {
+            let network = store
+                .get_network(body.network_id)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+            mtu::normalize(m, network.cidr_ip.is_ipv6()).map_err(ApiError::Validation)?
+        }
+        None => None,
+    };
+    let keepalive = match body.persistent_keepalive {
+        Some(k) => keepalive::normalize(k).map_err(ApiError::Validation)?,
+        None => None,
+    };
+    let quota = match body.monthly_quota_bytes {
+        Some(q) => usage::normalize_quota(q).map_err(ApiError::Validation)?,
+        None => None,
+    };
+    let upload = match body.upload_kbps {
+        Some(k) => rate_limit::normalize(k, "upload_kbps").map_err(ApiError::Validation)?,
+        None => None,
+    };
+    let download = match body.download_kbps {
+        Some(k) => rate_limit::normalize(k, "download_kbps").map_err(ApiError::Validation)?,
+        None => None,
+    };
+    let hooks = Hooks::default()
+        .apply(&body.hooks)
+        .map_err(ApiError::Validation)?;
     let key = store.create_key().await?;
 
-    let client = store
-        .create_client(body.network_id, &body.name, key.id)
+    let mut client = store
+        .create_client(body.network_id, &name, key.id)
         .await?;
+    if !tags.is_empty() {
+        client = store
+            .set_client_tags(client.id, &tags)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    let settings = vpn::ClientUpdate {
+        notes: notes.is_some().then_some(notes),
+        mtu: mtu.is_some().then_some(mtu),
+        persistent_keepalive: keepalive.is_some().then_some(keepalive),
+        monthly_quota_bytes: quota.is_some().then_some(quota),
+        upload_kbps: upload.is_some().then_some(upload),
+        download_kbps: download.is_some().then_some(download),
+        hooks: (hooks != Hooks::default()).then_some(hooks),
+    };
+    if settings != vpn::ClientUpdate::default() {
+        client = store
+            .update_client(client.id, &settings)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    hooks::audit_change(
+        &audit,
+        auth.user_id,
+        "client.hooks_updated",
+        client.network_id,
+        client.id,
+        &Hooks::default(),
+        &client.hooks,
+    )
+    .await?;
 
     let servers = store.list_servers_by_network(client.network_id).await?;
     for server in &servers {
         store.ensure_psk(server.id, client.id).await?;
     }
+    events.publish(EventKind::ClientCreated, client.network_id, client.id);
 
     let resp = build_response(&store, client).await?;
     Ok(HttpResponse::Created().json(resp))
@@ -93,26 +276,190 @@ async fn get_client(
     Ok(HttpResponse::Ok().json(resp))
 }
 
+async fn update_client(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    usage_store: web::Data<UsageStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateClientRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let before = store.get_client(id).await?.ok_or(ApiError::NotFound)?;
+
+    // Everything is checked before anything is written.
+    let notes = match &body.notes {
+        Some(notes) => Some(notes::normalize(notes).map_err(ApiError::Validation)?),
+        None => None,
+    };
+    let mtu = match body.mtu {
+        Some(m) => {
+            let network = store
+                .get_network(before.network_id)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+            Some(mtu::normalize(m, network.cidr_ip.is_ipv6()).map_err(ApiError::Validation)?)
+        }
+        None => None,
+    };
+    let persistent_keepalive = match body.persistent_keepalive {
+        Some(k) => Some(keepalive::normalize(k).map_err(ApiError::Validation)?),
+        None => None,
+    };
+    let monthly_quota_bytes = match body.monthly_quota_bytes {
+        Some(q) => Some(usage::normalize_quota(q).map_err(ApiError::Validation)?),
+        None => None,
+    };
+    let upload_kbps = match body.upload_kbps {
+        Some(k) => Some(rate_limit::normalize(k, "upload_kbps").map_err(ApiError::Validation)?),
+        None => None,
+    };
+    let download_kbps = match body.download_kbps {
+        Some(k) => Some(rate_limit::normalize(k, "download_kbps").map_err(ApiError::Validation)?),
+        None => None,
+    };
+    let hooks = before
+        .hooks
+        .apply(&body.hooks)
+        .map_err(ApiError::Validation)?;
+    let settings = vpn::ClientUpdate {
+        notes,
+        mtu,
+        persistent_keepalive,
+        monthly_quota_bytes,
+        upload_kbps,
+        download_kbps,
+        hooks: (hooks != before.hooks).then_some(hooks),
+    };
+
+    let mut client = store
+        .update_client(id, &settings)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let mut changes = FieldChanges::default();
+    changes
+        .field("notes", &before.notes, &client.notes)
+        .field("mtu", &before.mtu, &client.mtu)
+        .field(
+            "persistent_keepalive",
+            &before.persistent_keepalive,
+            &client.persistent_keepalive,
+        )
+        .field(
+            "monthly_quota_bytes",
+            &before.monthly_quota_bytes,
+            &client.monthly_quota_bytes,
+        )
+        .field("upload_kbps", &before.upload_kbps, &client.upload_kbps)
+        .field(
+            "download_kbps",
+            &before.download_kbps,
+            &client.download_kbps,
+        )
+        .field("hooks", &before.hooks, &client.hooks);
+    if !changes.is_empty() {
+        audit
+            .record(
+                Some(auth.user_id),
+                "client.updated",
+                Some(client.network_id),
+                Some(client.id),
+                changes.into_details(),
+            )
+            .await?;
+    }
+    if settings.monthly_quota_bytes.is_some() {
+        // A new quota may put the client over it, or lift the cut-off.
+        let quota_changes = usage_store.sync_quotas(Some(client.network_id)).await?;
+        usage::announce_quota_changes(&quota_changes, &audit, &events).await;
+        client = store.get_client(id).await?.ok_or(ApiError::NotFound)?;
+    }
+    events.publish(EventKind::ClientUpdated, client.network_id, client.id);
+
+    let resp = build_response(&store, client).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
 async fn delete_client(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
     let client = store.get_client(id).await?.ok_or(ApiError::NotFound)?;
     store.delete_client(id).await?;
     store.delete_key(client.key_id).await?;
+    events.publish(EventKind::ClientDeleted, client.network_id, client.id);
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Move a client to another network, keeping its keys. It gets a new
+/// address there, and both networks' daemons pick up the change through
+/// their config serials.
+async fn move_client(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+    body: web::Json<MoveClientRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let before = store.get_client(id).await?.ok_or(ApiError::NotFound)?;
+    if before.network_id == body.network_id {
+        return Err(ApiError::Validation("client is already in that network".into()));
+    }
+    let from = store
+        .get_network(before.network_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let client = store
+        .move_client(id, body.network_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let servers = store.list_servers_by_network(client.network_id).await?;
+    for server in &servers {
+        store.ensure_psk(server.id, client.id).await?;
+    }
+
+    let resp = build_response(&store, client).await?;
+    let details = serde_json::json!({
+        "from_network_id": from.id,
+        "from_address": vpn::compute_address(&from, before.address_offset).to_string(),
+        "to_network_id": resp.network_id,
+        "to_address": resp.address,
+    });
+    // Recorded in both networks, so each one's log tells the whole story.
+    for network_id in [from.id, resp.network_id] {
+        audit
+            .record(
+                Some(auth.user_id),
+                "client.moved",
+                Some(network_id),
+                Some(id),
+                details.clone(),
+            )
+            .await?;
+        events.publish(EventKind::ClientUpdated, network_id, id);
+    }
+    tracing::info!(client_id = %id, from = %from.id, to = %resp.network_id, "client moved");
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
 pub async fn list_clients(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
     path: web::Path<Uuid>,
+    query: ListQuery,
 ) -> Result<HttpResponse, ApiError> {
     let network_id = path.into_inner();
     let network = store.get_network(network_id).await?.ok_or(ApiError::NotFound)?;
-    let clients = store.list_clients_by_network(network_id).await?;
+    let opts = query.options(&["name", "created_at", "address_offset"])?;
+    let (clients, total) = store.page_clients(network_id, &opts).await?;
 
     let key_ids: Vec<_> = clients.iter().map(|c| c.key_id).collect();
     let keys = store.get_keys_batch(&key_ids).await?;
@@ -120,36 +467,97 @@ pub async fn list_clients(
     let resp: Vec<_> = clients
         .into_iter()
         .map(|c| {
-            let key = &keys[&c.key_id];
+            let public_key = keys[&c.key_id].public_key.clone();
             let address = vpn::compute_address(&network, c.address_offset);
-            ClientResponse {
-                id: c.id,
-                network_id: c.network_id,
-                name: c.name,
-                public_key: key.public_key.clone(),
-                address_offset: c.address_offset,
-                address: address.to_string(),
-                created_at: c.created_at,
-                updated_at: c.updated_at,
-            }
+            list_response(c, public_key, address.to_string())
         })
         .collect();
-    Ok(HttpResponse::Ok().json(resp))
+    Ok(query.respond(resp, total))
+}
+
+/// Clients across all networks. `?format=csv` exports every match as CSV,
+/// ignoring paging.
+async fn list_all_clients(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    query: ListQuery,
+    format: web::Query<FormatQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let mut opts = query.options(&["name", "network_name", "created_at"])?;
+    if format.format == Format::Csv {
+        opts.limit = i64::MAX;
+        opts.offset = 0;
+    }
+    let (clients, total) = store.page_all_clients(&opts).await?;
+
+    if format.format == Format::Csv {
+        let mut csv = CsvWriter::default();
+        csv.row([
+            "id",
+            "network_id",
+            "network_name",
+            "name",
+            "address",
+            "public_key",
+            "access_allowed",
+            "tags",
+            "notes",
+            "created_at",
+        ]);
+        for l in clients {
+            let c = l.client;
+            csv.row([
+                c.id.to_string(),
+                c.network_id.to_string(),
+                l.network_name,
+                c.name,
+                l.address,
+                l.public_key,
+                c.access_allowed.to_string(),
+                c.tags.join(";"),
+                c.notes.unwrap_or_default(),
+                c.created_at.to_rfc3339(),
+            ]);
+        }
+        return Ok(csv.respond("clients.csv"));
+    }
+
+    let resp: Vec<_> = clients
+        .into_iter()
+        .map(|l| ClientListItem {
+            client: list_response(l.client, l.public_key, l.address),
+            network_name: l.network_name,
+        })
+        .collect();
+    Ok(query.respond(resp, total))
 }
 
 #[derive(Debug, Deserialize)]
 struct ConfigQuery {
     #[serde(default)]
     forward_internet: bool,
+    /// Comma-separated CIDRs to keep off the tunnel, on top of the network's.
+    exclude: Option<String>,
+    /// Overrides the network's LAN access setting.
+    lan_access: Option<bool>,
 }
 
 async fn client_config(
-    _auth: AuthUser,
+    auth: AuthUser,
     store: web::Data<VpnStore>,
     path: web::Path<Uuid>,
     query: web::Query<ConfigQuery>,
 ) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
+    let exclude: Vec<&str> = match &query.exclude {
+        Some(exclude) => exclude.split(',').collect(),
+        None => Vec::new(),
+    };
+    let options = ConfigOptions {
+        forward_internet: query.forward_internet,
+        exclude: excludes::parse(&exclude).map_err(ApiError::Validation)?,
+        lan_access: query.lan_access,
+    };
     let client = store.get_client(id).await?.ok_or(ApiError::NotFound)?;
     let key = store.get_key(client.key_id).await?;
 
@@ -170,46 +578,229 @@ async fn client_config(
         }
     }
 
+    let client_routes = store.list_routes_by_client(client.id).await?;
     let config = client.wg_quick_config(
         &key,
         &snapshot,
-        query.forward_internet,
+        &client_routes,
+        &options,
         &preshared_keys,
+        auth.claims.locale,
     );
 
     Ok(HttpResponse::Ok().json(serde_json::json!({ "config": config })))
 }
 
+#[derive(Debug, Deserialize)]
+struct UsageQuery {
+    /// How far back to report, e.g. `24h` or `30d`.
+    #[serde(default = "default_usage_range")]
+    range: String,
+}
+
+fn default_usage_range() -> String {
+    "7d".into()
+}
+
+#[derive(Debug, Serialize)]
+struct UsageResponse {
+    /// `hour` or `day`, in UTC.
+    step: &'static str,
+    since: DateTime<Utc>,
+    rx_bytes: i64,
+    tx_bytes: i64,
+    /// Buckets with traffic, oldest first.
+    points: Vec<UsagePointResponse>,
+}
+
+#[derive(Debug, Serialize)]
+struct UsagePointResponse {
+    at: DateTime<Utc>,
+    rx_bytes: i64,
+    tx_bytes: i64,
+}
+
+/// Bytes the client moved through its servers over `?range=`. `rx_bytes` is
+/// what the servers received from it, `tx_bytes` what they sent it.
+async fn client_usage(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    usage_store: web::Data<UsageStore>,
+    path: web::Path<Uuid>,
+    query: web::Query<UsageQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let span = usage::parse_range(&query.range).map_err(ApiError::Validation)?;
+    let step = usage::step(span);
+    let client = store
+        .get_client(path.into_inner())
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let since = Utc::now() - span;
+    let points: Vec<UsagePointResponse> = usage_store
+        .series(client.id, since, step)
+        .await?
+        .into_iter()
+        .map(|p| UsagePointResponse {
+            at: p.at,
+            rx_bytes: p.rx_bytes,
+            tx_bytes: p.tx_bytes,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(UsageResponse {
+        step: step.as_str(),
+        since,
+        rx_bytes: points.iter().map(|p| p.rx_bytes).sum(),
+        tx_bytes: points.iter().map(|p| p.tx_bytes).sum(),
+        points,
+    }))
+}
+
 async fn rotate_client_psk(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
     let client = store.get_client(id).await?.ok_or(ApiError::NotFound)?;
     let servers = store.list_servers_by_network(client.network_id).await?;
     let server_ids: Vec<_> = servers.iter().map(|s| s.id).collect();
-    store.rotate_psks_for_client(client.id, &server_ids).await?;
+    store.rotate_psks(&[client.id], &server_ids).await?;
+    events.publish(EventKind::ClientUpdated, client.network_id, client.id);
     Ok(HttpResponse::NoContent().finish())
 }
 
+async fn set_tags(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+    body: web::Json<SetTagsRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let tags = tags::normalize(&body.tags).map_err(ApiError::Validation)?;
+    let client = store
+        .set_client_tags(path.into_inner(), &tags)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    audit
+        .record(
+            Some(auth.user_id),
+            "client.tags.set",
+            Some(client.network_id),
+            Some(client.id),
+            serde_json::json!({ "tags": tags }),
+        )
+        .await?;
+    events.publish(EventKind::ClientUpdated, client.network_id, client.id);
+
+    let resp = build_response(&store, client).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+async fn set_access_schedule(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+    body: web::Json<AccessSchedule>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let schedule = body.into_inner();
+    schedule.validate().map_err(ApiError::Validation)?;
+
+    let allowed = schedule.is_open(Utc::now());
+    let client = store
+        .set_client_access_schedule(id, Some(&schedule), allowed)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    audit
+        .record(
+            Some(auth.user_id),
+            "client.access_schedule.set",
+            Some(client.network_id),
+            Some(client.id),
+            serde_json::json!({ "schedule": schedule }),
+        )
+        .await?;
+    events.publish(EventKind::ClientUpdated, client.network_id, client.id);
+
+    let resp = build_response(&store, client).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+async fn clear_access_schedule(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let client = store.get_client(id).await?.ok_or(ApiError::NotFound)?;
+    // Its tags' schedules take over.
+    let tag_schedules = store
+        .list_tag_access_schedules(Some(client.network_id))
+        .await?;
+    let tagged = access::tagged(&tag_schedules, client.network_id, &client.tags);
+    let allowed = access::is_allowed(None, &tagged, Utc::now());
+    let client = store
+        .set_client_access_schedule(id, None, allowed)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    audit
+        .record(
+            Some(auth.user_id),
+            "client.access_schedule.clear",
+            Some(client.network_id),
+            Some(client.id),
+            serde_json::json!({}),
+        )
+        .await?;
+    events.publish(EventKind::ClientUpdated, client.network_id, client.id);
+
+    let resp = build_response(&store, client).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::resource("/api/clients")
+            .route(web::get().to(list_all_clients))
             .route(web::post().to(create_client)),
     )
     .service(
         web::resource("/api/clients/{id}")
             .route(web::get().to(get_client))
+            .route(web::patch().to(update_client))
             .route(web::delete().to(delete_client)),
     )
+    .service(
+        web::resource("/api/clients/{id}/move")
+            .route(web::post().to(move_client)),
+    )
     .service(
         web::resource("/api/clients/{id}/config")
             .route(web::get().to(client_config)),
     )
+    .service(
+        web::resource("/api/clients/{id}/usage")
+            .route(web::get().to(client_usage)),
+    )
     .service(
         web::resource("/api/clients/{id}/psk/rotate")
             .route(web::post().to(rotate_client_psk)),
     )
+    .service(
+        web::resource("/api/clients/{id}/tags")
+            .route(web::put().to(set_tags)),
+    )
+    .service(
+        web::resource("/api/clients/{id}/access-schedule")
+            .route(web::put().to(set_access_schedule))
+            .route(web::delete().to(clear_access_schedule)),
+    )
     ;
 }
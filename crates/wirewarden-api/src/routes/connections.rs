@@ -0,0 +1,83 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::connection::{ConnectionEvent, ConnectionStore, PeerKind};
+use crate::error::ApiError;
+use crate::extract::AuthUser;
+
+#[derive(Debug, Deserialize)]
+struct ConnectionQuery {
+    network_id: Option<Uuid>,
+    peer_id: Option<Uuid>,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ConnectionEventResponse {
+    id: Uuid,
+    network_id: Uuid,
+    server_id: Uuid,
+    peer_id: Uuid,
+    peer_kind: PeerKind,
+    peer_name: String,
+    connected: bool,
+    last_handshake: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<ConnectionEvent> for ConnectionEventResponse {
+    fn from(e: ConnectionEvent) -> Self {
+        Self {
+            id: e.id,
+            network_id: e.network_id,
+            server_id: e.server_id,
+            peer_id: e.peer_id,
+            peer_kind: e.peer_kind,
+            peer_name: e.peer_name,
+            connected: e.connected,
+            last_handshake: e.last_handshake,
+            created_at: e.created_at,
+        }
+    }
+}
+
+async fn list_connections(
+    _auth: AuthUser,
+    connections: web::Data<ConnectionStore>,
+    query: web::Query<ConnectionQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let limit = query.limit.clamp(1, 1000);
+    let events = connections
+        .list(query.network_id, query.peer_id, limit)
+        .await?;
+    let resp: Vec<_> = events
+        .into_iter()
+        .map(ConnectionEventResponse::from)
+        .collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/api/connections").route(web::get().to(list_connections)));
+}
@@ -12,49 +12,401 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use actix_web::{web, HttpResponse};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::db::vpn::{self, VpnStore};
+use actix_web::http::header::{ContentType, ETag, EntityTag, IfNoneMatch};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use chrono::Utc;
+use ipnetwork::IpNetwork;
+use serde::Deserialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::acl;
+use crate::config::Config;
+use crate::connections;
+use crate::daemon_cache::{DaemonConfigCache, RenderedConfig};
+use crate::db::audit::AuditStore;
+use crate::db::connection::ConnectionStore;
+use crate::db::usage::UsageStore;
+use crate::db::vpn::{
+    self, CheckIn, Network, Topology, VpnStore, WgAclRule, WgClient, WgKey, WgServer,
+    WgServerRoute,
+};
+use crate::dns_zone::Zone;
 use crate::error::ApiError;
+use crate::events::{Event, EventBus, EventKind};
 use crate::extract::AuthServer;
-use wirewarden_types::daemon::{DaemonConfig, DaemonNetworkInfo, DaemonPeer, DaemonServerInfo};
+use crate::keepalive;
+use crate::mtu;
+use crate::policy_routing::PolicyRouting;
+use crate::signing::ConfigSigner;
+use crate::usage;
+use wirewarden_types::daemon::{
+    CAPABILITIES_HEADER, CONFIG_SERIAL_HEADER, CONFIG_SIGNATURE_HEADER, Capabilities,
+    DAEMON_VERSION_HEADER, DaemonAclAction, DaemonConfig, DaemonConfigDelta, DaemonDnsRecord,
+    DaemonDnsZone, DaemonNetworkInfo, DaemonPeer, DaemonServerInfo, DaemonTelemetry,
+    MIN_DAEMON_VERSION_HEADER,
+};
+
+/// Upper bound on `?wait=`, kept under common proxy idle timeouts.
+const MAX_WAIT: Duration = Duration::from_secs(55);
 
+#[derive(Debug, Deserialize)]
+struct ConfigQuery {
+    /// Seconds to hold the request open while the config still matches
+    /// `If-None-Match`.
+    #[serde(default)]
+    wait: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaQuery {
+    /// Config serial the daemon holds.
+    since: i64,
+    /// Seconds to hold the request open while the serial is still `since`.
+    #[serde(default)]
+    wait: u64,
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn daemon_config(
+    req: HttpRequest,
+    AuthServer(mut server): AuthServer,
+    store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
+    cache: web::Data<DaemonConfigCache>,
+    signer: web::Data<ConfigSigner>,
+    config: web::Data<Config>,
+    query: web::Query<ConfigQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let caps = Capabilities::from_header(header(CAPABILITIES_HEADER));
+    let if_none_match = req.get_header::<IfNoneMatch>();
+    let daemon_version = header(DAEMON_VERSION_HEADER);
+    tracing::debug!(
+        server_id = %server.id,
+        daemon_version,
+        capabilities = caps.to_header(),
+        "daemon config requested"
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(query.wait).min(MAX_WAIT);
+    // Subscribed up front so a change made while rendering still wakes us.
+    let mut rx = events.subscribe_all();
+
+    loop {
+        let (server_id, network_id) = (server.id, server.network_id);
+        let serial = check_in(&store, &events, &server, daemon_version)
+            .await?
+            .config_serial;
+        let rendered = rendered_config(&store, &cache, server, serial, caps).await?;
+
+        let current = is_current(if_none_match.as_ref(), &rendered.etag);
+        if current && Instant::now() < deadline {
+            wait_for_network(&mut rx, network_id, deadline).await;
+            // Reloaded so renames and port changes are picked up.
+            server = store.get_server(server_id).await?.ok_or(ApiError::NotFound)?;
+            continue;
+        }
+
+        let mut resp = if current {
+            HttpResponse::NotModified()
+        } else {
+            HttpResponse::Ok()
+        };
+        resp.insert_header(ETag(rendered.etag));
+        resp.insert_header((CONFIG_SERIAL_HEADER, serial.to_string()));
+        if let Some(min) = config.min_daemon_version {
+            resp.insert_header((MIN_DAEMON_VERSION_HEADER, min.to_string()));
+        }
+        if current {
+            return Ok(resp.finish());
+        }
+        let signature = signer.sign(server_id, serial, &rendered.body);
+        resp.insert_header((CONFIG_SIGNATURE_HEADER, signature));
+        return Ok(resp.content_type(ContentType::json()).body(rendered.body));
+    }
+}
+
+/// The peers changed since the serial the daemon holds, or every peer when
+/// that config is no longer cached. Long-polls like [`daemon_config`], with
+/// the serial standing in for the ETag.
+#[allow(clippy::too_many_arguments)]
+async fn daemon_config_delta(
+    req: HttpRequest,
+    AuthServer(mut server): AuthServer,
+    store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
+    cache: web::Data<DaemonConfigCache>,
+    signer: web::Data<ConfigSigner>,
+    config: web::Data<Config>,
+    query: web::Query<DeltaQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let caps = Capabilities::from_header(header(CAPABILITIES_HEADER));
+    let daemon_version = header(DAEMON_VERSION_HEADER);
+
+    let deadline = Instant::now() + Duration::from_secs(query.wait).min(MAX_WAIT);
+    let mut rx = events.subscribe_all();
+
+    loop {
+        let (server_id, network_id) = (server.id, server.network_id);
+        let serial = check_in(&store, &events, &server, daemon_version)
+            .await?
+            .config_serial;
+
+        let current = serial == query.since;
+        if current && Instant::now() < deadline {
+            wait_for_network(&mut rx, network_id, deadline).await;
+            server = store.get_server(server_id).await?.ok_or(ApiError::NotFound)?;
+            continue;
+        }
+
+        let mut resp = if current {
+            HttpResponse::NotModified()
+        } else {
+            HttpResponse::Ok()
+        };
+        resp.insert_header((CONFIG_SERIAL_HEADER, serial.to_string()));
+        if let Some(min) = config.min_daemon_version {
+            resp.insert_header((MIN_DAEMON_VERSION_HEADER, min.to_string()));
+        }
+        if current {
+            return Ok(resp.finish());
+        }
+        let base = cache.config_at(server_id, query.since, caps);
+        let latest = current_config(&store, &cache, server, serial, caps).await?;
+        let delta = DaemonConfigDelta::between(base.as_deref(), &latest, serial);
+        tracing::debug!(
+            server_id = %server_id,
+            since = query.since,
+            serial,
+            full = delta.full,
+            upserted = delta.upserted.len(),
+            removed = delta.removed.len(),
+            "sending config delta"
+        );
+        let body = serde_json::to_vec(&delta).map_err(|_| ApiError::Internal)?;
+        let signature = signer.sign(server_id, serial, &body);
+        resp.insert_header((CONFIG_SIGNATURE_HEADER, signature));
+        return Ok(resp.content_type(ContentType::json()).body(body));
+    }
+}
+
+/// A daemon stopping on purpose: mark its server offline now rather than
+/// once its check-ins lapse.
+async fn daemon_offline(
     AuthServer(server): AuthServer,
     store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
 ) -> Result<HttpResponse, ApiError> {
+    if store.mark_server_offline(server.id).await? {
+        tracing::info!(server_id = %server.id, "daemon reported shutdown, server offline");
+        events.publish(EventKind::ServerOffline, server.network_id, server.id);
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Per-peer transfer counters and handshakes from the server's interface,
+/// for client usage history and peer connection state.
+async fn daemon_telemetry(
+    AuthServer(server): AuthServer,
+    usage: web::Data<UsageStore>,
+    connections: web::Data<ConnectionStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    body: web::Json<DaemonTelemetry>,
+) -> Result<HttpResponse, ApiError> {
+    usage
+        .record(server.id, server.network_id, &body.peers)
+        .await?;
+    let changes = usage.sync_quotas(Some(server.network_id)).await?;
+    usage::announce_quota_changes(&changes, &audit, &events).await;
+    if body.handshakes {
+        let changes = connections
+            .record(server.id, server.network_id, &body.peers, Utc::now())
+            .await?;
+        connections::announce(&changes, &events);
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The config for `server` at `serial`, rendered on a cache miss.
+async fn rendered_config(
+    store: &VpnStore,
+    cache: &DaemonConfigCache,
+    server: WgServer,
+    serial: i64,
+    caps: Capabilities,
+) -> Result<RenderedConfig, ApiError> {
+    if let Some(rendered) = cache.get(server.id, serial, caps) {
+        return Ok(rendered);
+    }
+    let (_, rendered) = render_and_cache(store, cache, server, serial, caps).await?;
+    Ok(rendered)
+}
+
+/// The config for `server` at `serial` and its serialized form, which gRPC
+/// sends together, rendered when either is not cached.
+pub(crate) async fn cached_config(
+    store: &VpnStore,
+    cache: &DaemonConfigCache,
+    server: WgServer,
+    serial: i64,
+    caps: Capabilities,
+) -> Result<(Arc<DaemonConfig>, RenderedConfig), ApiError> {
+    let cached = (
+        cache.config_at(server.id, serial, caps),
+        cache.get(server.id, serial, caps),
+    );
+    if let (Some(config), Some(rendered)) = cached {
+        return Ok((config, rendered));
+    }
+    render_and_cache(store, cache, server, serial, caps).await
+}
+
+async fn render_and_cache(
+    store: &VpnStore,
+    cache: &DaemonConfigCache,
+    server: WgServer,
+    serial: i64,
+    caps: Capabilities,
+) -> Result<(Arc<DaemonConfig>, RenderedConfig), ApiError> {
+    let config = Arc::new(load_inputs(store, server).await?.render(caps)?);
+    let body = Bytes::from(serde_json::to_vec(&*config).map_err(|_| ApiError::Internal)?);
+    let rendered = RenderedConfig::new(body);
+    cache.insert(config.server.id, serial, caps, rendered.clone());
+    cache.remember(config.server.id, serial, caps, config.clone());
+    Ok((config, rendered))
+}
+
+/// The config for `server` at `serial`, rendered when it is not among the
+/// cached ones.
+async fn current_config(
+    store: &VpnStore,
+    cache: &DaemonConfigCache,
+    server: WgServer,
+    serial: i64,
+    caps: Capabilities,
+) -> Result<Arc<DaemonConfig>, ApiError> {
+    if let Some(config) = cache.config_at(server.id, serial, caps) {
+        return Ok(config);
+    }
+    let config = Arc::new(load_inputs(store, server).await?.render(caps)?);
+    cache.remember(config.server.id, serial, caps, config.clone());
+    Ok(config)
+}
+
+/// Whether `If-None-Match` names the config the daemon would be sent.
+fn is_current(if_none_match: Option<&IfNoneMatch>, etag: &EntityTag) -> bool {
+    match if_none_match {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|t| t.weak_eq(etag)),
+        None => false,
+    }
+}
+
+/// Wait until an event in `network_id` arrives or `deadline` passes.
+async fn wait_for_network(rx: &mut broadcast::Receiver<Event>, network_id: Uuid, deadline: Instant) {
+    let sleep = tokio::time::sleep_until(deadline);
+    tokio::pin!(sleep);
+    loop {
+        tokio::select! {
+            _ = &mut sleep => return,
+            event = rx.recv() => match event {
+                Ok(e) if e.network_id == network_id => return,
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => return,
+                Err(RecvError::Closed) => {
+                    sleep.await;
+                    return;
+                }
+            },
+        }
+    }
+}
+
+/// Longest daemon version stored; `git describe` output is far shorter.
+const MAX_DAEMON_VERSION_LEN: usize = 64;
+
+/// Record a daemon check-in, announcing the server if it was offline.
+pub(crate) async fn check_in(
+    store: &VpnStore,
+    events: &EventBus,
+    server: &WgServer,
+    daemon_version: Option<&str>,
+) -> Result<CheckIn, ApiError> {
+    if daemon_version.is_some_and(|v| v.len() > MAX_DAEMON_VERSION_LEN) {
+        return Err(ApiError::Validation(format!(
+            "daemon version must be at most {MAX_DAEMON_VERSION_LEN} characters"
+        )));
+    }
+    let check_in = store
+        .touch_server(server.id, daemon_version)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if check_in.was_offline {
+        events.publish(EventKind::ServerOnline, server.network_id, server.id);
+    }
+    Ok(check_in)
+}
+
+/// Everything a server's config is rendered from.
+#[derive(Debug)]
+struct ConfigInputs {
+    server: WgServer,
+    server_key: WgKey,
+    network: Network,
+    other_servers: Vec<WgServer>,
+    clients: Vec<WgClient>,
+    keys: HashMap<Uuid, WgKey>,
+    routes: HashMap<Uuid, Vec<WgServerRoute>>,
+    /// Preshared keys between `server` and each client.
+    psks: HashMap<Uuid, String>,
+    acl_rules: Vec<WgAclRule>,
+}
+
+async fn load_inputs(store: &VpnStore, server: WgServer) -> Result<ConfigInputs, ApiError> {
     let network = store
         .get_network(server.network_id)
         .await?
         .ok_or(ApiError::NotFound)?;
-
     let server_key = store.get_key(server.key_id).await?;
-    let address = vpn::compute_address(&network, server.address_offset);
-    let cidr = network.cidr_ip.to_string();
-
-    let server_info = DaemonServerInfo {
-        id: server.id,
-        name: server.name.clone(),
-        private_key: server_key.private_key,
-        public_key: server_key.public_key,
-        address: format!("{address}/{}", network.prefix()),
-        listen_port: server.endpoint_port,
-    };
-
-    let network_info = DaemonNetworkInfo {
-        id: network.id,
-        name: network.name.clone(),
-        cidr,
-        persistent_keepalive: network.persistent_keepalive,
-    };
-
-    let (servers, clients) = futures::future::try_join(
+
+    // A disabled network keeps its interfaces up but admits no peers.
+    if !network.enabled {
+        return Ok(ConfigInputs {
+            server,
+            server_key,
+            network,
+            other_servers: Vec::new(),
+            clients: Vec::new(),
+            keys: HashMap::new(),
+            routes: HashMap::new(),
+            psks: HashMap::new(),
+            acl_rules: Vec::new(),
+        });
+    }
+
+    let (servers, clients, acl_rules) = futures::future::try_join3(
         store.list_servers_by_network(server.network_id),
         store.list_clients_by_network(server.network_id),
+        store.list_acl_rules_by_network(server.network_id),
     )
     .await?;
 
-    let other_servers: Vec<_> = servers.iter().filter(|s| s.id != server.id).collect();
+    let other_servers: Vec<_> = servers.into_iter().filter(|s| s.id != server.id).collect();
+    // Clients outside their access window are left out until it reopens,
+    // and those over quota until the month turns.
+    let clients: Vec<_> = clients
+        .into_iter()
+        .filter(|c| c.access_allowed && !c.quota_exceeded)
+        .collect();
 
     let key_ids: Vec<_> = other_servers
         .iter()
@@ -67,61 +419,479 @@ async fn daemon_config(
         other_servers.iter().map(|s| store.list_routes_by_server(s.id)),
     )
     .await?;
-    let server_routes: std::collections::HashMap<_, _> = other_servers
+    let routes = other_servers
         .iter()
         .map(|s| s.id)
         .zip(route_lists)
         .collect();
 
-    let mut peers = Vec::with_capacity(key_ids.len());
+    let client_ids: Vec<_> = clients.iter().map(|c| c.id).collect();
+    let psks = store.ensure_psks(server.id, &client_ids).await?;
 
-    for other in &other_servers {
-        let key = &keys[&other.key_id];
-        let ip = vpn::compute_address(&network, other.address_offset);
-        let endpoint = other
-            .endpoint_host
-            .as_ref()
-            .map(|h| format!("{h}:{}", other.endpoint_port));
+    Ok(ConfigInputs {
+        server,
+        server_key,
+        network,
+        other_servers,
+        clients,
+        keys,
+        routes,
+        psks,
+        acl_rules,
+    })
+}
 
-        let mut allowed_ips = vec![format!("{ip}/32")];
-        if let Some(routes) = server_routes.get(&other.id) {
-            for route in routes {
-                allowed_ips.push(route.route_cidr.to_string());
+impl ConfigInputs {
+    /// Render for a daemon with `caps`, leaving out what it cannot handle.
+    /// Deny rules are refused instead: dropping them would open traffic the
+    /// network blocks.
+    fn render(&self, caps: Capabilities) -> Result<DaemonConfig, ApiError> {
+        let network = &self.network;
+        let address = vpn::compute_address(network, self.server.address_offset);
+
+        // Hub servers leave each other out; clients still reach every one.
+        let servers: &[WgServer] = match network.topology {
+            Topology::Mesh => &self.other_servers,
+            Topology::Hub => &[],
+        };
+        let mut peers = Vec::with_capacity(servers.len() + self.clients.len());
+
+        for other in servers {
+            let key = &self.keys[&other.key_id];
+            let ip = vpn::compute_address(network, other.address_offset);
+            let endpoint = other
+                .endpoint_host
+                .as_ref()
+                .map(|h| format!("{h}:{}", other.endpoint_port));
+
+            let mut allowed_ips = vec![IpNetwork::from(ip).to_string()];
+            if let Some(routes) = self.routes.get(&other.id) {
+                // Routes of the other family have no tunnel address to
+                // travel between, e.g. IPv4 routes on an IPv6-only network.
+                for route in routes {
+                    if route.route_cidr.is_ipv4() == network.cidr_ip.is_ipv4() {
+                        allowed_ips.push(route.route_cidr.to_string());
+                    }
+                }
             }
+
+            peers.push(DaemonPeer {
+                public_key: key.public_key.clone(),
+                allowed_ips,
+                endpoint,
+                preshared_key: None,
+                persistent_keepalive: keepalive::link(
+                    other.persistent_keepalive,
+                    self.server.persistent_keepalive,
+                )
+                .filter(|_| caps.peer_keepalive),
+                upload_kbps: None,
+                download_kbps: None,
+            });
         }
 
-        peers.push(DaemonPeer {
-            public_key: key.public_key.clone(),
-            allowed_ips,
-            endpoint,
-            preshared_key: None,
-        });
-    }
+        for client in &self.clients {
+            let key = &self.keys[&client.key_id];
+            let ip = vpn::compute_address(network, client.address_offset);
+            peers.push(DaemonPeer {
+                public_key: key.public_key.clone(),
+                allowed_ips: vec![IpNetwork::from(ip).to_string()],
+                endpoint: None,
+                preshared_key: self
+                    .psks
+                    .get(&client.id)
+                    .filter(|_| caps.preshared_keys)
+                    .cloned(),
+                persistent_keepalive: keepalive::link(
+                    client.persistent_keepalive,
+                    self.server.persistent_keepalive,
+                )
+                .filter(|_| caps.peer_keepalive),
+                upload_kbps: client
+                    .upload_kbps
+                    .filter(|_| caps.rate_limits)
+                    .map(|kbps| kbps as u32),
+                download_kbps: client
+                    .download_kbps
+                    .filter(|_| caps.rate_limits)
+                    .map(|kbps| kbps as u32),
+            });
+        }
 
-    for client in &clients {
-        let key = &keys[&client.key_id];
-        let ip = vpn::compute_address(&network, client.address_offset);
-        let preshared_key = store.ensure_psk(server.id, client.id).await?;
-        peers.push(DaemonPeer {
-            public_key: key.public_key.clone(),
-            allowed_ips: vec![format!("{ip}/32")],
-            endpoint: None,
-            preshared_key: Some(preshared_key),
-        });
-    }
+        let acl = acl::compile(&self.acl_rules, &acl::tag_members(network, &self.clients));
+        if !caps.acl && acl.iter().any(|rule| rule.action == DaemonAclAction::Deny) {
+            tracing::warn!(
+                server_id = %self.server.id,
+                "refusing config to a daemon without ACL support, network has deny rules"
+            );
+            return Err(ApiError::AclUnsupported);
+        }
 
-    let config = DaemonConfig {
-        server: server_info,
-        network: network_info,
-        peers,
-    };
+        let routing = if caps.policy_routing {
+            self.server.policy_routing()
+        } else {
+            PolicyRouting::default()
+        };
+        Ok(DaemonConfig {
+            version: caps.config_version(),
+            server: DaemonServerInfo {
+                id: self.server.id,
+                name: self.server.name.clone(),
+                private_key: self.server_key.private_key.clone(),
+                public_key: self.server_key.public_key.clone(),
+                address: format!("{address}/{}", network.prefix()),
+                listen_port: self.server.effective_listen_port(),
+                manage_nat: caps.nat
+                    && self.server.forwards_internet_traffic
+                    && self.server.manage_nat,
+                fwmark: routing.fwmark,
+                route_table: routing.route_table,
+                rule_priority: routing.rule_priority,
+                mtu: mtu::effective(self.server.mtu, network.mtu).filter(|_| caps.mtu),
+            },
+            network: DaemonNetworkInfo {
+                id: network.id,
+                name: network.name.clone(),
+                cidr: network.cidr_ip.to_string(),
+                persistent_keepalive: network.persistent_keepalive,
+                dns_servers: if caps.dns {
+                    network.dns_servers.clone()
+                } else {
+                    Vec::new()
+                },
+                dns_zone: self.dns_zone().filter(|_| caps.dns),
+                acl: if caps.acl { acl } else { Vec::new() },
+            },
+            peers,
+        })
+    }
 
-    Ok(HttpResponse::Ok().json(config))
+    /// Hostnames of this server and the peers it admits, or `None` when the
+    /// network's name makes no DNS label.
+    fn dns_zone(&self) -> Option<DaemonDnsZone> {
+        let servers = std::iter::once(&self.server).chain(&self.other_servers);
+        let zone = Zone::build(&self.network, servers, &self.clients).ok()?;
+        Some(DaemonDnsZone {
+            origin: zone.origin,
+            records: zone
+                .records
+                .into_iter()
+                .map(|r| DaemonDnsRecord {
+                    name: r.name,
+                    address: r.address.to_string(),
+                })
+                .collect(),
+        })
+    }
 }
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::resource("/api/daemon/config")
             .route(web::get().to(daemon_config)),
-    );
+    )
+    .service(
+        web::resource("/api/daemon/config/delta").route(web::get().to(daemon_config_delta)),
+    )
+    .service(web::resource("/api/daemon/offline").route(web::post().to(daemon_offline)))
+    .service(web::resource("/api/daemon/telemetry").route(web::post().to(daemon_telemetry)));
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+
+    use crate::db::vpn::AclAction;
+    use crate::hooks::Hooks;
+    use wirewarden_types::daemon::CONFIG_VERSION;
+
+    fn key(n: u128) -> WgKey {
+        WgKey {
+            id: Uuid::from_u128(n),
+            private_key: format!("private-{n}"),
+            public_key: format!("public-{n}"),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn server(n: u128, offset: i32) -> WgServer {
+        WgServer {
+            id: Uuid::from_u128(n),
+            network_id: Uuid::nil(),
+            name: format!("server-{n}"),
+            key_id: Uuid::from_u128(n),
+            api_token: String::new(),
+            address_offset: offset,
+            forwards_internet_traffic: false,
+            manage_nat: false,
+            fwmark: None,
+            route_table: None,
+            rule_priority: None,
+            mtu: None,
+            persistent_keepalive: None,
+            endpoint_host: Some(format!("relay{n}.example.com")),
+            endpoint_port: 51820,
+            tags: Vec::new(),
+            notes: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            last_seen_at: None,
+            offline: false,
+            daemon_version: None,
+            listen_port: None,
+            priority: 0,
+        }
+    }
+
+    fn client(n: u128, offset: i32) -> WgClient {
+        WgClient {
+            id: Uuid::from_u128(n),
+            network_id: Uuid::nil(),
+            name: format!("client-{n}"),
+            key_id: Uuid::from_u128(n),
+            address_offset: offset,
+            access_schedule: None,
+            access_allowed: true,
+            tags: Vec::new(),
+            notes: None,
+            mtu: None,
+            persistent_keepalive: None,
+            monthly_quota_bytes: None,
+            quota_exceeded: false,
+            upload_kbps: None,
+            download_kbps: None,
+            hooks: Hooks::default(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// A server in a /16 with `servers` other relays and `clients` clients.
+    fn inputs(servers: u128, clients: u128) -> ConfigInputs {
+        let other_servers: Vec<_> = (2..2 + servers).map(|n| server(n, n as i32)).collect();
+        let clients: Vec<_> = (1000..1000 + clients).map(|n| client(n, n as i32)).collect();
+        let keys = other_servers
+            .iter()
+            .map(|s| s.key_id)
+            .chain(clients.iter().map(|c| c.key_id))
+            .map(|id| (id, key(id.as_u128())))
+            .collect();
+        let routes = other_servers
+            .iter()
+            .map(|s| {
+                let route = WgServerRoute {
+                    id: Uuid::new_v4(),
+                    server_id: s.id,
+                    route_cidr: "192.168.0.0/24".parse::<IpNetwork>().unwrap(),
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                };
+                (s.id, vec![route])
+            })
+            .collect();
+        let psks = clients.iter().map(|c| (c.id, "psk".to_string())).collect();
+
+        ConfigInputs {
+            server: server(1, 1),
+            server_key: key(1),
+            network: Network {
+                id: Uuid::nil(),
+                name: "bench".into(),
+                cidr_ip: "10.0.0.0/16".parse().unwrap(),
+                owner_id: None,
+                dns_servers: Vec::new(),
+                persistent_keepalive: 25,
+                enabled: true,
+                config_serial: 1,
+                notes: None,
+                port_range_start: None,
+                port_range_end: None,
+                endpoint_template: None,
+                allocation: Default::default(),
+                mtu: None,
+                excluded_cidrs: Vec::new(),
+                lan_access: true,
+                hooks: Hooks::default(),
+                search_domains: Vec::new(),
+                topology: Default::default(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            },
+            other_servers,
+            clients,
+            keys,
+            routes,
+            psks,
+            acl_rules: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let config = inputs(1, 1).render(Capabilities::CURRENT).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.server.address, "10.0.0.1/16");
+        assert_eq!(config.peers.len(), 2);
+
+        let relay = &config.peers[0];
+        assert_eq!(relay.endpoint.as_deref(), Some("relay2.example.com:51820"));
+        assert_eq!(relay.allowed_ips, ["10.0.0.2/32", "192.168.0.0/24"]);
+        assert!(relay.preshared_key.is_none());
+
+        let client = &config.peers[1];
+        assert_eq!(client.allowed_ips, ["10.0.3.232/32"]);
+        assert_eq!(client.preshared_key.as_deref(), Some("psk"));
+    }
+
+    #[test]
+    fn test_render_hub_leaves_out_servers() {
+        let mut inputs = inputs(2, 1);
+        inputs.network.topology = Topology::Hub;
+        let config = inputs.render(Capabilities::CURRENT).unwrap();
+        assert_eq!(config.peers.len(), 1);
+        assert_eq!(config.peers[0].allowed_ips, ["10.0.3.232/32"]);
+    }
+
+    #[test]
+    fn test_render_keepalive_overrides() {
+        let mut inputs = inputs(1, 2);
+        inputs.server.persistent_keepalive = Some(60);
+        inputs.clients[0].persistent_keepalive = Some(0);
+        let config = inputs.render(Capabilities::CURRENT).unwrap();
+        let keepalives: Vec<_> = config.peers.iter().map(|p| p.persistent_keepalive).collect();
+        assert_eq!(keepalives, [Some(60), Some(0), Some(60)]);
+
+        let config = self::inputs(1, 1).render(Capabilities::CURRENT).unwrap();
+        assert!(config.peers.iter().all(|p| p.persistent_keepalive.is_none()));
+    }
+
+    #[test]
+    fn test_render_dns_zone() {
+        let mut inputs = inputs(1, 1);
+        inputs.network.dns_servers = vec!["1.1.1.1".into()];
+        let config = inputs.render(Capabilities::CURRENT).unwrap();
+        assert_eq!(config.network.dns_servers, ["1.1.1.1"]);
+
+        let zone = config.network.dns_zone.unwrap();
+        assert_eq!(zone.origin, "bench.wirewarden.internal");
+        let records: Vec<_> = zone
+            .records
+            .iter()
+            .map(|r| (r.name.as_str(), r.address.as_str()))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                ("server-1.bench.wirewarden.internal", "10.0.0.1"),
+                ("server-2.bench.wirewarden.internal", "10.0.0.2"),
+                ("client-1000.bench.wirewarden.internal", "10.0.3.232"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_ipv6_only() {
+        let mut inputs = inputs(1, 1);
+        inputs.network.cidr_ip = "fd00::/64".parse().unwrap();
+        let config = inputs.render(Capabilities::CURRENT).unwrap();
+        assert_eq!(config.server.address, "fd00::1/64");
+        assert_eq!(config.peers[0].allowed_ips, ["fd00::2/128"]);
+        assert_eq!(config.peers[1].allowed_ips, ["fd00::3e8/128"]);
+    }
+
+    #[test]
+    fn test_render_without_psk_capability() {
+        let caps = Capabilities::from_header(Some(""));
+        let config = inputs(1, 1).render(caps).unwrap();
+        assert!(config.peers.iter().all(|p| p.preshared_key.is_none()));
+    }
+
+    #[test]
+    fn test_render_for_legacy_daemon() {
+        let mut inputs = inputs(1, 1);
+        inputs.network.dns_servers = vec!["1.1.1.1".into()];
+        inputs.network.mtu = Some(1380);
+        inputs.server.persistent_keepalive = Some(60);
+        inputs.server.fwmark = Some(51820);
+        inputs.clients[0].upload_kbps = Some(1000);
+
+        let config = inputs.render(Capabilities::LEGACY).unwrap();
+        assert_eq!(config.version, 1);
+        assert!(config.server.fwmark.is_none());
+        assert!(config.server.mtu.is_none());
+        assert!(config.network.dns_servers.is_empty());
+        assert!(config.network.dns_zone.is_none());
+        assert!(config.peers.iter().all(|p| p.persistent_keepalive.is_none()));
+        assert!(config.peers.iter().all(|p| p.upload_kbps.is_none()));
+        assert_eq!(config.peers[1].preshared_key.as_deref(), Some("psk"));
+
+        let config = inputs.render(Capabilities::CURRENT).unwrap();
+        assert_eq!(config.server.fwmark, Some(51820));
+        assert_eq!(config.server.mtu, Some(1380));
+        assert_eq!(config.peers[1].upload_kbps, Some(1000));
+    }
+
+    #[test]
+    fn test_render_refuses_deny_rules_without_acl() {
+        let rule = |action| WgAclRule {
+            id: Uuid::nil(),
+            network_id: Uuid::nil(),
+            position: 1,
+            action,
+            source: "*".into(),
+            destination: "*".into(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let without_acl = Capabilities {
+            acl: false,
+            ..Capabilities::CURRENT
+        };
+
+        let mut inputs = inputs(1, 1);
+        let acl = |inputs: &ConfigInputs, caps| inputs.render(caps).map(|c| c.network.acl.len());
+        inputs.acl_rules = vec![rule(AclAction::Allow)];
+        assert_eq!(acl(&inputs, without_acl).unwrap(), 0);
+        assert_eq!(acl(&inputs, Capabilities::CURRENT).unwrap(), 1);
+
+        inputs.acl_rules.push(rule(AclAction::Deny));
+        let refused = acl(&inputs, without_acl);
+        assert!(matches!(refused, Err(ApiError::AclUnsupported)));
+        assert_eq!(acl(&inputs, Capabilities::CURRENT).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_is_current() {
+        let etag = EntityTag::new_strong("abc".into());
+        let other = EntityTag::new_strong("def".into());
+
+        assert!(!is_current(None, &etag));
+        assert!(is_current(Some(&IfNoneMatch::Any), &etag));
+        assert!(is_current(Some(&IfNoneMatch::Items(vec![other.clone(), etag.clone()])), &etag));
+        assert!(is_current(Some(&IfNoneMatch::Items(vec![EntityTag::new_weak("abc".into())])), &etag));
+        assert!(!is_current(Some(&IfNoneMatch::Items(vec![other])), &etag));
+    }
+
+    // `cargo bench -p wirewarden-api daemon`. The uncached path also pays
+    // for the snapshot queries, key decryption, and one PSK query per client,
+    // none of which is measured here.
+
+    #[bench]
+    fn bench_render_uncached(b: &mut test::Bencher) {
+        let inputs = inputs(5, 250);
+        b.iter(|| serde_json::to_vec(&inputs.render(Capabilities::CURRENT).unwrap()).unwrap());
+    }
+
+    #[bench]
+    fn bench_cache_hit(b: &mut test::Bencher) {
+        let cache = DaemonConfigCache::default();
+        let inputs = inputs(5, 250);
+        let caps = Capabilities::CURRENT;
+        let body = Bytes::from(serde_json::to_vec(&inputs.render(caps).unwrap()).unwrap());
+        cache.insert(inputs.server.id, 1, caps, RenderedConfig::new(body));
+        b.iter(|| cache.get(inputs.server.id, 1, caps).unwrap());
+    }
 }
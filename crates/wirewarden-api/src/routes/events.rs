@@ -0,0 +1,97 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::events::{Event, EventBus};
+use crate::extract::AuthUser;
+
+/// Comment frames keep idle connections open through proxies.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    network_id: Option<Uuid>,
+}
+
+fn frame(event: &Event) -> Bytes {
+    let data = serde_json::to_string(event).expect("event serializes");
+    Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {data}\n\n",
+        event.id,
+        event.kind.as_str()
+    ))
+}
+
+async fn stream_events(
+    _auth: AuthUser,
+    events: web::Data<EventBus>,
+    query: web::Query<EventsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let rx = events.subscribe_all();
+    let network_id = query.network_id;
+
+    let stream = futures::stream::unfold(rx, move |mut rx| async move {
+        loop {
+            let next = tokio::time::timeout(KEEPALIVE, rx.recv()).await;
+            let bytes = match next {
+                Err(_) => Bytes::from_static(b": keepalive\n\n"),
+                Ok(Ok(event)) if network_id.is_none_or(|id| id == event.network_id) => {
+                    frame(&event)
+                }
+                Ok(Ok(_)) => continue,
+                // Tell the client it missed events so it can refetch.
+                Ok(Err(RecvError::Lagged(n))) => {
+                    Bytes::from(format!("event: lagged\ndata: {n}\n\n"))
+                }
+                Ok(Err(RecvError::Closed)) => return None,
+            };
+            return Some((Ok::<_, actix_web::Error>(bytes), rx));
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/api/events").route(web::get().to(stream_events)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventKind;
+
+    #[test]
+    fn test_frame() {
+        let mut event = Event::new(EventKind::ClientCreated, Uuid::nil(), Uuid::nil());
+        event.id = Uuid::from_u128(7);
+        let text = String::from_utf8(frame(&event).to_vec()).unwrap();
+        assert!(text.starts_with(
+            "id: 00000000-0000-0000-0000-000000000007\nevent: client.created\ndata: {"
+        ));
+        assert!(text.ends_with("}\n\n"));
+    }
+}
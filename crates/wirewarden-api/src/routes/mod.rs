@@ -12,10 +12,24 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+pub mod access;
+pub mod acl;
+pub mod activity;
+pub mod approvals;
+pub mod audit;
 pub mod auth;
+pub mod client_routes;
 pub mod clients;
+pub mod connections;
 pub mod daemon;
+pub mod events;
 pub mod networks;
 pub mod passkey;
+pub mod reports;
+pub mod schedules;
+pub mod search;
 pub mod server_routes;
 pub mod servers;
+pub mod status_page;
+pub mod tools;
+pub mod webhooks;
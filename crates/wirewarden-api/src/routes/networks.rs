@@ -13,6 +13,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use std::net::IpAddr;
+use std::ops::RangeInclusive;
 
 use actix_web::{web, HttpResponse};
 use chrono::{DateTime, Utc};
@@ -20,23 +21,89 @@ use ipnetwork::{IpNetwork, Ipv4Network};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::db::vpn::VpnStore;
+use crate::changes::Change;
+use crate::config::Config;
+use crate::db::approval::ApprovalStore;
+use crate::db::audit::AuditStore;
+use crate::db::digest::{DigestStore, DigestSubscription};
+use crate::db::vpn::{self, Allocation, Topology, VpnStore};
+use crate::dns_zone::Zone;
+use crate::endpoint_template;
 use crate::error::ApiError;
+use crate::events::{EventBus, EventKind};
+use crate::excludes;
+use crate::hooks::{self, Hooks};
 use crate::extract::AuthUser;
+use crate::mtu;
+use crate::names;
+use crate::notes;
+use crate::pagination::ListQuery;
+use crate::routes::servers::validate_endpoint_host;
+use crate::search_domains;
 
 fn is_private_ipv4_network(net: Ipv4Network) -> bool {
     let ip = net.ip();
     ip.is_private() || ip.octets()[0] == 100 && ip.octets()[1] >= 64 && ip.octets()[1] <= 127
 }
 
-fn validate_dns_servers(servers: &[String]) -> Result<(), ApiError> {
+/// A private IPv4 range, or a unique local IPv6 one (`fc00::/7`) whose
+/// clients get only IPv6 addresses.
+fn validate_cidr(cidr: IpNetwork) -> Result<(), ApiError> {
+    match cidr {
+        IpNetwork::V4(v4) if !is_private_ipv4_network(v4) => Err(ApiError::Validation(
+            "CIDR must be in a private IP range".into(),
+        )),
+        IpNetwork::V6(v6) if !v6.ip().is_unique_local() => Err(ApiError::Validation(
+            "IPv6 CIDR must be in the unique local range fc00::/7".into(),
+        )),
+        IpNetwork::V6(v6) if !(64..=126).contains(&v6.prefix()) => Err(ApiError::Validation(
+            "IPv6 CIDR prefix must be between /64 and /126".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// DNS servers must be IP literals. Clients of an IPv6-only network can
+/// reach only IPv6 ones, typically a DNS64 resolver for IPv4-only names.
+fn validate_dns_servers(servers: &[String], cidr: IpNetwork) -> Result<(), ApiError> {
     for s in servers {
-        s.parse::<IpAddr>()
+        let ip = s
+            .parse::<IpAddr>()
             .map_err(|_| ApiError::Validation(format!("invalid DNS server IP: {s}")))?;
+        if cidr.is_ipv6() && ip.is_ipv4() {
+            return Err(ApiError::Validation(format!(
+                "DNS server {s} is IPv4 but the network is IPv6-only; use an IPv6 (DNS64) resolver"
+            )));
+        }
     }
     Ok(())
 }
 
+/// Excluded CIDRs as stored: in network form, without duplicates.
+fn parse_excluded_cidrs(cidrs: &[String]) -> Result<Vec<String>, ApiError> {
+    let nets = excludes::parse(cidrs).map_err(ApiError::Validation)?;
+    Ok(nets.iter().map(ToString::to_string).collect())
+}
+
+/// Parse a port range: `51820-51829`, or one port. Empty means no range.
+fn parse_port_range(s: &str) -> Result<Option<RangeInclusive<i32>>, ApiError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || ApiError::Validation(format!("invalid port range: {s}"));
+    let (start, end) = s.split_once('-').unwrap_or((s, s));
+    let port = |p: &str| match p.trim().parse::<i32>() {
+        Ok(port @ 1..=65535) => Ok(port),
+        _ => Err(invalid()),
+    };
+    let (start, end) = (port(start)?, port(end)?);
+    if start > end {
+        return Err(invalid());
+    }
+    Ok(Some(start..=end))
+}
+
 #[derive(Debug, Deserialize)]
 struct CreateNetworkRequest {
     name: String,
@@ -44,12 +111,40 @@ struct CreateNetworkRequest {
     dns_servers: Vec<String>,
     #[serde(default = "default_keepalive")]
     persistent_keepalive: i32,
+    notes: Option<String>,
+    /// Listen ports for the network's servers, e.g. `51820-51829`.
+    port_range: Option<String>,
+    /// Endpoint hostname for new servers, e.g. `{server}.vpn.example.com`.
+    endpoint_template: Option<String>,
+    #[serde(default)]
+    allocation: Allocation,
+    /// Interface MTU for the network's peers.
+    mtu: Option<u32>,
+    /// CIDRs full-tunnel clients keep off the VPN, besides private ranges.
+    #[serde(default)]
+    excluded_cidrs: Vec<String>,
+    /// Whether full-tunnel clients keep private ranges off the VPN.
+    #[serde(default = "default_lan_access")]
+    lan_access: bool,
+    /// wg-quick hooks for the network's client configs.
+    #[serde(flatten)]
+    hooks: Hooks,
+    /// DNS search domains for the network's clients.
+    #[serde(default)]
+    search_domains: Vec<String>,
+    /// Whether the network's servers peer with each other.
+    #[serde(default)]
+    topology: Topology,
 }
 
 fn default_keepalive() -> i32 {
     25
 }
 
+fn default_lan_access() -> bool {
+    true
+}
+
 #[derive(Debug, Serialize)]
 struct NetworkResponse {
     id: Uuid,
@@ -57,6 +152,19 @@ struct NetworkResponse {
     cidr: String,
     dns_servers: Vec<String>,
     persistent_keepalive: i32,
+    enabled: bool,
+    config_serial: i64,
+    notes: Option<String>,
+    port_range: Option<String>,
+    endpoint_template: Option<String>,
+    allocation: Allocation,
+    mtu: Option<u32>,
+    excluded_cidrs: Vec<String>,
+    lan_access: bool,
+    #[serde(flatten)]
+    hooks: Hooks,
+    search_domains: Vec<String>,
+    topology: Topology,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
@@ -64,12 +172,25 @@ struct NetworkResponse {
 impl NetworkResponse {
     fn from_model(n: crate::db::vpn::Network) -> Self {
         let cidr = n.cidr_ip.to_string();
+        let port_range = n.port_range().map(|r| format!("{}-{}", r.start(), r.end()));
         Self {
             id: n.id,
             name: n.name,
             cidr,
             dns_servers: n.dns_servers,
             persistent_keepalive: n.persistent_keepalive,
+            enabled: n.enabled,
+            config_serial: n.config_serial,
+            notes: n.notes,
+            port_range,
+            endpoint_template: n.endpoint_template,
+            allocation: n.allocation,
+            mtu: n.mtu.map(|mtu| mtu as u32),
+            excluded_cidrs: n.excluded_cidrs,
+            lan_access: n.lan_access,
+            hooks: n.hooks,
+            search_domains: n.search_domains,
+            topology: n.topology,
             created_at: n.created_at,
             updated_at: n.updated_at,
         }
@@ -79,15 +200,20 @@ impl NetworkResponse {
 async fn list_networks(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
+    query: ListQuery,
 ) -> Result<HttpResponse, ApiError> {
-    let networks = store.list_networks().await?;
+    let opts = query.options(&["name", "created_at"])?;
+    let (networks, total) = store.page_networks(&opts).await?;
     let resp: Vec<_> = networks.into_iter().map(NetworkResponse::from_model).collect();
-    Ok(HttpResponse::Ok().json(resp))
+    Ok(query.respond(resp, total))
 }
 
 async fn create_network(
-    _auth: AuthUser,
+    auth: AuthUser,
     store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    config: web::Data<Config>,
     body: web::Json<CreateNetworkRequest>,
 ) -> Result<HttpResponse, ApiError> {
     let cidr: IpNetwork = body
@@ -95,24 +221,107 @@ async fn create_network(
         .parse()
         .map_err(|_| ApiError::Validation("invalid CIDR".into()))?;
 
-    if cidr.is_ipv6() {
-        return Err(ApiError::Validation("IPv6 not supported".into()));
-    }
-
-    let v4 = match cidr {
-        IpNetwork::V4(v4) => v4,
-        _ => unreachable!(),
+    validate_cidr(cidr)?;
+    validate_dns_servers(&body.dns_servers, cidr)?;
+    let name = names::normalize(&body.name).map_err(ApiError::Validation)?;
+    let notes = match &body.notes {
+        Some(notes) => notes::normalize(notes).map_err(ApiError::Validation)?,
+        None => None,
+    };
+    let port_range = match &body.port_range {
+        Some(range) => parse_port_range(range)?,
+        None => None,
+    };
+    let template = match &body.endpoint_template {
+        Some(template) => endpoint_template::normalize(template).map_err(ApiError::Validation)?,
+        None => None,
     };
+    let mtu = match body.mtu {
+        Some(m) => mtu::normalize(m, cidr.is_ipv6()).map_err(ApiError::Validation)?,
+        None => None,
+    };
+    let excluded_cidrs = parse_excluded_cidrs(&body.excluded_cidrs)?;
+    check_network_hooks(&body.hooks, &config)?;
+    let hooks = Hooks::default()
+        .apply(&body.hooks)
+        .map_err(ApiError::Validation)?;
+    let search_domains =
+        search_domains::normalize(&body.search_domains).map_err(ApiError::Validation)?;
 
-    if !is_private_ipv4_network(v4) {
-        return Err(ApiError::Validation("CIDR must be in a private IP range".into()));
+    let mut network = store
+        .create_network(&name, cidr, None, &body.dns_servers, body.persistent_keepalive)
+        .await?;
+    if notes.is_some() {
+        network = store
+            .set_network_notes(network.id, notes.as_deref())
+            .await?
+            .ok_or(ApiError::NotFound)?;
     }
-
-    validate_dns_servers(&body.dns_servers)?;
-
-    let network = store
-        .create_network(&body.name, cidr, None, &body.dns_servers, body.persistent_keepalive)
+    if port_range.is_some() {
+        network = store
+            .set_network_port_range(network.id, port_range)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if template.is_some() {
+        network = store
+            .set_network_endpoint_template(network.id, template.as_deref())
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if body.allocation != Allocation::default() {
+        network = store
+            .set_network_allocation(network.id, body.allocation)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if mtu.is_some() {
+        network = store
+            .set_network_mtu(network.id, mtu)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if !excluded_cidrs.is_empty() {
+        network = store
+            .set_network_excluded_cidrs(network.id, &excluded_cidrs)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if !body.lan_access {
+        network = store
+            .set_network_lan_access(network.id, false)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if hooks != Hooks::default() {
+        network = store
+            .set_network_hooks(network.id, &hooks)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+        hooks::audit_change(
+            &audit,
+            auth.user_id,
+            "network.hooks_updated",
+            network.id,
+            network.id,
+            &Hooks::default(),
+            &network.hooks,
+        )
         .await?;
+    }
+    if !search_domains.is_empty() {
+        network = store
+            .set_network_search_domains(network.id, &search_domains)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if body.topology != Topology::default() {
+        network = store
+            .set_network_topology(network.id, body.topology)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    events.publish(EventKind::NetworkCreated, network.id, network.id);
 
     Ok(HttpResponse::Created().json(NetworkResponse::from_model(network)))
 }
@@ -132,30 +341,407 @@ struct UpdateNetworkRequest {
     dns_servers: Vec<String>,
     #[serde(default = "default_keepalive")]
     persistent_keepalive: i32,
+    enabled: Option<bool>,
+    /// Replaces the notes when present; an empty string clears them.
+    notes: Option<String>,
+    /// Replaces the port range when present; an empty string clears it.
+    /// Servers already outside a new range keep their ports.
+    port_range: Option<String>,
+    /// Replaces the endpoint template when present; an empty string clears
+    /// it.
+    endpoint_template: Option<String>,
+    /// Applies to addresses given out from now on.
+    allocation: Option<Allocation>,
+    /// Replaces the MTU when present; zero clears it.
+    mtu: Option<u32>,
+    /// Replaces the excluded CIDRs when present.
+    excluded_cidrs: Option<Vec<String>>,
+    lan_access: Option<bool>,
+    /// Hooks present replace the current ones; empty ones clear them.
+    #[serde(flatten)]
+    hooks: Hooks,
+    /// Replaces the search domains when present.
+    search_domains: Option<Vec<String>>,
+    topology: Option<Topology>,
+}
+
+/// Refuse network-wide hook commands unless the operator allowed them; they
+/// run as root on every client of the network. Clearing is always allowed.
+fn check_network_hooks(requested: &Hooks, config: &Config) -> Result<(), ApiError> {
+    if requested.sets_any() && !config.allow_network_hooks {
+        return Err(ApiError::Validation(
+            "network hooks are disabled; set ALLOW_NETWORK_HOOKS to allow them".into(),
+        ));
+    }
+    Ok(())
 }
 
 async fn update_network(
-    _auth: AuthUser,
+    auth: AuthUser,
     store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    config: web::Data<Config>,
     path: web::Path<Uuid>,
     body: web::Json<UpdateNetworkRequest>,
 ) -> Result<HttpResponse, ApiError> {
-    validate_dns_servers(&body.dns_servers)?;
     let id = path.into_inner();
-    let network = store
+    let current = store.get_network(id).await?.ok_or(ApiError::NotFound)?;
+    validate_dns_servers(&body.dns_servers, current.cidr_ip)?;
+    let notes = match &body.notes {
+        Some(notes) => Some(notes::normalize(notes).map_err(ApiError::Validation)?),
+        None => None,
+    };
+    let port_range = match &body.port_range {
+        Some(range) => Some(parse_port_range(range)?),
+        None => None,
+    };
+    let template = match &body.endpoint_template {
+        Some(template) => {
+            Some(endpoint_template::normalize(template).map_err(ApiError::Validation)?)
+        }
+        None => None,
+    };
+    // Render the new template for every server before writing anything, so a
+    // template that yields an invalid host for some server changes nothing.
+    let retemplated = match &template {
+        Some(template) if *template != current.endpoint_template => {
+            retemplated_endpoints(&store, &current, template.as_deref()).await?
+        }
+        _ => Vec::new(),
+    };
+    let mtu = match body.mtu {
+        Some(m) => Some(
+            mtu::normalize(m, current.cidr_ip.is_ipv6()).map_err(ApiError::Validation)?,
+        ),
+        None => None,
+    };
+    let excluded_cidrs = match &body.excluded_cidrs {
+        Some(cidrs) => Some(parse_excluded_cidrs(cidrs)?),
+        None => None,
+    };
+    check_network_hooks(&body.hooks, &config)?;
+    let hooks = current.hooks.apply(&body.hooks).map_err(ApiError::Validation)?;
+    let search_domains = match &body.search_domains {
+        Some(domains) => {
+            Some(search_domains::normalize(domains).map_err(ApiError::Validation)?)
+        }
+        None => None,
+    };
+    let mut network = store
         .update_network_settings(id, &body.dns_servers, body.persistent_keepalive)
         .await?
         .ok_or(ApiError::NotFound)?;
+    if let Some(enabled) = body.enabled
+        && enabled != network.enabled
+    {
+        network = store
+            .set_network_enabled(id, enabled)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if let Some(notes) = notes {
+        network = store
+            .set_network_notes(id, notes.as_deref())
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if let Some(port_range) = port_range
+        && port_range != network.port_range()
+    {
+        network = store
+            .set_network_port_range(id, port_range)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if let Some(template) = template
+        && template != network.endpoint_template
+    {
+        network = store
+            .set_network_endpoint_template(id, template.as_deref())
+            .await?
+            .ok_or(ApiError::NotFound)?;
+        for (server_id, host) in retemplated {
+            if store.set_server_endpoint(server_id, &host).await?.is_some() {
+                tracing::info!(%server_id, endpoint_host = host, "server endpoint retemplated");
+                events.publish(EventKind::ServerUpdated, id, server_id);
+            }
+        }
+    }
+    if let Some(allocation) = body.allocation
+        && allocation != network.allocation
+    {
+        network = store
+            .set_network_allocation(id, allocation)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if let Some(mtu) = mtu
+        && mtu != network.mtu
+    {
+        network = store
+            .set_network_mtu(id, mtu)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if let Some(excluded_cidrs) = excluded_cidrs
+        && excluded_cidrs != network.excluded_cidrs
+    {
+        network = store
+            .set_network_excluded_cidrs(id, &excluded_cidrs)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if let Some(lan_access) = body.lan_access
+        && lan_access != network.lan_access
+    {
+        network = store
+            .set_network_lan_access(id, lan_access)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if hooks != network.hooks {
+        let before = network.hooks;
+        network = store
+            .set_network_hooks(id, &hooks)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+        hooks::audit_change(
+            &audit,
+            auth.user_id,
+            "network.hooks_updated",
+            id,
+            id,
+            &before,
+            &network.hooks,
+        )
+        .await?;
+    }
+    if let Some(search_domains) = search_domains
+        && search_domains != network.search_domains
+    {
+        network = store
+            .set_network_search_domains(id, &search_domains)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    if let Some(topology) = body.topology
+        && topology != network.topology
+    {
+        network = store
+            .set_network_topology(id, topology)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    events.publish(EventKind::NetworkUpdated, network.id, network.id);
     Ok(HttpResponse::Ok().json(NetworkResponse::from_model(network)))
 }
 
+/// The new endpoint of each server whose endpoint is still the network's
+/// current template rendering. Endpoints set any other way are left alone, as
+/// is everything when the template is cleared.
+async fn retemplated_endpoints(
+    store: &VpnStore,
+    network: &vpn::Network,
+    new: Option<&str>,
+) -> Result<Vec<(Uuid, String)>, ApiError> {
+    let (Some(old), Some(new)) = (network.endpoint_template.as_deref(), new) else {
+        return Ok(Vec::new());
+    };
+    let mut endpoints = Vec::new();
+    for server in store.list_servers_by_network(network.id).await? {
+        let rendered = endpoint_template::render(old, &network.name, &server.name);
+        if server.endpoint_host.as_deref() != Some(rendered.as_str()) {
+            continue;
+        }
+        let host = endpoint_template::render(new, &network.name, &server.name);
+        validate_endpoint_host(&host)?;
+        endpoints.push((server.id, host));
+    }
+    Ok(endpoints)
+}
+
 async fn delete_network(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    approvals: web::Data<ApprovalStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let change = Change::DeleteNetwork { network_id: id };
+    super::approvals::submit_change(
+        &auth, change, &config, &store, &approvals, &audit, &events,
+    )
+    .await
+}
+
+async fn rotate_network_psks(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    approvals: web::Data<ApprovalStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let change = Change::RotateNetworkPsks { network_id: id };
+    super::approvals::submit_change(
+        &auth, change, &config, &store, &approvals, &audit, &events,
+    )
+    .await
+}
+
+#[derive(Debug, Serialize)]
+struct AllocatedAddress {
+    id: Uuid,
+    name: String,
+    address_offset: i32,
+    address: String,
+}
+
+#[derive(Debug, Serialize)]
+struct UtilizationResponse {
+    network_id: Uuid,
+    cidr: String,
+    total: i64,
+    used: i64,
+    free: i64,
+    servers: Vec<AllocatedAddress>,
+    clients: Vec<AllocatedAddress>,
+}
+
+async fn network_utilization(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    store.delete_network(id).await?;
+    let network = store.get_network(id).await?.ok_or(ApiError::NotFound)?;
+    let (servers, clients) = futures::future::try_join(
+        store.list_servers_by_network(id),
+        store.list_clients_by_network(id),
+    )
+    .await?;
+
+    let allocated = |id: Uuid, name: String, offset: i32| AllocatedAddress {
+        id,
+        name,
+        address_offset: offset,
+        address: vpn::compute_address(&network, offset).to_string(),
+    };
+    let servers: Vec<_> = servers
+        .into_iter()
+        .map(|s| allocated(s.id, s.name, s.address_offset))
+        .collect();
+    let clients: Vec<_> = clients
+        .into_iter()
+        .map(|c| allocated(c.id, c.name, c.address_offset))
+        .collect();
+
+    let total = network.usable_addresses();
+    let used = (servers.len() + clients.len()) as i64;
+
+    Ok(HttpResponse::Ok().json(UtilizationResponse {
+        network_id: network.id,
+        cidr: network.cidr_ip.to_string(),
+        total,
+        used,
+        free: (total - used).max(0),
+        servers,
+        clients,
+    }))
+}
+
+/// `?format=` on the DNS endpoint.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ZoneFormat {
+    #[default]
+    Json,
+    /// An RFC 1035 zone file.
+    Zone,
+}
+
+#[derive(Debug, Deserialize)]
+struct ZoneQuery {
+    #[serde(default)]
+    format: ZoneFormat,
+}
+
+async fn network_dns(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    path: web::Path<Uuid>,
+    query: web::Query<ZoneQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let network = store.get_network(id).await?.ok_or(ApiError::NotFound)?;
+    let (servers, clients) = futures::future::try_join(
+        store.list_servers_by_network(id),
+        store.list_clients_by_network(id),
+    )
+    .await?;
+    let zone = Zone::build(&network, &servers, &clients).map_err(ApiError::Validation)?;
+
+    match query.format {
+        ZoneFormat::Json => Ok(HttpResponse::Ok().json(zone)),
+        ZoneFormat::Zone => Ok(HttpResponse::Ok()
+            .content_type("text/dns; charset=utf-8")
+            .body(zone.to_zone_file())),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DigestResponse {
+    network_id: Uuid,
+    subscribed: bool,
+    subscribed_at: Option<DateTime<Utc>>,
+    next_digest_at: Option<DateTime<Utc>>,
+}
+
+impl DigestResponse {
+    fn new(network_id: Uuid, sub: Option<DigestSubscription>) -> Self {
+        Self {
+            network_id,
+            subscribed: sub.is_some(),
+            subscribed_at: sub.as_ref().map(|s| s.created_at),
+            next_digest_at: sub.map(|s| s.next_digest_at),
+        }
+    }
+}
+
+async fn get_digest(
+    auth: AuthUser,
+    digests: web::Data<DigestStore>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let sub = digests.get(auth.user_id, id).await?;
+    Ok(HttpResponse::Ok().json(DigestResponse::new(id, sub)))
+}
+
+async fn subscribe_digest(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    digests: web::Data<DigestStore>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    store.get_network(id).await?.ok_or(ApiError::NotFound)?;
+    let sub = digests.subscribe(auth.user_id, id).await?;
+    Ok(HttpResponse::Ok().json(DigestResponse::new(sub.network_id, Some(sub))))
+}
+
+async fn unsubscribe_digest(
+    auth: AuthUser,
+    digests: web::Data<DigestStore>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    digests.unsubscribe(auth.user_id, path.into_inner()).await?;
     Ok(HttpResponse::NoContent().finish())
 }
 
@@ -167,7 +753,71 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route("/{id}", web::get().to(get_network))
             .route("/{id}", web::patch().to(update_network))
             .route("/{id}", web::delete().to(delete_network))
+            .route("/{id}/psk/rotate", web::post().to(rotate_network_psks))
+            .route("/{id}/utilization", web::get().to(network_utilization))
+            .route("/{id}/dns", web::get().to(network_dns))
+            .route("/{id}/digest", web::get().to(get_digest))
+            .route("/{id}/digest", web::put().to(subscribe_digest))
+            .route("/{id}/digest", web::delete().to(unsubscribe_digest))
             .route("/{id}/servers", web::get().to(super::servers::list_servers))
-            .route("/{id}/clients", web::get().to(super::clients::list_clients)),
+            .route("/{id}/clients", web::get().to(super::clients::list_clients))
+            .route("/{id}/acl", web::get().to(super::acl::list_acl_rules))
+            .route("/{id}/acl", web::post().to(super::acl::add_acl_rule))
+            .route(
+                "/{id}/access-schedules",
+                web::get().to(super::access::list_tag_schedules),
+            )
+            .route(
+                "/{id}/access-schedules/{tag}",
+                web::put().to(super::access::set_tag_schedule),
+            )
+            .route(
+                "/{id}/access-schedules/{tag}",
+                web::delete().to(super::access::clear_tag_schedule),
+            ),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("51820-51829", Some(51820..=51829) ; "range")]
+    #[test_case(" 51820 - 51829 ", Some(51820..=51829) ; "spaces")]
+    #[test_case("51820", Some(51820..=51820) ; "single port")]
+    #[test_case("", None ; "empty clears")]
+    fn test_parse_port_range(input: &str, expected: Option<RangeInclusive<i32>>) {
+        assert_eq!(parse_port_range(input).unwrap(), expected);
+    }
+
+    #[test_case("51829-51820" ; "reversed")]
+    #[test_case("0-10" ; "port zero")]
+    #[test_case("65535-65536" ; "past max")]
+    #[test_case("wg" ; "not a number")]
+    #[test_case("1-2-3" ; "too many parts")]
+    fn test_parse_port_range_rejects(input: &str) {
+        assert!(parse_port_range(input).is_err());
+    }
+
+    #[test_case("10.0.0.0/24", true ; "private ipv4")]
+    #[test_case("8.8.8.0/24", false ; "public ipv4")]
+    #[test_case("fd00:1::/64", true ; "unique local ipv6")]
+    #[test_case("fd00:1::/120", true ; "small ipv6")]
+    #[test_case("2001:db8::/64", false ; "global ipv6")]
+    #[test_case("fd00::/48", false ; "ipv6 too wide")]
+    #[test_case("fd00::/127", false ; "ipv6 too narrow")]
+    fn test_validate_cidr(cidr: &str, valid: bool) {
+        assert_eq!(validate_cidr(cidr.parse().unwrap()).is_ok(), valid);
+    }
+
+    #[test_case("fd00::/64", &["2001:4860:4860::6464"], true ; "ipv6 resolver")]
+    #[test_case("fd00::/64", &["8.8.8.8"], false ; "ipv4 resolver on ipv6 network")]
+    #[test_case("10.0.0.0/24", &["8.8.8.8", "2001:4860:4860::8888"], true ; "either on ipv4")]
+    #[test_case("10.0.0.0/24", &["dns.example"], false ; "not an ip")]
+    fn test_validate_dns_servers(cidr: &str, servers: &[&str], valid: bool) {
+        let servers: Vec<String> = servers.iter().map(|s| s.to_string()).collect();
+        let result = validate_dns_servers(&servers, cidr.parse().unwrap());
+        assert_eq!(result.is_ok(), valid);
+    }
+}
@@ -270,7 +270,7 @@ async fn login_finish(
         .await?
         .ok_or(ApiError::UserNotFound)?;
 
-    let token = crate::auth::create_token(user.id, &config.jwt_secret)?;
+    let token = crate::auth::create_token(&user, &config.jwt_secret)?;
     tracing::info!(user_id = %user.id, "passkey login success");
 
     Ok(HttpResponse::Ok()
@@ -0,0 +1,289 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{HttpResponse, web};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::report::{
+    Bucket, GrowthPoint, NetworkPeers, ReportStore, RotationCompliance, Summary, UserDevices,
+};
+use crate::error::ApiError;
+use crate::extract::AuthUser;
+
+/// Most points a growth report returns, so a wide range cannot make the
+/// database generate millions of buckets.
+const MAX_GROWTH_POINTS: i64 = 400;
+
+/// How recently a client must have handshaken to count as seen in the
+/// summary.
+const RECENTLY_SEEN: Duration = Duration::hours(24);
+
+/// Share of `capacity` that `used` takes, 0 when there is none.
+fn utilization(used: i64, capacity: i64) -> f64 {
+    if capacity > 0 {
+        used as f64 / capacity as f64
+    } else {
+        0.0
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SummaryResponse {
+    networks: i64,
+    servers: i64,
+    servers_online: i64,
+    servers_offline: i64,
+    clients: i64,
+    clients_recently_seen: i64,
+    addresses_used: i64,
+    address_capacity: i64,
+    utilization: f64,
+}
+
+impl From<Summary> for SummaryResponse {
+    fn from(s: Summary) -> Self {
+        let used = s.servers + s.clients;
+        Self {
+            networks: s.networks,
+            servers: s.servers,
+            servers_online: s.servers_online,
+            servers_offline: s.servers - s.servers_online,
+            clients: s.clients,
+            clients_recently_seen: s.clients_seen,
+            addresses_used: used,
+            address_capacity: s.capacity,
+            utilization: utilization(used, s.capacity),
+        }
+    }
+}
+
+/// Everything the dashboard's home page shows, in one call.
+async fn summary(
+    _auth: AuthUser,
+    reports: web::Data<ReportStore>,
+) -> Result<HttpResponse, ApiError> {
+    let summary = reports.summary(Utc::now() - RECENTLY_SEEN).await?;
+    Ok(HttpResponse::Ok().json(SummaryResponse::from(summary)))
+}
+
+#[derive(Debug, Serialize)]
+struct NetworkPeersResponse {
+    network_id: Uuid,
+    name: String,
+    enabled: bool,
+    servers: i64,
+    clients: i64,
+    capacity: i64,
+    /// Share of the address space in use, 0 to 1.
+    utilization: f64,
+}
+
+impl From<NetworkPeers> for NetworkPeersResponse {
+    fn from(n: NetworkPeers) -> Self {
+        Self {
+            utilization: utilization(n.servers + n.clients, n.capacity),
+            network_id: n.network_id,
+            name: n.name,
+            enabled: n.enabled,
+            servers: n.servers,
+            clients: n.clients,
+            capacity: n.capacity,
+        }
+    }
+}
+
+async fn peers_per_network(
+    _auth: AuthUser,
+    reports: web::Data<ReportStore>,
+) -> Result<HttpResponse, ApiError> {
+    let rows = reports.peers_per_network().await?;
+    let resp: Vec<_> = rows.into_iter().map(NetworkPeersResponse::from).collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[derive(Debug, Deserialize)]
+struct GrowthQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    #[serde(default = "default_bucket")]
+    bucket: Bucket,
+    network_id: Option<Uuid>,
+}
+
+fn default_bucket() -> Bucket {
+    Bucket::Day
+}
+
+/// The shortest a bucket can be, for bounding the number of points.
+fn min_bucket_len(bucket: Bucket) -> Duration {
+    match bucket {
+        Bucket::Day => Duration::days(1),
+        Bucket::Week => Duration::weeks(1),
+        Bucket::Month => Duration::days(28),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GrowthResponse {
+    bucket: Bucket,
+    points: Vec<GrowthPointResponse>,
+}
+
+#[derive(Debug, Serialize)]
+struct GrowthPointResponse {
+    period_start: DateTime<Utc>,
+    servers_added: i64,
+    clients_added: i64,
+    servers_total: i64,
+    clients_total: i64,
+}
+
+impl From<GrowthPoint> for GrowthPointResponse {
+    fn from(p: GrowthPoint) -> Self {
+        Self {
+            period_start: p.period_start,
+            servers_added: p.servers_added,
+            clients_added: p.clients_added,
+            servers_total: p.servers_total,
+            clients_total: p.clients_total,
+        }
+    }
+}
+
+/// Devices added per day, week or month. Without `from`, the last 30
+/// buckets are shown.
+async fn growth(
+    _auth: AuthUser,
+    reports: web::Data<ReportStore>,
+    query: web::Query<GrowthQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let bucket_len = min_bucket_len(query.bucket);
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - bucket_len * 30);
+    if from > to {
+        return Err(ApiError::Validation("from must not be after to".into()));
+    }
+    if (to - from).num_seconds() / bucket_len.num_seconds() >= MAX_GROWTH_POINTS {
+        return Err(ApiError::Validation(format!(
+            "range too long; use a coarser bucket or at most {MAX_GROWTH_POINTS} buckets"
+        )));
+    }
+
+    let points = reports
+        .growth(from, to, query.bucket, query.network_id)
+        .await?;
+    Ok(HttpResponse::Ok().json(GrowthResponse {
+        bucket: query.bucket,
+        points: points.into_iter().map(GrowthPointResponse::from).collect(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct RotationQuery {
+    #[serde(default = "default_max_age_days")]
+    max_age_days: i32,
+    network_id: Option<Uuid>,
+}
+
+fn default_max_age_days() -> i32 {
+    90
+}
+
+#[derive(Debug, Serialize)]
+struct RotationResponse {
+    network_id: Uuid,
+    name: String,
+    pairs: i64,
+    compliant: i64,
+    stale: i64,
+    missing: i64,
+    oldest_rotation: Option<DateTime<Utc>>,
+}
+
+impl From<RotationCompliance> for RotationResponse {
+    fn from(r: RotationCompliance) -> Self {
+        Self {
+            network_id: r.network_id,
+            name: r.name,
+            pairs: r.pairs,
+            compliant: r.compliant,
+            stale: r.stale,
+            missing: r.missing,
+            oldest_rotation: r.oldest_rotation,
+        }
+    }
+}
+
+/// Preshared keys per network, split by whether they were rotated within
+/// `max_age_days`.
+async fn rotation_compliance(
+    _auth: AuthUser,
+    reports: web::Data<ReportStore>,
+    query: web::Query<RotationQuery>,
+) -> Result<HttpResponse, ApiError> {
+    if query.max_age_days < 1 {
+        return Err(ApiError::Validation(
+            "max_age_days must be at least 1".into(),
+        ));
+    }
+    let rows = reports
+        .rotation_compliance(query.max_age_days, query.network_id)
+        .await?;
+    let resp: Vec<_> = rows.into_iter().map(RotationResponse::from).collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[derive(Debug, Serialize)]
+struct UserDevicesResponse {
+    user_id: Option<Uuid>,
+    username: Option<String>,
+    networks: i64,
+    servers: i64,
+    clients: i64,
+}
+
+impl From<UserDevices> for UserDevicesResponse {
+    fn from(u: UserDevices) -> Self {
+        Self {
+            user_id: u.user_id,
+            username: u.username,
+            networks: u.networks,
+            servers: u.servers,
+            clients: u.clients,
+        }
+    }
+}
+
+/// Devices in the networks each user owns; unowned networks have no user.
+async fn devices_per_user(
+    _auth: AuthUser,
+    reports: web::Data<ReportStore>,
+) -> Result<HttpResponse, ApiError> {
+    let rows = reports.devices_per_user().await?;
+    let resp: Vec<_> = rows.into_iter().map(UserDevicesResponse::from).collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/reports")
+            .route("/peers-per-network", web::get().to(peers_per_network))
+            .route("/growth", web::get().to(growth))
+            .route("/rotation", web::get().to(rotation_compliance))
+            .route("/devices-per-user", web::get().to(devices_per_user)),
+    );
+    cfg.service(web::resource("/api/summary").route(web::get().to(summary)));
+}
@@ -0,0 +1,185 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::changes::Change;
+use crate::config::Config;
+use crate::db::approval::{ApprovalStore, ChangeStatus};
+use crate::db::audit::AuditStore;
+use crate::db::schedule::{ScheduleStatus, ScheduleStore, ScheduledChange};
+use crate::db::vpn::VpnStore;
+use crate::error::ApiError;
+use crate::extract::AuthUser;
+
+#[derive(Debug, Deserialize)]
+struct CreateScheduleRequest {
+    change: Change,
+    run_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    status: Option<ScheduleStatus>,
+}
+
+#[derive(Debug, Serialize)]
+struct ScheduledChangeResponse {
+    id: Uuid,
+    change: Change,
+    run_at: DateTime<Utc>,
+    created_by: Option<Uuid>,
+    status: ScheduleStatus,
+    error: Option<String>,
+    executed_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    /// The pending change that must be approved before this one runs.
+    approval_id: Option<Uuid>,
+}
+
+impl From<ScheduledChange> for ScheduledChangeResponse {
+    fn from(s: ScheduledChange) -> Self {
+        Self {
+            id: s.id,
+            change: s.change.0,
+            run_at: s.run_at,
+            created_by: s.created_by,
+            status: s.status,
+            error: s.error,
+            executed_at: s.executed_at,
+            created_at: s.created_at,
+            approval_id: s.approval_id,
+        }
+    }
+}
+
+/// Queue a change for `run_at`. Under the two-person rule a destructive
+/// change is also queued for approval and runs only once it is approved,
+/// at `run_at` or as soon as it is approved after that.
+async fn create_schedule(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    schedules: web::Data<ScheduleStore>,
+    approvals: web::Data<ApprovalStore>,
+    audit: web::Data<AuditStore>,
+    config: web::Data<Config>,
+    body: web::Json<CreateScheduleRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let CreateScheduleRequest { change, run_at } = body.into_inner();
+
+    if run_at <= Utc::now() {
+        return Err(ApiError::Validation("run_at must be in the future".into()));
+    }
+
+    change.check_target(&store).await?;
+
+    let approval_id = if config.require_approval && change.is_destructive() {
+        Some(approvals.create(&change, auth.user_id).await?.id)
+    } else {
+        None
+    };
+    let scheduled = schedules
+        .create(&change, run_at, auth.user_id, approval_id)
+        .await?;
+    audit
+        .record(
+            Some(auth.user_id),
+            "schedule.create",
+            Some(change.network_id()),
+            Some(change.target_id()),
+            serde_json::json!({
+                "scheduled_change_id": scheduled.id,
+                "run_at": run_at,
+                "change": change,
+                "pending_change_id": approval_id,
+                "impact": change.impact(),
+            }),
+        )
+        .await?;
+
+    Ok(HttpResponse::Created().json(ScheduledChangeResponse::from(scheduled)))
+}
+
+async fn list_schedules(
+    _auth: AuthUser,
+    schedules: web::Data<ScheduleStore>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let scheduled = schedules.list(query.status).await?;
+    let resp: Vec<_> = scheduled
+        .into_iter()
+        .map(ScheduledChangeResponse::from)
+        .collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+async fn get_schedule(
+    _auth: AuthUser,
+    schedules: web::Data<ScheduleStore>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let scheduled = schedules
+        .get(path.into_inner())
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(HttpResponse::Ok().json(ScheduledChangeResponse::from(scheduled)))
+}
+
+async fn cancel_schedule(
+    auth: AuthUser,
+    schedules: web::Data<ScheduleStore>,
+    approvals: web::Data<ApprovalStore>,
+    audit: web::Data<AuditStore>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let Some(cancelled) = schedules.cancel(id).await? else {
+        return Err(match schedules.get(id).await? {
+            Some(_) => ApiError::ChangeAlreadyDecided,
+            None => ApiError::NotFound,
+        });
+    };
+    // Nothing is left for the approval to release.
+    if let Some(approval_id) = cancelled.approval_id {
+        approvals
+            .decide(approval_id, auth.user_id, ChangeStatus::Rejected)
+            .await?;
+    }
+
+    let change = &cancelled.change.0;
+    audit
+        .record(
+            Some(auth.user_id),
+            "schedule.cancel",
+            Some(change.network_id()),
+            Some(change.target_id()),
+            serde_json::json!({ "scheduled_change_id": id, "change": change }),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(ScheduledChangeResponse::from(cancelled)))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/scheduled-changes")
+            .route("", web::get().to(list_schedules))
+            .route("", web::post().to(create_schedule))
+            .route("/{id}", web::get().to(get_schedule))
+            .route("/{id}", web::delete().to(cancel_schedule)),
+    );
+}
@@ -0,0 +1,79 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::net::IpAddr;
+
+use actix_web::{web, HttpResponse};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::vpn::{SearchHit, SearchKind, VpnStore};
+use crate::error::ApiError;
+use crate::extract::AuthUser;
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    q: String,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    20
+}
+
+#[derive(Debug, Serialize)]
+struct SearchResult {
+    kind: SearchKind,
+    id: Uuid,
+    network_id: Uuid,
+    name: String,
+    address: Option<String>,
+    public_key: Option<String>,
+}
+
+impl From<SearchHit> for SearchResult {
+    fn from(h: SearchHit) -> Self {
+        Self {
+            kind: h.kind,
+            id: h.id,
+            network_id: h.network_id,
+            name: h.name,
+            address: h.address,
+            public_key: h.public_key,
+        }
+    }
+}
+
+async fn search(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let q = query.q.trim();
+    if q.is_empty() {
+        return Err(ApiError::Validation("q must not be empty".into()));
+    }
+    let ip = q.parse::<IpAddr>().ok().map(IpNetwork::from);
+    let limit = query.limit.clamp(1, 100);
+
+    let hits = store.search(q, ip, limit).await?;
+    let resp: Vec<_> = hits.into_iter().map(SearchResult::from).collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/api/search").route(web::get().to(search)));
+}
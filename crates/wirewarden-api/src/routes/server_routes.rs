@@ -20,7 +20,9 @@ use uuid::Uuid;
 
 use crate::db::vpn::VpnStore;
 use crate::error::ApiError;
+use crate::events::{EventBus, EventKind};
 use crate::extract::AuthUser;
+use crate::pagination::ListQuery;
 
 #[derive(Debug, Deserialize)]
 struct CreateRouteRequest {
@@ -40,9 +42,11 @@ async fn list_routes(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
     path: web::Path<Uuid>,
+    query: ListQuery,
 ) -> Result<HttpResponse, ApiError> {
     let server_id = path.into_inner();
-    let routes = store.list_routes_by_server(server_id).await?;
+    let opts = query.options(&["route_cidr", "created_at"])?;
+    let (routes, total) = store.page_routes(server_id, &opts).await?;
     let resp: Vec<_> = routes
         .into_iter()
         .map(|r| RouteResponse {
@@ -53,12 +57,13 @@ async fn list_routes(
             updated_at: r.updated_at,
         })
         .collect();
-    Ok(HttpResponse::Ok().json(resp))
+    Ok(query.respond(resp, total))
 }
 
 async fn add_route(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
     path: web::Path<Uuid>,
     body: web::Json<CreateRouteRequest>,
 ) -> Result<HttpResponse, ApiError> {
@@ -68,7 +73,9 @@ async fn add_route(
         .parse()
         .map_err(|_| ApiError::Validation("invalid CIDR".into()))?;
 
-    let route = store.add_route(server_id, cidr).await?;
+    let server = store.get_server(server_id).await?.ok_or(ApiError::NotFound)?;
+    let route = store.add_route(server.id, cidr).await?;
+    events.publish(EventKind::ServerUpdated, server.network_id, server.id);
     let resp = RouteResponse {
         id: route.id,
         server_id: route.server_id,
@@ -82,10 +89,15 @@ async fn add_route(
 async fn delete_route(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-    store.delete_route(id).await?;
+    if let Some(route) = store.delete_route(id).await?
+        && let Some(server) = store.get_server(route.server_id).await?
+    {
+        events.publish(EventKind::ServerUpdated, server.network_id, server.id);
+    }
     Ok(HttpResponse::NoContent().finish())
 }
 
@@ -12,72 +12,201 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use actix_web::{web, HttpResponse};
-use chrono::{DateTime, Utc};
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use wirewarden_types::api;
+use wirewarden_types::version::Version;
 
+use crate::changes::Change;
 use crate::config::Config;
+use crate::csv::{CsvWriter, Format, FormatQuery};
+use crate::db::approval::ApprovalStore;
+use crate::db::audit::{AuditStore, FieldChanges};
 use crate::db::vpn::{self, VpnStore};
+use crate::endpoint_template;
 use crate::error::ApiError;
-use crate::extract::AuthUser;
+use crate::events::{EventBus, EventKind};
+use crate::extract::{AuthServer, AuthUser};
+use crate::keepalive;
+use crate::mtu;
+use crate::names;
+use crate::notes;
+use crate::pagination::ListQuery;
+use crate::policy_routing::PolicyRouting;
+use crate::signing::ConfigSigner;
+use crate::tags;
 
 #[derive(Debug, Deserialize)]
 struct CreateServerRequest {
     network_id: Uuid,
     name: String,
     forwards_internet_traffic: bool,
+    /// Have the daemon add the masquerade rules; needs
+    /// `forwards_internet_traffic`.
+    #[serde(default)]
+    manage_nat: bool,
+    fwmark: Option<u32>,
+    route_table: Option<u32>,
+    /// Needs `route_table`.
+    rule_priority: Option<u32>,
+    /// Overrides the network's MTU.
+    mtu: Option<u32>,
+    /// Overrides the network's keepalive on the server's links, unless the
+    /// peer at the other end sets its own.
+    persistent_keepalive: Option<i32>,
+    endpoint_host: Option<String>,
+    /// Defaults to the next free port in the network's range, or 51820.
+    endpoint_port: Option<i32>,
+    /// Defaults to `endpoint_port`.
+    listen_port: Option<u16>,
+    /// Defaults to zero.
+    priority: Option<i32>,
+    #[serde(default)]
+    tags: Vec<String>,
+    notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateServerRequest {
+    /// Replaces the notes when present; an empty string clears them.
+    notes: Option<String>,
+    manage_nat: Option<bool>,
+    /// Policy routing fields replace the current value when present; zero
+    /// clears them.
+    fwmark: Option<u32>,
+    route_table: Option<u32>,
+    rule_priority: Option<u32>,
+    /// Replaces the MTU override when present; zero clears it.
+    mtu: Option<u32>,
+    /// Replaces the keepalive override when present; zero turns keepalive
+    /// off and a negative value clears it.
+    persistent_keepalive: Option<i32>,
+    /// Replaces the listen port when present; zero listens on
+    /// `endpoint_port` again.
+    listen_port: Option<u16>,
+    /// Replaces the priority when present.
+    priority: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetTagsRequest {
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateEndpointRequest {
+    /// Defaults to the address the request came from.
     endpoint_host: Option<String>,
-    endpoint_port: i32,
 }
 
 #[derive(Debug, Serialize)]
-struct ServerResponse {
-    id: Uuid,
-    network_id: Uuid,
-    name: String,
+struct UpdateEndpointResponse {
+    endpoint_host: String,
+    changed: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ServerListItem {
+    #[serde(flatten)]
+    server: api::Server,
+    network_name: String,
+}
+
+/// Only forwarded traffic is masqueraded.
+const NAT_WITHOUT_FORWARDING: &str =
+    "manage_nat requires a server that forwards internet traffic";
+
+/// Whether a reported daemon build is older than the minimum supported.
+/// Unparseable versions (development builds) are never flagged.
+fn daemon_outdated(reported: Option<&str>, min: Option<Version>) -> bool {
+    match (reported.and_then(|v| v.parse::<Version>().ok()), min) {
+        (Some(reported), Some(min)) => reported < min,
+        _ => false,
+    }
+}
+
+/// Response for a listed server; the token is always redacted.
+fn list_response(
+    s: vpn::WgServer,
     public_key: String,
-    api_token: String,
-    address_offset: i32,
     address: String,
-    forwards_internet_traffic: bool,
-    endpoint_host: Option<String>,
-    endpoint_port: i32,
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
-    connect_command: Option<String>,
+    min_daemon_version: Option<Version>,
+) -> api::Server {
+    let routing = s.policy_routing();
+    api::Server {
+        daemon_outdated: daemon_outdated(s.daemon_version.as_deref(), min_daemon_version),
+        id: s.id,
+        network_id: s.network_id,
+        name: s.name,
+        public_key,
+        api_token: redact_token(&s.api_token),
+        address_offset: s.address_offset,
+        address,
+        forwards_internet_traffic: s.forwards_internet_traffic,
+        manage_nat: s.manage_nat,
+        fwmark: routing.fwmark,
+        route_table: routing.route_table,
+        rule_priority: routing.rule_priority,
+        mtu: s.mtu.map(|mtu| mtu as u32),
+        persistent_keepalive: s.persistent_keepalive,
+        endpoint_host: s.endpoint_host,
+        endpoint_port: s.endpoint_port,
+        listen_port: s.listen_port.map(|port| port as u16),
+        priority: s.priority,
+        tags: s.tags,
+        notes: s.notes,
+        last_seen_at: s.last_seen_at,
+        offline: s.offline,
+        daemon_version: s.daemon_version,
+        created_at: s.created_at,
+        updated_at: s.updated_at,
+        connect_command: None,
+        config_signing_key: None,
+    }
 }
 
+/// The response for `server`. Given the config signer, it is what a daemon
+/// enrolls from: the full token, the signer's public key and the command
+/// tying them together.
 async fn build_response(
     store: &VpnStore,
     server: vpn::WgServer,
-    full_token: bool,
-    public_url: &str,
-) -> Result<ServerResponse, ApiError> {
+    enrollment: Option<&ConfigSigner>,
+    config: &Config,
+) -> Result<api::Server, ApiError> {
     let key = store.get_key(server.key_id).await?;
     let network = store
         .get_network(server.network_id)
         .await?
         .ok_or(ApiError::NotFound)?;
     let address = vpn::compute_address(&network, server.address_offset);
+    let routing = server.policy_routing();
 
-    let api_token = if full_token {
+    let api_token = if enrollment.is_some() {
         server.api_token.clone()
     } else {
         redact_token(&server.api_token)
     };
 
-    let connect_command = if full_token {
-        Some(format!(
-            "wirewarden connect --api-host {} --api-token {}",
-            public_url.trim_end_matches('/'),
-            server.api_token
-        ))
-    } else {
-        None
-    };
+    // Handed out with the token, for the daemon to verify configs against.
+    let config_signing_key = enrollment.map(ConfigSigner::public_key);
+    let connect_command = config_signing_key.as_ref().map(|key| {
+        format!(
+            "wirewarden connect --api-host {} --api-token {} --config-key {key} --server-id {}",
+            config.public_url.trim_end_matches('/'),
+            server.api_token,
+            server.id
+        )
+    });
 
-    Ok(ServerResponse {
+    Ok(api::Server {
+        daemon_outdated: daemon_outdated(
+            server.daemon_version.as_deref(),
+            config.min_daemon_version,
+        ),
         id: server.id,
         network_id: server.network_id,
         name: server.name,
@@ -86,64 +215,404 @@ async fn build_response(
         address_offset: server.address_offset,
         address: address.to_string(),
         forwards_internet_traffic: server.forwards_internet_traffic,
+        manage_nat: server.manage_nat,
+        fwmark: routing.fwmark,
+        route_table: routing.route_table,
+        rule_priority: routing.rule_priority,
+        mtu: server.mtu.map(|mtu| mtu as u32),
+        persistent_keepalive: server.persistent_keepalive,
         endpoint_host: server.endpoint_host,
         endpoint_port: server.endpoint_port,
+        listen_port: server.listen_port.map(|port| port as u16),
+        priority: server.priority,
+        tags: server.tags,
+        notes: server.notes,
+        last_seen_at: server.last_seen_at,
+        offline: server.offline,
+        daemon_version: server.daemon_version,
         created_at: server.created_at,
         updated_at: server.updated_at,
         connect_command,
+        config_signing_key,
     })
 }
 
 async fn create_server(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
+    events: web::Data<EventBus>,
+    signer: web::Data<ConfigSigner>,
     config: web::Data<Config>,
     body: web::Json<CreateServerRequest>,
 ) -> Result<HttpResponse, ApiError> {
+    let name = names::normalize(&body.name).map_err(ApiError::Validation)?;
+    let tags = tags::normalize(&body.tags).map_err(ApiError::Validation)?;
+    let notes = match &body.notes {
+        Some(notes) => notes::normalize(notes).map_err(ApiError::Validation)?,
+        None => None,
+    };
+    if body.manage_nat && !body.forwards_internet_traffic {
+        return Err(ApiError::Validation(NAT_WITHOUT_FORWARDING.into()));
+    }
+    let routing = PolicyRouting {
+        fwmark: body.fwmark,
+        route_table: body.route_table,
+        rule_priority: body.rule_priority,
+    }
+    .normalize()
+    .map_err(ApiError::Validation)?;
+    let network = store
+        .get_network(body.network_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let mtu = match body.mtu {
+        Some(m) => mtu::normalize(m, network.cidr_ip.is_ipv6()).map_err(ApiError::Validation)?,
+        None => None,
+    };
+    let keepalive = match body.persistent_keepalive {
+        Some(k) => keepalive::normalize(k).map_err(ApiError::Validation)?,
+        None => None,
+    };
+    // Without an endpoint of its own, a server takes the network's template.
+    let templated = match (&body.endpoint_host, &network.endpoint_template) {
+        (None, Some(template)) => Some(endpoint_template::render(template, &network.name, &name)),
+        _ => None,
+    };
+    let endpoint_host = body.endpoint_host.as_deref().or(templated.as_deref());
+    if let Some(host) = endpoint_host {
+        validate_endpoint_host(host)?;
+    }
+    let endpoint_port = match body.endpoint_port {
+        Some(port) => {
+            if let Some(range) = network.port_range()
+                && !range.contains(&port)
+            {
+                return Err(ApiError::Validation(format!(
+                    "port {port} is outside the network's range {}-{}",
+                    range.start(),
+                    range.end()
+                )));
+            }
+            port
+        }
+        None => store.next_port(body.network_id, endpoint_host).await?,
+    };
     let key = store.create_key().await?;
 
-    let server = store
+    let mut server = store
         .create_server(
             body.network_id,
-            &body.name,
+            &name,
             key.id,
             body.forwards_internet_traffic,
-            body.endpoint_host.as_deref(),
-            body.endpoint_port,
+            endpoint_host,
+            endpoint_port,
         )
         .await?;
+    if !tags.is_empty() {
+        server = store
+            .set_server_tags(server.id, &tags)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
+    let settings = vpn::ServerUpdate {
+        notes: notes.is_some().then_some(notes),
+        manage_nat: body.manage_nat.then_some(true),
+        policy_routing: (routing != PolicyRouting::default()).then_some(routing),
+        mtu: mtu.is_some().then_some(mtu),
+        persistent_keepalive: keepalive.is_some().then_some(keepalive),
+        listen_port: body
+            .listen_port
+            .filter(|&port| port != 0)
+            .map(|port| Some(port.into())),
+        priority: body.priority.filter(|&priority| priority != 0),
+    };
+    if settings != vpn::ServerUpdate::default() {
+        server = store
+            .update_server(server.id, &settings)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    }
 
     let clients = store.list_clients_by_network(server.network_id).await?;
     for client in &clients {
         store.ensure_psk(server.id, client.id).await?;
     }
+    events.publish(EventKind::ServerCreated, server.network_id, server.id);
 
-    let resp = build_response(&store, server, true, &config.public_url).await?;
+    let resp = build_response(&store, server, Some(&signer), &config).await?;
     Ok(HttpResponse::Created().json(resp))
 }
 
 async fn get_server(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
+    signer: web::Data<ConfigSigner>,
     config: web::Data<Config>,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
     let server = store.get_server(id).await?.ok_or(ApiError::NotFound)?;
-    let resp = build_response(&store, server, true, &config.public_url).await?;
+    let resp = build_response(&store, server, Some(&signer), &config).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+async fn update_server(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateServerRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let before = store.get_server(id).await?.ok_or(ApiError::NotFound)?;
+
+    // Everything is checked before anything is written.
+    let notes = match &body.notes {
+        Some(notes) => Some(notes::normalize(notes).map_err(ApiError::Validation)?),
+        None => None,
+    };
+    if body.manage_nat == Some(true) && !before.forwards_internet_traffic {
+        return Err(ApiError::Validation(NAT_WITHOUT_FORWARDING.into()));
+    }
+    let update = PolicyRouting {
+        fwmark: body.fwmark,
+        route_table: body.route_table,
+        rule_priority: body.rule_priority,
+    };
+    let policy_routing = if update != PolicyRouting::default() {
+        let routing = before
+            .policy_routing()
+            .merge(update)
+            .normalize()
+            .map_err(ApiError::Validation)?;
+        Some(routing)
+    } else {
+        None
+    };
+    let mtu = match body.mtu {
+        Some(m) => {
+            let network = store
+                .get_network(before.network_id)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+            Some(mtu::normalize(m, network.cidr_ip.is_ipv6()).map_err(ApiError::Validation)?)
+        }
+        None => None,
+    };
+    let persistent_keepalive = match body.persistent_keepalive {
+        Some(k) => Some(keepalive::normalize(k).map_err(ApiError::Validation)?),
+        None => None,
+    };
+    let settings = vpn::ServerUpdate {
+        notes,
+        manage_nat: body.manage_nat,
+        policy_routing,
+        mtu,
+        persistent_keepalive,
+        listen_port: body
+            .listen_port
+            .map(|port| (port != 0).then_some(port.into())),
+        priority: body.priority,
+    };
+
+    let server = store
+        .update_server(id, &settings)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let mut changes = FieldChanges::default();
+    changes
+        .field("notes", &before.notes, &server.notes)
+        .field("manage_nat", &before.manage_nat, &server.manage_nat)
+        .field("fwmark", &before.fwmark, &server.fwmark)
+        .field("route_table", &before.route_table, &server.route_table)
+        .field(
+            "rule_priority",
+            &before.rule_priority,
+            &server.rule_priority,
+        )
+        .field("mtu", &before.mtu, &server.mtu)
+        .field(
+            "persistent_keepalive",
+            &before.persistent_keepalive,
+            &server.persistent_keepalive,
+        )
+        .field("listen_port", &before.listen_port, &server.listen_port)
+        .field("priority", &before.priority, &server.priority);
+    if !changes.is_empty() {
+        audit
+            .record(
+                Some(auth.user_id),
+                "server.updated",
+                Some(server.network_id),
+                Some(server.id),
+                changes.into_details(),
+            )
+            .await?;
+    }
+    events.publish(EventKind::ServerUpdated, server.network_id, server.id);
+
+    let resp = build_response(&store, server, None, &config).await?;
     Ok(HttpResponse::Ok().json(resp))
 }
 
 async fn delete_server(
-    _auth: AuthUser,
+    auth: AuthUser,
     store: web::Data<VpnStore>,
+    approvals: web::Data<ApprovalStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    config: web::Data<Config>,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
     let server = store.get_server(id).await?.ok_or(ApiError::NotFound)?;
-    store.delete_server(id).await?;
-    store.delete_key(server.key_id).await?;
-    Ok(HttpResponse::NoContent().finish())
+    let change = Change::DeleteServer {
+        network_id: server.network_id,
+        server_id: server.id,
+    };
+    super::approvals::submit_change(
+        &auth, change, &config, &store, &approvals, &audit, &events,
+    )
+    .await
+}
+
+async fn set_tags(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+    body: web::Json<SetTagsRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let tags = tags::normalize(&body.tags).map_err(ApiError::Validation)?;
+    let server = store
+        .set_server_tags(path.into_inner(), &tags)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    audit
+        .record(
+            Some(auth.user_id),
+            "server.tags.set",
+            Some(server.network_id),
+            Some(server.id),
+            serde_json::json!({ "tags": tags }),
+        )
+        .await?;
+    events.publish(EventKind::ServerUpdated, server.network_id, server.id);
+
+    let resp = build_response(&store, server, None, &config).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Issue a new API token. The old one stops working immediately, so the
+/// daemon must be reconnected with the returned `connect_command`.
+async fn rotate_token(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    signer: web::Data<ConfigSigner>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let server = store
+        .rotate_server_token(path.into_inner())
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    audit
+        .record(
+            Some(auth.user_id),
+            "server.token.rotate",
+            Some(server.network_id),
+            Some(server.id),
+            serde_json::json!({}),
+        )
+        .await?;
+    events.publish(EventKind::ServerUpdated, server.network_id, server.id);
+    events.publish(EventKind::ServerTokenRotated, server.network_id, server.id);
+    tracing::info!(server_id = %server.id, "server token rotated");
+
+    let resp = build_response(&store, server, Some(&signer), &config).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Called by dynamic-DNS scripts, routers, and the daemon with the server's
+/// own API token when its public address changes.
+async fn update_endpoint(
+    AuthServer(server): AuthServer,
+    req: HttpRequest,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+    events: web::Data<EventBus>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateEndpointRequest>,
+) -> Result<HttpResponse, ApiError> {
+    // A token only grants access to its own server.
+    if path.into_inner() != server.id {
+        return Err(ApiError::Unauthorized);
+    }
+    let host = match &body.endpoint_host {
+        Some(host) => host.trim().to_string(),
+        None => observed_host(&req)?,
+    };
+    let host = host.as_str();
+    validate_endpoint_host(host)?;
+
+    let changed = store.set_server_endpoint(server.id, host).await?.is_some();
+    if changed {
+        events.publish(EventKind::ServerUpdated, server.network_id, server.id);
+        audit
+            .record(
+                None,
+                "server.endpoint_updated",
+                Some(server.network_id),
+                Some(server.id),
+                serde_json::json!({ "from": server.endpoint_host, "to": host }),
+            )
+            .await?;
+        tracing::info!(server_id = %server.id, endpoint_host = host, "server endpoint updated");
+    }
+
+    Ok(HttpResponse::Ok().json(UpdateEndpointResponse {
+        endpoint_host: host.to_string(),
+        changed,
+    }))
+}
+
+/// The caller's address as an endpoint host. Proxy headers are honoured
+/// since the caller has already authenticated as the server.
+fn observed_host(req: &HttpRequest) -> Result<String, ApiError> {
+    let conn = req.connection_info();
+    let addr = conn
+        .realip_remote_addr()
+        .ok_or_else(|| ApiError::Validation("could not determine source address".into()))?;
+    let ip = addr
+        .parse::<IpAddr>()
+        .or_else(|_| addr.parse::<SocketAddr>().map(|a| a.ip()))
+        .map_err(|_| ApiError::Validation(format!("unusable source address: {addr}")))?;
+    Ok(match ip {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("[{v6}]"),
+    })
+}
+
+/// A hostname or IP literal; IPv6 literals must be bracketed.
+pub(crate) fn validate_endpoint_host(host: &str) -> Result<(), ApiError> {
+    let valid = !host.is_empty()
+        && host.len() <= 253
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+        && (!host.contains(':') || (host.starts_with('[') && host.ends_with(']')));
+    if valid {
+        Ok(())
+    } else {
+        Err(ApiError::Validation(format!("invalid endpoint host: {host}")))
+    }
 }
 
 fn redact_token(token: &str) -> String {
@@ -157,11 +626,14 @@ fn redact_token(token: &str) -> String {
 pub async fn list_servers(
     _auth: AuthUser,
     store: web::Data<VpnStore>,
+    config: web::Data<Config>,
     path: web::Path<Uuid>,
+    query: ListQuery,
 ) -> Result<HttpResponse, ApiError> {
     let network_id = path.into_inner();
     let network = store.get_network(network_id).await?.ok_or(ApiError::NotFound)?;
-    let servers = store.list_servers_by_network(network_id).await?;
+    let opts = query.options(&["name", "created_at", "address_offset"])?;
+    let (servers, total) = store.page_servers(network_id, &opts).await?;
 
     let key_ids: Vec<_> = servers.iter().map(|s| s.key_id).collect();
     let keys = store.get_keys_batch(&key_ids).await?;
@@ -169,37 +641,136 @@ pub async fn list_servers(
     let resp: Vec<_> = servers
         .into_iter()
         .map(|s| {
-            let key = &keys[&s.key_id];
+            let public_key = keys[&s.key_id].public_key.clone();
             let address = vpn::compute_address(&network, s.address_offset);
-            ServerResponse {
-                id: s.id,
-                network_id: s.network_id,
-                name: s.name,
-                public_key: key.public_key.clone(),
-                api_token: redact_token(&s.api_token),
-                address_offset: s.address_offset,
-                address: address.to_string(),
-                forwards_internet_traffic: s.forwards_internet_traffic,
-                endpoint_host: s.endpoint_host,
-                endpoint_port: s.endpoint_port,
-                created_at: s.created_at,
-                updated_at: s.updated_at,
-                connect_command: None,
-            }
+            list_response(
+                s,
+                public_key,
+                address.to_string(),
+                config.min_daemon_version,
+            )
         })
         .collect();
-    Ok(HttpResponse::Ok().json(resp))
+    Ok(query.respond(resp, total))
+}
+
+/// Servers across all networks. `?format=csv` exports every match as CSV,
+/// ignoring paging.
+async fn list_all_servers(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    config: web::Data<Config>,
+    query: ListQuery,
+    format: web::Query<FormatQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let mut opts = query.options(&["name", "network_name", "created_at", "last_seen_at"])?;
+    if format.format == Format::Csv {
+        opts.limit = i64::MAX;
+        opts.offset = 0;
+    }
+    let (servers, total) = store.page_all_servers(&opts).await?;
+
+    if format.format == Format::Csv {
+        let mut csv = CsvWriter::default();
+        csv.row([
+            "id",
+            "network_id",
+            "network_name",
+            "name",
+            "address",
+            "public_key",
+            "endpoint",
+            "forwards_internet_traffic",
+            "tags",
+            "notes",
+            "last_seen_at",
+            "offline",
+            "daemon_version",
+            "created_at",
+        ]);
+        for l in servers {
+            let s = l.server;
+            let endpoint = s
+                .endpoint_host
+                .map(|host| format!("{host}:{}", s.endpoint_port))
+                .unwrap_or_default();
+            csv.row([
+                s.id.to_string(),
+                s.network_id.to_string(),
+                l.network_name,
+                s.name,
+                l.address,
+                l.public_key,
+                endpoint,
+                s.forwards_internet_traffic.to_string(),
+                s.tags.join(";"),
+                s.notes.unwrap_or_default(),
+                s.last_seen_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                s.offline.to_string(),
+                s.daemon_version.unwrap_or_default(),
+                s.created_at.to_rfc3339(),
+            ]);
+        }
+        return Ok(csv.respond("servers.csv"));
+    }
+
+    let resp: Vec<_> = servers
+        .into_iter()
+        .map(|l| ServerListItem {
+            server: list_response(
+                l.server,
+                l.public_key,
+                l.address,
+                config.min_daemon_version,
+            ),
+            network_name: l.network_name,
+        })
+        .collect();
+    Ok(query.respond(resp, total))
 }
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::resource("/api/servers")
+            .route(web::get().to(list_all_servers))
             .route(web::post().to(create_server)),
     )
     .service(
         web::resource("/api/servers/{id}")
             .route(web::get().to(get_server))
+            .route(web::patch().to(update_server))
             .route(web::delete().to(delete_server)),
     )
+    .route("/api/servers/{id}/tags", web::put().to(set_tags))
+    .route("/api/servers/{id}/token/rotate", web::post().to(rotate_token))
+    .route("/api/servers/{id}/endpoint", web::post().to(update_endpoint))
     ;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("vpn.example.com", true ; "hostname")]
+    #[test_case("203.0.113.7", true ; "ipv4")]
+    #[test_case("[2001:db8::1]", true ; "bracketed ipv6")]
+    #[test_case("2001:db8::1", false ; "bare ipv6")]
+    #[test_case("", false ; "empty")]
+    #[test_case("host name", false ; "whitespace")]
+    #[test_case("host\nEndpoint = evil", false ; "config injection")]
+    fn test_validate_endpoint_host(host: &str, valid: bool) {
+        assert_eq!(validate_endpoint_host(host).is_ok(), valid);
+    }
+
+    #[test_case(Some("0.3.0"), Some("0.4.0"), true ; "older")]
+    #[test_case(Some("v0.4.0"), Some("0.4.0"), false ; "equal")]
+    #[test_case(Some("0.5.1-3-gabc123"), Some("0.4.0"), false ; "newer with suffix")]
+    #[test_case(Some("abc1234"), Some("0.4.0"), false ; "unparseable")]
+    #[test_case(None, Some("0.4.0"), false ; "never reported")]
+    #[test_case(Some("0.1.0"), None, false ; "no minimum")]
+    fn test_daemon_outdated(reported: Option<&str>, min: Option<&str>, outdated: bool) {
+        let min = min.map(|m| m.parse().unwrap());
+        assert_eq!(daemon_outdated(reported, min), outdated);
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! A read-only status page for embedding outside the admin UI, e.g. an
+//! "is the VPN up" widget. It needs no session, only `STATUS_PAGE_TOKEN` as
+//! a bearer token, and shows names, online flags and counts; no addresses,
+//! keys or endpoints. The token is not taken from the query string, where
+//! proxies and access logs would record it.
+
+use actix_web::{HttpRequest, HttpResponse, web};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::db::vpn::VpnStore;
+use crate::error::ApiError;
+
+#[derive(Debug, Serialize)]
+struct StatusPage {
+    networks: Vec<NetworkStatus>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct NetworkStatus {
+    name: String,
+    servers: Vec<ServerStatus>,
+    servers_online: usize,
+    servers_total: usize,
+    clients: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct ServerStatus {
+    name: String,
+    online: bool,
+}
+
+async fn status_page(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    store: web::Data<VpnStore>,
+) -> Result<HttpResponse, ApiError> {
+    let expected = config
+        .status_page_token
+        .as_deref()
+        .ok_or(ApiError::NotFound)?;
+    let given = req
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(ApiError::Unauthorized)?;
+    if !token_matches(given, expected) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let networks = store.list_network_status().await?;
+    let servers = store.list_server_status().await?;
+    let networks = networks
+        .into_iter()
+        .map(|n| {
+            let servers: Vec<_> = servers
+                .iter()
+                .filter(|s| s.network_id == n.id)
+                .map(|s| ServerStatus {
+                    name: s.name.clone(),
+                    online: s.online,
+                })
+                .collect();
+            NetworkStatus {
+                name: n.name,
+                servers_online: servers.iter().filter(|s| s.online).count(),
+                servers_total: servers.len(),
+                servers,
+                clients: n.clients,
+            }
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(StatusPage {
+            networks,
+            updated_at: Utc::now(),
+        }))
+}
+
+/// Compare without an early exit, so response time does not reveal how much
+/// of a guess was right.
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/api/status-page").route(web::get().to(status_page)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("s3cret", "s3cret", true ; "equal")]
+    #[test_case("s3cres", "s3cret", false ; "last byte differs")]
+    #[test_case("s3cre", "s3cret", false ; "prefix")]
+    #[test_case("", "s3cret", false ; "empty")]
+    fn test_token_matches(given: &str, expected: &str, matches: bool) {
+        assert_eq!(token_matches(given, expected), matches);
+    }
+}
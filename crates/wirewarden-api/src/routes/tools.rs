@@ -0,0 +1,273 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::db::audit::AuditStore;
+use crate::db::log_settings::LogSettingsStore;
+use crate::db::{self, MigrationState};
+use crate::db::vpn::{self, SearchKind, VpnStore};
+use crate::error::ApiError;
+use crate::extract::AuthUser;
+use crate::logging::{self, LogControl};
+
+#[derive(Debug, Deserialize)]
+struct ValidateKeyRequest {
+    public_key: String,
+}
+
+#[derive(Debug, Serialize)]
+struct KeyOwner {
+    kind: SearchKind,
+    id: Uuid,
+    network_id: Uuid,
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ValidateKeyResponse {
+    valid: bool,
+    /// Why the key is invalid, when it is.
+    error: Option<&'static str>,
+    /// The server or client already using this key.
+    in_use_by: Option<KeyOwner>,
+}
+
+/// Check a pasted public key before it is submitted, so the UI can give
+/// immediate feedback. Invalid keys are a 200 with `valid: false`.
+async fn validate_key(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+    body: web::Json<ValidateKeyRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let key = body.public_key.trim();
+    if let Err(error) = vpn::validate_public_key(key) {
+        return Ok(HttpResponse::Ok().json(ValidateKeyResponse {
+            valid: false,
+            error: Some(error),
+            in_use_by: None,
+        }));
+    }
+
+    let owner = store.find_public_key_owner(key).await?;
+    Ok(HttpResponse::Ok().json(ValidateKeyResponse {
+        valid: true,
+        error: None,
+        in_use_by: owner.map(|o| KeyOwner {
+            kind: o.kind,
+            id: o.id,
+            network_id: o.network_id,
+            name: o.name,
+        }),
+    }))
+}
+
+/// Keys younger than this may belong to a create that is still running.
+const ORPHAN_KEY_GRACE_SECS: i64 = 3600;
+
+#[derive(Debug, Serialize)]
+struct OrphanResponse {
+    keys: Vec<Uuid>,
+}
+
+impl From<vpn::OrphanReport> for OrphanResponse {
+    fn from(r: vpn::OrphanReport) -> Self {
+        Self { keys: r.keys }
+    }
+}
+
+/// Report keys nothing refers to.
+async fn find_orphans(
+    _auth: AuthUser,
+    store: web::Data<VpnStore>,
+) -> Result<HttpResponse, ApiError> {
+    let report = store.find_orphans(ORPHAN_KEY_GRACE_SECS).await?;
+    Ok(HttpResponse::Ok().json(OrphanResponse::from(report)))
+}
+
+/// Delete orphaned keys, returning what was removed.
+async fn purge_orphans(
+    auth: AuthUser,
+    store: web::Data<VpnStore>,
+    audit: web::Data<AuditStore>,
+) -> Result<HttpResponse, ApiError> {
+    let resp = OrphanResponse::from(store.purge_orphans(ORPHAN_KEY_GRACE_SECS).await?);
+    if !resp.keys.is_empty() {
+        audit
+            .record(
+                Some(auth.user_id),
+                "tools.orphans.purge",
+                None,
+                None,
+                serde_json::to_value(&resp).map_err(|_| ApiError::Internal)?,
+            )
+            .await?;
+    }
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[derive(Debug, Serialize)]
+struct MigrationResponse {
+    version: i64,
+    description: String,
+    state: MigrationState,
+}
+
+#[derive(Debug, Serialize)]
+struct MigrationsResponse {
+    pending: usize,
+    migrations: Vec<MigrationResponse>,
+}
+
+/// Schema status: which of this build's migrations the database has, and
+/// any it has that this build does not know about.
+async fn migrations(
+    _auth: AuthUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let status = db::migration_status(&pool).await?;
+    let pending = status
+        .iter()
+        .filter(|m| m.state == MigrationState::Pending)
+        .count();
+    let migrations = status
+        .into_iter()
+        .map(|m| MigrationResponse {
+            version: m.version,
+            description: m.description,
+            state: m.state,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(MigrationsResponse {
+        pending,
+        migrations,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct LoggingResponse {
+    filter: String,
+    request_sample_rate: f64,
+    /// False when running on the `RUST_LOG` defaults.
+    overridden: bool,
+    updated_by: Option<Uuid>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetLoggingRequest {
+    filter: String,
+    request_sample_rate: f64,
+}
+
+/// The log filter and request log sampling in effect across replicas.
+async fn get_logging(
+    _auth: AuthUser,
+    store: web::Data<LogSettingsStore>,
+    control: web::Data<LogControl>,
+) -> Result<HttpResponse, ApiError> {
+    let resp = match store.get().await? {
+        Some(s) => LoggingResponse {
+            filter: s.filter,
+            request_sample_rate: s.request_sample_rate,
+            overridden: true,
+            updated_by: s.updated_by,
+            updated_at: Some(s.updated_at),
+        },
+        None => LoggingResponse {
+            filter: control.default_filter().to_owned(),
+            request_sample_rate: 1.0,
+            overridden: false,
+            updated_by: None,
+            updated_at: None,
+        },
+    };
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Change the log filter and request sampling. Applied here immediately and
+/// on other replicas at their next scheduler tick.
+async fn set_logging(
+    auth: AuthUser,
+    store: web::Data<LogSettingsStore>,
+    control: web::Data<LogControl>,
+    audit: web::Data<AuditStore>,
+    body: web::Json<SetLoggingRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let filter = body.filter.trim();
+    logging::validate(filter, body.request_sample_rate).map_err(ApiError::Validation)?;
+
+    let settings = store
+        .set(filter, body.request_sample_rate, auth.user_id)
+        .await?;
+    control.apply(Some(&settings));
+    audit
+        .record(
+            Some(auth.user_id),
+            "tools.logging.set",
+            None,
+            None,
+            serde_json::json!({
+                "filter": settings.filter,
+                "request_sample_rate": settings.request_sample_rate,
+            }),
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(LoggingResponse {
+        filter: settings.filter,
+        request_sample_rate: settings.request_sample_rate,
+        overridden: true,
+        updated_by: settings.updated_by,
+        updated_at: Some(settings.updated_at),
+    }))
+}
+
+/// Go back to the `RUST_LOG` defaults.
+async fn reset_logging(
+    auth: AuthUser,
+    store: web::Data<LogSettingsStore>,
+    control: web::Data<LogControl>,
+    audit: web::Data<AuditStore>,
+) -> Result<HttpResponse, ApiError> {
+    if store.clear().await? {
+        audit
+            .record(
+                Some(auth.user_id),
+                "tools.logging.reset",
+                None,
+                None,
+                serde_json::json!({}),
+            )
+            .await?;
+    }
+    control.apply(None);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/api/tools/validate-key").route(web::post().to(validate_key)))
+        .service(web::resource("/api/tools/orphans").route(web::get().to(find_orphans)))
+        .service(web::resource("/api/tools/orphans/purge").route(web::post().to(purge_orphans)))
+        .service(web::resource("/api/tools/migrations").route(web::get().to(migrations)))
+        .service(
+            web::resource("/api/tools/logging")
+                .route(web::get().to(get_logging))
+                .route(web::put().to(set_logging))
+                .route(web::delete().to(reset_logging)),
+        );
+}
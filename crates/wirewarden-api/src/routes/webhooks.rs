@@ -0,0 +1,259 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use actix_web::{web, HttpResponse};
+use aes_gcm::aead::{OsRng, rand_core::RngCore};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use uuid::Uuid;
+use wirewarden_types::redact::{Redacted, redact_opt};
+
+use crate::db::webhook::{DeliveryStatus, Webhook, WebhookDelivery, WebhookStore};
+use crate::error::ApiError;
+use crate::events::{Event, EventKind};
+use crate::extract::AuthUser;
+
+#[derive(Deserialize)]
+struct CreateWebhookRequest {
+    url: String,
+    /// Generated when omitted.
+    secret: Option<String>,
+    /// Event kinds to deliver; empty subscribes to everything.
+    #[serde(default)]
+    events: Vec<EventKind>,
+}
+
+impl fmt::Debug for CreateWebhookRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CreateWebhookRequest")
+            .field("url", &self.url)
+            .field("secret", &redact_opt(&self.secret))
+            .field("events", &self.events)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateWebhookRequest {
+    url: String,
+    #[serde(default)]
+    events: Vec<EventKind>,
+    enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookResponse {
+    id: Uuid,
+    url: String,
+    events: Vec<String>,
+    enabled: bool,
+    created_by: Option<Uuid>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<Webhook> for WebhookResponse {
+    fn from(w: Webhook) -> Self {
+        Self {
+            id: w.id,
+            url: w.url,
+            events: w.events,
+            enabled: w.enabled,
+            created_by: w.created_by,
+            created_at: w.created_at,
+            updated_at: w.updated_at,
+        }
+    }
+}
+
+/// Returned once on creation; the secret is not readable afterwards.
+#[derive(Serialize)]
+struct CreatedWebhookResponse {
+    #[serde(flatten)]
+    webhook: WebhookResponse,
+    secret: String,
+}
+
+impl fmt::Debug for CreatedWebhookResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CreatedWebhookResponse")
+            .field("webhook", &self.webhook)
+            .field("secret", &Redacted)
+            .finish()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DeliveryResponse {
+    id: Uuid,
+    event: Event,
+    status: DeliveryStatus,
+    attempts: i32,
+    next_attempt_at: Option<DateTime<Utc>>,
+    last_status_code: Option<i32>,
+    last_error: Option<String>,
+    delivered_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<WebhookDelivery> for DeliveryResponse {
+    fn from(d: WebhookDelivery) -> Self {
+        Self {
+            id: d.id,
+            event: d.event.0,
+            next_attempt_at: (d.status == DeliveryStatus::Pending).then_some(d.next_attempt_at),
+            status: d.status,
+            attempts: d.attempts,
+            last_status_code: d.last_status_code,
+            last_error: d.last_error,
+            delivered_at: d.delivered_at,
+            created_at: d.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DeliveriesQuery {
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+fn validate_url(raw: &str) -> Result<(), ApiError> {
+    let url = Url::parse(raw).map_err(|_| ApiError::Validation("invalid webhook URL".into()))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(ApiError::Validation("webhook URL must be http or https".into()));
+    }
+    Ok(())
+}
+
+fn event_names(events: &[EventKind]) -> Vec<String> {
+    events.iter().map(|e| e.as_str().to_string()).collect()
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+async fn list_event_kinds(_auth: AuthUser) -> HttpResponse {
+    HttpResponse::Ok().json(EventKind::ALL)
+}
+
+async fn list_webhooks(
+    _auth: AuthUser,
+    webhooks: web::Data<WebhookStore>,
+) -> Result<HttpResponse, ApiError> {
+    let hooks = webhooks.list().await?;
+    let resp: Vec<_> = hooks.into_iter().map(WebhookResponse::from).collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+async fn create_webhook(
+    auth: AuthUser,
+    webhooks: web::Data<WebhookStore>,
+    body: web::Json<CreateWebhookRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+    validate_url(&body.url)?;
+    let secret = match body.secret {
+        Some(s) if s.is_empty() => {
+            return Err(ApiError::Validation("webhook secret must not be empty".into()));
+        }
+        Some(s) => s,
+        None => generate_secret(),
+    };
+
+    let hook = webhooks
+        .create(&body.url, &secret, &event_names(&body.events), auth.user_id)
+        .await?;
+    Ok(HttpResponse::Created().json(CreatedWebhookResponse {
+        webhook: hook.into(),
+        secret,
+    }))
+}
+
+async fn get_webhook(
+    _auth: AuthUser,
+    webhooks: web::Data<WebhookStore>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let hook = webhooks
+        .get(path.into_inner())
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(HttpResponse::Ok().json(WebhookResponse::from(hook)))
+}
+
+async fn update_webhook(
+    _auth: AuthUser,
+    webhooks: web::Data<WebhookStore>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateWebhookRequest>,
+) -> Result<HttpResponse, ApiError> {
+    validate_url(&body.url)?;
+    let hook = webhooks
+        .update(path.into_inner(), &body.url, &event_names(&body.events), body.enabled)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(HttpResponse::Ok().json(WebhookResponse::from(hook)))
+}
+
+async fn delete_webhook(
+    _auth: AuthUser,
+    webhooks: web::Data<WebhookStore>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    if !webhooks.delete(path.into_inner()).await? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn list_deliveries(
+    _auth: AuthUser,
+    webhooks: web::Data<WebhookStore>,
+    path: web::Path<Uuid>,
+    query: web::Query<DeliveriesQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    webhooks.get(id).await?.ok_or(ApiError::NotFound)?;
+    let deliveries = webhooks
+        .list_deliveries(id, query.limit.clamp(1, 1000))
+        .await?;
+    let resp: Vec<_> = deliveries.into_iter().map(DeliveryResponse::from).collect();
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/webhooks")
+            .route("", web::get().to(list_webhooks))
+            .route("", web::post().to(create_webhook))
+            .route("/event-kinds", web::get().to(list_event_kinds))
+            .route("/{id}", web::get().to(get_webhook))
+            .route("/{id}", web::put().to(update_webhook))
+            .route("/{id}", web::delete().to(delete_webhook))
+            .route("/{id}/deliveries", web::get().to(list_deliveries)),
+    );
+}
@@ -0,0 +1,402 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Background housekeeping: applies scheduled changes and expires stale
+//! webauthn challenges.
+//!
+//! Every replica runs the scheduler. Work queued in rows (scheduled changes,
+//! digests) is claimed with `SKIP LOCKED`; the remaining jobs go through
+//! [`JobStore`] so only one replica runs each per interval.
+
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::AppState;
+use crate::access;
+use crate::db::approval::ApprovalStore;
+use crate::db::audit::AuditStore;
+use crate::db::digest::{DigestStore, DueDigest};
+use crate::db::job::JobStore;
+use crate::db::log_settings::LogSettingsStore;
+use crate::db::schedule::{ScheduleStore, ScheduledChange};
+use crate::db::usage::UsageStore;
+use crate::db::vpn::VpnStore;
+use crate::db::webauthn::ChallengeStore;
+use crate::digest::Digest;
+use crate::events::{EventBus, EventKind};
+use crate::logging::LogControl;
+use crate::mailer::Mailer;
+use crate::usage;
+
+const TICK: Duration = Duration::from_secs(30);
+const CHALLENGE_CLEANUP_EVERY: Duration = Duration::from_secs(60);
+const USAGE_ROLLUP_EVERY: Duration = Duration::from_secs(60 * 60);
+const QUOTA_SYNC_EVERY: Duration = Duration::from_secs(5 * 60);
+const CLAIM_BATCH: i64 = 32;
+/// How long a claimed change may run before another replica assumes its
+/// scheduler died and runs it again.
+const SCHEDULE_LEASE_SECS: i64 = 10 * 60;
+
+#[derive(Clone)]
+pub struct Scheduler {
+    pub vpn: VpnStore,
+    pub schedules: ScheduleStore,
+    pub approvals: ApprovalStore,
+    pub audit: AuditStore,
+    pub challenges: ChallengeStore,
+    pub digests: DigestStore,
+    pub jobs: JobStore,
+    pub usage: UsageStore,
+    pub log_settings: LogSettingsStore,
+    pub log_control: LogControl,
+    pub mailer: Mailer,
+    pub events: EventBus,
+    pub server_offline_secs: i64,
+    pub usage_retention_days: i32,
+    pub require_approval: bool,
+}
+
+impl Scheduler {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            vpn: state.vpn.get_ref().clone(),
+            schedules: state.schedules.get_ref().clone(),
+            approvals: state.approvals.get_ref().clone(),
+            audit: state.audit.get_ref().clone(),
+            challenges: state.challenges.get_ref().clone(),
+            digests: state.digests.get_ref().clone(),
+            jobs: JobStore::new(state.pool.get_ref().clone()),
+            usage: state.usage.get_ref().clone(),
+            log_settings: state.log_settings.get_ref().clone(),
+            log_control: state.log_control.get_ref().clone(),
+            mailer: state.mailer.get_ref().clone(),
+            events: state.events.get_ref().clone(),
+            server_offline_secs: state.config.server_offline_secs,
+            usage_retention_days: state.config.usage_retention_days,
+            require_approval: state.config.require_approval,
+        }
+    }
+
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TICK);
+            loop {
+                interval.tick().await;
+                self.sync_log_settings().await;
+                self.run_due().await;
+                self.exclusive("access_windows", TICK, self.sync_access_windows())
+                    .await;
+                self.exclusive("offline_servers", TICK, self.detect_offline_servers())
+                    .await;
+                self.send_digests().await;
+                self.exclusive(
+                    "challenge_cleanup",
+                    CHALLENGE_CLEANUP_EVERY,
+                    self.cleanup_challenges(),
+                )
+                .await;
+                self.exclusive("usage_rollup", USAGE_ROLLUP_EVERY, self.rollup_usage())
+                    .await;
+                self.exclusive("quota_sync", QUOTA_SYNC_EVERY, self.sync_quotas())
+                    .await;
+            }
+        });
+    }
+
+    /// Run `job` if this replica claims it for the current interval.
+    async fn exclusive<F>(&self, name: &'static str, every: Duration, job: F)
+    where
+        F: Future<Output = ()>,
+    {
+        let claim = match self.jobs.try_claim(name, every).await {
+            Ok(Some(claim)) => claim,
+            Ok(None) => {
+                tracing::debug!(job = name, "job not due on this replica");
+                return;
+            }
+            Err(e) => {
+                tracing::warn!(job = name, error = %e, "failed to claim job");
+                return;
+            }
+        };
+        job.await;
+        if let Err(e) = claim.finish().await {
+            tracing::warn!(job = name, error = %e, "failed to record job run");
+        }
+    }
+
+    /// Pick up log settings changed through another replica.
+    async fn sync_log_settings(&self) {
+        match self.log_settings.get().await {
+            Ok(settings) => self.log_control.apply(settings.as_ref()),
+            Err(e) => tracing::warn!(error = %e, "failed to load log settings"),
+        }
+    }
+
+    async fn cleanup_challenges(&self) {
+        if let Err(e) = self.challenges.cleanup().await {
+            tracing::warn!(error = %e, "webauthn challenge cleanup failed");
+        }
+    }
+
+    async fn rollup_usage(&self) {
+        if let Err(e) = self.usage.rollup(self.usage_retention_days).await {
+            tracing::warn!(error = %e, "client usage rollup failed");
+        }
+    }
+
+    /// Lift quota marks once a new month starts; reports only ever add.
+    async fn sync_quotas(&self) {
+        match self.usage.sync_quotas(None).await {
+            Ok(changes) => {
+                usage::announce_quota_changes(&changes, &self.audit, &self.events).await;
+            }
+            Err(e) => tracing::warn!(error = %e, "client quota sync failed"),
+        }
+    }
+
+    /// Apply every scheduled change that is due and, when approval is
+    /// required, approved.
+    pub async fn run_due(&self) {
+        let due = match self
+            .schedules
+            .claim_due(CLAIM_BATCH, SCHEDULE_LEASE_SECS, self.require_approval)
+            .await
+        {
+            Ok(due) => due,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to claim scheduled changes");
+                return;
+            }
+        };
+
+        for scheduled in due {
+            self.run_one(scheduled).await;
+        }
+    }
+
+    /// Open or close client access windows whose state has changed since the
+    /// last tick, under the client's own schedule or its tags'.
+    pub async fn sync_access_windows(&self) {
+        let loaded = futures::future::try_join(
+            self.vpn.list_scheduled_clients(),
+            self.vpn.list_tag_access_schedules(None),
+        )
+        .await;
+        let (clients, tag_schedules) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to load client access schedules");
+                return;
+            }
+        };
+
+        let now = Utc::now();
+        for client in clients {
+            let tagged = access::tagged(&tag_schedules, client.network_id, &client.tags);
+            let own = client.access_schedule.as_ref().map(|s| &s.0);
+            let allowed = access::is_allowed(own, &tagged, now);
+            if allowed == client.access_allowed {
+                continue;
+            }
+
+            if let Err(e) = self.vpn.set_client_access_allowed(client.id, allowed).await {
+                tracing::warn!(client_id = %client.id, error = %e, "failed to toggle client access");
+                continue;
+            }
+            let action = if allowed {
+                "client.access_opened"
+            } else {
+                "client.access_closed"
+            };
+            if let Err(e) = self
+                .audit
+                .record(
+                    None,
+                    action,
+                    Some(client.network_id),
+                    Some(client.id),
+                    serde_json::json!({}),
+                )
+                .await
+            {
+                tracing::error!(error = %e, "failed to audit client access toggle");
+            }
+            self.events
+                .publish(EventKind::ClientUpdated, client.network_id, client.id);
+            tracing::info!(client_id = %client.id, allowed, "client access window toggled");
+        }
+    }
+
+    async fn detect_offline_servers(&self) {
+        let servers = match self
+            .vpn
+            .mark_offline_servers(self.server_offline_secs)
+            .await
+        {
+            Ok(servers) => servers,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to check for offline servers");
+                return;
+            }
+        };
+        for server in servers {
+            tracing::warn!(server_id = %server.id, last_seen_at = ?server.last_seen_at, "server went offline");
+            self.events
+                .publish(EventKind::ServerOffline, server.network_id, server.id);
+        }
+    }
+
+    async fn send_digests(&self) {
+        let due = match self.digests.claim_due(CLAIM_BATCH).await {
+            Ok(due) => due,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to claim due digests");
+                return;
+            }
+        };
+        for digest in due {
+            self.send_digest(digest).await;
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %due.user_id, network_id = %due.network_id))]
+    async fn send_digest(&self, due: DueDigest) {
+        let digest =
+            match Digest::build(&self.vpn, &self.schedules, due.network_id, Utc::now()).await {
+                Ok(Some(digest)) => digest,
+                Ok(None) => return,
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to build digest");
+                    return;
+                }
+            };
+        if let Err(e) = self
+            .mailer
+            .send(&due.email, &digest.subject(due.locale), digest.render(due.locale))
+            .await
+        {
+            tracing::warn!(error = %e, "failed to send digest");
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(scheduled_change_id = %scheduled.id))]
+    async fn run_one(&self, scheduled: ScheduledChange) {
+        let change = &scheduled.change.0;
+        // Scheduled before the two-person rule was turned on: it waits for
+        // an approval like any change submitted now.
+        if self.require_approval && change.is_destructive() && scheduled.approval_id.is_none() {
+            self.request_approval(&scheduled).await;
+            return;
+        }
+
+        let result = change.apply(&self.vpn, &self.events).await;
+        let error = result.as_ref().err().map(|e| e.to_string());
+
+        if let Err(e) = self.schedules.finish(scheduled.id, error.as_deref()).await {
+            tracing::error!(error = %e, "failed to record scheduled change result");
+        }
+
+        let (action, details) = match &error {
+            None => (
+                change.action(),
+                serde_json::json!({ "scheduled_change_id": scheduled.id, "change": change }),
+            ),
+            Some(err) => (
+                "schedule.failed",
+                serde_json::json!({
+                    "scheduled_change_id": scheduled.id,
+                    "change": change,
+                    "error": err,
+                }),
+            ),
+        };
+        if let Err(e) = self
+            .audit
+            .record(
+                scheduled.created_by,
+                action,
+                Some(change.network_id()),
+                Some(change.target_id()),
+                details,
+            )
+            .await
+        {
+            tracing::error!(error = %e, "failed to audit scheduled change");
+        }
+
+        match error {
+            None => tracing::info!(action = change.action(), "scheduled change applied"),
+            Some(err) => {
+                tracing::warn!(action = change.action(), error = %err, "scheduled change failed")
+            }
+        }
+    }
+
+    /// Queue `scheduled` for approval on behalf of whoever scheduled it and
+    /// hold it until the approval is granted.
+    async fn request_approval(&self, scheduled: &ScheduledChange) {
+        let change = &scheduled.change.0;
+        let Some(created_by) = scheduled.created_by else {
+            // Nobody left to request it for; it cannot be reviewed.
+            let error = "scheduled change requires approval but its creator was deleted";
+            if let Err(e) = self.schedules.finish(scheduled.id, Some(error)).await {
+                tracing::error!(error = %e, "failed to record scheduled change result");
+            }
+            tracing::warn!(action = change.action(), "{error}");
+            return;
+        };
+
+        let pending = match self.approvals.create(change, created_by).await {
+            Ok(pending) => pending,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to queue scheduled change for approval");
+                return;
+            }
+        };
+        if let Err(e) = self
+            .schedules
+            .await_approval(scheduled.id, pending.id)
+            .await
+        {
+            tracing::error!(error = %e, "failed to hold scheduled change for approval");
+            return;
+        }
+        if let Err(e) = self
+            .audit
+            .record(
+                scheduled.created_by,
+                "change.requested",
+                Some(change.network_id()),
+                Some(change.target_id()),
+                serde_json::json!({
+                    "pending_change_id": pending.id,
+                    "scheduled_change_id": scheduled.id,
+                    "change": change,
+                    "impact": change.impact(),
+                }),
+            )
+            .await
+        {
+            tracing::error!(error = %e, "failed to audit scheduled change approval request");
+        }
+        tracing::info!(
+            change_id = %pending.id,
+            action = change.action(),
+            "scheduled change held for approval"
+        );
+    }
+}
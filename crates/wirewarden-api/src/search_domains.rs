@@ -0,0 +1,84 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! DNS search domains on networks, written after the DNS servers in client
+//! configs.
+
+const MAX_DOMAIN_LEN: usize = 253;
+const MAX_LABEL_LEN: usize = 63;
+
+/// Lowercase `domains` and drop trailing dots, blanks, and repeats. wg-quick
+/// tells servers and domains apart by whether they parse as addresses, so a
+/// domain must not look like one.
+pub fn normalize(domains: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for domain in domains {
+        let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+        if domain.is_empty() {
+            continue;
+        }
+        if !is_domain(&domain) {
+            return Err(format!("invalid search domain: {domain}"));
+        }
+        if !normalized.contains(&domain) {
+            normalized.push(domain);
+        }
+    }
+    Ok(normalized)
+}
+
+fn is_domain(domain: &str) -> bool {
+    let valid_labels = domain.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= MAX_LABEL_LEN
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    let numeric_tld = domain
+        .rsplit('.')
+        .next()
+        .is_some_and(|tld| tld.chars().all(|c| c.is_ascii_digit()));
+    domain.len() <= MAX_DOMAIN_LEN && valid_labels && !numeric_tld
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(&[" Corp.Example.com. "], &["corp.example.com"] ; "trimmed and lowercased")]
+    #[test_case(&["home.arpa", "", "HOME.ARPA"], &["home.arpa"] ; "skips blanks and repeats")]
+    #[test_case(&["lan", "vpn.internal"], &["lan", "vpn.internal"] ; "order kept")]
+    fn test_normalize(input: &[&str], expected: &[&str]) {
+        let input: Vec<String> = input.iter().map(|s| s.to_string()).collect();
+        assert_eq!(normalize(&input).unwrap(), expected);
+    }
+
+    #[test_case("10.0.0.1" ; "ipv4 address")]
+    #[test_case("fd00::1" ; "ipv6 address")]
+    #[test_case("corp..example.com" ; "empty label")]
+    #[test_case("-corp.example.com" ; "leading dash")]
+    #[test_case("corp example.com" ; "space")]
+    #[test_case("corp.example.com,lan" ; "comma")]
+    fn test_normalize_rejects(input: &str) {
+        assert!(normalize(&[input.to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_normalize_rejects_long_labels() {
+        let domain = format!("{}.example.com", "a".repeat(MAX_LABEL_LEN + 1));
+        assert!(normalize(&[domain]).is_err());
+    }
+}
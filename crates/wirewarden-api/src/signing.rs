@@ -0,0 +1,102 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Signatures over the configs served to daemons. A daemon given the
+//! public key at enrollment refuses configs it does not verify, so a proxy
+//! or broken TLS path between it and the API cannot slip in a peer. Each
+//! signature also covers the server and config serial, so neither can a
+//! config meant for another server or one older than the daemon applied.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signer, SigningKey};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use uuid::Uuid;
+use wirewarden_types::daemon::signed_message;
+
+/// HKDF info string naming what the derived key is for.
+const KEY_INFO: &[u8] = b"wirewarden daemon config signing key";
+
+/// Signs daemon configs. The key is derived from the key encryption
+/// secret, so it is stable across restarts and replicas without another
+/// secret to manage.
+#[derive(Debug, Clone)]
+pub struct ConfigSigner {
+    key: SigningKey,
+}
+
+impl ConfigSigner {
+    pub fn from_secret(secret: &[u8; 32]) -> Self {
+        let mut seed = [0u8; 32];
+        Hkdf::<Sha256>::new(None, secret)
+            .expand(KEY_INFO, &mut seed)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        Self {
+            key: SigningKey::from_bytes(&seed),
+        }
+    }
+
+    /// The public key, base64 encoded, as daemons are configured with it.
+    pub fn public_key(&self) -> String {
+        STANDARD.encode(self.key.verifying_key().as_bytes())
+    }
+
+    /// A base64 signature over `body` as served to `server_id` at `serial`;
+    /// see [`signed_message`].
+    pub fn sign(&self, server_id: Uuid, serial: i64, body: &[u8]) -> String {
+        let message = signed_message(server_id, serial, body);
+        STANDARD.encode(self.key.sign(&message).to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signature, VerifyingKey};
+
+    use super::*;
+
+    #[test]
+    fn test_signatures_verify_with_the_public_key() {
+        let signer = ConfigSigner::from_secret(&[7; 32]);
+        let key: [u8; 32] = STANDARD
+            .decode(signer.public_key())
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let key = VerifyingKey::from_bytes(&key).unwrap();
+        let server = Uuid::from_u128(1);
+        let sig: [u8; 64] = STANDARD
+            .decode(signer.sign(server, 3, b"body"))
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let sig = Signature::from_bytes(&sig);
+        let verifies = |server, serial, body| {
+            key.verify_strict(&signed_message(server, serial, body), &sig)
+                .is_ok()
+        };
+        assert!(verifies(server, 3, b"body"));
+        assert!(!verifies(server, 3, b"other"));
+        assert!(!verifies(server, 2, b"body"), "older serial");
+        assert!(!verifies(Uuid::from_u128(2), 3, b"body"), "other server");
+    }
+
+    #[test]
+    fn test_key_follows_the_secret() {
+        let key = |byte| ConfigSigner::from_secret(&[byte; 32]).public_key();
+        assert_eq!(key(1), key(1));
+        assert_ne!(key(1), key(2));
+    }
+}
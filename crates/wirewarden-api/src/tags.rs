@@ -0,0 +1,68 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Free-form labels on servers and clients ("kids", "work", "iot").
+
+const MAX_TAGS: usize = 32;
+const MAX_TAG_LEN: usize = 32;
+
+/// Lowercase, trim, and de-duplicate `tags`, rejecting anything that is not
+/// a short run of letters, digits, `-`, or `_`. Order is preserved.
+pub fn normalize(tags: &[String]) -> Result<Vec<String>, String> {
+    let mut out: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || tag.len() > MAX_TAG_LEN {
+            return Err(format!("tags must be 1 to {MAX_TAG_LEN} characters"));
+        }
+        if !tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "tag {tag:?} may only contain letters, digits, '-' and '_'"
+            ));
+        }
+        if !out.contains(&tag) {
+            out.push(tag);
+        }
+    }
+    if out.len() > MAX_TAGS {
+        return Err(format!("at most {MAX_TAGS} tags are allowed"));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(&["Kids", " work ", "kids"], &["kids", "work"] ; "normalizes and dedupes")]
+    #[test_case(&["iot_2", "home-lab"], &["iot_2", "home-lab"] ; "punctuation")]
+    #[test_case(&[], &[] ; "empty")]
+    fn test_normalize(input: &[&str], expected: &[&str]) {
+        let input: Vec<String> = input.iter().map(|s| s.to_string()).collect();
+        assert_eq!(normalize(&input).unwrap(), expected);
+    }
+
+    #[test_case(&[""] ; "blank")]
+    #[test_case(&["two words"] ; "whitespace")]
+    #[test_case(&["a,b"] ; "comma")]
+    #[test_case(&["abcdefghijklmnopqrstuvwxyz0123456789"] ; "too long")]
+    fn test_normalize_rejects(input: &[&str]) {
+        let input: Vec<String> = input.iter().map(|s| s.to_string()).collect();
+        assert!(normalize(&input).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+//! Client usage history: the `?range=` of a usage query and the buckets it
+//! is reported in, and monthly quotas.
+
+use chrono::TimeDelta;
+
+use crate::db::audit::AuditStore;
+use crate::db::usage::QuotaChange;
+use crate::events::{EventBus, EventKind};
+
+/// Hourly buckets are kept this long before being rolled up into days, so
+/// any range served hourly is still whole.
+pub const HOURLY_RETENTION_DAYS: i32 = 8;
+
+const MAX_RANGE_DAYS: i64 = 366;
+
+/// Ranges up to this long are reported hourly, longer ones daily.
+const HOURLY_UP_TO: TimeDelta = TimeDelta::days(7);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Hour,
+    Day,
+}
+
+impl Step {
+    /// The `date_trunc` field, and the name used in responses.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+}
+
+/// Check a monthly quota in bytes; zero clears it.
+pub fn normalize_quota(bytes: i64) -> Result<Option<i64>, String> {
+    match bytes {
+        0 => Ok(None),
+        b if b < 0 => Err("monthly_quota_bytes must not be negative".into()),
+        b => Ok(Some(b)),
+    }
+}
+
+/// Audit and announce clients that went over quota or came back under it.
+pub async fn announce_quota_changes(
+    changes: &[QuotaChange],
+    audit: &AuditStore,
+    events: &EventBus,
+) {
+    for change in changes {
+        let action = if change.quota_exceeded {
+            "client.quota_exceeded"
+        } else {
+            "client.quota_restored"
+        };
+        tracing::info!(
+            client_id = %change.client_id,
+            used_bytes = change.used_bytes,
+            quota_bytes = change.monthly_quota_bytes,
+            action,
+            "client quota state changed"
+        );
+        let details = serde_json::json!({
+            "used_bytes": change.used_bytes,
+            "monthly_quota_bytes": change.monthly_quota_bytes,
+        });
+        if let Err(e) = audit
+            .record(
+                None,
+                action,
+                Some(change.network_id),
+                Some(change.client_id),
+                details,
+            )
+            .await
+        {
+            tracing::error!(error = %e, "failed to audit client quota change");
+        }
+        events.publish(EventKind::ClientUpdated, change.network_id, change.client_id);
+    }
+}
+
+/// Parse a range such as `24h`, `7d` or `4w`.
+pub fn parse_range(range: &str) -> Result<TimeDelta, String> {
+    let invalid = || format!("invalid range {range:?}: expected e.g. 24h, 7d or 4w");
+    let split = range.len().checked_sub(1).ok_or_else(invalid)?;
+    let (count, unit) = range.split_at_checked(split).ok_or_else(invalid)?;
+    let count: i64 = count.parse().map_err(|_| invalid())?;
+    let span = match unit {
+        "h" => TimeDelta::try_hours(count),
+        "d" => TimeDelta::try_days(count),
+        "w" => TimeDelta::try_weeks(count),
+        _ => None,
+    };
+    let span = span.filter(|s| *s > TimeDelta::zero()).ok_or_else(invalid)?;
+    if span > TimeDelta::days(MAX_RANGE_DAYS) {
+        return Err(format!("range must be at most {MAX_RANGE_DAYS}d"));
+    }
+    Ok(span)
+}
+
+pub fn step(span: TimeDelta) -> Step {
+    if span <= HOURLY_UP_TO {
+        Step::Hour
+    } else {
+        Step::Day
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("24h", Ok(TimeDelta::hours(24)) ; "hours")]
+    #[test_case("7d", Ok(TimeDelta::days(7)) ; "days")]
+    #[test_case("4w", Ok(TimeDelta::weeks(4)) ; "weeks")]
+    #[test_case("366d", Ok(TimeDelta::days(366)) ; "maximum")]
+    #[test_case("367d", Err(()) ; "too long")]
+    #[test_case("999999999999999w", Err(()) ; "overflow")]
+    #[test_case("0d", Err(()) ; "zero")]
+    #[test_case("-1d", Err(()) ; "negative")]
+    #[test_case("7", Err(()) ; "no unit")]
+    #[test_case("d", Err(()) ; "no count")]
+    #[test_case("7m", Err(()) ; "unknown unit")]
+    #[test_case("", Err(()) ; "empty")]
+    #[test_case("7é", Err(()) ; "non ascii unit")]
+    fn test_parse_range(range: &str, expected: Result<TimeDelta, ()>) {
+        assert_eq!(parse_range(range).map_err(|_| ()), expected);
+    }
+
+    #[test_case(0, Ok(None) ; "zero clears")]
+    #[test_case(1 << 30, Ok(Some(1 << 30)) ; "gigabyte")]
+    #[test_case(-1, Err(()) ; "negative")]
+    fn test_normalize_quota(bytes: i64, expected: Result<Option<i64>, ()>) {
+        assert_eq!(normalize_quota(bytes).map_err(|_| ()), expected);
+    }
+
+    #[test_case(TimeDelta::hours(24), Step::Hour ; "day")]
+    #[test_case(TimeDelta::days(7), Step::Hour ; "week")]
+    #[test_case(TimeDelta::days(30), Step::Day ; "month")]
+    fn test_step(span: TimeDelta, expected: Step) {
+        assert_eq!(step(span), expected);
+    }
+}
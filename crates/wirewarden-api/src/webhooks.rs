@@ -0,0 +1,279 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Fan-out of resource events to registered webhooks, with signed delivery
+//! and exponential backoff.
+//!
+//! Each delivery carries the Unix time it was sent in `X-Wirewarden-Timestamp`
+//! and `sha256=<hex>` in `X-Wirewarden-Signature`: an HMAC-SHA256, keyed by
+//! the webhook secret, of the timestamp, a `.`, and the raw body. Receivers
+//! should recompute it and reject timestamps more than
+//! [`SIGNATURE_TOLERANCE_SECS`] from their own clock, so a captured delivery
+//! cannot be replayed later; [`verify`] does both.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::db::webhook::{Webhook, WebhookDelivery, WebhookStore};
+use crate::events::EventBus;
+
+const DELIVERY_TICK: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const CLAIM_BATCH: i64 = 32;
+const LEASE_SECS: i64 = 60;
+const MAX_ATTEMPTS: i32 = 8;
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 3600;
+
+pub const SIGNATURE_HEADER: &str = "X-Wirewarden-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Wirewarden-Timestamp";
+pub const EVENT_HEADER: &str = "X-Wirewarden-Event";
+pub const DELIVERY_HEADER: &str = "X-Wirewarden-Delivery";
+
+/// How far a delivery's timestamp may be from the receiver's clock before
+/// [`verify`] rejects it.
+pub const SIGNATURE_TOLERANCE_SECS: i64 = 5 * 60;
+
+/// Hex-encoded HMAC-SHA256 of `timestamp.body` keyed by the webhook secret.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = mac(secret, timestamp);
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .fold(String::with_capacity(64), |mut out, b| {
+            let _ = write!(out, "{b:02x}");
+            out
+        })
+}
+
+/// Check a delivery's signature header value (`sha256=<hex>`) against its
+/// timestamp and body, and that the timestamp is within
+/// [`SIGNATURE_TOLERANCE_SECS`] of `now`, both in Unix seconds.
+pub fn verify(secret: &str, timestamp: i64, body: &[u8], signature: &str, now: i64) -> bool {
+    if now.abs_diff(timestamp) > SIGNATURE_TOLERANCE_SECS as u64 {
+        return false;
+    }
+    let Some(hex) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+    let Some(expected) = decode_hex(hex) else {
+        return false;
+    };
+    let mut mac = mac(secret, timestamp);
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn mac(secret: &str, timestamp: i64) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Delay before the next attempt after `attempts` failures.
+fn backoff(attempts: i32) -> chrono::Duration {
+    let exp = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    let secs = BASE_BACKOFF_SECS
+        .saturating_mul(1 << exp)
+        .min(MAX_BACKOFF_SECS);
+    chrono::Duration::seconds(secs)
+}
+
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    store: WebhookStore,
+    http: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(store: WebhookStore) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("failed to build webhook http client");
+        Self { store, http }
+    }
+
+    pub fn spawn(self, events: &EventBus) {
+        let mut rx = events.subscribe();
+        let store = self.store.clone();
+        tokio::spawn(async move {
+            loop {
+                let event = match rx.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(n)) => {
+                        tracing::warn!(skipped = n, "webhook fan-out lagged; events dropped");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                };
+
+                let hooks = match store.list_subscribed(event.kind.as_str()).await {
+                    Ok(hooks) => hooks,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to load webhooks");
+                        continue;
+                    }
+                };
+                for hook in hooks {
+                    if let Err(e) = store.enqueue(hook.id, &event).await {
+                        tracing::warn!(webhook_id = %hook.id, error = %e, "failed to enqueue webhook delivery");
+                    }
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DELIVERY_TICK);
+            loop {
+                interval.tick().await;
+                self.deliver_due().await;
+            }
+        });
+    }
+
+    async fn deliver_due(&self) {
+        let due = match self.store.claim_due(CLAIM_BATCH, LEASE_SECS).await {
+            Ok(due) => due,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to claim webhook deliveries");
+                return;
+            }
+        };
+
+        let mut hooks: HashMap<_, Option<Webhook>> = HashMap::new();
+        for delivery in due {
+            if let Entry::Vacant(slot) = hooks.entry(delivery.webhook_id) {
+                slot.insert(self.store.get(delivery.webhook_id).await.ok().flatten());
+            }
+            // The webhook was deleted; its deliveries cascade away.
+            let Some(hook) = &hooks[&delivery.webhook_id] else {
+                continue;
+            };
+            self.deliver(hook, delivery).await;
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(webhook_id = %hook.id, delivery_id = %delivery.id))]
+    async fn deliver(&self, hook: &Webhook, delivery: WebhookDelivery) {
+        let body = serde_json::to_vec(&delivery.event.0).expect("event serializes");
+        let timestamp = Utc::now().timestamp();
+        let result = self
+            .http
+            .post(&hook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(
+                SIGNATURE_HEADER,
+                format!("sha256={}", sign(&hook.secret, timestamp, &body)),
+            )
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(EVENT_HEADER, delivery.event.kind.as_str())
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .body(body)
+            .send()
+            .await;
+
+        let (status_code, error) = match result {
+            Ok(resp) if resp.status().is_success() => {
+                let code = resp.status().as_u16().into();
+                if let Err(e) = self.store.mark_delivered(delivery.id, code).await {
+                    tracing::error!(error = %e, "failed to record webhook delivery");
+                }
+                return;
+            }
+            Ok(resp) => (
+                Some(i32::from(resp.status().as_u16())),
+                format!("unexpected status {}", resp.status()),
+            ),
+            Err(e) => (None, e.to_string()),
+        };
+
+        let attempts = delivery.attempts + 1;
+        let retry_at = (attempts < MAX_ATTEMPTS).then(|| Utc::now() + backoff(attempts));
+        tracing::warn!(attempts, error = %error, retrying = retry_at.is_some(), "webhook delivery failed");
+        if let Err(e) = self
+            .store
+            .mark_failed(delivery.id, status_code, &error, retry_at)
+            .await
+        {
+            tracing::error!(error = %e, "failed to record webhook delivery failure");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn test_sign_covers_timestamp() {
+        // The MAC covers the timestamp, a dot, and the body, in that order.
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"Jefe").unwrap();
+        mac.update(b"1700000000.what do ya want for nothing?");
+        let expected = mac.finalize().into_bytes();
+        let signature = sign("Jefe", NOW, b"what do ya want for nothing?");
+        assert_eq!(decode_hex(&signature).unwrap(), expected.as_slice());
+        assert_ne!(signature, sign("Jefe", NOW + 1, b"what do ya want for nothing?"));
+    }
+
+    #[test_case(NOW, "body", NOW, true ; "valid")]
+    #[test_case(NOW, "body", NOW + SIGNATURE_TOLERANCE_SECS, true ; "at tolerance")]
+    #[test_case(NOW, "body", NOW + SIGNATURE_TOLERANCE_SECS + 1, false ; "replayed late")]
+    #[test_case(NOW, "body", NOW - SIGNATURE_TOLERANCE_SECS - 1, false ; "from the future")]
+    #[test_case(NOW + 1, "body", NOW, false ; "timestamp changed")]
+    #[test_case(NOW, "bodY", NOW, false ; "body changed")]
+    fn test_verify(timestamp: i64, body: &str, now: i64, ok: bool) {
+        let signature = format!("sha256={}", sign("secret", NOW, b"body"));
+        assert_eq!(verify("secret", timestamp, body.as_bytes(), &signature, now), ok);
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("00ff" ; "missing prefix")]
+    #[test_case("sha256=zz" ; "not hex")]
+    #[test_case("sha256=abc" ; "odd length")]
+    fn test_verify_rejects_malformed(signature: &str) {
+        assert!(!verify("secret", NOW, b"body", signature, NOW));
+    }
+
+    #[test_case(1, 30 ; "first retry")]
+    #[test_case(2, 60 ; "second retry")]
+    #[test_case(5, 480 ; "fifth retry")]
+    #[test_case(10, 3600 ; "capped")]
+    fn test_backoff(attempts: i32, secs: i64) {
+        assert_eq!(backoff(attempts).num_seconds(), secs);
+    }
+}
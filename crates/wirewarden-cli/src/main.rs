@@ -0,0 +1,364 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+mod session;
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use uuid::Uuid;
+use wirewarden_client::api::{CreateClientRequest, Hooks, Network};
+use wirewarden_client::{Client, ConfigParams, ListParams, all_pages};
+
+use crate::session::Session;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+fn init_tracing() {
+    use tracing_subscriber::{EnvFilter, fmt};
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+#[derive(Debug, Parser)]
+#[command(name = env!("CARGO_PKG_NAME"))]
+#[command(version = env!("GIT_VERSION"))]
+#[command(about = "Administer a wirewarden API from the terminal")]
+struct Cli {
+    /// Where the login session is stored
+    #[arg(long, global = true, default_value_os_t = session::default_path())]
+    session: PathBuf,
+
+    /// Print machine-readable JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Log in and save the session for later commands
+    Login {
+        /// API server base URL
+        #[arg(long)]
+        api_url: String,
+
+        #[arg(short, long)]
+        username: String,
+
+        /// Read from stdin when unset
+        #[arg(long, env = "WIREWARDEN_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+
+    /// End the session and forget the saved token
+    Logout,
+
+    /// List networks
+    Networks {
+        /// Only networks whose name contains this
+        #[arg(long)]
+        filter: Option<String>,
+    },
+
+    /// Create a client and print its ID
+    CreateClient {
+        /// Network name or ID
+        #[arg(long)]
+        network: String,
+
+        #[arg(long)]
+        name: String,
+
+        /// Tag to attach; repeatable
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+
+    /// Print a client's wg-quick config
+    ClientConfig {
+        id: Uuid,
+
+        /// Route all traffic through the network's exit servers
+        #[arg(long)]
+        forward_internet: bool,
+
+        /// CIDR to keep off the tunnel when forwarding internet traffic;
+        /// repeatable
+        #[arg(long = "exclude", value_name = "CIDR")]
+        exclude: Vec<String>,
+
+        /// Whether to keep private ranges off the tunnel when forwarding
+        /// internet traffic; defaults to the network's setting
+        #[arg(long, value_name = "BOOL")]
+        lan_access: Option<bool>,
+    },
+
+    /// Move a client to another network, keeping its keys, and print its
+    /// new address
+    MoveClient {
+        id: Uuid,
+
+        /// Network name or ID to move to
+        #[arg(long)]
+        network: String,
+    },
+
+    /// Show servers in a network and whether their daemons are checking in
+    ServerStatus {
+        /// Network name or ID
+        network: String,
+    },
+
+    /// Find keys no server or client uses
+    Orphans {
+        /// Delete what is found
+        #[arg(long)]
+        purge: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    init_tracing();
+    let cli = Cli::parse();
+
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Command::Login {
+            api_url,
+            username,
+            password,
+        } => {
+            let password = match password {
+                Some(p) => p,
+                None => prompt_password()?,
+            };
+            let mut api = Client::new(&api_url);
+            let user = api.login(&username, &password).await?;
+            let token = api.token().unwrap_or_default().to_string();
+            session::save(&cli.session, &Session { api_url, token }).await?;
+            eprintln!("logged in as {}", user.username);
+        }
+        Command::Logout => {
+            if let Ok(session) = session::load(&cli.session).await {
+                let mut api = client(&session);
+                // The server-side session is a stateless JWT; failing to reach
+                // the API must not leave the token on disk.
+                if let Err(e) = api.logout().await {
+                    tracing::warn!(error = %e, "logout request failed");
+                }
+            }
+            session::clear(&cli.session).await?;
+        }
+        Command::Networks { filter } => {
+            let api = client(&session::load(&cli.session).await?);
+            let params = ListParams {
+                filter,
+                ..Default::default()
+            };
+            let networks = all_pages(&params, async |p| api.list_networks(p).await).await?;
+            if cli.json {
+                print_json(&networks)?;
+            } else {
+                println!("{:<36}  {:<24}  {:<18}  ENABLED", "ID", "NAME", "CIDR");
+                for n in &networks {
+                    println!(
+                        "{:<36}  {:<24}  {:<18}  {}",
+                        n.id, n.name, n.cidr, n.enabled
+                    );
+                }
+            }
+        }
+        Command::CreateClient {
+            network,
+            name,
+            tags,
+        } => {
+            let api = client(&session::load(&cli.session).await?);
+            let network = resolve_network(&api, &network).await?;
+            let body = CreateClientRequest {
+                network_id: network.id,
+                name,
+                tags,
+                notes: None,
+                mtu: None,
+                persistent_keepalive: None,
+                monthly_quota_bytes: None,
+                upload_kbps: None,
+                download_kbps: None,
+                hooks: Hooks::default(),
+            };
+            let created = api.create_client(&body).await?;
+            if cli.json {
+                print_json(&created)?;
+            } else {
+                println!("{}", created.id);
+            }
+        }
+        Command::ClientConfig {
+            id,
+            forward_internet,
+            exclude,
+            lan_access,
+        } => {
+            let api = client(&session::load(&cli.session).await?);
+            let params = ConfigParams {
+                forward_internet,
+                exclude: (!exclude.is_empty()).then(|| exclude.join(",")),
+                lan_access,
+            };
+            print!("{}", api.client_config_with(id, &params).await?);
+        }
+        Command::MoveClient { id, network } => {
+            let api = client(&session::load(&cli.session).await?);
+            let network = resolve_network(&api, &network).await?;
+            let moved = api.move_client(id, network.id).await?;
+            if cli.json {
+                print_json(&moved)?;
+            } else {
+                println!("{}", moved.address);
+            }
+        }
+        Command::ServerStatus { network } => {
+            let api = client(&session::load(&cli.session).await?);
+            let network = resolve_network(&api, &network).await?;
+            let servers = all_pages(&ListParams::default(), async |p| {
+                api.list_servers(network.id, p).await
+            })
+            .await?;
+            if cli.json {
+                print_json(&servers)?;
+            } else {
+                println!(
+                    "{:<24}  {:<15}  {:<28}  {:<8}  LAST SEEN",
+                    "NAME", "ADDRESS", "ENDPOINT", "STATUS"
+                );
+                let now = Utc::now();
+                for s in &servers {
+                    let endpoint = s
+                        .endpoint_host
+                        .as_ref()
+                        .map(|h| format!("{h}:{}", s.endpoint_port))
+                        .unwrap_or_else(|| "-".into());
+                    let status = if s.offline { "offline" } else { "online" };
+                    println!(
+                        "{:<24}  {:<15}  {:<28}  {:<8}  {}",
+                        s.name,
+                        s.address,
+                        endpoint,
+                        status,
+                        last_seen(s.last_seen_at, now),
+                    );
+                }
+            }
+        }
+        Command::Orphans { purge } => {
+            let api = client(&session::load(&cli.session).await?);
+            let report = if purge {
+                api.purge_orphans().await?
+            } else {
+                api.find_orphans().await?
+            };
+            if cli.json {
+                print_json(&report)?;
+            } else {
+                let verb = if purge { "removed" } else { "found" };
+                for id in &report.keys {
+                    println!("{id}");
+                }
+                println!("{verb} {} unused keys", report.keys.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn client(session: &Session) -> Client {
+    Client::new(&session.api_url).with_token(session.token.clone())
+}
+
+/// Accept either a network ID or its (case-insensitive) name.
+async fn resolve_network(api: &Client, network: &str) -> Result<Network> {
+    if let Ok(id) = network.parse::<Uuid>() {
+        return Ok(api.get_network(id).await?);
+    }
+    let params = ListParams {
+        filter: Some(network.to_string()),
+        ..Default::default()
+    };
+    all_pages(&params, async |p| api.list_networks(p).await)
+        .await?
+        .into_iter()
+        .find(|n| n.name.eq_ignore_ascii_case(network))
+        .ok_or_else(|| format!("no network named {network:?}").into())
+}
+
+/// Read a password from the terminal without echoing it.
+fn prompt_password() -> Result<String> {
+    Ok(rpassword::prompt_password("password: ")?)
+}
+
+fn print_json<T>(value: &T) -> Result<()>
+where
+    T: Serialize,
+{
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn last_seen(at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> String {
+    let Some(at) = at else {
+        return "never".into();
+    };
+    let secs = (now - at).num_seconds().max(0);
+    match secs {
+        0..60 => format!("{secs}s ago"),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86_400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use test_case::test_case;
+
+    #[test_case(None, "never" ; "never seen")]
+    #[test_case(Some(5), "5s ago" ; "seconds")]
+    #[test_case(Some(125), "2m ago" ; "minutes")]
+    #[test_case(Some(7_200), "2h ago" ; "hours")]
+    #[test_case(Some(259_200), "3d ago" ; "days")]
+    fn test_last_seen(ago: Option<i64>, expected: &str) {
+        let now = Utc::now();
+        let at = ago.map(|s| now - Duration::seconds(s));
+        assert_eq!(last_seen(at, now), expected);
+    }
+}
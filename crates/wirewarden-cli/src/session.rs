@@ -0,0 +1,152 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Saved login for the CLI, so each invocation doesn't need credentials.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::debug;
+use wirewarden_client::redact::Redacted;
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub api_url: String,
+    pub token: String,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("api_url", &self.api_url)
+            .field("token", &Redacted)
+            .finish()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("not logged in — run `wirewarden-cli login` first")]
+    NotLoggedIn,
+
+    #[error("failed to access session file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to parse session file: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("failed to serialize session: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// `$XDG_CONFIG_HOME/wirewarden/cli.toml`, falling back to `~/.config`.
+pub fn default_path() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .unwrap_or_default();
+    base.join("wirewarden").join("cli.toml")
+}
+
+pub async fn load(path: &Path) -> Result<Session, SessionError> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(contents) => Ok(toml::from_str(&contents)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(SessionError::NotLoggedIn),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write the session readable only by the current user; the token grants
+/// full API access. A file left looser by something else is tightened
+/// before the token goes in.
+pub async fn save(path: &Path, session: &Session) -> Result<(), SessionError> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let contents = toml::to_string(session)?;
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .await?;
+    }
+    tokio::io::AsyncWriteExt::write_all(&mut file, contents.as_bytes()).await?;
+
+    debug!(path = %path.display(), "saved session");
+    Ok(())
+}
+
+pub async fn clear(path: &Path) -> Result<(), SessionError> {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("cli.toml");
+        assert!(matches!(load(&path).await, Err(SessionError::NotLoggedIn)));
+
+        let session = Session {
+            api_url: "https://vpn.example.com".into(),
+            token: "abc.def".into(),
+        };
+        save(&path, &session).await.unwrap();
+        assert_eq!(load(&path).await.unwrap(), session);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        clear(&path).await.unwrap();
+        assert!(matches!(load(&path).await, Err(SessionError::NotLoggedIn)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_save_tightens_existing_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cli.toml");
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let session = Session {
+            api_url: "https://vpn.example.com".into(),
+            token: "abc.def".into(),
+        };
+        save(&path, &session).await.unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,695 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Typed client for the wirewarden management API.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), wirewarden_client::ClientError> {
+//! let mut api = wirewarden_client::Client::new("https://vpn.example.com");
+//! api.login("admin", "hunter2").await?;
+//! for network in api.list_networks(&Default::default()).await?.items {
+//!     println!("{} {}", network.name, network.cidr);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use reqwest::header::{COOKIE, HeaderMap, SET_COOKIE};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use tracing::debug;
+use uuid::Uuid;
+use wirewarden_types::api::{
+    AclRule, Activity, ClientConfig, ClientRoute, ClientRouteKind, ClientUsage, ConnectionEvent,
+    CreateAclRuleRequest, CreateClientRequest, CreateClientRouteRequest, CreateNetworkRequest,
+    CreateRouteRequest, CreateServerRequest, DnsZone, ErrorBody, GrowthQuery, GrowthReport,
+    LoginRequest, MoveClientRequest, Network, NetworkPeersReport, NotificationSettings,
+    OrphanReport, RotationReport, Route, Server, SetTagsRequest, Summary, UpdateNetworkRequest,
+    UpdateNotesRequest, UpdateServerRequest, User, UserDevicesReport, VerifyEmailRequest,
+};
+use wirewarden_types::daemon::{CAPABILITIES_HEADER, Capabilities, DaemonConfig, DaemonTelemetry};
+use wirewarden_types::redact::redact_opt;
+
+pub use wirewarden_types::api;
+pub use wirewarden_types::redact;
+
+const AUTH_COOKIE: &str = "token";
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("API returned {status}: {message}")]
+    Api { status: u16, message: String },
+
+    #[error("login response did not set an auth cookie")]
+    MissingAuthCookie,
+}
+
+impl ClientError {
+    /// HTTP status of an API error response.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Api { status, .. } => Some(*status),
+            Self::Http(e) => e.status().map(|s| s.as_u16()),
+            Self::MissingAuthCookie => None,
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, ClientError>;
+
+/// Paging, sorting, and filtering for list endpoints.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ListParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_page: Option<i64>,
+    /// Column to sort by; prefix with `-` for descending.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    /// Case-insensitive substring match on the name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    /// Comma-separated tags that must all be present.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// How to render a client's wg-quick config.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConfigParams {
+    /// Route internet traffic through the network's exit servers.
+    pub forward_internet: bool,
+    /// Comma-separated CIDRs to keep off the tunnel, on top of the network's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+    /// Keep private ranges off the tunnel; `None` takes the network's choice.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lan_access: Option<bool>,
+}
+
+/// One page of a list endpoint.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Total matching rows across all pages.
+    pub total: i64,
+}
+
+/// The largest `per_page` the API accepts.
+pub const MAX_PER_PAGE: i64 = 500;
+
+/// Every item a list endpoint matches, fetched a page at a time from the
+/// first until `total` items have arrived. `params.page` is ignored, and
+/// `params.per_page` defaults to [`MAX_PER_PAGE`].
+///
+/// ```ignore
+/// let networks = all_pages(&params, async |p| api.list_networks(p).await).await?;
+/// ```
+pub async fn all_pages<T>(
+    params: &ListParams,
+    fetch: impl AsyncFn(&ListParams) -> Result<Page<T>>,
+) -> Result<Vec<T>> {
+    let per_page = params.per_page.unwrap_or(MAX_PER_PAGE);
+    let mut params = ListParams {
+        page: Some(1),
+        per_page: Some(per_page),
+        ..params.clone()
+    };
+    let mut items = Vec::new();
+    loop {
+        let page = fetch(&params).await?;
+        let short = (page.items.len() as i64) < per_page;
+        items.extend(page.items);
+        if short || items.len() as i64 >= page.total {
+            return Ok(items);
+        }
+        params.page = params.page.map(|p| p + 1);
+    }
+}
+
+/// What a delete request did. Destructive changes may need a second
+/// approver, in which case the API queues them instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deletion {
+    Applied,
+    PendingApproval,
+}
+
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("http", &self.http)
+            .field("base_url", &self.base_url)
+            .field("token", &redact_opt(&self.token))
+            .finish()
+    }
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Self {
+        Self::with_http(reqwest::Client::new(), base_url)
+    }
+
+    /// Use a preconfigured `reqwest::Client` (timeouts, proxies, TLS roots).
+    pub fn with_http(http: reqwest::Client, base_url: &str) -> Self {
+        Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    /// Reuse a session token from an earlier [`Client::login`].
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// The session token, if logged in.
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    // -- Auth --
+
+    #[tracing::instrument(skip(self, password))]
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<User> {
+        let body = LoginRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        let resp = check(
+            self.request(Method::POST, "/api/auth/login")
+                .json(&body)
+                .send()
+                .await?,
+        )
+        .await?;
+        let token = auth_cookie(resp.headers()).ok_or(ClientError::MissingAuthCookie)?;
+        let user = resp.json().await?;
+        self.token = Some(token);
+        Ok(user)
+    }
+
+    pub async fn logout(&mut self) -> Result<()> {
+        self.send(self.request(Method::POST, "/api/auth/logout"))
+            .await?;
+        self.token = None;
+        Ok(())
+    }
+
+    pub async fn me(&self) -> Result<User> {
+        self.get("/api/auth/me").await
+    }
+
+    /// Confirm an email address with the token from its verification link.
+    /// When logged in as the same user, the session is refreshed so changes
+    /// are allowed straight away.
+    #[tracing::instrument(skip_all)]
+    pub async fn verify_email(&mut self, token: &str) -> Result<User> {
+        let body = VerifyEmailRequest {
+            token: token.to_string(),
+        };
+        let resp = self
+            .send(self.request(Method::POST, "/api/auth/verify").json(&body))
+            .await?;
+        if let Some(token) = auth_cookie(resp.headers()) {
+            self.token = Some(token);
+        }
+        Ok(resp.json().await?)
+    }
+
+    /// Mail the current user a new verification link.
+    pub async fn resend_verification(&self) -> Result<()> {
+        self.send(self.request(Method::POST, "/api/auth/verify/resend"))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn notification_settings(&self) -> Result<NotificationSettings> {
+        self.get("/api/auth/me/notifications").await
+    }
+
+    pub async fn set_notification_settings(
+        &self,
+        settings: &NotificationSettings,
+    ) -> Result<NotificationSettings> {
+        self.json(Method::PUT, "/api/auth/me/notifications", settings)
+            .await
+    }
+
+    // -- Networks --
+
+    pub async fn list_networks(&self, params: &ListParams) -> Result<Page<Network>> {
+        self.list("/api/networks", params).await
+    }
+
+    pub async fn get_network(&self, id: Uuid) -> Result<Network> {
+        self.get(&format!("/api/networks/{id}")).await
+    }
+
+    pub async fn create_network(&self, body: &CreateNetworkRequest) -> Result<Network> {
+        self.json(Method::POST, "/api/networks", body).await
+    }
+
+    pub async fn update_network(&self, id: Uuid, body: &UpdateNetworkRequest) -> Result<Network> {
+        self.json(Method::PATCH, &format!("/api/networks/{id}"), body)
+            .await
+    }
+
+    pub async fn delete_network(&self, id: Uuid) -> Result<Deletion> {
+        self.delete(&format!("/api/networks/{id}")).await
+    }
+
+    /// The network's peer hostnames.
+    pub async fn network_dns(&self, id: Uuid) -> Result<DnsZone> {
+        self.get(&format!("/api/networks/{id}/dns")).await
+    }
+
+    /// The network's peer hostnames as a zone file.
+    pub async fn network_zone_file(&self, id: Uuid) -> Result<String> {
+        let req = self
+            .request(Method::GET, &format!("/api/networks/{id}/dns"))
+            .query(&[("format", "zone")]);
+        Ok(self.send(req).await?.text().await?)
+    }
+
+    // -- Servers --
+
+    pub async fn list_servers(
+        &self,
+        network_id: Uuid,
+        params: &ListParams,
+    ) -> Result<Page<Server>> {
+        self.list(&format!("/api/networks/{network_id}/servers"), params)
+            .await
+    }
+
+    pub async fn get_server(&self, id: Uuid) -> Result<Server> {
+        self.get(&format!("/api/servers/{id}")).await
+    }
+
+    /// The returned server carries the full API token; later reads mask it.
+    pub async fn create_server(&self, body: &CreateServerRequest) -> Result<Server> {
+        self.json(Method::POST, "/api/servers", body).await
+    }
+
+    pub async fn update_server(&self, id: Uuid, body: &UpdateServerRequest) -> Result<Server> {
+        self.json(Method::PATCH, &format!("/api/servers/{id}"), body)
+            .await
+    }
+
+    pub async fn set_server_tags(&self, id: Uuid, tags: Vec<String>) -> Result<Server> {
+        let body = SetTagsRequest { tags };
+        self.json(Method::PUT, &format!("/api/servers/{id}/tags"), &body)
+            .await
+    }
+
+    /// Issue a new API token; the old one is revoked immediately.
+    pub async fn rotate_server_token(&self, id: Uuid) -> Result<Server> {
+        let req = self.request(Method::POST, &format!("/api/servers/{id}/token/rotate"));
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    pub async fn delete_server(&self, id: Uuid) -> Result<Deletion> {
+        self.delete(&format!("/api/servers/{id}")).await
+    }
+
+    // -- Clients --
+
+    pub async fn list_clients(
+        &self,
+        network_id: Uuid,
+        params: &ListParams,
+    ) -> Result<Page<api::Client>> {
+        self.list(&format!("/api/networks/{network_id}/clients"), params)
+            .await
+    }
+
+    pub async fn get_client(&self, id: Uuid) -> Result<api::Client> {
+        self.get(&format!("/api/clients/{id}")).await
+    }
+
+    pub async fn create_client(&self, body: &CreateClientRequest) -> Result<api::Client> {
+        self.json(Method::POST, "/api/clients", body).await
+    }
+
+    pub async fn update_client(&self, id: Uuid, body: &UpdateNotesRequest) -> Result<api::Client> {
+        self.json(Method::PATCH, &format!("/api/clients/{id}"), body)
+            .await
+    }
+
+    pub async fn set_client_tags(&self, id: Uuid, tags: Vec<String>) -> Result<api::Client> {
+        let body = SetTagsRequest { tags };
+        self.json(Method::PUT, &format!("/api/clients/{id}/tags"), &body)
+            .await
+    }
+
+    /// Move a client to another network, keeping its keys; it gets a new
+    /// address there.
+    pub async fn move_client(&self, id: Uuid, network_id: Uuid) -> Result<api::Client> {
+        let body = MoveClientRequest { network_id };
+        self.json(Method::POST, &format!("/api/clients/{id}/move"), &body)
+            .await
+    }
+
+    pub async fn delete_client(&self, id: Uuid) -> Result<Deletion> {
+        self.delete(&format!("/api/clients/{id}")).await
+    }
+
+    /// Render the client's wg-quick config.
+    pub async fn client_config(&self, id: Uuid, forward_internet: bool) -> Result<String> {
+        let params = ConfigParams {
+            forward_internet,
+            ..Default::default()
+        };
+        self.client_config_with(id, &params).await
+    }
+
+    /// Render the client's wg-quick config with every option spelled out.
+    pub async fn client_config_with(&self, id: Uuid, params: &ConfigParams) -> Result<String> {
+        let req = self
+            .request(Method::GET, &format!("/api/clients/{id}/config"))
+            .query(params);
+        let config: ClientConfig = self.send(req).await?.json().await?;
+        Ok(config.config)
+    }
+
+    /// The client's traffic over `range`, such as `24h` or `30d`: hourly up
+    /// to a week, daily beyond.
+    pub async fn client_usage(&self, id: Uuid, range: &str) -> Result<ClientUsage> {
+        let req = self
+            .request(Method::GET, &format!("/api/clients/{id}/usage"))
+            .query(&[("range", range)]);
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    // -- Routes --
+
+    pub async fn list_routes(&self, server_id: Uuid, params: &ListParams) -> Result<Page<Route>> {
+        self.list(&format!("/api/servers/{server_id}/routes"), params)
+            .await
+    }
+
+    pub async fn add_route(&self, server_id: Uuid, route_cidr: &str) -> Result<Route> {
+        let body = CreateRouteRequest {
+            route_cidr: route_cidr.to_string(),
+        };
+        self.json(
+            Method::POST,
+            &format!("/api/servers/{server_id}/routes"),
+            &body,
+        )
+        .await
+    }
+
+    pub async fn delete_route(&self, id: Uuid) -> Result<Deletion> {
+        self.delete(&format!("/api/routes/{id}")).await
+    }
+
+    // -- Client routes --
+
+    pub async fn list_client_routes(
+        &self,
+        client_id: Uuid,
+        params: &ListParams,
+    ) -> Result<Page<ClientRoute>> {
+        self.list(&format!("/api/clients/{client_id}/routes"), params)
+            .await
+    }
+
+    /// Add an override to one client's AllowedIPs. `server_id` is required
+    /// for [`ClientRouteKind::Extra`] and rejected for
+    /// [`ClientRouteKind::Only`].
+    pub async fn add_client_route(
+        &self,
+        client_id: Uuid,
+        kind: ClientRouteKind,
+        server_id: Option<Uuid>,
+        route_cidr: &str,
+    ) -> Result<ClientRoute> {
+        let body = CreateClientRouteRequest {
+            kind,
+            server_id,
+            route_cidr: route_cidr.to_string(),
+        };
+        self.json(
+            Method::POST,
+            &format!("/api/clients/{client_id}/routes"),
+            &body,
+        )
+        .await
+    }
+
+    pub async fn delete_client_route(&self, id: Uuid) -> Result<Deletion> {
+        self.delete(&format!("/api/client-routes/{id}")).await
+    }
+
+    // -- ACL rules --
+
+    /// The network's forwarding rules, in the order they are checked.
+    pub async fn list_acl_rules(&self, network_id: Uuid) -> Result<Vec<AclRule>> {
+        self.get(&format!("/api/networks/{network_id}/acl")).await
+    }
+
+    /// Add a forwarding rule. Each side is `*`, `tag:<tag>`, or a CIDR.
+    pub async fn add_acl_rule(
+        &self,
+        network_id: Uuid,
+        body: &CreateAclRuleRequest,
+    ) -> Result<AclRule> {
+        self.json(Method::POST, &format!("/api/networks/{network_id}/acl"), body)
+            .await
+    }
+
+    pub async fn delete_acl_rule(&self, id: Uuid) -> Result<Deletion> {
+        self.delete(&format!("/api/acl-rules/{id}")).await
+    }
+
+    // -- Activity --
+
+    /// The latest peer connect and disconnect events, newest first,
+    /// optionally for one network or peer.
+    pub async fn connection_events(
+        &self,
+        network_id: Option<Uuid>,
+        peer_id: Option<Uuid>,
+    ) -> Result<Vec<ConnectionEvent>> {
+        let mut req = self.request(Method::GET, "/api/connections");
+        if let Some(network_id) = network_id {
+            req = req.query(&[("network_id", network_id)]);
+        }
+        if let Some(peer_id) = peer_id {
+            req = req.query(&[("peer_id", peer_id)]);
+        }
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    /// Audit entries and connection events, newest first, optionally for
+    /// one network. Only the paging fields of `params` apply.
+    pub async fn activity(
+        &self,
+        network_id: Option<Uuid>,
+        params: &ListParams,
+    ) -> Result<Page<Activity>> {
+        match network_id {
+            Some(id) => self.list(&format!("/api/activity?network_id={id}"), params).await,
+            None => self.list("/api/activity", params).await,
+        }
+    }
+
+    // -- Tools --
+
+    /// Keys no server or client refers to, left by failed creates and
+    /// interrupted deletes.
+    pub async fn find_orphans(&self) -> Result<OrphanReport> {
+        self.get("/api/tools/orphans").await
+    }
+
+    /// Delete everything [`Client::find_orphans`] reports, returning what
+    /// was removed.
+    pub async fn purge_orphans(&self) -> Result<OrphanReport> {
+        let req = self.request(Method::POST, "/api/tools/orphans/purge");
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    // -- Reports --
+
+    /// Totals across every network, for a dashboard.
+    pub async fn summary(&self) -> Result<Summary> {
+        self.get("/api/summary").await
+    }
+
+    pub async fn peers_per_network(&self) -> Result<Vec<NetworkPeersReport>> {
+        self.get("/api/reports/peers-per-network").await
+    }
+
+    pub async fn growth_report(&self, query: &GrowthQuery) -> Result<GrowthReport> {
+        let req = self
+            .request(Method::GET, "/api/reports/growth")
+            .query(query);
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    /// Preshared key ages per network, optionally just one; keys older
+    /// than `max_age_days` are stale.
+    pub async fn rotation_report(
+        &self,
+        max_age_days: i32,
+        network_id: Option<Uuid>,
+    ) -> Result<Vec<RotationReport>> {
+        let mut req = self
+            .request(Method::GET, "/api/reports/rotation")
+            .query(&[("max_age_days", max_age_days)]);
+        if let Some(network_id) = network_id {
+            req = req.query(&[("network_id", network_id)]);
+        }
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    pub async fn devices_per_user(&self) -> Result<Vec<UserDevicesReport>> {
+        self.get("/api/reports/devices-per-user").await
+    }
+
+    // -- Daemon --
+
+    /// Fetch a server's WireGuard config using its API token rather than a
+    /// user session, announcing what this build's daemon handles.
+    pub async fn daemon_config(&self, api_token: &str) -> Result<DaemonConfig> {
+        let req = self
+            .http
+            .get(self.url("/api/daemon/config"))
+            .bearer_auth(api_token)
+            .header(CAPABILITIES_HEADER, Capabilities::CURRENT.to_header());
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    /// Report a server's per-peer transfer counters as its daemon would.
+    pub async fn daemon_telemetry(&self, api_token: &str, body: &DaemonTelemetry) -> Result<()> {
+        let req = self
+            .http
+            .post(self.url("/api/daemon/telemetry"))
+            .bearer_auth(api_token)
+            .json(body);
+        self.send(req).await?;
+        Ok(())
+    }
+
+    // -- Plumbing --
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let req = self.http.request(method, self.url(path));
+        match &self.token {
+            Some(token) => req.header(COOKIE, format!("{AUTH_COOKIE}={token}")),
+            None => req,
+        }
+    }
+
+    #[tracing::instrument(skip(self, req))]
+    async fn send(&self, req: RequestBuilder) -> Result<Response> {
+        check(req.send().await?).await
+    }
+
+    async fn get<T>(&self, path: &str) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(self
+            .send(self.request(Method::GET, path))
+            .await?
+            .json()
+            .await?)
+    }
+
+    async fn json<B, T>(&self, method: Method, path: &str, body: &B) -> Result<T>
+    where
+        B: Serialize,
+        T: DeserializeOwned,
+    {
+        let req = self.request(method, path).json(body);
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    async fn list<T>(&self, path: &str, params: &ListParams) -> Result<Page<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let resp = self
+            .send(self.request(Method::GET, path).query(params))
+            .await?;
+        let total = resp
+            .headers()
+            .get("x-total-count")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok());
+        let items: Vec<T> = resp.json().await?;
+        let total = total.unwrap_or(items.len() as i64);
+        Ok(Page { items, total })
+    }
+
+    async fn delete(&self, path: &str) -> Result<Deletion> {
+        let resp = self.send(self.request(Method::DELETE, path)).await?;
+        Ok(match resp.status() {
+            StatusCode::ACCEPTED => Deletion::PendingApproval,
+            _ => Deletion::Applied,
+        })
+    }
+}
+
+/// Turn non-2xx responses into [`ClientError::Api`] carrying the API's
+/// error message.
+async fn check(resp: Response) -> Result<Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let body = resp.text().await.unwrap_or_default();
+    debug!(status = status.as_u16(), body = %body, "API request failed");
+    let message = serde_json::from_str::<ErrorBody>(&body)
+        .map(|e| e.error)
+        .unwrap_or(body);
+    Err(ClientError::Api {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+fn auth_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next()?.split_once('='))
+        .find(|(name, value)| name.trim() == AUTH_COOKIE && !value.is_empty())
+        .map(|(_, value)| value.to_string())
+}
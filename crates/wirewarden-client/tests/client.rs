@@ -0,0 +1,144 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+use wirewarden_client::{Client, ClientError, Deletion, ListParams};
+
+/// Serve one canned response and hand back the raw request it received.
+async fn spawn_mock_api(
+    status: u16,
+    headers: &str,
+    body: &str,
+) -> (SocketAddr, tokio::task::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let response = format!(
+        "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len(),
+    );
+
+    let handle = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; 8192];
+        let n = stream.read(&mut buf).await.unwrap();
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+        String::from_utf8_lossy(&buf[..n]).into_owned()
+    });
+
+    (addr, handle)
+}
+
+fn user_json() -> String {
+    serde_json::json!({
+        "id": Uuid::nil(),
+        "username": "admin",
+        "display_name": "Admin",
+        "email": "admin@example.com",
+        "locale": "en",
+        "created_at": chrono::Utc::now(),
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn login_captures_session_cookie() {
+    let (addr, req) = spawn_mock_api(
+        200,
+        "Set-Cookie: token=abc.def; HttpOnly; Path=/\r\n",
+        &user_json(),
+    )
+    .await;
+
+    let mut api = Client::new(&format!("http://{addr}/"));
+    let user = api.login("admin", "pw").await.unwrap();
+
+    assert_eq!(user.username, "admin");
+    assert_eq!(api.token(), Some("abc.def"));
+    let req = req.await.unwrap();
+    assert!(req.starts_with("POST /api/auth/login "), "{req}");
+    assert!(req.contains(r#""username":"admin""#), "{req}");
+}
+
+#[tokio::test]
+async fn login_without_cookie_fails() {
+    let (addr, _req) = spawn_mock_api(200, "", &user_json()).await;
+
+    let mut api = Client::new(&format!("http://{addr}"));
+    let err = api.login("admin", "pw").await.unwrap_err();
+
+    assert!(matches!(err, ClientError::MissingAuthCookie), "{err}");
+}
+
+#[tokio::test]
+async fn list_sends_cookie_and_reads_total() {
+    let (addr, req) = spawn_mock_api(200, "X-Total-Count: 42\r\n", "[]").await;
+
+    let api = Client::new(&format!("http://{addr}")).with_token("tok");
+    let params = ListParams {
+        per_page: Some(10),
+        sort: Some("-name".into()),
+        ..Default::default()
+    };
+    let page = api.list_networks(&params).await.unwrap();
+
+    assert!(page.items.is_empty());
+    assert_eq!(page.total, 42);
+    let req = req.await.unwrap().to_lowercase();
+    assert!(
+        req.starts_with("get /api/networks?per_page=10&sort=-name "),
+        "{req}"
+    );
+    assert!(req.contains("cookie: token=tok"), "{req}");
+}
+
+#[tokio::test]
+async fn api_error_carries_message() {
+    let (addr, _req) = spawn_mock_api(404, "", r#"{"error":"not found"}"#).await;
+
+    let api = Client::new(&format!("http://{addr}")).with_token("tok");
+    let err = api.get_network(Uuid::nil()).await.unwrap_err();
+
+    assert_eq!(err.status(), Some(404));
+    assert!(
+        matches!(&err, ClientError::Api { message, .. } if message == "not found"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn delete_reports_pending_approval() {
+    let (addr, _req) = spawn_mock_api(202, "", r#"{"id":"x"}"#).await;
+
+    let api = Client::new(&format!("http://{addr}")).with_token("tok");
+    let outcome = api.delete_network(Uuid::nil()).await.unwrap();
+
+    assert_eq!(outcome, Deletion::PendingApproval);
+}
+
+#[tokio::test]
+async fn client_config_unwraps_body() {
+    let (addr, req) = spawn_mock_api(200, "", r#"{"config":"[Interface]\n"}"#).await;
+
+    let api = Client::new(&format!("http://{addr}")).with_token("tok");
+    let config = api.client_config(Uuid::nil(), true).await.unwrap();
+
+    assert_eq!(config, "[Interface]\n");
+    assert!(req.await.unwrap().contains("?forward_internet=true "));
+}
@@ -12,12 +12,34 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use reqwest::Client;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::{Certificate, Client, ClientBuilder, NoProxy, Proxy};
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{debug, info, warn};
-use wirewarden_types::daemon::DaemonConfig;
+use uuid::Uuid;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic::metadata::{Ascii, MetadataKey};
+use tonic::{Code, Request};
+use wirewarden_types::daemon::{
+    CAPABILITIES_HEADER, CONFIG_SERIAL_HEADER, CONFIG_SIGNATURE_HEADER, CONFIG_VERSION,
+    Capabilities, DAEMON_VERSION_HEADER, DaemonConfig, DaemonConfigDelta, DaemonTelemetry,
+    MIN_DAEMON_VERSION_HEADER, signed_message,
+};
+use wirewarden_types::grpc::daemon_client::DaemonClient;
+use wirewarden_types::grpc::{ConvertError, GetConfigRequest};
+use wirewarden_types::version::Version;
 
-use crate::config::ServerEntry;
+use crate::config::{HttpConfig, ProxyConfig, ServerEntry};
+use crate::tls::{self, TlsError};
 
 #[derive(Debug, Error)]
 pub enum ApiError {
@@ -32,35 +54,400 @@ pub enum ApiError {
 
     #[error("not found (404) — server may be deleted")]
     NotFound,
+
+    #[error("reflector returned an invalid address: {0}")]
+    InvalidReflectorResponse(String),
+
+    #[error("gRPC connection failed: {0}")]
+    Transport(#[from] tonic::transport::Error),
+
+    #[error("gRPC call failed: {0}")]
+    Grpc(tonic::Status),
+
+    #[error("gRPC response invalid: {0}")]
+    InvalidConfig(#[from] ConvertError),
+
+    #[error("api token is not a valid header value")]
+    InvalidToken,
+
+    #[error("config format version {0} is newer than this daemon supports; upgrade wirewarden")]
+    UnsupportedVersion(u32),
+
+    #[error(transparent)]
+    Tls(#[from] TlsError),
+
+    #[error("no client trusting this API's certificate is available")]
+    Untrusted,
+
+    #[error("fetch did not finish before the cycle deadline")]
+    CycleTimeout,
+
+    #[error("config signature missing or invalid; refusing to apply it")]
+    BadSignature,
+
+    #[error("config serial {serial} is older than the {applied} applied; refusing to roll back")]
+    StaleConfig { serial: i64, applied: i64 },
+
+    #[error("config is for server {got}, not {expected}; refusing to apply it")]
+    WrongServer { expected: Uuid, got: Uuid },
+
+    #[error("config response invalid: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+}
+
+impl From<tonic::Status> for ApiError {
+    fn from(status: tonic::Status) -> Self {
+        match status.code() {
+            Code::Unauthenticated => Self::Unauthorized,
+            Code::NotFound => Self::NotFound,
+            _ => Self::Grpc(status),
+        }
+    }
 }
 
 impl ApiError {
     pub fn is_gone(&self) -> bool {
         matches!(self, Self::Unauthorized | Self::NotFound)
     }
+
+    /// Whether trying again shortly might succeed: network failures,
+    /// timeouts, and server-side errors.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Request(e) => !e.is_builder() && !e.is_decode(),
+            Self::ServerError { status, .. } => *status >= 500,
+            Self::Transport(_) => true,
+            Self::Grpc(status) => matches!(
+                status.code(),
+                Code::Unavailable | Code::DeadlineExceeded | Code::ResourceExhausted
+            ),
+            _ => false,
+        }
+    }
 }
 
-#[tracing::instrument(skip(client, entry), fields(api_host = %entry.api_host))]
+/// The HTTP client for API and reflector requests, sent through `proxy` when
+/// set. gRPC endpoints connect directly either way.
+pub fn build_client(proxy: Option<&ProxyConfig>, http: &HttpConfig) -> Result<Client, ApiError> {
+    Ok(client_builder(proxy, http)?.build()?)
+}
+
+fn client_builder(
+    proxy: Option<&ProxyConfig>,
+    http: &HttpConfig,
+) -> Result<ClientBuilder, ApiError> {
+    let mut builder = Client::builder()
+        .connect_timeout(Duration::from_secs(http.connect_timeout_secs))
+        .timeout(Duration::from_secs(http.request_timeout_secs));
+    if let Some(proxy) = proxy {
+        let no_proxy = proxy.no_proxy.as_deref().and_then(NoProxy::from_string);
+        builder = builder.proxy(Proxy::all(&proxy.url)?.no_proxy(no_proxy));
+    }
+    Ok(builder)
+}
+
+/// A client for an entry with its own certificate trust (see
+/// [`ServerEntry::has_custom_trust`]). A pinned certificate is checked on
+/// each connection, through the proxy like any other request.
+#[tracing::instrument(skip_all, fields(api_host = %entry.api_host))]
+pub async fn entry_client(
+    proxy: Option<&ProxyConfig>,
+    http: &HttpConfig,
+    entry: &ServerEntry,
+) -> Result<Client, ApiError> {
+    let mut builder = client_builder(proxy, http)?;
+    if let Some(pin) = &entry.cert_sha256 {
+        builder = builder.use_preconfigured_tls(tls::pinned_config(pin)?);
+    } else if let Some(path) = &entry.ca_cert {
+        for cert in Certificate::from_pem_bundle(&tls::read_ca(path).await?)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    Ok(builder.build()?)
+}
+
+/// A fetched config and the ETag and config serial the API served it with,
+/// if any.
+#[derive(Debug, Clone)]
+pub struct FetchedConfig {
+    pub config: DaemonConfig,
+    pub etag: Option<String>,
+    pub serial: Option<i64>,
+}
+
+/// Fetch the desired config over the transport `entry` is configured for.
+/// `prev` is the last config fetched for this entry. When it carries a
+/// serial only the peers changed since are fetched and applied to it; if its
+/// ETag or serial is still current, `prev` is returned unchanged.
 pub async fn fetch_config(
     client: &Client,
     entry: &ServerEntry,
-) -> Result<DaemonConfig, ApiError> {
-    let url = format!("{}/api/daemon/config", entry.api_host.trim_end_matches('/'));
+    prev: Option<&FetchedConfig>,
+) -> Result<FetchedConfig, ApiError> {
+    fetch_config_via(client, None, entry, prev).await
+}
+
+/// [`fetch_config`], over `channel` when `entry` uses gRPC. Without one a
+/// channel is opened for this fetch alone.
+pub async fn fetch_config_via(
+    client: &Client,
+    channel: Option<&Channel>,
+    entry: &ServerEntry,
+    prev: Option<&FetchedConfig>,
+) -> Result<FetchedConfig, ApiError> {
+    let fetched = match (&entry.grpc_endpoint, channel) {
+        (Some(_), Some(channel)) => fetch_config_grpc(channel.clone(), entry).await?,
+        (Some(endpoint), None) => {
+            let channel = grpc_channel(endpoint, entry).await?;
+            fetch_config_grpc(channel, entry).await?
+        }
+        (None, _) => fetch_config_rest(client, entry, prev).await?,
+    };
+    if fetched.config.version > CONFIG_VERSION {
+        return Err(ApiError::UnsupportedVersion(fetched.config.version));
+    }
+    // The API signs every server's configs with one key, so a valid
+    // signature alone does not make a config this entry's.
+    let expected = entry.server_id.or(prev.map(|p| p.config.server.id));
+    check_server(expected, &fetched.config)?;
+    Ok(fetched)
+}
+
+/// Refuse `config` when it is for a server other than `expected`, once
+/// that is known.
+pub fn check_server(expected: Option<Uuid>, config: &DaemonConfig) -> Result<(), ApiError> {
+    match expected {
+        Some(expected) if expected != config.server.id => Err(ApiError::WrongServer {
+            expected,
+            got: config.server.id,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// A channel to `endpoint`, trusting `entry`'s CA as well as the system's.
+/// It connects on first use and reconnects after failures, so one can serve
+/// every fetch for the entry.
+pub async fn grpc_channel(endpoint: &str, entry: &ServerEntry) -> Result<Channel, ApiError> {
+    let mut channel = Endpoint::from_shared(endpoint.to_string())?
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(30));
+    if endpoint.starts_with("https://") {
+        let mut tls_config = ClientTlsConfig::new().with_enabled_roots();
+        if let Some(path) = &entry.ca_cert {
+            let pem = tls::read_ca(path).await?;
+            tls_config = tls_config.ca_certificate(tonic::transport::Certificate::from_pem(pem));
+        }
+        channel = channel.tls_config(tls_config)?;
+    }
+    Ok(channel.connect_lazy())
+}
+
+#[tracing::instrument(skip_all, fields(endpoint = entry.grpc_endpoint))]
+async fn fetch_config_grpc(
+    channel: Channel,
+    entry: &ServerEntry,
+) -> Result<FetchedConfig, ApiError> {
+    let token = &entry.api_token;
+    let mut client = DaemonClient::new(channel);
+
+    let mut request = Request::new(GetConfigRequest {});
+    let auth = format!("Bearer {token}")
+        .parse()
+        .map_err(|_| ApiError::InvalidToken)?;
+    request.metadata_mut().insert("authorization", auth);
+    let version = env!("GIT_VERSION")
+        .parse()
+        .expect("git version is a valid header value");
+    let caps = Capabilities::CURRENT
+        .to_header()
+        .parse()
+        .expect("capabilities are a valid header value");
+    let metadata = request.metadata_mut();
+    metadata.insert(grpc_key(DAEMON_VERSION_HEADER), version);
+    metadata.insert(grpc_key(CAPABILITIES_HEADER), caps);
+
+    let response = client.get_config(request).await?;
+    let min_version = response
+        .metadata()
+        .get(grpc_key(MIN_DAEMON_VERSION_HEADER))
+        .and_then(|v| v.to_str().ok());
+    check_min_version(min_version);
+    let mut response = response.into_inner();
+    debug!(config_serial = response.config_serial, "received gRPC config");
+    let serial = response.config_serial;
+    let signature = response.signature.take();
+    let config = match response.signed_config.take() {
+        // What was signed is applied, not the fields converted alongside it.
+        Some(body) if entry.config_key.is_some() => {
+            let config: DaemonConfig = serde_json::from_slice(&body)?;
+            let server_id = config.server.id;
+            verify(entry, server_id, Some(serial), &body, signature.as_deref())?;
+            config
+        }
+        _ if entry.config_key.is_some() => return Err(ApiError::BadSignature),
+        _ => DaemonConfig::try_from(response)?,
+    };
+    info!(
+        server_name = %config.server.name,
+        network = %config.network.name,
+        peer_count = config.peers.len(),
+        "fetched config successfully over gRPC"
+    );
+    Ok(FetchedConfig {
+        config,
+        etag: None,
+        serial: Some(serial),
+    })
+}
+
+/// The Ed25519 key in a `config_key` setting, if it is one.
+pub fn parse_config_key(key: &str) -> Option<VerifyingKey> {
+    let bytes: [u8; 32] = STANDARD.decode(key).ok()?.try_into().ok()?;
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+/// The signature the API sent with `resp`'s body.
+fn signature_header(resp: &reqwest::Response) -> Option<String> {
+    resp.headers()
+        .get(CONFIG_SIGNATURE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Check `signature` over `body` as served for `server_id` at `serial` when
+/// `entry` has a config key; entries without one accept anything, as before
+/// signing existed. A config for another server, or one whose serial was
+/// tampered with or left out, fails.
+fn verify(
+    entry: &ServerEntry,
+    server_id: Uuid,
+    serial: Option<i64>,
+    body: &[u8],
+    signature: Option<&str>,
+) -> Result<(), ApiError> {
+    let Some(key) = &entry.config_key else {
+        return Ok(());
+    };
+    let key = parse_config_key(key).ok_or(ApiError::BadSignature)?;
+    let serial = serial.ok_or(ApiError::BadSignature)?;
+    let signature: [u8; 64] = signature
+        .and_then(|s| STANDARD.decode(s).ok())
+        .and_then(|s| s.try_into().ok())
+        .ok_or(ApiError::BadSignature)?;
+    let message = signed_message(server_id, serial, body);
+    key.verify_strict(&message, &Signature::from_bytes(&signature))
+        .map_err(|_| ApiError::BadSignature)
+}
+
+/// Set once any API announces a minimum daemon release newer than this
+/// build.
+static OUTDATED: AtomicBool = AtomicBool::new(false);
+
+/// Whether an API has said this build is older than it supports.
+pub fn outdated() -> bool {
+    OUTDATED.load(Ordering::Relaxed)
+}
+
+/// The minimum release last warned about, so polls repeating it stay quiet.
+static WARNED_MIN: Mutex<Option<Version>> = Mutex::new(None);
+
+/// Warn when the API announces a minimum daemon release newer than this
+/// build, once per minimum announced. Development builds without a release
+/// tag are not compared.
+fn check_min_version(min: Option<&str>) {
+    let Some(min) = min.and_then(|m| m.parse::<Version>().ok()) else {
+        return;
+    };
+    if let Ok(current) = env!("GIT_VERSION").parse::<Version>()
+        && current < min
+    {
+        OUTDATED.store(true, Ordering::Relaxed);
+        let mut warned = WARNED_MIN.lock().unwrap_or_else(|e| e.into_inner());
+        if warned.replace(min) == Some(min) {
+            return;
+        }
+        warn!(
+            version = %current,
+            min_version = %min,
+            "this daemon is older than the API supports — please upgrade"
+        );
+    }
+}
+
+/// gRPC metadata keys are the REST header names, lowercased.
+fn grpc_key(header: &'static str) -> MetadataKey<Ascii> {
+    MetadataKey::from_bytes(header.to_ascii_lowercase().as_bytes())
+        .expect("header names are valid metadata keys")
+}
+
+#[tracing::instrument(skip(client, entry, prev), fields(api_host = %entry.api_host))]
+async fn fetch_config_rest(
+    client: &Client,
+    entry: &ServerEntry,
+    prev: Option<&FetchedConfig>,
+) -> Result<FetchedConfig, ApiError> {
+    let host = entry.api_host.trim_end_matches('/');
+    let since = prev.and_then(|p| p.serial);
+    let url = match since {
+        Some(since) => format!("{host}/api/daemon/config/delta?since={since}"),
+        None => format!("{host}/api/daemon/config"),
+    };
 
     debug!(url = %url, "fetching daemon config from API");
 
-    let resp = client
+    let mut req = client
         .get(&url)
         .bearer_auth(&entry.api_token)
-        .send()
-        .await?;
+        .header(DAEMON_VERSION_HEADER, env!("GIT_VERSION"))
+        .header(CAPABILITIES_HEADER, Capabilities::CURRENT.to_header());
+    let prev = prev.filter(|p| p.etag.is_some() || p.serial.is_some());
+    if let Some(etag) = prev.filter(|_| since.is_none()).and_then(|p| p.etag.as_deref()) {
+        req = req.header(IF_NONE_MATCH, etag);
+    }
+    let resp = req.send().await?;
 
     let status = resp.status().as_u16();
     debug!(status, "received API response");
+    check_min_version(
+        resp.headers()
+            .get(MIN_DAEMON_VERSION_HEADER)
+            .and_then(|v| v.to_str().ok()),
+    );
 
-    match status {
-        200 => {
-            let config: DaemonConfig = resp.json().await?;
+    match (status, prev) {
+        (304, Some(prev)) => {
+            debug!("config not modified");
+            Ok(prev.clone())
+        }
+        (200, Some(prev)) if since.is_some() => {
+            let signature = signature_header(&resp);
+            let body = resp.bytes().await?;
+            let delta: DaemonConfigDelta = serde_json::from_slice(&body)?;
+            let (server_id, serial) = (prev.config.server.id, Some(delta.serial));
+            verify(entry, server_id, serial, &body, signature.as_deref())?;
+            info!(
+                serial = delta.serial,
+                full = delta.full,
+                upserted = delta.upserted.len(),
+                removed = delta.removed.len(),
+                "fetched config delta successfully"
+            );
+            let serial = Some(delta.serial);
+            Ok(FetchedConfig {
+                config: delta.apply(&prev.config),
+                etag: None,
+                serial,
+            })
+        }
+        (200, _) => {
+            let header = |name| resp.headers().get(name).and_then(|v| v.to_str().ok());
+            let etag = header(ETAG.as_str()).map(str::to_string);
+            let serial = header(CONFIG_SERIAL_HEADER).and_then(|s| s.parse().ok());
+            let signature = signature_header(&resp);
+            let body = resp.bytes().await?;
+            let config: DaemonConfig = serde_json::from_slice(&body)?;
+            verify(entry, config.server.id, serial, &body, signature.as_deref())?;
             info!(
                 server_name = %config.server.name,
                 network = %config.network.name,
@@ -69,13 +456,17 @@ pub async fn fetch_config(
                 address = %config.server.address,
                 "fetched config successfully"
             );
-            Ok(config)
+            Ok(FetchedConfig {
+                config,
+                etag,
+                serial,
+            })
         }
-        401 => {
+        (401, _) => {
             warn!("API returned 401 — token may be revoked");
             Err(ApiError::Unauthorized)
         }
-        404 => {
+        (404, _) => {
             warn!("API returned 404 — server may be deleted");
             Err(ApiError::NotFound)
         }
@@ -86,3 +477,131 @@ pub async fn fetch_config(
         }
     }
 }
+
+#[derive(Debug, Serialize)]
+struct UpdateEndpointRequest {
+    endpoint_host: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateEndpointResponse {
+    endpoint_host: String,
+    changed: bool,
+}
+
+/// Ask `reflector` for this host's public IP. The response body must be the
+/// bare address.
+#[tracing::instrument(skip(client))]
+pub async fn detect_public_ip(client: &Client, reflector: &str) -> Result<IpAddr, ApiError> {
+    let resp = client.get(reflector).send().await?.error_for_status()?;
+    let body = resp.text().await?;
+    body.trim()
+        .parse()
+        .map_err(|_| ApiError::InvalidReflectorResponse(body.trim().to_string()))
+}
+
+/// Check that the API answers its unauthenticated `/health` endpoint.
+#[tracing::instrument(skip(client, entry), fields(api_host = %entry.api_host))]
+pub async fn check_health(client: &Client, entry: &ServerEntry) -> Result<(), ApiError> {
+    let url = format!("{}/health", entry.api_host.trim_end_matches('/'));
+    let resp = client.get(&url).send().await?;
+    match resp.status().as_u16() {
+        200..=299 => Ok(()),
+        status => {
+            let body = resp.text().await.unwrap_or_default();
+            Err(ApiError::ServerError { status, body })
+        }
+    }
+}
+
+/// Tell the API this server is going away on purpose, so it is shown as
+/// offline at once.
+#[tracing::instrument(skip(client, entry), fields(api_host = %entry.api_host))]
+pub async fn report_offline(client: &Client, entry: &ServerEntry) -> Result<(), ApiError> {
+    let url = format!("{}/api/daemon/offline", entry.api_host.trim_end_matches('/'));
+    let resp = client
+        .post(&url)
+        .bearer_auth(&entry.api_token)
+        .send()
+        .await?;
+
+    match resp.status().as_u16() {
+        200..=299 => Ok(()),
+        401 => Err(ApiError::Unauthorized),
+        404 => Err(ApiError::NotFound),
+        status => {
+            let body = resp.text().await.unwrap_or_default();
+            Err(ApiError::ServerError { status, body })
+        }
+    }
+}
+
+/// Send the interface's per-peer transfer counters.
+#[tracing::instrument(skip_all, fields(api_host = %entry.api_host))]
+pub async fn report_telemetry(
+    client: &Client,
+    entry: &ServerEntry,
+    telemetry: &DaemonTelemetry,
+) -> Result<(), ApiError> {
+    let url = format!("{}/api/daemon/telemetry", entry.api_host.trim_end_matches('/'));
+    let resp = client
+        .post(&url)
+        .bearer_auth(&entry.api_token)
+        .json(telemetry)
+        .send()
+        .await?;
+
+    match resp.status().as_u16() {
+        200..=299 => Ok(()),
+        401 => Err(ApiError::Unauthorized),
+        404 => Err(ApiError::NotFound),
+        status => {
+            let body = resp.text().await.unwrap_or_default();
+            Err(ApiError::ServerError { status, body })
+        }
+    }
+}
+
+/// Report the server's public endpoint. With `ip` unset the API records the
+/// address it observes this request coming from. Returns the stored host.
+#[tracing::instrument(skip(client, entry), fields(api_host = %entry.api_host))]
+pub async fn update_endpoint(
+    client: &Client,
+    entry: &ServerEntry,
+    server_id: Uuid,
+    ip: Option<IpAddr>,
+) -> Result<String, ApiError> {
+    let url = format!(
+        "{}/api/servers/{server_id}/endpoint",
+        entry.api_host.trim_end_matches('/')
+    );
+    let endpoint_host = ip.map(|ip| match ip {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("[{v6}]"),
+    });
+
+    let resp = client
+        .post(&url)
+        .bearer_auth(&entry.api_token)
+        .json(&UpdateEndpointRequest { endpoint_host })
+        .send()
+        .await?;
+
+    match resp.status().as_u16() {
+        200 => {
+            let body: UpdateEndpointResponse = resp.json().await?;
+            if body.changed {
+                info!(endpoint_host = %body.endpoint_host, "updated server endpoint");
+            } else {
+                debug!(endpoint_host = %body.endpoint_host, "server endpoint unchanged");
+            }
+            Ok(body.endpoint_host)
+        }
+        401 => Err(ApiError::Unauthorized),
+        404 => Err(ApiError::NotFound),
+        status => {
+            let body = resp.text().await.unwrap_or_default();
+            Err(ApiError::ServerError { status, body })
+        }
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Last-applied configs on disk, so interfaces can come up while the API is
+//! unreachable. One `<server-id>.json` per server; each file holds a private
+//! key and the API token, so it is written owner-only.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
+use uuid::Uuid;
+use wirewarden_types::daemon::DaemonConfig;
+use wirewarden_types::redact::Redacted;
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("failed to access config cache: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to serialize cached config: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedConfig {
+    api_token: String,
+    config: DaemonConfig,
+    /// Config serial the API served `config` at, where known.
+    #[serde(default)]
+    serial: Option<i64>,
+}
+
+impl fmt::Debug for CachedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachedConfig")
+            .field("api_token", &Redacted)
+            .field("config", &self.config)
+            .field("serial", &self.serial)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigCache {
+    dir: PathBuf,
+}
+
+impl ConfigCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, server_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{server_id}.json"))
+    }
+
+    /// Every cached config, keyed by API token. Unreadable entries are
+    /// skipped; a missing directory is an empty cache.
+    pub async fn load_all(&self) -> HashMap<String, DaemonConfig> {
+        self.load_entries()
+            .await
+            .into_iter()
+            .map(|cached| (cached.api_token, cached.config))
+            .collect()
+    }
+
+    /// The serial each cached config was served at, keyed by API token, for
+    /// those that have one.
+    pub async fn load_serials(&self) -> HashMap<String, i64> {
+        self.load_entries()
+            .await
+            .into_iter()
+            .filter_map(|cached| Some((cached.api_token, cached.serial?)))
+            .collect()
+    }
+
+    async fn load_entries(&self) -> Vec<CachedConfig> {
+        let mut configs = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return configs,
+            Err(e) => {
+                warn!(dir = %self.dir.display(), error = %e, "failed to read config cache");
+                return configs;
+            }
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            match read(&path).await {
+                Ok(cached) => {
+                    debug!(path = %path.display(), server = %cached.config.server.name, "loaded cached config");
+                    configs.push(cached);
+                }
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "skipping unreadable cached config")
+                }
+            }
+        }
+        configs
+    }
+
+    /// Replace the cached config for `config`'s server, served at `serial`.
+    pub async fn store(
+        &self,
+        api_token: &str,
+        config: &DaemonConfig,
+        serial: Option<i64>,
+    ) -> Result<(), CacheError> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let cached = CachedConfig {
+            api_token: api_token.to_string(),
+            config: config.clone(),
+            serial,
+        };
+        let contents = serde_json::to_vec_pretty(&cached)?;
+        let path = self.path(config.server.id);
+        write_private(&path, &contents).await?;
+        debug!(path = %path.display(), "cached config");
+        Ok(())
+    }
+
+    pub async fn remove(&self, server_id: Uuid) -> Result<(), CacheError> {
+        match tokio::fs::remove_file(self.path(server_id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Write `contents` to `path`, readable by the owner only. Written aside and
+/// renamed so a crash never leaves a torn file.
+pub(crate) async fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    write_private_like(path, contents, path).await
+}
+
+/// [`write_private`], giving the file the owner and mode of `like`, or for
+/// a `like` that does not exist, the owner of `path`'s directory. A root
+/// `connect` so leaves the config of a daemon run with `--user` to that
+/// user, rather than replacing it with a file the daemon cannot read.
+pub(crate) async fn write_private_like(
+    path: &Path,
+    contents: &[u8],
+    like: &Path,
+) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let tmp = path.with_extension("tmp");
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp).await?;
+    #[cfg(unix)]
+    match tokio::fs::metadata(like).await {
+        Ok(meta) => {
+            give_to(&file, &meta)?;
+            file.set_permissions(meta.permissions()).await?;
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            give_to(&file, &tokio::fs::metadata(dir).await?)?;
+        }
+        Err(e) => return Err(e),
+    }
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    tokio::fs::rename(&tmp, path).await?;
+    // The rename itself is only durable once the directory is synced.
+    #[cfg(unix)]
+    tokio::fs::File::open(dir).await?.sync_all().await?;
+    Ok(())
+}
+
+/// Give `file` the owner and group of `meta`. Only root can give files
+/// away; anyone else's files are theirs already.
+#[cfg(unix)]
+fn give_to(file: &tokio::fs::File, meta: &std::fs::Metadata) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    // SAFETY: geteuid has no preconditions.
+    if unsafe { libc::geteuid() } != 0 {
+        return Ok(());
+    }
+    std::os::unix::fs::fchown(file, Some(meta.uid()), Some(meta.gid()))
+}
+
+async fn read(path: &Path) -> Result<CachedConfig, CacheError> {
+    let contents = tokio::fs::read(path).await?;
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wirewarden_types::daemon::{CONFIG_VERSION, DaemonNetworkInfo, DaemonServerInfo};
+
+    fn sample_config() -> DaemonConfig {
+        DaemonConfig {
+            version: CONFIG_VERSION,
+            server: DaemonServerInfo {
+                id: Uuid::new_v4(),
+                name: "cached".into(),
+                private_key: "private".into(),
+                public_key: "public".into(),
+                address: "10.0.0.1".into(),
+                listen_port: 51820,
+                manage_nat: false,
+                fwmark: None,
+                route_table: None,
+                rule_priority: None,
+                mtu: None,
+            },
+            network: DaemonNetworkInfo {
+                id: Uuid::new_v4(),
+                name: "net".into(),
+                cidr: "10.0.0.0/24".into(),
+                persistent_keepalive: 25,
+                dns_servers: Vec::new(),
+                dns_zone: None,
+                acl: Vec::new(),
+            },
+            peers: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn store_load_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ConfigCache::new(dir.path().join("state"));
+        assert!(cache.load_all().await.is_empty(), "missing dir is empty");
+
+        let config = sample_config();
+        cache.store("token", &config, Some(4)).await.unwrap();
+        tokio::fs::write(dir.path().join("state/junk.json"), "{")
+            .await
+            .unwrap();
+        let loaded = cache.load_all().await;
+        assert_eq!(loaded.len(), 1, "unreadable files are skipped");
+        assert_eq!(loaded["token"], config);
+        assert_eq!(cache.load_serials().await["token"], 4);
+
+        cache.remove(config.server.id).await.unwrap();
+        cache.remove(config.server.id).await.unwrap();
+        assert!(cache.load_all().await.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn store_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ConfigCache::new(dir.path());
+        let config = sample_config();
+        cache.store("token", &config, None).await.unwrap();
+        let meta = std::fs::metadata(cache.path(config.server.id)).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rewrites_keep_the_owner_and_mode() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.toml");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        // As root, as for `connect`, the file is someone else's.
+        // SAFETY: geteuid has no preconditions.
+        if unsafe { libc::geteuid() } == 0 {
+            std::os::unix::fs::chown(&path, Some(65534), Some(65534)).unwrap();
+        }
+        let before = std::fs::metadata(&path).unwrap();
+
+        write_private(&path, b"new").await.unwrap();
+        let backup = dir.path().join("daemon.toml.bak");
+        write_private_like(&backup, b"old", &path).await.unwrap();
+
+        for file in [&path, &backup] {
+            let after = std::fs::metadata(file).unwrap();
+            assert_eq!(after.permissions().mode() & 0o777, 0o640);
+            assert_eq!((after.uid(), after.gid()), (before.uid(), before.gid()));
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+    }
+}
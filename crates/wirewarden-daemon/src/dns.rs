@@ -0,0 +1,797 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The optional DNS forwarder. Each server gets a UDP listener on its VPN
+//! address that answers for the peer hostnames in its config and passes
+//! every other query to the network's resolvers, so clients pointed at a
+//! server resolve both their peers and the internet through the tunnel.
+//!
+//! Only the question is parsed; replies from upstream are relayed as they
+//! come.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+use tokio::sync::{OnceCell, Semaphore, oneshot, watch};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+use wirewarden_types::daemon::{DaemonConfig, DaemonDnsZone};
+
+use crate::config::DnsConfig;
+
+/// Where the host's own resolvers are listed, the last resort upstream.
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// How long answers from the zone may be cached, in seconds. Matches the
+/// zone the API exports.
+const TTL: u32 = 300;
+
+/// How long to wait on each upstream before trying the next.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Largest datagram read, enough for EDNS replies.
+const MAX_PACKET: usize = 4096;
+
+/// Most forwarded queries outstanding per server; more are dropped, and
+/// the client retries.
+const MAX_IN_FLIGHT: usize = 256;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+const RCODE_SERVFAIL: u8 = 2;
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// The peer hostnames one server answers for.
+#[derive(Debug, Default, PartialEq)]
+pub struct Zone {
+    /// Lowercase, without the trailing dot. Empty for no zone.
+    origin: String,
+    records: HashMap<String, Vec<IpAddr>>,
+}
+
+impl Zone {
+    pub fn new(zone: &DaemonDnsZone) -> Self {
+        let mut records: HashMap<String, Vec<IpAddr>> = HashMap::new();
+        for record in &zone.records {
+            if let Ok(address) = record.address.parse() {
+                records
+                    .entry(record.name.to_ascii_lowercase())
+                    .or_default()
+                    .push(address);
+            }
+        }
+        Self {
+            origin: zone.origin.to_ascii_lowercase(),
+            records,
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        !self.origin.is_empty()
+            && (name == self.origin
+                || name
+                    .strip_suffix(&self.origin)
+                    .is_some_and(|host| host.ends_with('.')))
+    }
+
+    /// The reply to `query` when it asks about a name in the zone, or `None`
+    /// to forward it.
+    pub fn answer(&self, query: &[u8]) -> Option<Vec<u8>> {
+        let question = Question::parse(query)?;
+        if question.class != CLASS_IN || !self.contains(&question.name) {
+            return None;
+        }
+        let (rcode, addresses) = match self.records.get(&question.name) {
+            Some(addresses) => (0, addresses.iter().filter(|a| question.wants(a)).collect()),
+            None if question.name == self.origin => (0, Vec::new()),
+            None => (RCODE_NXDOMAIN, Vec::new()),
+        };
+        Some(reply(query, &question, rcode, true, &addresses))
+    }
+}
+
+/// The one question a query asks.
+#[derive(Debug, PartialEq)]
+struct Question {
+    /// Lowercase, without the trailing dot.
+    name: String,
+    qtype: u16,
+    class: u16,
+    /// Offset just past the question.
+    end: usize,
+}
+
+impl Question {
+    /// The question of a standard query with exactly one, or `None` for
+    /// anything else.
+    fn parse(packet: &[u8]) -> Option<Self> {
+        if !is_query(packet) || packet[2] & 0x78 != 0 || packet[4..6] != [0, 1] {
+            return None;
+        }
+        let mut labels = Vec::new();
+        let mut pos = 12;
+        loop {
+            let len = *packet.get(pos)? as usize;
+            pos += 1;
+            if len == 0 {
+                break;
+            }
+            // Compression pointers have no place in a lone question.
+            if len & 0xC0 != 0 {
+                return None;
+            }
+            let label = packet.get(pos..pos + len)?;
+            labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+            pos += len;
+        }
+        let name = labels.join(".");
+        if name.len() > 253 {
+            return None;
+        }
+        let field = |at: usize| Some(u16::from_be_bytes([*packet.get(at)?, *packet.get(at + 1)?]));
+        Some(Self {
+            name,
+            qtype: field(pos)?,
+            class: field(pos + 2)?,
+            end: pos + 4,
+        })
+    }
+
+    fn wants(&self, address: &IpAddr) -> bool {
+        match self.qtype {
+            TYPE_A => address.is_ipv4(),
+            TYPE_AAAA => address.is_ipv6(),
+            TYPE_ANY => true,
+            _ => false,
+        }
+    }
+}
+
+/// Whether `packet` is long enough to be a message and is not a response.
+fn is_query(packet: &[u8]) -> bool {
+    packet.len() >= 12 && packet[2] & 0x80 == 0
+}
+
+/// A response to `query` with `addresses` as answers to its question.
+fn reply(
+    query: &[u8],
+    question: &Question,
+    rcode: u8,
+    authoritative: bool,
+    addresses: &[&IpAddr],
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(question.end + addresses.len() * 28);
+    out.extend_from_slice(&query[..2]);
+    // QR, AA and the query's RD; then RA, since everything else is forwarded.
+    let aa = if authoritative { 0x04 } else { 0 };
+    out.push(0x80 | aa | (query[2] & 0x01));
+    out.push(0x80 | rcode);
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    out.extend_from_slice(&query[12..question.end]);
+    for address in addresses {
+        // A pointer back to the question's name.
+        out.extend_from_slice(&[0xC0, 0x0C]);
+        let (rtype, data) = match address {
+            IpAddr::V4(v4) => (TYPE_A, v4.octets().to_vec()),
+            IpAddr::V6(v6) => (TYPE_AAAA, v6.octets().to_vec()),
+        };
+        out.extend_from_slice(&rtype.to_be_bytes());
+        out.extend_from_slice(&CLASS_IN.to_be_bytes());
+        out.extend_from_slice(&TTL.to_be_bytes());
+        out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        out.extend_from_slice(&data);
+    }
+    out
+}
+
+/// What a forwarder answers from and where it sends the rest.
+#[derive(Debug, Default, PartialEq)]
+struct Settings {
+    zone: Zone,
+    upstreams: Vec<SocketAddr>,
+}
+
+/// Where queries outside the zone go: `[dns] upstreams`, else the
+/// network's DNS servers, else the host's. Network DNS servers inside the
+/// network's CIDR are forwarders themselves and would send queries in
+/// circles, so they are skipped.
+fn upstreams(
+    config: &DnsConfig,
+    daemon_config: &DaemonConfig,
+    host: &[SocketAddr],
+) -> Vec<SocketAddr> {
+    if !config.upstreams.is_empty() {
+        return config
+            .upstreams
+            .iter()
+            .filter_map(|s| parse_upstream(s))
+            .collect();
+    }
+    let network: Vec<_> = daemon_config
+        .network
+        .dns_servers
+        .iter()
+        .filter_map(|s| parse_upstream(s))
+        .filter(|addr| !in_cidr(addr.ip(), &daemon_config.network.cidr))
+        .collect();
+    if network.is_empty() {
+        host.to_vec()
+    } else {
+        network
+    }
+}
+
+/// `ip` or `ip:port`, with port 53 by default.
+fn parse_upstream(s: &str) -> Option<SocketAddr> {
+    let s = s.trim();
+    s.parse()
+        .ok()
+        .or_else(|| s.parse().ok().map(|ip| SocketAddr::new(ip, 53)))
+}
+
+fn in_cidr(ip: IpAddr, cidr: &str) -> bool {
+    let Some((net, prefix)) = cidr.split_once('/') else {
+        return false;
+    };
+    let (Ok(net), Ok(prefix)) = (net.parse::<IpAddr>(), prefix.parse::<u32>()) else {
+        return false;
+    };
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+/// The `nameserver` lines of a resolv.conf.
+fn parse_resolv_conf(text: &str) -> Vec<SocketAddr> {
+    text.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|rest| rest.split_whitespace().next())
+        .filter_map(|ip| ip.parse().ok().map(|ip| SocketAddr::new(ip, 53)))
+        .collect()
+}
+
+/// The forwarders running, one per server.
+#[derive(Debug, Default)]
+pub struct Resolvers {
+    running: HashMap<Uuid, Forwarder>,
+    /// Addresses that could not be bound, to warn about only once.
+    failed: HashSet<SocketAddr>,
+}
+
+impl Resolvers {
+    /// Run a forwarder for each server in `configs` as `config` says, and
+    /// stop those whose server is gone.
+    pub async fn sync<'a>(
+        &mut self,
+        config: &DnsConfig,
+        configs: impl IntoIterator<Item = &'a DaemonConfig>,
+    ) {
+        if !config.enabled {
+            if !self.running.is_empty() {
+                info!("DNS forwarder disabled, stopping");
+                self.running.clear();
+            }
+            return;
+        }
+
+        let host = match tokio::fs::read_to_string(RESOLV_CONF).await {
+            Ok(text) => parse_resolv_conf(&text),
+            Err(e) => {
+                debug!(error = %e, "cannot read {RESOLV_CONF}");
+                Vec::new()
+            }
+        };
+
+        let mut seen = HashSet::new();
+        for daemon_config in configs {
+            let server = &daemon_config.server;
+            let ip = server
+                .address
+                .split('/')
+                .next()
+                .and_then(|ip| ip.parse().ok());
+            let Some(ip) = ip else {
+                warn!(server = %server.name, address = %server.address, "no address to serve DNS on");
+                continue;
+            };
+            seen.insert(server.id);
+            let listen = SocketAddr::new(ip, config.port);
+            let settings = Settings {
+                zone: daemon_config
+                    .network
+                    .dns_zone
+                    .as_ref()
+                    .map(Zone::new)
+                    .unwrap_or_default(),
+                upstreams: upstreams(config, daemon_config, &host),
+            };
+
+            if let Some(forwarder) = self.running.get(&server.id)
+                && forwarder.listen == listen
+            {
+                forwarder.settings.send_if_modified(|current| {
+                    let changed = **current != settings;
+                    if changed {
+                        debug!(server = %server.name, "DNS forwarder updated");
+                        *current = Arc::new(settings);
+                    }
+                    changed
+                });
+                continue;
+            }
+
+            self.running.remove(&server.id);
+            match Forwarder::start(listen, settings).await {
+                Ok(forwarder) => {
+                    info!(server = %server.name, %listen, "serving DNS");
+                    self.failed.remove(&listen);
+                    self.running.insert(server.id, forwarder);
+                }
+                Err(e) if self.failed.insert(listen) => {
+                    warn!(server = %server.name, %listen, error = %e, "cannot serve DNS");
+                }
+                Err(e) => debug!(server = %server.name, %listen, error = %e, "cannot serve DNS"),
+            }
+        }
+        self.running.retain(|id, _| seen.contains(id));
+    }
+}
+
+/// One server's listener, stopped when dropped.
+#[derive(Debug)]
+struct Forwarder {
+    listen: SocketAddr,
+    settings: watch::Sender<Arc<Settings>>,
+    task: JoinHandle<()>,
+}
+
+impl Forwarder {
+    async fn start(listen: SocketAddr, settings: Settings) -> io::Result<Self> {
+        let socket = UdpSocket::bind(listen).await?;
+        let (tx, rx) = watch::channel(Arc::new(settings));
+        Ok(Self {
+            listen,
+            settings: tx,
+            task: tokio::spawn(serve(socket, rx)),
+        })
+    }
+}
+
+impl Drop for Forwarder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(socket: UdpSocket, settings: watch::Receiver<Arc<Settings>>) {
+    let socket = Arc::new(socket);
+    let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+    let upstream = Arc::new(Upstream::default());
+    let mut buf = vec![0; MAX_PACKET];
+    loop {
+        let (len, client) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                debug!(error = %e, "DNS receive failed");
+                continue;
+            }
+        };
+        let query = &buf[..len];
+        if !is_query(query) {
+            continue;
+        }
+        let settings = settings.borrow().clone();
+        if let Some(reply) = settings.zone.answer(query) {
+            if let Err(e) = socket.send_to(&reply, client).await {
+                debug!(%client, error = %e, "DNS reply failed");
+            }
+            continue;
+        }
+
+        let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+            debug!(%client, "too many DNS queries in flight, dropping");
+            continue;
+        };
+        let query = query.to_vec();
+        let socket = socket.clone();
+        let upstream = upstream.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let reply = match upstream.forward(&query, &settings.upstreams).await {
+                Some(reply) => reply,
+                None => match Question::parse(&query) {
+                    Some(question) => reply(&query, &question, RCODE_SERVFAIL, false, &[]),
+                    None => return,
+                },
+            };
+            if let Err(e) = socket.send_to(&reply, client).await {
+                debug!(%client, error = %e, "DNS reply failed");
+            }
+        });
+    }
+}
+
+/// The sockets a forwarder sends upstream queries from, one per address
+/// family and opened on first use, shared by all its queries. Each query
+/// goes out under a random ID of its own, so clients that pick the same ID
+/// cannot get each other's replies; the client's ID is put back on the way
+/// out. Replies are matched on ID and the upstream they came from.
+#[derive(Default)]
+struct Upstream {
+    v4: OnceCell<Arc<UdpSocket>>,
+    v6: OnceCell<Arc<UdpSocket>>,
+    pending: Arc<Mutex<HashMap<u16, Pending>>>,
+    readers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+/// A query waiting on a reply.
+struct Pending {
+    upstream: SocketAddr,
+    reply: oneshot::Sender<Vec<u8>>,
+}
+
+impl Upstream {
+    /// The first reply any of `upstreams` gives to `query`, trying them in
+    /// order.
+    async fn forward(&self, query: &[u8], upstreams: &[SocketAddr]) -> Option<Vec<u8>> {
+        for &upstream in upstreams {
+            match tokio::time::timeout(UPSTREAM_TIMEOUT, self.exchange(query, upstream)).await {
+                Ok(Ok(reply)) => return Some(reply),
+                Ok(Err(e)) => debug!(%upstream, error = %e, "DNS upstream failed"),
+                Err(_) => debug!(%upstream, "DNS upstream timed out"),
+            }
+        }
+        None
+    }
+
+    async fn exchange(&self, query: &[u8], upstream: SocketAddr) -> io::Result<Vec<u8>> {
+        let socket = self.socket(upstream).await?;
+        let (tx, rx) = oneshot::channel();
+        let id = self.register(upstream, tx)?;
+        // Dropped on every way out, timeouts included.
+        let _pending = PendingGuard { pending: &self.pending, id };
+
+        let mut outgoing = query.to_vec();
+        outgoing[..2].copy_from_slice(&id.to_be_bytes());
+        socket.send_to(&outgoing, upstream).await?;
+        let mut reply = rx
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "upstream socket closed"))?;
+        reply[..2].copy_from_slice(&query[..2]);
+        Ok(reply)
+    }
+
+    /// A free ID for a query to `upstream`, now waiting on `reply`.
+    fn register(&self, upstream: SocketAddr, reply: oneshot::Sender<Vec<u8>>) -> io::Result<u16> {
+        let mut pending = self.pending.lock().unwrap();
+        loop {
+            let mut id = [0; 2];
+            openssl::rand::rand_bytes(&mut id).map_err(io::Error::other)?;
+            let id = u16::from_be_bytes(id);
+            if let Entry::Vacant(entry) = pending.entry(id) {
+                entry.insert(Pending { upstream, reply });
+                return Ok(id);
+            }
+        }
+    }
+
+    async fn socket(&self, upstream: SocketAddr) -> io::Result<Arc<UdpSocket>> {
+        let (cell, local): (_, IpAddr) = match upstream {
+            SocketAddr::V4(_) => (&self.v4, Ipv4Addr::UNSPECIFIED.into()),
+            SocketAddr::V6(_) => (&self.v6, Ipv6Addr::UNSPECIFIED.into()),
+        };
+        let socket = cell
+            .get_or_try_init(|| async {
+                let socket = Arc::new(UdpSocket::bind((local, 0)).await?);
+                let reader = tokio::spawn(read_replies(socket.clone(), self.pending.clone()));
+                self.readers.lock().unwrap().push(reader);
+                Ok::<_, io::Error>(socket)
+            })
+            .await?;
+        Ok(socket.clone())
+    }
+}
+
+impl Drop for Upstream {
+    fn drop(&mut self) {
+        for reader in self.readers.get_mut().unwrap().drain(..) {
+            reader.abort();
+        }
+    }
+}
+
+struct PendingGuard<'a> {
+    pending: &'a Mutex<HashMap<u16, Pending>>,
+    id: u16,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Hand each reply on `socket` to the query waiting on it. Anything not
+/// answering a pending query from the upstream it was sent to is stray or
+/// spoofed.
+async fn read_replies(socket: Arc<UdpSocket>, pending: Arc<Mutex<HashMap<u16, Pending>>>) {
+    let mut buf = vec![0; MAX_PACKET];
+    loop {
+        let (len, from) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                debug!(error = %e, "DNS upstream receive failed");
+                continue;
+            }
+        };
+        if len < 12 {
+            continue;
+        }
+        let id = u16::from_be_bytes([buf[0], buf[1]]);
+        let mut pending = pending.lock().unwrap();
+        if let Entry::Occupied(entry) = pending.entry(id)
+            && entry.get().upstream == from
+        {
+            let _ = entry.remove().reply.send(buf[..len].to_vec());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+    use wirewarden_types::daemon::{
+        CONFIG_VERSION, DaemonDnsRecord, DaemonNetworkInfo, DaemonServerInfo,
+    };
+
+    use super::*;
+
+    fn query(name: &str, qtype: u16) -> Vec<u8> {
+        let mut packet = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+        packet.extend_from_slice(&qtype.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet
+    }
+
+    fn zone() -> Zone {
+        let record = |name: &str, address: &str| DaemonDnsRecord {
+            name: format!("{name}.home.wirewarden.internal"),
+            address: address.into(),
+        };
+        Zone::new(&DaemonDnsZone {
+            origin: "home.wirewarden.internal".into(),
+            records: vec![
+                record("gw", "10.0.0.1"),
+                record("gw", "fd00::1"),
+                record("laptop", "10.0.0.2"),
+            ],
+        })
+    }
+
+    /// The rcode and answer addresses of a reply built by [`reply`].
+    fn decode(reply: &[u8], question_end: usize) -> (u8, Vec<IpAddr>) {
+        let count = u16::from_be_bytes([reply[6], reply[7]]) as usize;
+        let mut pos = question_end;
+        let mut addresses = Vec::new();
+        for _ in 0..count {
+            let len = u16::from_be_bytes([reply[pos + 10], reply[pos + 11]]) as usize;
+            let data = &reply[pos + 12..pos + 12 + len];
+            addresses.push(match len {
+                4 => IpAddr::from(<[u8; 4]>::try_from(data).unwrap()),
+                _ => IpAddr::from(<[u8; 16]>::try_from(data).unwrap()),
+            });
+            pos += 12 + len;
+        }
+        (reply[3] & 0x0F, addresses)
+    }
+
+    #[test_case("gw.home.wirewarden.internal", TYPE_A, 0, &["10.0.0.1"] ; "a")]
+    #[test_case("GW.Home.wirewarden.internal", TYPE_AAAA, 0, &["fd00::1"] ; "aaaa any case")]
+    #[test_case("gw.home.wirewarden.internal", TYPE_ANY, 0, &["10.0.0.1", "fd00::1"] ; "any")]
+    #[test_case("laptop.home.wirewarden.internal", TYPE_AAAA, 0, &[] ; "no record of type")]
+    #[test_case("home.wirewarden.internal", TYPE_A, 0, &[] ; "origin")]
+    #[test_case("phone.home.wirewarden.internal", TYPE_A, RCODE_NXDOMAIN, &[] ; "unknown peer")]
+    fn test_answer(name: &str, qtype: u16, rcode: u8, expected: &[&str]) {
+        let query = query(name, qtype);
+        let reply = zone().answer(&query).unwrap();
+        assert_eq!(reply[..2], query[..2]);
+        assert_eq!(reply[2] & 0x84, 0x84, "authoritative response");
+        let expected: Vec<IpAddr> = expected.iter().map(|a| a.parse().unwrap()).collect();
+        assert_eq!(decode(&reply, query.len()), (rcode, expected));
+    }
+
+    #[test_case(query("example.com", TYPE_A) ; "outside the zone")]
+    #[test_case(query("xhome.wirewarden.internal", TYPE_A) ; "suffix without dot")]
+    #[test_case(vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 0] ; "response")]
+    #[test_case(vec![0x12, 0x34, 0x01] ; "truncated")]
+    fn test_forwarded(query: Vec<u8>) {
+        assert!(zone().answer(&query).is_none());
+    }
+
+    #[test]
+    fn test_no_zone_answers_nothing() {
+        let query = query("gw.home.wirewarden.internal", TYPE_A);
+        assert!(Zone::default().answer(&query).is_none());
+    }
+
+    #[test_case("10.0.0.53", "10.0.0.0/24", true ; "inside")]
+    #[test_case("10.0.1.1", "10.0.0.0/24", false ; "outside")]
+    #[test_case("fd00::35", "fd00::/64", true ; "v6")]
+    #[test_case("10.0.0.1", "fd00::/64", false ; "other family")]
+    #[test_case("1.1.1.1", "0.0.0.0/0", true ; "everything")]
+    fn test_in_cidr(ip: &str, cidr: &str, expected: bool) {
+        assert_eq!(in_cidr(ip.parse().unwrap(), cidr), expected);
+    }
+
+    #[test]
+    fn test_parse_resolv_conf() {
+        let text = "# generated\nnameserver 127.0.0.53\nsearch lan\nnameserver ::1 # v6\n";
+        let expected: Vec<SocketAddr> = vec![
+            "127.0.0.53:53".parse().unwrap(),
+            "[::1]:53".parse().unwrap(),
+        ];
+        assert_eq!(parse_resolv_conf(text), expected);
+    }
+
+    fn daemon_config(dns_servers: &[&str]) -> DaemonConfig {
+        DaemonConfig {
+            version: CONFIG_VERSION,
+            server: DaemonServerInfo {
+                id: Uuid::nil(),
+                name: "gw".into(),
+                private_key: "priv".into(),
+                public_key: "pub".into(),
+                address: "10.0.0.1/24".into(),
+                listen_port: 51820,
+                manage_nat: false,
+                fwmark: None,
+                route_table: None,
+                rule_priority: None,
+                mtu: None,
+            },
+            network: DaemonNetworkInfo {
+                id: Uuid::nil(),
+                name: "home".into(),
+                cidr: "10.0.0.0/24".into(),
+                persistent_keepalive: 25,
+                dns_servers: dns_servers.iter().map(|s| s.to_string()).collect(),
+                dns_zone: None,
+                acl: Vec::new(),
+            },
+            peers: Vec::new(),
+        }
+    }
+
+    #[test_case(&[], &["9.9.9.9"], &["9.9.9.9:53"] ; "own upstreams")]
+    #[test_case(&["1.1.1.1", "10.0.0.1"], &[], &["1.1.1.1:53"] ; "network without forwarders")]
+    #[test_case(&["10.0.0.1"], &[], &["127.0.0.53:53"] ; "only forwarders falls back to host")]
+    #[test_case(&["1.1.1.1"], &["192.168.1.1:5353"], &["192.168.1.1:5353"] ; "own upstreams win")]
+    fn test_upstreams(network: &[&str], own: &[&str], expected: &[&str]) {
+        let config = DnsConfig {
+            enabled: true,
+            upstreams: own.iter().map(|s| s.to_string()).collect(),
+            ..DnsConfig::default()
+        };
+        let host = ["127.0.0.53:53".parse().unwrap()];
+        let expected: Vec<SocketAddr> = expected.iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(upstreams(&config, &daemon_config(network), &host), expected);
+    }
+
+    #[tokio::test]
+    async fn test_forwarder_answers_and_forwards() {
+        // An upstream that answers every query with a bare response.
+        let upstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = upstream.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            while let Ok((len, from)) = upstream.recv_from(&mut buf).await {
+                let mut reply = buf[..len].to_vec();
+                reply[2] |= 0x80;
+                upstream.send_to(&reply, from).await.unwrap();
+            }
+        });
+
+        let settings = Settings {
+            zone: zone(),
+            upstreams: vec![upstream_addr],
+        };
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let listen = socket.local_addr().unwrap();
+        let (_tx, rx) = watch::channel(Arc::new(settings));
+        let task = tokio::spawn(serve(socket, rx));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(listen).await.unwrap();
+        let mut buf = [0; 512];
+
+        let peer = query("laptop.home.wirewarden.internal", TYPE_A);
+        client.send(&peer).await.unwrap();
+        let len = client.recv(&mut buf).await.unwrap();
+        let expected = vec!["10.0.0.2".parse::<IpAddr>().unwrap()];
+        assert_eq!(decode(&buf[..len], peer.len()), (0, expected));
+
+        let outside = query("example.com", TYPE_A);
+        client.send(&outside).await.unwrap();
+        let len = client.recv(&mut buf).await.unwrap();
+        assert_eq!(buf[2] & 0x84, 0x80, "relayed, not authoritative");
+        assert_eq!(buf[12..len], outside[12..]);
+
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_upstream_keeps_same_id_queries_apart() {
+        // An upstream that echoes queries back, the first one late, so the
+        // replies arrive in the other order.
+        let upstream = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let upstream_addr = upstream.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            let mut first = true;
+            while let Ok((len, from)) = upstream.recv_from(&mut buf).await {
+                let mut reply = buf[..len].to_vec();
+                reply[2] |= 0x80;
+                let upstream = upstream.clone();
+                let delay = if first { 200 } else { 0 };
+                first = false;
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    upstream.send_to(&reply, from).await.unwrap();
+                });
+            }
+        });
+
+        let forwarder = Upstream::default();
+        let one = query("one.example.com", TYPE_A);
+        let two = query("two.example.com", TYPE_A);
+        let upstreams = [upstream_addr];
+        let (a, b) = tokio::join!(
+            forwarder.forward(&one, &upstreams),
+            async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                forwarder.forward(&two, &upstreams).await
+            },
+        );
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert_eq!(a[..2], one[..2]);
+        assert_eq!(a[12..], one[12..]);
+        assert_eq!(b[..2], two[..2]);
+        assert_eq!(b[12..], two[12..]);
+        assert!(forwarder.pending.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,657 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `wirewarden doctor`: checks that this host can run the daemon, each with a
+//! verdict and, for anything short of a pass, what to do about it.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, Ipv6Addr, UdpSocket};
+use std::path::{Path, PathBuf};
+
+use crate::api;
+use crate::cache::ConfigCache;
+use crate::config::{self, Backend, ConfigError, DaemonToml, HttpConfig, WireguardConfig};
+use crate::netlink::Platform;
+use crate::status;
+
+/// Bit of `CAP_NET_ADMIN` in the kernel's capability sets.
+const CAP_NET_ADMIN: u32 = 12;
+
+/// How long each API has to answer before it counts as unreachable.
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    /// Not broken now, but likely to be.
+    Warn,
+    Fail,
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    pub verdict: Verdict,
+    pub detail: String,
+    /// What to do about a warning or failure.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            verdict: Verdict::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            verdict: Verdict::Warn,
+            fix: Some(fix.into()),
+            ..Self::pass(name, detail)
+        }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            verdict: Verdict::Fail,
+            fix: Some(fix.into()),
+            ..Self::pass(name, detail)
+        }
+    }
+}
+
+/// Run every check. Checks that need the config file are skipped when it
+/// cannot be read.
+pub async fn run<P: Platform>(config_path: &Path, state_dir: &Path) -> Vec<Check> {
+    let (check, config) = check_config(config_path).await;
+    let wireguard = config.as_ref().map(|c| c.wireguard.clone()).unwrap_or_default();
+    let mut checks = vec![check_wireguard(&wireguard), check_net_admin(), check];
+    checks.extend(check_config_writable(config_path).await);
+    if let Some(config) = config {
+        checks.extend(check_apis(&config).await);
+        checks.extend(check_ports::<P>(&config, &ConfigCache::new(state_dir)).await);
+    }
+    checks
+}
+
+/// The checks `wirewarden daemon` runs before its first cycle: those whose
+/// failure would otherwise surface mid-cycle as an opaque netlink or I/O
+/// error. A simulating daemon touches no interfaces, so only its config is
+/// checked.
+pub async fn preflight(config_path: &Path, simulate: bool) -> Vec<Check> {
+    let (check, config) = check_config(config_path).await;
+    let mut checks = Vec::new();
+    if !simulate {
+        let wireguard = config.map(|c| c.wireguard).unwrap_or_default();
+        checks.push(check_net_admin());
+        checks.push(check_wireguard(&wireguard));
+    }
+    checks.push(check);
+    checks.extend(check_config_writable(config_path).await);
+    checks
+}
+
+async fn check_config(path: &Path) -> (Check, Option<DaemonToml>) {
+    const NAME: &str = "config";
+    let shown = path.display();
+    match tokio::fs::metadata(path).await {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let check = Check::warn(
+                NAME,
+                format!("{shown} does not exist"),
+                "add a server with `wirewarden connect`, which creates it",
+            );
+            return (check, Some(DaemonToml::default()));
+        }
+        _ => {}
+    }
+    match config::load(path).await {
+        Ok(config) => {
+            let check = Check::pass(NAME, format!("{shown}: {} servers", config.servers.len()));
+            (check, Some(config))
+        }
+        Err(ConfigError::Read(e)) if e.kind() == ErrorKind::PermissionDenied => {
+            let check = Check::fail(
+                NAME,
+                format!("{shown} is not readable"),
+                "run as root, or as the user the daemon runs as",
+            );
+            (check, None)
+        }
+        Err(e) => {
+            let fix = format!("correct {shown}; see doc/daemon.md for the format");
+            (Check::fail(NAME, e.to_string(), fix), None)
+        }
+    }
+}
+
+/// Whether the daemon can rewrite its config, as it does to drop servers
+/// whose token was revoked. Saves replace the file with one written beside
+/// it, so its directory must be writable too. `None` while the file does
+/// not exist, since `connect` creates it.
+async fn check_config_writable(path: &Path) -> Option<Check> {
+    const NAME: &str = "config writable";
+    let shown = path.display();
+    let opened = tokio::fs::OpenOptions::new().append(true).open(path).await;
+    let denied = |what: String| {
+        Check::fail(
+            NAME,
+            format!("{what} is not writable; revoked servers cannot be removed from {shown}"),
+            format!("run as root, or `chown` {what} to the user the daemon runs as"),
+        )
+    };
+    match opened {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => return None,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            return Some(denied(shown.to_string()));
+        }
+        Err(e) => {
+            return Some(Check::fail(
+                NAME,
+                format!("cannot open {shown}: {e}"),
+                "check the file system is mounted read-write",
+            ));
+        }
+    }
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut probe = path.as_os_str().to_os_string();
+    probe.push(".doctor");
+    let created = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .await;
+    Some(match created {
+        Ok(_) => {
+            let _ = tokio::fs::remove_file(&probe).await;
+            Check::pass(NAME, format!("{shown} and its directory are writable"))
+        }
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => denied(dir.display().to_string()),
+        Err(e) => Check::fail(
+            NAME,
+            format!("cannot write in {}: {e}", dir.display()),
+            "check the file system is mounted read-write",
+        ),
+    })
+}
+
+/// The configured backend, or with `auto`, the kernel module and failing
+/// that the userspace command the daemon would fall back to.
+#[cfg(target_os = "linux")]
+fn check_wireguard(wireguard: &WireguardConfig) -> Check {
+    const NAME: &str = "wireguard";
+    let kernel = match wireguard.backend {
+        Backend::Userspace => return check_userspace(&wireguard.userspace_command),
+        Backend::WgQuick => return check_wg_quick(),
+        Backend::Kernel | Backend::Auto => check_kernel(),
+    };
+    if wireguard.backend == Backend::Kernel || kernel.verdict == Verdict::Pass {
+        return kernel;
+    }
+    let userspace = check_userspace(&wireguard.userspace_command);
+    if userspace.verdict != Verdict::Pass {
+        return Check::fail(
+            NAME,
+            format!("{}, and {}", kernel.detail, userspace.detail),
+            format!("{}; or {}", kernel.fix.unwrap_or_default(), userspace.fix.unwrap_or_default()),
+        );
+    }
+    Check::warn(
+        NAME,
+        format!("{}; falling back to {}", kernel.detail, userspace.detail),
+        "userspace WireGuard is slower; load the kernel module, or set \
+         `backend = \"userspace\"` under [wireguard] to silence this",
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn check_kernel() -> Check {
+    const NAME: &str = "wireguard";
+    match wireguard_uapi::WgSocket::connect() {
+        Ok(_) => Check::pass(NAME, "kernel support available"),
+        Err(e) if Path::new("/sys/module/wireguard").exists() => Check::fail(
+            NAME,
+            format!("module loaded but not answering: {e}"),
+            "check `dmesg` for WireGuard errors",
+        ),
+        Err(_) => Check::fail(
+            NAME,
+            "kernel module not loaded",
+            "run `modprobe wireguard`; it needs Linux 5.6 or later, or wireguard-dkms",
+        ),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check_userspace(command: &[String]) -> Check {
+    const NAME: &str = "wireguard";
+    let Some(program) = command.first() else {
+        return Check::fail(
+            NAME,
+            "userspace_command is empty",
+            "set wireguard.userspace_command, e.g. [\"boringtun-cli\"]",
+        );
+    };
+    match find_program(program) {
+        Some(path) => Check::pass(NAME, format!("userspace {}", path.display())),
+        None => Check::fail(
+            NAME,
+            format!("userspace command {program} not found"),
+            "install boringtun-cli or wireguard-go, or set wireguard.userspace_command",
+        ),
+    }
+}
+
+/// `wg-quick` and the `wg` it drives, both from wireguard-tools.
+#[cfg(unix)]
+fn check_wg_quick() -> Check {
+    const NAME: &str = "wireguard";
+    match (find_program("wg-quick"), find_program("wg")) {
+        (Some(path), Some(_)) => Check::pass(NAME, format!("wg-quick via {}", path.display())),
+        _ => Check::fail(
+            NAME,
+            "wg-quick or wg not found",
+            "install wireguard-tools, which provides both",
+        ),
+    }
+}
+
+/// `program` itself if it is a path, otherwise the first match on `PATH`.
+#[cfg(unix)]
+fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(PathBuf::from(program)).filter(|p| p.is_file());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|p| p.is_file())
+}
+
+/// FreeBSD has the kernel driver, configured through `wg(8)`, and
+/// `wg-quick`.
+#[cfg(target_os = "freebsd")]
+fn check_wireguard(wireguard: &WireguardConfig) -> Check {
+    const NAME: &str = "wireguard";
+    match wireguard.backend {
+        Backend::Userspace => {
+            return Check::fail(
+                NAME,
+                "the userspace backend is only supported on Linux",
+                "remove `backend = \"userspace\"` under [wireguard] to use if_wg",
+            );
+        }
+        Backend::WgQuick => return check_wg_quick(),
+        Backend::Auto | Backend::Kernel => {}
+    }
+    match find_program("wg") {
+        Some(path) => Check::pass(NAME, format!("if_wg via {}", path.display())),
+        None => Check::fail(
+            NAME,
+            "wg(8) not found",
+            "run `pkg install wireguard-tools`; if_wg needs FreeBSD 13.2 or later",
+        ),
+    }
+}
+
+/// Elsewhere only `wg-quick` can manage interfaces.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "freebsd"))))]
+fn check_wireguard(wireguard: &WireguardConfig) -> Check {
+    match wireguard.backend {
+        Backend::Auto | Backend::WgQuick => check_wg_quick(),
+        Backend::Kernel | Backend::Userspace => Check::fail(
+            "wireguard",
+            "only the wg-quick backend is supported on this platform",
+            "set `backend = \"wg-quick\"` under [wireguard]",
+        ),
+    }
+}
+
+#[cfg(not(unix))]
+fn check_wireguard(_wireguard: &WireguardConfig) -> Check {
+    Check::fail(
+        "wireguard",
+        "interfaces are only managed on Unix hosts",
+        "run the daemon on a Linux, FreeBSD or other Unix host",
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn check_net_admin() -> Check {
+    const NAME: &str = "permissions";
+    let status = match std::fs::read_to_string("/proc/self/status") {
+        Ok(status) => status,
+        Err(e) => {
+            return Check::warn(
+                NAME,
+                format!("cannot read capabilities: {e}"),
+                "make sure /proc is mounted",
+            );
+        }
+    };
+    match has_capability(&status, CAP_NET_ADMIN) {
+        Some(true) => Check::pass(NAME, "CAP_NET_ADMIN held"),
+        Some(false) => Check::fail(
+            NAME,
+            "CAP_NET_ADMIN missing; interfaces cannot be changed",
+            "run as root, or set AmbientCapabilities=CAP_NET_ADMIN in the systemd unit",
+        ),
+        None => Check::warn(
+            NAME,
+            "capabilities not reported by the kernel",
+            "run as root to be sure",
+        ),
+    }
+}
+
+/// Outside Linux there are no capabilities to grant; only root may change
+/// interfaces.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn check_net_admin() -> Check {
+    const NAME: &str = "permissions";
+    // SAFETY: geteuid has no preconditions.
+    if unsafe { libc::geteuid() } == 0 {
+        Check::pass(NAME, "running as root")
+    } else {
+        Check::fail(
+            NAME,
+            "not running as root; interfaces cannot be changed",
+            "run the daemon as root",
+        )
+    }
+}
+
+#[cfg(not(unix))]
+fn check_net_admin() -> Check {
+    Check::fail(
+        "permissions",
+        "interfaces are only managed on Unix hosts",
+        "run the daemon on a Linux, FreeBSD or other Unix host",
+    )
+}
+
+/// Whether the effective set in a `/proc/<pid>/status` holds `cap`; `None`
+/// if the set is missing or malformed.
+fn has_capability(status: &str, cap: u32) -> Option<bool> {
+    let hex = status.lines().find_map(|l| l.strip_prefix("CapEff:"))?;
+    let set = u64::from_str_radix(hex.trim(), 16).ok()?;
+    Some(set & (1 << cap) != 0)
+}
+
+async fn check_apis(config: &DaemonToml) -> Vec<Check> {
+    let proxy = config.proxy.as_ref();
+    let http = HttpConfig {
+        connect_timeout_secs: PROBE_TIMEOUT_SECS,
+        request_timeout_secs: PROBE_TIMEOUT_SECS,
+        ..config.http
+    };
+    let client = match api::build_client(proxy, &http) {
+        Ok(client) => client,
+        Err(e) => {
+            return vec![Check::fail(
+                "proxy",
+                e.to_string(),
+                "correct the [proxy] url in the config file",
+            )];
+        }
+    };
+    let probes = config.servers.iter().map(|entry| {
+        let (client, http) = (&client, &http);
+        async move {
+            let result = if entry.has_custom_trust() {
+                match api::entry_client(proxy, http, entry).await {
+                    Ok(client) => api::check_health(&client, entry).await,
+                    Err(e) => Err(e),
+                }
+            } else {
+                api::check_health(client, entry).await
+            };
+            let name = format!("api {}", entry.api_host);
+            match result {
+                Ok(()) => Check::pass(name, "reachable"),
+                Err(e) => Check::fail(
+                    name,
+                    e.to_string(),
+                    "check the URL, DNS, firewall and any [proxy] settings",
+                ),
+            }
+        }
+    });
+    futures::future::join_all(probes).await
+}
+
+/// Whether each server's WireGuard port is free for it. Ports are known only
+/// from the configs the daemon has cached.
+async fn check_ports<P: Platform>(config: &DaemonToml, cache: &ConfigCache) -> Vec<Check> {
+    let cached = cache.load_all().await;
+    let interfaces = status::interfaces_by_token::<P>(
+        cache,
+        &config.interfaces.all_prefixes(),
+        &config.named_interfaces(),
+    )
+    .await;
+    let mut ports: HashMap<i32, Vec<&str>> = HashMap::new();
+    let mut checks = Vec::new();
+    for entry in &config.servers {
+        let Some(desired) = cached.get(&entry.api_token) else {
+            checks.push(Check::warn(
+                format!("port {}", entry.api_host),
+                "unknown until the daemon first fetches this server",
+                "start the daemon, then run doctor again",
+            ));
+            continue;
+        };
+        let port = desired.server.listen_port;
+        let name = format!("port {port}");
+        ports.entry(port).or_default().push(&desired.server.name);
+
+        let interface = interfaces
+            .as_ref()
+            .ok()
+            .and_then(|m| m.get(&entry.api_token));
+        if let Some(interface) = interface
+            && P::device_state(interface)
+                .await
+                .is_ok_and(|d| i32::from(d.listen_port) == port)
+        {
+            checks.push(Check::pass(name, format!("held by {interface}")));
+            continue;
+        }
+        checks.push(match udp_port_free(port) {
+            Ok(()) => Check::pass(name, "free"),
+            Err(e) if e.kind() == ErrorKind::AddrInUse && interfaces.is_err() => Check::warn(
+                name,
+                "in use, perhaps by this daemon's own interface",
+                "run as root so doctor can see the interfaces",
+            ),
+            Err(e) if e.kind() == ErrorKind::AddrInUse => Check::fail(
+                name,
+                format!(
+                    "in use by another program; {} cannot listen",
+                    desired.server.name
+                ),
+                format!(
+                    "find it with `ss -ulpn 'sport = :{port}'`, or change the server's listen port"
+                ),
+            ),
+            Err(e) => Check::warn(name, format!("cannot check: {e}"), "check the port by hand"),
+        });
+    }
+
+    let mut shared: Vec<_> = ports.into_iter().filter(|(_, s)| s.len() > 1).collect();
+    shared.sort();
+    for (port, servers) in shared {
+        checks.push(Check::fail(
+            format!("port {port}"),
+            format!("shared by {}", servers.join(", ")),
+            "give each server on this host its own listen port",
+        ));
+    }
+    checks
+}
+
+/// Bind `port` on every address, as WireGuard does, and release it.
+fn udp_port_free(port: i32) -> std::io::Result<()> {
+    let port = u16::try_from(port).map_err(|_| std::io::Error::from(ErrorKind::InvalidInput))?;
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+    match UdpSocket::bind((Ipv6Addr::UNSPECIFIED, port)) {
+        // Hosts without IPv6 cannot conflict on it.
+        Err(e) if e.kind() == ErrorKind::AddrNotAvailable => Ok(()),
+        result => result.map(drop),
+    }
+}
+
+/// Whether no check failed.
+pub fn healthy(checks: &[Check]) -> bool {
+    checks.iter().all(|c| c.verdict != Verdict::Fail)
+}
+
+/// One line per check, with the fix indented below it.
+pub fn render(checks: &[Check]) -> String {
+    let width = checks
+        .iter()
+        .map(|c| c.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for check in checks {
+        let verdict = match check.verdict {
+            Verdict::Pass => "PASS",
+            Verdict::Warn => "WARN",
+            Verdict::Fail => "FAIL",
+        };
+        writeln!(out, "{verdict}  {:<width$}  {}", check.name, check.detail).unwrap();
+        if let Some(fix) = &check.fix {
+            writeln!(out, "      {:<width$}  fix: {fix}", "").unwrap();
+        }
+    }
+    let failed = checks.iter().filter(|c| c.verdict == Verdict::Fail).count();
+    match failed {
+        0 => out.push_str("\nall checks passed\n"),
+        1 => out.push_str("\n1 check failed\n"),
+        n => writeln!(out, "\n{n} checks failed").unwrap(),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const STATUS: &str = "Name:\twirewarden\nCapInh:\t0000000000000000\n";
+
+    #[test_case("CapEff:\t000001ffffffffff", Some(true) ; "root")]
+    #[test_case("CapEff:\t0000000000001000", Some(true) ; "net admin only")]
+    #[test_case("CapEff:\t0000000000002000", Some(false) ; "net raw only")]
+    #[test_case("CapEff:\t0000000000000000", Some(false) ; "unprivileged")]
+    #[test_case("CapEff:\tzz", None ; "malformed")]
+    #[test_case("", None ; "missing")]
+    fn capability(line: &str, expected: Option<bool>) {
+        let status = format!("{STATUS}{line}\n");
+        assert_eq!(has_capability(&status, CAP_NET_ADMIN), expected);
+    }
+
+    #[tokio::test]
+    async fn missing_config_is_a_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let (check, config) = check_config(&dir.path().join("daemon.toml")).await;
+        assert_eq!(check.verdict, Verdict::Warn);
+        assert!(config.is_some_and(|c| c.servers.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn config_writability() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.toml");
+        assert!(check_config_writable(&path).await.is_none());
+
+        tokio::fs::write(&path, "").await.unwrap();
+        let check = check_config_writable(&path).await.unwrap();
+        assert_eq!(check.verdict, Verdict::Pass);
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"");
+        assert!(!dir.path().join("daemon.toml.doctor").exists());
+
+        // SAFETY: geteuid has no preconditions.
+        if unsafe { libc::geteuid() } != 0 {
+            use std::os::unix::fs::PermissionsExt;
+            let read_only = std::fs::Permissions::from_mode(0o500);
+            std::fs::set_permissions(dir.path(), read_only).unwrap();
+            let check = check_config_writable(&path).await.unwrap();
+            std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o700)).unwrap();
+            assert_eq!(check.verdict, Verdict::Fail, "read-only directory passed");
+        }
+    }
+
+    #[tokio::test]
+    async fn simulated_preflight_skips_the_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let checks = preflight(&dir.path().join("daemon.toml"), true).await;
+        let names: Vec<_> = checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["config"]);
+        assert!(healthy(&checks));
+    }
+
+    #[tokio::test]
+    async fn malformed_config_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.toml");
+        tokio::fs::write(&path, "servers = 3").await.unwrap();
+        let (check, config) = check_config(&path).await;
+        assert_eq!(check.verdict, Verdict::Fail);
+        assert!(config.is_none());
+    }
+
+    #[test_case("sh", true ; "on path")]
+    #[test_case("/bin/sh", true ; "absolute")]
+    #[test_case("wirewarden-no-such-program", false ; "missing")]
+    #[test_case("/nonexistent/sh", false ; "missing path")]
+    #[cfg(target_os = "linux")]
+    fn program_lookup(program: &str, found: bool) {
+        assert_eq!(find_program(program).is_some(), found);
+    }
+
+    #[test]
+    fn port_in_use_is_detected() {
+        let held = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let port = held.local_addr().unwrap().port();
+        let err = udp_port_free(i32::from(port)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AddrInUse);
+    }
+
+    #[test]
+    fn render_shows_fixes_and_counts_failures() {
+        let checks = [
+            Check::pass("config", "2 servers"),
+            Check::fail("permissions", "CAP_NET_ADMIN missing", "run as root"),
+        ];
+        assert_eq!(
+            render(&checks),
+            "PASS  config       2 servers\n\
+             FAIL  permissions  CAP_NET_ADMIN missing\n\
+             \x20                  fix: run as root\n\
+             \n1 check failed\n"
+        );
+        assert!(!healthy(&checks));
+    }
+}
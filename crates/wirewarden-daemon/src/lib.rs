@@ -13,6 +13,18 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 pub mod api;
+pub mod cache;
 pub mod config;
+pub mod dns;
+pub mod doctor;
 pub mod netlink;
+pub mod plan;
+pub mod privsep;
 pub mod reconcile;
+pub mod service;
+pub mod status;
+pub mod systemd;
+pub mod telemetry;
+pub mod tls;
+pub mod update;
+pub mod watch;
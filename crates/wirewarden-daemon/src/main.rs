@@ -12,27 +12,77 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use std::path::PathBuf;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use clap::{Parser, Subcommand};
 use tracing::{debug, error, info, warn};
-use wirewarden_daemon::{config, netlink, reconcile};
-
-fn init_tracing() {
-    use tracing_subscriber::{EnvFilter, fmt};
-
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry, fmt, reload};
+use uuid::Uuid;
+use wirewarden_daemon::{
+    api, cache, config, dns, doctor, netlink, plan, privsep, reconcile, service, status,
+    systemd, telemetry, update, watch,
+};
+
+/// Filters SIGUSR1 steps through, raising only the daemon's own verbosity.
+const LOG_LEVELS: [&str; 3] = [
+    "info",
+    "info,wirewarden_daemon=debug",
+    "info,wirewarden_daemon=trace",
+];
+
+type LogFilter = reload::Handle<EnvFilter, Registry>;
+
+/// Log at `default_level` unless `RUST_LOG` says otherwise. The returned
+/// handle swaps the filter at runtime.
+fn init_tracing(default_level: &str) -> LogFilter {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_level));
+    let (filter, handle) = reload::Layer::new(filter);
 
     #[cfg(distribute)]
     {
-        fmt().json().with_env_filter(filter).init();
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt::layer().json())
+            .init();
     }
 
     #[cfg(not(distribute))]
     {
-        fmt().pretty().with_env_filter(filter).init();
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt::layer().pretty())
+            .init();
     }
+
+    handle
+}
+
+/// Step through [`LOG_LEVELS`] on each SIGUSR1, so a misbehaving daemon can
+/// be debugged without a restart. A no-op where SIGUSR1 does not exist.
+fn spawn_log_level_cycler(log_filter: LogFilter) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut usr1 = signal(SignalKind::user_defined1())?;
+        tokio::spawn(async move {
+            let mut level = 0;
+            while usr1.recv().await.is_some() {
+                level = (level + 1) % LOG_LEVELS.len();
+                match log_filter.reload(EnvFilter::new(LOG_LEVELS[level])) {
+                    Ok(()) => warn!(filter = LOG_LEVELS[level], "log filter changed"),
+                    Err(e) => error!(error = %e, "failed to change log filter"),
+                }
+            }
+        });
+    }
+    #[cfg(not(unix))]
+    drop(log_filter);
+    Ok(())
 }
 
 #[derive(Debug, Parser)]
@@ -52,9 +102,151 @@ enum Command {
         #[arg(short, long, default_value = "/etc/wirewarden/daemon.toml")]
         config: PathBuf,
 
-        /// Polling interval in seconds
+        /// Polling interval in seconds, for servers without their own
+        #[arg(short, long, default_value_t = 30)]
+        interval: u64,
+
+        /// Directory for last-applied configs, used while the API is
+        /// unreachable
+        #[arg(long, default_value = "/var/lib/wirewarden")]
+        state_dir: PathBuf,
+
+        /// Apply configs to in-memory interfaces and log each change instead
+        /// of touching the host; needs no root or WireGuard
+        #[arg(long)]
+        simulate: bool,
+
+        /// Once started as root, hand the state directory and config file
+        /// to this user and run as it, keeping only CAP_NET_ADMIN
+        #[arg(long)]
+        user: Option<String>,
+    },
+
+    /// Remove a server connection
+    Disconnect {
+        /// API server base URL of the entry to remove
+        #[arg(long, required_unless_present = "api_token", conflicts_with = "api_token")]
+        api_host: Option<String>,
+
+        /// API token of the entry to remove
+        #[arg(long)]
+        api_token: Option<String>,
+
+        /// Remove the server's interface now instead of leaving it to the
+        /// running daemon
+        #[arg(long)]
+        teardown: bool,
+
+        /// The daemon's state directory, used to find the interface
+        #[arg(long, default_value = "/var/lib/wirewarden")]
+        state_dir: PathBuf,
+
+        /// Path to the configuration file
+        #[arg(short, long, default_value = "/etc/wirewarden/daemon.toml")]
+        config: PathBuf,
+    },
+
+    /// Show each configured server, its interface and how its last cycle went
+    Status {
+        /// Path to the configuration file
+        #[arg(short, long, default_value = "/etc/wirewarden/daemon.toml")]
+        config: PathBuf,
+
+        /// The daemon's state directory
+        #[arg(long, default_value = "/var/lib/wirewarden")]
+        state_dir: PathBuf,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List configured servers, their interfaces and whether their APIs answer
+    List {
+        /// Path to the configuration file
+        #[arg(short, long, default_value = "/etc/wirewarden/daemon.toml")]
+        config: PathBuf,
+
+        /// The daemon's state directory, used to match interfaces to servers
+        #[arg(long, default_value = "/var/lib/wirewarden")]
+        state_dir: PathBuf,
+    },
+
+    /// Show what the next cycle would change on each interface, without
+    /// applying it
+    Plan {
+        /// Path to the configuration file
+        #[arg(short, long, default_value = "/etc/wirewarden/daemon.toml")]
+        config: PathBuf,
+
+        /// The daemon's state directory, for servers whose fetch fails
+        #[arg(long, default_value = "/var/lib/wirewarden")]
+        state_dir: PathBuf,
+    },
+
+    /// Check that this host can run the daemon: WireGuard support,
+    /// permissions, the config file, each API and each listen port
+    Doctor {
+        /// Path to the configuration file
+        #[arg(short, long, default_value = "/etc/wirewarden/daemon.toml")]
+        config: PathBuf,
+
+        /// The daemon's state directory, for each server's listen port
+        #[arg(long, default_value = "/var/lib/wirewarden")]
+        state_dir: PathBuf,
+    },
+
+    /// Install the daemon as a hardened systemd service, enable it and
+    /// start it
+    InstallService {
+        /// Path to the configuration file
+        #[arg(short, long, default_value = "/etc/wirewarden/daemon.toml")]
+        config: PathBuf,
+
+        /// Polling interval in seconds, for servers without their own
         #[arg(short, long, default_value_t = 30)]
         interval: u64,
+
+        /// Directory for last-applied configs
+        #[arg(long, default_value = "/var/lib/wirewarden")]
+        state_dir: PathBuf,
+
+        /// Directory to write the unit file to
+        #[arg(long, default_value = service::UNIT_DIR)]
+        unit_dir: PathBuf,
+
+        /// Enable the service without starting it
+        #[arg(long)]
+        no_start: bool,
+    },
+
+    /// Stop and disable the service and remove its unit file, keeping the
+    /// config and state
+    UninstallService {
+        /// Directory the unit file was written to
+        #[arg(long, default_value = service::UNIT_DIR)]
+        unit_dir: PathBuf,
+    },
+
+    /// Install the newest release from `[update] release_url` and restart
+    /// the service onto it
+    SelfUpdate {
+        /// Path to the configuration file
+        #[arg(short, long, default_value = "/etc/wirewarden/daemon.toml")]
+        config: PathBuf,
+
+        /// Only report whether a newer release is available
+        #[arg(long)]
+        check: bool,
+
+        /// Install the release even if it is not newer than this build
+        #[arg(long)]
+        force: bool,
+
+        /// Leave the running service on the old binary until it next
+        /// restarts
+        #[arg(long)]
+        no_restart: bool,
     },
 
     /// Register a new server connection
@@ -67,39 +259,292 @@ enum Command {
         #[arg(long)]
         api_token: String,
 
+        /// Keep the server's endpoint updated with this host's public IP
+        #[arg(long)]
+        auto_endpoint: bool,
+
+        /// URL that returns this host's public IP as plain text; implies
+        /// --auto-endpoint. Without it the API's observed address is used.
+        #[arg(long)]
+        endpoint_reflector: Option<String>,
+
+        /// Fetch configs over gRPC from this URL (the API's GRPC_BIND_ADDR)
+        /// instead of the REST API
+        #[arg(long)]
+        grpc_endpoint: Option<String>,
+
+        /// PEM bundle of extra CAs to trust for this API
+        #[arg(long)]
+        ca_cert: Option<PathBuf>,
+
+        /// SHA-256 fingerprint of the API's certificate; trusts exactly that
+        /// certificate, e.g. a self-signed one
+        #[arg(long)]
+        cert_sha256: Option<String>,
+
+        /// Poll this server every this many seconds instead of the daemon's
+        /// default interval
+        #[arg(long)]
+        interval_secs: Option<u64>,
+
+        /// Base64 key the API signs configs with, as printed in the connect
+        /// command; unsigned configs are then refused
+        #[arg(long)]
+        config_key: Option<String>,
+
+        /// ID of the server the token belongs to, as printed in the connect
+        /// command; configs for other servers are then refused
+        #[arg(long)]
+        server_id: Option<Uuid>,
+
+        /// Name the server's interface this, e.g. wg-home, instead of
+        /// allocating one
+        #[arg(long)]
+        interface: Option<String>,
+
         /// Path to the configuration file
         #[arg(short, long, default_value = "/etc/wirewarden/daemon.toml")]
         config: PathBuf,
     },
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    init_tracing();
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    // Keep `status`, `list`, `plan` and `doctor` output clean for scripts.
+    let log_filter = init_tracing(match cli.command {
+        Command::Status { .. }
+        | Command::List { .. }
+        | Command::Plan { .. }
+        | Command::Doctor { .. } => "warn",
+        _ => "info",
+    });
+
+    // Capabilities are per thread, so root goes before the runtime starts
+    // its workers.
+    if let Command::Daemon {
+        config,
+        state_dir,
+        user: Some(user),
+        ..
+    } = &cli.command
+    {
+        drop_privileges(user, config, state_dir)?;
+    }
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let restart = runtime.block_on(run(cli.command, log_filter))?;
+    // The daemon's tasks, sockets and watchers go with the runtime, before
+    // the new release takes over the process.
+    drop(runtime);
+    match restart {
+        Some(exe) => Err(update::restart(&exe).into()),
+        None => Ok(()),
+    }
+}
 
-    match cli.command {
-        Command::Daemon { config, interval } => run_daemon(config, interval).await,
+/// Hand the daemon's files to `user` and switch to it.
+fn drop_privileges(
+    user: &str,
+    config_path: &Path,
+    state_dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let account = privsep::lookup(user)?;
+    std::fs::create_dir_all(state_dir)?;
+    privsep::hand_over(state_dir, account)?;
+    // Saves write a temporary file beside the config and rename it over,
+    // which needs the directory. Later saves, even by root, keep the owner.
+    let config_dir = match config_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(config_dir)?;
+    privsep::hand_over_dir(config_dir, account)?;
+    privsep::hand_over(config_path, account)?;
+    privsep::hand_over(&config::backup_path(config_path), account)?;
+    privsep::drop_to(account)?;
+    Ok(())
+}
+
+/// Run `command`, returning the executable to restart onto when the daemon
+/// installed a release.
+async fn run(
+    command: Command,
+    log_filter: LogFilter,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    match command {
+        Command::Daemon {
+            config,
+            interval,
+            state_dir,
+            simulate: false,
+            user,
+        } => {
+            preflight(&config, false).await;
+            // After dropping privileges the binary is not writable, and a
+            // restarted process could not drop them again.
+            let self_update = user.is_none();
+            return run_daemon::<netlink::CurrentPlatform>(
+                config,
+                interval,
+                state_dir,
+                log_filter,
+                self_update,
+            )
+            .await;
+        }
+        Command::Daemon {
+            config,
+            interval,
+            state_dir,
+            simulate: true,
+            ..
+        } => {
+            warn!("simulating: no interfaces on this host will be changed");
+            preflight(&config, true).await;
+            return run_daemon::<netlink::SimPlatform>(
+                config, interval, state_dir, log_filter, false,
+            )
+            .await;
+        }
+        Command::Status {
+            config,
+            state_dir,
+            json,
+        } => run_status(config, state_dir, json).await,
+        Command::List { config, state_dir } => run_list(config, state_dir).await,
+        Command::Plan { config, state_dir } => run_plan(config, state_dir).await,
+        Command::Doctor { config, state_dir } => run_doctor(config, state_dir).await,
+        Command::Disconnect {
+            api_host,
+            api_token,
+            teardown,
+            state_dir,
+            config,
+        } => {
+            let selector = match (api_host, api_token) {
+                (_, Some(token)) => config::EntrySelector::ApiToken(token),
+                (Some(host), None) => config::EntrySelector::ApiHost(host),
+                (None, None) => unreachable!("clap requires one of them"),
+            };
+            run_disconnect(config, selector, teardown, state_dir).await
+        }
         Command::Connect {
             api_host,
             api_token,
+            auto_endpoint,
+            endpoint_reflector,
+            grpc_endpoint,
+            ca_cert,
+            cert_sha256,
+            interval_secs,
+            config_key,
+            server_id,
+            interface,
+            config,
+        } => {
+            let entry = config::ServerEntry {
+                api_host,
+                api_token,
+                auto_endpoint: auto_endpoint || endpoint_reflector.is_some(),
+                endpoint_reflector,
+                grpc_endpoint,
+                ca_cert,
+                cert_sha256,
+                interval_secs,
+                config_key,
+                server_id,
+                interface,
+            };
+            run_connect(config, entry).await
+        }
+        Command::InstallService {
+            config,
+            interval,
+            state_dir,
+            unit_dir,
+            no_start,
+        } => run_install_service(config, interval, state_dir, unit_dir, !no_start).await,
+        Command::SelfUpdate {
             config,
-        } => run_connect(config, api_host, api_token).await,
+            check,
+            force,
+            no_restart,
+        } => run_self_update(config, check, force, !no_restart).await,
+        Command::UninstallService { unit_dir } => {
+            if service::uninstall(&unit_dir).await? {
+                info!("removed {}", service::UNIT_NAME);
+            } else {
+                warn!("{} is not installed", service::UNIT_NAME);
+            }
+            Ok(())
+        }
+    }?;
+    Ok(None)
+}
+
+async fn run_install_service(
+    config_path: PathBuf,
+    interval: u64,
+    state_dir: PathBuf,
+    unit_dir: PathBuf,
+    start: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // The unit's sandbox is cut to the backend the daemon will use.
+    let daemon_config = config::load(&config_path).await?;
+    let options = service::UnitOptions {
+        exe: std::env::current_exe()?,
+        config: std::path::absolute(&config_path)?,
+        state_dir: std::path::absolute(&state_dir)?,
+        interval,
+        backend: daemon_config.wireguard.backend,
+        self_update: daemon_config.update.enabled,
+        dns: daemon_config.dns.enabled && daemon_config.dns.port < 1024,
+    };
+    service::install(&unit_dir, &options, start).await?;
+    if daemon_config.servers.is_empty() {
+        warn!("no servers configured yet; add one with `wirewarden connect`");
     }
+    Ok(())
 }
 
-async fn run_daemon(
+/// Exit with what to do about it if this host cannot run the daemon, rather
+/// than failing mid-cycle with opaque netlink errors.
+async fn preflight(config_path: &Path, simulate: bool) {
+    let checks = doctor::preflight(config_path, simulate).await;
+    for check in checks.iter().filter(|c| c.verdict == doctor::Verdict::Warn) {
+        warn!(check = %check.name, fix = check.fix.as_deref(), "{}", check.detail);
+    }
+    if !doctor::healthy(&checks) {
+        let failed: Vec<_> = checks
+            .into_iter()
+            .filter(|c| c.verdict == doctor::Verdict::Fail)
+            .collect();
+        eprint!("{}", doctor::render(&failed));
+        std::process::exit(1);
+    }
+}
+
+async fn run_daemon<P: netlink::Platform>(
     config_path: PathBuf,
     interval_secs: u64,
-) -> Result<(), Box<dyn std::error::Error>> {
+    state_dir: PathBuf,
+    log_filter: LogFilter,
+    self_update: bool,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
     info!(
         config = %config_path.display(),
         interval = interval_secs,
+        state_dir = %state_dir.display(),
         version = env!("GIT_VERSION"),
         "starting wirewarden daemon"
     );
 
     let mut daemon_config = config::load(&config_path).await?;
+    // Fixed for the life of the process; the simulator ignores it.
+    let backend = netlink::select_backend(&daemon_config.wireguard);
+    info!(?backend, "selected WireGuard backend");
 
     if daemon_config.servers.is_empty() {
         warn!("no servers configured — use `wirewarden connect` to add one");
@@ -110,33 +555,123 @@ async fn run_daemon(
         );
     }
 
-    let client = reqwest::Client::new();
+    let mut client = api::build_client(daemon_config.proxy.as_ref(), &daemon_config.http)?;
     let interval = Duration::from_secs(interval_secs);
-    let mut reconcile_state = reconcile::ReconcileState::default();
+    let status_path = status::path(&state_dir);
+    let config_cache = cache::ConfigCache::new(state_dir);
+    let mut reconcile_state = reconcile::ReconcileState::with_cache(config_cache)
+        .await
+        .with_interval(interval);
 
     let mut shutdown = std::pin::pin!(shutdown_signal());
+    let mut reload = ReloadSignal::new()?;
+    spawn_log_level_cycler(log_filter)?;
+    let mut watcher = match watch::ConfigWatcher::new(&config_path) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            warn!(error = %e, "cannot watch config file, reloading once per cycle");
+            None
+        }
+    };
+
+    let notifier = systemd::Notifier::from_env();
+
+    // Resolved now: once the binary is replaced, the running one's path
+    // names a deleted file.
+    let exe = if self_update {
+        Some(std::env::current_exe()?)
+    } else {
+        if daemon_config.update.enabled {
+            warn!("self-update is disabled when running as another user");
+        }
+        None
+    };
+    let mut update_schedule = update::UpdateSchedule::default();
+    let mut resolvers = dns::Resolvers::default();
+    let mut telemetry_schedule = telemetry::TelemetrySchedule::default();
 
     info!("entering main poll loop");
     let mut cycle: u64 = 0;
+    // Set once a release is installed; the loop then stops so the process
+    // can shut down before the new binary replaces it.
+    let mut restart = None;
 
     loop {
         cycle += 1;
         debug!(cycle, "poll cycle start");
 
-        reconcile::reconcile_all::<netlink::CurrentPlatform>(
+        reconcile::reconcile_all::<P>(
             &client,
             &config_path,
             &mut daemon_config,
             &mut reconcile_state,
         )
         .await;
+        // After reconciling, so new servers' addresses exist to bind to.
+        resolvers
+            .sync(&daemon_config.dns, reconcile_state.applied_configs())
+            .await;
+        if telemetry_schedule.due(&daemon_config.telemetry, Instant::now()) {
+            telemetry_schedule.reported(Instant::now());
+            telemetry::report_all::<P>(&client, &daemon_config, &reconcile_state).await;
+        }
+        let daemon_status = reconcile_state.status(&daemon_config);
+        if let Err(e) = status::save(&status_path, &daemon_status).await {
+            warn!(error = %e, "failed to write status file");
+        }
+
+        // Ready once the first reconcile has run. Servers the API cannot
+        // reach come up from the cache, or report their failure in STATUS,
+        // rather than holding up boot.
+        notifier.status(&systemd::summarize(&daemon_status));
+        if cycle == 1 {
+            notifier.ready();
+        }
+        notifier.watchdog();
+
+        if let Some(exe) = &exe
+            && update_schedule.due(&daemon_config.update, api::outdated(), Instant::now())
+        {
+            update_schedule.checked(Instant::now());
+            if let Some(version) = install_update(&client, &daemon_config.update, exe).await {
+                info!(%version, "restarting onto the new release");
+                notifier.status(&format!("restarting onto {version}"));
+                restart = Some(exe.clone());
+                break;
+            }
+        }
 
-        debug!(cycle, interval = interval_secs, "sleeping until next cycle");
+        // Wake for the next server due; with none configured, check the
+        // config again after the default interval. Wake sooner if the
+        // watchdog needs a ping or a usage report is due before then.
+        let mut wake = reconcile_state
+            .next_due(&daemon_config)
+            .unwrap_or_else(|| Instant::now() + interval);
+        if let Some(report) = telemetry_schedule.next_due(&daemon_config.telemetry, Instant::now())
+        {
+            wake = wake.min(report);
+        }
+        if let Some(ping) = notifier.watchdog_interval() {
+            wake = wake.min(Instant::now() + ping);
+        }
+        debug!(
+            cycle,
+            sleep_ms = wake.saturating_duration_since(Instant::now()).as_millis() as u64,
+            "sleeping until next server is due"
+        );
 
         tokio::select! {
-            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::time::sleep_until(wake.into()) => {}
+            _ = config_changed(&mut watcher) => {
+                info!("config file changed, reloading");
+            }
+            _ = reload.recv() => {
+                info!("received SIGHUP, reloading config and reconciling now");
+                reconcile_state.poll_now();
+            }
             _ = &mut shutdown => {
                 info!("received shutdown signal");
+                notifier.stopping();
                 break;
             }
         }
@@ -154,16 +689,63 @@ async fn run_daemon(
                 } else {
                     debug!(server_count = new_count, "config reloaded, no changes");
                 }
+                if fresh.proxy != daemon_config.proxy || fresh.http != daemon_config.http {
+                    match api::build_client(fresh.proxy.as_ref(), &fresh.http) {
+                        Ok(rebuilt) => {
+                            info!(proxied = fresh.proxy.is_some(), "HTTP settings changed");
+                            client = rebuilt;
+                        }
+                        Err(e) => error!(error = %e, "invalid HTTP settings, keeping previous"),
+                    }
+                }
                 daemon_config = fresh;
             }
             Err(e) => error!(error = %e, "failed to reload config, using previous"),
         }
     }
 
-    let ifaces: Vec<&str> = reconcile_state.interface_names().collect();
-    teardown_interfaces::<netlink::CurrentPlatform>(&ifaces).await;
+    if restart.is_some() {
+        // The new process adopts the interfaces.
+        info!("leaving managed interfaces up for the new release");
+    } else if daemon_config.teardown.on_shutdown() {
+        reconcile::teardown_all::<P>(
+            &client,
+            &daemon_config,
+            &reconcile_state,
+        )
+        .await;
+    } else {
+        info!(policy = ?daemon_config.teardown, "leaving managed interfaces up");
+    }
     info!("shutdown complete");
-    Ok(())
+    Ok(restart)
+}
+
+/// Install a newer release if there is one, returning its version. Failures
+/// are logged and retried at the next scheduled check.
+async fn install_update(
+    client: &reqwest::Client,
+    config: &config::UpdateConfig,
+    exe: &Path,
+) -> Option<wirewarden_types::version::Version> {
+    let release = match update::latest(client, config).await {
+        Ok(release) => release,
+        Err(e) => {
+            warn!(error = %e, "release check failed");
+            return None;
+        }
+    };
+    if !update::is_newer(release.version, update::current_version(), false) {
+        debug!(latest = %release.version, "no newer release");
+        return None;
+    }
+    match update::install(client, config, &release, exe).await {
+        Ok(()) => Some(release.version),
+        Err(e) => {
+            error!(version = %release.version, error = %e, "self-update failed");
+            None
+        }
+    }
 }
 
 async fn shutdown_signal() {
@@ -185,46 +767,255 @@ async fn shutdown_signal() {
     }
 }
 
-async fn teardown_interfaces<P: netlink::Platform>(interfaces: &[&str]) {
-    if interfaces.is_empty() {
-        return;
+/// Wait for the config file to change; never, without a watcher.
+async fn config_changed(watcher: &mut Option<watch::ConfigWatcher>) {
+    match watcher {
+        Some(watcher) => watcher.changed().await,
+        None => std::future::pending().await,
     }
+}
+
+/// Listens for SIGHUP, which asks for an immediate reload and reconcile.
+/// Never fires where SIGHUP does not exist.
+#[derive(Debug)]
+struct ReloadSignal {
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+}
 
-    info!(count = interfaces.len(), "removing managed interfaces");
-    for interface in interfaces {
-        match P::remove_interface(interface).await {
-            Ok(()) => info!(interface = %interface, "removed interface"),
-            Err(e) => warn!(interface = %interface, error = %e, "failed to remove interface"),
+impl ReloadSignal {
+    fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            hangup: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if self.hangup.recv().await.is_some() {
+            return;
         }
+        std::future::pending::<()>().await
     }
 }
 
+async fn run_disconnect(
+    config_path: PathBuf,
+    selector: config::EntrySelector,
+    teardown: bool,
+    state_dir: PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut daemon_config = config::load(&config_path).await?;
+    netlink::select_backend(&daemon_config.wireguard);
+    let entry = config::remove_entry(&mut daemon_config, &selector)?;
+    config::save(&config_path, &daemon_config).await?;
+    info!(api_host = %entry.api_host, "server removed");
+
+    if !teardown {
+        info!("a running daemon removes its interface per the teardown policy");
+        return Ok(());
+    }
+    let cache = cache::ConfigCache::new(state_dir);
+    let prefixes = daemon_config.interfaces.all_prefixes();
+    let names: Vec<&str> = entry.interface.as_deref().into_iter().collect();
+    let removed = reconcile::teardown_server::<netlink::CurrentPlatform>(
+        &cache,
+        &entry.api_token,
+        &prefixes,
+        &names,
+    )
+    .await?;
+    if removed.is_empty() {
+        warn!("no interface found for this server");
+    } else {
+        info!(interfaces = ?removed, "removed interfaces");
+    }
+    Ok(())
+}
+
+async fn run_self_update(
+    config_path: PathBuf,
+    check: bool,
+    force: bool,
+    restart: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let daemon_config = config::load(&config_path).await?;
+    let client = api::build_client(daemon_config.proxy.as_ref(), &daemon_config.http)?;
+    let release = update::latest(&client, &daemon_config.update).await?;
+    let current = env!("GIT_VERSION");
+    if !update::is_newer(release.version, update::current_version(), force) {
+        println!(
+            "up to date: running {current}, latest release is {}",
+            release.version
+        );
+        return Ok(());
+    }
+    if check {
+        println!(
+            "release {} is available, running {current}",
+            release.version
+        );
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()?;
+    update::install(&client, &daemon_config.update, &release, &exe).await?;
+    println!("installed {} at {}", release.version, exe.display());
+    if restart && let Err(e) = service::try_restart().await {
+        warn!(error = %e, "could not restart the service; restart it to run the new release");
+    }
+    Ok(())
+}
+
+async fn run_status(
+    config_path: PathBuf,
+    state_dir: PathBuf,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let daemon_config = config::load(&config_path).await?;
+    netlink::select_backend(&daemon_config.wireguard);
+    let daemon_status = status::load(&status::path(&state_dir)).await?;
+    let report =
+        status::report::<netlink::CurrentPlatform>(&daemon_config, daemon_status.as_ref()).await;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", status::render(&report));
+    }
+    Ok(())
+}
+
+/// How long `list` waits on each API before calling it unreachable.
+const LIST_PROBE_TIMEOUT_SECS: u64 = 5;
+
+async fn run_list(
+    config_path: PathBuf,
+    state_dir: PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let daemon_config = config::load(&config_path).await?;
+    netlink::select_backend(&daemon_config.wireguard);
+    let http = config::HttpConfig {
+        connect_timeout_secs: LIST_PROBE_TIMEOUT_SECS,
+        request_timeout_secs: LIST_PROBE_TIMEOUT_SECS,
+        ..daemon_config.http
+    };
+    let proxy = daemon_config.proxy.as_ref();
+    let client = api::build_client(proxy, &http)?;
+
+    let cache = cache::ConfigCache::new(state_dir);
+    let prefixes = daemon_config.interfaces.all_prefixes();
+    let names = daemon_config.named_interfaces();
+    // Discovery needs CAP_NET_ADMIN; without it the interfaces are unknown.
+    let interfaces =
+        match status::interfaces_by_token::<netlink::CurrentPlatform>(&cache, &prefixes, &names)
+            .await
+        {
+            Ok(interfaces) => interfaces,
+            Err(e) => {
+                warn!(error = %e, "cannot discover interfaces");
+                Default::default()
+            }
+        };
+
+    let probes = daemon_config.servers.iter().map(|entry| {
+        let client = &client;
+        let http = &http;
+        async move {
+            if entry.has_custom_trust() {
+                let client = api::entry_client(proxy, http, entry).await?;
+                api::check_health(&client, entry).await
+            } else {
+                api::check_health(client, entry).await
+            }
+        }
+    });
+    let reachable = futures::future::join_all(probes).await;
+
+    let rows: Vec<status::ListRow> = daemon_config
+        .servers
+        .iter()
+        .zip(reachable)
+        .map(|(entry, api)| status::ListRow {
+            api_host: entry.api_host.clone(),
+            token: status::redact_token(&entry.api_token),
+            interface: interfaces.get(&entry.api_token).cloned(),
+            api: api.map_err(|e| e.to_string()),
+        })
+        .collect();
+    print!("{}", status::render_list(&rows));
+    Ok(())
+}
+
+async fn run_plan(
+    config_path: PathBuf,
+    state_dir: PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let daemon_config = config::load(&config_path).await?;
+    netlink::select_backend(&daemon_config.wireguard);
+    let client = api::build_client(daemon_config.proxy.as_ref(), &daemon_config.http)?;
+    let cache = cache::ConfigCache::new(state_dir);
+    let plan = plan::plan::<netlink::CurrentPlatform>(&client, &daemon_config, &cache).await?;
+    print!("{}", plan::render(&plan));
+    Ok(())
+}
+
+async fn run_doctor(
+    config_path: PathBuf,
+    state_dir: PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Ok(daemon_config) = config::load(&config_path).await {
+        netlink::select_backend(&daemon_config.wireguard);
+    }
+    let checks = doctor::run::<netlink::CurrentPlatform>(&config_path, &state_dir).await;
+    print!("{}", doctor::render(&checks));
+    if !doctor::healthy(&checks) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 async fn run_connect(
     config_path: PathBuf,
-    api_host: String,
-    api_token: String,
+    entry: config::ServerEntry,
 ) -> Result<(), Box<dyn std::error::Error>> {
     info!(
         config = %config_path.display(),
-        api_host = %api_host,
+        api_host = %entry.api_host,
+        auto_endpoint = entry.auto_endpoint,
         "connecting new server"
     );
 
     let mut daemon_config = config::load(&config_path).await?;
 
-    let entry = config::ServerEntry {
-        api_host,
-        api_token,
-    };
-
     config::validate_new_entry(&daemon_config, &entry)?;
 
+    // Catch mistyped tokens and hosts now rather than at the daemon's first
+    // fetch. An API that is merely unreachable does not block provisioning.
+    let proxy = daemon_config.proxy.as_ref();
+    let client = if entry.has_custom_trust() {
+        api::entry_client(proxy, &daemon_config.http, &entry).await?
+    } else {
+        api::build_client(proxy, &daemon_config.http)?
+    };
+    match api::fetch_config(&client, &entry, None).await {
+        Ok(fetched) => println!(
+            "verified: server {} on network {}",
+            fetched.config.server.name, fetched.config.network.name
+        ),
+        Err(e) if e.is_gone() => {
+            return Err(format!("{} rejected the token: {e}; nothing saved", entry.api_host).into());
+        }
+        Err(e) => warn!(error = %e, "could not verify the token; saving it anyway"),
+    }
+
     daemon_config.servers.push(entry);
     config::save(&config_path, &daemon_config).await?;
 
     info!(
         config = %config_path.display(),
-        "server added — restart the daemon to apply"
+        "server added — a running daemon applies it automatically"
     );
     Ok(())
 }
@@ -12,11 +12,42 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::net::IpAddr;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
 
 use thiserror::Error;
+use tracing::{debug, info, warn};
 use wirewarden_types::daemon::DaemonConfig;
+use wirewarden_types::redact::redact_opt;
+
+use crate::config::{Backend, WireguardConfig};
+
+#[cfg(target_os = "linux")]
+pub mod userspace;
+
+#[cfg(any(target_os = "freebsd", test))]
+pub mod freebsd;
+
+#[cfg(unix)]
+pub mod wg_quick;
+
+#[cfg(target_os = "linux")]
+pub mod acl;
+
+#[cfg(target_os = "linux")]
+pub mod nat;
+
+#[cfg(target_os = "linux")]
+pub mod routes;
+
+#[cfg(target_os = "linux")]
+pub mod shaping;
+
+#[cfg(unix)]
+mod wg_tool;
 
 #[derive(Debug, Error)]
 pub enum PlatformError {
@@ -42,7 +73,62 @@ pub enum PlatformError {
     Io(#[from] std::io::Error),
 }
 
-/// Interface name prefix for wirewarden-managed WireGuard interfaces.
+/// A WireGuard interface as the kernel reports it.
+#[derive(Clone, Default, PartialEq)]
+pub struct DeviceState {
+    /// Base64; `None` if no key is set.
+    pub private_key: Option<String>,
+    pub listen_port: u16,
+    /// Assigned addresses in CIDR form.
+    pub addresses: Vec<String>,
+    pub peers: Vec<PeerState>,
+}
+
+impl fmt::Debug for DeviceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceState")
+            .field("private_key", &redact_opt(&self.private_key))
+            .field("listen_port", &self.listen_port)
+            .field("addresses", &self.addresses)
+            .field("peers", &self.peers)
+            .finish()
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct PeerState {
+    /// Base64.
+    pub public_key: String,
+    /// In CIDR form.
+    pub allowed_ips: Vec<String>,
+    /// Set by config or learned from the peer's last packet.
+    pub endpoint: Option<String>,
+    /// Seconds; zero when off.
+    pub persistent_keepalive: u16,
+    /// Base64; `None` if no preshared key is set.
+    pub preshared_key: Option<String>,
+}
+
+impl fmt::Debug for PeerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerState")
+            .field("public_key", &self.public_key)
+            .field("allowed_ips", &self.allowed_ips)
+            .field("endpoint", &self.endpoint)
+            .field("persistent_keepalive", &self.persistent_keepalive)
+            .field("preshared_key", &redact_opt(&self.preshared_key))
+            .finish()
+    }
+}
+
+/// Bytes moved with a peer since it was added to the interface.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transfer {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Default interface name prefix for wirewarden-managed WireGuard interfaces.
 pub const IFACE_PREFIX: &str = "wwg";
 
 pub trait Platform {
@@ -55,23 +141,91 @@ pub trait Platform {
     ) -> impl Future<Output = Result<(), PlatformError>> + Send;
     fn interface_exists(name: &str) -> impl Future<Output = Result<bool, PlatformError>> + Send;
 
-    /// List all wirewarden-managed interfaces (`wwg*`) and their private keys.
+    /// Read back an interface's keys, port, addresses and peers without
+    /// changing anything.
+    fn device_state(name: &str) -> impl Future<Output = Result<DeviceState, PlatformError>> + Send;
+
+    /// Last handshake per peer, keyed by base64 public key; `None` for peers
+    /// that have not completed one.
+    fn peer_handshakes(
+        name: &str,
+    ) -> impl Future<Output = Result<HashMap<String, Option<SystemTime>>, PlatformError>> + Send;
+
+    /// Transfer counters per peer, keyed by base64 public key.
+    fn peer_transfer(
+        name: &str,
+    ) -> impl Future<Output = Result<HashMap<String, Transfer>, PlatformError>> + Send;
+
+    /// Remove peers by base64 public key, leaving the interface up.
+    fn remove_peers(
+        name: &str,
+        public_keys: &[String],
+    ) -> impl Future<Output = Result<(), PlatformError>> + Send;
+
+    /// Rename an interface, keeping its keys, peers and addresses. The link
+    /// goes down briefly, so sessions re-handshake.
+    fn rename_interface(
+        old: &str,
+        new: &str,
+    ) -> impl Future<Output = Result<(), PlatformError>> + Send;
+
+    /// List WireGuard interfaces named by any of `prefixes` (see
+    /// [`has_prefix`]) and their private keys.
     ///
     /// Returns a map of interface name to base64-encoded private key.
-    fn list_managed_interfaces()
-    -> impl Future<Output = Result<HashMap<String, String>, PlatformError>> + Send;
+    fn list_managed_interfaces(
+        prefixes: &[&str],
+    ) -> impl Future<Output = Result<HashMap<String, String>, PlatformError>> + Send;
 }
 
 use std::future::Future;
 
+/// [`Platform::list_managed_interfaces`], plus those of `names` that exist
+/// as WireGuard interfaces: names server entries set explicitly, outside
+/// any prefix.
+pub async fn managed_interfaces<P: Platform>(
+    prefixes: &[&str],
+    names: &[&str],
+) -> Result<HashMap<String, String>, PlatformError> {
+    let mut found = P::list_managed_interfaces(prefixes).await?;
+    for &name in names {
+        if found.contains_key(name) || !P::interface_exists(name).await? {
+            continue;
+        }
+        match P::device_state(name).await {
+            Ok(DeviceState {
+                private_key: Some(key),
+                ..
+            }) => {
+                found.insert(name.to_owned(), key);
+            }
+            Ok(_) => {}
+            Err(e) => debug!(interface = name, error = %e, "not a usable WireGuard interface"),
+        }
+    }
+    Ok(found)
+}
+
+pub type CurrentPlatform = HostPlatform;
+
+/// The host kernel's own WireGuard, where the daemon can drive it.
 #[cfg(target_os = "linux")]
-pub type CurrentPlatform = linux::LinuxPlatform;
+type KernelPlatform = linux::LinuxPlatform;
+
+#[cfg(target_os = "freebsd")]
+type KernelPlatform = freebsd::FreeBsdPlatform;
 
-#[cfg(not(target_os = "linux"))]
-pub type CurrentPlatform = StubPlatform;
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+type KernelPlatform = StubPlatform;
 
 // -- Helper utilities --
 
+/// Whether `name` is `prefix` followed by a number, e.g. `wwg0`.
+pub fn has_prefix(name: &str, prefix: &str) -> bool {
+    name.strip_prefix(prefix)
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
 pub fn decode_key(b64: &str) -> Result<[u8; 32], PlatformError> {
     use base64::Engine;
     let bytes = base64::engine::general_purpose::STANDARD.decode(b64)?;
@@ -92,7 +246,23 @@ pub fn parse_cidr(s: &str) -> Result<(IpAddr, u8), PlatformError> {
     Ok((addr, prefix))
 }
 
-// -- Stub platform for non-Linux --
+/// Reject interface names that could carry syntax into an `nft` or `tc`
+/// script.
+#[cfg(target_os = "linux")]
+fn check_interface_name(interface: &str) -> Result<(), PlatformError> {
+    if interface.is_empty()
+        || !interface
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"_-.".contains(&b))
+    {
+        return Err(PlatformError::Interface(format!(
+            "invalid interface name {interface:?}"
+        )));
+    }
+    Ok(())
+}
+
+// -- Stub platform for hosts without kernel support --
 
 pub struct StubPlatform;
 
@@ -117,25 +287,405 @@ impl Platform for StubPlatform {
         Err(PlatformError::Unsupported)
     }
 
-    async fn list_managed_interfaces() -> Result<HashMap<String, String>, PlatformError> {
+    async fn device_state(_name: &str) -> Result<DeviceState, PlatformError> {
+        Err(PlatformError::Unsupported)
+    }
+
+    async fn peer_handshakes(
+        _name: &str,
+    ) -> Result<HashMap<String, Option<SystemTime>>, PlatformError> {
+        Err(PlatformError::Unsupported)
+    }
+
+    async fn peer_transfer(_name: &str) -> Result<HashMap<String, Transfer>, PlatformError> {
+        Err(PlatformError::Unsupported)
+    }
+
+    async fn remove_peers(_name: &str, _public_keys: &[String]) -> Result<(), PlatformError> {
+        Err(PlatformError::Unsupported)
+    }
+
+    async fn rename_interface(_old: &str, _new: &str) -> Result<(), PlatformError> {
+        Err(PlatformError::Unsupported)
+    }
+
+    async fn list_managed_interfaces(
+        _prefixes: &[&str],
+    ) -> Result<HashMap<String, String>, PlatformError> {
         Err(PlatformError::Unsupported)
     }
 }
 
+// -- Backend selection --
+
+/// What [`HostPlatform`] drives, settled once per process by
+/// [`select_backend`].
+static BACKEND: OnceLock<(Backend, WireguardConfig)> = OnceLock::new();
+
+/// Settle which WireGuard implementation host interfaces run on, resolving
+/// `auto` by probing for the kernel module and otherwise falling back to
+/// userspace on Linux and `wg-quick` elsewhere. Only the first call counts,
+/// so interfaces never switch implementation mid-run. Returns the backend
+/// in use, never [`Backend::Auto`].
+pub fn select_backend(config: &WireguardConfig) -> Backend {
+    BACKEND
+        .get_or_init(|| {
+            let backend = match config.backend {
+                Backend::Auto if kernel_available() => Backend::Kernel,
+                Backend::Auto if cfg!(target_os = "linux") => Backend::Userspace,
+                Backend::Auto => Backend::WgQuick,
+                backend => backend,
+            };
+            (backend, config.clone())
+        })
+        .0
+}
+
+fn selected() -> &'static (Backend, WireguardConfig) {
+    select_backend(&WireguardConfig::default());
+    BACKEND.get().expect("backend was just selected")
+}
+
+/// Whether the kernel's WireGuard module answers.
+pub fn kernel_available() -> bool {
+    #[cfg(target_os = "linux")]
+    return wireguard_uapi::WgSocket::connect().is_ok();
+    // if_wg loads itself when the first interface is cloned.
+    #[cfg(target_os = "freebsd")]
+    return true;
+    #[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+    false
+}
+
+/// The host's interfaces, on the kernel, a userspace implementation or
+/// `wg-quick` as [`select_backend`] chose.
+pub struct HostPlatform;
+
+impl Platform for HostPlatform {
+    async fn ensure_interface(name: &str) -> Result<(), PlatformError> {
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => userspace::UserspacePlatform::ensure_interface(name).await,
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::ensure_interface(name).await,
+            _ => KernelPlatform::ensure_interface(name).await,
+        }
+    }
+
+    async fn remove_interface(name: &str) -> Result<(), PlatformError> {
+        #[cfg(target_os = "linux")]
+        if let Err(e) = nat::remove(name).await {
+            warn!(interface = name, error = %e, "failed to remove NAT rules");
+        }
+        #[cfg(target_os = "linux")]
+        if let Err(e) = acl::remove(name).await {
+            warn!(interface = name, error = %e, "failed to remove forwarding rules");
+        }
+        #[cfg(target_os = "linux")]
+        if selected().0 != Backend::WgQuick
+            && let Err(e) = routes::remove(name).await
+        {
+            warn!(interface = name, error = %e, "failed to remove routing rules");
+        }
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => userspace::UserspacePlatform::remove_interface(name).await,
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::remove_interface(name).await,
+            _ => KernelPlatform::remove_interface(name).await,
+        }
+    }
+
+    async fn apply_config(
+        name: &str,
+        config: &DaemonConfig,
+        prev: Option<&DaemonConfig>,
+    ) -> Result<(), PlatformError> {
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => {
+                userspace::UserspacePlatform::apply_config(name, config, prev).await
+            }
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::apply_config(name, config, prev).await,
+            _ => KernelPlatform::apply_config(name, config, prev).await,
+        }?;
+        // wg-quick routes the allowed IPs itself.
+        #[cfg(target_os = "linux")]
+        if selected().0 != Backend::WgQuick {
+            routes::sync(name, config, prev).await?;
+        } else if config.server.route_table.is_some() {
+            warn!(interface = name, "wg-quick routes in the main table; route_table is ignored");
+        }
+        #[cfg(target_os = "linux")]
+        nat::sync(name, config, prev).await?;
+        #[cfg(target_os = "linux")]
+        acl::sync(name, config, prev).await?;
+        #[cfg(target_os = "linux")]
+        shaping::sync(name, config, prev).await?;
+        #[cfg(not(target_os = "linux"))]
+        if config.server.manage_nat {
+            warn!(interface = name, "NAT is only managed on Linux; masquerade by hand");
+        }
+        #[cfg(not(target_os = "linux"))]
+        if !config.network.acl.is_empty() {
+            warn!(interface = name, "forwarding rules are only enforced on Linux");
+        }
+        #[cfg(not(target_os = "linux"))]
+        if config
+            .peers
+            .iter()
+            .any(|p| p.upload_kbps.is_some() || p.download_kbps.is_some())
+        {
+            warn!(interface = name, "rate limits are only enforced on Linux; peers are uncapped");
+        }
+        #[cfg(not(target_os = "linux"))]
+        if config.server.route_table.is_some() {
+            warn!(interface = name, "policy routing is only managed on Linux; add rules by hand");
+        }
+        Ok(())
+    }
+
+    async fn interface_exists(name: &str) -> Result<bool, PlatformError> {
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => userspace::UserspacePlatform::interface_exists(name).await,
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::interface_exists(name).await,
+            _ => KernelPlatform::interface_exists(name).await,
+        }
+    }
+
+    async fn device_state(name: &str) -> Result<DeviceState, PlatformError> {
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => userspace::UserspacePlatform::device_state(name).await,
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::device_state(name).await,
+            _ => KernelPlatform::device_state(name).await,
+        }
+    }
+
+    async fn peer_handshakes(
+        name: &str,
+    ) -> Result<HashMap<String, Option<SystemTime>>, PlatformError> {
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => userspace::UserspacePlatform::peer_handshakes(name).await,
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::peer_handshakes(name).await,
+            _ => KernelPlatform::peer_handshakes(name).await,
+        }
+    }
+
+    async fn peer_transfer(name: &str) -> Result<HashMap<String, Transfer>, PlatformError> {
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => userspace::UserspacePlatform::peer_transfer(name).await,
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::peer_transfer(name).await,
+            _ => KernelPlatform::peer_transfer(name).await,
+        }
+    }
+
+    async fn remove_peers(name: &str, public_keys: &[String]) -> Result<(), PlatformError> {
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => {
+                userspace::UserspacePlatform::remove_peers(name, public_keys).await
+            }
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::remove_peers(name, public_keys).await,
+            _ => KernelPlatform::remove_peers(name, public_keys).await,
+        }
+    }
+
+    async fn rename_interface(old: &str, new: &str) -> Result<(), PlatformError> {
+        // The tables are named after the interface; they are added back under
+        // the new name when the config is next applied.
+        #[cfg(target_os = "linux")]
+        if let Err(e) = nat::remove(old).await {
+            warn!(interface = old, error = %e, "failed to remove NAT rules");
+        }
+        #[cfg(target_os = "linux")]
+        if let Err(e) = acl::remove(old).await {
+            warn!(interface = old, error = %e, "failed to remove forwarding rules");
+        }
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => userspace::UserspacePlatform::rename_interface(old, new).await,
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::rename_interface(old, new).await,
+            _ => KernelPlatform::rename_interface(old, new).await,
+        }
+    }
+
+    async fn list_managed_interfaces(
+        prefixes: &[&str],
+    ) -> Result<HashMap<String, String>, PlatformError> {
+        match selected().0 {
+            #[cfg(target_os = "linux")]
+            Backend::Userspace => {
+                userspace::UserspacePlatform::list_managed_interfaces(prefixes).await
+            }
+            #[cfg(unix)]
+            Backend::WgQuick => wg_quick::WgQuickPlatform::list_managed_interfaces(prefixes).await,
+            _ => KernelPlatform::list_managed_interfaces(prefixes).await,
+        }
+    }
+}
+
+// -- Simulated platform --
+
+/// Interfaces that exist only in this process, for [`SimPlatform`].
+static SIM_DEVICES: Mutex<BTreeMap<String, DeviceState>> = Mutex::new(BTreeMap::new());
+
+fn sim_devices() -> std::sync::MutexGuard<'static, BTreeMap<String, DeviceState>> {
+    SIM_DEVICES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An in-memory stand-in for the kernel, for `wirewarden daemon --simulate`.
+/// Needs no root or WireGuard; every operation is logged with the changes it
+/// would have made, and nothing survives the process.
+pub struct SimPlatform;
+
+impl Platform for SimPlatform {
+    async fn ensure_interface(name: &str) -> Result<(), PlatformError> {
+        if !sim_devices().contains_key(name) {
+            info!(interface = name, "simulated: create interface");
+            sim_devices().insert(name.to_owned(), DeviceState::default());
+        }
+        Ok(())
+    }
+
+    async fn remove_interface(name: &str) -> Result<(), PlatformError> {
+        if sim_devices().remove(name).is_some() {
+            info!(interface = name, "simulated: remove interface");
+        }
+        Ok(())
+    }
+
+    async fn apply_config(
+        name: &str,
+        config: &DaemonConfig,
+        _prev: Option<&DaemonConfig>,
+    ) -> Result<(), PlatformError> {
+        let mut devices = sim_devices();
+        for change in crate::plan::diff(devices.get(name), config) {
+            info!(interface = name, %change, "simulated: apply");
+        }
+        let peers = config
+            .peers
+            .iter()
+            .map(|p| PeerState {
+                public_key: p.public_key.clone(),
+                allowed_ips: p.allowed_ips.clone(),
+                endpoint: p.endpoint.clone(),
+                persistent_keepalive: p.keepalive(&config.network),
+                preshared_key: p.preshared_key.clone(),
+            })
+            .collect();
+        devices.insert(
+            name.to_owned(),
+            DeviceState {
+                private_key: Some(config.server.private_key.clone()),
+                listen_port: config.server.listen_port as u16,
+                addresses: vec![crate::plan::host_cidr(&config.server.address)],
+                peers,
+            },
+        );
+        Ok(())
+    }
+
+    async fn interface_exists(name: &str) -> Result<bool, PlatformError> {
+        Ok(sim_devices().contains_key(name))
+    }
+
+    async fn device_state(name: &str) -> Result<DeviceState, PlatformError> {
+        sim_devices()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| PlatformError::Interface(format!("interface {name} not found")))
+    }
+
+    async fn peer_handshakes(
+        name: &str,
+    ) -> Result<HashMap<String, Option<SystemTime>>, PlatformError> {
+        // Simulated peers never handshake, so drains end at once.
+        Ok(sim_devices()
+            .get(name)
+            .map(|d| {
+                d.peers
+                    .iter()
+                    .map(|p| (p.public_key.clone(), None))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn peer_transfer(name: &str) -> Result<HashMap<String, Transfer>, PlatformError> {
+        // Nothing moves through simulated peers.
+        Ok(sim_devices()
+            .get(name)
+            .map(|d| {
+                d.peers
+                    .iter()
+                    .map(|p| (p.public_key.clone(), Transfer::default()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn remove_peers(name: &str, public_keys: &[String]) -> Result<(), PlatformError> {
+        if let Some(device) = sim_devices().get_mut(name) {
+            device
+                .peers
+                .retain(|p| !public_keys.contains(&p.public_key));
+        }
+        info!(
+            interface = name,
+            count = public_keys.len(),
+            "simulated: remove peers"
+        );
+        Ok(())
+    }
+
+    async fn rename_interface(old: &str, new: &str) -> Result<(), PlatformError> {
+        let mut devices = sim_devices();
+        let device = devices
+            .remove(old)
+            .ok_or_else(|| PlatformError::Interface(format!("interface {old} not found")))?;
+        devices.insert(new.to_owned(), device);
+        info!(from = old, to = new, "simulated: rename interface");
+        Ok(())
+    }
+
+    async fn list_managed_interfaces(
+        prefixes: &[&str],
+    ) -> Result<HashMap<String, String>, PlatformError> {
+        Ok(sim_devices()
+            .iter()
+            .filter(|(name, _)| prefixes.iter().any(|p| has_prefix(name, p)))
+            .filter_map(|(name, d)| Some((name.clone(), d.private_key.clone()?)))
+            .collect())
+    }
+}
+
 // -- Linux implementation --
 
 #[cfg(target_os = "linux")]
 pub mod linux {
     use std::collections::HashMap;
     use std::net::{IpAddr, SocketAddr};
+    use std::time::{SystemTime, UNIX_EPOCH};
 
     use futures::TryStreamExt;
     use tracing::{debug, info};
     use wireguard_uapi::{DeviceInterface, RouteSocket, WgSocket, set};
 
-    use wirewarden_types::daemon::{DaemonConfig, DaemonPeer};
+    use wirewarden_types::daemon::{DaemonConfig, DaemonNetworkInfo, DaemonPeer};
 
-    use super::{Platform, PlatformError, decode_key, parse_cidr};
+    use super::{DeviceState, PeerState, Platform, Transfer, PlatformError, decode_key, parse_cidr};
 
     pub struct LinuxPlatform;
 
@@ -192,6 +742,11 @@ pub mod linux {
                     if prev.server.address != config.server.address {
                         assign_address(name, &config.server.address).await?;
                     }
+                    if prev.server.mtu != config.server.mtu
+                        && let Some(mtu) = config.server.mtu
+                    {
+                        set_link_mtu(name, mtu).await?;
+                    }
 
                     info!(
                         interface = name,
@@ -202,6 +757,9 @@ pub mod linux {
                 _ => {
                     apply_device_config(name, config)?;
                     assign_address(name, &config.server.address).await?;
+                    if let Some(mtu) = config.server.mtu {
+                        set_link_mtu(name, mtu).await?;
+                    }
                     set_link_up(name).await?;
                     info!(
                         interface = name,
@@ -223,7 +781,125 @@ pub mod linux {
             Ok(existing.iter().any(|n| n == name))
         }
 
-        async fn list_managed_interfaces() -> Result<HashMap<String, String>, PlatformError> {
+        async fn device_state(name: &str) -> Result<DeviceState, PlatformError> {
+            use base64::Engine;
+
+            let b64 = |key: &[u8; 32]| base64::engine::general_purpose::STANDARD.encode(key);
+
+            let mut wg =
+                WgSocket::connect().map_err(|e| PlatformError::Interface(e.to_string()))?;
+            let device = wg
+                .get_device(DeviceInterface::from_name(name))
+                .map_err(|e| PlatformError::Interface(e.to_string()))?;
+
+            // The kernel reports an all-zero preshared key for peers without one.
+            let peers = device
+                .peers
+                .iter()
+                .map(|peer| PeerState {
+                    public_key: b64(&peer.public_key),
+                    allowed_ips: peer
+                        .allowed_ips
+                        .iter()
+                        .map(|ip| format!("{}/{}", ip.ipaddr, ip.cidr_mask))
+                        .collect(),
+                    endpoint: peer.endpoint.map(|ep| ep.to_string()),
+                    persistent_keepalive: peer.persistent_keepalive_interval,
+                    preshared_key: (peer.preshared_key != [0; 32])
+                        .then(|| b64(&peer.preshared_key)),
+                })
+                .collect();
+
+            Ok(DeviceState {
+                private_key: device.private_key.as_ref().map(b64),
+                listen_port: device.listen_port,
+                addresses: link_addresses(name).await?,
+                peers,
+            })
+        }
+
+        async fn peer_handshakes(
+            name: &str,
+        ) -> Result<HashMap<String, Option<SystemTime>>, PlatformError> {
+            use base64::Engine;
+
+            let mut wg =
+                WgSocket::connect().map_err(|e| PlatformError::Interface(e.to_string()))?;
+            let device = wg
+                .get_device(DeviceInterface::from_name(name))
+                .map_err(|e| PlatformError::Interface(e.to_string()))?;
+
+            // The kernel reports a zero time for peers without a handshake.
+            Ok(device
+                .peers
+                .iter()
+                .map(|peer| {
+                    let key = base64::engine::general_purpose::STANDARD.encode(peer.public_key);
+                    let last = (!peer.last_handshake_time.is_zero())
+                        .then(|| UNIX_EPOCH + peer.last_handshake_time);
+                    (key, last)
+                })
+                .collect())
+        }
+
+        async fn peer_transfer(name: &str) -> Result<HashMap<String, Transfer>, PlatformError> {
+            use base64::Engine;
+
+            let mut wg =
+                WgSocket::connect().map_err(|e| PlatformError::Interface(e.to_string()))?;
+            let device = wg
+                .get_device(DeviceInterface::from_name(name))
+                .map_err(|e| PlatformError::Interface(e.to_string()))?;
+
+            Ok(device
+                .peers
+                .iter()
+                .map(|peer| {
+                    let key = base64::engine::general_purpose::STANDARD.encode(peer.public_key);
+                    let transfer = Transfer {
+                        rx_bytes: peer.rx_bytes,
+                        tx_bytes: peer.tx_bytes,
+                    };
+                    (key, transfer)
+                })
+                .collect())
+        }
+
+        async fn remove_peers(name: &str, public_keys: &[String]) -> Result<(), PlatformError> {
+            let keys: Vec<&str> = public_keys.iter().map(String::as_str).collect();
+            remove_peers(name, &keys)
+        }
+
+        async fn rename_interface(old: &str, new: &str) -> Result<(), PlatformError> {
+            let (conn, handle, _) = rtnetlink::new_connection().map_err(PlatformError::Io)?;
+            tokio::spawn(conn);
+
+            let index = get_link_index(&handle, old).await?;
+
+            // Links can only be renamed while down.
+            let steps = [
+                rtnetlink::LinkUnspec::new_with_index(index).down().build(),
+                rtnetlink::LinkUnspec::new_with_index(index)
+                    .name(new.to_string())
+                    .build(),
+                rtnetlink::LinkUnspec::new_with_index(index).up().build(),
+            ];
+            for msg in steps {
+                handle
+                    .link()
+                    .set(msg)
+                    .execute()
+                    .await
+                    .map_err(|e| PlatformError::Interface(e.to_string()))?;
+            }
+
+            info!(from = old, to = new, "renamed interface via netlink");
+            Ok(())
+        }
+
+        async fn list_managed_interfaces(
+            prefixes: &[&str],
+        ) -> Result<HashMap<String, String>, PlatformError> {
             use base64::Engine;
 
             let mut route =
@@ -234,7 +910,7 @@ pub mod linux {
 
             let managed: Vec<&str> = all_names
                 .iter()
-                .filter(|n| n.starts_with(super::IFACE_PREFIX))
+                .filter(|n| prefixes.iter().any(|p| super::has_prefix(n, p)))
                 .map(|n| n.as_str())
                 .collect();
 
@@ -266,7 +942,7 @@ pub mod linux {
         let peer_data: Vec<PeerOwned> = config
             .peers
             .iter()
-            .map(|p| build_peer_owned(p, config.network.persistent_keepalive))
+            .map(|p| build_peer_owned(p, &config.network))
             .collect::<Result<_, PlatformError>>()?;
 
         let peers: Vec<set::Peer<'_>> = peer_data
@@ -290,7 +966,7 @@ pub mod linux {
                     .collect();
 
                 if p.persistent_keepalive > 0 {
-                    peer = peer.persistent_keepalive_interval(p.persistent_keepalive as u16);
+                    peer = peer.persistent_keepalive_interval(p.persistent_keepalive);
                 }
 
                 peer.allowed_ips(allowed)
@@ -300,6 +976,7 @@ pub mod linux {
         let dev = set::Device::from_ifname(name)
             .private_key(&private_key)
             .listen_port(listen_port)
+            .fwmark(config.server.fwmark.unwrap_or(0))
             .flags(vec![set::WgDeviceF::ReplacePeers])
             .peers(peers);
 
@@ -323,9 +1000,10 @@ pub mod linux {
     ) -> Result<(), PlatformError> {
         let key_changed = prev.server.private_key != next.server.private_key;
         let port_changed = prev.server.listen_port != next.server.listen_port;
+        let fwmark_changed = prev.server.fwmark != next.server.fwmark;
 
-        if key_changed || port_changed {
-            set_device_key_port(name, next)?;
+        if key_changed || port_changed || fwmark_changed {
+            set_device_fields(name, next)?;
         }
 
         let prev_peers: HashMap<&str, &DaemonPeer> = prev
@@ -353,13 +1031,17 @@ pub mod linux {
 
         let updated: Vec<&DaemonPeer> = next_peers
             .iter()
-            .filter(|(k, p)| prev_peers.get(*k).is_some_and(|old| old != *p))
+            .filter(|(k, p)| {
+                prev_peers.get(*k).is_some_and(|old| {
+                    old != *p || old.keepalive(&prev.network) != p.keepalive(&next.network)
+                })
+            })
             .map(|(_, p)| *p)
             .collect();
 
         if !added.is_empty() {
             debug!(interface = name, count = added.len(), "adding peers");
-            add_peers(name, &added, next.network.persistent_keepalive)?;
+            add_peers(name, &added, &next.network)?;
         }
 
         if !removed.is_empty() {
@@ -369,7 +1051,7 @@ pub mod linux {
 
         if !updated.is_empty() {
             debug!(interface = name, count = updated.len(), "updating peers");
-            update_peers(name, &updated, next.network.persistent_keepalive)?;
+            update_peers(name, &updated, &next.network)?;
         }
 
         if added.is_empty()
@@ -377,6 +1059,7 @@ pub mod linux {
             && updated.is_empty()
             && !key_changed
             && !port_changed
+            && !fwmark_changed
         {
             debug!(interface = name, "no device-level changes needed");
         }
@@ -384,25 +1067,27 @@ pub mod linux {
         Ok(())
     }
 
-    fn set_device_key_port(name: &str, config: &DaemonConfig) -> Result<(), PlatformError> {
+    fn set_device_fields(name: &str, config: &DaemonConfig) -> Result<(), PlatformError> {
         let private_key = decode_key(&config.server.private_key)?;
         let listen_port = config.server.listen_port as u16;
+        let fwmark = config.server.fwmark.unwrap_or(0);
 
         let dev = set::Device::from_ifname(name)
             .private_key(&private_key)
-            .listen_port(listen_port);
+            .listen_port(listen_port)
+            .fwmark(fwmark);
 
         let mut wg = WgSocket::connect().map_err(|e| PlatformError::Interface(e.to_string()))?;
         wg.set_device(dev)
             .map_err(|e| PlatformError::Interface(e.to_string()))?;
 
-        debug!(interface = name, listen_port, "updated device key/port");
+        debug!(interface = name, listen_port, fwmark, "updated device key/port/fwmark");
         Ok(())
     }
 
     fn build_peer_owned(
         peer: &DaemonPeer,
-        persistent_keepalive: i32,
+        network: &DaemonNetworkInfo,
     ) -> Result<PeerOwned, PlatformError> {
         let pub_key = decode_key(&peer.public_key)?;
         let endpoint: Option<SocketAddr> = peer.endpoint.as_deref().and_then(|ep| ep.parse().ok());
@@ -419,7 +1104,7 @@ pub mod linux {
             pub_key,
             endpoint,
             allowed_ips,
-            persistent_keepalive,
+            persistent_keepalive: peer.keepalive(network),
             preshared_key,
         })
     }
@@ -445,7 +1130,7 @@ pub mod linux {
             .collect();
 
         if p.persistent_keepalive > 0 {
-            peer = peer.persistent_keepalive_interval(p.persistent_keepalive as u16);
+            peer = peer.persistent_keepalive_interval(p.persistent_keepalive);
         }
 
         peer.allowed_ips(allowed)
@@ -454,11 +1139,11 @@ pub mod linux {
     fn add_peers(
         name: &str,
         peers: &[&DaemonPeer],
-        persistent_keepalive: i32,
+        network: &DaemonNetworkInfo,
     ) -> Result<(), PlatformError> {
         let owned: Vec<PeerOwned> = peers
             .iter()
-            .map(|p| build_peer_owned(p, persistent_keepalive))
+            .map(|p| build_peer_owned(p, network))
             .collect::<Result<_, _>>()?;
 
         let set_peers: Vec<set::Peer<'_>> = owned
@@ -496,11 +1181,11 @@ pub mod linux {
     fn update_peers(
         name: &str,
         peers: &[&DaemonPeer],
-        persistent_keepalive: i32,
+        network: &DaemonNetworkInfo,
     ) -> Result<(), PlatformError> {
         let owned: Vec<PeerOwned> = peers
             .iter()
-            .map(|p| build_peer_owned(p, persistent_keepalive))
+            .map(|p| build_peer_owned(p, network))
             .collect::<Result<_, _>>()?;
 
         let set_peers: Vec<set::Peer<'_>> = owned
@@ -525,12 +1210,58 @@ pub mod linux {
         pub_key: [u8; 32],
         endpoint: Option<SocketAddr>,
         allowed_ips: Vec<(IpAddr, u8)>,
-        persistent_keepalive: i32,
+        persistent_keepalive: u16,
         preshared_key: Option<[u8; 32]>,
     }
 
+    /// The link's addresses in CIDR form.
+    pub(super) async fn link_addresses(name: &str) -> Result<Vec<String>, PlatformError> {
+        use rtnetlink::packet_route::address::AddressAttribute;
+
+        let (conn, handle, _) = rtnetlink::new_connection().map_err(PlatformError::Io)?;
+        tokio::spawn(conn);
+
+        let index = get_link_index(&handle, name).await?;
+        let messages: Vec<_> = handle
+            .address()
+            .get()
+            .set_link_index_filter(index)
+            .execute()
+            .try_collect()
+            .await
+            .map_err(|e| PlatformError::Interface(e.to_string()))?;
+        Ok(messages
+            .iter()
+            .filter_map(|msg| {
+                msg.attributes.iter().find_map(|attr| match attr {
+                    AddressAttribute::Address(addr) => {
+                        Some(format!("{addr}/{}", msg.header.prefix_len))
+                    }
+                    _ => None,
+                })
+            })
+            .collect())
+    }
+
+    /// Delete a link of any kind; a no-op if it does not exist.
+    pub(super) async fn delete_link(name: &str) -> Result<(), PlatformError> {
+        let (conn, handle, _) = rtnetlink::new_connection().map_err(PlatformError::Io)?;
+        tokio::spawn(conn);
+
+        let mut links = handle.link().get().match_name(name.to_string()).execute();
+        let Ok(Some(link)) = links.try_next().await else {
+            return Ok(());
+        };
+        handle
+            .link()
+            .del(link.header.index)
+            .execute()
+            .await
+            .map_err(|e| PlatformError::Interface(e.to_string()))
+    }
+
     /// Resolve interface name to its index via rtnetlink.
-    async fn get_link_index(handle: &rtnetlink::Handle, name: &str) -> Result<u32, PlatformError> {
+    pub(super) async fn get_link_index(handle: &rtnetlink::Handle, name: &str) -> Result<u32, PlatformError> {
         let mut links = handle.link().get().match_name(name.to_string()).execute();
         let link = links
             .try_next()
@@ -540,7 +1271,7 @@ pub mod linux {
         Ok(link.header.index)
     }
 
-    async fn assign_address(name: &str, address: &str) -> Result<(), PlatformError> {
+    pub(super) async fn assign_address(name: &str, address: &str) -> Result<(), PlatformError> {
         let (addr, prefix) = if address.contains('/') {
             parse_cidr(address)?
         } else {
@@ -549,7 +1280,7 @@ pub mod linux {
             (addr, prefix)
         };
 
-        let (conn, handle, _) = rtnetlink::new_connection().map_err(|e| PlatformError::Io(e))?;
+        let (conn, handle, _) = rtnetlink::new_connection().map_err(PlatformError::Io)?;
         tokio::spawn(conn);
 
         let index = get_link_index(&handle, name).await?;
@@ -586,8 +1317,8 @@ pub mod linux {
         Ok(())
     }
 
-    async fn set_link_up(name: &str) -> Result<(), PlatformError> {
-        let (conn, handle, _) = rtnetlink::new_connection().map_err(|e| PlatformError::Io(e))?;
+    pub(super) async fn set_link_up(name: &str) -> Result<(), PlatformError> {
+        let (conn, handle, _) = rtnetlink::new_connection().map_err(PlatformError::Io)?;
         tokio::spawn(conn);
 
         let index = get_link_index(&handle, name).await?;
@@ -603,4 +1334,24 @@ pub mod linux {
         info!(interface = name, "set link up via netlink");
         Ok(())
     }
+
+    /// Set the link's MTU. Clearing the setting leaves the current MTU in
+    /// place until the interface is recreated.
+    pub(super) async fn set_link_mtu(name: &str, mtu: u32) -> Result<(), PlatformError> {
+        let (conn, handle, _) = rtnetlink::new_connection().map_err(PlatformError::Io)?;
+        tokio::spawn(conn);
+
+        let index = get_link_index(&handle, name).await?;
+
+        let msg = rtnetlink::LinkUnspec::new_with_index(index).mtu(mtu).build();
+        handle
+            .link()
+            .set(msg)
+            .execute()
+            .await
+            .map_err(|e| PlatformError::Interface(e.to_string()))?;
+
+        info!(interface = name, mtu, "set link mtu via netlink");
+        Ok(())
+    }
 }
@@ -0,0 +1,229 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Forwarding rules between peers. Like NAT, each interface with rules gets
+//! an nftables table of its own, replaced whole on every change; its forward
+//! chain checks traffic arriving from the interface against the rules in
+//! order. Replies to allowed connections always pass.
+
+use std::fmt::Write as _;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+
+use tracing::info;
+use wirewarden_types::daemon::{DaemonAclAction, DaemonAclRule, DaemonConfig};
+
+use super::nat::nft;
+use super::{PlatformError, check_interface_name, parse_cidr};
+
+/// Bring `interface`'s table in line with the network's rules. As with NAT,
+/// a table is only looked for when there were rules before or nothing is
+/// known.
+pub async fn sync(
+    interface: &str,
+    config: &DaemonConfig,
+    prev: Option<&DaemonConfig>,
+) -> Result<(), PlatformError> {
+    let rules = &config.network.acl;
+    if rules.is_empty() {
+        if prev.is_none_or(|p| !p.network.acl.is_empty()) {
+            remove(interface).await?;
+        }
+        return Ok(());
+    }
+    if prev.is_some_and(|p| p.network.acl == *rules) {
+        return Ok(());
+    }
+    nft(&script(interface, rules)?).await?;
+    info!(interface, rules = rules.len(), "applied forwarding rules");
+    Ok(())
+}
+
+/// Delete `interface`'s table if there is one. Without `nft` installed
+/// there can be none, so that is not an error.
+pub async fn remove(interface: &str) -> Result<(), PlatformError> {
+    match nft(&script(interface, &[])?).await {
+        Err(PlatformError::Io(e)) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// The table holding `interface`'s rules.
+fn table_name(interface: &str) -> String {
+    let name: String = interface
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("wirewarden_acl_{name}")
+}
+
+/// An `nft -f` script that replaces `interface`'s table with `rules`, or
+/// with none only deletes it.
+fn script(interface: &str, rules: &[DaemonAclRule]) -> Result<String, PlatformError> {
+    check_interface_name(interface)?;
+    let table = table_name(interface);
+    let mut script = String::new();
+    let _ = writeln!(script, "table inet {table}");
+    let _ = writeln!(script, "delete table inet {table}");
+    if rules.is_empty() {
+        return Ok(script);
+    }
+    let _ = writeln!(script, "table inet {table} {{");
+    let _ = writeln!(script, "\tchain forward {{");
+    let _ = writeln!(
+        script,
+        "\t\ttype filter hook forward priority filter; policy accept;"
+    );
+    let _ = writeln!(
+        script,
+        "\t\tiifname \"{interface}\" ct state established,related accept"
+    );
+    for rule in rules {
+        for line in rule_lines(rule)? {
+            let _ = writeln!(script, "\t\tiifname \"{interface}\" {line}");
+        }
+    }
+    let _ = writeln!(script, "\t}}");
+    let _ = writeln!(script, "}}");
+    Ok(script)
+}
+
+/// The matches and verdict for `rule`, one line per address family it
+/// covers. A side naming only one family keeps the rule to that family.
+fn rule_lines(rule: &DaemonAclRule) -> Result<Vec<String>, PlatformError> {
+    let verdict = match rule.action {
+        DaemonAclAction::Allow => "accept",
+        DaemonAclAction::Deny => "drop",
+    };
+    let sources = parse_side(&rule.sources)?;
+    let destinations = parse_side(&rule.destinations)?;
+    if sources.is_empty() && destinations.is_empty() {
+        return Ok(vec![verdict.to_string()]);
+    }
+    let mut lines = Vec::new();
+    for (family, v4) in [("ip", true), ("ip6", false)] {
+        let pick = |side: &[(IpAddr, u8)]| -> Vec<String> {
+            side.iter()
+                .filter(|(addr, _)| addr.is_ipv4() == v4)
+                .map(|(addr, prefix)| format!("{addr}/{prefix}"))
+                .collect()
+        };
+        let (saddr, daddr) = (pick(&sources), pick(&destinations));
+        if (!sources.is_empty() && saddr.is_empty())
+            || (!destinations.is_empty() && daddr.is_empty())
+        {
+            continue;
+        }
+        let mut line = String::new();
+        if !saddr.is_empty() {
+            let _ = write!(line, "{family} saddr {} ", set(&saddr));
+        }
+        if !daddr.is_empty() {
+            let _ = write!(line, "{family} daddr {} ", set(&daddr));
+        }
+        line.push_str(verdict);
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+/// Parse one side's CIDRs, so only addresses reach the script.
+fn parse_side(cidrs: &[String]) -> Result<Vec<(IpAddr, u8)>, PlatformError> {
+    cidrs.iter().map(|c| parse_cidr(c)).collect()
+}
+
+/// `items` as one element, or an anonymous set.
+fn set(items: &[String]) -> String {
+    match items {
+        [one] => one.clone(),
+        _ => format!("{{ {} }}", items.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(action: DaemonAclAction, sources: &[&str], destinations: &[&str]) -> DaemonAclRule {
+        DaemonAclRule {
+            action,
+            sources: sources.iter().map(|s| s.to_string()).collect(),
+            destinations: destinations.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn replaces_table_with_rules() {
+        let rules = [
+            rule(DaemonAclAction::Allow, &["10.0.0.2/32", "10.0.0.3/32"], &["10.0.0.1/32"]),
+            rule(DaemonAclAction::Deny, &["10.0.0.2/32", "10.0.0.3/32"], &[]),
+        ];
+        assert_eq!(
+            script("wwg0", &rules).unwrap(),
+            "table inet wirewarden_acl_wwg0\n\
+             delete table inet wirewarden_acl_wwg0\n\
+             table inet wirewarden_acl_wwg0 {\n\
+             \tchain forward {\n\
+             \t\ttype filter hook forward priority filter; policy accept;\n\
+             \t\tiifname \"wwg0\" ct state established,related accept\n\
+             \t\tiifname \"wwg0\" ip saddr { 10.0.0.2/32, 10.0.0.3/32 } \
+             ip daddr 10.0.0.1/32 accept\n\
+             \t\tiifname \"wwg0\" ip saddr { 10.0.0.2/32, 10.0.0.3/32 } drop\n\
+             \t}\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn splits_families() {
+        let lines = rule_lines(&rule(
+            DaemonAclAction::Deny,
+            &[],
+            &["10.1.0.0/16", "fd00::/64"],
+        ))
+        .unwrap();
+        assert_eq!(lines, ["ip daddr 10.1.0.0/16 drop", "ip6 daddr fd00::/64 drop"]);
+    }
+
+    #[test]
+    fn skips_families_a_side_cannot_match() {
+        let lines = rule_lines(&rule(
+            DaemonAclAction::Allow,
+            &["fd00::2/128"],
+            &["10.1.0.0/16"],
+        ))
+        .unwrap();
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn catch_all_has_no_matches() {
+        assert_eq!(rule_lines(&rule(DaemonAclAction::Deny, &[], &[])).unwrap(), ["drop"]);
+    }
+
+    #[test]
+    fn removal_only_deletes() {
+        assert_eq!(
+            script("wwg0", &[]).unwrap(),
+            "table inet wirewarden_acl_wwg0\ndelete table inet wirewarden_acl_wwg0\n"
+        );
+    }
+
+    #[test]
+    fn rejects_injected_addresses() {
+        let rules = [rule(DaemonAclAction::Allow, &["10.0.0.2/32 accept; drop"], &[])];
+        assert!(script("wwg0", &rules).is_err());
+        assert!(script("wwg0\" accept\n", &[]).is_err());
+    }
+}
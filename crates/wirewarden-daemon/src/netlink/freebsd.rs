@@ -0,0 +1,201 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! FreeBSD's kernel WireGuard, `if_wg(4)`, as on OPNsense and pfSense.
+//! Interfaces are cloned, addressed and renamed with `ifconfig(8)` and
+//! configured with `wg(8)`, which talks to the driver over its ioctl. The
+//! driver is in the kernel from FreeBSD 13.2; `wg(8)` comes with the
+//! wireguard-tools package where the base system lacks it.
+
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use tracing::{debug, info};
+use wirewarden_types::daemon::DaemonConfig;
+
+use super::wg_tool::{parse_dump, run, wg_conf};
+use super::{
+    DeviceState, Platform, PlatformError, Transfer, decode_key, has_prefix, parse_cidr,
+};
+
+pub struct FreeBsdPlatform;
+
+impl Platform for FreeBsdPlatform {
+    async fn ensure_interface(name: &str) -> Result<(), PlatformError> {
+        if Self::interface_exists(name).await? {
+            debug!(interface = name, "interface already exists");
+            return Ok(());
+        }
+        info!(interface = name, "creating wireguard interface");
+        // Cloning loads if_wg if it is not already.
+        run("ifconfig", &["wg", "create", "name", name], None)
+            .await
+            .map(drop)
+    }
+
+    async fn remove_interface(name: &str) -> Result<(), PlatformError> {
+        if Self::interface_exists(name).await? {
+            info!(interface = name, "removing interface");
+            run("ifconfig", &[name, "destroy"], None).await?;
+        }
+        Ok(())
+    }
+
+    async fn apply_config(
+        name: &str,
+        config: &DaemonConfig,
+        _prev: Option<&DaemonConfig>,
+    ) -> Result<(), PlatformError> {
+        Self::ensure_interface(name).await?;
+
+        // syncconf reads the device back and changes only what differs, so
+        // sessions with unchanged peers carry on without `prev`.
+        let conf = wg_conf(config, None)?;
+        run("wg", &["syncconf", name, "/dev/stdin"], Some(&conf)).await?;
+
+        let address = crate::plan::host_cidr(&config.server.address);
+        let live = parse_addresses(&run("ifconfig", &[name], None).await?);
+        if live != [address.as_str()] {
+            for stale in &live {
+                let (ip, _) = stale.split_once('/').unwrap_or((stale, ""));
+                run("ifconfig", &[name, family(stale)?, ip, "-alias"], None).await?;
+            }
+            run(
+                "ifconfig",
+                &[name, family(&address)?, &address, "alias"],
+                None,
+            )
+            .await?;
+            info!(interface = name, %address, "assigned address via ifconfig");
+        }
+        if let Some(mtu) = config.server.mtu {
+            run("ifconfig", &[name, "mtu", &mtu.to_string()], None).await?;
+        }
+        run("ifconfig", &[name, "up"], None).await?;
+
+        info!(
+            interface = name,
+            server = %config.server.name,
+            "applied configuration via wg(8)"
+        );
+        Ok(())
+    }
+
+    async fn interface_exists(name: &str) -> Result<bool, PlatformError> {
+        Ok(interfaces().await?.iter().any(|n| n == name))
+    }
+
+    async fn device_state(name: &str) -> Result<DeviceState, PlatformError> {
+        let mut state = parse_dump(&run("wg", &["show", name, "dump"], None).await?)?.state;
+        let ifconfig = run("ifconfig", &[name], None).await?;
+        state.addresses = parse_addresses(&ifconfig);
+        Ok(state)
+    }
+
+    async fn peer_handshakes(
+        name: &str,
+    ) -> Result<HashMap<String, Option<SystemTime>>, PlatformError> {
+        Ok(parse_dump(&run("wg", &["show", name, "dump"], None).await?)?.handshakes)
+    }
+
+    async fn peer_transfer(name: &str) -> Result<HashMap<String, Transfer>, PlatformError> {
+        Ok(parse_dump(&run("wg", &["show", name, "dump"], None).await?)?.transfer)
+    }
+
+    async fn remove_peers(name: &str, public_keys: &[String]) -> Result<(), PlatformError> {
+        if public_keys.is_empty() {
+            return Ok(());
+        }
+        let mut args = vec!["set", name];
+        for key in public_keys {
+            decode_key(key)?;
+            args.extend(["peer", key.as_str(), "remove"]);
+        }
+        run("wg", &args, None).await.map(drop)
+    }
+
+    async fn rename_interface(old: &str, new: &str) -> Result<(), PlatformError> {
+        // Unlike on Linux, the link can stay up while it is renamed.
+        run("ifconfig", &[old, "name", new], None).await?;
+        info!(from = old, to = new, "renamed interface via ifconfig");
+        Ok(())
+    }
+
+    async fn list_managed_interfaces(
+        prefixes: &[&str],
+    ) -> Result<HashMap<String, String>, PlatformError> {
+        let mut result = HashMap::new();
+        for name in interfaces().await? {
+            if !prefixes.iter().any(|p| has_prefix(&name, p)) {
+                continue;
+            }
+            let state = parse_dump(&run("wg", &["show", &name, "dump"], None).await?)?.state;
+            if let Some(key) = state.private_key {
+                debug!(interface = %name, "discovered managed interface");
+                result.insert(name, key);
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Every interface the driver created; if_wg puts them in the `wg` group.
+async fn interfaces() -> Result<Vec<String>, PlatformError> {
+    let out = run("ifconfig", &["-g", "wg"], None).await?;
+    Ok(out.split_whitespace().map(str::to_owned).collect())
+}
+
+/// `ifconfig`'s address family keyword for an address in CIDR form.
+fn family(cidr: &str) -> Result<&'static str, PlatformError> {
+    Ok(if parse_cidr(cidr)?.0.is_ipv4() {
+        "inet"
+    } else {
+        "inet6"
+    })
+}
+
+/// Addresses in `ifconfig <name>` output, in CIDR form. IPv6 link-local
+/// addresses belong to the link rather than the config, so are skipped.
+fn parse_addresses(ifconfig: &str) -> Vec<String> {
+    ifconfig
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            match (words.next()?, words.next()?, words.next()?, words.next()?) {
+                ("inet", ip, "netmask", mask) => {
+                    let mask = u32::from_str_radix(mask.strip_prefix("0x")?, 16).ok()?;
+                    Some(format!("{ip}/{}", mask.count_ones()))
+                }
+                ("inet6", ip, "prefixlen", len) if !ip.contains('%') => Some(format!("{ip}/{len}")),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ifconfig_addresses() {
+        let ifconfig = "wwg0: flags=10080c1<UP,RUNNING,NOARP,MULTICAST,LOWER_UP> metric 0 mtu 1420\n\
+            \toptions=80000<LINKSTATE>\n\
+            \tinet 10.0.0.1 netmask 0xffffff00\n\
+            \tinet6 fe80::1%wwg0 prefixlen 64 scopeid 0x3\n\
+            \tinet6 fd00::1 prefixlen 64\n\
+            \tgroups: wg\n";
+        assert_eq!(parse_addresses(ifconfig), ["10.0.0.1/24", "fd00::1/64"]);
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Masquerading for servers that forward internet traffic. Each interface
+//! gets an nftables table of its own, replaced whole on every change, so
+//! the host's other rules are never touched and teardown is one deletion.
+
+use std::fmt::Write as _;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::process::Stdio;
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+use wirewarden_types::daemon::DaemonConfig;
+
+use super::{PlatformError, check_interface_name, parse_cidr};
+
+/// Bring `interface`'s table in line with `config`. A table is only looked
+/// for when NAT was on before or nothing is known, so hosts that never use
+/// it never need `nft`.
+pub async fn sync(
+    interface: &str,
+    config: &DaemonConfig,
+    prev: Option<&DaemonConfig>,
+) -> Result<(), PlatformError> {
+    if !config.server.manage_nat {
+        if prev.is_none_or(|p| p.server.manage_nat) {
+            remove(interface).await?;
+        }
+        return Ok(());
+    }
+    let (addr, prefix) = parse_cidr(&config.network.cidr)?;
+    nft(&script(interface, Some((addr, prefix)))?).await?;
+    enable_forwarding(addr);
+    info!(interface, cidr = %config.network.cidr, "masquerading forwarded traffic");
+    Ok(())
+}
+
+/// Delete `interface`'s table if there is one. Without `nft` installed
+/// there can be none, so that is not an error.
+pub async fn remove(interface: &str) -> Result<(), PlatformError> {
+    match nft(&script(interface, None)?).await {
+        Err(PlatformError::Io(e)) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// The table holding `interface`'s rules.
+fn table_name(interface: &str) -> String {
+    let name: String = interface
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("wirewarden_{name}")
+}
+
+/// An `nft -f` script that replaces `interface`'s table with forward and
+/// masquerade rules for `network`, or with `None` only deletes it. Declaring
+/// the table first makes the deletion succeed when there was none.
+fn script(interface: &str, network: Option<(IpAddr, u8)>) -> Result<String, PlatformError> {
+    check_interface_name(interface)?;
+    let table = table_name(interface);
+    let mut script = String::new();
+    let _ = writeln!(script, "table inet {table}");
+    let _ = writeln!(script, "delete table inet {table}");
+    let Some((addr, prefix)) = network else {
+        return Ok(script);
+    };
+    let family = if addr.is_ipv4() { "ip" } else { "ip6" };
+    let _ = writeln!(script, "table inet {table} {{");
+    let _ = writeln!(script, "\tchain forward {{");
+    let _ = writeln!(
+        script,
+        "\t\ttype filter hook forward priority filter; policy accept;"
+    );
+    let _ = writeln!(script, "\t\tiifname \"{interface}\" accept");
+    let _ = writeln!(
+        script,
+        "\t\toifname \"{interface}\" ct state established,related accept"
+    );
+    let _ = writeln!(script, "\t}}");
+    let _ = writeln!(script, "\tchain postrouting {{");
+    let _ = writeln!(
+        script,
+        "\t\ttype nat hook postrouting priority srcnat; policy accept;"
+    );
+    let _ = writeln!(
+        script,
+        "\t\toifname != \"{interface}\" {family} saddr {addr}/{prefix} masquerade"
+    );
+    let _ = writeln!(script, "\t}}");
+    let _ = writeln!(script, "}}");
+    Ok(script)
+}
+
+/// Run `script` through `nft -f -`, as one transaction.
+pub(super) async fn nft(script: &str) -> Result<(), PlatformError> {
+    let mut child = Command::new("nft")
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(script.as_bytes()).await?;
+    drop(stdin);
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(PlatformError::Interface(format!(
+            "nft failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Turn on forwarding for `addr`'s family, without which nothing reaches
+/// the masquerade rule. It is left on at teardown, since other services
+/// may rely on it; failing to set it, as without root, only warns.
+fn enable_forwarding(addr: IpAddr) {
+    let path = if addr.is_ipv4() {
+        "/proc/sys/net/ipv4/ip_forward"
+    } else {
+        "/proc/sys/net/ipv6/conf/all/forwarding"
+    };
+    if std::fs::read_to_string(path).is_ok_and(|v| v.trim() != "0") {
+        return;
+    }
+    match std::fs::write(path, "1") {
+        Ok(()) => debug!(path, "enabled forwarding"),
+        Err(e) => warn!(path, error = %e, "cannot enable forwarding; set it with sysctl"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_table_with_rules() {
+        let script = script("wwg0", Some(("10.0.0.0".parse().unwrap(), 24))).unwrap();
+        assert_eq!(
+            script,
+            "table inet wirewarden_wwg0\n\
+             delete table inet wirewarden_wwg0\n\
+             table inet wirewarden_wwg0 {\n\
+             \tchain forward {\n\
+             \t\ttype filter hook forward priority filter; policy accept;\n\
+             \t\tiifname \"wwg0\" accept\n\
+             \t\toifname \"wwg0\" ct state established,related accept\n\
+             \t}\n\
+             \tchain postrouting {\n\
+             \t\ttype nat hook postrouting priority srcnat; policy accept;\n\
+             \t\toifname != \"wwg0\" ip saddr 10.0.0.0/24 masquerade\n\
+             \t}\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn matches_ipv6_sources() {
+        let script = script("wwg1", Some(("fd00::".parse().unwrap(), 64))).unwrap();
+        assert!(script.contains("oifname != \"wwg1\" ip6 saddr fd00::/64 masquerade"));
+    }
+
+    #[test]
+    fn removal_only_deletes() {
+        assert_eq!(
+            script("wg-legacy.0", None).unwrap(),
+            "table inet wirewarden_wg_legacy_0\ndelete table inet wirewarden_wg_legacy_0\n"
+        );
+    }
+
+    #[test]
+    fn rejects_injected_interface_names() {
+        assert!(script("wwg0\" accept\n", None).is_err());
+        assert!(script("", None).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Kernel routes for what a server reaches through its interface: the
+//! network's CIDR and the routes other servers advertise, so traffic for a
+//! remote site leaves through the tunnel rather than the default route.
+//! WireGuard's allowed IPs only pick the peer once a packet is on the
+//! interface.
+//!
+//! With a `route_table` in the config the routes go in that table instead of
+//! main, and a rule sends traffic to it. With an `fwmark` as well, the rule
+//! skips packets carrying the mark, so WireGuard's own packets are not
+//! routed back into the tunnel.
+
+use std::collections::BTreeSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use futures::TryStreamExt;
+use rtnetlink::packet_route::AddressFamily;
+use rtnetlink::packet_route::route::{RouteAttribute, RouteHeader, RouteMessage, RouteScope};
+use rtnetlink::packet_route::rule::{RuleAction, RuleAttribute, RuleFlags, RuleMessage};
+use rtnetlink::{IpVersion, RouteMessageBuilder};
+use tracing::{debug, info, warn};
+use wirewarden_types::daemon::DaemonConfig;
+
+use super::linux::get_link_index;
+use super::{PlatformError, parse_cidr};
+
+/// A rule looking up the table a config routes in, for one address family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Rule {
+    v6: bool,
+    table: u32,
+    priority: Option<u32>,
+    /// Packets with this mark skip the table.
+    fwmark: Option<u32>,
+}
+
+/// Route `interface`'s destinations from `config` through it, and drop the
+/// routes and rules only `prev` had. Routes go with the link when it is
+/// removed; rules are left to [`remove`].
+pub async fn sync(
+    interface: &str,
+    config: &DaemonConfig,
+    prev: Option<&DaemonConfig>,
+) -> Result<(), PlatformError> {
+    let table = table_for(config);
+    let wanted = destinations(config)?;
+    let wanted_rules = rules(config, &wanted);
+    let (stale, stale_rules) = match prev {
+        Some(prev) => {
+            let prev_table = table_for(prev);
+            let routes = destinations(prev)?;
+            let stale_rules = rules(prev, &routes)
+                .difference(&wanted_rules)
+                .copied()
+                .collect();
+            let stale = routes
+                .into_iter()
+                .filter(|dest| prev_table != table || !wanted.contains(dest))
+                .map(|(addr, prefix)| (prev_table, addr, prefix))
+                .collect();
+            (stale, stale_rules)
+        }
+        None => (Vec::new(), BTreeSet::new()),
+    };
+
+    let (conn, handle, _) = rtnetlink::new_connection().map_err(PlatformError::Io)?;
+    tokio::spawn(conn);
+    let index = get_link_index(&handle, interface).await?;
+
+    for rule in &stale_rules {
+        match handle.rule().del(rule_message(rule)).execute().await {
+            Ok(()) => debug!(interface, ?rule, "removed rule"),
+            Err(rtnetlink::Error::NetlinkError(e)) if e.raw_code() == -libc::ENOENT => {}
+            Err(e) => return Err(PlatformError::Interface(e.to_string())),
+        }
+    }
+    for &(table, addr, prefix) in &stale {
+        match handle
+            .route()
+            .del(route(index, table, addr, prefix)?)
+            .execute()
+            .await
+        {
+            Ok(()) => debug!(interface, %addr, prefix, table, "removed route"),
+            // Already gone, e.g. with an address it was tied to.
+            Err(rtnetlink::Error::NetlinkError(e)) if e.raw_code() == -libc::ESRCH => {}
+            Err(e) => return Err(PlatformError::Interface(e.to_string())),
+        }
+    }
+    for &(addr, prefix) in &wanted {
+        handle
+            .route()
+            .add(route(index, table, addr, prefix)?)
+            .replace()
+            .execute()
+            .await
+            .map_err(|e| PlatformError::Interface(e.to_string()))?;
+    }
+    for rule in &wanted_rules {
+        let mut request = handle.rule().add();
+        *request.message_mut() = rule_message(rule);
+        match request.execute().await {
+            Ok(()) => debug!(interface, ?rule, "added rule"),
+            // Left by an earlier run.
+            Err(rtnetlink::Error::NetlinkError(e)) if e.raw_code() == -libc::EEXIST => {}
+            Err(e) => return Err(PlatformError::Interface(e.to_string())),
+        }
+    }
+    info!(
+        interface,
+        routes = wanted.len(),
+        rules = wanted_rules.len(),
+        table,
+        "installed routes"
+    );
+    Ok(())
+}
+
+/// Delete the rules looking up the tables `interface` routes in, found
+/// from its routes before the link and they are gone. Rules name no
+/// interface, so they would outlive it.
+pub async fn remove(interface: &str) -> Result<(), PlatformError> {
+    let (conn, handle, _) = rtnetlink::new_connection().map_err(PlatformError::Io)?;
+    tokio::spawn(conn);
+    // No link, no routes to find the tables by.
+    let Ok(index) = get_link_index(&handle, interface).await else {
+        return Ok(());
+    };
+
+    let mut tables = BTreeSet::new();
+    let dumps = [
+        RouteMessageBuilder::<Ipv4Addr>::new().build(),
+        RouteMessageBuilder::<Ipv6Addr>::new().build(),
+    ];
+    for dump in dumps {
+        let routes: Vec<RouteMessage> = handle
+            .route()
+            .get(dump)
+            .execute()
+            .try_collect()
+            .await
+            .map_err(|e| PlatformError::Interface(e.to_string()))?;
+        tables.extend(
+            routes
+                .iter()
+                .filter(|r| r.attributes.contains(&RouteAttribute::Oif(index)))
+                .map(route_table)
+                .filter(|&t| !RESERVED_TABLES.contains(&t)),
+        );
+    }
+    if tables.is_empty() {
+        return Ok(());
+    }
+
+    for version in [IpVersion::V4, IpVersion::V6] {
+        let rules: Vec<RuleMessage> = handle
+            .rule()
+            .get(version)
+            .execute()
+            .try_collect()
+            .await
+            .map_err(|e| PlatformError::Interface(e.to_string()))?;
+        for rule in rules
+            .into_iter()
+            .filter(|r| tables.contains(&rule_table(r)))
+        {
+            match handle.rule().del(rule).execute().await {
+                Ok(()) => {}
+                Err(rtnetlink::Error::NetlinkError(e)) if e.raw_code() == -libc::ENOENT => {}
+                Err(e) => return Err(PlatformError::Interface(e.to_string())),
+            }
+        }
+    }
+    info!(interface, ?tables, "removed routing rules");
+    Ok(())
+}
+
+/// Tables the kernel keeps for itself: default, main and local.
+const RESERVED_TABLES: [u32; 3] = [253, 254, 255];
+
+/// The table `config`'s routes go in.
+fn table_for(config: &DaemonConfig) -> u32 {
+    config
+        .server
+        .route_table
+        .unwrap_or(RouteHeader::RT_TABLE_MAIN.into())
+}
+
+/// One rule per address family `destinations` has, when `config` routes in
+/// a table of its own.
+fn rules(config: &DaemonConfig, destinations: &BTreeSet<(IpAddr, u8)>) -> BTreeSet<Rule> {
+    let Some(table) = config.server.route_table else {
+        return BTreeSet::new();
+    };
+    destinations
+        .iter()
+        .map(|(addr, _)| Rule {
+            v6: addr.is_ipv6(),
+            table,
+            priority: config.server.rule_priority,
+            fwmark: config.server.fwmark,
+        })
+        .collect()
+}
+
+/// `ip rule [not fwmark M] lookup T [priority P]` as a netlink message.
+fn rule_message(rule: &Rule) -> RuleMessage {
+    let mut message = RuleMessage::default();
+    message.header.family = if rule.v6 {
+        AddressFamily::Inet6
+    } else {
+        AddressFamily::Inet
+    };
+    message.header.action = RuleAction::ToTable;
+    message.attributes.push(RuleAttribute::Table(rule.table));
+    if let Some(priority) = rule.priority {
+        message.attributes.push(RuleAttribute::Priority(priority));
+    }
+    if let Some(fwmark) = rule.fwmark {
+        message.header.flags = RuleFlags::Invert;
+        message.attributes.push(RuleAttribute::FwMark(fwmark));
+        message.attributes.push(RuleAttribute::FwMask(u32::MAX));
+    }
+    message
+}
+
+/// The table a route is in; ids past 255 only fit the attribute.
+fn route_table(route: &RouteMessage) -> u32 {
+    route
+        .attributes
+        .iter()
+        .find_map(|a| match a {
+            RouteAttribute::Table(t) => Some(*t),
+            _ => None,
+        })
+        .unwrap_or(route.header.table.into())
+}
+
+/// The table a rule looks up.
+fn rule_table(rule: &RuleMessage) -> u32 {
+    rule.attributes
+        .iter()
+        .find_map(|a| match a {
+            RuleAttribute::Table(t) => Some(*t),
+            _ => None,
+        })
+        .unwrap_or(rule.header.table.into())
+}
+
+/// A link-scoped route to `addr/prefix` out of link `index`, in `table`.
+fn route(index: u32, table: u32, addr: IpAddr, prefix: u8) -> Result<RouteMessage, PlatformError> {
+    let builder = RouteMessageBuilder::<IpAddr>::new()
+        .destination_prefix(addr, prefix)
+        .map_err(|e| PlatformError::Interface(e.to_string()))?;
+    Ok(builder
+        .output_interface(index)
+        .scope(RouteScope::Link)
+        .table_id(table)
+        .build())
+}
+
+/// The network's CIDR and every peer range outside it, masked to their
+/// prefix. Default routes are skipped: sending all traffic into the tunnel,
+/// the server's own handshakes included, would cut it off.
+fn destinations(config: &DaemonConfig) -> Result<BTreeSet<(IpAddr, u8)>, PlatformError> {
+    let network = masked(parse_cidr(&config.network.cidr)?);
+    let mut out = BTreeSet::from([network]);
+    for cidr in config.peers.iter().flat_map(|p| &p.allowed_ips) {
+        let (addr, prefix) = masked(parse_cidr(cidr)?);
+        if prefix == 0 {
+            warn!(cidr = %cidr, "not routing a default route through the tunnel");
+        } else if !contains(network, addr) {
+            out.insert((addr, prefix));
+        }
+    }
+    Ok(out)
+}
+
+/// `addr` with the bits past `prefix` cleared.
+fn masked((addr, prefix): (IpAddr, u8)) -> (IpAddr, u8) {
+    let addr = match addr {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX
+                .checked_shl(32 - u32::from(prefix.min(32)))
+                .unwrap_or(0);
+            IpAddr::from(Ipv4Addr::from(u32::from(v4) & mask))
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(prefix.min(128)))
+                .unwrap_or(0);
+            IpAddr::from(Ipv6Addr::from(u128::from(v6) & mask))
+        }
+    };
+    (addr, prefix)
+}
+
+/// Whether `addr` falls inside `network`, which must be masked.
+fn contains(network: (IpAddr, u8), addr: IpAddr) -> bool {
+    addr.is_ipv4() == network.0.is_ipv4() && masked((addr, network.1)).0 == network.0
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use wirewarden_types::daemon::{
+        CONFIG_VERSION, DaemonNetworkInfo, DaemonPeer, DaemonServerInfo,
+    };
+
+    use super::*;
+
+    fn config(cidr: &str, peers: &[&[&str]]) -> DaemonConfig {
+        DaemonConfig {
+            version: CONFIG_VERSION,
+            server: DaemonServerInfo {
+                id: Uuid::nil(),
+                name: "gateway".into(),
+                private_key: String::new(),
+                public_key: String::new(),
+                address: "10.0.0.1/24".into(),
+                listen_port: 51820,
+                manage_nat: false,
+                fwmark: None,
+                route_table: None,
+                rule_priority: None,
+                mtu: None,
+            },
+            network: DaemonNetworkInfo {
+                id: Uuid::nil(),
+                name: "home".into(),
+                cidr: cidr.into(),
+                persistent_keepalive: 25,
+                dns_servers: Vec::new(),
+                dns_zone: None,
+                acl: Vec::new(),
+            },
+            peers: peers
+                .iter()
+                .map(|ips| DaemonPeer {
+                    public_key: String::new(),
+                    allowed_ips: ips.iter().map(|ip| ip.to_string()).collect(),
+                    endpoint: None,
+                    preshared_key: None,
+                    persistent_keepalive: None,
+                    upload_kbps: None,
+                    download_kbps: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn cidr(s: &str) -> (IpAddr, u8) {
+        parse_cidr(s).unwrap()
+    }
+
+    #[test]
+    fn routes_network_and_remote_sites() {
+        let config = config(
+            "10.0.0.0/24",
+            &[&["10.0.0.2/32", "192.168.1.0/24"], &["10.0.0.3/32"]],
+        );
+        assert_eq!(
+            destinations(&config).unwrap(),
+            BTreeSet::from([cidr("10.0.0.0/24"), cidr("192.168.1.0/24")])
+        );
+    }
+
+    #[test]
+    fn skips_default_routes() {
+        let config = config("fd00::/64", &[&["fd00::2/128", "::/0"]]);
+        assert_eq!(
+            destinations(&config).unwrap(),
+            BTreeSet::from([cidr("fd00::/64")])
+        );
+    }
+
+    #[test]
+    fn rules_only_with_a_table() {
+        let mut config = config("10.0.0.0/24", &[&["10.0.0.2/32", "fd00:1::/64"]]);
+        let dests = destinations(&config).unwrap();
+        assert!(rules(&config, &dests).is_empty());
+
+        config.server.fwmark = Some(51820);
+        config.server.route_table = Some(1000);
+        let rule = |v6| Rule {
+            v6,
+            table: 1000,
+            priority: None,
+            fwmark: Some(51820),
+        };
+        assert_eq!(
+            rules(&config, &dests),
+            BTreeSet::from([rule(false), rule(true)])
+        );
+        assert_eq!(table_for(&config), 1000);
+    }
+
+    #[test]
+    fn rule_skips_marked_packets() {
+        let rule = Rule {
+            v6: false,
+            table: 1000,
+            priority: Some(100),
+            fwmark: Some(51820),
+        };
+        let message = rule_message(&rule);
+        assert_eq!(message.header.family, AddressFamily::Inet);
+        assert_eq!(message.header.flags, RuleFlags::Invert);
+        assert_eq!(rule_table(&message), 1000);
+        assert!(message.attributes.contains(&RuleAttribute::FwMark(51820)));
+        assert!(message.attributes.contains(&RuleAttribute::Priority(100)));
+
+        let unmarked = rule_message(&Rule {
+            fwmark: None,
+            ..rule
+        });
+        assert!(unmarked.header.flags.is_empty());
+    }
+
+    #[test]
+    fn masks_host_bits() {
+        assert_eq!(masked(cidr("192.168.1.7/24")), cidr("192.168.1.0/24"));
+        assert_eq!(masked(cidr("fd00::1:2/112")), cidr("fd00::1:0/112"));
+        assert_eq!(masked(cidr("10.1.2.3/32")), cidr("10.1.2.3/32"));
+    }
+}
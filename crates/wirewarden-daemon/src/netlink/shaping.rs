@@ -0,0 +1,269 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Per-peer bandwidth caps, applied with `tc`. Downloads are shaped by an
+//! HTB class per capped peer on the interface's egress; uploads can only be
+//! policed on ingress, so excess is dropped rather than queued. Peers are
+//! matched on their tunnel address. The qdiscs belong to the device, so
+//! they go with it on removal and follow it on rename.
+
+use std::fmt::Write as _;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::process::Stdio;
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+use wirewarden_types::daemon::{DaemonConfig, DaemonPeer};
+
+use super::{PlatformError, check_interface_name, parse_cidr};
+
+/// Smallest policer burst, in bytes; below a few packets' worth TCP
+/// cannot reach the cap at all.
+const MIN_BURST_BYTES: u64 = 16 * 1024;
+
+/// One capped peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cap {
+    addr: IpAddr,
+    upload_kbps: Option<u32>,
+    download_kbps: Option<u32>,
+}
+
+/// Bring `interface`'s qdiscs in line with the caps in `config`. Nothing is
+/// run when the caps are unchanged, and hosts that never cap anyone only
+/// look for leftovers at startup.
+pub async fn sync(
+    interface: &str,
+    config: &DaemonConfig,
+    prev: Option<&DaemonConfig>,
+) -> Result<(), PlatformError> {
+    let caps = peer_caps(config);
+    if let Some(prev) = prev
+        && peer_caps(prev) == caps
+    {
+        return Ok(());
+    }
+    let capped = config.peers.iter().filter(|p| is_capped(p)).count();
+    if capped > caps.len() {
+        warn!(
+            interface,
+            peers = capped - caps.len(),
+            "capped peers without a host address are not limited"
+        );
+    }
+    let script = script(interface, &caps)?;
+    clear(interface).await?;
+    if caps.is_empty() {
+        return Ok(());
+    }
+    tc(&script).await?;
+    info!(interface, peers = caps.len(), "shaping capped peers");
+    Ok(())
+}
+
+/// The caps in `config`, in peer order. A capped peer without a host
+/// address among its allowed IPs cannot be matched, so it is left out.
+fn peer_caps(config: &DaemonConfig) -> Vec<Cap> {
+    config
+        .peers
+        .iter()
+        .filter(|p| is_capped(p))
+        .filter_map(|p| {
+            let addr = p.allowed_ips.iter().find_map(|ip| match parse_cidr(ip) {
+                Ok((addr @ IpAddr::V4(_), 32)) | Ok((addr @ IpAddr::V6(_), 128)) => Some(addr),
+                _ => None,
+            })?;
+            Some(Cap {
+                addr,
+                upload_kbps: p.upload_kbps,
+                download_kbps: p.download_kbps,
+            })
+        })
+        .collect()
+}
+
+/// Whether `peer` has either cap.
+fn is_capped(peer: &DaemonPeer) -> bool {
+    peer.upload_kbps.is_some() || peer.download_kbps.is_some()
+}
+
+/// Delete both of `interface`'s qdiscs. Either may be missing, which `tc`
+/// reports as a failure, and without `tc` installed there are none.
+async fn clear(interface: &str) -> Result<(), PlatformError> {
+    for kind in ["root", "ingress"] {
+        let output = match Command::new("tc")
+            .args(["qdisc", "del", "dev", interface, kind])
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+        {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            result => result?,
+        };
+        if !output.status.success() {
+            debug!(
+                interface,
+                kind,
+                stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+                "no qdisc to delete"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// A `tc -batch` script installing `caps` on `interface`: an HTB root with
+/// a class and filter per download cap, then an ingress qdisc with a
+/// policing filter per upload cap.
+fn script(interface: &str, caps: &[Cap]) -> Result<String, PlatformError> {
+    check_interface_name(interface)?;
+    let mut script = String::new();
+    let downloads: Vec<_> = caps
+        .iter()
+        .filter_map(|c| Some((c.addr, c.download_kbps?)))
+        .collect();
+    if !downloads.is_empty() {
+        let _ = writeln!(script, "qdisc add dev {interface} root handle 1: htb");
+    }
+    for (i, (addr, kbps)) in downloads.into_iter().enumerate() {
+        let class = format!("1:{:x}", i + 1);
+        let _ = writeln!(
+            script,
+            "class add dev {interface} parent 1: classid {class} htb rate {kbps}kbit ceil {kbps}kbit"
+        );
+        let _ = writeln!(
+            script,
+            "filter add dev {interface} parent 1: {} dst {} flowid {class}",
+            matcher(addr),
+            host(addr)
+        );
+    }
+    let uploads: Vec<_> = caps
+        .iter()
+        .filter_map(|c| Some((c.addr, c.upload_kbps?)))
+        .collect();
+    if !uploads.is_empty() {
+        let _ = writeln!(script, "qdisc add dev {interface} handle ffff: ingress");
+    }
+    for (addr, kbps) in uploads {
+        let burst = (u64::from(kbps) * 1000 / 8 / 10).max(MIN_BURST_BYTES);
+        let _ = writeln!(
+            script,
+            "filter add dev {interface} parent ffff: {} src {} \
+             police rate {kbps}kbit burst {burst} drop flowid :1",
+            matcher(addr),
+            host(addr)
+        );
+    }
+    Ok(script)
+}
+
+/// The start of a u32 filter matching `addr`'s family, up to the direction.
+fn matcher(addr: IpAddr) -> &'static str {
+    if addr.is_ipv4() {
+        "protocol ip prio 1 u32 match ip"
+    } else {
+        "protocol ipv6 prio 2 u32 match ip6"
+    }
+}
+
+/// `addr` as a host prefix.
+fn host(addr: IpAddr) -> String {
+    match addr {
+        IpAddr::V4(a) => format!("{a}/32"),
+        IpAddr::V6(a) => format!("{a}/128"),
+    }
+}
+
+/// Run `script` through `tc -batch -`.
+async fn tc(script: &str) -> Result<(), PlatformError> {
+    let mut child = Command::new("tc")
+        .args(["-batch", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(script.as_bytes()).await?;
+    drop(stdin);
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(PlatformError::Interface(format!(
+            "tc failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cap(addr: &str, upload_kbps: Option<u32>, download_kbps: Option<u32>) -> Cap {
+        Cap {
+            addr: addr.parse().unwrap(),
+            upload_kbps,
+            download_kbps,
+        }
+    }
+
+    #[test]
+    fn shapes_downloads_and_polices_uploads() {
+        let caps = [
+            cap("10.0.0.2", Some(1000), Some(8000)),
+            cap("10.0.0.3", None, Some(500)),
+        ];
+        assert_eq!(
+            script("wwg0", &caps).unwrap(),
+            "qdisc add dev wwg0 root handle 1: htb\n\
+             class add dev wwg0 parent 1: classid 1:1 htb rate 8000kbit ceil 8000kbit\n\
+             filter add dev wwg0 parent 1: protocol ip prio 1 u32 match ip dst 10.0.0.2/32 \
+             flowid 1:1\n\
+             class add dev wwg0 parent 1: classid 1:2 htb rate 500kbit ceil 500kbit\n\
+             filter add dev wwg0 parent 1: protocol ip prio 1 u32 match ip dst 10.0.0.3/32 \
+             flowid 1:2\n\
+             qdisc add dev wwg0 handle ffff: ingress\n\
+             filter add dev wwg0 parent ffff: protocol ip prio 1 u32 match ip src 10.0.0.2/32 \
+             police rate 1000kbit burst 16384 drop flowid :1\n"
+        );
+    }
+
+    #[test]
+    fn matches_ipv6_peers() {
+        let script = script("wwg1", &[cap("fd00::2", Some(100_000), None)]).unwrap();
+        assert!(!script.contains("htb"));
+        assert!(script.contains(
+            "protocol ipv6 prio 2 u32 match ip6 src fd00::2/128 \
+             police rate 100000kbit burst 1250000 drop"
+        ));
+    }
+
+    #[test]
+    fn class_ids_are_hex() {
+        let caps: Vec<_> = (0..10)
+            .map(|i| cap(&format!("10.0.0.{}", i + 2), None, Some(100)))
+            .collect();
+        assert!(script("wwg0", &caps).unwrap().contains("classid 1:a "));
+    }
+
+    #[test]
+    fn rejects_injected_interface_names() {
+        assert!(script("wwg0 root\nqdisc del dev eth0", &[]).is_err());
+        assert!(script("", &[]).is_err());
+    }
+}
@@ -0,0 +1,617 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! WireGuard in userspace, for hosts without the kernel module such as
+//! containers and old kernels. Each interface is a TUN device served by its
+//! own process, e.g. `boringtun-cli` or `wireguard-go`, which the daemon
+//! starts and then configures over the cross-platform UAPI socket
+//! (<https://www.wireguard.com/xplatform/>). Addresses and link state still
+//! go through rtnetlink.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tracing::{debug, info};
+use wirewarden_types::daemon::{DaemonConfig, DaemonPeer};
+
+use super::linux::{assign_address, delete_link, link_addresses, set_link_mtu, set_link_up};
+use super::{DeviceState, PeerState, Platform, PlatformError, Transfer, decode_key, has_prefix};
+
+/// Where userspace implementations serve their UAPI sockets.
+pub const SOCKET_DIR: &str = "/var/run/wireguard";
+
+/// How long a started implementation has to serve its socket.
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct UserspacePlatform;
+
+impl Platform for UserspacePlatform {
+    async fn ensure_interface(name: &str) -> Result<(), PlatformError> {
+        if Self::interface_exists(name).await? {
+            debug!(interface = name, "userspace interface already running");
+            return Ok(());
+        }
+        start(name, &super::selected().1.userspace_command).await
+    }
+
+    async fn remove_interface(name: &str) -> Result<(), PlatformError> {
+        if Self::interface_exists(name).await? {
+            info!(interface = name, "removing userspace interface");
+        }
+        // The implementation exits once its TUN device is gone.
+        delete_link(name).await?;
+        match tokio::fs::remove_file(socket_path(name)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn apply_config(
+        name: &str,
+        config: &DaemonConfig,
+        prev: Option<&DaemonConfig>,
+    ) -> Result<(), PlatformError> {
+        Self::ensure_interface(name).await?;
+
+        let live = parse_get(&uapi(name, "get=1\n\n").await?)?;
+        if let Some(request) = set_request(&live, config, prev)? {
+            uapi(name, &request).await?;
+        }
+
+        let address = crate::plan::host_cidr(&config.server.address);
+        if link_addresses(name).await? != [address] {
+            assign_address(name, &config.server.address).await?;
+        }
+        if let Some(mtu) = config.server.mtu {
+            set_link_mtu(name, mtu).await?;
+        }
+        set_link_up(name).await?;
+
+        info!(
+            interface = name,
+            server = %config.server.name,
+            "applied userspace configuration"
+        );
+        Ok(())
+    }
+
+    async fn interface_exists(name: &str) -> Result<bool, PlatformError> {
+        // A socket left by an implementation that died refuses connections.
+        Ok(UnixStream::connect(socket_path(name)).await.is_ok())
+    }
+
+    async fn device_state(name: &str) -> Result<DeviceState, PlatformError> {
+        let device = parse_get(&uapi(name, "get=1\n\n").await?)?;
+        let peers = device
+            .peers
+            .iter()
+            .map(|peer| PeerState {
+                public_key: BASE64.encode(peer.public_key),
+                allowed_ips: peer.allowed_ips.clone(),
+                endpoint: peer.endpoint.clone(),
+                persistent_keepalive: peer.persistent_keepalive,
+                preshared_key: peer.preshared_key.map(|k| BASE64.encode(k)),
+            })
+            .collect();
+        Ok(DeviceState {
+            private_key: device.private_key.map(|k| BASE64.encode(k)),
+            listen_port: device.listen_port,
+            addresses: link_addresses(name).await?,
+            peers,
+        })
+    }
+
+    async fn peer_handshakes(
+        name: &str,
+    ) -> Result<HashMap<String, Option<SystemTime>>, PlatformError> {
+        let device = parse_get(&uapi(name, "get=1\n\n").await?)?;
+        // Peers without a handshake report a zero time, as in the kernel.
+        Ok(device
+            .peers
+            .iter()
+            .map(|peer| {
+                let last =
+                    (!peer.last_handshake.is_zero()).then(|| UNIX_EPOCH + peer.last_handshake);
+                (BASE64.encode(peer.public_key), last)
+            })
+            .collect())
+    }
+
+    async fn peer_transfer(name: &str) -> Result<HashMap<String, Transfer>, PlatformError> {
+        let device = parse_get(&uapi(name, "get=1\n\n").await?)?;
+        Ok(device
+            .peers
+            .iter()
+            .map(|peer| (BASE64.encode(peer.public_key), peer.transfer))
+            .collect())
+    }
+
+    async fn remove_peers(name: &str, public_keys: &[String]) -> Result<(), PlatformError> {
+        let mut request = String::from("set=1\n");
+        for key in public_keys {
+            let _ = writeln!(
+                request,
+                "public_key={}\nremove=true",
+                to_hex(&decode_key(key)?)
+            );
+        }
+        request.push('\n');
+        uapi(name, &request).await.map(drop)
+    }
+
+    async fn rename_interface(old: &str, _new: &str) -> Result<(), PlatformError> {
+        // The implementation keeps serving the socket under the old name.
+        Err(PlatformError::Interface(format!(
+            "cannot rename {old}: userspace interfaces are found by their socket name"
+        )))
+    }
+
+    async fn list_managed_interfaces(
+        prefixes: &[&str],
+    ) -> Result<HashMap<String, String>, PlatformError> {
+        let mut entries = match tokio::fs::read_dir(SOCKET_DIR).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut result = HashMap::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            let Some(name) = file_name.to_str().and_then(|n| n.strip_suffix(".sock")) else {
+                continue;
+            };
+            if !prefixes.iter().any(|p| has_prefix(name, p)) {
+                continue;
+            }
+            // Stale sockets are skipped, like interfaces that are gone.
+            let device = match uapi(name, "get=1\n\n").await {
+                Ok(response) => parse_get(&response)?,
+                Err(e) => {
+                    debug!(interface = name, error = %e, "skipping unreachable userspace interface");
+                    continue;
+                }
+            };
+            if let Some(key) = device.private_key {
+                debug!(interface = name, "discovered managed userspace interface");
+                result.insert(name.to_owned(), BASE64.encode(key));
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn socket_path(name: &str) -> PathBuf {
+    Path::new(SOCKET_DIR).join(format!("{name}.sock"))
+}
+
+/// Run `command` with the interface name appended and wait for its socket.
+/// Both daemonizing commands and ones that stay in the foreground work.
+async fn start(name: &str, command: &[String]) -> Result<(), PlatformError> {
+    let (program, args) = command.split_first().ok_or_else(|| {
+        PlatformError::Interface("wireguard.userspace_command is empty".to_string())
+    })?;
+    tokio::fs::create_dir_all(SOCKET_DIR).await?;
+
+    info!(interface = name, program, "starting userspace wireguard");
+    let mut child = tokio::process::Command::new(program)
+        .args(args)
+        .arg(name)
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(|e| PlatformError::Interface(format!("cannot start {program}: {e}")))?;
+
+    let deadline = Instant::now() + START_TIMEOUT;
+    loop {
+        if UserspacePlatform::interface_exists(name).await? {
+            return Ok(());
+        }
+        if let Some(status) = child.try_wait()?
+            && !status.success()
+        {
+            return Err(PlatformError::Interface(format!(
+                "{program} exited with {status}"
+            )));
+        }
+        if Instant::now() >= deadline {
+            let _ = child.start_kill();
+            return Err(PlatformError::Interface(format!(
+                "{program} did not serve {} within {} seconds",
+                socket_path(name).display(),
+                START_TIMEOUT.as_secs()
+            )));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Send one request and return the response, failing on a non-zero errno.
+async fn uapi(name: &str, request: &str) -> Result<String, PlatformError> {
+    let mut stream = UnixStream::connect(socket_path(name)).await?;
+    stream.write_all(request.as_bytes()).await?;
+
+    // A response ends with an empty line; the connection stays open.
+    let mut reader = BufReader::new(stream);
+    let mut response = String::new();
+    loop {
+        let start = response.len();
+        if reader.read_line(&mut response).await? == 0 || &response[start..] == "\n" {
+            break;
+        }
+    }
+
+    match response.lines().find_map(|l| l.strip_prefix("errno=")) {
+        Some("0") => Ok(response),
+        Some(errno) => Err(PlatformError::Interface(format!(
+            "{name}: userspace wireguard rejected the request (errno {errno})"
+        ))),
+        None => Err(PlatformError::Interface(format!(
+            "{name}: userspace wireguard closed the connection"
+        ))),
+    }
+}
+
+/// A device as a `get=1` response describes it.
+#[derive(Debug, Default, PartialEq)]
+struct UapiDevice {
+    private_key: Option<[u8; 32]>,
+    listen_port: u16,
+    fwmark: u32,
+    peers: Vec<UapiPeer>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct UapiPeer {
+    public_key: [u8; 32],
+    preshared_key: Option<[u8; 32]>,
+    endpoint: Option<String>,
+    persistent_keepalive: u16,
+    allowed_ips: Vec<String>,
+    /// Since the Unix epoch; zero before the first handshake.
+    last_handshake: Duration,
+    transfer: Transfer,
+}
+
+fn parse_get(response: &str) -> Result<UapiDevice, PlatformError> {
+    let mut device = UapiDevice::default();
+    for line in response.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        // Peer attributes follow the `public_key` line that opens the peer.
+        match (key, device.peers.last_mut()) {
+            ("private_key", _) => device.private_key = nonzero(from_hex(value)?),
+            ("listen_port", _) => device.listen_port = parse(line, value)?,
+            ("fwmark", _) => device.fwmark = parse(line, value)?,
+            ("public_key", _) => device.peers.push(UapiPeer {
+                public_key: from_hex(value)?,
+                ..UapiPeer::default()
+            }),
+            ("preshared_key", Some(peer)) => peer.preshared_key = nonzero(from_hex(value)?),
+            ("endpoint", Some(peer)) => peer.endpoint = Some(value.to_owned()),
+            ("persistent_keepalive_interval", Some(peer)) => {
+                peer.persistent_keepalive = parse(line, value)?;
+            }
+            ("allowed_ip", Some(peer)) => peer.allowed_ips.push(value.to_owned()),
+            ("last_handshake_time_sec", Some(peer)) => {
+                let nanos = peer.last_handshake.subsec_nanos();
+                peer.last_handshake = Duration::new(parse(line, value)?, nanos);
+            }
+            ("last_handshake_time_nsec", Some(peer)) => {
+                let secs = peer.last_handshake.as_secs();
+                peer.last_handshake = Duration::new(secs, parse(line, value)?);
+            }
+            ("rx_bytes", Some(peer)) => peer.transfer.rx_bytes = parse(line, value)?,
+            ("tx_bytes", Some(peer)) => peer.transfer.tx_bytes = parse(line, value)?,
+            _ => {}
+        }
+    }
+    Ok(device)
+}
+
+/// The `set=1` request that brings `live` to `config`, or `None` if it is
+/// already there. Peers are updated in place rather than replaced, so their
+/// sessions survive; a peer's endpoint is only re-sent when the peer is new
+/// or its config changed since `prev`, so roaming clients are not reset.
+fn set_request(
+    live: &UapiDevice,
+    config: &DaemonConfig,
+    prev: Option<&DaemonConfig>,
+) -> Result<Option<String>, PlatformError> {
+    let mut request = String::new();
+
+    let private_key = decode_key(&config.server.private_key)?;
+    if live.private_key != Some(private_key) {
+        let _ = writeln!(request, "private_key={}", to_hex(&private_key));
+    }
+    let listen_port = config.server.listen_port as u16;
+    if live.listen_port != listen_port {
+        let _ = writeln!(request, "listen_port={listen_port}");
+    }
+    let fwmark = config.server.fwmark.unwrap_or(0);
+    if live.fwmark != fwmark {
+        let _ = writeln!(request, "fwmark={fwmark}");
+    }
+
+    let wanted: Vec<[u8; 32]> = config
+        .peers
+        .iter()
+        .map(|p| decode_key(&p.public_key))
+        .collect::<Result<_, _>>()?;
+    for peer in live
+        .peers
+        .iter()
+        .filter(|p| !wanted.contains(&p.public_key))
+    {
+        let _ = writeln!(
+            request,
+            "public_key={}\nremove=true",
+            to_hex(&peer.public_key)
+        );
+    }
+
+    for (peer, key) in config.peers.iter().zip(&wanted) {
+        let keepalive = peer.keepalive(&config.network);
+        let preshared_key = peer.preshared_key.as_deref().map(decode_key).transpose()?;
+        let unchanged_since_prev = prev.is_some_and(|prev| prev.peers.contains(peer));
+        let in_sync = live
+            .peers
+            .iter()
+            .find(|p| p.public_key == *key)
+            .is_some_and(|p| {
+                unchanged_since_prev
+                    && p.preshared_key == preshared_key
+                    && p.persistent_keepalive == keepalive
+                    && same_ips(&p.allowed_ips, &peer.allowed_ips)
+            });
+        if !in_sync {
+            write_peer(&mut request, peer, key, preshared_key, keepalive);
+        }
+    }
+
+    if request.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(format!("set=1\n{request}\n")))
+}
+
+fn write_peer(
+    request: &mut String,
+    peer: &DaemonPeer,
+    key: &[u8; 32],
+    preshared_key: Option<[u8; 32]>,
+    keepalive: u16,
+) {
+    let _ = writeln!(request, "public_key={}", to_hex(key));
+    // An all-zero key clears one set before.
+    let _ = writeln!(
+        request,
+        "preshared_key={}",
+        to_hex(&preshared_key.unwrap_or_default())
+    );
+    // Hostnames are left to the peer to roam in, as with the kernel.
+    if let Some(endpoint) = peer
+        .endpoint
+        .as_deref()
+        .and_then(|e| e.parse::<SocketAddr>().ok())
+    {
+        let _ = writeln!(request, "endpoint={endpoint}");
+    }
+    let _ = writeln!(request, "persistent_keepalive_interval={keepalive}");
+    let _ = writeln!(request, "replace_allowed_ips=true");
+    for ip in &peer.allowed_ips {
+        let _ = writeln!(request, "allowed_ip={ip}");
+    }
+}
+
+fn same_ips(live: &[String], wanted: &[String]) -> bool {
+    live.len() == wanted.len() && wanted.iter().all(|ip| live.contains(ip))
+}
+
+fn parse<T>(line: &str, value: &str) -> Result<T, PlatformError>
+where
+    T: FromStr,
+{
+    value
+        .parse()
+        .map_err(|_| PlatformError::Interface(format!("malformed UAPI line: {line}")))
+}
+
+fn nonzero(key: [u8; 32]) -> Option<[u8; 32]> {
+    (key != [0; 32]).then_some(key)
+}
+
+fn to_hex(key: &[u8; 32]) -> String {
+    key.iter().fold(String::with_capacity(64), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    })
+}
+
+fn from_hex(hex: &str) -> Result<[u8; 32], PlatformError> {
+    if hex.len() != 64 {
+        return Err(PlatformError::InvalidKeyLength(hex.len() / 2));
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = hex
+            .get(i * 2..i * 2 + 2)
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .ok_or_else(|| PlatformError::Interface(format!("malformed hex key: {hex}")))?;
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+    use wirewarden_types::daemon::{CONFIG_VERSION, DaemonNetworkInfo, DaemonServerInfo};
+
+    const PRIVATE: &str = "YWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWE=";
+    const PEER_A: &str = "Y2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjYWE=";
+    const PEER_B: &str = "ZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGQ=";
+
+    fn hex(b64: &str) -> String {
+        to_hex(&decode_key(b64).unwrap())
+    }
+
+    fn config() -> DaemonConfig {
+        DaemonConfig {
+            version: CONFIG_VERSION,
+            server: DaemonServerInfo {
+                id: Uuid::nil(),
+                name: "relay".into(),
+                private_key: PRIVATE.into(),
+                public_key: "YmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmI=".into(),
+                address: "10.0.0.1".into(),
+                listen_port: 51820,
+                manage_nat: false,
+                fwmark: None,
+                route_table: None,
+                rule_priority: None,
+                mtu: None,
+            },
+            network: DaemonNetworkInfo {
+                id: Uuid::nil(),
+                name: "home".into(),
+                cidr: "10.0.0.0/24".into(),
+                persistent_keepalive: 25,
+                dns_servers: Vec::new(),
+                dns_zone: None,
+                acl: Vec::new(),
+            },
+            peers: vec![DaemonPeer {
+                public_key: PEER_A.into(),
+                allowed_ips: vec!["10.0.0.2/32".into()],
+                endpoint: None,
+                preshared_key: None,
+                persistent_keepalive: None,
+                upload_kbps: None,
+                download_kbps: None,
+            }],
+        }
+    }
+
+    /// What a device running [`config`] answers to `get=1`.
+    fn response() -> String {
+        format!(
+            "private_key={}\nlisten_port=51820\n\
+             public_key={}\npreshared_key={}\nendpoint=203.0.113.7:40000\n\
+             last_handshake_time_sec=1700000000\nlast_handshake_time_nsec=5\n\
+             tx_bytes=92\nrx_bytes=148\npersistent_keepalive_interval=25\n\
+             allowed_ip=10.0.0.2/32\nprotocol_version=1\nerrno=0\n\n",
+            hex(PRIVATE),
+            hex(PEER_A),
+            "0".repeat(64),
+        )
+    }
+
+    #[test]
+    fn parses_get_response() {
+        let device = parse_get(&response()).unwrap();
+        assert_eq!(device.private_key, Some(decode_key(PRIVATE).unwrap()));
+        assert_eq!(device.listen_port, 51820);
+        assert_eq!(
+            device.peers,
+            [UapiPeer {
+                public_key: decode_key(PEER_A).unwrap(),
+                preshared_key: None,
+                endpoint: Some("203.0.113.7:40000".into()),
+                persistent_keepalive: 25,
+                allowed_ips: vec!["10.0.0.2/32".into()],
+                last_handshake: Duration::new(1_700_000_000, 5),
+                transfer: Transfer {
+                    rx_bytes: 148,
+                    tx_bytes: 92,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn in_sync_needs_no_request() {
+        let live = parse_get(&response()).unwrap();
+        assert_eq!(
+            set_request(&live, &config(), Some(&config())).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn new_device_gets_everything() {
+        let request = set_request(&UapiDevice::default(), &config(), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            request,
+            format!(
+                "set=1\nprivate_key={}\nlisten_port=51820\n\
+                 public_key={}\npreshared_key={}\npersistent_keepalive_interval=25\n\
+                 replace_allowed_ips=true\nallowed_ip=10.0.0.2/32\n\n",
+                hex(PRIVATE),
+                hex(PEER_A),
+                "0".repeat(64),
+            )
+        );
+    }
+
+    #[test]
+    fn peers_are_swapped_in_place() {
+        let live = parse_get(&response()).unwrap();
+        let mut next = config();
+        next.peers[0].public_key = PEER_B.into();
+        let request = set_request(&live, &next, Some(&config())).unwrap().unwrap();
+        assert!(request.starts_with(&format!(
+            "set=1\npublic_key={}\nremove=true\npublic_key={}\n",
+            hex(PEER_A),
+            hex(PEER_B)
+        )));
+        assert!(!request.contains("private_key") && !request.contains("listen_port"));
+    }
+
+    #[test]
+    fn fwmark_is_set_and_cleared() {
+        let live = parse_get(&response()).unwrap();
+        let mut next = config();
+        next.server.fwmark = Some(51820);
+        let request = set_request(&live, &next, Some(&config())).unwrap().unwrap();
+        assert_eq!(request, "set=1\nfwmark=51820\n\n");
+
+        let response =
+            response().replace("listen_port=51820\n", "listen_port=51820\nfwmark=51820\n");
+        let marked = parse_get(&response).unwrap();
+        assert_eq!(marked.fwmark, 51820);
+        let request = set_request(&marked, &config(), Some(&next)).unwrap().unwrap();
+        assert_eq!(request, "set=1\nfwmark=0\n\n");
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let key = decode_key(PRIVATE).unwrap();
+        assert_eq!(from_hex(&to_hex(&key)).unwrap(), key);
+        assert!(from_hex("zz").is_err());
+        assert!(from_hex(&"é".repeat(32)).is_err());
+    }
+}
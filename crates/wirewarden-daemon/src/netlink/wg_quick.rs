@@ -0,0 +1,223 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Interfaces brought up by `wg-quick(8)` from files it reads under
+//! `/etc/wireguard`, for hosts where the daemon has no direct way to manage
+//! WireGuard. `wg-quick` creates the interface, assigns its address and
+//! adds routes for its peers the platform's own way; later changes that
+//! keep the address go through `wg syncconf`, so sessions with unchanged
+//! peers carry on.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use tracing::{debug, info};
+use wirewarden_types::daemon::DaemonConfig;
+
+use super::wg_tool::{parse_dump, run, wg_conf};
+use super::{DeviceState, Platform, PlatformError, Transfer, decode_key, has_prefix};
+use crate::cache::write_private;
+
+/// Where the rendered configs go; `wg-quick` looks here for bare names.
+pub const CONFIG_DIR: &str = "/etc/wireguard";
+
+pub struct WgQuickPlatform;
+
+impl Platform for WgQuickPlatform {
+    async fn ensure_interface(name: &str) -> Result<(), PlatformError> {
+        if Self::interface_exists(name).await? {
+            debug!(interface = name, "interface already exists");
+            return Ok(());
+        }
+        info!(interface = name, "creating wireguard interface");
+        let path = conf_path(name);
+        if !tokio::fs::try_exists(&path).await? {
+            write_conf(name, "[Interface]\n").await?;
+        }
+        wg_quick("up", name).await
+    }
+
+    async fn remove_interface(name: &str) -> Result<(), PlatformError> {
+        let path = conf_path(name);
+        if Self::interface_exists(name).await? {
+            info!(interface = name, "removing interface");
+            // wg-quick only takes down what a config file names.
+            if !tokio::fs::try_exists(&path).await? {
+                write_conf(name, "[Interface]\n").await?;
+            }
+            wg_quick("down", name).await?;
+        }
+        match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn apply_config(
+        name: &str,
+        config: &DaemonConfig,
+        _prev: Option<&DaemonConfig>,
+    ) -> Result<(), PlatformError> {
+        let path = conf_path(name);
+        let address = crate::plan::host_cidr(&config.server.address);
+        let previous = match tokio::fs::read_to_string(&path).await {
+            Ok(conf) => Some(conf),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let rendered = wg_conf(config, Some(&address))?;
+        write_conf(name, &rendered).await?;
+
+        let unchanged = previous.as_deref().is_some_and(|previous| {
+            ["Address", "MTU"]
+                .iter()
+                .all(|key| conf_value(previous, key) == conf_value(&rendered, key))
+        });
+        if !Self::interface_exists(name).await? {
+            wg_quick("up", name).await?;
+        } else if unchanged {
+            let conf = wg_conf(config, None)?;
+            run("wg", &["syncconf", name, "/dev/stdin"], Some(&conf)).await?;
+        } else {
+            // Only wg-quick knows the routes it added for the old address,
+            // and syncconf leaves the MTU alone.
+            wg_quick("down", name).await?;
+            wg_quick("up", name).await?;
+            info!(interface = name, %address, "reassigned address via wg-quick");
+        }
+
+        info!(
+            interface = name,
+            server = %config.server.name,
+            "applied configuration via wg-quick"
+        );
+        Ok(())
+    }
+
+    async fn interface_exists(name: &str) -> Result<bool, PlatformError> {
+        Ok(interfaces().await?.iter().any(|n| n == name))
+    }
+
+    async fn device_state(name: &str) -> Result<DeviceState, PlatformError> {
+        let mut state = parse_dump(&run("wg", &["show", name, "dump"], None).await?)?.state;
+        // wg-quick assigned what the file says, however the platform shows it.
+        let conf = tokio::fs::read_to_string(conf_path(name)).await?;
+        state.addresses = conf_value(&conf, "Address").into_iter().collect();
+        Ok(state)
+    }
+
+    async fn peer_handshakes(
+        name: &str,
+    ) -> Result<HashMap<String, Option<SystemTime>>, PlatformError> {
+        Ok(parse_dump(&run("wg", &["show", name, "dump"], None).await?)?.handshakes)
+    }
+
+    async fn peer_transfer(name: &str) -> Result<HashMap<String, Transfer>, PlatformError> {
+        Ok(parse_dump(&run("wg", &["show", name, "dump"], None).await?)?.transfer)
+    }
+
+    async fn remove_peers(name: &str, public_keys: &[String]) -> Result<(), PlatformError> {
+        if public_keys.is_empty() {
+            return Ok(());
+        }
+        // The file still lists them until the next apply rewrites it.
+        let mut args = vec!["set", name];
+        for key in public_keys {
+            decode_key(key)?;
+            args.extend(["peer", key.as_str(), "remove"]);
+        }
+        run("wg", &args, None).await.map(drop)
+    }
+
+    async fn rename_interface(old: &str, new: &str) -> Result<(), PlatformError> {
+        // wg-quick names an interface after its file, so the file moves
+        // while the interface is down.
+        wg_quick("down", old).await?;
+        tokio::fs::rename(conf_path(old), conf_path(new)).await?;
+        wg_quick("up", new).await?;
+        info!(from = old, to = new, "renamed interface via wg-quick");
+        Ok(())
+    }
+
+    async fn list_managed_interfaces(
+        prefixes: &[&str],
+    ) -> Result<HashMap<String, String>, PlatformError> {
+        let mut result = HashMap::new();
+        for name in interfaces().await? {
+            if !prefixes.iter().any(|p| has_prefix(&name, p)) {
+                continue;
+            }
+            let state = parse_dump(&run("wg", &["show", &name, "dump"], None).await?)?.state;
+            if let Some(key) = state.private_key {
+                debug!(interface = %name, "discovered managed interface");
+                result.insert(name, key);
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// The file `wg-quick` reads for interface `name`.
+fn conf_path(name: &str) -> PathBuf {
+    PathBuf::from(CONFIG_DIR).join(format!("{name}.conf"))
+}
+
+/// Write the file for `name`, readable by root only as `wg-quick` expects.
+async fn write_conf(name: &str, conf: &str) -> Result<(), PlatformError> {
+    tokio::fs::create_dir_all(CONFIG_DIR).await?;
+    write_private(&conf_path(name), conf.as_bytes()).await?;
+    Ok(())
+}
+
+/// Run `wg-quick up` or `down` on the rendered file. The full path is
+/// passed so platforms that search other directories still find it.
+async fn wg_quick(action: &str, name: &str) -> Result<(), PlatformError> {
+    let path = conf_path(name);
+    let path = path.to_string_lossy();
+    run("wg-quick", &[action, &path], None).await.map(drop)
+}
+
+/// Every WireGuard interface, whichever tool created it.
+async fn interfaces() -> Result<Vec<String>, PlatformError> {
+    let out = run("wg", &["show", "interfaces"], None).await?;
+    Ok(out.split_whitespace().map(str::to_owned).collect())
+}
+
+/// The value a rendered file gives `key`, such as its `Address`.
+fn conf_value(conf: &str, key: &str) -> Option<String> {
+    conf.lines().find_map(|line| {
+        let (k, value) = line.split_once('=')?;
+        (k.trim() == key).then(|| value.trim().to_owned())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_files_after_interfaces() {
+        assert_eq!(conf_path("wwg0"), PathBuf::from("/etc/wireguard/wwg0.conf"));
+    }
+
+    #[test]
+    fn reads_values_back() {
+        let conf = "[Interface]\nPrivateKey = k\nListenPort = 51820\nAddress = fd00::1/64\n\
+                    MTU = 1380\n\n[Peer]\n";
+        assert_eq!(conf_value(conf, "Address").as_deref(), Some("fd00::1/64"));
+        assert_eq!(conf_value(conf, "MTU").as_deref(), Some("1380"));
+        assert_eq!(conf_value("[Interface]\n", "Address"), None);
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Driving interfaces through `wg(8)`, shared by the backends that manage
+//! WireGuard with the command-line tools rather than a kernel API of their
+//! own.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::process::Stdio;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use wirewarden_types::daemon::DaemonConfig;
+
+use super::{DeviceState, PeerState, PlatformError, Transfer, decode_key, parse_cidr};
+
+/// Run `program`, feeding `input` on stdin, and return its stdout. Keys only
+/// ever travel on stdin, never in arguments other users can see.
+pub(super) async fn run(
+    program: &str,
+    args: &[&str],
+    input: Option<&str>,
+) -> Result<String, PlatformError> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| PlatformError::Interface(format!("cannot run {program}: {e}")))?;
+    if let Some(input) = input {
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(input.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(PlatformError::Interface(format!(
+            "{program} {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The configuration in `wg(8)`'s file format, with `address` and the MTU
+/// added for `wg-quick(8)` to assign. Keys and addresses are checked first so nothing
+/// from the API can add lines of its own.
+pub(super) fn wg_conf(
+    config: &DaemonConfig,
+    address: Option<&str>,
+) -> Result<String, PlatformError> {
+    let mut conf = String::new();
+    decode_key(&config.server.private_key)?;
+    let _ = writeln!(conf, "[Interface]");
+    let _ = writeln!(conf, "PrivateKey = {}", config.server.private_key);
+    let _ = writeln!(conf, "ListenPort = {}", config.server.listen_port);
+    if let Some(fwmark) = config.server.fwmark {
+        let _ = writeln!(conf, "FwMark = {fwmark}");
+    }
+    if let Some(address) = address {
+        parse_cidr(address)?;
+        let _ = writeln!(conf, "Address = {address}");
+        if let Some(mtu) = config.server.mtu {
+            let _ = writeln!(conf, "MTU = {mtu}");
+        }
+    }
+
+    for peer in &config.peers {
+        decode_key(&peer.public_key)?;
+        let _ = writeln!(conf, "\n[Peer]");
+        let _ = writeln!(conf, "PublicKey = {}", peer.public_key);
+        if let Some(psk) = &peer.preshared_key {
+            decode_key(psk)?;
+            let _ = writeln!(conf, "PresharedKey = {psk}");
+        }
+        // Hostnames are left to the peer to roam in, as with the kernel.
+        if let Some(endpoint) = peer
+            .endpoint
+            .as_deref()
+            .and_then(|e| e.parse::<SocketAddr>().ok())
+        {
+            let _ = writeln!(conf, "Endpoint = {endpoint}");
+        }
+        if !peer.allowed_ips.is_empty() {
+            for ip in &peer.allowed_ips {
+                parse_cidr(ip)?;
+            }
+            let _ = writeln!(conf, "AllowedIPs = {}", peer.allowed_ips.join(", "));
+        }
+        let keepalive = peer.keepalive(&config.network);
+        if keepalive > 0 {
+            let _ = writeln!(conf, "PersistentKeepalive = {keepalive}");
+        }
+    }
+    Ok(conf)
+}
+
+/// What `wg show <name> dump` reports about an interface.
+#[derive(Debug, Default)]
+pub(super) struct Dump {
+    /// Addresses are left empty; `wg` does not know them.
+    pub state: DeviceState,
+    pub handshakes: HashMap<String, Option<SystemTime>>,
+    pub transfer: HashMap<String, Transfer>,
+}
+
+/// Parse `wg show <name> dump`: the interface's keys and port, then one
+/// tab-separated line per peer.
+pub(super) fn parse_dump(dump: &str) -> Result<Dump, PlatformError> {
+    let malformed =
+        |line: &str| PlatformError::Interface(format!("malformed wg dump line: {line}"));
+    let none = |field: &str| (field != "(none)").then(|| field.to_owned());
+
+    let mut lines = dump.lines().filter(|l| !l.is_empty());
+    let header = lines.next().ok_or_else(|| malformed(""))?;
+    let fields: Vec<&str> = header.split('\t').collect();
+    let [private_key, _public_key, listen_port, _fwmark] = fields[..] else {
+        return Err(malformed(header));
+    };
+    let mut state = DeviceState {
+        private_key: none(private_key),
+        listen_port: listen_port.parse().map_err(|_| malformed(header))?,
+        ..DeviceState::default()
+    };
+
+    let mut handshakes = HashMap::new();
+    let mut transfer = HashMap::new();
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [
+            public_key,
+            psk,
+            endpoint,
+            allowed_ips,
+            handshake,
+            rx_bytes,
+            tx_bytes,
+            keepalive,
+        ] = fields[..]
+        else {
+            return Err(malformed(line));
+        };
+        let handshake: u64 = handshake.parse().map_err(|_| malformed(line))?;
+        state.peers.push(PeerState {
+            public_key: public_key.to_owned(),
+            allowed_ips: none(allowed_ips)
+                .map(|ips| ips.split(',').map(str::to_owned).collect())
+                .unwrap_or_default(),
+            endpoint: none(endpoint),
+            persistent_keepalive: match keepalive {
+                "off" => 0,
+                secs => secs.parse().map_err(|_| malformed(line))?,
+            },
+            preshared_key: none(psk),
+        });
+        // Peers without a handshake report zero, as in the kernel.
+        let last = (handshake != 0).then(|| UNIX_EPOCH + Duration::from_secs(handshake));
+        handshakes.insert(public_key.to_owned(), last);
+        let counters = Transfer {
+            rx_bytes: rx_bytes.parse().map_err(|_| malformed(line))?,
+            tx_bytes: tx_bytes.parse().map_err(|_| malformed(line))?,
+        };
+        transfer.insert(public_key.to_owned(), counters);
+    }
+    Ok(Dump {
+        state,
+        handshakes,
+        transfer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+    use wirewarden_types::daemon::{
+        CONFIG_VERSION, DaemonNetworkInfo, DaemonPeer, DaemonServerInfo,
+    };
+
+    const PRIVATE: &str = "YWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWE=";
+    const PUBLIC: &str = "YmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmI=";
+    const PEER_A: &str = "Y2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjYWE=";
+    const PEER_B: &str = "ZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGQ=";
+
+    fn config() -> DaemonConfig {
+        DaemonConfig {
+            version: CONFIG_VERSION,
+            server: DaemonServerInfo {
+                id: Uuid::nil(),
+                name: "relay".into(),
+                private_key: PRIVATE.into(),
+                public_key: PUBLIC.into(),
+                address: "10.0.0.1/24".into(),
+                listen_port: 51820,
+                manage_nat: false,
+                fwmark: None,
+                route_table: None,
+                rule_priority: None,
+                mtu: None,
+            },
+            network: DaemonNetworkInfo {
+                id: Uuid::nil(),
+                name: "home".into(),
+                cidr: "10.0.0.0/24".into(),
+                persistent_keepalive: 25,
+                dns_servers: Vec::new(),
+                dns_zone: None,
+                acl: Vec::new(),
+            },
+            peers: vec![
+                DaemonPeer {
+                    public_key: PEER_A.into(),
+                    allowed_ips: vec!["10.0.0.2/32".into(), "192.168.1.0/24".into()],
+                    endpoint: Some("203.0.113.7:51820".into()),
+                    preshared_key: Some(PEER_B.into()),
+                    persistent_keepalive: None,
+                    upload_kbps: None,
+                    download_kbps: None,
+                },
+                DaemonPeer {
+                    public_key: PEER_B.into(),
+                    allowed_ips: vec!["10.0.0.3/32".into()],
+                    endpoint: Some("relay.example.com:51820".into()),
+                    preshared_key: None,
+                    persistent_keepalive: None,
+                    upload_kbps: None,
+                    download_kbps: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn writes_wg_conf() {
+        let conf = wg_conf(&config(), None).unwrap();
+        assert_eq!(
+            conf,
+            format!(
+                "[Interface]\nPrivateKey = {PRIVATE}\nListenPort = 51820\n\
+                 \n[Peer]\nPublicKey = {PEER_A}\nPresharedKey = {PEER_B}\n\
+                 Endpoint = 203.0.113.7:51820\nAllowedIPs = 10.0.0.2/32, 192.168.1.0/24\n\
+                 PersistentKeepalive = 25\n\
+                 \n[Peer]\nPublicKey = {PEER_B}\nAllowedIPs = 10.0.0.3/32\n\
+                 PersistentKeepalive = 25\n"
+            )
+        );
+    }
+
+    #[test]
+    fn writes_address_for_wg_quick() {
+        let conf = wg_conf(&config(), Some("10.0.0.1/24")).unwrap();
+        assert!(conf.starts_with(&format!(
+            "[Interface]\nPrivateKey = {PRIVATE}\nListenPort = 51820\n\
+             Address = 10.0.0.1/24\n\n[Peer]"
+        )));
+        assert!(wg_conf(&config(), Some("10.0.0.1/24\nPostUp = true")).is_err());
+    }
+
+    #[test]
+    fn writes_mtu_only_for_wg_quick() {
+        let mut config = config();
+        config.server.mtu = Some(1380);
+        assert!(!wg_conf(&config, None).unwrap().contains("MTU"));
+        let conf = wg_conf(&config, Some("10.0.0.1/24")).unwrap();
+        assert!(conf.contains("Address = 10.0.0.1/24\nMTU = 1380\n\n[Peer]"));
+    }
+
+    #[test]
+    fn writes_peer_keepalive_over_network() {
+        let mut config = config();
+        config.network.persistent_keepalive = 0;
+        config.peers[1].persistent_keepalive = Some(15);
+        let conf = wg_conf(&config, None).unwrap();
+        assert_eq!(conf.matches("PersistentKeepalive").count(), 1);
+        assert!(conf.ends_with("AllowedIPs = 10.0.0.3/32\nPersistentKeepalive = 15\n"));
+    }
+
+    #[test]
+    fn wg_conf_rejects_injected_lines() {
+        let mut config = config();
+        config.peers[1].allowed_ips = vec!["10.0.0.3/32\n[Peer]".into()];
+        assert!(wg_conf(&config, None).is_err());
+    }
+
+    #[test]
+    fn parses_dump() {
+        let dump = format!(
+            "{PRIVATE}\t{PUBLIC}\t51820\toff\n\
+             {PEER_A}\t{PEER_B}\t203.0.113.7:40000\t10.0.0.2/32,192.168.1.0/24\t1700000000\t148\t92\t25\n\
+             {PEER_B}\t(none)\t(none)\t(none)\t0\t0\t0\toff\n"
+        );
+        let Dump {
+            state,
+            handshakes,
+            transfer,
+        } = parse_dump(&dump).unwrap();
+        assert_eq!(state.private_key.as_deref(), Some(PRIVATE));
+        assert_eq!(state.listen_port, 51820);
+        assert_eq!(
+            state.peers[0],
+            PeerState {
+                public_key: PEER_A.into(),
+                allowed_ips: vec!["10.0.0.2/32".into(), "192.168.1.0/24".into()],
+                endpoint: Some("203.0.113.7:40000".into()),
+                persistent_keepalive: 25,
+                preshared_key: Some(PEER_B.into()),
+            }
+        );
+        assert_eq!(
+            state.peers[1],
+            PeerState {
+                public_key: PEER_B.into(),
+                allowed_ips: Vec::new(),
+                endpoint: None,
+                persistent_keepalive: 0,
+                preshared_key: None,
+            }
+        );
+        assert_eq!(
+            handshakes[PEER_A],
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(handshakes[PEER_B], None);
+        assert_eq!(
+            transfer[PEER_A],
+            Transfer {
+                rx_bytes: 148,
+                tx_bytes: 92,
+            }
+        );
+        assert_eq!(transfer[PEER_B], Transfer::default());
+    }
+
+    #[test]
+    fn rejects_short_dump_lines() {
+        let dump = format!("{PRIVATE}\t{PUBLIC}\t51820\toff\n{PEER_A}\t(none)\n");
+        assert!(parse_dump(&dump).is_err());
+    }
+}
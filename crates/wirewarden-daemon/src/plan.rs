@@ -0,0 +1,612 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `wirewarden plan`: what the next reconcile would change, read from the
+//! API and the live interfaces without touching either.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use std::net::{IpAddr, SocketAddr};
+
+use reqwest::Client;
+use wirewarden_types::daemon::DaemonConfig;
+
+use crate::api;
+use crate::cache::ConfigCache;
+use crate::config::{DaemonToml, ServerEntry};
+use crate::netlink::{self, DeviceState, Platform, PlatformError, has_prefix, parse_cidr};
+
+/// One difference between an interface and the config it would be given.
+/// Key material is never shown; peers are named by their public keys.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    CreateInterface,
+    /// Named under a legacy prefix; renamed to the current one.
+    Rename {
+        prefix: String,
+    },
+    /// Renamed to the name its entry configures.
+    RenameTo {
+        name: String,
+    },
+    PrivateKey,
+    ListenPort {
+        from: u16,
+        to: u16,
+    },
+    Address {
+        from: Vec<String>,
+        to: String,
+    },
+    AddPeer {
+        public_key: String,
+        allowed_ips: Vec<String>,
+    },
+    RemovePeer {
+        public_key: String,
+    },
+    AllowedIps {
+        public_key: String,
+        from: Vec<String>,
+        to: Vec<String>,
+    },
+    Endpoint {
+        public_key: String,
+        from: Option<String>,
+        to: String,
+    },
+    Keepalive {
+        public_key: String,
+        from: u16,
+        to: u16,
+    },
+    PresharedKey {
+        public_key: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreateInterface => write!(f, "+ interface"),
+            Self::Rename { prefix } => write!(f, "~ rename to a {prefix}N name"),
+            Self::RenameTo { name } => write!(f, "~ rename to {name}"),
+            Self::PrivateKey => write!(f, "~ private key"),
+            Self::ListenPort { from, to } => write!(f, "~ listen port {from} -> {to}"),
+            Self::Address { from, to } => write!(f, "~ address {} -> {to}", list(from)),
+            Self::AddPeer {
+                public_key,
+                allowed_ips,
+            } => write!(f, "+ peer {public_key} ({})", list(allowed_ips)),
+            Self::RemovePeer { public_key } => write!(f, "- peer {public_key}"),
+            Self::AllowedIps {
+                public_key,
+                from,
+                to,
+            } => write!(
+                f,
+                "~ peer {public_key} allowed ips {} -> {}",
+                list(from),
+                list(to)
+            ),
+            Self::Endpoint {
+                public_key,
+                from,
+                to,
+            } => write!(
+                f,
+                "~ peer {public_key} endpoint {} -> {to}",
+                from.as_deref().unwrap_or("none")
+            ),
+            Self::Keepalive {
+                public_key,
+                from,
+                to,
+            } => write!(f, "~ peer {public_key} keepalive {from} -> {to}"),
+            Self::PresharedKey { public_key } => write!(f, "~ peer {public_key} preshared key"),
+        }
+    }
+}
+
+fn list(items: &[String]) -> String {
+    if items.is_empty() {
+        "none".into()
+    } else {
+        items.join(", ")
+    }
+}
+
+/// CIDR strings as `(addr, prefix)`, sorted, so equal sets compare equal
+/// however they were written. Unparsable entries are kept out; applying the
+/// config would fail on them anyway.
+fn cidrs<'a>(items: impl IntoIterator<Item = &'a String>) -> Vec<(IpAddr, u8)> {
+    let mut parsed: Vec<_> = items
+        .into_iter()
+        .filter_map(|s| parse_cidr(s).ok())
+        .collect();
+    parsed.sort();
+    parsed.dedup();
+    parsed
+}
+
+/// The address as the daemon assigns it: bare addresses get a host prefix.
+pub(crate) fn host_cidr(address: &str) -> String {
+    if address.contains('/') {
+        return address.to_owned();
+    }
+    match address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => format!("{address}/32"),
+        Ok(IpAddr::V6(_)) => format!("{address}/128"),
+        Err(_) => address.to_owned(),
+    }
+}
+
+/// What applying `desired` to `live` would change; `live` is `None` for an
+/// interface that does not exist yet.
+pub fn diff(live: Option<&DeviceState>, desired: &DaemonConfig) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let empty = DeviceState::default();
+    let live = match live {
+        Some(live) => live,
+        None => {
+            changes.push(Change::CreateInterface);
+            &empty
+        }
+    };
+
+    if live.private_key.as_deref() != Some(desired.server.private_key.as_str()) {
+        changes.push(Change::PrivateKey);
+    }
+    let port = desired.server.listen_port as u16;
+    if live.listen_port != port {
+        changes.push(Change::ListenPort {
+            from: live.listen_port,
+            to: port,
+        });
+    }
+    let address = host_cidr(&desired.server.address);
+    if cidrs(&live.addresses) != cidrs([&address]) {
+        changes.push(Change::Address {
+            from: live.addresses.clone(),
+            to: address,
+        });
+    }
+
+    let live_peers: HashMap<&str, _> = live
+        .peers
+        .iter()
+        .map(|p| (p.public_key.as_str(), p))
+        .collect();
+    for peer in &desired.peers {
+        let public_key = peer.public_key.clone();
+        let Some(current) = live_peers.get(peer.public_key.as_str()) else {
+            changes.push(Change::AddPeer {
+                public_key,
+                allowed_ips: peer.allowed_ips.clone(),
+            });
+            continue;
+        };
+        if cidrs(&current.allowed_ips) != cidrs(&peer.allowed_ips) {
+            changes.push(Change::AllowedIps {
+                public_key: public_key.clone(),
+                from: current.allowed_ips.clone(),
+                to: peer.allowed_ips.clone(),
+            });
+        }
+        // Without a configured endpoint the kernel's learned one is kept.
+        let endpoint = peer
+            .endpoint
+            .as_deref()
+            .and_then(|ep| ep.parse::<SocketAddr>().ok());
+        if let Some(endpoint) = endpoint {
+            let learned = current
+                .endpoint
+                .as_deref()
+                .and_then(|ep| ep.parse::<SocketAddr>().ok());
+            if learned != Some(endpoint) {
+                changes.push(Change::Endpoint {
+                    public_key: public_key.clone(),
+                    from: current.endpoint.clone(),
+                    to: endpoint.to_string(),
+                });
+            }
+        }
+        let keepalive = peer.keepalive(&desired.network);
+        if current.persistent_keepalive != keepalive {
+            changes.push(Change::Keepalive {
+                public_key: public_key.clone(),
+                from: current.persistent_keepalive,
+                to: keepalive,
+            });
+        }
+        if current.preshared_key != peer.preshared_key {
+            changes.push(Change::PresharedKey { public_key });
+        }
+    }
+
+    let wanted: HashSet<&str> = desired
+        .peers
+        .iter()
+        .map(|p| p.public_key.as_str())
+        .collect();
+    changes.extend(
+        live.peers
+            .iter()
+            .filter(|p| !wanted.contains(p.public_key.as_str()))
+            .map(|p| Change::RemovePeer {
+                public_key: p.public_key.clone(),
+            }),
+    );
+    changes
+}
+
+/// The plan for one configured server.
+#[derive(Debug)]
+pub struct ServerPlan {
+    pub api_host: String,
+    /// The server's name, once its config has been fetched.
+    pub server: Option<String>,
+    /// The existing interface the config would go to; `None` for a new one.
+    pub interface: Option<String>,
+    pub outcome: PlanOutcome,
+}
+
+#[derive(Debug)]
+pub enum PlanOutcome {
+    Changes(Vec<Change>),
+    /// The API no longer knows this server (401/404): its interface and
+    /// entry would be removed.
+    Gone,
+    FetchFailed(String),
+    /// The interface could not be read.
+    Unreadable(String),
+}
+
+/// Everything `wirewarden plan` prints.
+#[derive(Debug)]
+pub struct Plan {
+    pub servers: Vec<ServerPlan>,
+    /// Managed interfaces no configured server claims, removed under the
+    /// teardown policy.
+    pub orphans: Vec<String>,
+}
+
+/// Fetch every server's config and compare it with its interface. `client`
+/// serves entries without their own certificate trust; the rest are given
+/// a client each, as `list` does.
+pub async fn plan<P: Platform>(
+    client: &Client,
+    config: &DaemonToml,
+    cache: &ConfigCache,
+) -> Result<Plan, PlatformError> {
+    let prefix = config.interfaces.prefix.as_str();
+    let existing = netlink::managed_interfaces::<P>(
+        &config.interfaces.all_prefixes(),
+        &config.named_interfaces(),
+    )
+    .await?;
+    let key_to_iface: HashMap<&str, &str> = existing
+        .iter()
+        .map(|(name, key)| (key.as_str(), name.as_str()))
+        .collect();
+
+    let fetches = config
+        .servers
+        .iter()
+        .map(|entry| fetch(client, config, entry));
+    let fetched = futures::future::join_all(fetches).await;
+
+    // Servers whose fetch failed keep their interfaces, as the daemon holds
+    // their last good config.
+    let cached = cache.load_all().await;
+    let mut claimed: HashSet<&str> = HashSet::new();
+    let mut servers = Vec::with_capacity(config.servers.len());
+    for (entry, result) in config.servers.iter().zip(fetched) {
+        let desired = match result {
+            Ok(desired) => desired,
+            Err(e) => {
+                let gone = e.is_gone();
+                let last_good = cached.get(&entry.api_token);
+                let interface = last_good
+                    .and_then(|c| key_to_iface.get(c.server.private_key.as_str()).copied());
+                if !gone && let Some(name) = interface {
+                    claimed.insert(name);
+                }
+                servers.push(ServerPlan {
+                    api_host: entry.api_host.clone(),
+                    server: last_good.map(|c| c.server.name.clone()),
+                    interface: interface.map(str::to_owned),
+                    outcome: if gone {
+                        PlanOutcome::Gone
+                    } else {
+                        PlanOutcome::FetchFailed(e.to_string())
+                    },
+                });
+                continue;
+            }
+        };
+
+        let interface = key_to_iface
+            .get(desired.server.private_key.as_str())
+            .copied();
+        let outcome = match interface {
+            Some(name) => {
+                claimed.insert(name);
+                match P::device_state(name).await {
+                    Ok(live) => {
+                        let mut changes = diff(Some(&live), &desired);
+                        match entry.interface.as_deref() {
+                            Some(wanted) if wanted != name => changes.insert(
+                                0,
+                                Change::RenameTo {
+                                    name: wanted.to_owned(),
+                                },
+                            ),
+                            Some(_) => {}
+                            None if !has_prefix(name, prefix) => changes.insert(
+                                0,
+                                Change::Rename {
+                                    prefix: prefix.to_owned(),
+                                },
+                            ),
+                            None => {}
+                        }
+                        PlanOutcome::Changes(changes)
+                    }
+                    Err(e) => PlanOutcome::Unreadable(e.to_string()),
+                }
+            }
+            None => PlanOutcome::Changes(diff(None, &desired)),
+        };
+        servers.push(ServerPlan {
+            api_host: entry.api_host.clone(),
+            server: Some(desired.server.name),
+            interface: interface.map(str::to_owned),
+            outcome,
+        });
+    }
+
+    let mut orphans: Vec<String> = if config.teardown.on_disconnect() {
+        existing
+            .keys()
+            .filter(|name| has_prefix(name, prefix) && !claimed.contains(name.as_str()))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+    orphans.sort();
+    Ok(Plan { servers, orphans })
+}
+
+async fn fetch(
+    client: &Client,
+    config: &DaemonToml,
+    entry: &ServerEntry,
+) -> Result<DaemonConfig, api::ApiError> {
+    let fetched = if entry.has_custom_trust() {
+        let client = api::entry_client(config.proxy.as_ref(), &config.http, entry).await?;
+        api::fetch_config(&client, entry, None).await?
+    } else {
+        api::fetch_config(client, entry, None).await?
+    };
+    Ok(fetched.config)
+}
+
+/// Human-readable form of `plan`.
+pub fn render(plan: &Plan) -> String {
+    let mut out = String::new();
+    if plan.servers.is_empty() {
+        out.push_str("no servers configured\n");
+    }
+    for server in &plan.servers {
+        let name = server.server.as_deref().unwrap_or("unknown");
+        let interface = match (&server.interface, &server.outcome) {
+            (Some(name), _) => name.as_str(),
+            (None, PlanOutcome::Changes(_)) => "new interface",
+            (None, _) => "no interface",
+        };
+        writeln!(out, "{} ({name}) on {interface}", server.api_host).unwrap();
+        match &server.outcome {
+            PlanOutcome::Changes(changes) if changes.is_empty() => {
+                out.push_str("  no changes\n");
+            }
+            PlanOutcome::Changes(changes) => {
+                for change in changes {
+                    writeln!(out, "  {change}").unwrap();
+                }
+            }
+            PlanOutcome::Gone => {
+                out.push_str("  server gone: its interface and entry would be removed\n");
+            }
+            PlanOutcome::FetchFailed(e) => {
+                writeln!(out, "  fetch failed, last good config kept: {e}").unwrap();
+            }
+            PlanOutcome::Unreadable(e) => writeln!(out, "  cannot read interface: {e}").unwrap(),
+        }
+    }
+    for name in &plan.orphans {
+        writeln!(out, "- interface {name} (no configured server)").unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlink::PeerState;
+    use uuid::Uuid;
+    use wirewarden_types::daemon::{
+        CONFIG_VERSION, DaemonNetworkInfo, DaemonPeer, DaemonServerInfo,
+    };
+
+    const PRIVATE: &str = "YWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWFhYWE=";
+    const PEER_A: &str = "Y2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjYWE=";
+    const PEER_B: &str = "ZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGRkZGQ=";
+
+    fn desired() -> DaemonConfig {
+        DaemonConfig {
+            version: CONFIG_VERSION,
+            server: DaemonServerInfo {
+                id: Uuid::nil(),
+                name: "relay".into(),
+                private_key: PRIVATE.into(),
+                public_key: "YmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmI=".into(),
+                address: "10.0.0.1".into(),
+                listen_port: 51820,
+                manage_nat: false,
+                fwmark: None,
+                route_table: None,
+                rule_priority: None,
+                mtu: None,
+            },
+            network: DaemonNetworkInfo {
+                id: Uuid::nil(),
+                name: "home".into(),
+                cidr: "10.0.0.0/24".into(),
+                persistent_keepalive: 25,
+                dns_servers: Vec::new(),
+                dns_zone: None,
+                acl: Vec::new(),
+            },
+            peers: vec![DaemonPeer {
+                public_key: PEER_A.into(),
+                allowed_ips: vec!["10.0.0.2/32".into()],
+                endpoint: None,
+                preshared_key: None,
+                persistent_keepalive: None,
+                upload_kbps: None,
+                download_kbps: None,
+            }],
+        }
+    }
+
+    /// A device already running [`desired`].
+    fn live() -> DeviceState {
+        DeviceState {
+            private_key: Some(PRIVATE.into()),
+            listen_port: 51820,
+            addresses: vec!["10.0.0.1/32".into()],
+            peers: vec![PeerState {
+                public_key: PEER_A.into(),
+                allowed_ips: vec!["10.0.0.2/32".into()],
+                endpoint: Some("203.0.113.7:40000".into()),
+                persistent_keepalive: 25,
+                preshared_key: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn diff_in_sync() {
+        assert_eq!(diff(Some(&live()), &desired()), vec![]);
+    }
+
+    #[test]
+    fn diff_new_interface() {
+        let changes = diff(None, &desired());
+        assert_eq!(changes[0], Change::CreateInterface);
+        assert!(changes.contains(&Change::PrivateKey));
+        assert!(changes.contains(&Change::AddPeer {
+            public_key: PEER_A.into(),
+            allowed_ips: vec!["10.0.0.2/32".into()],
+        }));
+    }
+
+    #[test]
+    fn diff_peers_and_device() {
+        let mut desired = desired();
+        desired.server.listen_port = 51821;
+        desired.server.address = "10.0.0.5/24".into();
+        desired.peers[0].allowed_ips = vec!["10.0.1.0/24".into(), "10.0.0.2/32".into()];
+        desired.peers[0].endpoint = Some("198.51.100.1:51820".into());
+        desired.peers[0].preshared_key = Some(PRIVATE.into());
+        desired.network.persistent_keepalive = 0;
+        let mut live = live();
+        live.peers.push(PeerState {
+            public_key: PEER_B.into(),
+            allowed_ips: vec![],
+            endpoint: None,
+            persistent_keepalive: 0,
+            preshared_key: None,
+        });
+
+        assert_eq!(
+            diff(Some(&live), &desired),
+            vec![
+                Change::ListenPort {
+                    from: 51820,
+                    to: 51821
+                },
+                Change::Address {
+                    from: vec!["10.0.0.1/32".into()],
+                    to: "10.0.0.5/24".into(),
+                },
+                Change::AllowedIps {
+                    public_key: PEER_A.into(),
+                    from: vec!["10.0.0.2/32".into()],
+                    to: vec!["10.0.1.0/24".into(), "10.0.0.2/32".into()],
+                },
+                Change::Endpoint {
+                    public_key: PEER_A.into(),
+                    from: Some("203.0.113.7:40000".into()),
+                    to: "198.51.100.1:51820".into(),
+                },
+                Change::Keepalive {
+                    public_key: PEER_A.into(),
+                    from: 25,
+                    to: 0,
+                },
+                Change::PresharedKey {
+                    public_key: PEER_A.into(),
+                },
+                Change::RemovePeer {
+                    public_key: PEER_B.into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn render_plan() {
+        let plan = Plan {
+            servers: vec![
+                ServerPlan {
+                    api_host: "https://a.example".into(),
+                    server: Some("relay".into()),
+                    interface: Some("wwg0".into()),
+                    outcome: PlanOutcome::Changes(vec![Change::ListenPort {
+                        from: 51820,
+                        to: 51821,
+                    }]),
+                },
+                ServerPlan {
+                    api_host: "https://b.example".into(),
+                    server: None,
+                    interface: None,
+                    outcome: PlanOutcome::FetchFailed("timed out".into()),
+                },
+            ],
+            orphans: vec!["wwg3".into()],
+        };
+        assert_eq!(
+            render(&plan),
+            "https://a.example (relay) on wwg0\n\
+             \x20 ~ listen port 51820 -> 51821\n\
+             https://b.example (unknown) on no interface\n\
+             \x20 fetch failed, last good config kept: timed out\n\
+             - interface wwg3 (no configured server)\n"
+        );
+    }
+}
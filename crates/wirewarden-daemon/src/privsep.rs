@@ -0,0 +1,328 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Giving up root. The daemon starts as root, hands its state to an
+//! unprivileged account, and switches to it keeping only `CAP_NET_ADMIN`,
+//! plus `CAP_NET_BIND_SERVICE` for the DNS forwarder's port 53, so a bug in
+//! the HTTP, TLS or JSON handling cannot be turned into root on the host.
+//! Netlink sockets are opened per operation and the kernel checks
+//! `CAP_NET_ADMIN` on each message, which is all interface changes need.
+//!
+//! Capabilities belong to threads, not processes, so [`drop_to`] must run
+//! before any other thread exists, which means before the async runtime
+//! starts.
+
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+use tracing::{debug, info};
+
+#[derive(Debug, Error)]
+pub enum PrivsepError {
+    #[error("no such user: {0}")]
+    NoSuchUser(String),
+
+    #[error("must start as root to switch to another user")]
+    NotRoot,
+
+    #[error("cannot drop privileges with other threads running")]
+    Threaded,
+
+    #[error("failed to hand {path} to the daemon user: {error}")]
+    Chown { path: String, error: io::Error },
+
+    #[error("{0} is shared with other software; keep the config in a directory of its own")]
+    SharedDirectory(String),
+
+    #[error("{call} failed: {error}")]
+    Sys {
+        call: &'static str,
+        error: io::Error,
+    },
+
+    #[error("dropping privileges is only supported on Linux")]
+    Unsupported,
+}
+
+/// The user and group to run as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Account {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Look up `user` in the password database.
+pub fn lookup(user: &str) -> Result<Account, PrivsepError> {
+    let name =
+        std::ffi::CString::new(user).map_err(|_| PrivsepError::NoSuchUser(user.to_string()))?;
+    let mut pwd = std::mem::MaybeUninit::<libc::passwd>::uninit();
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: every pointer is valid for the duration of the call; a `buf`
+    // too small is reported as ERANGE, not overrun.
+    let rc = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            pwd.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 {
+        return Err(PrivsepError::Sys {
+            call: "getpwnam_r",
+            error: io::Error::from_raw_os_error(rc),
+        });
+    }
+    if result.is_null() {
+        return Err(PrivsepError::NoSuchUser(user.to_string()));
+    }
+    // SAFETY: a non-null result means `pwd` was filled in.
+    let pwd = unsafe { pwd.assume_init() };
+    Ok(Account {
+        uid: pwd.pw_uid,
+        gid: pwd.pw_gid,
+    })
+}
+
+/// Give `account` the directory `dir` itself, not what is in it, so the
+/// daemon can replace files there. Top-level directories such as `/etc` are
+/// refused.
+pub fn hand_over_dir(dir: &Path, account: Account) -> Result<(), PrivsepError> {
+    let dir = std::path::absolute(dir).map_err(|error| PrivsepError::Chown {
+        path: dir.display().to_string(),
+        error,
+    })?;
+    if dir.components().count() <= 2 {
+        return Err(PrivsepError::SharedDirectory(dir.display().to_string()));
+    }
+    std::os::unix::fs::lchown(&dir, Some(account.uid), Some(account.gid)).map_err(|error| {
+        PrivsepError::Chown {
+            path: dir.display().to_string(),
+            error,
+        }
+    })?;
+    debug!(path = %dir.display(), uid = account.uid, "handed over");
+    Ok(())
+}
+
+/// Give `account` ownership of `path` and, for a directory, everything in
+/// it. A missing path is skipped.
+pub fn hand_over(path: &Path, account: Account) -> Result<(), PrivsepError> {
+    let chown_err = |error| PrivsepError::Chown {
+        path: path.display().to_string(),
+        error,
+    };
+    let meta = match std::fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(chown_err(e)),
+    };
+    // Links are never followed, so one planted in the state directory
+    // cannot hand over a file elsewhere.
+    std::os::unix::fs::lchown(path, Some(account.uid), Some(account.gid)).map_err(chown_err)?;
+    if meta.is_dir() {
+        for entry in std::fs::read_dir(path).map_err(chown_err)? {
+            hand_over(&entry.map_err(chown_err)?.path(), account)?;
+        }
+    }
+    debug!(path = %path.display(), uid = account.uid, "handed over");
+    Ok(())
+}
+
+/// Switch the process to `account`, keeping `CAP_NET_ADMIN` and
+/// `CAP_NET_BIND_SERVICE` and nothing else. `CAP_NET_ADMIN` stays in the
+/// ambient set too, so it survives an exec, while `no_new_privs` stops an
+/// exec of a setuid binary from regaining root.
+#[cfg(target_os = "linux")]
+pub fn drop_to(account: Account) -> Result<(), PrivsepError> {
+    // SAFETY: geteuid has no preconditions.
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(PrivsepError::NotRoot);
+    }
+    let threads = std::fs::read_dir("/proc/self/task")
+        .map_err(|error| PrivsepError::Sys {
+            call: "read /proc/self/task",
+            error,
+        })?
+        .count();
+    if threads != 1 {
+        return Err(PrivsepError::Threaded);
+    }
+
+    let kept = (1u32 << CAP_NET_ADMIN) | (1u32 << CAP_NET_BIND_SERVICE);
+    // SAFETY: plain syscalls on this process's own credentials, with
+    // arguments that outlive each call.
+    unsafe {
+        check(
+            "prctl(PR_SET_KEEPCAPS)",
+            libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0),
+        )?;
+        check("setgroups", libc::setgroups(1, &account.gid))?;
+        check(
+            "setresgid",
+            libc::setresgid(account.gid, account.gid, account.gid),
+        )?;
+        check(
+            "setresuid",
+            libc::setresuid(account.uid, account.uid, account.uid),
+        )?;
+        check(
+            "prctl(PR_SET_KEEPCAPS)",
+            libc::prctl(libc::PR_SET_KEEPCAPS, 0, 0, 0, 0),
+        )?;
+
+        let header = CapHeader {
+            version: LINUX_CAPABILITY_VERSION_3,
+            pid: 0,
+        };
+        let data = [
+            CapData {
+                effective: kept,
+                permitted: kept,
+                inheritable: kept,
+            },
+            CapData::default(),
+        ];
+        check(
+            "capset",
+            libc::syscall(libc::SYS_capset, &header, data.as_ptr()) as libc::c_int,
+        )?;
+        check(
+            "prctl(PR_CAP_AMBIENT_RAISE)",
+            libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_RAISE,
+                CAP_NET_ADMIN as libc::c_ulong,
+                0,
+                0,
+            ),
+        )?;
+        check(
+            "prctl(PR_SET_NO_NEW_PRIVS)",
+            libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0),
+        )?;
+    }
+
+    info!(
+        uid = account.uid,
+        gid = account.gid,
+        "dropped root, keeping CAP_NET_ADMIN and CAP_NET_BIND_SERVICE"
+    );
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn drop_to(_account: Account) -> Result<(), PrivsepError> {
+    Err(PrivsepError::Unsupported)
+}
+
+/// Bit of `CAP_NET_ADMIN` in the kernel's capability sets.
+#[cfg(target_os = "linux")]
+const CAP_NET_ADMIN: u32 = 12;
+
+/// Bit of `CAP_NET_BIND_SERVICE` in the kernel's capability sets.
+#[cfg(target_os = "linux")]
+const CAP_NET_BIND_SERVICE: u32 = 10;
+
+#[cfg(target_os = "linux")]
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// `struct __user_cap_header_struct`, which libc does not export.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+/// `struct __user_cap_data_struct`; version 3 takes two, for capabilities
+/// 0-31 and 32-63.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+#[cfg(target_os = "linux")]
+fn check(call: &'static str, rc: libc::c_int) -> Result<(), PrivsepError> {
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(PrivsepError::Sys {
+            call,
+            error: io::Error::last_os_error(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::MetadataExt;
+
+    use super::*;
+
+    #[test]
+    fn looks_up_root() {
+        assert_eq!(lookup("root").unwrap(), Account { uid: 0, gid: 0 });
+    }
+
+    #[test]
+    fn unknown_user() {
+        assert!(matches!(
+            lookup("wirewarden-no-such-user"),
+            Err(PrivsepError::NoSuchUser(_))
+        ));
+    }
+
+    #[test]
+    fn hands_over_a_tree_to_the_current_user() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("cache")).unwrap();
+        std::fs::write(dir.path().join("cache/server.json"), "{}").unwrap();
+        let me = std::fs::metadata(dir.path()).unwrap();
+        let account = Account {
+            uid: me.uid(),
+            gid: me.gid(),
+        };
+
+        hand_over(dir.path(), account).unwrap();
+        hand_over(&dir.path().join("missing"), account).unwrap();
+
+        let file = std::fs::metadata(dir.path().join("cache/server.json")).unwrap();
+        assert_eq!((file.uid(), file.gid()), (account.uid, account.gid));
+    }
+
+    #[test]
+    fn hands_over_only_dedicated_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let me = std::fs::metadata(dir.path()).unwrap();
+        let account = Account {
+            uid: me.uid(),
+            gid: me.gid(),
+        };
+        hand_over_dir(dir.path(), account).unwrap();
+        for shared in ["/", "/etc"] {
+            assert!(matches!(
+                hand_over_dir(Path::new(shared), account),
+                Err(PrivsepError::SharedDirectory(_))
+            ));
+        }
+    }
+}
@@ -14,15 +14,58 @@
 
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
 
 use futures::stream::{FuturesUnordered, StreamExt};
 use reqwest::Client;
+use tonic::transport::Channel;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 use wirewarden_types::daemon::DaemonConfig;
 
-use crate::api;
-use crate::config::{self, DaemonToml};
-use crate::netlink::{IFACE_PREFIX, Platform, PlatformError};
+use crate::api::{self, FetchedConfig};
+use crate::cache::ConfigCache;
+use crate::config::{self, DaemonToml, HttpConfig, ProxyConfig, ServerEntry};
+use crate::netlink::{self, Platform, PlatformError, has_prefix};
+use crate::status::{DaemonStatus, LastResult, Outcome, ServerStatus, redact_token};
+
+/// How often servers with `auto_endpoint` re-check their public IP.
+const ENDPOINT_CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Pause before the first in-cycle retry of a failed fetch, growing linearly.
+const RETRY_PAUSE: Duration = Duration::from_millis(500);
+
+/// A peer whose last handshake is this old has no live session left.
+const SESSION_LIFETIME: Duration = Duration::from_secs(180);
+
+/// How often a draining interface is checked for quiet peers.
+const DRAIN_POLL: Duration = Duration::from_secs(1);
+
+/// Backoff after a server's first failed fetch, doubling per further failure.
+const BACKOFF_BASE: Duration = Duration::from_secs(30);
+/// Longest a failing server goes between fetches.
+const BACKOFF_MAX: Duration = Duration::from_secs(600);
+
+/// Consecutive fetch failures for one server.
+#[derive(Debug)]
+struct Backoff {
+    failures: u32,
+    retry_at: Instant,
+}
+
+/// Delay before retrying after `failures` consecutive failures: exponential,
+/// capped, with the upper half randomized by `jitter` in `[0, 1)` so servers
+/// sharing a dead API do not retry in lockstep.
+fn backoff_delay(failures: u32, jitter: f64) -> Duration {
+    let exp = failures.saturating_sub(1).min(16);
+    let delay = BACKOFF_BASE.saturating_mul(1 << exp).min(BACKOFF_MAX);
+    delay / 2 + (delay / 2).mul_f64(jitter)
+}
+
+/// A uniformly random value in `[0, 1)`.
+fn jitter() -> f64 {
+    (Uuid::new_v4().as_u128() as u64 >> 11) as f64 / (1u64 << 53) as f64
+}
 
 /// Tracks previously applied configs per interface so we can skip no-op cycles.
 #[derive(Debug, Default)]
@@ -31,19 +74,329 @@ pub struct ReconcileState {
     applied: HashMap<String, DaemonConfig>,
     /// Maps private key (base64) to assigned interface name for stable naming.
     assignments: HashMap<String, String>,
+    /// Last endpoint check per API token, for `auto_endpoint` servers.
+    endpoint_checked: HashMap<String, Instant>,
+    /// Last fetched config per API token, replayed when the API answers 304.
+    fetched: HashMap<String, FetchedConfig>,
+    /// Last successfully applied config per API token, reapplied while the
+    /// API is unreachable.
+    last_good: HashMap<String, DaemonConfig>,
+    /// Config serial of the last config applied per API token. Configs
+    /// served at an older serial are refused, so a replayed response can't
+    /// roll a server back.
+    serials: HashMap<String, i64>,
+    /// Where `last_good` and `serials` are persisted across restarts.
+    cache: Option<ConfigCache>,
+    /// Failing servers per API token, skipped until their retry time.
+    backoff: HashMap<String, Backoff>,
+    /// Polling interval for servers without `interval_secs`. Zero (the
+    /// default) polls every server on every cycle.
+    interval: Duration,
+    /// When each server, by API token, is next due for a fetch.
+    next_poll: HashMap<String, Instant>,
+    /// Clients for entries with their own certificate trust, per API token,
+    /// with the settings they were built from.
+    clients: HashMap<String, (TrustSettings, Client)>,
+    /// Channels to gRPC endpoints, per API token, with the entry they were
+    /// built for, so polls reuse one connection.
+    channels: HashMap<String, (ServerEntry, Channel)>,
+    /// How each server's last fetch and apply went, per API token.
+    results: HashMap<String, LastResult>,
+    /// When each orphaned interface kept for its live sessions was first
+    /// found orphaned, by name.
+    orphaned_since: HashMap<String, Instant>,
+}
+
+/// What an entry's dedicated client depends on; a change means a rebuild.
+#[derive(Debug, PartialEq)]
+struct TrustSettings {
+    entry: ServerEntry,
+    proxy: Option<ProxyConfig>,
+    http: HttpConfig,
 }
 
 impl ReconcileState {
+    /// Start from the configs persisted in `cache`, so servers come up even
+    /// if the API is down at boot.
+    pub async fn with_cache(cache: ConfigCache) -> Self {
+        let last_good = cache.load_all().await;
+        if !last_good.is_empty() {
+            info!(count = last_good.len(), "loaded cached configs");
+        }
+        Self {
+            last_good,
+            serials: cache.load_serials().await,
+            cache: Some(cache),
+            ..Self::default()
+        }
+    }
+
+    /// Poll servers without their own `interval_secs` this often.
+    pub fn with_interval(self, interval: Duration) -> Self {
+        Self { interval, ..self }
+    }
+
+    fn interval_for(&self, entry: &ServerEntry) -> Duration {
+        entry
+            .interval_secs
+            .map_or(self.interval, |secs| Duration::from_secs(secs.max(1)))
+    }
+
+    /// The earliest time any server in `config` is due for a fetch, or `None`
+    /// with no servers configured.
+    pub fn next_due(&self, config: &DaemonToml) -> Option<Instant> {
+        let now = Instant::now();
+        config
+            .servers
+            .iter()
+            .map(|entry| {
+                let token = &entry.api_token;
+                let poll = self.next_poll.get(token).copied().unwrap_or(now);
+                let retry = self.backoff.get(token).map_or(now, |b| b.retry_at);
+                poll.max(retry)
+            })
+            .min()
+    }
+
+    /// Make every server due for a fetch on the next cycle, including those
+    /// backing off. Failure counts are kept, so a server that fails again
+    /// resumes its backoff where it left off.
+    pub fn poll_now(&mut self) {
+        let now = Instant::now();
+        self.next_poll.clear();
+        for backoff in self.backoff.values_mut() {
+            backoff.retry_at = now;
+        }
+    }
+
+    /// Record a config that applied cleanly, served at `serial` if known.
+    async fn remember(&mut self, api_token: &str, config: &DaemonConfig, serial: Option<i64>) {
+        let serial = serial.or_else(|| self.serials.get(api_token).copied());
+        if self.last_good.get(api_token) == Some(config)
+            && self.serials.get(api_token).copied() == serial
+        {
+            return;
+        }
+        self.last_good.insert(api_token.to_string(), config.clone());
+        if let Some(serial) = serial {
+            self.serials.insert(api_token.to_string(), serial);
+        }
+        if let Some(cache) = &self.cache
+            && let Err(e) = cache.store(api_token, config, serial).await
+        {
+            warn!(server = %config.server.name, error = %e, "failed to cache config");
+        }
+    }
+
+    /// The client to reach `entry`'s API with: its own when it has custom
+    /// trust, otherwise `shared`. `None` if its own could not be built.
+    fn api_client<'a>(&'a self, entry: &ServerEntry, shared: &'a Client) -> Option<&'a Client> {
+        if entry.has_custom_trust() {
+            self.clients.get(&entry.api_token).map(|(_, client)| client)
+        } else {
+            Some(shared)
+        }
+    }
+
+    /// Build clients for entries with custom trust, and channels for entries
+    /// using gRPC, that lack a current one.
+    async fn refresh_clients(&mut self, config: &DaemonToml) {
+        self.refresh_channels(config).await;
+        self.clients
+            .retain(|token, _| config.servers.iter().any(|s| &s.api_token == token));
+        for entry in config.servers.iter().filter(|e| e.has_custom_trust()) {
+            let settings = TrustSettings {
+                entry: entry.clone(),
+                proxy: config.proxy.clone(),
+                http: config.http,
+            };
+            if self
+                .clients
+                .get(&entry.api_token)
+                .is_some_and(|(built, _)| *built == settings)
+            {
+                continue;
+            }
+            self.clients.remove(&entry.api_token);
+            match api::entry_client(config.proxy.as_ref(), &config.http, entry).await {
+                Ok(client) => {
+                    self.clients
+                        .insert(entry.api_token.clone(), (settings, client));
+                }
+                Err(e) => error!(
+                    api_host = %entry.api_host,
+                    error = %e,
+                    "failed to set up certificate trust, will retry next cycle"
+                ),
+            }
+        }
+    }
+
+    async fn refresh_channels(&mut self, config: &DaemonToml) {
+        // A changed entry gets a new channel.
+        self.channels
+            .retain(|_, (built, _)| config.servers.contains(built));
+        for entry in &config.servers {
+            let Some(endpoint) = &entry.grpc_endpoint else {
+                continue;
+            };
+            if self.channels.contains_key(&entry.api_token) {
+                continue;
+            }
+            match api::grpc_channel(endpoint, entry).await {
+                Ok(channel) => {
+                    self.channels
+                        .insert(entry.api_token.clone(), (entry.clone(), channel));
+                }
+                // Fetches open their own channel meanwhile and report this.
+                Err(e) => error!(
+                    endpoint,
+                    error = %e,
+                    "failed to set up gRPC channel, will retry next cycle"
+                ),
+            }
+        }
+    }
+
+    /// Whether a server's fetch is due: its interval has passed and it is
+    /// not backing off.
+    fn is_due(&self, api_token: &str, now: Instant) -> bool {
+        self.next_poll.get(api_token).is_none_or(|&at| now >= at)
+            && self
+                .backoff
+                .get(api_token)
+                .is_none_or(|b| now >= b.retry_at)
+    }
+
+    /// Record a failed fetch, returning the failure count and retry delay.
+    fn record_failure(&mut self, api_token: &str) -> (u32, Duration) {
+        let backoff = self
+            .backoff
+            .entry(api_token.to_string())
+            .or_insert(Backoff {
+                failures: 0,
+                retry_at: Instant::now(),
+            });
+        backoff.failures += 1;
+        let delay = backoff_delay(backoff.failures, jitter());
+        backoff.retry_at = Instant::now() + delay;
+        (backoff.failures, delay)
+    }
+
+    /// Refuse `fetched` when it was served at an older serial than the
+    /// config last applied for `api_token`.
+    fn check_serial(
+        &self,
+        api_token: &str,
+        fetched: FetchedConfig,
+    ) -> Result<FetchedConfig, api::ApiError> {
+        match (fetched.serial, self.serials.get(api_token)) {
+            (Some(serial), Some(&applied)) if serial < applied => {
+                Err(api::ApiError::StaleConfig { serial, applied })
+            }
+            _ => Ok(fetched),
+        }
+    }
+
+    /// Refuse `fetched` when it is for a server other than the one whose
+    /// config was last applied for `api_token`, which is how an entry
+    /// enrolled without a server ID stays bound to its server across
+    /// restarts.
+    fn check_server(
+        &self,
+        api_token: &str,
+        fetched: FetchedConfig,
+    ) -> Result<FetchedConfig, api::ApiError> {
+        let expected = self.last_good.get(api_token).map(|c| c.server.id);
+        api::check_server(expected, &fetched.config)?;
+        Ok(fetched)
+    }
+
+    /// Drop everything kept for a server that is gone or unconfigured.
+    async fn forget(&mut self, api_token: &str) {
+        self.fetched.remove(api_token);
+        self.serials.remove(api_token);
+        self.results.remove(api_token);
+        self.backoff.remove(api_token);
+        self.next_poll.remove(api_token);
+        self.clients.remove(api_token);
+        self.channels.remove(api_token);
+        let Some(config) = self.last_good.remove(api_token) else {
+            return;
+        };
+        if let Some(cache) = &self.cache
+            && let Err(e) = cache.remove(config.server.id).await
+        {
+            warn!(server = %config.server.name, error = %e, "failed to remove cached config");
+        }
+    }
+
+    /// The configured server behind each managed interface, where known.
+    fn interface_entries<'a>(&self, config: &'a DaemonToml) -> HashMap<&str, &'a ServerEntry> {
+        config
+            .servers
+            .iter()
+            .filter_map(|entry| {
+                let key = &self.last_good.get(&entry.api_token)?.server.private_key;
+                Some((self.assignments.get(key)?.as_str(), entry))
+            })
+            .collect()
+    }
+
+    /// What the daemon knows about each server in `config`, for
+    /// `wirewarden status`.
+    pub fn status(&self, config: &DaemonToml) -> DaemonStatus {
+        let interfaces = self.interface_entries(config);
+        let servers = config
+            .servers
+            .iter()
+            .map(|entry| ServerStatus {
+                api_token: entry.api_token.clone(),
+                server: self
+                    .last_good
+                    .get(&entry.api_token)
+                    .map(|c| c.server.name.clone()),
+                interface: interfaces
+                    .iter()
+                    .find(|(_, e)| e.api_token == entry.api_token)
+                    .map(|(name, _)| name.to_string()),
+                last_result: self.results.get(&entry.api_token).cloned(),
+            })
+            .collect();
+        DaemonStatus {
+            updated_at: chrono::Utc::now(),
+            servers,
+        }
+    }
+
+    /// The config in effect on each managed interface.
+    pub fn applied_configs(&self) -> impl Iterator<Item = &DaemonConfig> {
+        self.applied.values()
+    }
+
     /// Return all interface names currently managed by this state.
     pub fn interface_names(&self) -> impl Iterator<Item = &str> {
         self.assignments.values().map(|s| s.as_str())
     }
+
+    /// Each managed interface with its server entry and the client that
+    /// reaches the entry's API.
+    pub fn interface_apis<'a>(
+        &'a self,
+        config: &'a DaemonToml,
+        shared: &'a Client,
+    ) -> Vec<(&'a str, &'a ServerEntry, &'a Client)> {
+        self.interface_entries(config)
+            .into_iter()
+            .filter_map(|(name, entry)| Some((name, entry, self.api_client(entry, shared)?)))
+            .collect()
+    }
 }
 
-/// Allocate the lowest available `wwgN` name, skipping names in `taken`.
-fn next_interface_name(taken: &HashSet<String>) -> String {
+/// Allocate the lowest available `<prefix>N` name, skipping names in `taken`.
+fn next_interface_name(prefix: &str, taken: &HashSet<String>) -> String {
     (0..)
-        .map(|i| format!("{IFACE_PREFIX}{i}"))
+        .map(|i| format!("{prefix}{i}"))
         .find(|name| !taken.contains(name))
         .unwrap()
 }
@@ -66,13 +419,44 @@ pub async fn reconcile_all<P: Platform>(
     let server_count = config.servers.len();
     info!(server_count, "starting reconciliation cycle");
 
+    // Forget cached configs for entries dropped from daemon.toml.
+    let dropped: Vec<String> = state
+        .fetched
+        .keys()
+        .chain(state.last_good.keys())
+        .chain(state.backoff.keys())
+        .chain(state.next_poll.keys())
+        .chain(state.results.keys())
+        .filter(|token| !config.servers.iter().any(|s| &s.api_token == *token))
+        .cloned()
+        .collect();
+    for token in dropped {
+        state.forget(&token).await;
+    }
+
     if server_count == 0 {
         debug!("no servers to reconcile");
         return;
     }
 
     // Phase 1: Discover existing wirewarden-managed interfaces and their keys.
-    let existing = match P::list_managed_interfaces().await {
+    // Explicitly named ones are found by name, including those assigned
+    // earlier whose entries have since dropped or changed the name.
+    let prefix = config.interfaces.prefix.as_str();
+    let prefixes = config.interfaces.all_prefixes();
+    let mut named: Vec<String> = config
+        .named_interfaces()
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+    for name in state.interface_names() {
+        let listed = prefixes.iter().any(|p| has_prefix(name, p));
+        if !listed && !named.iter().any(|n| n == name) {
+            named.push(name.to_owned());
+        }
+    }
+    let names: Vec<&str> = named.iter().map(String::as_str).collect();
+    let existing = match netlink::managed_interfaces::<P>(&prefixes, &names).await {
         Ok(map) => map,
         Err(e) => {
             error!(error = %e, "failed to list managed interfaces, skipping cycle");
@@ -86,90 +470,319 @@ pub async fn reconcile_all<P: Platform>(
         .map(|(name, key)| (key.as_str(), name.as_str()))
         .collect();
 
+    state.refresh_clients(config).await;
+
     // Phase 2: Fetch configs and assign interface names.
     let mut fetched: Vec<(usize, DaemonConfig, String)> = Vec::new();
     let mut to_remove: Vec<usize> = Vec::new();
-    let mut taken: HashSet<String> = HashSet::new();
+    // Old names of interfaces renamed this cycle, gone from the host.
+    let mut renamed: HashSet<String> = HashSet::new();
+    // Explicit names are never allocated to other entries.
+    let mut taken: HashSet<String> = config
+        .named_interfaces()
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+    // Which entry, by API token, has each server's private key this cycle.
+    let mut claimed: HashMap<String, String> = HashMap::new();
 
-    // Fetch all configs concurrently.
-    let fetch_results: Vec<(usize, Result<DaemonConfig, api::ApiError>)> = config
-        .servers
-        .iter()
-        .enumerate()
-        .map(|(i, entry)| async move {
+    // Fetch due servers concurrently, up to the configured limit, stalest
+    // first so servers deferred by an earlier deadline go ahead of the rest.
+    // Servers not yet due or backing off are skipped; `None` marks them below.
+    let now = Instant::now();
+    let mut due: Vec<usize> = (0..server_count)
+        .filter(|&i| {
+            let entry = &config.servers[i];
+            let due = state.is_due(&entry.api_token, now);
+            if !due {
+                debug!(api_host = %entry.api_host, "not due, skipping fetch");
+            }
+            due
+        })
+        .collect();
+    due.sort_by_key(|&i| state.next_poll.get(&config.servers[i].api_token));
+
+    let state_ref = &*state;
+    let retries = config.http.retries;
+    let fetch = |i: usize| {
+        let entry = &config.servers[i];
+        async move {
             debug!(
                 api_host = %entry.api_host,
                 "fetching config for server {}/{}",
                 i + 1,
                 server_count,
             );
-            let result = api::fetch_config(client, entry).await;
-            (i, result)
-        })
-        .collect::<FuturesUnordered<_>>()
-        .collect()
-        .await;
+            let prev = state_ref.fetched.get(&entry.api_token);
+            let channel = state_ref.channels.get(&entry.api_token).map(|(_, c)| c);
+            let result = match state_ref.api_client(entry, client) {
+                Some(client) => fetch_with_retries(client, channel, entry, prev, retries)
+                    .await
+                    .and_then(|fetched| state_ref.check_serial(&entry.api_token, fetched))
+                    .and_then(|fetched| state_ref.check_server(&entry.api_token, fetched)),
+                None => Err(api::ApiError::Untrusted),
+            };
+            (i, Some(result))
+        }
+    };
+
+    // Collect until the cycle deadline, starting a queued fetch as each one
+    // finishes. Fetches still running then have failed; the rest wait.
+    let limit = config.http.max_concurrent_fetches.max(1);
+    let deadline = tokio::time::Instant::now()
+        + Duration::from_secs(config.http.cycle_timeout_secs);
+    let mut queue = due.into_iter();
+    let mut started: Vec<usize> = queue.by_ref().take(limit).collect();
+    let mut fetches: FuturesUnordered<_> = started.iter().map(|&i| fetch(i)).collect();
+    let mut fetch_results: Vec<_> = Vec::with_capacity(server_count);
+    while let Ok(Some(result)) = tokio::time::timeout_at(deadline, fetches.next()).await {
+        fetch_results.push(result);
+        if let Some(i) = queue.next() {
+            started.push(i);
+            fetches.push(fetch(i));
+        }
+    }
+    // Cancels fetches that missed the deadline.
+    drop(fetches);
+    let deferred = queue.len();
+    if deferred > 0 {
+        warn!(deferred, "cycle deadline reached, deferring remaining servers");
+    }
+    for i in 0..server_count {
+        if fetch_results.iter().any(|(done, _)| *done == i) {
+            continue;
+        }
+        let result = started
+            .contains(&i)
+            .then_some(Err(api::ApiError::CycleTimeout));
+        fetch_results.push((i, result));
+    }
+    fetch_results.sort_by_key(|(i, _)| *i);
 
     // Assign interfaces: prefer existing interface with matching private key.
     for (i, result) in fetch_results {
-        match result {
-            Ok(daemon_config) => {
-                let key = &daemon_config.server.private_key;
-
-                // Check if there's an existing interface with this private key.
-                let iface_name = if let Some(&name) = key_to_iface.get(key.as_str()) {
-                    debug!(
-                        interface = name,
-                        server = %daemon_config.server.name,
-                        "matched to existing interface by private key"
-                    );
-                    name.to_owned()
-                } else if let Some(name) = state.assignments.get(key) {
-                    // We assigned this key before but interface may not exist yet.
-                    debug!(
-                        interface = %name,
-                        server = %daemon_config.server.name,
-                        "reusing previous assignment"
-                    );
-                    name.clone()
+        let token = &config.servers[i].api_token;
+        if result.is_some() {
+            let next = now + state.interval_for(&config.servers[i]);
+            state.next_poll.insert(token.clone(), next);
+        }
+        let daemon_config = match result {
+            Some(Ok(fetched_config)) => {
+                state
+                    .results
+                    .insert(token.clone(), LastResult::now(Outcome::Ok));
+                if state.backoff.remove(token).is_some() {
+                    info!(api_host = %config.servers[i].api_host, "fetch recovered");
+                }
+                if fetched_config.etag.is_some() || fetched_config.serial.is_some() {
+                    state.fetched.insert(token.clone(), fetched_config.clone());
                 } else {
-                    // Allocate a new name.
-                    let name = next_interface_name(&taken);
-                    debug!(
-                        interface = %name,
-                        server = %daemon_config.server.name,
-                        "allocated new interface"
-                    );
-                    name
-                };
-
-                taken.insert(iface_name.clone());
-                state.assignments.insert(key.clone(), iface_name.clone());
-                fetched.push((i, daemon_config, iface_name));
+                    state.fetched.remove(token);
+                }
+                fetched_config.config
             }
-            Err(e) if e.is_gone() => {
+            Some(Err(e)) if e.is_gone() => {
+                state.forget(token).await;
                 warn!(
                     api_host = %config.servers[i].api_host,
                     "server gone (401/404), will tear down"
                 );
                 to_remove.push(i);
+                continue;
             }
-            Err(e) => {
+            Some(Err(e)) => {
+                let error = e.to_string();
+                state
+                    .results
+                    .insert(token.clone(), LastResult::now(Outcome::FetchFailed { error }));
+                let (failures, retry_in) = state.record_failure(token);
+                let fallback = state.last_good.get(token);
                 error!(
                     api_host = %config.servers[i].api_host,
                     error = %e,
-                    "fetch failed, will retry next cycle"
+                    failures,
+                    retry_in_secs = retry_in.as_secs(),
+                    keeping_last_good = fallback.is_some(),
+                    "fetch failed, backing off"
                 );
+                match fallback {
+                    Some(last_good) => last_good.clone(),
+                    None => continue,
+                }
             }
+            // Not fetched this cycle: hold the last good config, if any, in place.
+            None => match state.last_good.get(token) {
+                Some(last_good) => last_good.clone(),
+                None => continue,
+            },
+        };
+        let key = &daemon_config.server.private_key;
+
+        // Two entries for one server, such as tokens from before and after a
+        // rotation, would fight over its interface; the first keeps it.
+        if let Some(first) = claimed.get(key) {
+            warn!(
+                api_host = %config.servers[i].api_host,
+                server = %daemon_config.server.name,
+                "server already configured by another entry, skipping"
+            );
+            let error = format!(
+                "{} is already configured by the entry with token {}",
+                daemon_config.server.name,
+                redact_token(first)
+            );
+            state
+                .results
+                .insert(token.clone(), LastResult::now(Outcome::ApplyFailed { error }));
+            continue;
+        }
+        claimed.insert(key.clone(), token.clone());
+
+        // Check if there's an existing interface with this private key.
+        let iface_name = if let Some(wanted) = config.servers[i].interface.as_deref() {
+            match key_to_iface.get(key.as_str()) {
+                Some(&name) if name != wanted => {
+                    // Rename rather than recreate it, so its peers and
+                    // addresses carry over.
+                    if existing.contains_key(wanted) {
+                        warn!(
+                            interface = name,
+                            wanted,
+                            "configured interface name is held by another interface, keeping name"
+                        );
+                        name.to_owned()
+                    } else {
+                        match P::rename_interface(name, wanted).await {
+                            Ok(()) => {
+                                info!(
+                                    from = name,
+                                    to = wanted,
+                                    "renamed interface to configured name"
+                                );
+                                state.applied.remove(name);
+                                renamed.insert(name.to_owned());
+                                wanted.to_owned()
+                            }
+                            Err(e) => {
+                                warn!(interface = name, error = %e, "failed to rename interface, keeping name");
+                                name.to_owned()
+                            }
+                        }
+                    }
+                }
+                _ => wanted.to_owned(),
+            }
+        } else if let Some(&name) = key_to_iface.get(key.as_str()) {
+            debug!(
+                interface = name,
+                server = %daemon_config.server.name,
+                "matched to existing interface by private key"
+            );
+            if has_prefix(name, prefix) {
+                name.to_owned()
+            } else {
+                // Named under an old prefix: rename it rather than recreate
+                // it, so its peers and addresses carry over.
+                let mut in_use = taken.clone();
+                in_use.extend(existing.keys().cloned());
+                let new_name = next_interface_name(prefix, &in_use);
+                match P::rename_interface(name, &new_name).await {
+                    Ok(()) => {
+                        info!(from = name, to = %new_name, "renamed interface to current prefix");
+                        // Applied afresh, since the link went down.
+                        state.applied.remove(name);
+                        new_name
+                    }
+                    Err(e) => {
+                        warn!(interface = name, error = %e, "failed to rename interface, keeping name");
+                        name.to_owned()
+                    }
+                }
+            }
+        } else if let Some(name) = state
+            .assignments
+            .get(key)
+            .filter(|name| has_prefix(name, prefix))
+        {
+            // We assigned this key before but interface may not exist yet.
+            debug!(
+                interface = %name,
+                server = %daemon_config.server.name,
+                "reusing previous assignment"
+            );
+            name.clone()
+        } else {
+            // Allocate a new name.
+            let name = next_interface_name(prefix, &taken);
+            debug!(
+                interface = %name,
+                server = %daemon_config.server.name,
+                "allocated new interface"
+            );
+            name
+        };
+
+        taken.insert(iface_name.clone());
+        state.assignments.insert(key.clone(), iface_name.clone());
+        fetched.push((i, daemon_config, iface_name));
+    }
+
+    // Phase 2b: Keep auto-endpoint servers pointed at our public IP.
+    for (i, daemon_config, _) in &fetched {
+        let entry = &config.servers[*i];
+        let due = state
+            .endpoint_checked
+            .get(&entry.api_token)
+            .is_none_or(|at| at.elapsed() >= ENDPOINT_CHECK_INTERVAL);
+        if entry.auto_endpoint
+            && due
+            && let Some(api_client) = state.api_client(entry, client)
+        {
+            sync_endpoint(client, api_client, entry, daemon_config).await;
+            state.endpoint_checked.insert(entry.api_token.clone(), Instant::now());
         }
     }
 
     // Phase 3: Apply configs.
     let mut active_ifaces: HashSet<String> = HashSet::new();
 
-    for (_, daemon_config, interface) in fetched {
+    // Each interface binds its listen port exclusively. Interfaces keep the
+    // ports they hold, and a config that wants one held or claimed by another
+    // interface is not applied, rather than failing in the kernel.
+    let mut ports: HashMap<i32, String> = fetched
+        .iter()
+        .filter_map(|(_, _, interface)| {
+            let port = state.applied.get(interface)?.server.listen_port;
+            Some((port, interface.clone()))
+        })
+        .collect();
+
+    for (i, daemon_config, interface) in fetched {
         active_ifaces.insert(interface.clone());
 
+        let port = daemon_config.server.listen_port;
+        match ports.get(&port) {
+            Some(holder) if *holder != interface => {
+                error!(
+                    interface = interface.as_str(),
+                    server = %daemon_config.server.name,
+                    port,
+                    holder = holder.as_str(),
+                    "listen port already in use, not applying config"
+                );
+                let error = format!("listen port {port} is already used by {holder}");
+                state.results.insert(
+                    config.servers[i].api_token.clone(),
+                    LastResult::now(Outcome::ApplyFailed { error }),
+                );
+                continue;
+            }
+            Some(_) => {}
+            None => {
+                ports.insert(port, interface.clone());
+            }
+        }
+
         if state.applied.get(&interface) == Some(&daemon_config) {
             debug!(
                 interface = interface.as_str(),
@@ -194,6 +807,13 @@ pub async fn reconcile_all<P: Platform>(
                     peer_count = daemon_config.peers.len(),
                     "interface configured successfully"
                 );
+                let token = &config.servers[i].api_token;
+                let serial = state
+                    .fetched
+                    .get(token)
+                    .filter(|f| f.config == daemon_config)
+                    .and_then(|f| f.serial);
+                state.remember(token, &daemon_config, serial).await;
                 state.applied.insert(interface, daemon_config);
             }
             Err(e) => {
@@ -202,22 +822,64 @@ pub async fn reconcile_all<P: Platform>(
                     error = %e,
                     "failed to apply config, will retry next cycle"
                 );
+                let error = e.to_string();
+                state.results.insert(
+                    config.servers[i].api_token.clone(),
+                    LastResult::now(Outcome::ApplyFailed { error }),
+                );
             }
         }
     }
 
-    // Phase 4: Clean up orphaned wirewarden-managed interfaces.
-    for (name, _key) in &existing {
-        if !active_ifaces.contains(name) {
-            warn!(interface = %name, "removing orphaned managed interface");
-            if let Err(e) = P::remove_interface(name).await {
-                error!(interface = %name, error = %e, "failed to remove orphaned interface");
+    // Phase 4: Clean up orphaned wirewarden-managed interfaces, unless the
+    // teardown policy leaves them in place.
+    let mut deferred: HashSet<&str> = HashSet::new();
+    for name in existing.keys() {
+        // Interfaces under legacy prefixes are only ever adopted.
+        if active_ifaces.contains(name)
+            || renamed.contains(name)
+            || !(has_prefix(name, prefix) || named.contains(name))
+        {
+            continue;
+        }
+        if !config.teardown.on_disconnect() {
+            debug!(interface = %name, "leaving orphaned managed interface");
+            continue;
+        }
+        // An orphan can be a transient mismatch with the API; hold off
+        // cutting off users still on it.
+        if config.orphan_grace_secs > 0 {
+            let grace = Duration::from_secs(config.orphan_grace_secs);
+            let since = *state
+                .orphaned_since
+                .entry(name.clone())
+                .or_insert_with(Instant::now);
+            let live = live_sessions::<P>(name).await;
+            if live > 0 && since.elapsed() < grace {
+                warn!(
+                    interface = %name,
+                    live_sessions = live,
+                    remaining_secs = (grace - since.elapsed()).as_secs(),
+                    "orphaned interface has live sessions, deferring removal"
+                );
+                deferred.insert(name.as_str());
+                continue;
             }
-            state.applied.remove(name);
-            // Remove from assignments by value.
-            state.assignments.retain(|_, v| v != name);
         }
+        warn!(interface = %name, "removing orphaned managed interface");
+        if config.drain_secs > 0 {
+            drain_interface::<P>(name, Duration::from_secs(config.drain_secs)).await;
+        }
+        if let Err(e) = P::remove_interface(name).await {
+            error!(interface = %name, error = %e, "failed to remove orphaned interface");
+        }
+        state.applied.remove(name);
+        // Remove from assignments by value.
+        state.assignments.retain(|_, v| v != name);
     }
+    state
+        .orphaned_since
+        .retain(|name, _| deferred.contains(name.as_str()));
 
     // Phase 5: Remove gone server entries from config.
     if !to_remove.is_empty() {
@@ -243,6 +905,176 @@ pub async fn reconcile_all<P: Platform>(
     );
 }
 
+/// Remove every managed interface on shutdown. With `drain_secs` set, each
+/// server is first reported offline and its peers drained, all at once so
+/// shutdown takes at most `drain_secs` longer.
+pub async fn teardown_all<P: Platform>(
+    client: &Client,
+    config: &DaemonToml,
+    state: &ReconcileState,
+) {
+    let count = state.interface_names().count();
+    if count > 0 {
+        info!(count, "removing managed interfaces");
+    }
+    let entries = state.interface_entries(config);
+    let drain = Duration::from_secs(config.drain_secs);
+    let teardowns = state.interface_names().map(|name| {
+        let entry = entries.get(name).copied();
+        async move {
+            if !drain.is_zero() {
+                if let Some(entry) = entry
+                    && let Some(api_client) = state.api_client(entry, client)
+                    && let Err(e) = api::report_offline(api_client, entry).await
+                {
+                    warn!(interface = name, error = %e, "failed to report server offline");
+                }
+                drain_interface::<P>(name, drain).await;
+            }
+            match P::remove_interface(name).await {
+                Ok(()) => info!(interface = name, "removed interface"),
+                Err(e) => warn!(interface = name, error = %e, "failed to remove interface"),
+            }
+        }
+    });
+    futures::future::join_all(teardowns).await;
+}
+
+/// Remove the interfaces of the server behind `api_token` now, found by the
+/// private key in its cached config, and drop that cache entry. Returns the
+/// names removed; none if the server was never applied on this host. `names`
+/// are explicit interface names to look for beyond the prefixes.
+pub async fn teardown_server<P: Platform>(
+    cache: &ConfigCache,
+    api_token: &str,
+    prefixes: &[&str],
+    names: &[&str],
+) -> Result<Vec<String>, PlatformError> {
+    let Some(config) = cache.load_all().await.remove(api_token) else {
+        return Ok(Vec::new());
+    };
+    let mut removed = Vec::new();
+    for (name, key) in netlink::managed_interfaces::<P>(prefixes, names).await? {
+        if key == config.server.private_key {
+            P::remove_interface(&name).await?;
+            removed.push(name);
+        }
+    }
+    if let Err(e) = cache.remove(config.server.id).await {
+        warn!(server = %config.server.name, error = %e, "failed to remove cached config");
+    }
+    Ok(removed)
+}
+
+/// Remove `name`'s peers as their sessions go quiet, waiting at most
+/// `timeout` before removing the rest. The interface itself is kept.
+pub async fn drain_interface<P: Platform>(name: &str, timeout: Duration) {
+    info!(interface = name, timeout_secs = timeout.as_secs(), "draining peers");
+    let deadline = Instant::now() + timeout;
+    loop {
+        let peers = match P::peer_handshakes(name).await {
+            Ok(peers) => peers,
+            Err(e) => {
+                warn!(interface = name, error = %e, "failed to read peers, not draining");
+                return;
+            }
+        };
+        if peers.is_empty() {
+            debug!(interface = name, "drained");
+            return;
+        }
+        let now = Instant::now();
+        let expired = now >= deadline;
+        let quiet: Vec<String> = peers
+            .into_iter()
+            .filter(|(_, last)| expired || last.is_none_or(|at| !session_live(at)))
+            .map(|(key, _)| key)
+            .collect();
+        if !quiet.is_empty() {
+            debug!(interface = name, count = quiet.len(), "removing quiet peers");
+            if let Err(e) = P::remove_peers(name, &quiet).await {
+                warn!(interface = name, error = %e, "failed to remove peers, not draining");
+                return;
+            }
+        }
+        if expired {
+            return;
+        }
+        tokio::time::sleep(DRAIN_POLL.min(deadline - now)).await;
+    }
+}
+
+/// How many of `name`'s peers have a live session. Unreadable peers count
+/// as none, so they never hold an interface up.
+async fn live_sessions<P: Platform>(name: &str) -> usize {
+    match P::peer_handshakes(name).await {
+        Ok(peers) => peers
+            .values()
+            .filter(|last| last.is_some_and(session_live))
+            .count(),
+        Err(e) => {
+            warn!(interface = name, error = %e, "failed to read peers");
+            0
+        }
+    }
+}
+
+/// Whether a handshake at `at` may still back a session.
+fn session_live(at: SystemTime) -> bool {
+    !at.elapsed().is_ok_and(|age| age >= SESSION_LIFETIME)
+}
+
+/// Fetch `entry`'s config, trying again up to `retries` times, after a short
+/// growing pause, while failures look transient.
+async fn fetch_with_retries(
+    client: &Client,
+    channel: Option<&Channel>,
+    entry: &ServerEntry,
+    prev: Option<&FetchedConfig>,
+    retries: u32,
+) -> Result<FetchedConfig, api::ApiError> {
+    let mut attempt = 0;
+    loop {
+        match api::fetch_config_via(client, channel, entry, prev).await {
+            Err(e) if e.is_retryable() && attempt < retries => {
+                attempt += 1;
+                debug!(api_host = %entry.api_host, error = %e, attempt, "fetch failed, retrying");
+                tokio::time::sleep(RETRY_PAUSE * attempt).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Detect this host's public IP and report it as the server's endpoint.
+/// Failures are logged and retried at the next check. `client` reaches the
+/// reflector and `api_client` the server's API.
+async fn sync_endpoint(
+    client: &Client,
+    api_client: &Client,
+    entry: &ServerEntry,
+    daemon_config: &DaemonConfig,
+) {
+    let ip = match &entry.endpoint_reflector {
+        Some(reflector) => match api::detect_public_ip(client, reflector).await {
+            Ok(ip) => Some(ip),
+            Err(e) => {
+                warn!(reflector = %reflector, error = %e, "failed to detect public IP");
+                return;
+            }
+        },
+        None => None,
+    };
+
+    if let Err(e) = api::update_endpoint(api_client, entry, daemon_config.server.id, ip).await {
+        warn!(
+            server = %daemon_config.server.name,
+            error = %e,
+            "failed to update server endpoint"
+        );
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ReconcileError {
     #[error(transparent)]
@@ -257,3 +1089,77 @@ impl ReconcileError {
         matches!(self, Self::Api(e) if e.is_gone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(1, 0.0, 15 ; "first failure low")]
+    #[test_case(1, 0.999, 29 ; "first failure high")]
+    #[test_case(2, 0.0, 30 ; "doubles")]
+    #[test_case(3, 0.5, 90 ; "third failure mid")]
+    #[test_case(10, 0.0, 300 ; "capped low")]
+    #[test_case(u32::MAX, 0.999, 599 ; "capped high")]
+    fn test_backoff_delay(failures: u32, jitter: f64, secs: u64) {
+        assert_eq!(backoff_delay(failures, jitter).as_secs(), secs);
+    }
+
+    #[test]
+    fn test_jitter_range() {
+        for _ in 0..1000 {
+            assert!((0.0..1.0).contains(&jitter()));
+        }
+    }
+
+    #[test]
+    fn test_poll_now() {
+        let mut state = ReconcileState::default();
+        let later = Instant::now() + Duration::from_secs(60);
+        state.next_poll.insert("a".into(), later);
+        state.record_failure("b");
+
+        let now = Instant::now();
+        assert!(!state.is_due("a", now));
+        assert!(!state.is_due("b", now));
+
+        state.poll_now();
+        let now = Instant::now();
+        assert!(state.is_due("a", now));
+        assert!(state.is_due("b", now));
+        assert_eq!(state.backoff["b"].failures, 1, "failure count is kept");
+    }
+
+    #[tokio::test]
+    async fn test_grpc_channels_follow_config() {
+        let grpc = ServerEntry {
+            api_host: "https://vpn.example.com".into(),
+            api_token: "a".into(),
+            grpc_endpoint: Some("http://vpn.example.com:50051".into()),
+            ..Default::default()
+        };
+        let rest = ServerEntry {
+            api_token: "b".into(),
+            grpc_endpoint: None,
+            ..grpc.clone()
+        };
+        let mut config = DaemonToml {
+            servers: vec![grpc, rest],
+            ..Default::default()
+        };
+        let mut state = ReconcileState::default();
+        state.refresh_clients(&config).await;
+        assert_eq!(state.channels.keys().collect::<Vec<_>>(), ["a"]);
+
+        config.servers[0].grpc_endpoint = Some("http://vpn.example.com:50052".into());
+        state.refresh_clients(&config).await;
+        assert_eq!(
+            state.channels["a"].0.grpc_endpoint.as_deref(),
+            Some("http://vpn.example.com:50052")
+        );
+
+        config.servers.remove(0);
+        state.refresh_clients(&config).await;
+        assert!(state.channels.is_empty());
+    }
+}
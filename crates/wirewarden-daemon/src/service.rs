@@ -0,0 +1,382 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Installing the daemon as a systemd service. The unit runs the daemon as
+//! root with every capability but `CAP_NET_ADMIN` taken away, and sandboxes
+//! it so it can write only its config directory and state directory.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use thiserror::Error;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use crate::config::Backend;
+use crate::netlink::wg_quick;
+
+/// Name of the installed unit.
+pub const UNIT_NAME: &str = "wirewarden-daemon.service";
+
+/// Where units written by hand go.
+pub const UNIT_DIR: &str = "/etc/systemd/system";
+
+/// The state directory systemd creates for the unit through `StateDirectory=`.
+const DEFAULT_STATE_DIR: &str = "/var/lib/wirewarden";
+
+#[derive(Debug, Error)]
+pub enum ServiceError {
+    #[error("failed to write {path}: {error}")]
+    Write { path: String, error: io::Error },
+
+    #[error("failed to remove {path}: {error}")]
+    Remove { path: String, error: io::Error },
+
+    #[error("cannot run systemctl: {0}")]
+    Spawn(io::Error),
+
+    #[error("systemctl {args} failed: {stderr}")]
+    Systemctl { args: String, stderr: String },
+
+    #[error("{UNIT_NAME} did not start; see `journalctl -u {UNIT_NAME}`")]
+    NotStarted,
+}
+
+/// What the unit runs.
+#[derive(Debug, Clone)]
+pub struct UnitOptions {
+    /// The daemon binary, normally the running executable.
+    pub exe: PathBuf,
+    pub config: PathBuf,
+    pub state_dir: PathBuf,
+    pub interval: u64,
+    /// The configured backend, which decides what else the unit may touch.
+    pub backend: Backend,
+    /// Whether the daemon updates itself, which needs `exe`'s directory
+    /// writable.
+    pub self_update: bool,
+    /// Whether the daemon serves DNS on a port below 1024, which needs
+    /// `CAP_NET_BIND_SERVICE`.
+    pub dns: bool,
+}
+
+/// The unit file for `options`.
+pub fn render_unit(options: &UnitOptions) -> String {
+    let mut exec = vec![
+        quote(&options.exe.to_string_lossy()),
+        "daemon".to_owned(),
+        "--config".to_owned(),
+        quote(&options.config.to_string_lossy()),
+        "--interval".to_owned(),
+        options.interval.to_string(),
+    ];
+    if options.state_dir != Path::new(DEFAULT_STATE_DIR) {
+        exec.push("--state-dir".to_owned());
+        exec.push(quote(&options.state_dir.to_string_lossy()));
+    }
+
+    // `connect` and the daemon replace the config through a file beside it.
+    let mut writable = Vec::new();
+    if let Some(dir) = options
+        .config
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+    {
+        writable.push(dir.to_path_buf());
+    }
+    let mut directories = String::new();
+    if options.state_dir == Path::new(DEFAULT_STATE_DIR) {
+        directories.push_str("StateDirectory=wirewarden\nStateDirectoryMode=0700\n");
+    } else {
+        writable.push(options.state_dir.clone());
+    }
+    match options.backend {
+        Backend::WgQuick => writable.push(PathBuf::from(wg_quick::CONFIG_DIR)),
+        // Userspace implementations serve their UAPI sockets under
+        // /run/wireguard, and `auto` falls back to one without the kernel
+        // module. It is kept at stop, as other tools share it.
+        Backend::Auto | Backend::Userspace => {
+            directories.push_str("RuntimeDirectory=wireguard\nRuntimeDirectoryPreserve=yes\n");
+        }
+        Backend::Kernel => {}
+    }
+    if options.self_update
+        && let Some(dir) = options.exe.parent()
+    {
+        writable.push(dir.to_path_buf());
+    }
+    let capabilities = if options.dns {
+        "CAP_NET_ADMIN CAP_NET_BIND_SERVICE"
+    } else {
+        "CAP_NET_ADMIN"
+    };
+    let writable: Vec<String> = writable
+        .iter()
+        .map(|p| format!("-{}", quote(&p.to_string_lossy())))
+        .collect();
+
+    format!(
+        "\
+# Written by `wirewarden install-service`; rerun it rather than editing.
+[Unit]
+Description=Wirewarden WireGuard Configuration Daemon
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+WatchdogSec=120
+ExecStart={exec}
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+RestartSec=5
+{directories}
+CapabilityBoundingSet={capabilities}
+AmbientCapabilities={capabilities}
+NoNewPrivileges=yes
+ProtectSystem=strict
+ReadWritePaths={writable}
+ProtectHome=yes
+PrivateTmp=yes
+DevicePolicy=closed
+DeviceAllow=/dev/net/tun rw
+ProtectClock=yes
+ProtectHostname=yes
+ProtectKernelLogs=yes
+ProtectKernelModules=yes
+# No ProtectKernelTunables=: NAT turns on IP forwarding under /proc/sys.
+ProtectControlGroups=yes
+ProtectProc=invisible
+RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6 AF_NETLINK
+RestrictNamespaces=yes
+RestrictRealtime=yes
+RestrictSUIDSGID=yes
+LockPersonality=yes
+MemoryDenyWriteExecute=yes
+SystemCallArchitectures=native
+SystemCallFilter=@system-service
+UMask=0077
+
+[Install]
+WantedBy=multi-user.target
+",
+        exec = exec.join(" "),
+        writable = writable.join(" "),
+    )
+}
+
+/// `arg` as one word of a unit's command line. `%` and `$` are doubled so
+/// systemd does not expand them.
+fn quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty()
+        && !escaped
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        return escaped;
+    }
+    let mut quoted = String::from("\"");
+    for c in escaped.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Write the unit for `options` into `unit_dir`, returning its path.
+pub async fn write_unit(unit_dir: &Path, options: &UnitOptions) -> Result<PathBuf, ServiceError> {
+    let path = unit_dir.join(UNIT_NAME);
+    let write_err = |error| ServiceError::Write {
+        path: path.display().to_string(),
+        error,
+    };
+    tokio::fs::create_dir_all(unit_dir)
+        .await
+        .map_err(write_err)?;
+    tokio::fs::write(&path, render_unit(options))
+        .await
+        .map_err(write_err)?;
+    debug!(path = %path.display(), "wrote unit");
+    Ok(path)
+}
+
+/// Write the unit, enable it and, with `start`, start it and check it came
+/// up. A unit already running is restarted onto the new file.
+pub async fn install(
+    unit_dir: &Path,
+    options: &UnitOptions,
+    start: bool,
+) -> Result<PathBuf, ServiceError> {
+    let path = write_unit(unit_dir, options).await?;
+    systemctl(&["daemon-reload"]).await?;
+    systemctl(&["enable", UNIT_NAME]).await?;
+    info!(path = %path.display(), "installed and enabled {UNIT_NAME}");
+    if start {
+        // `Type=notify` holds this until the daemon reports ready, so a
+        // daemon that fails its first cycle shows up here.
+        systemctl(&["restart", UNIT_NAME]).await?;
+        if !is_active().await? {
+            return Err(ServiceError::NotStarted);
+        }
+        info!("{UNIT_NAME} is running");
+    }
+    Ok(path)
+}
+
+/// Stop and disable the unit and remove its file. The config file and state
+/// directory are kept. Returns whether a unit file was removed.
+pub async fn uninstall(unit_dir: &Path) -> Result<bool, ServiceError> {
+    let path = unit_dir.join(UNIT_NAME);
+    if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        systemctl(&["disable", "--now", UNIT_NAME]).await?;
+    }
+    let removed = match tokio::fs::remove_file(&path).await {
+        Ok(()) => true,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+        Err(error) => {
+            return Err(ServiceError::Remove {
+                path: path.display().to_string(),
+                error,
+            });
+        }
+    };
+    systemctl(&["daemon-reload"]).await?;
+    Ok(removed)
+}
+
+/// Restart the unit onto a replaced binary if it is running.
+pub async fn try_restart() -> Result<(), ServiceError> {
+    systemctl(&["try-restart", UNIT_NAME]).await
+}
+
+async fn is_active() -> Result<bool, ServiceError> {
+    let status = Command::new("systemctl")
+        .args(["is-active", "--quiet", UNIT_NAME])
+        .stdin(Stdio::null())
+        .status()
+        .await
+        .map_err(ServiceError::Spawn)?;
+    Ok(status.success())
+}
+
+async fn systemctl(args: &[&str]) -> Result<(), ServiceError> {
+    let output = Command::new("systemctl")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(ServiceError::Spawn)?;
+    if !output.status.success() {
+        return Err(ServiceError::Systemctl {
+            args: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn options() -> UnitOptions {
+        UnitOptions {
+            exe: "/usr/local/bin/wirewarden".into(),
+            config: "/etc/wirewarden/daemon.toml".into(),
+            state_dir: DEFAULT_STATE_DIR.into(),
+            interval: 30,
+            backend: Backend::default(),
+            self_update: false,
+            dns: false,
+        }
+    }
+
+    #[test]
+    fn unit_is_hardened() {
+        let unit = render_unit(&options());
+        assert!(unit.contains(
+            "ExecStart=/usr/local/bin/wirewarden daemon --config /etc/wirewarden/daemon.toml --interval 30\n"
+        ));
+        assert!(unit.contains("CapabilityBoundingSet=CAP_NET_ADMIN\n"));
+        assert!(unit.contains("ProtectSystem=strict\n"));
+        assert!(unit.contains("ReadWritePaths=-/etc/wirewarden\n"));
+        assert!(unit.contains("RuntimeDirectory=wireguard\n"));
+        assert!(unit.contains("StateDirectory=wirewarden\n"));
+        assert!(
+            !unit.contains("--user"),
+            "privileges are dropped by systemd"
+        );
+    }
+
+    #[test]
+    fn custom_state_dir_and_wg_quick_are_writable() {
+        let unit = render_unit(&UnitOptions {
+            state_dir: "/srv/wirewarden".into(),
+            backend: Backend::WgQuick,
+            ..options()
+        });
+        assert!(unit.contains("--state-dir /srv/wirewarden"));
+        assert!(
+            unit.contains("ReadWritePaths=-/etc/wirewarden -/srv/wirewarden -/etc/wireguard\n")
+        );
+        assert!(!unit.contains("StateDirectory="));
+        assert!(!unit.contains("RuntimeDirectory="));
+    }
+
+    #[test]
+    fn self_update_makes_the_binary_writable() {
+        let unit = render_unit(&UnitOptions {
+            self_update: true,
+            ..options()
+        });
+        assert!(unit.contains("ReadWritePaths=-/etc/wirewarden -/usr/local/bin\n"));
+    }
+
+    #[test]
+    fn dns_may_bind_port_53() {
+        let unit = render_unit(&UnitOptions {
+            dns: true,
+            ..options()
+        });
+        assert!(unit.contains("CapabilityBoundingSet=CAP_NET_ADMIN CAP_NET_BIND_SERVICE\n"));
+        assert!(unit.contains("AmbientCapabilities=CAP_NET_ADMIN CAP_NET_BIND_SERVICE\n"));
+    }
+
+    #[test_case("/usr/bin/wirewarden", "/usr/bin/wirewarden" ; "plain")]
+    #[test_case("/opt/my tools/ww", "\"/opt/my tools/ww\"" ; "space")]
+    #[test_case("/etc/100%", "/etc/100%%" ; "specifier")]
+    #[test_case("/tmp/$HOME", "/tmp/$$HOME" ; "variable")]
+    #[test_case("a\"b", "\"a\\\"b\"" ; "quote")]
+    #[test_case("", "\"\"" ; "empty")]
+    fn quoting(arg: &str, expected: &str) {
+        assert_eq!(quote(arg), expected);
+    }
+
+    #[tokio::test]
+    async fn writes_the_unit_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_unit(dir.path(), &options()).await.unwrap();
+        assert_eq!(path, dir.path().join(UNIT_NAME));
+        let written = tokio::fs::read_to_string(&path).await.unwrap();
+        assert_eq!(written, render_unit(&options()));
+    }
+}
@@ -0,0 +1,423 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! What the daemon last did for each server. The daemon writes this to its
+//! state directory after every cycle; `wirewarden status` reads it back and
+//! adds what the host currently shows.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use wirewarden_types::redact::Redacted;
+
+use crate::cache::{ConfigCache, write_private};
+use crate::config::DaemonToml;
+use crate::netlink::{self, Platform, PlatformError};
+
+/// File name of the status file within the state directory.
+pub const STATUS_FILE: &str = "status.json";
+
+#[derive(Debug, Error)]
+pub enum StatusError {
+    #[error("failed to access status file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("malformed status file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// How a server's most recent fetch and apply went.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Outcome {
+    /// The server's current config is applied.
+    Ok,
+    /// The fetch failed; the last good config, if any, stays applied.
+    FetchFailed { error: String },
+    /// The fetched config could not be applied to the interface.
+    ApplyFailed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastResult {
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+impl LastResult {
+    pub fn now(outcome: Outcome) -> Self {
+        Self {
+            at: Utc::now(),
+            outcome,
+        }
+    }
+}
+
+/// The daemon's view of one configured server.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ServerStatus {
+    pub api_token: String,
+    pub server: Option<String>,
+    pub interface: Option<String>,
+    pub last_result: Option<LastResult>,
+}
+
+impl fmt::Debug for ServerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerStatus")
+            .field("api_token", &Redacted)
+            .field("server", &self.server)
+            .field("interface", &self.interface)
+            .field("last_result", &self.last_result)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub updated_at: DateTime<Utc>,
+    pub servers: Vec<ServerStatus>,
+}
+
+pub fn path(state_dir: &Path) -> PathBuf {
+    state_dir.join(STATUS_FILE)
+}
+
+/// Replace the status file. It names API tokens, so it is owner-only.
+pub async fn save(path: &Path, status: &DaemonStatus) -> Result<(), StatusError> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    write_private(path, &serde_json::to_vec_pretty(status)?).await?;
+    Ok(())
+}
+
+/// The last status written, or `None` if the daemon has not written one.
+pub async fn load(path: &Path) -> Result<Option<DaemonStatus>, StatusError> {
+    match tokio::fs::read(path).await {
+        Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// One line of `wirewarden status`: a configured server as the daemon last
+/// saw it and as the host shows it now. Unknown host facts are `None`.
+#[derive(Debug, Serialize)]
+pub struct EntryReport {
+    pub api_host: String,
+    pub server: Option<String>,
+    pub interface: Option<String>,
+    pub interface_exists: Option<bool>,
+    pub peers: Option<usize>,
+    pub last_result: Option<LastResult>,
+}
+
+/// Everything `wirewarden status` prints.
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    /// When the daemon last wrote its status; `None` if it never has.
+    pub updated_at: Option<DateTime<Utc>>,
+    pub servers: Vec<EntryReport>,
+}
+
+/// Build a report for every server in `config`.
+pub async fn report<P: Platform>(
+    config: &DaemonToml,
+    status: Option<&DaemonStatus>,
+) -> StatusReport {
+    let mut reports = Vec::with_capacity(config.servers.len());
+    for entry in &config.servers {
+        let known = status.and_then(|s| {
+            s.servers
+                .iter()
+                .find(|server| server.api_token == entry.api_token)
+        });
+        let interface = known.and_then(|k| k.interface.clone());
+        let (interface_exists, peers) = match &interface {
+            Some(name) => (
+                P::interface_exists(name).await.ok(),
+                P::peer_handshakes(name).await.ok().map(|peers| peers.len()),
+            ),
+            None => (None, None),
+        };
+        reports.push(EntryReport {
+            api_host: entry.api_host.clone(),
+            server: known.and_then(|k| k.server.clone()),
+            interface,
+            interface_exists,
+            peers,
+            last_result: known.and_then(|k| k.last_result.clone()),
+        });
+    }
+    StatusReport {
+        updated_at: status.map(|s| s.updated_at),
+        servers: reports,
+    }
+}
+
+/// Human-readable form of `report`.
+pub fn render(report: &StatusReport) -> String {
+    let mut out = String::new();
+    match report.updated_at {
+        Some(at) => writeln!(out, "daemon status as of {}", timestamp(at)),
+        None => writeln!(out, "no status from the daemon yet"),
+    }
+    .unwrap();
+    if report.servers.is_empty() {
+        out.push_str("no servers configured\n");
+    }
+
+    for report in &report.servers {
+        let interface = match (&report.interface, report.interface_exists) {
+            (None, _) => "none".to_string(),
+            (Some(name), Some(false)) => format!("{name} (missing)"),
+            (Some(name), _) => match report.peers {
+                Some(peers) => format!("{name} ({peers} peers)"),
+                None => name.clone(),
+            },
+        };
+        let last = match &report.last_result {
+            None => "none".to_string(),
+            Some(last) => {
+                let at = timestamp(last.at);
+                match &last.outcome {
+                    Outcome::Ok => format!("ok at {at}"),
+                    Outcome::FetchFailed { error } => format!("fetch failed at {at}: {error}"),
+                    Outcome::ApplyFailed { error } => format!("apply failed at {at}: {error}"),
+                }
+            }
+        };
+        writeln!(out).unwrap();
+        writeln!(out, "{}", report.api_host).unwrap();
+        writeln!(
+            out,
+            "  server:      {}",
+            report.server.as_deref().unwrap_or("unknown")
+        )
+        .unwrap();
+        writeln!(out, "  interface:   {interface}").unwrap();
+        writeln!(out, "  last result: {last}").unwrap();
+    }
+    out
+}
+
+/// One row of `wirewarden list`.
+#[derive(Debug)]
+pub struct ListRow {
+    pub api_host: String,
+    /// The API token, redacted by [`redact_token`].
+    pub token: String,
+    /// The live interface serving this server; `None` if there is none or it
+    /// could not be determined.
+    pub interface: Option<String>,
+    /// Whether the API answered, or why not.
+    pub api: Result<(), String>,
+}
+
+/// Keep only the last four characters of an API token.
+pub fn redact_token(token: &str) -> String {
+    let tail: String = token
+        .chars()
+        .rev()
+        .take(4)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    format!("…{tail}")
+}
+
+/// Live managed interfaces by the API token of the server they serve, matched
+/// through the private keys in the daemon's cached configs. `names` are the
+/// explicitly named interfaces, found outside the prefixes.
+pub async fn interfaces_by_token<P: Platform>(
+    cache: &ConfigCache,
+    prefixes: &[&str],
+    names: &[&str],
+) -> Result<HashMap<String, String>, PlatformError> {
+    let live = netlink::managed_interfaces::<P>(prefixes, names).await?;
+    let by_key: HashMap<&str, &str> = live
+        .iter()
+        .map(|(name, key)| (key.as_str(), name.as_str()))
+        .collect();
+    Ok(cache
+        .load_all()
+        .await
+        .into_iter()
+        .filter_map(|(token, config)| {
+            let name = by_key.get(config.server.private_key.as_str())?;
+            Some((token, name.to_string()))
+        })
+        .collect())
+}
+
+/// `rows` as an aligned table.
+pub fn render_list(rows: &[ListRow]) -> String {
+    let header = ["API HOST", "TOKEN", "INTERFACE", "API"];
+    let cells: Vec<[String; 4]> = rows
+        .iter()
+        .map(|row| {
+            [
+                row.api_host.clone(),
+                row.token.clone(),
+                row.interface.clone().unwrap_or_else(|| "-".into()),
+                match &row.api {
+                    Ok(()) => "reachable".into(),
+                    Err(e) => format!("unreachable: {e}"),
+                },
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(|h| h.chars().count());
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    let lines = std::iter::once(header.map(String::from)).chain(cells);
+    for line in lines {
+        let last = line.len() - 1;
+        for (i, cell) in line.iter().enumerate() {
+            if i == last {
+                writeln!(out, "{cell}").unwrap();
+            } else {
+                write!(out, "{cell:<width$}  ", width = widths[i]).unwrap();
+            }
+        }
+    }
+    out
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = path(dir.path());
+        assert!(load(&path).await.unwrap().is_none());
+
+        let status = DaemonStatus {
+            updated_at: Utc::now(),
+            servers: vec![ServerStatus {
+                api_token: "token".into(),
+                server: Some("relay".into()),
+                interface: Some("wwg0".into()),
+                last_result: Some(LastResult::now(Outcome::FetchFailed {
+                    error: "timed out".into(),
+                })),
+            }],
+        };
+        save(&path, &status).await.unwrap();
+
+        let loaded = load(&path).await.unwrap().unwrap();
+        assert_eq!(loaded.servers[0].interface.as_deref(), Some("wwg0"));
+        assert_eq!(loaded.servers[0].last_result, status.servers[0].last_result);
+    }
+
+    #[test]
+    fn redacts_tokens() {
+        assert_eq!(
+            redact_token("aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaa1234"),
+            "…1234"
+        );
+        assert_eq!(redact_token("ab"), "…ab");
+    }
+
+    #[test]
+    fn render_list_table() {
+        let rows = [
+            ListRow {
+                api_host: "https://vpn.example.com".into(),
+                token: "…1234".into(),
+                interface: Some("wwg0".into()),
+                api: Ok(()),
+            },
+            ListRow {
+                api_host: "http://10.0.0.1".into(),
+                token: "…5678".into(),
+                interface: None,
+                api: Err("connection refused".into()),
+            },
+        ];
+
+        assert_eq!(
+            render_list(&rows),
+            "API HOST                 TOKEN  INTERFACE  API\n\
+             https://vpn.example.com  …1234  wwg0       reachable\n\
+             http://10.0.0.1          …5678  -          unreachable: connection refused\n"
+        );
+    }
+
+    #[test]
+    fn render_text() {
+        let at = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z")
+            .unwrap()
+            .to_utc();
+        let servers = vec![
+            EntryReport {
+                api_host: "https://vpn.example.com".into(),
+                server: Some("relay".into()),
+                interface: Some("wwg0".into()),
+                interface_exists: Some(true),
+                peers: Some(3),
+                last_result: Some(LastResult {
+                    at,
+                    outcome: Outcome::Ok,
+                }),
+            },
+            EntryReport {
+                api_host: "https://other.example.com".into(),
+                server: None,
+                interface: None,
+                interface_exists: None,
+                peers: None,
+                last_result: None,
+            },
+        ];
+
+        let report = StatusReport {
+            updated_at: Some(at),
+            servers,
+        };
+        assert_eq!(
+            render(&report),
+            "daemon status as of 2026-01-02T03:04:05Z\n\
+             \n\
+             https://vpn.example.com\n  \
+             server:      relay\n  \
+             interface:   wwg0 (3 peers)\n  \
+             last result: ok at 2026-01-02T03:04:05Z\n\
+             \n\
+             https://other.example.com\n  \
+             server:      unknown\n  \
+             interface:   none\n  \
+             last result: none\n"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The systemd notification protocol (`sd_notify`): readiness, watchdog pings
+//! and a one-line status for `systemctl status`. Outside a `Type=notify` unit
+//! `NOTIFY_SOCKET` is unset and every call is a no-op.
+
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use crate::status::{DaemonStatus, Outcome};
+
+/// Where notifications go: a socket path, or a name in the abstract namespace
+/// when `NOTIFY_SOCKET` starts with `@`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Path(PathBuf),
+    Abstract(Vec<u8>),
+}
+
+/// Sends notifications to the service manager.
+#[derive(Debug)]
+pub struct Notifier {
+    target: Option<(UnixDatagram, Target)>,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// Set up from `NOTIFY_SOCKET`, `WATCHDOG_USEC` and `WATCHDOG_PID`.
+    pub fn from_env() -> Self {
+        let target =
+            std::env::var_os("NOTIFY_SOCKET").and_then(|s| parse_target(s.as_encoded_bytes()));
+        let watchdog = parse_watchdog(
+            std::env::var("WATCHDOG_USEC").ok().as_deref(),
+            std::env::var("WATCHDOG_PID").ok().as_deref(),
+            std::process::id(),
+        );
+        Self::new(target, watchdog)
+    }
+
+    fn new(target: Option<Target>, watchdog: Option<Duration>) -> Self {
+        let target = target.and_then(|target| match UnixDatagram::unbound() {
+            Ok(socket) => Some((socket, target)),
+            Err(e) => {
+                warn!(error = %e, "cannot create systemd notification socket");
+                None
+            }
+        });
+        Self { target, watchdog }
+    }
+
+    /// How often to ping the watchdog: half the unit's `WatchdogSec`, as
+    /// systemd recommends. `None` when the watchdog is off.
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog.map(|timeout| timeout / 2)
+    }
+
+    /// Tell systemd startup has finished.
+    pub fn ready(&self) {
+        self.send("READY=1");
+    }
+
+    /// Tell systemd the daemon is still making progress.
+    pub fn watchdog(&self) {
+        if self.watchdog.is_some() {
+            self.send("WATCHDOG=1");
+        }
+    }
+
+    /// Set the line `systemctl status` shows.
+    pub fn status(&self, status: &str) {
+        self.send(&format!("STATUS={status}"));
+    }
+
+    /// Tell systemd the daemon is shutting down.
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    fn send(&self, message: &str) {
+        let Some((socket, target)) = &self.target else {
+            return;
+        };
+        match send_to(socket, target, message.as_bytes()) {
+            Ok(()) => debug!(message, "notified systemd"),
+            Err(e) => warn!(error = %e, message, "failed to notify systemd"),
+        }
+    }
+}
+
+fn send_to(socket: &UnixDatagram, target: &Target, message: &[u8]) -> io::Result<()> {
+    match target {
+        Target::Path(path) => socket.send_to(message, path)?,
+        #[cfg(target_os = "linux")]
+        Target::Abstract(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(message, &addr)?
+        }
+        #[cfg(not(target_os = "linux"))]
+        Target::Abstract(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract sockets need Linux",
+            ));
+        }
+    };
+    Ok(())
+}
+
+fn parse_target(socket: &[u8]) -> Option<Target> {
+    match socket {
+        [] => None,
+        [b'@', name @ ..] => Some(Target::Abstract(name.to_vec())),
+        path => Some(Target::Path(PathBuf::from(
+            String::from_utf8_lossy(path).into_owned(),
+        ))),
+    }
+}
+
+/// The watchdog timeout, if the watchdog is on and meant for this process.
+/// `WATCHDOG_PID` names the process the watchdog applies to; when it names
+/// another, such as a parent that exec'd us without clearing it, ignore it.
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    let usec: u64 = usec?.parse().ok().filter(|&usec| usec > 0)?;
+    if let Some(pid) = pid
+        && pid.parse::<u32>().ok() != Some(own_pid)
+    {
+        return None;
+    }
+    Some(Duration::from_micros(usec))
+}
+
+/// The `STATUS=` line for a cycle: how many interfaces are up and how many
+/// servers failed their last fetch or apply.
+pub fn summarize(status: &DaemonStatus) -> String {
+    let servers = status.servers.len();
+    let up = status
+        .servers
+        .iter()
+        .filter(|s| s.interface.is_some())
+        .count();
+    let failing = status
+        .servers
+        .iter()
+        .filter(|s| {
+            s.last_result
+                .as_ref()
+                .is_some_and(|r| r.outcome != Outcome::Ok)
+        })
+        .count();
+    let mut line = format!(
+        "{up} of {servers} interface{} up",
+        if servers == 1 { "" } else { "s" }
+    );
+    if failing > 0 {
+        line.push_str(&format!(", {failing} failing"));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::status::{LastResult, ServerStatus};
+
+    #[test_case(b"", None ; "empty")]
+    #[test_case(b"/run/systemd/notify", Some(Target::Path("/run/systemd/notify".into())) ; "path")]
+    #[test_case(b"@/org/systemd/notify", Some(Target::Abstract(b"/org/systemd/notify".to_vec())) ; "abstract name")]
+    fn targets(socket: &[u8], expected: Option<Target>) {
+        assert_eq!(parse_target(socket), expected);
+    }
+
+    #[test_case(None, None, None ; "off")]
+    #[test_case(Some("30000000"), None, Some(30) ; "on")]
+    #[test_case(Some("30000000"), Some("42"), Some(30) ; "for this process")]
+    #[test_case(Some("30000000"), Some("7"), None ; "for another process")]
+    #[test_case(Some("0"), None, None ; "zero")]
+    #[test_case(Some("soon"), None, None ; "malformed")]
+    fn watchdog(usec: Option<&str>, pid: Option<&str>, expected_secs: Option<u64>) {
+        assert_eq!(
+            parse_watchdog(usec, pid, 42),
+            expected_secs.map(Duration::from_secs)
+        );
+    }
+
+    #[test]
+    fn sends_to_the_notify_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let listener = UnixDatagram::bind(&path).unwrap();
+        let notifier = Notifier::new(Some(Target::Path(path)), Some(Duration::from_secs(10)));
+
+        notifier.ready();
+        notifier.watchdog();
+        notifier.status("1 of 1 interface up");
+        notifier.stopping();
+
+        let mut received = Vec::new();
+        let mut buf = [0u8; 256];
+        for _ in 0..4 {
+            let n = listener.recv(&mut buf).unwrap();
+            received.push(String::from_utf8_lossy(&buf[..n]).into_owned());
+        }
+        assert_eq!(
+            received,
+            [
+                "READY=1",
+                "WATCHDOG=1",
+                "STATUS=1 of 1 interface up",
+                "STOPPING=1"
+            ]
+        );
+        assert_eq!(notifier.watchdog_interval(), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn without_a_socket_nothing_is_sent() {
+        let notifier = Notifier::new(None, None);
+        notifier.ready();
+        notifier.watchdog();
+        assert_eq!(notifier.watchdog_interval(), None);
+    }
+
+    fn server(interface: bool, outcome: Option<Outcome>) -> ServerStatus {
+        ServerStatus {
+            api_token: "t".into(),
+            server: None,
+            interface: interface.then(|| "wg0".into()),
+            last_result: outcome.map(LastResult::now),
+        }
+    }
+
+    #[test]
+    fn summary_counts_interfaces_and_failures() {
+        let status = DaemonStatus {
+            updated_at: chrono::Utc::now(),
+            servers: vec![
+                server(true, Some(Outcome::Ok)),
+                server(
+                    true,
+                    Some(Outcome::FetchFailed {
+                        error: "timed out".into(),
+                    }),
+                ),
+                server(false, None),
+            ],
+        };
+        assert_eq!(summarize(&status), "2 of 3 interfaces up, 1 failing");
+
+        let status = DaemonStatus {
+            updated_at: chrono::Utc::now(),
+            servers: vec![server(true, Some(Outcome::Ok))],
+        };
+        assert_eq!(summarize(&status), "1 of 1 interface up");
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Usage reporting. Every `[telemetry] interval_secs` each interface's
+//! per-peer transfer counters and last handshakes are sent to its server's
+//! API as the platform reports them; the API works out what moved since the
+//! last report, and which peers connected or dropped off.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use tracing::{debug, warn};
+use wirewarden_types::daemon::{DaemonPeerTelemetry, DaemonTelemetry};
+
+use crate::api::{self, ApiError};
+use crate::config::{DaemonToml, TelemetryConfig};
+use crate::netlink::{Platform, PlatformError};
+use crate::reconcile::ReconcileState;
+
+#[derive(Debug, Default)]
+pub struct TelemetrySchedule {
+    last_report: Option<Instant>,
+}
+
+impl TelemetrySchedule {
+    /// When the next report is due, or `None` with reporting off. The first
+    /// is due at once.
+    pub fn next_due(&self, config: &TelemetryConfig, now: Instant) -> Option<Instant> {
+        if !config.enabled {
+            return None;
+        }
+        Some(match self.last_report {
+            Some(last) => last + Duration::from_secs(config.interval_secs),
+            None => now,
+        })
+    }
+
+    pub fn due(&self, config: &TelemetryConfig, now: Instant) -> bool {
+        self.next_due(config, now).is_some_and(|due| due <= now)
+    }
+
+    pub fn reported(&mut self, now: Instant) {
+        self.last_report = Some(now);
+    }
+}
+
+/// Report every managed interface's counters to its API, all at once.
+pub async fn report_all<P: Platform>(client: &Client, config: &DaemonToml, state: &ReconcileState) {
+    let reports = state.interface_apis(config, client).into_iter().map(
+        |(name, entry, api_client)| async move {
+            let transfer = match P::peer_transfer(name).await {
+                Ok(transfer) => transfer,
+                Err(PlatformError::Unsupported) => return,
+                Err(e) => {
+                    warn!(interface = name, error = %e, "failed to read transfer counters");
+                    return;
+                }
+            };
+            // Without handshakes the counters still count; the API is told
+            // so it does not take every peer for disconnected.
+            let handshakes = match P::peer_handshakes(name).await {
+                Ok(handshakes) => Some(handshakes),
+                Err(e) => {
+                    debug!(interface = name, error = %e, "cannot read handshakes");
+                    None
+                }
+            };
+            let telemetry = DaemonTelemetry {
+                peers: transfer
+                    .into_iter()
+                    .map(|(public_key, t)| DaemonPeerTelemetry {
+                        last_handshake: handshakes
+                            .as_ref()
+                            .and_then(|h| h.get(&public_key).copied().flatten())
+                            .map(DateTime::<Utc>::from),
+                        public_key,
+                        rx_bytes: t.rx_bytes,
+                        tx_bytes: t.tx_bytes,
+                    })
+                    .collect(),
+                handshakes: handshakes.is_some(),
+            };
+            match api::report_telemetry(api_client, entry, &telemetry).await {
+                Ok(()) => debug!(
+                    interface = name,
+                    peers = telemetry.peers.len(),
+                    "reported transfer counters"
+                ),
+                // APIs from before usage history have no endpoint for it.
+                Err(ApiError::NotFound) => {
+                    debug!(interface = name, "API does not accept telemetry");
+                }
+                Err(e) => warn!(interface = name, error = %e, "failed to report telemetry"),
+            }
+        },
+    );
+    futures::future::join_all(reports).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule() {
+        let config = TelemetryConfig {
+            enabled: true,
+            interval_secs: 300,
+        };
+        let start = Instant::now();
+        let mut schedule = TelemetrySchedule::default();
+        assert!(schedule.due(&config, start));
+        assert!(!schedule.due(
+            &TelemetryConfig {
+                enabled: false,
+                ..config.clone()
+            },
+            start
+        ));
+
+        schedule.reported(start);
+        assert!(!schedule.due(&config, start + Duration::from_secs(299)));
+        assert!(schedule.due(&config, start + Duration::from_secs(300)));
+        assert_eq!(
+            schedule.next_due(&config, start),
+            Some(start + Duration::from_secs(300))
+        );
+    }
+}
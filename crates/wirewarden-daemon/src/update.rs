@@ -0,0 +1,568 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Self-update from a release manifest. The manifest names the newest
+//! release and, per platform, where its binary is, its SHA-256 and an
+//! Ed25519 signature over the version, platform and hash together, so a
+//! signed entry cannot be moved to another release or platform. The
+//! signature is checked before anything is downloaded; the binary is then
+//! streamed beside the running executable, hashed as it arrives, and only
+//! renamed over it once the hash matches and it reports the promised
+//! version.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signature, VerifyingKey};
+use openssl::sha::Sha256;
+use reqwest::Client;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info};
+use wirewarden_types::version::Version;
+
+use crate::config::UpdateConfig;
+use crate::tls;
+
+/// How often an API's minimum version may bring a check forward.
+const OUTDATED_RECHECK: Duration = Duration::from_secs(60 * 60);
+
+/// The most a release binary may download before it is abandoned.
+const MAX_BINARY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Prefixed to signed release metadata so the signature is not valid for
+/// any other message the key signs.
+const SIGNATURE_CONTEXT: &str = "wirewarden release v1";
+
+#[derive(Debug, Error)]
+pub enum UpdateError {
+    #[error("self-update needs [update] release_url and public_key in the config")]
+    NotConfigured,
+
+    #[error("invalid release signing key {0:?}: expected a base64 Ed25519 public key")]
+    InvalidKey(String),
+
+    #[error("HTTP request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("{url} returned {status}")]
+    Status { url: String, status: u16 },
+
+    #[error("release manifest invalid: {0}")]
+    Manifest(#[from] serde_json::Error),
+
+    #[error("release manifest has an invalid version {0:?}")]
+    InvalidVersion(String),
+
+    #[error("release {version} has no binary for {platform}")]
+    NoBinary { version: Version, platform: String },
+
+    #[error("release signature missing or invalid; refusing to install it")]
+    BadSignature,
+
+    #[error("release manifest has an invalid sha256 {0:?}")]
+    InvalidChecksum(String),
+
+    #[error("binary is larger than {limit} bytes")]
+    TooLarge { limit: u64 },
+
+    #[error("downloaded binary has sha256 {actual}, not the signed {expected}")]
+    ChecksumMismatch { expected: String, actual: String },
+
+    #[error("downloaded binary reports {actual:?}, not the promised {expected}")]
+    WrongVersion { expected: Version, actual: String },
+
+    #[error("failed to install {path}: {error}")]
+    Install { path: String, error: io::Error },
+}
+
+/// The release manifest at `[update] release_url`.
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    /// Keyed by [`platform`], e.g. `x86_64-linux`.
+    pub binaries: HashMap<String, Binary>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Binary {
+    pub url: String,
+    /// Hex SHA-256 of the binary.
+    pub sha256: String,
+    /// Base64 Ed25519 signature over [`signed_message`].
+    pub signature: String,
+}
+
+/// A release available for this platform.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub version: Version,
+    pub platform: String,
+    pub binary: Binary,
+}
+
+/// This build's platform, as manifests key their binaries.
+pub fn platform() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// This build's release, or `None` for an untagged development build.
+pub fn current_version() -> Option<Version> {
+    env!("GIT_VERSION").parse().ok()
+}
+
+/// Whether `release` should replace `current`. Development builds are only
+/// replaced when forced.
+pub fn is_newer(release: Version, current: Option<Version>, force: bool) -> bool {
+    force || current.is_some_and(|current| release > current)
+}
+
+fn parse_key(config: &UpdateConfig) -> Result<VerifyingKey, UpdateError> {
+    let key = config
+        .public_key
+        .as_deref()
+        .ok_or(UpdateError::NotConfigured)?;
+    crate::api::parse_config_key(key).ok_or_else(|| UpdateError::InvalidKey(key.to_string()))
+}
+
+/// Normalize a manifest's `sha256` to lowercase hex.
+fn parse_checksum(sha256: &str) -> Result<String, UpdateError> {
+    tls::parse_fingerprint(sha256).map_err(|_| UpdateError::InvalidChecksum(sha256.to_string()))
+}
+
+/// What a release's signature covers: one line each for the context,
+/// version, platform and lowercase hex SHA-256.
+pub fn signed_message(version: Version, platform: &str, sha256: &str) -> Vec<u8> {
+    format!("{SIGNATURE_CONTEXT}\n{version}\n{platform}\n{sha256}\n").into_bytes()
+}
+
+/// Check that `release` is signed by `key`, returning the binary's
+/// expected SHA-256.
+pub fn verify(key: &VerifyingKey, release: &Release) -> Result<String, UpdateError> {
+    let sha256 = parse_checksum(&release.binary.sha256)?;
+    let signature: [u8; 64] = STANDARD
+        .decode(release.binary.signature.trim())
+        .ok()
+        .and_then(|s| s.try_into().ok())
+        .ok_or(UpdateError::BadSignature)?;
+    let message = signed_message(release.version, &release.platform, &sha256);
+    key.verify_strict(&message, &Signature::from_bytes(&signature))
+        .map_err(|_| UpdateError::BadSignature)?;
+    Ok(sha256)
+}
+
+/// Pick this platform's binary from `manifest`.
+pub fn select(manifest: Manifest, platform: &str) -> Result<Release, UpdateError> {
+    let version = manifest
+        .version
+        .parse()
+        .map_err(|_| UpdateError::InvalidVersion(manifest.version.clone()))?;
+    let mut binaries = manifest.binaries;
+    let binary = binaries
+        .remove(platform)
+        .ok_or_else(|| UpdateError::NoBinary {
+            version,
+            platform: platform.to_string(),
+        })?;
+    Ok(Release {
+        version,
+        platform: platform.to_string(),
+        binary,
+    })
+}
+
+async fn get(client: &Client, url: &str) -> Result<reqwest::Response, UpdateError> {
+    let resp = client.get(url).send().await?;
+    let status = resp.status().as_u16();
+    if status != 200 {
+        return Err(UpdateError::Status {
+            url: url.to_string(),
+            status,
+        });
+    }
+    Ok(resp)
+}
+
+/// Fetch the manifest and return this platform's release.
+pub async fn latest(client: &Client, config: &UpdateConfig) -> Result<Release, UpdateError> {
+    let url = config
+        .release_url
+        .as_deref()
+        .ok_or(UpdateError::NotConfigured)?;
+    debug!(url, "fetching release manifest");
+    let body = get(client, url).await?.bytes().await?;
+    let manifest: Manifest = serde_json::from_slice(&body)?;
+    select(manifest, &platform())
+}
+
+/// Download `release`'s binary, verify it and put it in place of `exe`.
+pub async fn install(
+    client: &Client,
+    config: &UpdateConfig,
+    release: &Release,
+    exe: &Path,
+) -> Result<(), UpdateError> {
+    let key = parse_key(config)?;
+    let sha256 = verify(&key, release)?;
+    info!(version = %release.version, url = %release.binary.url, "downloading release");
+    let staged = staged_path(exe);
+    let result = async {
+        let mut resp = get(client, &release.binary.url).await?;
+        if resp
+            .content_length()
+            .is_some_and(|len| len > MAX_BINARY_BYTES)
+        {
+            return Err(UpdateError::TooLarge {
+                limit: MAX_BINARY_BYTES,
+            });
+        }
+        let mut stage = Stage::create(&staged, MAX_BINARY_BYTES).await?;
+        while let Some(chunk) = resp.chunk().await? {
+            stage.write(&chunk).await?;
+        }
+        stage.finish(&sha256).await?;
+        check_version(&staged, release.version).await?;
+        swap(&staged, exe).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&staged).await;
+    }
+    result?;
+    info!(version = %release.version, path = %exe.display(), "installed release");
+    Ok(())
+}
+
+fn install_err(path: &Path) -> impl Fn(io::Error) -> UpdateError + use<> {
+    let path = path.display().to_string();
+    move |error| UpdateError::Install {
+        path: path.clone(),
+        error,
+    }
+}
+
+/// Where a new binary for `exe` is written before it replaces it.
+fn staged_path(exe: &Path) -> PathBuf {
+    exe.with_extension("update")
+}
+
+/// A binary being written to disk, hashed as it goes.
+struct Stage {
+    path: PathBuf,
+    file: tokio::fs::File,
+    hasher: Sha256,
+    len: u64,
+    limit: u64,
+}
+
+impl Stage {
+    /// Create `path` executable, refusing more than `limit` bytes.
+    async fn create(path: &Path, limit: u64) -> Result<Self, UpdateError> {
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o755);
+        let file = options.open(path).await.map_err(install_err(path))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            hasher: Sha256::new(),
+            len: 0,
+            limit,
+        })
+    }
+
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), UpdateError> {
+        self.len += chunk.len() as u64;
+        if self.len > self.limit {
+            return Err(UpdateError::TooLarge { limit: self.limit });
+        }
+        self.hasher.update(chunk);
+        self.file
+            .write_all(chunk)
+            .await
+            .map_err(install_err(&self.path))
+    }
+
+    /// Sync the file and check it hashes to `sha256`.
+    async fn finish(mut self, sha256: &str) -> Result<(), UpdateError> {
+        let err = install_err(&self.path);
+        self.file.flush().await.map_err(&err)?;
+        self.file.sync_all().await.map_err(&err)?;
+        let actual: String = self
+            .hasher
+            .finish()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        if actual != sha256 {
+            return Err(UpdateError::ChecksumMismatch {
+                expected: sha256.to_string(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Run `--version` on the staged binary and check it is `expected`. This
+/// also catches a binary built for another architecture.
+async fn check_version(staged: &Path, expected: Version) -> Result<(), UpdateError> {
+    let output = tokio::process::Command::new(staged)
+        .arg("--version")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(install_err(staged))?;
+    let reported = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    let version = reported
+        .split_whitespace()
+        .last()
+        .and_then(|v| v.parse::<Version>().ok());
+    if !output.status.success() || version != Some(expected) {
+        return Err(UpdateError::WrongVersion {
+            expected,
+            actual: reported,
+        });
+    }
+    Ok(())
+}
+
+/// Rename `staged` over `exe`. A running process keeps the old binary.
+async fn swap(staged: &Path, exe: &Path) -> Result<(), UpdateError> {
+    let err = install_err(exe);
+    tokio::fs::rename(staged, exe).await.map_err(&err)?;
+    #[cfg(unix)]
+    {
+        let dir = match exe.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        tokio::fs::File::open(dir)
+            .await
+            .map_err(&err)?
+            .sync_all()
+            .await
+            .map_err(&err)?;
+    }
+    Ok(())
+}
+
+/// Replace this process with `exe`, run with the same arguments. Called
+/// once the daemon has shut down, leaving managed interfaces up for the new
+/// process to adopt. Only returns on failure.
+pub fn restart(exe: &Path) -> io::Error {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        std::process::Command::new(exe)
+            .args(std::env::args_os().skip(1))
+            .exec()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = exe;
+        io::Error::from(io::ErrorKind::Unsupported)
+    }
+}
+
+/// When the daemon next checks for a release.
+#[derive(Debug, Default)]
+pub struct UpdateSchedule {
+    last_check: Option<Instant>,
+}
+
+impl UpdateSchedule {
+    /// Whether a check is due: at startup, every `check_interval_secs`, and
+    /// at most hourly while an API says this build is too old.
+    pub fn due(&self, config: &UpdateConfig, outdated: bool, now: Instant) -> bool {
+        if !config.enabled {
+            return false;
+        }
+        let Some(last) = self.last_check else {
+            return true;
+        };
+        let since = now.saturating_duration_since(last);
+        since >= Duration::from_secs(config.check_interval_secs)
+            || (outdated && since >= OUTDATED_RECHECK)
+    }
+
+    pub fn checked(&mut self, now: Instant) {
+        self.last_check = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+    use test_case::test_case;
+
+    use super::*;
+
+    fn version(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test_case("1.2.0", Some("1.1.9"), false, true ; "newer")]
+    #[test_case("1.2.0", Some("1.2.0"), false, false ; "same")]
+    #[test_case("1.1.0", Some("1.2.0"), false, false ; "older")]
+    #[test_case("1.1.0", Some("1.2.0"), true, true ; "forced")]
+    #[test_case("1.2.0", None, false, false ; "development build")]
+    fn newer(release: &str, current: Option<&str>, force: bool, expected: bool) {
+        assert_eq!(
+            is_newer(version(release), current.map(version), force),
+            expected
+        );
+    }
+
+    const SHA256: &str = "9c56cc51b374c3ba189210d5b6d4bf57790d351c96c47c02190ecf1e430635ab";
+
+    fn signed_release(signer: &SigningKey) -> Release {
+        let message = signed_message(version("0.5.0"), "x86_64-linux", SHA256);
+        Release {
+            version: version("0.5.0"),
+            platform: "x86_64-linux".into(),
+            binary: Binary {
+                url: "https://example.com/x86".into(),
+                sha256: SHA256.to_uppercase(),
+                signature: STANDARD.encode(signer.sign(&message).to_bytes()),
+            },
+        }
+    }
+
+    #[test]
+    fn signatures() {
+        let signer = SigningKey::from_bytes(&[7; 32]);
+        let key = signer.verifying_key();
+        let release = signed_release(&signer);
+        assert_eq!(verify(&key, &release).unwrap(), SHA256);
+
+        let mut older = release.clone();
+        older.version = version("0.4.0");
+        let mut other_platform = release.clone();
+        other_platform.platform = "aarch64-linux".into();
+        let mut other_binary = release.clone();
+        other_binary.binary.sha256 = "00".repeat(32);
+        let mut garbled = release.clone();
+        garbled.binary.signature = "not base64".into();
+        for tampered in [older, other_platform, other_binary, garbled] {
+            assert!(matches!(
+                verify(&key, &tampered),
+                Err(UpdateError::BadSignature)
+            ));
+        }
+
+        let mut bad_hash = release;
+        bad_hash.binary.sha256 = "abc".into();
+        assert!(matches!(
+            verify(&key, &bad_hash),
+            Err(UpdateError::InvalidChecksum(_))
+        ));
+    }
+
+    #[test]
+    fn selects_this_platform() {
+        let manifest: Manifest = serde_json::from_str(
+            r#"{
+                "version": "v0.5.0",
+                "binaries": {
+                    "x86_64-linux": {"url": "https://example.com/x86", "sha256": "aa", "signature": "sig"},
+                    "aarch64-linux": {"url": "https://example.com/arm", "sha256": "bb", "signature": "sig"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let release = select(manifest, "aarch64-linux").unwrap();
+        assert_eq!(release.version, version("0.5.0"));
+        assert_eq!(release.platform, "aarch64-linux");
+        assert_eq!(release.binary.url, "https://example.com/arm");
+
+        let manifest: Manifest =
+            serde_json::from_str(r#"{"version": "0.5.0", "binaries": {}}"#).unwrap();
+        assert!(matches!(
+            select(manifest, "riscv64-linux"),
+            Err(UpdateError::NoBinary { .. })
+        ));
+    }
+
+    #[test]
+    fn schedule() {
+        let config = UpdateConfig {
+            enabled: true,
+            check_interval_secs: 24 * 60 * 60,
+            ..UpdateConfig::default()
+        };
+        let start = Instant::now();
+        let mut schedule = UpdateSchedule::default();
+        assert!(!schedule.due(&UpdateConfig::default(), true, start));
+        assert!(schedule.due(&config, false, start));
+
+        schedule.checked(start);
+        let later = start + OUTDATED_RECHECK;
+        assert!(!schedule.due(&config, false, later));
+        assert!(schedule.due(&config, true, later));
+        assert!(schedule.due(&config, false, start + Duration::from_secs(24 * 60 * 60)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn staged_binary_must_report_the_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("wirewarden");
+        std::fs::write(&exe, "old").unwrap();
+
+        let script = b"#!/bin/sh\necho wirewarden-daemon v0.5.0\n";
+        let sha256 = tls::fingerprint(script);
+        let staged = staged_path(&exe);
+        let mut stage = Stage::create(&staged, 1024).await.unwrap();
+        for chunk in script.chunks(7) {
+            stage.write(chunk).await.unwrap();
+        }
+        stage.finish(&sha256).await.unwrap();
+        assert!(matches!(
+            check_version(&staged, version("0.6.0")).await,
+            Err(UpdateError::WrongVersion { .. })
+        ));
+        check_version(&staged, version("0.5.0")).await.unwrap();
+
+        swap(&staged, &exe).await.unwrap();
+        assert!(!staged.exists());
+        assert!(std::fs::read_to_string(&exe).unwrap().contains("v0.5.0"));
+    }
+
+    #[tokio::test]
+    async fn stage_checks_size_and_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wirewarden.update");
+
+        let mut stage = Stage::create(&path, 8).await.unwrap();
+        stage.write(b"12345").await.unwrap();
+        assert!(matches!(
+            stage.write(b"6789").await,
+            Err(UpdateError::TooLarge { limit: 8 })
+        ));
+
+        let mut stage = Stage::create(&path, 8).await.unwrap();
+        stage.write(b"binary").await.unwrap();
+        assert!(matches!(
+            stage.finish(SHA256).await,
+            Err(UpdateError::ChecksumMismatch { .. })
+        ));
+    }
+}
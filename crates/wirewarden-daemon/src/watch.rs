@@ -0,0 +1,122 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Watches `daemon.toml` so edits, such as a server added by `connect`, are
+//! picked up without waiting for the next poll. The parent directory is
+//! watched rather than the file, which catches the file being created,
+//! replaced or deleted.
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::time::Duration;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// How long to wait for further events after the first, so a file written in
+/// several steps is read once, whole.
+const SETTLE: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Error)]
+pub enum WatchError {
+    #[error("config path has no file name")]
+    NoFileName,
+
+    #[error("failed to create config directory: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to watch config directory: {0}")]
+    Notify(#[from] notify::Error),
+}
+
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    // Dropping the watcher stops the events.
+    _watcher: RecommendedWatcher,
+    events: mpsc::Receiver<()>,
+}
+
+impl ConfigWatcher {
+    /// Watch `path`, creating its directory if needed so a config written
+    /// later is still seen.
+    pub fn new(path: &Path) -> Result<Self, WatchError> {
+        let name: OsString = path.file_name().ok_or(WatchError::NoFileName)?.into();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(dir)?;
+
+        // One pending notification is enough; `changed` rereads the whole file.
+        let (tx, events) = mpsc::channel(1);
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                let Ok(event) = res else { return };
+                if matches!(event.kind, EventKind::Access(_)) {
+                    return;
+                }
+                if event.paths.iter().any(|p| p.file_name() == Some(&name)) {
+                    let _ = tx.try_send(());
+                }
+            })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        debug!(dir = %dir.display(), "watching config directory");
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Wait until the config file changes and then settles.
+    pub async fn changed(&mut self) {
+        if self.events.recv().await.is_none() {
+            return std::future::pending().await;
+        }
+        tokio::time::sleep(SETTLE).await;
+        while self.events.try_recv().is_ok() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sees_file_created_later() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("conf").join("daemon.toml");
+        let mut watcher = ConfigWatcher::new(&path).unwrap();
+
+        std::fs::write(&path, "servers = []").unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .expect("creating the file is a change");
+    }
+
+    #[tokio::test]
+    async fn ignores_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.toml");
+        let mut watcher = ConfigWatcher::new(&path).unwrap();
+
+        std::fs::write(dir.path().join("other.toml"), "").unwrap();
+
+        let waited = tokio::time::timeout(Duration::from_millis(500), watcher.changed()).await;
+        assert!(waited.is_err(), "only daemon.toml counts");
+    }
+}
@@ -12,18 +12,48 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+// The test lock is held across awaits on purpose to serialize reconcile tests.
+#![allow(clippy::await_holding_lock)]
+
 use std::collections::HashMap;
+use std::io::{Read, Write};
 use std::net::SocketAddr;
-use std::sync::Mutex;
-
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use openssl::asn1::Asn1Time;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{SslAcceptor, SslMethod};
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509, X509Builder, X509NameBuilder};
+use test_case::test_case;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
 use uuid::Uuid;
 
-use wirewarden_daemon::config::{self, DaemonToml, ServerEntry};
-use wirewarden_daemon::netlink::{Platform, PlatformError};
+use wirewarden_daemon::cache::ConfigCache;
+use wirewarden_daemon::config::{
+    self, DaemonToml, HttpConfig, InterfaceNaming, ServerEntry, TeardownPolicy,
+};
+use wirewarden_daemon::netlink::{
+    DeviceState, Platform, PlatformError, SimPlatform, Transfer, has_prefix,
+};
+use wirewarden_daemon::plan::{self, PlanOutcome};
 use wirewarden_daemon::reconcile;
-use wirewarden_types::daemon::{DaemonConfig, DaemonNetworkInfo, DaemonPeer, DaemonServerInfo};
+use wirewarden_daemon::status;
+use wirewarden_daemon::tls;
+use wirewarden_types::daemon::{
+    CONFIG_VERSION, DaemonConfig, DaemonNetworkInfo, DaemonPeer, DaemonServerInfo,
+};
+use wirewarden_types::grpc::{self, GetConfigRequest};
+use wirewarden_types::grpc::daemon_server::{Daemon, DaemonServer};
 
 // -- Mock platform that records calls --
 // Global statics require serial execution for reconcile tests.
@@ -31,6 +61,12 @@ use wirewarden_types::daemon::{DaemonConfig, DaemonNetworkInfo, DaemonPeer, Daem
 static TEST_LOCK: Mutex<()> = Mutex::new(());
 static APPLIED: Mutex<Vec<String>> = Mutex::new(Vec::new());
 static REMOVED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// Interfaces, with their private keys, that already exist on the "host".
+static MANAGED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+/// Peers on every mock interface, with their last handshake.
+static PEERS: Mutex<Vec<(String, Option<SystemTime>)>> = Mutex::new(Vec::new());
+static REMOVED_PEERS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static RENAMED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
 
 struct MockPlatform;
 
@@ -53,12 +89,65 @@ impl Platform for MockPlatform {
         Ok(())
     }
 
-    async fn interface_exists(_name: &str) -> Result<bool, PlatformError> {
-        Ok(false)
+    async fn interface_exists(name: &str) -> Result<bool, PlatformError> {
+        Ok(MANAGED.lock().unwrap().iter().any(|(n, _)| n == name))
+    }
+
+    async fn device_state(name: &str) -> Result<DeviceState, PlatformError> {
+        let private_key = MANAGED
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, key)| key.clone());
+        Ok(DeviceState {
+            private_key,
+            ..DeviceState::default()
+        })
+    }
+
+    async fn peer_handshakes(
+        _name: &str,
+    ) -> Result<HashMap<String, Option<SystemTime>>, PlatformError> {
+        Ok(PEERS.lock().unwrap().iter().cloned().collect())
+    }
+
+    async fn peer_transfer(_name: &str) -> Result<HashMap<String, Transfer>, PlatformError> {
+        Ok(PEERS
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, _)| (key.clone(), Transfer::default()))
+            .collect())
+    }
+
+    async fn remove_peers(_name: &str, public_keys: &[String]) -> Result<(), PlatformError> {
+        PEERS
+            .lock()
+            .unwrap()
+            .retain(|(key, _)| !public_keys.contains(key));
+        REMOVED_PEERS.lock().unwrap().extend(public_keys.iter().cloned());
+        Ok(())
+    }
+
+    async fn rename_interface(old: &str, new: &str) -> Result<(), PlatformError> {
+        RENAMED.lock().unwrap().push((old.to_string(), new.to_string()));
+        for (name, _) in MANAGED.lock().unwrap().iter_mut().filter(|(n, _)| n == old) {
+            *name = new.to_string();
+        }
+        Ok(())
     }
 
-    async fn list_managed_interfaces() -> Result<HashMap<String, String>, PlatformError> {
-        Ok(HashMap::new())
+    async fn list_managed_interfaces(
+        prefixes: &[&str],
+    ) -> Result<HashMap<String, String>, PlatformError> {
+        Ok(MANAGED
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| prefixes.iter().any(|p| has_prefix(name, p)))
+            .cloned()
+            .collect())
     }
 }
 
@@ -68,6 +157,10 @@ fn lock_and_clear() -> std::sync::MutexGuard<'static, ()> {
     let guard = TEST_LOCK.lock().unwrap();
     APPLIED.lock().unwrap().clear();
     REMOVED.lock().unwrap().clear();
+    MANAGED.lock().unwrap().clear();
+    PEERS.lock().unwrap().clear();
+    REMOVED_PEERS.lock().unwrap().clear();
+    RENAMED.lock().unwrap().clear();
     guard
 }
 
@@ -83,6 +176,7 @@ fn removed() -> Vec<String> {
 
 fn sample_daemon_config() -> DaemonConfig {
     DaemonConfig {
+        version: CONFIG_VERSION,
         server: DaemonServerInfo {
             id: Uuid::new_v4(),
             name: "test-server".into(),
@@ -90,18 +184,29 @@ fn sample_daemon_config() -> DaemonConfig {
             public_key: "YmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmJiYmI=".into(),
             address: "10.0.0.1".into(),
             listen_port: 51820,
+            manage_nat: false,
+            fwmark: None,
+            route_table: None,
+            rule_priority: None,
+            mtu: None,
         },
         network: DaemonNetworkInfo {
             id: Uuid::new_v4(),
             name: "test-network".into(),
             cidr: "10.0.0.0/24".into(),
             persistent_keepalive: 25,
+            dns_servers: Vec::new(),
+            dns_zone: None,
+            acl: Vec::new(),
         },
         peers: vec![DaemonPeer {
             public_key: "Y2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjY2NjYWE=".into(),
             allowed_ips: vec!["10.0.0.2/32".into()],
             endpoint: None,
             preshared_key: None,
+            persistent_keepalive: None,
+            upload_kbps: None,
+            download_kbps: None,
         }],
     }
 }
@@ -109,6 +214,7 @@ fn sample_daemon_config() -> DaemonConfig {
 /// A second sample config with a different private key.
 fn sample_daemon_config_2() -> DaemonConfig {
     DaemonConfig {
+        version: CONFIG_VERSION,
         server: DaemonServerInfo {
             id: Uuid::new_v4(),
             name: "test-server-2".into(),
@@ -116,12 +222,20 @@ fn sample_daemon_config_2() -> DaemonConfig {
             public_key: "ZWVlZWVlZWVlZWVlZWVlZWVlZWVlZWVlZWVlZWVlZWU=".into(),
             address: "10.0.0.3".into(),
             listen_port: 51821,
+            manage_nat: false,
+            fwmark: None,
+            route_table: None,
+            rule_priority: None,
+            mtu: None,
         },
         network: DaemonNetworkInfo {
             id: Uuid::new_v4(),
             name: "test-network".into(),
             cidr: "10.0.0.0/24".into(),
             persistent_keepalive: 25,
+            dns_servers: Vec::new(),
+            dns_zone: None,
+            acl: Vec::new(),
         },
         peers: vec![],
     }
@@ -159,6 +273,307 @@ async fn spawn_mock_api(status: u16, body: &str) -> (SocketAddr, tokio::sync::on
     (addr, tx)
 }
 
+/// Spawn an HTTP server that, like one API serving several servers, answers
+/// with the body for the bearer token sent, or 401 for any other.
+async fn spawn_multi_server_api(bodies: Vec<(&'static str, String)>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = stream.read(&mut buf).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..n]);
+
+            let (status, body) = bodies
+                .iter()
+                .find(|(token, _)| request.contains(&format!("Bearer {token}\r\n")))
+                .map_or((401, r#"{"error":"unauthorized"}"#), |(_, body)| {
+                    (200, body.as_str())
+                });
+            let response = format!(
+                "HTTP/1.1 {status} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body,
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    addr
+}
+
+/// Spawn an HTTP server answering every request with `status` and `body`.
+/// Returns the address and the number of requests served so far.
+async fn spawn_counting_mock_api(status: u16, body: &str) -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let body = body.to_string();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let _ = stream.read(&mut buf).await;
+            counter.fetch_add(1, Ordering::SeqCst);
+
+            let response = format!(
+                "HTTP/1.1 {status} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body,
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    (addr, requests)
+}
+
+/// Spawn an HTTP server that answers 500 to the first `failures` requests
+/// and 200 with `body` after. Returns the address and the request count.
+async fn spawn_flaky_mock_api(failures: usize, body: &str) -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let body = body.to_string();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let _ = stream.read(&mut buf).await;
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+
+            let (status, body) = if n < failures {
+                (500, r#"{"error":"internal"}"#)
+            } else {
+                (200, body.as_str())
+            };
+            let response = format!(
+                "HTTP/1.1 {status} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body,
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    (addr, requests)
+}
+
+/// Spawn a server that accepts connections and never answers.
+async fn spawn_hung_api() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            held.push(stream);
+        }
+    });
+    addr
+}
+
+/// A self-signed certificate for 127.0.0.1 and its key.
+fn self_signed_cert() -> (X509, PKey<Private>) {
+    issue_cert("homelab.invalid", "127.0.0.1", None)
+}
+
+/// A certificate for `cn` and `ip` and its key, signed by `issuer` or else
+/// by itself.
+fn issue_cert(
+    cn: &str,
+    ip: &str,
+    issuer: Option<(&X509, &PKey<Private>)>,
+) -> (X509, PKey<Private>) {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", cn).unwrap();
+    let name = name.build();
+
+    let mut builder = X509Builder::new().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    match issuer {
+        Some((cert, _)) => builder.set_issuer_name(cert.subject_name()).unwrap(),
+        None => builder.set_issuer_name(&name).unwrap(),
+    }
+    builder.set_pubkey(&key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    let san = SubjectAlternativeName::new()
+        .ip(ip)
+        .build(&builder.x509v3_context(issuer.map(|(cert, _)| cert.as_ref()), None))
+        .unwrap();
+    builder.append_extension(san).unwrap();
+    let signing_key = issuer.map_or(&key, |(_, key)| key);
+    builder.sign(signing_key, MessageDigest::sha256()).unwrap();
+    (builder.build(), key)
+}
+
+/// Spawn an HTTP proxy that tunnels every CONNECT to `target`, whatever host
+/// it names. Returns its address and the number of tunnels opened so far.
+async fn spawn_connect_proxy(target: SocketAddr) -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let tunnels = Arc::new(AtomicUsize::new(0));
+    let counter = tunnels.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let counter = counter.clone();
+            tokio::spawn(async move {
+                let mut head = Vec::new();
+                let mut byte = [0u8; 1];
+                while !head.ends_with(b"\r\n\r\n") {
+                    if !matches!(stream.read(&mut byte).await, Ok(1)) {
+                        return;
+                    }
+                    head.push(byte[0]);
+                }
+                if !head.starts_with(b"CONNECT ") {
+                    return;
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut upstream = tokio::net::TcpStream::connect(target).await.unwrap();
+                let established = b"HTTP/1.1 200 Connection established\r\n\r\n";
+                if stream.write_all(established).await.is_ok() {
+                    let _ = tokio::io::copy_bidirectional(&mut stream, &mut upstream).await;
+                }
+            });
+        }
+    });
+    (addr, tunnels)
+}
+
+/// Spawn an HTTPS server presenting `cert` that answers every request with
+/// 200 and `body`.
+fn spawn_tls_mock_api(cert: &X509, key: &PKey<Private>, body: &str) -> SocketAddr {
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(cert).unwrap();
+    acceptor.set_private_key(key).unwrap();
+    let acceptor = acceptor.build();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let body = body.to_string();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            // Failed handshakes end here.
+            let Ok(mut stream) = acceptor.accept(stream.unwrap()) else {
+                continue;
+            };
+            let mut buf = vec![0u8; 4096];
+            if !matches!(stream.read(&mut buf), Ok(n) if n > 0) {
+                continue;
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body,
+            );
+            let _ = stream.write_all(response.as_bytes());
+            let _ = stream.shutdown();
+        }
+    });
+    addr
+}
+
+/// Spawn an HTTP server that serves `body` with ETag `"v1"`, answering 304
+/// to requests that send it back. Returns the address and the number of 304s
+/// sent so far.
+async fn spawn_etag_mock_api(body: &str) -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let body = body.to_string();
+    let not_modified = Arc::new(AtomicUsize::new(0));
+    let counter = not_modified.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = stream.read(&mut buf).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+
+            let response = if request.contains("if-none-match: \"v1\"") {
+                counter.fetch_add(1, Ordering::SeqCst);
+                "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n".to_string()
+            } else {
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body,
+                )
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    (addr, not_modified)
+}
+
+/// gRPC daemon service that serves `sample_daemon_config` to `token`.
+#[derive(Debug)]
+struct MockGrpc {
+    token: &'static str,
+}
+
+#[tonic::async_trait]
+impl Daemon for MockGrpc {
+    async fn get_config(
+        &self,
+        request: Request<GetConfigRequest>,
+    ) -> Result<Response<grpc::DaemonConfig>, Status> {
+        let auth = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok());
+        if auth != Some(&format!("Bearer {}", self.token)) {
+            return Err(Status::unauthenticated("invalid token"));
+        }
+        let config = grpc::DaemonConfig::from_config(sample_daemon_config(), 1);
+        Ok(Response::new(config))
+    }
+
+    type WatchConfigStream =
+        futures::stream::BoxStream<'static, Result<grpc::DaemonConfig, Status>>;
+
+    async fn watch_config(
+        &self,
+        _request: Request<GetConfigRequest>,
+    ) -> Result<Response<Self::WatchConfigStream>, Status> {
+        Err(Status::unimplemented("not mocked"))
+    }
+}
+
+async fn spawn_mock_grpc(token: &'static str) -> SocketAddr {
+    let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = incoming.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(DaemonServer::new(MockGrpc { token }))
+            .serve_with_incoming(incoming),
+    );
+    addr
+}
+
 // -- Tests --
 
 #[tokio::test]
@@ -175,7 +590,9 @@ async fn reconcile_applies_config_from_api() {
         servers: vec![ServerEntry {
             api_host: format!("http://{addr}"),
             api_token: "test-token".into(),
+            ..Default::default()
         }],
+        ..Default::default()
     };
 
     let client = reqwest::Client::new();
@@ -188,6 +605,43 @@ async fn reconcile_applies_config_from_api() {
     assert_eq!(daemon_config.servers.len(), 1, "server entry should remain");
 }
 
+#[tokio::test]
+async fn reconcile_reuses_config_on_not_modified() {
+    let _guard = lock_and_clear();
+
+    let body = serde_json::to_string(&sample_daemon_config()).unwrap();
+    let (addr, not_modified) = spawn_etag_mock_api(&body).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let mut daemon_config = DaemonToml {
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "test-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    for _ in 0..2 {
+        reconcile::reconcile_all::<MockPlatform>(
+            &client,
+            &config_path,
+            &mut daemon_config,
+            &mut state,
+        )
+        .await;
+    }
+
+    assert_eq!(not_modified.load(Ordering::SeqCst), 1);
+    assert_eq!(applied(), vec!["wwg0"], "unchanged config is not reapplied");
+    assert!(removed().is_empty(), "interface survives the 304");
+    assert_eq!(daemon_config.servers.len(), 1);
+}
+
 #[tokio::test]
 async fn reconcile_multiple_servers() {
     let _guard = lock_and_clear();
@@ -205,12 +659,15 @@ async fn reconcile_multiple_servers() {
             ServerEntry {
                 api_host: format!("http://{addr1}"),
                 api_token: "token-1".into(),
+                ..Default::default()
             },
             ServerEntry {
                 api_host: format!("http://{addr2}"),
                 api_token: "token-2".into(),
+                ..Default::default()
             },
         ],
+        ..Default::default()
     };
 
     let client = reqwest::Client::new();
@@ -224,6 +681,77 @@ async fn reconcile_multiple_servers() {
     assert_eq!(daemon_config.servers.len(), 2);
 }
 
+#[tokio::test]
+async fn reconcile_servers_sharing_an_api_host() {
+    let _guard = lock_and_clear();
+
+    let first = sample_daemon_config();
+    let second = sample_daemon_config_2();
+    // A third server wanting the first one's port.
+    let mut clashing = sample_daemon_config_2();
+    clashing.server.name = "clashing".into();
+    clashing.server.private_key = "ZmZmZmZmZmZmZmZmZmZmZmZmZmZmZmZmZmZmZmZmZmY=".into();
+    clashing.server.listen_port = first.server.listen_port;
+    let body = |config: &DaemonConfig| serde_json::to_string(config).unwrap();
+    let addr = spawn_multi_server_api(vec![
+        ("token-a", body(&first)),
+        ("token-b", body(&second)),
+        ("token-c", body(&clashing)),
+        // The first server again, under another token.
+        ("token-d", body(&first)),
+    ])
+    .await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let entry = |api_token: &str| ServerEntry {
+        api_host: format!("http://{addr}"),
+        api_token: api_token.into(),
+        ..Default::default()
+    };
+    let mut daemon_config = DaemonToml {
+        servers: ["token-a", "token-b", "token-c", "token-d"]
+            .into_iter()
+            .map(entry)
+            .collect(),
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    let mut apps = applied();
+    apps.sort();
+    assert_eq!(apps, vec!["wwg0", "wwg1"]);
+    assert_eq!(daemon_config.servers.len(), 4);
+
+    let daemon_status = state.status(&daemon_config);
+    let outcomes: Vec<_> = daemon_status
+        .servers
+        .iter()
+        .map(|s| s.last_result.as_ref().unwrap().outcome.clone())
+        .collect();
+    assert_eq!(outcomes[..2], [status::Outcome::Ok, status::Outcome::Ok]);
+    assert!(matches!(
+        &outcomes[2],
+        status::Outcome::ApplyFailed { error } if error.contains("port 51820")
+    ));
+    assert!(matches!(
+        &outcomes[3],
+        status::Outcome::ApplyFailed { error } if error.contains("test-server is already configured")
+    ));
+
+    // Nothing changes on the next cycle: the first server keeps its port.
+    APPLIED.lock().unwrap().clear();
+    state.poll_now();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+    assert!(applied().is_empty());
+}
+
 #[tokio::test]
 async fn reconcile_removes_server_on_401() {
     let _guard = lock_and_clear();
@@ -234,7 +762,7 @@ async fn reconcile_removes_server_on_401() {
     let config_path = tmp.path().to_path_buf();
 
     // Seed the file so save can overwrite it
-    config::save(&config_path, &DaemonToml { servers: vec![] })
+    config::save(&config_path, &DaemonToml::default())
         .await
         .unwrap();
 
@@ -242,7 +770,9 @@ async fn reconcile_removes_server_on_401() {
         servers: vec![ServerEntry {
             api_host: format!("http://{addr}"),
             api_token: "revoked-token".into(),
+            ..Default::default()
         }],
+        ..Default::default()
     };
 
     let client = reqwest::Client::new();
@@ -269,7 +799,7 @@ async fn reconcile_removes_server_on_404() {
 
     let tmp = tempfile::NamedTempFile::new().unwrap();
     let config_path = tmp.path().to_path_buf();
-    config::save(&config_path, &DaemonToml { servers: vec![] })
+    config::save(&config_path, &DaemonToml::default())
         .await
         .unwrap();
 
@@ -277,7 +807,9 @@ async fn reconcile_removes_server_on_404() {
         servers: vec![ServerEntry {
             api_host: format!("http://{addr}"),
             api_token: "deleted-server-token".into(),
+            ..Default::default()
         }],
+        ..Default::default()
     };
 
     let client = reqwest::Client::new();
@@ -289,20 +821,29 @@ async fn reconcile_removes_server_on_404() {
     assert!(daemon_config.servers.is_empty());
 }
 
+#[test_case(TeardownPolicy::Always, &["wwg0"] ; "always")]
+#[test_case(TeardownPolicy::OnlyOnDisconnect, &["wwg0"] ; "only on disconnect")]
+#[test_case(TeardownPolicy::Never, &[] ; "never")]
 #[tokio::test]
-async fn reconcile_keeps_server_on_transient_error() {
+async fn reconcile_orphan_teardown_follows_policy(teardown: TeardownPolicy, expected: &[&str]) {
     let _guard = lock_and_clear();
-
-    let (addr, _shutdown) = spawn_mock_api(500, r#"{"error":"internal"}"#).await;
+    MANAGED
+        .lock()
+        .unwrap()
+        .push(("wwg0".into(), "orphaned-key".into()));
 
     let tmp = tempfile::NamedTempFile::new().unwrap();
     let config_path = tmp.path().to_path_buf();
 
+    let (addr, _shutdown) = spawn_mock_api(404, r#"{"error":"not found"}"#).await;
     let mut daemon_config = DaemonToml {
+        teardown,
         servers: vec![ServerEntry {
             api_host: format!("http://{addr}"),
-            api_token: "some-token".into(),
+            api_token: "gone-token".into(),
+            ..Default::default()
         }],
+        ..Default::default()
     };
 
     let client = reqwest::Client::new();
@@ -310,40 +851,40 @@ async fn reconcile_keeps_server_on_transient_error() {
     reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
         .await;
 
-    assert!(applied().is_empty());
-    assert!(removed().is_empty());
-    assert_eq!(
-        daemon_config.servers.len(),
-        1,
-        "should keep entry for retry"
-    );
+    assert_eq!(removed(), expected);
+    assert!(daemon_config.servers.is_empty(), "the gone entry is dropped either way");
 }
 
+#[test_case(Some(0), 60, &[] ; "live session deferred")]
+#[test_case(Some(600), 60, &["wwg0"] ; "stale session removed")]
+#[test_case(None, 60, &["wwg0"] ; "never handshaked removed")]
+#[test_case(Some(0), 0, &["wwg0"] ; "no grace period")]
 #[tokio::test]
-async fn reconcile_mixed_success_and_gone() {
+async fn reconcile_orphan_grace_keeps_live_sessions(
+    handshake_age_secs: Option<u64>,
+    orphan_grace_secs: u64,
+    expected: &[&str],
+) {
     let _guard = lock_and_clear();
-
-    let body = serde_json::to_string(&sample_daemon_config()).unwrap();
-    let (good_addr, _s1) = spawn_mock_api(200, &body).await;
-    let (gone_addr, _s2) = spawn_mock_api(404, "{}").await;
+    MANAGED
+        .lock()
+        .unwrap()
+        .push(("wwg0".into(), "orphaned-key".into()));
+    let handshake = handshake_age_secs.map(|age| SystemTime::now() - Duration::from_secs(age));
+    PEERS.lock().unwrap().push(("peer".into(), handshake));
 
     let tmp = tempfile::NamedTempFile::new().unwrap();
     let config_path = tmp.path().to_path_buf();
-    config::save(&config_path, &DaemonToml { servers: vec![] })
-        .await
-        .unwrap();
 
+    let (addr, _shutdown) = spawn_mock_api(404, r#"{"error":"not found"}"#).await;
     let mut daemon_config = DaemonToml {
-        servers: vec![
-            ServerEntry {
-                api_host: format!("http://{good_addr}"),
-                api_token: "good-token".into(),
-            },
-            ServerEntry {
-                api_host: format!("http://{gone_addr}"),
-                api_token: "gone-token".into(),
-            },
-        ],
+        orphan_grace_secs,
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "gone-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
     };
 
     let client = reqwest::Client::new();
@@ -351,67 +892,859 @@ async fn reconcile_mixed_success_and_gone() {
     reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
         .await;
 
-    assert_eq!(applied(), vec!["wwg0"]);
-    assert_eq!(daemon_config.servers.len(), 1);
+    assert_eq!(removed(), expected);
 }
 
 #[tokio::test]
-async fn connect_writes_config_and_validates() {
-    let dir = tempfile::tempdir().unwrap();
-    let config_path = dir.path().join("daemon.toml");
+async fn drain_removes_quiet_peers_first() {
+    let _guard = lock_and_clear();
+    let now = SystemTime::now();
+    PEERS.lock().unwrap().extend([
+        ("active".to_string(), Some(now)),
+        ("never".to_string(), None),
+        ("stale".to_string(), Some(now - Duration::from_secs(600))),
+    ]);
+
+    reconcile::drain_interface::<MockPlatform>("wwg0", Duration::from_secs(1)).await;
+
+    assert!(PEERS.lock().unwrap().is_empty());
+    let removed_peers = REMOVED_PEERS.lock().unwrap().clone();
+    assert_eq!(removed_peers.len(), 3);
+    assert_eq!(
+        removed_peers.last().map(String::as_str),
+        Some("active"),
+        "the live session is removed once the drain runs out"
+    );
+}
 
-    // First entry
-    let mut cfg = config::load(&config_path).await.unwrap();
-    let entry1 = ServerEntry {
-        api_host: "https://vpn1.example.com".into(),
-        api_token: "aaaa".into(),
-    };
-    config::validate_new_entry(&cfg, &entry1).unwrap();
-    cfg.servers.push(entry1);
-    config::save(&config_path, &cfg).await.unwrap();
+#[tokio::test]
+async fn teardown_reports_offline_before_removing() {
+    let _guard = lock_and_clear();
 
-    // Reload and add second
-    let mut cfg = config::load(&config_path).await.unwrap();
-    assert_eq!(cfg.servers.len(), 1);
+    let body = serde_json::to_string(&sample_daemon_config()).unwrap();
+    let (addr, requests) = spawn_counting_mock_api(200, &body).await;
 
-    let entry2 = ServerEntry {
-        api_host: "https://vpn2.example.com".into(),
-        api_token: "bbbb".into(),
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let mut daemon_config = DaemonToml {
+        drain_secs: 1,
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "test-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
     };
-    config::validate_new_entry(&cfg, &entry2).unwrap();
-    cfg.servers.push(entry2);
-    config::save(&config_path, &cfg).await.unwrap();
 
-    // Verify
-    let cfg = config::load(&config_path).await.unwrap();
-    assert_eq!(cfg.servers.len(), 2);
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
 
-    // Duplicate token rejected
-    let dup_token = ServerEntry {
-        api_host: "https://vpn3.example.com".into(),
-        api_token: "aaaa".into(),
-    };
-    assert!(config::validate_new_entry(&cfg, &dup_token).is_err());
+    reconcile::teardown_all::<MockPlatform>(&client, &daemon_config, &state).await;
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2, "the server was reported offline");
+    assert_eq!(removed(), vec!["wwg0"]);
 }
 
 #[tokio::test]
-async fn api_fetch_parses_valid_response() {
-    let body = serde_json::to_string(&sample_daemon_config()).unwrap();
+async fn reconcile_renames_legacy_interface() {
+    let _guard = lock_and_clear();
+    let config = sample_daemon_config();
+    MANAGED.lock().unwrap().extend([
+        ("vpn0".to_string(), config.server.private_key.clone()),
+        ("vpn1".to_string(), "someone-elses-key".to_string()),
+        ("wg0".to_string(), "not-ours".to_string()),
+    ]);
+
+    let body = serde_json::to_string(&config).unwrap();
     let (addr, _shutdown) = spawn_mock_api(200, &body).await;
 
-    let entry = ServerEntry {
-        api_host: format!("http://{addr}"),
-        api_token: "test-token".into(),
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let mut daemon_config = DaemonToml {
+        interfaces: InterfaceNaming {
+            prefix: "wg-ww".into(),
+            legacy_prefixes: vec!["vpn".into()],
+        },
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "test-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
     };
 
     let client = reqwest::Client::new();
-    let result = wirewarden_daemon::api::fetch_config(&client, &entry).await;
-    let config = result.unwrap();
-    assert_eq!(config.server.name, "test-server");
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert_eq!(
+        *RENAMED.lock().unwrap(),
+        vec![("vpn0".to_string(), "wg-ww0".to_string())]
+    );
+    assert_eq!(applied(), vec!["wg-ww0"]);
+    assert!(removed().is_empty(), "legacy and foreign interfaces are left alone");
+}
+
+#[tokio::test]
+async fn reconcile_renames_to_configured_interface_name() {
+    let _guard = lock_and_clear();
+    let config = sample_daemon_config();
+    MANAGED.lock().unwrap().extend([
+        ("wwg0".to_string(), config.server.private_key.clone()),
+        ("wg-old".to_string(), "not-ours".to_string()),
+    ]);
+
+    let body = serde_json::to_string(&config).unwrap();
+    let (addr, _shutdown) = spawn_mock_api(200, &body).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let mut daemon_config = DaemonToml {
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "test-token".into(),
+            interface: Some("wg-home".into()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert_eq!(
+        *RENAMED.lock().unwrap(),
+        vec![("wwg0".to_string(), "wg-home".to_string())]
+    );
+    assert_eq!(applied(), vec!["wg-home"]);
+    assert!(removed().is_empty(), "unconfigured names are left alone");
+}
+
+#[tokio::test]
+async fn teardown_server_removes_its_interface() {
+    let _guard = lock_and_clear();
+    let config = sample_daemon_config();
+    MANAGED.lock().unwrap().extend([
+        ("wwg0".to_string(), "another-server".to_string()),
+        ("wwg1".to_string(), config.server.private_key.clone()),
+    ]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let cache = ConfigCache::new(dir.path());
+    cache.store("test-token", &config, None).await.unwrap();
+
+    let removed_ifaces =
+        reconcile::teardown_server::<MockPlatform>(&cache, "test-token", &["wwg"], &[])
+            .await
+            .unwrap();
+
+    assert_eq!(removed_ifaces, vec!["wwg1"]);
+    assert_eq!(removed(), vec!["wwg1"]);
+    assert!(cache.load_all().await.is_empty(), "cached config is dropped");
+
+    let again = reconcile::teardown_server::<MockPlatform>(&cache, "test-token", &["wwg"], &[])
+        .await
+        .unwrap();
+    assert!(again.is_empty());
+}
+
+#[tokio::test]
+async fn simulated_reconcile_matches_plan() {
+    let config = sample_daemon_config();
+    let body = serde_json::to_string(&config).unwrap();
+    let (addr, _shutdown) = spawn_mock_api(200, &body).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut daemon_config = DaemonToml {
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "test-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<SimPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    let device = SimPlatform::device_state("wwg0").await.unwrap();
+    assert_eq!(device.private_key.as_deref(), Some(config.server.private_key.as_str()));
+    assert_eq!(device.addresses, vec!["10.0.0.1/32"]);
+    assert_eq!(device.peers.len(), 1);
+
+    let cache = ConfigCache::new(dir.path());
+    let plan = plan::plan::<SimPlatform>(&client, &daemon_config, &cache)
+        .await
+        .unwrap();
+    assert_eq!(plan.servers[0].interface.as_deref(), Some("wwg0"));
+    assert!(
+        matches!(&plan.servers[0].outcome, PlanOutcome::Changes(c) if c.is_empty()),
+        "nothing left to apply: {plan:?}"
+    );
+    assert!(plan.orphans.is_empty());
+}
+
+#[tokio::test]
+async fn reconcile_keeps_server_on_transient_error() {
+    let _guard = lock_and_clear();
+
+    let (addr, _shutdown) = spawn_mock_api(500, r#"{"error":"internal"}"#).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let mut daemon_config = DaemonToml {
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "some-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert!(applied().is_empty());
+    assert!(removed().is_empty());
+    assert_eq!(
+        daemon_config.servers.len(),
+        1,
+        "should keep entry for retry"
+    );
+}
+
+#[tokio::test]
+async fn reconcile_backs_off_failing_server() {
+    let _guard = lock_and_clear();
+
+    let (addr, requests) = spawn_counting_mock_api(500, r#"{"error":"internal"}"#).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let mut daemon_config = DaemonToml {
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "some-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    for _ in 0..3 {
+        reconcile::reconcile_all::<MockPlatform>(
+            &client,
+            &config_path,
+            &mut daemon_config,
+            &mut state,
+        )
+        .await;
+    }
+
+    assert_eq!(
+        requests.load(Ordering::SeqCst),
+        1 + HttpConfig::default().retries as usize,
+        "later cycles wait out the backoff"
+    );
+    assert_eq!(daemon_config.servers.len(), 1);
+}
+
+#[tokio::test]
+async fn reconcile_polls_servers_on_their_own_interval() {
+    let _guard = lock_and_clear();
+
+    let body1 = serde_json::to_string(&sample_daemon_config()).unwrap();
+    let body2 = serde_json::to_string(&sample_daemon_config_2()).unwrap();
+    let (fast_addr, fast) = spawn_counting_mock_api(200, &body1).await;
+    let (slow_addr, slow) = spawn_counting_mock_api(200, &body2).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let entry = |addr: SocketAddr, token: &str, interval_secs| ServerEntry {
+        api_host: format!("http://{addr}"),
+        api_token: token.into(),
+        interval_secs,
+        ..Default::default()
+    };
+    let mut daemon_config = DaemonToml {
+        servers: vec![
+            entry(fast_addr, "fast-token", None),
+            entry(slow_addr, "slow-token", Some(3600)),
+        ],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default().with_interval(Duration::ZERO);
+    for _ in 0..3 {
+        reconcile::reconcile_all::<MockPlatform>(
+            &client,
+            &config_path,
+            &mut daemon_config,
+            &mut state,
+        )
+        .await;
+    }
+
+    assert_eq!(fast.load(Ordering::SeqCst), 3);
+    assert_eq!(slow.load(Ordering::SeqCst), 1, "slow server waits its hour");
+    let mut applied = applied();
+    applied.sort();
+    assert_eq!(applied, vec!["wwg0", "wwg1"], "each config is applied once");
+
+    daemon_config.servers.remove(0);
+    let next = state.next_due(&daemon_config).unwrap();
+    assert!(next > Instant::now() + Duration::from_secs(3500));
+}
+
+#[tokio::test]
+async fn reconcile_retries_transient_failure_within_cycle() {
+    let _guard = lock_and_clear();
+
+    let body = serde_json::to_string(&sample_daemon_config()).unwrap();
+    let (addr, requests) = spawn_flaky_mock_api(1, &body).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let mut daemon_config = DaemonToml {
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "test-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert_eq!(applied(), vec!["wwg0"], "the retry succeeds in the same cycle");
+}
+
+#[tokio::test]
+async fn reconcile_gives_up_at_cycle_deadline() {
+    let _guard = lock_and_clear();
+
+    let addr = spawn_hung_api().await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let mut daemon_config = DaemonToml {
+        http: HttpConfig {
+            cycle_timeout_secs: 1,
+            ..HttpConfig::default()
+        },
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "some-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    let started = Instant::now();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert!(started.elapsed() < Duration::from_secs(5), "the hung fetch is abandoned");
+    assert!(applied().is_empty());
+    assert_eq!(daemon_config.servers.len(), 1, "a timeout is not gone");
+}
+
+#[tokio::test]
+async fn reconcile_defers_servers_past_cycle_deadline() {
+    let _guard = lock_and_clear();
+
+    let hung = spawn_hung_api().await;
+    let body = serde_json::to_string(&sample_daemon_config()).unwrap();
+    let (addr, requests) = spawn_counting_mock_api(200, &body).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let entry = |api_host: String, api_token: &str| ServerEntry {
+        api_host,
+        api_token: api_token.into(),
+        ..Default::default()
+    };
+    let mut daemon_config = DaemonToml {
+        http: HttpConfig {
+            cycle_timeout_secs: 1,
+            max_concurrent_fetches: 1,
+            ..HttpConfig::default()
+        },
+        servers: vec![
+            entry(format!("http://{hung}"), "hung-token"),
+            entry(format!("http://{addr}"), "test-token"),
+        ],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert_eq!(requests.load(Ordering::SeqCst), 0, "queued behind the hung fetch");
+    assert!(applied().is_empty());
+
+    // The hung server is now backing off; the deferred one goes next cycle.
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert_eq!(applied(), vec!["wwg0"]);
+    assert_eq!(daemon_config.servers.len(), 2);
+}
+
+#[tokio::test]
+async fn status_reports_last_results() {
+    let _guard = lock_and_clear();
+
+    let body = serde_json::to_string(&sample_daemon_config()).unwrap();
+    let (good, _s1) = spawn_mock_api(200, &body).await;
+    let (bad, _s2) = spawn_mock_api(500, r#"{"error":"internal"}"#).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+
+    let entry = |api_host: String, api_token: &str| ServerEntry {
+        api_host,
+        api_token: api_token.into(),
+        ..Default::default()
+    };
+    let mut daemon_config = DaemonToml {
+        http: HttpConfig {
+            retries: 0,
+            ..HttpConfig::default()
+        },
+        servers: vec![
+            entry(format!("http://{good}"), "good-token"),
+            entry(format!("http://{bad}"), "bad-token"),
+        ],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    let daemon_status = state.status(&daemon_config);
+    let report = status::report::<MockPlatform>(&daemon_config, Some(&daemon_status)).await;
+
+    let [good, bad] = &report.servers[..] else {
+        panic!("expected two servers, got {report:?}");
+    };
+    assert_eq!(good.server.as_deref(), Some("test-server"));
+    assert_eq!(good.interface.as_deref(), Some("wwg0"));
+    assert_eq!(
+        good.last_result.as_ref().map(|r| &r.outcome),
+        Some(&status::Outcome::Ok)
+    );
+    assert_eq!(bad.interface, None);
+    assert!(matches!(
+        bad.last_result.as_ref().map(|r| &r.outcome),
+        Some(status::Outcome::FetchFailed { .. })
+    ));
+}
+
+#[tokio::test]
+async fn reconcile_applies_cached_config_while_api_down() {
+    let _guard = lock_and_clear();
+
+    let (addr, _shutdown) = spawn_mock_api(500, r#"{"error":"internal"}"#).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+    let state_dir = tempfile::tempdir().unwrap();
+    let cache = ConfigCache::new(state_dir.path());
+    cache
+        .store("some-token", &sample_daemon_config(), None)
+        .await
+        .unwrap();
+
+    let mut daemon_config = DaemonToml {
+        servers: vec![ServerEntry {
+            api_host: format!("http://{addr}"),
+            api_token: "some-token".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::with_cache(cache).await;
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert_eq!(applied(), vec!["wwg0"], "cached config is applied at boot");
+    assert!(removed().is_empty());
+    assert_eq!(daemon_config.servers.len(), 1);
+}
+
+#[tokio::test]
+async fn reconcile_caches_applied_config_until_gone() {
+    let _guard = lock_and_clear();
+
+    let config = sample_daemon_config();
+    let body = serde_json::to_string(&config).unwrap();
+    let (addr, _shutdown) = spawn_mock_api(200, &body).await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+    let state_dir = tempfile::tempdir().unwrap();
+
+    let entry = ServerEntry {
+        api_host: format!("http://{addr}"),
+        api_token: "test-token".into(),
+        ..Default::default()
+    };
+    let mut daemon_config = DaemonToml {
+        servers: vec![entry.clone()],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let cache = ConfigCache::new(state_dir.path());
+    let mut state = reconcile::ReconcileState::with_cache(cache).await;
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    let cached = ConfigCache::new(state_dir.path()).load_all().await;
+    assert_eq!(cached.get("test-token"), Some(&config));
+
+    let (addr, _shutdown) = spawn_mock_api(404, r#"{"error":"not found"}"#).await;
+    daemon_config.servers = vec![ServerEntry {
+        api_host: format!("http://{addr}"),
+        ..entry
+    }];
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert!(daemon_config.servers.is_empty());
+    let cached = ConfigCache::new(state_dir.path()).load_all().await;
+    assert!(cached.is_empty(), "gone servers are dropped from the cache");
+}
+
+#[tokio::test]
+async fn reconcile_mixed_success_and_gone() {
+    let _guard = lock_and_clear();
+
+    let body = serde_json::to_string(&sample_daemon_config()).unwrap();
+    let (good_addr, _s1) = spawn_mock_api(200, &body).await;
+    let (gone_addr, _s2) = spawn_mock_api(404, "{}").await;
+
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let config_path = tmp.path().to_path_buf();
+    config::save(&config_path, &DaemonToml::default())
+        .await
+        .unwrap();
+
+    let mut daemon_config = DaemonToml {
+        servers: vec![
+            ServerEntry {
+                api_host: format!("http://{good_addr}"),
+                api_token: "good-token".into(),
+                ..Default::default()
+            },
+            ServerEntry {
+                api_host: format!("http://{gone_addr}"),
+                api_token: "gone-token".into(),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let mut state = reconcile::ReconcileState::default();
+    reconcile::reconcile_all::<MockPlatform>(&client, &config_path, &mut daemon_config, &mut state)
+        .await;
+
+    assert_eq!(applied(), vec!["wwg0"]);
+    assert_eq!(daemon_config.servers.len(), 1);
+}
+
+#[tokio::test]
+async fn connect_writes_config_and_validates() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("daemon.toml");
+
+    // First entry
+    let mut cfg = config::load(&config_path).await.unwrap();
+    let entry1 = ServerEntry {
+        api_host: "https://vpn1.example.com".into(),
+        api_token: "aaaa".into(),
+        ..Default::default()
+    };
+    config::validate_new_entry(&cfg, &entry1).unwrap();
+    cfg.servers.push(entry1);
+    config::save(&config_path, &cfg).await.unwrap();
+
+    // Reload and add second
+    let mut cfg = config::load(&config_path).await.unwrap();
+    assert_eq!(cfg.servers.len(), 1);
+
+    let entry2 = ServerEntry {
+        api_host: "https://vpn2.example.com".into(),
+        api_token: "bbbb".into(),
+        ..Default::default()
+    };
+    config::validate_new_entry(&cfg, &entry2).unwrap();
+    cfg.servers.push(entry2);
+    config::save(&config_path, &cfg).await.unwrap();
+
+    // Verify
+    let cfg = config::load(&config_path).await.unwrap();
+    assert_eq!(cfg.servers.len(), 2);
+
+    // Duplicate token rejected
+    let dup_token = ServerEntry {
+        api_host: "https://vpn3.example.com".into(),
+        api_token: "aaaa".into(),
+        ..Default::default()
+    };
+    assert!(config::validate_new_entry(&cfg, &dup_token).is_err());
+}
+
+#[tokio::test]
+async fn api_fetch_parses_valid_response() {
+    let body = serde_json::to_string(&sample_daemon_config()).unwrap();
+    let (addr, _shutdown) = spawn_mock_api(200, &body).await;
+
+    let entry = ServerEntry {
+        api_host: format!("http://{addr}"),
+        api_token: "test-token".into(),
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let result = wirewarden_daemon::api::fetch_config(&client, &entry, None).await;
+    let config = result.unwrap().config;
+    assert_eq!(config.server.name, "test-server");
     assert_eq!(config.peers.len(), 1);
     assert_eq!(config.network.cidr, "10.0.0.0/24");
 }
 
+#[tokio::test]
+async fn api_fetch_rejects_newer_config_version() {
+    let mut config = sample_daemon_config();
+    config.version = CONFIG_VERSION + 1;
+    let body = serde_json::to_string(&config).unwrap();
+    let (addr, _shutdown) = spawn_mock_api(200, &body).await;
+
+    let entry = ServerEntry {
+        api_host: format!("http://{addr}"),
+        api_token: "test-token".into(),
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let err = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        wirewarden_daemon::api::ApiError::UnsupportedVersion(v) if v == CONFIG_VERSION + 1
+    ));
+    assert!(!err.is_gone(), "the entry is kept until the daemon is upgraded");
+}
+
+#[tokio::test]
+async fn api_fetch_goes_through_proxy() {
+    let config = sample_daemon_config();
+    let body = serde_json::to_string(&config).unwrap();
+    // The mock answers any request, so it stands in for a forward proxy.
+    let (proxy_addr, _shutdown) = spawn_mock_api(200, &body).await;
+
+    let proxy = config::ProxyConfig {
+        url: format!("http://{proxy_addr}"),
+        no_proxy: None,
+    };
+    let client = wirewarden_daemon::api::build_client(Some(&proxy), &HttpConfig::default()).unwrap();
+    let entry = ServerEntry {
+        api_host: "http://api.unreachable.invalid".into(),
+        api_token: "test-token".into(),
+        ..Default::default()
+    };
+
+    let fetched = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap();
+    assert_eq!(fetched.config, config);
+}
+
+#[tokio::test]
+async fn api_fetch_trusts_pinned_certificate() {
+    let config = sample_daemon_config();
+    let (cert, key) = self_signed_cert();
+    let addr = spawn_tls_mock_api(&cert, &key, &serde_json::to_string(&config).unwrap());
+    let fingerprint = tls::fingerprint(&cert.to_der().unwrap());
+
+    let mut entry = ServerEntry {
+        api_host: format!("https://{addr}"),
+        api_token: "test-token".into(),
+        ..Default::default()
+    };
+    let untrusted = reqwest::Client::new();
+    let result = wirewarden_daemon::api::fetch_config(&untrusted, &entry, None).await;
+    assert!(result.is_err(), "self-signed certificate is rejected by default");
+
+    entry.cert_sha256 = Some(fingerprint);
+    let client = wirewarden_daemon::api::entry_client(None, &HttpConfig::default(), &entry)
+        .await
+        .unwrap();
+    let fetched = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap();
+    assert_eq!(fetched.config, config);
+
+    entry.cert_sha256 = Some("00".repeat(32));
+    let client = wirewarden_daemon::api::entry_client(None, &HttpConfig::default(), &entry)
+        .await
+        .unwrap();
+    let err = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:?}").contains("FingerprintMismatch"),
+        "a certificate other than the pinned one is refused: {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn api_fetch_trusts_pinned_ca_issued_certificate() {
+    let config = sample_daemon_config();
+    let (ca, ca_key) = issue_cert("Homelab CA", "127.0.0.2", None);
+    // Issued for another address than the one it is reached at.
+    let (cert, key) = issue_cert("api.homelab.invalid", "127.0.0.2", Some((&ca, &ca_key)));
+    let addr = spawn_tls_mock_api(&cert, &key, &serde_json::to_string(&config).unwrap());
+
+    let entry = ServerEntry {
+        api_host: format!("https://{addr}"),
+        api_token: "test-token".into(),
+        cert_sha256: Some(tls::fingerprint(&cert.to_der().unwrap())),
+        ..Default::default()
+    };
+    let client = wirewarden_daemon::api::entry_client(None, &HttpConfig::default(), &entry)
+        .await
+        .unwrap();
+    let fetched = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap();
+    assert_eq!(fetched.config, config);
+}
+
+#[tokio::test]
+async fn api_fetch_checks_the_pin_through_the_proxy() {
+    let config = sample_daemon_config();
+    let (cert, key) = self_signed_cert();
+    let api = spawn_tls_mock_api(&cert, &key, &serde_json::to_string(&config).unwrap());
+    let (proxy, tunnels) = spawn_connect_proxy(api).await;
+
+    let proxy = config::ProxyConfig {
+        url: format!("http://{proxy}"),
+        no_proxy: None,
+    };
+    let entry = ServerEntry {
+        // Only the proxy can reach it.
+        api_host: "https://api.unreachable.invalid".into(),
+        api_token: "test-token".into(),
+        cert_sha256: Some(tls::fingerprint(&cert.to_der().unwrap())),
+        ..Default::default()
+    };
+    let client = wirewarden_daemon::api::entry_client(Some(&proxy), &HttpConfig::default(), &entry)
+        .await
+        .unwrap();
+    let fetched = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap();
+    assert_eq!(fetched.config, config);
+    assert_eq!(tunnels.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn api_fetch_trusts_custom_ca() {
+    let config = sample_daemon_config();
+    let (cert, key) = self_signed_cert();
+    let addr = spawn_tls_mock_api(&cert, &key, &serde_json::to_string(&config).unwrap());
+
+    let ca = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(ca.path(), cert.to_pem().unwrap()).unwrap();
+    let entry = ServerEntry {
+        api_host: format!("https://{addr}"),
+        api_token: "test-token".into(),
+        ca_cert: Some(ca.path().to_path_buf()),
+        ..Default::default()
+    };
+    let client = wirewarden_daemon::api::entry_client(None, &HttpConfig::default(), &entry)
+        .await
+        .unwrap();
+    let fetched = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap();
+    assert_eq!(fetched.config, config);
+}
+
+#[tokio::test]
+async fn api_fetch_times_out_on_hung_api() {
+    let addr = spawn_hung_api().await;
+    let http = HttpConfig {
+        request_timeout_secs: 1,
+        ..HttpConfig::default()
+    };
+    let client = wirewarden_daemon::api::build_client(None, &http).unwrap();
+    let entry = ServerEntry {
+        api_host: format!("http://{addr}"),
+        api_token: "test-token".into(),
+        ..Default::default()
+    };
+
+    let started = Instant::now();
+    let err = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(err.is_retryable(), "timeouts are worth retrying: {err}");
+}
+
+#[test]
+fn api_build_client_rejects_invalid_proxy() {
+    let proxy = config::ProxyConfig {
+        url: "not a url".into(),
+        no_proxy: None,
+    };
+    assert!(wirewarden_daemon::api::build_client(Some(&proxy), &HttpConfig::default()).is_err());
+}
+
 #[tokio::test]
 async fn api_fetch_returns_unauthorized_on_401() {
     let (addr, _shutdown) = spawn_mock_api(401, "{}").await;
@@ -419,10 +1752,81 @@ async fn api_fetch_returns_unauthorized_on_401() {
     let entry = ServerEntry {
         api_host: format!("http://{addr}"),
         api_token: "bad-token".into(),
+        ..Default::default()
     };
 
     let client = reqwest::Client::new();
-    let result = wirewarden_daemon::api::fetch_config(&client, &entry).await;
+    let result = wirewarden_daemon::api::fetch_config(&client, &entry, None).await;
     assert!(result.is_err());
     assert!(result.unwrap_err().is_gone());
 }
+
+#[tokio::test]
+async fn api_detect_public_ip_parses_reflector_body() {
+    let (addr, _shutdown) = spawn_mock_api(200, "203.0.113.9\n").await;
+
+    let client = reqwest::Client::new();
+    let ip = wirewarden_daemon::api::detect_public_ip(&client, &format!("http://{addr}"))
+        .await
+        .unwrap();
+    assert_eq!(ip.to_string(), "203.0.113.9");
+}
+
+#[tokio::test]
+async fn api_update_endpoint_returns_stored_host() {
+    let (addr, _shutdown) =
+        spawn_mock_api(200, r#"{"endpoint_host":"203.0.113.9","changed":true}"#).await;
+
+    let entry = ServerEntry {
+        api_host: format!("http://{addr}"),
+        api_token: "test-token".into(),
+        auto_endpoint: true,
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let host = wirewarden_daemon::api::update_endpoint(&client, &entry, Uuid::new_v4(), None)
+        .await
+        .unwrap();
+    assert_eq!(host, "203.0.113.9");
+}
+
+#[tokio::test]
+async fn api_fetch_over_grpc() {
+    let addr = spawn_mock_grpc("test-token").await;
+
+    let entry = ServerEntry {
+        api_host: "http://unused.invalid".into(),
+        api_token: "test-token".into(),
+        grpc_endpoint: Some(format!("http://{addr}")),
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let config = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap()
+        .config;
+    let expected = sample_daemon_config();
+    assert_eq!(config.server.name, expected.server.name);
+    assert_eq!(config.network.cidr, expected.network.cidr);
+    assert_eq!(config.peers, expected.peers);
+}
+
+#[tokio::test]
+async fn api_fetch_over_grpc_unauthenticated_is_gone() {
+    let addr = spawn_mock_grpc("test-token").await;
+
+    let entry = ServerEntry {
+        api_host: "http://unused.invalid".into(),
+        api_token: "revoked-token".into(),
+        grpc_endpoint: Some(format!("http://{addr}")),
+        ..Default::default()
+    };
+
+    let client = reqwest::Client::new();
+    let err = wirewarden_daemon::api::fetch_config(&client, &entry, None)
+        .await
+        .unwrap_err();
+    assert!(err.is_gone(), "{err}");
+}
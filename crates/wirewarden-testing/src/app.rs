@@ -0,0 +1,178 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The API served in-process on a loopback port.
+
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+
+use actix_web::dev::ServerHandle;
+use actix_web::{App, HttpServer};
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use wirewarden_api::AppState;
+use wirewarden_api::config::Config;
+use wirewarden_api::grpc::DaemonService;
+use wirewarden_api::logging::LogControl;
+use wirewarden_api::middleware;
+use wirewarden_types::grpc::daemon_server::DaemonServer;
+
+use crate::db::TestDb;
+use crate::fixtures::{PASSWORD, WG_KEY_SECRET};
+
+/// Signs session tokens for every [`TestApp`].
+pub const JWT_SECRET: &str = "wirewarden-test-jwt-secret";
+
+/// A running API on its own database. Background jobs (the scheduler,
+/// webhooks) are not started; tests drive those directly. gRPC runs only
+/// once [`serve_grpc`](Self::serve_grpc) is called.
+pub struct TestApp {
+    pub state: AppState,
+    pub addr: SocketAddr,
+    handle: ServerHandle,
+}
+
+/// What [`TestApp::spawn`] runs with: no approvals, no mail, keys
+/// encrypted with [`WG_KEY_SECRET`].
+pub fn config() -> Config {
+    Config {
+        database_url: String::new(),
+        bind_addr: "127.0.0.1:0".into(),
+        grpc_bind_addr: None,
+        grpc_tls: None,
+        jwt_secret: JWT_SECRET.into(),
+        webauthn_rp_id: "localhost".into(),
+        webauthn_rp_origin: "http://localhost".into(),
+        wg_key_secret: WG_KEY_SECRET,
+        public_url: "http://localhost".into(),
+        require_approval: false,
+        require_email_verification: false,
+        approval_cooldown_secs: 86_400,
+        server_offline_secs: 300,
+        key_cache_capacity: 64,
+        server_token_cache_secs: 0,
+        min_daemon_version: None,
+        smtp: None,
+        mail_from: "wirewarden@localhost".into(),
+        mail_log_bodies: false,
+        allow_network_hooks: false,
+        status_page_token: None,
+        usage_retention_days: 366,
+    }
+}
+
+/// One subscriber per test process, writing through libtest's captured
+/// output so logs show only for failing tests.
+fn log_control() -> LogControl {
+    static CONTROL: OnceLock<LogControl> = OnceLock::new();
+    CONTROL
+        .get_or_init(|| {
+            let (filter, control) = LogControl::layer();
+            // Another harness may have installed a subscriber already.
+            let _ = tracing_subscriber::registry()
+                .with(filter)
+                .with(tracing_subscriber::fmt::layer().with_test_writer())
+                .try_init();
+            control
+        })
+        .clone()
+}
+
+impl TestApp {
+    pub async fn spawn(db: &TestDb) -> Self {
+        Self::spawn_with(db, config()).await
+    }
+
+    /// As [`spawn`](Self::spawn), with a [`config`] the test has adjusted.
+    pub async fn spawn_with(db: &TestDb, config: Config) -> Self {
+        let state = AppState::new(db.pool().clone(), config, log_control())
+            .expect("invalid test configuration");
+        state
+            .events
+            .relay(db.pool().clone())
+            .await
+            .expect("failed to relay test events");
+        let app_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap(middleware::Localize)
+                .wrap(middleware::RequestLogger)
+                .configure(|cfg| app_state.configure(cfg))
+        })
+        .workers(1)
+        .disable_signals()
+        .bind(("127.0.0.1", 0))
+        .expect("failed to bind test API");
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        tokio::spawn(server);
+        Self {
+            state,
+            addr,
+            handle,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Serve the daemon gRPC endpoint on another loopback port and return
+    /// its URL. It stops with the test's runtime.
+    pub fn serve_grpc(&self) -> String {
+        let incoming = TcpIncoming::bind(([127, 0, 0, 1], 0).into())
+            .expect("failed to bind test gRPC endpoint");
+        let addr = incoming
+            .local_addr()
+            .expect("test gRPC endpoint has an address");
+        let service = DaemonService {
+            store: self.state.vpn.get_ref().clone(),
+            events: self.state.events.get_ref().clone(),
+            min_daemon_version: self.state.config.min_daemon_version,
+            signer: self.state.signer.get_ref().clone(),
+            cache: self.state.daemon_cache.clone().into_inner(),
+        };
+        tokio::spawn(
+            Server::builder()
+                .add_service(DaemonServer::new(service))
+                .serve_with_incoming(incoming),
+        );
+        format!("http://{addr}")
+    }
+
+    /// An SDK client without a session.
+    pub fn client(&self) -> wirewarden_client::Client {
+        wirewarden_client::Client::new(&self.url())
+    }
+
+    /// An SDK client logged in as a fixture user.
+    pub async fn login(&self, username: &str) -> wirewarden_client::Client {
+        let mut client = self.client();
+        client
+            .login(username, PASSWORD)
+            .await
+            .expect("failed to log in fixture user");
+        client
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        // Sends the stop at once; waiting for it is not needed.
+        drop(self.handle.stop(false));
+    }
+}
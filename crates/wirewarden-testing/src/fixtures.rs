@@ -0,0 +1,257 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Builders for the rows most tests need. They go through the API's own
+//! stores, so keys are encrypted and addresses and preshared keys allocated
+//! as the routes do.
+
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use ipnetwork::IpNetwork;
+use sqlx::PgPool;
+use uuid::Uuid;
+use wirewarden_api::db::user::{User, UserStore};
+use wirewarden_api::db::vpn::{Network, VpnStore, WgClient, WgServer};
+
+/// Encrypts WireGuard private keys in test databases. [`TestApp`] uses the
+/// same secret, so it can read what fixtures write.
+///
+/// [`TestApp`]: crate::TestApp
+pub const WG_KEY_SECRET: [u8; 32] = [0x42; 32];
+
+/// The password of every fixture user.
+pub const PASSWORD: &str = "correct horse battery staple";
+
+#[derive(Debug, Clone)]
+pub struct Fixtures {
+    users: UserStore,
+    vpn: VpnStore,
+}
+
+impl Fixtures {
+    pub fn new(pool: &PgPool) -> Self {
+        Self {
+            users: UserStore::new(pool.clone()),
+            vpn: VpnStore::new(pool.clone(), WG_KEY_SECRET, 64, Duration::ZERO),
+        }
+    }
+
+    /// A user whose password is [`PASSWORD`].
+    pub async fn user(&self, username: &str) -> User {
+        self.users
+            .create(
+                username,
+                username,
+                &format!("{username}@example.com"),
+                PASSWORD,
+            )
+            .await
+            .expect("failed to create fixture user")
+    }
+
+    pub fn network<'a>(&'a self, name: &'a str) -> NetworkBuilder<'a> {
+        NetworkBuilder {
+            vpn: &self.vpn,
+            name,
+            cidr: "10.0.0.0/24",
+            owner_id: None,
+            persistent_keepalive: 25,
+            port_range: None,
+        }
+    }
+
+    pub fn server<'a>(&'a self, network: &Network, name: &'a str) -> ServerBuilder<'a> {
+        ServerBuilder {
+            vpn: &self.vpn,
+            network_id: network.id,
+            name,
+            endpoint_host: None,
+            endpoint_port: None,
+            forwards_internet_traffic: false,
+        }
+    }
+
+    pub fn client<'a>(&'a self, network: &Network, name: &'a str) -> ClientBuilder<'a> {
+        ClientBuilder {
+            vpn: &self.vpn,
+            network_id: network.id,
+            name,
+        }
+    }
+}
+
+/// A network on `10.0.0.0/24` with a 25 second keepalive and no port range,
+/// unless told otherwise.
+#[derive(Debug)]
+pub struct NetworkBuilder<'a> {
+    vpn: &'a VpnStore,
+    name: &'a str,
+    cidr: &'a str,
+    owner_id: Option<Uuid>,
+    persistent_keepalive: i32,
+    port_range: Option<RangeInclusive<i32>>,
+}
+
+impl<'a> NetworkBuilder<'a> {
+    pub fn cidr(mut self, cidr: &'a str) -> Self {
+        self.cidr = cidr;
+        self
+    }
+
+    pub fn owner(mut self, user: &User) -> Self {
+        self.owner_id = Some(user.id);
+        self
+    }
+
+    pub fn persistent_keepalive(mut self, secs: i32) -> Self {
+        self.persistent_keepalive = secs;
+        self
+    }
+
+    pub fn port_range(mut self, range: RangeInclusive<i32>) -> Self {
+        self.port_range = Some(range);
+        self
+    }
+
+    pub async fn create(self) -> Network {
+        let cidr: IpNetwork = self.cidr.parse().expect("invalid fixture CIDR");
+        let network = self
+            .vpn
+            .create_network(
+                self.name,
+                cidr,
+                self.owner_id,
+                &[],
+                self.persistent_keepalive,
+            )
+            .await
+            .expect("failed to create fixture network");
+        if self.port_range.is_none() {
+            return network;
+        }
+        self.vpn
+            .set_network_port_range(network.id, self.port_range)
+            .await
+            .expect("failed to set fixture port range")
+            .expect("fixture network vanished")
+    }
+}
+
+/// A server with no public endpoint, listening on the port the API would
+/// pick, unless told otherwise. Gets a preshared key with every client
+/// already on the network.
+#[derive(Debug)]
+pub struct ServerBuilder<'a> {
+    vpn: &'a VpnStore,
+    network_id: Uuid,
+    name: &'a str,
+    endpoint_host: Option<&'a str>,
+    endpoint_port: Option<i32>,
+    forwards_internet_traffic: bool,
+}
+
+impl<'a> ServerBuilder<'a> {
+    pub fn endpoint(mut self, host: &'a str, port: i32) -> Self {
+        self.endpoint_host = Some(host);
+        self.endpoint_port = Some(port);
+        self
+    }
+
+    /// A public endpoint on the port the API would pick.
+    pub fn endpoint_host(mut self, host: &'a str) -> Self {
+        self.endpoint_host = Some(host);
+        self
+    }
+
+    pub fn forwards_internet_traffic(mut self) -> Self {
+        self.forwards_internet_traffic = true;
+        self
+    }
+
+    pub async fn create(self) -> WgServer {
+        let endpoint_port = match self.endpoint_port {
+            Some(port) => port,
+            None => self
+                .vpn
+                .next_port(self.network_id, self.endpoint_host)
+                .await
+                .expect("failed to pick fixture port"),
+        };
+        let key = self
+            .vpn
+            .create_key()
+            .await
+            .expect("failed to create fixture key");
+        let server = self
+            .vpn
+            .create_server(
+                self.network_id,
+                self.name,
+                key.id,
+                self.forwards_internet_traffic,
+                self.endpoint_host,
+                endpoint_port,
+            )
+            .await
+            .expect("failed to create fixture server");
+        let clients = self
+            .vpn
+            .list_clients_by_network(self.network_id)
+            .await
+            .expect("failed to list fixture clients");
+        for client in clients {
+            self.vpn
+                .ensure_psk(server.id, client.id)
+                .await
+                .expect("failed to create fixture preshared key");
+        }
+        server
+    }
+}
+
+/// A client. Gets a preshared key with every server already on the network.
+#[derive(Debug)]
+pub struct ClientBuilder<'a> {
+    vpn: &'a VpnStore,
+    network_id: Uuid,
+    name: &'a str,
+}
+
+impl ClientBuilder<'_> {
+    pub async fn create(self) -> WgClient {
+        let key = self
+            .vpn
+            .create_key()
+            .await
+            .expect("failed to create fixture key");
+        let client = self
+            .vpn
+            .create_client(self.network_id, self.name, key.id)
+            .await
+            .expect("failed to create fixture client");
+        let servers = self
+            .vpn
+            .list_servers_by_network(self.network_id)
+            .await
+            .expect("failed to list fixture servers");
+        for server in servers {
+            self.vpn
+                .ensure_psk(server.id, client.id)
+                .await
+                .expect("failed to create fixture preshared key");
+        }
+        client
+    }
+}
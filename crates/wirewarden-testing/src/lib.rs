@@ -0,0 +1,39 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Test harness for the API: a throwaway PostgreSQL database per test,
+//! fixture builders for the common rows, and the API served in-process.
+//!
+//! ```ignore
+//! let Some(db) = TestDb::new().await else { return };
+//! let fixtures = Fixtures::new(db.pool());
+//! fixtures.user("alice").await;
+//! let network = fixtures.network("home").create().await;
+//! let app = TestApp::spawn(&db).await;
+//! let client = app.login("alice").await;
+//! ```
+//!
+//! Without PostgreSQL the database tests fail; set
+//! [`db::SKIP_VAR`] to skip them instead.
+//!
+//! `tests/contract.rs` runs the daemon's reconcile loop against a
+//! [`TestApp`], so changes to the daemon protocol are checked from both ends.
+
+pub mod app;
+pub mod db;
+pub mod fixtures;
+
+pub use app::TestApp;
+pub use db::TestDb;
+pub use fixtures::Fixtures;
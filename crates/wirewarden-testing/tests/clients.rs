@@ -0,0 +1,657 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{TimeDelta, Utc};
+use reqwest::header::COOKIE;
+use wirewarden_api::db::audit::AuditStore;
+use wirewarden_api::db::usage::UsageStore;
+use wirewarden_api::scheduler::Scheduler;
+use wirewarden_client::ListParams;
+use wirewarden_testing::{Fixtures, TestApp, TestDb};
+use wirewarden_types::api::{Activity, CreateClientRequest, Hooks, PeerKind, UpdateNotesRequest};
+use wirewarden_types::daemon::{DaemonPeerTelemetry, DaemonTelemetry};
+
+#[tokio::test]
+async fn moving_a_client_keeps_its_keys_and_moves_its_peers() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").cidr("10.1.0.0/24").create().await;
+    let office = fixtures
+        .network("office")
+        .cidr("10.2.0.0/24")
+        .create()
+        .await;
+    let home_gw = fixtures.server(&home, "gateway").create().await;
+    let office_gw = fixtures.server(&office, "gateway").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    // Holds the office's first free address, so the laptop gets the next.
+    fixtures.client(&office, "desktop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let before = client.get_client(laptop.id).await.unwrap();
+    let home_config = client.daemon_config(&home_gw.api_token).await.unwrap();
+    assert!(
+        home_config
+            .peers
+            .iter()
+            .any(|p| p.public_key == before.public_key)
+    );
+
+    let moved = client.move_client(laptop.id, office.id).await.unwrap();
+    assert_eq!(moved.network_id, office.id);
+    assert_eq!(moved.public_key, before.public_key);
+    assert_eq!(moved.address, "10.2.0.3");
+
+    let home_config = client.daemon_config(&home_gw.api_token).await.unwrap();
+    assert!(
+        home_config
+            .peers
+            .iter()
+            .all(|p| p.public_key != before.public_key)
+    );
+    let office_config = client.daemon_config(&office_gw.api_token).await.unwrap();
+    let peer = office_config
+        .peers
+        .iter()
+        .find(|p| p.public_key == before.public_key)
+        .expect("moved client is not a peer of the office gateway");
+    assert_eq!(peer.allowed_ips, ["10.2.0.3/32"]);
+    assert!(peer.preshared_key.is_some());
+    let rendered = client.client_config(laptop.id, false).await.unwrap();
+    assert!(rendered.contains("10.2.0.3"));
+
+    let audit = AuditStore::new(db.pool().clone());
+    for network in [home.id, office.id] {
+        let entries = audit.list(Some(network), 10).await.unwrap();
+        let entry = entries
+            .iter()
+            .find(|e| e.action == "client.moved")
+            .expect("move missing from a network's audit log");
+        assert_eq!(entry.target_id, Some(laptop.id));
+        assert_eq!(entry.details["from_address"], "10.1.0.2");
+        assert_eq!(entry.details["to_address"], "10.2.0.3");
+    }
+
+    let again = client.move_client(laptop.id, office.id).await.unwrap_err();
+    assert_eq!(again.status(), Some(400));
+    fixtures.client(&home, "Laptop").create().await;
+    let taken = client.move_client(laptop.id, home.id).await.unwrap_err();
+    assert_eq!(taken.status(), Some(409));
+}
+
+#[tokio::test]
+async fn usage_adds_up_daemon_counters() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let gateway = fixtures.server(&home, "gateway").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let public_key = client.get_client(laptop.id).await.unwrap().public_key;
+    let report = |rx_bytes, tx_bytes| DaemonTelemetry {
+        peers: vec![
+            DaemonPeerTelemetry {
+                public_key: public_key.clone(),
+                rx_bytes,
+                tx_bytes,
+                last_handshake: None,
+            },
+            // Not a client of the network.
+            DaemonPeerTelemetry {
+                public_key: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".into(),
+                rx_bytes: 1 << 40,
+                tx_bytes: 1 << 40,
+                last_handshake: None,
+            },
+        ],
+        handshakes: false,
+    };
+    // The first report is the baseline; the last is after a counter reset.
+    for (rx, tx) in [(1000, 2000), (1500, 2600), (100, 50)] {
+        client
+            .daemon_telemetry(&gateway.api_token, &report(rx, tx))
+            .await
+            .unwrap();
+    }
+
+    let usage = client.client_usage(laptop.id, "24h").await.unwrap();
+    assert_eq!(usage.step, "hour");
+    assert_eq!((usage.rx_bytes, usage.tx_bytes), (600, 650));
+    assert_eq!(usage.points.len(), 1);
+    let usage = client.client_usage(laptop.id, "30d").await.unwrap();
+    assert_eq!(usage.step, "day");
+    assert_eq!((usage.rx_bytes, usage.tx_bytes), (600, 650));
+
+    let err = client.client_usage(laptop.id, "7x").await.unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    let err = client
+        .daemon_telemetry("not-a-token", &report(0, 0))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(401));
+
+    // Old hours fold into days, and days past retention go.
+    sqlx::query(
+        "INSERT INTO client_usage (client_id, resolution, bucket, rx_bytes, tx_bytes)
+         VALUES ($1, 'hour', date_trunc('day', now(), 'UTC') - interval '20 days 1 hour', 10, 20),
+                ($1, 'hour', date_trunc('day', now(), 'UTC') - interval '20 days 2 hours', 1, 2),
+                ($1, 'day', date_trunc('day', now(), 'UTC') - interval '400 days', 5, 5)",
+    )
+    .bind(laptop.id)
+    .execute(db.pool())
+    .await
+    .unwrap();
+    UsageStore::new(db.pool().clone())
+        .rollup(366)
+        .await
+        .unwrap();
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT resolution, rx_bytes FROM client_usage WHERE client_id = $1 ORDER BY bucket",
+    )
+    .bind(laptop.id)
+    .fetch_all(db.pool())
+    .await
+    .unwrap();
+    assert_eq!(rows, [("day".to_string(), 11), ("hour".to_string(), 600)]);
+    let usage = client.client_usage(laptop.id, "30d").await.unwrap();
+    assert_eq!((usage.rx_bytes, usage.tx_bytes), (611, 672));
+}
+
+#[tokio::test]
+async fn clients_over_quota_drop_out_of_daemon_configs() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let gateway = fixtures.server(&home, "gateway").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let set_quota = |monthly_quota_bytes| UpdateNotesRequest {
+        notes: None,
+        mtu: None,
+        persistent_keepalive: None,
+        monthly_quota_bytes: Some(monthly_quota_bytes),
+        upload_kbps: None,
+        download_kbps: None,
+        hooks: Hooks::default(),
+    };
+    let updated = client
+        .update_client(laptop.id, &set_quota(1000))
+        .await
+        .unwrap();
+    assert_eq!(updated.monthly_quota_bytes, Some(1000));
+    assert!(!updated.quota_exceeded);
+    let err = client
+        .update_client(laptop.id, &set_quota(-1))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+
+    let report = |bytes| DaemonTelemetry {
+        peers: vec![DaemonPeerTelemetry {
+            public_key: updated.public_key.clone(),
+            rx_bytes: bytes,
+            tx_bytes: bytes,
+            last_handshake: None,
+        }],
+        handshakes: false,
+    };
+    let peers = async || {
+        client
+            .daemon_config(&gateway.api_token)
+            .await
+            .unwrap()
+            .peers
+            .len()
+    };
+    // Baseline, then 800 bytes: under the quota.
+    for bytes in [0, 400] {
+        client
+            .daemon_telemetry(&gateway.api_token, &report(bytes))
+            .await
+            .unwrap();
+    }
+    assert!(!client.get_client(laptop.id).await.unwrap().quota_exceeded);
+    assert_eq!(peers().await, 1);
+
+    client
+        .daemon_telemetry(&gateway.api_token, &report(500))
+        .await
+        .unwrap();
+    assert!(client.get_client(laptop.id).await.unwrap().quota_exceeded);
+    assert_eq!(peers().await, 0);
+
+    let raised = client
+        .update_client(laptop.id, &set_quota(5000))
+        .await
+        .unwrap();
+    assert!(!raised.quota_exceeded);
+    assert_eq!(peers().await, 1);
+    let cleared = client.update_client(laptop.id, &set_quota(0)).await.unwrap();
+    assert_eq!(cleared.monthly_quota_bytes, None);
+
+    let actions: Vec<String> = AuditStore::new(db.pool().clone())
+        .list(Some(home.id), 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|e| e.action)
+        .filter(|a| a.starts_with("client.quota"))
+        .collect();
+    assert_eq!(actions, ["client.quota_restored", "client.quota_exceeded"]);
+}
+
+#[tokio::test]
+async fn tag_schedules_shut_out_tagged_clients_without_their_own() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let gateway = fixtures.server(&home, "gateway").create().await;
+    let tablet = fixtures.client(&home, "tablet").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+    let scheduler = Scheduler::new(&app.state);
+    for id in [tablet.id, laptop.id] {
+        client
+            .set_client_tags(id, vec!["kids".into()])
+            .await
+            .unwrap();
+    }
+
+    // Daily windows relative to whatever time the test runs.
+    let now = Utc::now();
+    let window = |from: i64, to: i64| {
+        let at = |hours| {
+            (now + TimeDelta::hours(hours))
+                .format("%H:%M:%S")
+                .to_string()
+        };
+        serde_json::json!({
+            "timezone": "UTC",
+            "windows": [{
+                "days": ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+                "start": at(from),
+                "end": at(to),
+            }],
+        })
+    };
+    let url = format!(
+        "{}/api/networks/{}/access-schedules/kids",
+        app.url(),
+        home.id
+    );
+    let http = reqwest::Client::new();
+    let cookie = format!("token={}", client.token().unwrap());
+    let resp = http
+        .put(&url)
+        .header(COOKIE, &cookie)
+        .json(&window(1, 2))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // The laptop's own, open schedule takes precedence over its tag's.
+    let own = format!("{}/api/clients/{}/access-schedule", app.url(), laptop.id);
+    let resp = http
+        .put(&own)
+        .header(COOKIE, &cookie)
+        .json(&window(-1, 1))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    scheduler.sync_access_windows().await;
+    assert!(!client.get_client(tablet.id).await.unwrap().access_allowed);
+    assert!(client.get_client(laptop.id).await.unwrap().access_allowed);
+    let config = client.daemon_config(&gateway.api_token).await.unwrap();
+    assert_eq!(config.peers.len(), 1);
+
+    // Removing the tag's schedule lets the tablet back in.
+    let resp = http
+        .delete(&url)
+        .header(COOKIE, &cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+    scheduler.sync_access_windows().await;
+    assert!(client.get_client(tablet.id).await.unwrap().access_allowed);
+
+    let resp = http
+        .put(&url)
+        .header(COOKIE, &cookie)
+        .json(&serde_json::json!({ "timezone": "Mars/Olympus_Mons", "windows": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn rate_limits_reach_daemon_configs() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let gateway = fixtures.server(&home, "gateway").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let set_caps = |upload_kbps, download_kbps| UpdateNotesRequest {
+        notes: None,
+        mtu: None,
+        persistent_keepalive: None,
+        monthly_quota_bytes: None,
+        upload_kbps,
+        download_kbps,
+        hooks: Hooks::default(),
+    };
+    let updated = client
+        .update_client(laptop.id, &set_caps(Some(1000), Some(8000)))
+        .await
+        .unwrap();
+    assert_eq!(updated.upload_kbps, Some(1000));
+    assert_eq!(updated.download_kbps, Some(8000));
+    let err = client
+        .update_client(laptop.id, &set_caps(Some(1), None))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+
+    let config = client.daemon_config(&gateway.api_token).await.unwrap();
+    let peer = &config.peers[0];
+    assert_eq!(peer.upload_kbps, Some(1000));
+    assert_eq!(peer.download_kbps, Some(8000));
+
+    // An absent cap is kept; zero clears it.
+    let updated = client
+        .update_client(laptop.id, &set_caps(Some(0), None))
+        .await
+        .unwrap();
+    assert_eq!(updated.upload_kbps, None);
+    assert_eq!(updated.download_kbps, Some(8000));
+    let config = client.daemon_config(&gateway.api_token).await.unwrap();
+    assert_eq!(config.peers[0].upload_kbps, None);
+}
+
+#[tokio::test]
+async fn handshakes_record_connection_events() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let gateway = fixtures.server(&home, "gateway").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    let phone = fixtures.client(&home, "phone").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let laptop_key = client.get_client(laptop.id).await.unwrap().public_key;
+    let phone_key = client.get_client(phone.id).await.unwrap().public_key;
+    let report = |laptop_age: i64| DaemonTelemetry {
+        peers: vec![
+            DaemonPeerTelemetry {
+                public_key: laptop_key.clone(),
+                rx_bytes: 0,
+                tx_bytes: 0,
+                last_handshake: Some(Utc::now() - TimeDelta::seconds(laptop_age)),
+            },
+            // Never connected: a baseline, not an event.
+            DaemonPeerTelemetry {
+                public_key: phone_key.clone(),
+                rx_bytes: 0,
+                tx_bytes: 0,
+                last_handshake: None,
+            },
+        ],
+        handshakes: true,
+    };
+    // Connects, stays up, then goes quiet past the handshake timeout.
+    for age in [10, 60, 600] {
+        client
+            .daemon_telemetry(&gateway.api_token, &report(age))
+            .await
+            .unwrap();
+    }
+
+    let events = client.connection_events(Some(home.id), None).await.unwrap();
+    let changes: Vec<_> = events
+        .iter()
+        .map(|e| (e.peer_id, e.peer_name.as_str(), e.connected))
+        .collect();
+    assert_eq!(
+        changes,
+        [(laptop.id, "laptop", false), (laptop.id, "laptop", true)]
+    );
+    assert!(events.iter().all(|e| e.server_id == gateway.id));
+    assert_eq!(events[0].peer_kind, PeerKind::Client);
+    let phone_events = client.connection_events(None, Some(phone.id)).await.unwrap();
+    assert!(phone_events.is_empty());
+
+    // Daemons without handshake support leave the state alone.
+    let mut stale = report(10);
+    stale.handshakes = false;
+    client
+        .daemon_telemetry(&gateway.api_token, &stale)
+        .await
+        .unwrap();
+    let events = client.connection_events(Some(home.id), None).await.unwrap();
+    assert_eq!(events.len(), 2);
+}
+
+#[tokio::test]
+async fn activity_merges_audit_and_connection_events() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let office = fixtures.network("office").create().await;
+    let gateway = fixtures.server(&office, "gateway").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let moved = client.move_client(laptop.id, office.id).await.unwrap();
+    let telemetry = DaemonTelemetry {
+        peers: vec![DaemonPeerTelemetry {
+            public_key: moved.public_key,
+            rx_bytes: 0,
+            tx_bytes: 0,
+            last_handshake: Some(Utc::now()),
+        }],
+        handshakes: true,
+    };
+    client
+        .daemon_telemetry(&gateway.api_token, &telemetry)
+        .await
+        .unwrap();
+
+    let feed = client
+        .activity(Some(office.id), &ListParams::default())
+        .await
+        .unwrap();
+    assert_eq!(feed.total, 2);
+    match &feed.items[..] {
+        [Activity::Connection(connected), Activity::Audit(moved)] => {
+            assert_eq!(connected.peer_id, laptop.id);
+            assert!(connected.connected);
+            assert_eq!(moved.action, "client.moved");
+            assert_eq!(moved.target_id, Some(laptop.id));
+        }
+        other => panic!("unexpected feed: {other:?}"),
+    }
+
+    let all = client.activity(None, &ListParams::default()).await.unwrap();
+    assert_eq!(all.total, 3);
+    let second = ListParams {
+        page: Some(2),
+        per_page: Some(2),
+        ..Default::default()
+    };
+    let page = client.activity(None, &second).await.unwrap();
+    assert_eq!((page.items.len(), page.total), (1, 3));
+    assert_eq!(page.items[0], all.items[2]);
+}
+
+#[tokio::test]
+async fn a_failed_create_leaves_a_key_that_purging_removes() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    // The key is generated before the insert that fails on the name.
+    let duplicate = CreateClientRequest {
+        network_id: home.id,
+        name: "laptop".into(),
+        tags: Vec::new(),
+        notes: None,
+        mtu: None,
+        persistent_keepalive: None,
+        monthly_quota_bytes: None,
+        upload_kbps: None,
+        download_kbps: None,
+        hooks: Hooks::default(),
+    };
+    assert!(client.create_client(&duplicate).await.is_err());
+
+    // Within the grace period the key could still belong to a create in
+    // progress.
+    assert!(client.find_orphans().await.unwrap().keys.is_empty());
+    sqlx::query("UPDATE wg_keys SET created_at = now() - interval '2 hours'")
+        .execute(db.pool())
+        .await
+        .unwrap();
+
+    let found = client.find_orphans().await.unwrap();
+    assert_eq!(found.keys.len(), 1);
+    assert_eq!(client.purge_orphans().await.unwrap().keys, found.keys);
+    assert!(client.find_orphans().await.unwrap().keys.is_empty());
+
+    let kept: i64 = sqlx::query_scalar(
+        "SELECT count(*) FROM wg_keys k JOIN wg_clients c ON c.key_id = k.id WHERE c.id = $1",
+    )
+    .bind(laptop.id)
+    .fetch_one(db.pool())
+    .await
+    .unwrap();
+    assert_eq!(kept, 1);
+}
+
+#[tokio::test]
+async fn an_update_applies_all_or_nothing_and_is_audited_once() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let update = |mtu| UpdateNotesRequest {
+        notes: Some("desk drawer".into()),
+        mtu: Some(mtu),
+        persistent_keepalive: Some(25),
+        monthly_quota_bytes: None,
+        upload_kbps: None,
+        download_kbps: None,
+        hooks: Hooks {
+            post_up: Some("logger up".into()),
+            ..Default::default()
+        },
+    };
+
+    // The MTU is invalid, so the notes, keepalive and hook are not written
+    // either.
+    let err = client
+        .update_client(laptop.id, &update(100))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    let unchanged = client.get_client(laptop.id).await.unwrap();
+    assert_eq!(unchanged.notes, None);
+    assert_eq!(unchanged.persistent_keepalive, None);
+    assert_eq!(unchanged.hooks, Hooks::default());
+
+    let updated = client
+        .update_client(laptop.id, &update(1380))
+        .await
+        .unwrap();
+    assert_eq!(updated.notes.as_deref(), Some("desk drawer"));
+    assert_eq!(updated.mtu, Some(1380));
+
+    let audit = AuditStore::new(db.pool().clone());
+    let entries: Vec<_> = audit
+        .list(Some(home.id), 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|e| e.action == "client.updated")
+        .collect();
+    assert_eq!(entries.len(), 1);
+    let details = &entries[0].details;
+    assert_eq!(details["mtu"]["from"], serde_json::Value::Null);
+    assert_eq!(details["mtu"]["to"], 1380);
+    assert_eq!(details["notes"]["to"], "desk drawer");
+    assert_eq!(details["persistent_keepalive"]["to"], 25);
+    assert_eq!(details["hooks"]["to"]["post_up"], "logger up");
+    assert!(details.get("upload_kbps").is_none());
+
+    // Writing the same values again changes nothing, so records nothing.
+    client
+        .update_client(laptop.id, &update(1380))
+        .await
+        .unwrap();
+    let count = audit
+        .list(Some(home.id), 10)
+        .await
+        .unwrap()
+        .iter()
+        .filter(|e| e.action == "client.updated")
+        .count();
+    assert_eq!(count, 1);
+}
@@ -0,0 +1,523 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The API and the daemon together: configs rendered by the real API from a
+//! real database, fetched and applied by the daemon's reconcile loop against
+//! a platform that records what it was told. A field added on one side and
+//! not the other, or rendered differently over REST and gRPC, fails here.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use wirewarden_api::db::vpn::WgServer;
+use wirewarden_api::signing::ConfigSigner;
+use wirewarden_client::ListParams;
+use wirewarden_daemon::cache::ConfigCache;
+use wirewarden_daemon::config::{DaemonToml, InterfaceNaming, ServerEntry};
+use wirewarden_daemon::netlink::{DeviceState, Platform, PlatformError, Transfer, has_prefix};
+use wirewarden_daemon::reconcile::{self, ReconcileState};
+use wirewarden_testing::{Fixtures, TestApp, TestDb};
+use wirewarden_types::api::{Client, Network, Server, User};
+use wirewarden_types::daemon::{CAPABILITIES_HEADER, Capabilities, DaemonConfig};
+
+/// Interfaces the daemon has created, with the config last applied to each.
+/// Tests run in parallel, so each names its interfaces with its own prefix.
+static DEVICES: Mutex<BTreeMap<String, Option<DaemonConfig>>> = Mutex::new(BTreeMap::new());
+
+fn devices() -> MutexGuard<'static, BTreeMap<String, Option<DaemonConfig>>> {
+    DEVICES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// What `name` was last configured with.
+fn applied(name: &str) -> Option<DaemonConfig> {
+    devices().get(name).cloned().flatten()
+}
+
+struct RecordingPlatform;
+
+impl Platform for RecordingPlatform {
+    async fn ensure_interface(name: &str) -> Result<(), PlatformError> {
+        devices().entry(name.to_string()).or_default();
+        Ok(())
+    }
+
+    async fn remove_interface(name: &str) -> Result<(), PlatformError> {
+        devices().remove(name);
+        Ok(())
+    }
+
+    async fn apply_config(
+        name: &str,
+        config: &DaemonConfig,
+        _prev: Option<&DaemonConfig>,
+    ) -> Result<(), PlatformError> {
+        devices().insert(name.to_string(), Some(config.clone()));
+        Ok(())
+    }
+
+    async fn interface_exists(name: &str) -> Result<bool, PlatformError> {
+        Ok(devices().contains_key(name))
+    }
+
+    async fn device_state(_name: &str) -> Result<DeviceState, PlatformError> {
+        Ok(DeviceState::default())
+    }
+
+    async fn peer_handshakes(
+        name: &str,
+    ) -> Result<HashMap<String, Option<SystemTime>>, PlatformError> {
+        Ok(applied(name)
+            .into_iter()
+            .flat_map(|c| c.peers)
+            .map(|p| (p.public_key, None))
+            .collect())
+    }
+
+    async fn peer_transfer(name: &str) -> Result<HashMap<String, Transfer>, PlatformError> {
+        Ok(applied(name)
+            .into_iter()
+            .flat_map(|c| c.peers)
+            .map(|p| (p.public_key, Transfer::default()))
+            .collect())
+    }
+
+    async fn remove_peers(name: &str, public_keys: &[String]) -> Result<(), PlatformError> {
+        if let Some(Some(config)) = devices().get_mut(name) {
+            config
+                .peers
+                .retain(|p| !public_keys.contains(&p.public_key));
+        }
+        Ok(())
+    }
+
+    async fn rename_interface(old: &str, new: &str) -> Result<(), PlatformError> {
+        let mut devices = devices();
+        let config = devices.remove(old).flatten();
+        devices.insert(new.to_string(), config);
+        Ok(())
+    }
+
+    async fn list_managed_interfaces(
+        prefixes: &[&str],
+    ) -> Result<HashMap<String, String>, PlatformError> {
+        Ok(devices()
+            .iter()
+            .filter(|(name, _)| prefixes.iter().any(|p| has_prefix(name, p)))
+            .filter_map(|(name, config)| {
+                let config = config.as_ref()?;
+                Some((name.clone(), config.server.private_key.clone()))
+            })
+            .collect())
+    }
+}
+
+/// A daemon with one server entry, naming its interfaces `<prefix>N`.
+fn daemon_toml(prefix: &str, entry: ServerEntry) -> DaemonToml {
+    DaemonToml {
+        interfaces: InterfaceNaming {
+            prefix: prefix.into(),
+            legacy_prefixes: Vec::new(),
+        },
+        servers: vec![entry],
+        ..Default::default()
+    }
+}
+
+fn entry(app: &TestApp, server: &WgServer) -> ServerEntry {
+    ServerEntry {
+        api_host: app.url(),
+        api_token: server.api_token.clone(),
+        config_key: Some(app.state.signer.public_key()),
+        ..Default::default()
+    }
+}
+
+/// One daemon cycle, polling every server whether or not it is due.
+async fn reconcile(config_path: &Path, config: &mut DaemonToml, state: &mut ReconcileState) {
+    state.poll_now();
+    reconcile::reconcile_all::<RecordingPlatform>(
+        &reqwest::Client::new(),
+        config_path,
+        config,
+        state,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn daemon_applies_what_the_api_serves() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let network = fixtures.network("home").cidr("10.7.0.0/24").create().await;
+    let server = fixtures.server(&network, "gateway").create().await;
+    fixtures
+        .server(&network, "relay")
+        .endpoint("relay.example.com", 51821)
+        .create()
+        .await;
+    fixtures.client(&network, "laptop").create().await;
+    fixtures.client(&network, "phone").create().await;
+    let app = TestApp::spawn(&db).await;
+
+    let served: serde_json::Value = reqwest::Client::new()
+        .get(format!("{}/api/daemon/config", app.url()))
+        .bearer_auth(&server.api_token)
+        .header(CAPABILITIES_HEADER, Capabilities::CURRENT.to_header())
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("daemon.toml");
+    let mut daemon_config = daemon_toml("ctrest", entry(&app, &server));
+    let mut state = ReconcileState::default();
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+
+    let config = applied("ctrest0").expect("daemon applied nothing");
+    assert_eq!(
+        serde_json::to_value(&config).unwrap(),
+        served,
+        "daemon applied something other than what the API served"
+    );
+
+    // And what was served is what the management API describes.
+    let admin = app.login("alice").await;
+    let key = app.state.vpn.get_key(server.key_id).await.unwrap();
+    assert_eq!(config.server.id, server.id);
+    assert_eq!(config.server.private_key, key.private_key);
+    assert_eq!(config.server.listen_port, server.endpoint_port);
+    assert_eq!(config.network.id, network.id);
+
+    let params = ListParams::default();
+    let clients = admin.list_clients(network.id, &params).await.unwrap().items;
+    let relays: Vec<_> = admin
+        .list_servers(network.id, &params)
+        .await
+        .unwrap()
+        .items
+        .into_iter()
+        .filter(|s| s.id != server.id)
+        .collect();
+    assert_eq!(config.peers.len(), clients.len() + relays.len());
+    for client in &clients {
+        let peer = config
+            .peers
+            .iter()
+            .find(|p| p.public_key == client.public_key)
+            .unwrap_or_else(|| panic!("client {} missing", client.name));
+        assert_eq!(peer.allowed_ips, vec![format!("{}/32", client.address)]);
+        assert_eq!(peer.endpoint, None);
+        let psk = app
+            .state
+            .vpn
+            .ensure_psk(server.id, client.id)
+            .await
+            .unwrap();
+        assert_eq!(peer.preshared_key.as_deref(), Some(psk.as_str()));
+    }
+    for relay in &relays {
+        let peer = config
+            .peers
+            .iter()
+            .find(|p| p.public_key == relay.public_key)
+            .unwrap_or_else(|| panic!("relay {} missing", relay.name));
+        assert!(peer.allowed_ips.contains(&format!("{}/32", relay.address)));
+        assert_eq!(peer.endpoint.as_deref(), Some("relay.example.com:51821"));
+        assert_eq!(peer.preshared_key, None);
+    }
+}
+
+#[tokio::test]
+async fn grpc_and_rest_apply_the_same_config() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let network = fixtures.network("home").create().await;
+    let server = fixtures.server(&network, "gateway").create().await;
+    fixtures
+        .server(&network, "relay")
+        .endpoint("relay.example.com", 51820)
+        .forwards_internet_traffic()
+        .create()
+        .await;
+    fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let grpc = app.serve_grpc();
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("daemon.toml");
+    let mut rest_config = daemon_toml("ctviarest", entry(&app, &server));
+    let mut grpc_config = daemon_toml(
+        "ctviagrpc",
+        ServerEntry {
+            grpc_endpoint: Some(grpc),
+            ..entry(&app, &server)
+        },
+    );
+    reconcile(
+        &config_path,
+        &mut grpc_config,
+        &mut ReconcileState::default(),
+    )
+    .await;
+    // Rendered into the cache REST serves from.
+    let (serial,): (i64,) = sqlx::query_as("SELECT config_serial FROM networks WHERE id = $1")
+        .bind(network.id)
+        .fetch_one(db.pool())
+        .await
+        .unwrap();
+    let cached = app
+        .state
+        .daemon_cache
+        .config_at(server.id, serial, Capabilities::CURRENT);
+    assert!(cached.is_some(), "gRPC fetch bypassed the config cache");
+    reconcile(
+        &config_path,
+        &mut rest_config,
+        &mut ReconcileState::default(),
+    )
+    .await;
+
+    let over_rest = applied("ctviarest0").expect("nothing applied over REST");
+    let over_grpc = applied("ctviagrpc0").expect("nothing applied over gRPC");
+    assert_eq!(over_grpc, over_rest);
+}
+
+#[tokio::test]
+async fn changes_and_deletion_reach_the_daemon() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let network = fixtures.network("home").create().await;
+    let server = fixtures.server(&network, "gateway").create().await;
+    fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("daemon.toml");
+    let mut daemon_config = daemon_toml("ctchange", entry(&app, &server));
+    let mut state = ReconcileState::default();
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+    assert_eq!(applied("ctchange0").unwrap().peers.len(), 1);
+
+    let phone = fixtures.client(&network, "phone").create().await;
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+    let config = applied("ctchange0").unwrap();
+    assert_eq!(config.peers.len(), 2);
+    let key = app.state.vpn.get_key(phone.key_id).await.unwrap();
+    assert!(config.peers.iter().any(|p| p.public_key == key.public_key));
+
+    app.state.vpn.delete_server(server.id).await.unwrap();
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+    assert!(!devices().contains_key("ctchange0"), "interface left up");
+    assert!(daemon_config.servers.is_empty(), "entry kept");
+}
+
+#[tokio::test]
+async fn deltas_leave_the_daemon_with_the_full_config() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let network = fixtures.network("home").create().await;
+    let server = fixtures.server(&network, "gateway").create().await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    fixtures.client(&network, "tablet").create().await;
+    let app = TestApp::spawn(&db).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("daemon.toml");
+    let mut daemon_config = daemon_toml("ctdelta", entry(&app, &server));
+    let mut state = ReconcileState::default();
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+
+    // Served as a delta on top of the first fetch.
+    app.state.vpn.delete_client(laptop.id).await.unwrap();
+    fixtures.client(&network, "phone").create().await;
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+
+    let mut full_config = daemon_toml("ctdeltafull", entry(&app, &server));
+    reconcile(&config_path, &mut full_config, &mut ReconcileState::default()).await;
+
+    let sorted = |name| {
+        let mut config = applied(name).unwrap();
+        config.peers.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        config
+    };
+    let via_delta = sorted("ctdelta0");
+    assert_eq!(via_delta.peers.len(), 2);
+    assert_eq!(via_delta, sorted("ctdeltafull0"));
+}
+
+#[tokio::test]
+async fn configs_signed_with_another_key_are_refused() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let network = fixtures.network("home").create().await;
+    let server = fixtures.server(&network, "gateway").create().await;
+    let app = TestApp::spawn(&db).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("daemon.toml");
+    let mut daemon_config = daemon_toml(
+        "ctforged",
+        ServerEntry {
+            config_key: Some(ConfigSigner::from_secret(&[9; 32]).public_key()),
+            interface: None,
+            ..entry(&app, &server)
+        },
+    );
+    reconcile(&config_path, &mut daemon_config, &mut ReconcileState::default()).await;
+    assert!(applied("ctforged0").is_none(), "unverified config applied");
+    assert_eq!(daemon_config.servers.len(), 1, "entry dropped");
+}
+
+#[tokio::test]
+async fn configs_for_another_server_are_refused() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let network = fixtures.network("home").create().await;
+    let server = fixtures.server(&network, "gateway").create().await;
+    let other = fixtures.server(&network, "relay").create().await;
+    let app = TestApp::spawn(&db).await;
+
+    // Enrolled for `other`, the validly signed config for `server` is not
+    // this entry's, even on the first fetch.
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("daemon.toml");
+    let mut daemon_config = daemon_toml(
+        "ctpinned",
+        ServerEntry {
+            server_id: Some(other.id),
+            ..entry(&app, &server)
+        },
+    );
+    reconcile(&config_path, &mut daemon_config, &mut ReconcileState::default()).await;
+    assert!(applied("ctpinned0").is_none(), "other server's config applied");
+
+    // Without an enrolled ID, the server of the cached config binds the
+    // entry after a restart.
+    daemon_config.servers[0].server_id = None;
+    let mut state = ReconcileState::default();
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+    let mut cached = applied("ctpinned0").expect("nothing applied");
+    cached.server.id = other.id;
+    let cache = ConfigCache::new(dir.path().join("state"));
+    cache.store(&server.api_token, &cached, None).await.unwrap();
+    state = ReconcileState::with_cache(cache).await;
+    fixtures.client(&network, "laptop").create().await;
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+    assert_eq!(applied("ctpinned0").unwrap(), cached, "other server's config applied");
+}
+
+#[tokio::test]
+async fn configs_older_than_the_last_applied_are_refused() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let network = fixtures.network("home").create().await;
+    let server = fixtures.server(&network, "gateway").create().await;
+    let app = TestApp::spawn(&db).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("daemon.toml");
+    let mut daemon_config = daemon_toml("ctstale", entry(&app, &server));
+    let mut state = ReconcileState::default();
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+    let first = applied("ctstale0").expect("nothing applied");
+
+    // A daemon that has since applied a serial the API is still short of,
+    // as if what it now serves were an old response replayed.
+    let cache = ConfigCache::new(dir.path().join("state"));
+    cache
+        .store(&server.api_token, &first, Some(i64::MAX))
+        .await
+        .unwrap();
+    state = ReconcileState::with_cache(cache).await;
+    fixtures.client(&network, "laptop").create().await;
+    reconcile(&config_path, &mut daemon_config, &mut state).await;
+    assert_eq!(applied("ctstale0").unwrap(), first, "older config applied");
+}
+
+/// Fails when a key of `served` doesn't survive a round trip through `T`.
+fn assert_kept_by<T: DeserializeOwned + Serialize>(served: &serde_json::Value) {
+    let parsed: T = serde_json::from_value(served.clone()).unwrap();
+    let kept = serde_json::to_value(&parsed).unwrap();
+    let dropped: Vec<_> = served
+        .as_object()
+        .unwrap()
+        .keys()
+        .filter(|key| kept.get(key.as_str()).is_none())
+        .collect();
+    assert!(
+        dropped.is_empty(),
+        "{} drops {dropped:?}",
+        std::any::type_name::<T>()
+    );
+}
+
+#[tokio::test]
+async fn sdk_types_keep_every_field_the_api_returns() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let network = fixtures.network("home").create().await;
+    let server = fixtures.server(&network, "gateway").create().await;
+    let client = fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let admin = app.login("alice").await;
+
+    let get = |path: String| {
+        let request = reqwest::Client::new()
+            .get(format!("{}{path}", app.url()))
+            .header("Cookie", format!("token={}", admin.token().unwrap()));
+        async move {
+            request
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap()
+                .json::<serde_json::Value>()
+                .await
+                .unwrap()
+        }
+    };
+
+    assert_kept_by::<User>(&get("/api/auth/me".into()).await);
+    assert_kept_by::<Network>(&get(format!("/api/networks/{}", network.id)).await);
+    assert_kept_by::<Server>(&get(format!("/api/servers/{}", server.id)).await);
+    assert_kept_by::<Client>(&get(format!("/api/clients/{}", client.id)).await);
+}
@@ -0,0 +1,95 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use wirewarden_api::db::{self, MigrationState};
+use wirewarden_client::ListParams;
+use wirewarden_testing::{Fixtures, TestApp, TestDb};
+use wirewarden_types::daemon::DaemonConfig;
+
+#[tokio::test]
+async fn databases_are_migrated_and_separate() {
+    let Some(a) = TestDb::new().await else { return };
+    let Some(b) = TestDb::new().await else { return };
+    assert_ne!(a.name(), b.name());
+
+    let status = db::migration_status(a.pool()).await.unwrap();
+    assert!(status.iter().all(|m| m.state == MigrationState::Applied));
+
+    Fixtures::new(a.pool()).network("home").create().await;
+    let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM networks")
+        .fetch_one(b.pool())
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn fixtures_allocate_addresses_and_psks() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let network = fixtures.network("home").cidr("10.9.0.0/24").create().await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let server = fixtures
+        .server(&network, "relay")
+        .endpoint("vpn.example.com", 51820)
+        .create()
+        .await;
+    let phone = fixtures.client(&network, "phone").create().await;
+
+    assert_ne!(laptop.address_offset, server.address_offset);
+    assert_ne!(server.address_offset, phone.address_offset);
+    let (psks,): (i64,) = sqlx::query_as("SELECT count(*) FROM wg_peer_psks")
+        .fetch_one(db.pool())
+        .await
+        .unwrap();
+    assert_eq!(
+        psks, 2,
+        "one per server and client pair, whichever came first"
+    );
+}
+
+#[tokio::test]
+async fn app_serves_users_and_daemons() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let network = fixtures.network("home").create().await;
+    let server = fixtures.server(&network, "relay").create().await;
+    fixtures.client(&network, "laptop").create().await;
+
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+    let networks = client.list_networks(&ListParams::default()).await.unwrap();
+    assert_eq!(networks.total, 1);
+    assert_eq!(networks.items[0].id, network.id);
+
+    let config: DaemonConfig = reqwest::Client::new()
+        .get(format!("{}/api/daemon/config", app.url()))
+        .bearer_auth(&server.api_token)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(config.server.id, server.id);
+    assert_eq!(config.peers.len(), 1);
+    assert!(config.peers[0].preshared_key.is_some());
+}
@@ -0,0 +1,105 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Migrations that rewrite existing rows, run against data written under the
+//! schema before them.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+use wirewarden_api::db;
+use wirewarden_testing::TestDb;
+
+const CASE_INSENSITIVE_NAMES: i64 = 20260210000001;
+
+/// Apply the migrations in `versions` directly, without sqlx's bookkeeping.
+async fn apply(pool: &PgPool, versions: impl Fn(i64) -> bool) {
+    for migration in db::migrations().filter(|m| versions(m.version)) {
+        sqlx::raw_sql(&migration.sql)
+            .execute(pool)
+            .await
+            .unwrap_or_else(|e| panic!("migration {} failed: {e}", migration.version));
+    }
+}
+
+async fn names(pool: &PgPool, sql: &str) -> Vec<String> {
+    sqlx::query_scalar(sql).fetch_all(pool).await.unwrap()
+}
+
+#[tokio::test]
+async fn case_insensitive_names_resolve_collisions_with_existing_suffixes() {
+    let Some(db) = TestDb::unmigrated().await else {
+        return;
+    };
+    let pool = db.pool();
+    apply(pool, |v| v < CASE_INSENSITIVE_NAMES).await;
+
+    // Created in this order, so the first of each clash keeps its name.
+    let mut networks = Vec::new();
+    for (i, name) in ["home", "Home-2", " HOME ", "Home", "lab"].iter().enumerate() {
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO networks (name, cidr_ip, created_at)
+             VALUES ($1, $2::inet, now() + make_interval(secs => $3))
+             RETURNING id",
+        )
+        .bind(name)
+        .bind(format!("10.{i}.0.0/24"))
+        .bind(i as f64)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        networks.push(id);
+    }
+    let key: Uuid = sqlx::query_scalar(
+        "INSERT INTO wg_keys (private_key_enc, private_key_nonce, public_key)
+         VALUES ('\\x00', '\\x00', 'pub') RETURNING id",
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap();
+    for (i, (network, name)) in [
+        (networks[0], "laptop"),
+        (networks[0], "LAPTOP"),
+        (networks[0], "laptop-2"),
+        (networks[4], "laptop"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        sqlx::query(
+            "INSERT INTO wg_clients (network_id, name, key_id, address_offset, created_at)
+             VALUES ($1, $2, $3, $4, now() + make_interval(secs => $4))",
+        )
+        .bind(network)
+        .bind(name)
+        .bind(key)
+        .bind(i as i32 + 2)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    apply(pool, |v| v == CASE_INSENSITIVE_NAMES).await;
+
+    assert_eq!(
+        names(pool, "SELECT name FROM networks ORDER BY created_at").await,
+        ["home", "Home-2", "HOME-3", "Home-4", "lab"]
+    );
+    assert_eq!(
+        names(pool, "SELECT name FROM wg_clients ORDER BY created_at").await,
+        ["laptop", "LAPTOP-3", "laptop-2", "laptop"]
+    );
+
+    // The rest of the history still applies on top.
+    apply(pool, |v| v > CASE_INSENSITIVE_NAMES).await;
+}
@@ -0,0 +1,1768 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use reqwest::header::COOKIE;
+use uuid::Uuid;
+use wirewarden_api::db::audit::AuditStore;
+use wirewarden_api::db::vpn::VpnStoreError;
+use wirewarden_api::events::EventKind;
+use wirewarden_client::{Client, ClientError, ConfigParams, ListParams, all_pages};
+use wirewarden_testing::{Fixtures, TestApp, TestDb};
+use wirewarden_types::api::{
+    AclAction, Allocation, ClientRouteKind, CreateAclRuleRequest, CreateClientRequest,
+    CreateNetworkRequest, CreateServerRequest, Hooks, Server, Topology, UpdateNetworkRequest,
+    UpdateNotesRequest, UpdateServerRequest,
+};
+use wirewarden_types::daemon::{DAEMON_VERSION_HEADER, DaemonAclAction, DaemonAclRule};
+
+async fn create_server(
+    client: &Client,
+    network_id: Uuid,
+    name: &str,
+    host: Option<&str>,
+    port: Option<i32>,
+) -> Result<Server, ClientError> {
+    client
+        .create_server(&CreateServerRequest {
+            network_id,
+            name: name.into(),
+            forwards_internet_traffic: false,
+            manage_nat: false,
+            fwmark: None,
+            route_table: None,
+            rule_priority: None,
+            endpoint_host: host.map(Into::into),
+            endpoint_port: port,
+            tags: Vec::new(),
+            notes: None,
+            mtu: None,
+            persistent_keepalive: None,
+            listen_port: None,
+            priority: None,
+        })
+        .await
+}
+
+#[tokio::test]
+async fn servers_get_free_ports_from_the_network_range() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    // Another network's server already holds the first port on the host.
+    let other = fixtures.network("other").cidr("10.1.0.0/24").create().await;
+    fixtures
+        .server(&other, "old")
+        .endpoint("Relay.example.com", 51900)
+        .create()
+        .await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let network = client
+        .create_network(&CreateNetworkRequest {
+            name: "home".into(),
+            cidr: "10.2.0.0/24".into(),
+            dns_servers: Vec::new(),
+            persistent_keepalive: 25,
+            notes: None,
+            port_range: Some("51900-51902".into()),
+            endpoint_template: None,
+            allocation: Allocation::default(),
+            mtu: None,
+            excluded_cidrs: Vec::new(),
+            lan_access: None,
+            hooks: Hooks::default(),
+            search_domains: Vec::new(),
+            topology: Topology::default(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(network.port_range.as_deref(), Some("51900-51902"));
+
+    let host = Some("relay.example.com");
+    let a = create_server(&client, network.id, "a", host, None)
+        .await
+        .unwrap();
+    let b = create_server(&client, network.id, "b", host, None)
+        .await
+        .unwrap();
+    let elsewhere = create_server(&client, network.id, "c", Some("other.example.com"), None)
+        .await
+        .unwrap();
+    assert_eq!((a.endpoint_port, b.endpoint_port), (51901, 51902));
+    assert_eq!(elsewhere.endpoint_port, 51900);
+
+    let full = create_server(&client, network.id, "d", host, None)
+        .await
+        .unwrap_err();
+    assert_eq!(full.status(), Some(400));
+    let outside = create_server(&client, network.id, "e", host, Some(51820))
+        .await
+        .unwrap_err();
+    assert_eq!(outside.status(), Some(400));
+
+    let network = client
+        .update_network(
+            network.id,
+            &UpdateNetworkRequest {
+                dns_servers: Vec::new(),
+                persistent_keepalive: 25,
+                enabled: None,
+                notes: None,
+                port_range: Some(String::new()),
+                endpoint_template: None,
+                allocation: None,
+                mtu: None,
+                excluded_cidrs: None,
+                lan_access: None,
+                hooks: Hooks::default(),
+                search_domains: None,
+                topology: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(network.port_range, None);
+    let unrestricted = create_server(&client, network.id, "f", host, None)
+        .await
+        .unwrap();
+    assert_eq!(unrestricted.endpoint_port, 51820);
+}
+
+#[tokio::test]
+async fn all_pages_collects_every_page() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    for i in 0..5 {
+        fixtures
+            .network(&format!("net{i}"))
+            .cidr(&format!("10.{i}.0.0/24"))
+            .create()
+            .await;
+    }
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let params = ListParams {
+        per_page: Some(2),
+        sort: Some("name".into()),
+        ..Default::default()
+    };
+    let networks = all_pages(&params, async |p| client.list_networks(p).await)
+        .await
+        .unwrap();
+    let names: Vec<_> = networks.iter().map(|n| n.name.as_str()).collect();
+    assert_eq!(names, ["net0", "net1", "net2", "net3", "net4"]);
+}
+
+#[tokio::test]
+async fn servers_sharing_a_host_need_distinct_ports() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").cidr("10.1.0.0/24").create().await;
+    let office = fixtures
+        .network("office")
+        .cidr("10.2.0.0/24")
+        .create()
+        .await;
+    let roaming = fixtures
+        .server(&office, "roaming")
+        .endpoint("198.51.100.7", 51821)
+        .create()
+        .await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    // Without port ranges, servers on one host count up from 51820.
+    let host = Some("edge.example.com");
+    let a = create_server(&client, home.id, "a", host, None)
+        .await
+        .unwrap();
+    let b = create_server(&client, office.id, "b", host, None)
+        .await
+        .unwrap();
+    assert_eq!((a.endpoint_port, b.endpoint_port), (51820, 51821));
+
+    let clash = create_server(&client, home.id, "c", Some("EDGE.example.com"), Some(51821))
+        .await
+        .unwrap_err();
+    assert_eq!(clash.status(), Some(409));
+
+    // A server reporting a new address may not land on another's port.
+    let http = reqwest::Client::new();
+    let moved = http
+        .post(format!("{}/api/servers/{}/endpoint", app.url(), roaming.id))
+        .bearer_auth(&roaming.api_token)
+        .json(&serde_json::json!({ "endpoint_host": "edge.example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(moved.status(), 409);
+    let moved = http
+        .post(format!("{}/api/servers/{}/endpoint", app.url(), roaming.id))
+        .bearer_auth(&roaming.api_token)
+        .json(&serde_json::json!({ "endpoint_host": "relay.example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(moved.status(), 200);
+}
+
+#[tokio::test]
+async fn concurrent_servers_on_one_endpoint_get_one_port_each() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let app = TestApp::spawn(&db).await;
+    let mut tasks = Vec::new();
+    for i in 0..8 {
+        let network = fixtures
+            .network(&format!("net{i}"))
+            .cidr(&format!("10.{i}.0.0/24"))
+            .create()
+            .await;
+        let vpn = app.state.vpn.get_ref().clone();
+        let key = vpn.create_key().await.unwrap();
+        tasks.push(tokio::spawn(async move {
+            vpn.create_server(
+                network.id,
+                "edge",
+                key.id,
+                false,
+                Some("edge.example.com"),
+                51820,
+            )
+            .await
+        }));
+    }
+
+    let mut created = 0;
+    for task in tasks {
+        match task.await.unwrap() {
+            Ok(_) => created += 1,
+            Err(VpnStoreError::PortInUse { port: 51820 }) => {}
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    }
+    assert_eq!(created, 1);
+}
+
+#[tokio::test]
+async fn overlong_daemon_version_is_refused() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fx = Fixtures::new(db.pool());
+    let home = fx.network("home").create().await;
+    let server = fx.server(&home, "gw").create().await;
+    let app = TestApp::spawn(&db).await;
+
+    let http = reqwest::Client::new();
+    let fetch = |version: String| {
+        http.get(format!("{}/api/daemon/config", app.url()))
+            .bearer_auth(&server.api_token)
+            .header(DAEMON_VERSION_HEADER, version)
+            .send()
+    };
+    assert_eq!(fetch("v1.2.3".into()).await.unwrap().status(), 200);
+    assert_eq!(fetch("v".repeat(65)).await.unwrap().status(), 400);
+
+    let stored = app.state.vpn.get_server(server.id).await.unwrap().unwrap();
+    assert_eq!(stored.daemon_version.as_deref(), Some("v1.2.3"));
+}
+
+#[tokio::test]
+async fn status_page_shows_enabled_networks_without_secrets() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fx = Fixtures::new(db.pool());
+    let home = fx.network("home").create().await;
+    let up = fx
+        .server(&home, "up")
+        .endpoint("vpn.example.com", 51820)
+        .create()
+        .await;
+    fx.server(&home, "down").create().await;
+    fx.client(&home, "phone").create().await;
+    let lab = fx.network("lab").create().await;
+
+    let mut config = wirewarden_testing::app::config();
+    config.status_page_token = Some("s3cret".into());
+    let app = TestApp::spawn_with(&db, config).await;
+    app.state.vpn.touch_server(up.id, None).await.unwrap();
+    app.state
+        .vpn
+        .set_network_enabled(lab.id, false)
+        .await
+        .unwrap();
+
+    let http = reqwest::Client::new();
+    let url = format!("{}/api/status-page", app.url());
+    let missing = http.get(&url).send().await.unwrap();
+    assert_eq!(missing.status(), 401);
+    let wrong = http
+        .get(&url)
+        .query(&[("token", "s3cres")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(wrong.status(), 401);
+
+    let page = http.get(&url).bearer_auth("s3cret").send().await.unwrap();
+    assert_eq!(page.status(), 200);
+    let page: serde_json::Value = page.json().await.unwrap();
+    assert_eq!(
+        page["networks"],
+        serde_json::json!([{
+            "name": "home",
+            "servers": [
+                { "name": "down", "online": false },
+                { "name": "up", "online": true },
+            ],
+            "servers_online": 1,
+            "servers_total": 2,
+            "clients": 1,
+        }])
+    );
+    let body = page.to_string();
+    assert!(!body.contains("vpn.example.com") && !body.contains(&up.api_token));
+
+    // The query string ends up in access logs, so it is no place for a token.
+    let by_query = http
+        .get(&url)
+        .query(&[("token", "s3cret")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(by_query.status(), 401);
+
+    // Without a token configured the page does not exist.
+    let app = TestApp::spawn(&db).await;
+    let url = format!("{}/api/status-page", app.url());
+    let resp = http.get(&url).bearer_auth("s3cret").send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn ipv6_only_network_configures_clients_and_servers_with_v6() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let request = |cidr: &str, dns: &str| CreateNetworkRequest {
+        name: "v6".into(),
+        cidr: cidr.into(),
+        dns_servers: vec![dns.into()],
+        persistent_keepalive: 25,
+        notes: None,
+        port_range: None,
+        endpoint_template: None,
+        allocation: Allocation::default(),
+        mtu: None,
+        excluded_cidrs: Vec::new(),
+        lan_access: None,
+        hooks: Hooks::default(),
+        search_domains: Vec::new(),
+        topology: Topology::default(),
+    };
+    let err = client
+        .create_network(&request("fd00:6::/64", "1.1.1.1"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("DNS64"), "{err}");
+    let err = client
+        .create_network(&request("2001:db8::/64", "2001:4860:4860::6464"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("unique local"), "{err}");
+
+    let network = client
+        .create_network(&request("fd00:6::/64", "2001:4860:4860::6464"))
+        .await
+        .unwrap();
+    let server = create_server(&client, network.id, "gw", Some("vpn.example.com"), None)
+        .await
+        .unwrap();
+    assert_eq!(server.address, "fd00:6::1");
+    let laptop = client
+        .create_client(&CreateClientRequest {
+            network_id: network.id,
+            name: "laptop".into(),
+            tags: Vec::new(),
+            notes: None,
+            mtu: None,
+            persistent_keepalive: None,
+            monthly_quota_bytes: None,
+            upload_kbps: None,
+            download_kbps: None,
+            hooks: Hooks::default(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(laptop.address, "fd00:6::2");
+
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(config.contains("Address = fd00:6::2/64"), "{config}");
+    assert!(config.contains("AllowedIPs = fd00:6::/64\n"), "{config}");
+
+    let daemon = client.daemon_config(&server.api_token).await.unwrap();
+    assert_eq!(daemon.server.address, "fd00:6::1/64");
+    assert_eq!(daemon.peers[0].allowed_ips, ["fd00:6::2/128"]);
+}
+
+#[tokio::test]
+async fn endpoint_template_fills_and_follows_server_hosts() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let err = client
+        .create_network(&CreateNetworkRequest {
+            name: "home".into(),
+            cidr: "10.3.0.0/24".into(),
+            dns_servers: Vec::new(),
+            persistent_keepalive: 25,
+            notes: None,
+            port_range: None,
+            endpoint_template: Some("{host}.vpn.example.com".into()),
+            allocation: Allocation::default(),
+            mtu: None,
+            excluded_cidrs: Vec::new(),
+            lan_access: None,
+            hooks: Hooks::default(),
+            search_domains: Vec::new(),
+            topology: Topology::default(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+
+    let network = client
+        .create_network(&CreateNetworkRequest {
+            name: "home".into(),
+            cidr: "10.3.0.0/24".into(),
+            dns_servers: Vec::new(),
+            persistent_keepalive: 25,
+            notes: None,
+            port_range: None,
+            endpoint_template: Some("{server}.vpn.example.com".into()),
+            allocation: Allocation::default(),
+            mtu: None,
+            excluded_cidrs: Vec::new(),
+            lan_access: None,
+            hooks: Hooks::default(),
+            search_domains: Vec::new(),
+            topology: Topology::default(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        network.endpoint_template.as_deref(),
+        Some("{server}.vpn.example.com")
+    );
+
+    let templated = create_server(&client, network.id, "Relay 1", None, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        templated.endpoint_host.as_deref(),
+        Some("relay-1.vpn.example.com")
+    );
+    let explicit = create_server(&client, network.id, "edge", Some("203.0.113.9"), None)
+        .await
+        .unwrap();
+
+    let update = |template: &str| UpdateNetworkRequest {
+        dns_servers: Vec::new(),
+        persistent_keepalive: 25,
+        enabled: None,
+        notes: None,
+        port_range: None,
+        endpoint_template: Some(template.into()),
+        allocation: None,
+        mtu: None,
+        excluded_cidrs: None,
+        lan_access: None,
+        hooks: Hooks::default(),
+        search_domains: None,
+        topology: None,
+    };
+    let before = client.get_network(network.id).await.unwrap().config_serial;
+    client
+        .update_network(network.id, &update("{server}.{network}.example.net"))
+        .await
+        .unwrap();
+    let moved = client.get_server(templated.id).await.unwrap();
+    assert_eq!(
+        moved.endpoint_host.as_deref(),
+        Some("relay-1.home.example.net")
+    );
+    let kept = client.get_server(explicit.id).await.unwrap();
+    assert_eq!(kept.endpoint_host.as_deref(), Some("203.0.113.9"));
+    assert!(client.get_network(network.id).await.unwrap().config_serial > before);
+
+    // A template too long for one server's name changes nothing at all.
+    let long_name = "l".repeat(60);
+    let long = create_server(&client, network.id, &long_name, None, None)
+        .await
+        .unwrap();
+    let err = client
+        .update_network(
+            network.id,
+            &update("{server}-{server}-{server}-{server}.example.net"),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    let network = client.get_network(network.id).await.unwrap();
+    assert_eq!(
+        network.endpoint_template.as_deref(),
+        Some("{server}.{network}.example.net")
+    );
+    let kept = client.get_server(templated.id).await.unwrap();
+    assert_eq!(
+        kept.endpoint_host.as_deref(),
+        Some("relay-1.home.example.net")
+    );
+    let kept = client.get_server(long.id).await.unwrap();
+    assert_eq!(
+        kept.endpoint_host,
+        Some(format!("{long_name}.home.example.net"))
+    );
+
+    // Clearing the template keeps the hosts servers already have.
+    let network = client
+        .update_network(network.id, &update(""))
+        .await
+        .unwrap();
+    assert_eq!(network.endpoint_template, None);
+    let kept = client.get_server(templated.id).await.unwrap();
+    assert_eq!(
+        kept.endpoint_host.as_deref(),
+        Some("relay-1.home.example.net")
+    );
+}
+
+#[tokio::test]
+async fn manage_nat_reaches_the_daemon_for_forwarding_servers() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let request = |name: &str, forwards: bool| CreateServerRequest {
+        network_id: network.id,
+        name: name.into(),
+        forwards_internet_traffic: forwards,
+        manage_nat: true,
+        fwmark: None,
+        route_table: None,
+        rule_priority: None,
+        endpoint_host: None,
+        endpoint_port: None,
+        tags: Vec::new(),
+        notes: None,
+        mtu: None,
+        persistent_keepalive: None,
+        listen_port: None,
+        priority: None,
+    };
+    let err = client
+        .create_server(&request("relay", false))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("forwards internet traffic"), "{err}");
+
+    let server = client.create_server(&request("exit", true)).await.unwrap();
+    assert!(server.manage_nat);
+    let daemon = client.daemon_config(&server.api_token).await.unwrap();
+    assert!(daemon.server.manage_nat);
+
+    let off = UpdateServerRequest {
+        manage_nat: Some(false),
+        ..Default::default()
+    };
+    assert!(!client.update_server(server.id, &off).await.unwrap().manage_nat);
+    let daemon = client.daemon_config(&server.api_token).await.unwrap();
+    assert!(!daemon.server.manage_nat);
+}
+
+#[tokio::test]
+async fn allocation_strategy_picks_addresses() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let network = client
+        .create_network(&CreateNetworkRequest {
+            name: "small".into(),
+            cidr: "10.9.0.0/29".into(),
+            dns_servers: Vec::new(),
+            persistent_keepalive: 25,
+            notes: None,
+            port_range: None,
+            endpoint_template: None,
+            allocation: Allocation::SequentialFromHigh,
+            mtu: None,
+            excluded_cidrs: Vec::new(),
+            lan_access: None,
+            hooks: Hooks::default(),
+            search_domains: Vec::new(),
+            topology: Topology::default(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(network.allocation, Allocation::SequentialFromHigh);
+    let gateway = create_server(&client, network.id, "gw", None, None)
+        .await
+        .unwrap();
+    assert_eq!(gateway.address, "10.9.0.6");
+    let relay = create_server(&client, network.id, "relay", None, None)
+        .await
+        .unwrap();
+    assert_eq!(relay.address, "10.9.0.5");
+
+    let network = client
+        .update_network(
+            network.id,
+            &UpdateNetworkRequest {
+                dns_servers: Vec::new(),
+                persistent_keepalive: 25,
+                enabled: None,
+                notes: None,
+                port_range: None,
+                endpoint_template: None,
+                allocation: Some(Allocation::RandomInRange),
+                mtu: None,
+                excluded_cidrs: None,
+                lan_access: None,
+                hooks: Hooks::default(),
+                search_domains: None,
+                topology: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(network.allocation, Allocation::RandomInRange);
+    let mut addresses = Vec::new();
+    for name in ["a", "b", "c", "d"] {
+        let device = client
+            .create_client(&CreateClientRequest {
+                network_id: network.id,
+                name: name.into(),
+                tags: Vec::new(),
+                notes: None,
+                mtu: None,
+                persistent_keepalive: None,
+                monthly_quota_bytes: None,
+                upload_kbps: None,
+                download_kbps: None,
+                hooks: Hooks::default(),
+            })
+            .await
+            .unwrap();
+        addresses.push(device.address);
+    }
+    addresses.sort();
+    assert_eq!(addresses, ["10.9.0.1", "10.9.0.2", "10.9.0.3", "10.9.0.4"]);
+    let err = create_server(&client, network.id, "full", None, None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("no available addresses"), "{err}");
+}
+
+#[tokio::test]
+async fn policy_routing_reaches_the_daemon() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let request = |name: &str, route_table: u32| CreateServerRequest {
+        network_id: network.id,
+        name: name.into(),
+        forwards_internet_traffic: true,
+        manage_nat: false,
+        fwmark: Some(51820),
+        route_table: Some(route_table),
+        rule_priority: Some(100),
+        endpoint_host: None,
+        endpoint_port: None,
+        tags: Vec::new(),
+        notes: None,
+        mtu: None,
+        persistent_keepalive: None,
+        listen_port: None,
+        priority: None,
+    };
+    let err = client
+        .create_server(&request("main", 254))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("reserved"), "{err}");
+
+    let server = client.create_server(&request("exit", 51820)).await.unwrap();
+    assert_eq!(
+        (server.fwmark, server.route_table, server.rule_priority),
+        (Some(51820), Some(51820), Some(100))
+    );
+    let token = server.api_token.clone();
+    let daemon = client.daemon_config(&token).await.unwrap();
+    assert_eq!(
+        (
+            daemon.server.fwmark,
+            daemon.server.route_table,
+            daemon.server.rule_priority
+        ),
+        (Some(51820), Some(51820), Some(100))
+    );
+
+    // A rule priority is only meaningful with a table to look up.
+    let no_table = UpdateServerRequest {
+        route_table: Some(0),
+        ..Default::default()
+    };
+    let err = client
+        .update_server(server.id, &no_table)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("route_table"), "{err}");
+
+    let cleared = UpdateServerRequest {
+        route_table: Some(0),
+        rule_priority: Some(0),
+        ..Default::default()
+    };
+    let server = client.update_server(server.id, &cleared).await.unwrap();
+    assert_eq!(
+        (server.fwmark, server.route_table, server.rule_priority),
+        (Some(51820), None, None)
+    );
+    let daemon = client.daemon_config(&token).await.unwrap();
+    assert_eq!(daemon.server.fwmark, Some(51820));
+    assert_eq!(daemon.server.route_table, None);
+}
+
+#[tokio::test]
+async fn mtu_reaches_servers_and_clients() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let request = |mtu: u32| CreateNetworkRequest {
+        name: "home".into(),
+        cidr: "fd00:7::/64".into(),
+        dns_servers: Vec::new(),
+        persistent_keepalive: 25,
+        notes: None,
+        port_range: None,
+        endpoint_template: None,
+        allocation: Allocation::default(),
+        mtu: Some(mtu),
+        excluded_cidrs: Vec::new(),
+        lan_access: None,
+        hooks: Hooks::default(),
+        search_domains: Vec::new(),
+        topology: Topology::default(),
+    };
+    // IPv6 needs at least 1280 bytes on every link.
+    let err = client.create_network(&request(1200)).await.unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    let network = client.create_network(&request(1420)).await.unwrap();
+    assert_eq!(network.mtu, Some(1420));
+
+    let server = create_server(&client, network.id, "gw", Some("vpn.example.com"), None)
+        .await
+        .unwrap();
+    let laptop = client
+        .create_client(&CreateClientRequest {
+            network_id: network.id,
+            name: "laptop".into(),
+            tags: Vec::new(),
+            notes: None,
+            mtu: None,
+            persistent_keepalive: None,
+            monthly_quota_bytes: None,
+            upload_kbps: None,
+            download_kbps: None,
+            hooks: Hooks::default(),
+        })
+        .await
+        .unwrap();
+    let token = server.api_token.clone();
+    let daemon = client.daemon_config(&token).await.unwrap();
+    assert_eq!(daemon.server.mtu, Some(1420));
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(config.contains("MTU = 1420\n"), "{config}");
+
+    // Overrides win over the network's setting, and clearing one falls back.
+    let server = client
+        .update_server(
+            server.id,
+            &UpdateServerRequest {
+                mtu: Some(1380),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(server.mtu, Some(1380));
+    let daemon = client.daemon_config(&token).await.unwrap();
+    assert_eq!(daemon.server.mtu, Some(1380));
+
+    let laptop = client
+        .update_client(
+            laptop.id,
+            &UpdateNotesRequest {
+                notes: None,
+                mtu: Some(1300),
+                persistent_keepalive: None,
+                monthly_quota_bytes: None,
+                upload_kbps: None,
+                download_kbps: None,
+                hooks: Hooks::default(),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(laptop.mtu, Some(1300));
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(config.contains("MTU = 1300\n"), "{config}");
+
+    client
+        .update_client(
+            laptop.id,
+            &UpdateNotesRequest {
+                notes: None,
+                mtu: Some(0),
+                persistent_keepalive: None,
+                monthly_quota_bytes: None,
+                upload_kbps: None,
+                download_kbps: None,
+                hooks: Hooks::default(),
+            },
+        )
+        .await
+        .unwrap();
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(config.contains("MTU = 1420\n"), "{config}");
+}
+
+#[tokio::test]
+async fn keepalive_overrides_reach_daemons_and_clients() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let server = create_server(&client, network.id, "gw", Some("vpn.example.com"), None)
+        .await
+        .unwrap();
+    let phone = client
+        .create_client(&CreateClientRequest {
+            network_id: network.id,
+            name: "phone".into(),
+            tags: Vec::new(),
+            notes: None,
+            mtu: None,
+            persistent_keepalive: Some(15),
+            monthly_quota_bytes: None,
+            upload_kbps: None,
+            download_kbps: None,
+            hooks: Hooks::default(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(phone.persistent_keepalive, Some(15));
+    let token = server.api_token.clone();
+    let daemon = client.daemon_config(&token).await.unwrap();
+    assert_eq!(daemon.peers[0].persistent_keepalive, Some(15));
+    let config = client.client_config(phone.id, false).await.unwrap();
+    assert!(config.contains("PersistentKeepalive = 15\n"), "{config}");
+
+    // Zero turns keepalive off for the server; the phone keeps its own.
+    let keepalive = |persistent_keepalive| UpdateServerRequest {
+        persistent_keepalive: Some(persistent_keepalive),
+        ..Default::default()
+    };
+    let updated = client.update_server(server.id, &keepalive(0)).await.unwrap();
+    assert_eq!(updated.persistent_keepalive, Some(0));
+    let err = client.update_server(server.id, &keepalive(70000)).await.unwrap_err();
+    assert_eq!(err.status(), Some(400));
+
+    let phone = client
+        .update_client(
+            phone.id,
+            &UpdateNotesRequest {
+                notes: None,
+                mtu: None,
+                persistent_keepalive: Some(-1),
+                monthly_quota_bytes: None,
+                upload_kbps: None,
+                download_kbps: None,
+                hooks: Hooks::default(),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(phone.persistent_keepalive, None);
+    let daemon = client.daemon_config(&token).await.unwrap();
+    assert_eq!(daemon.peers[0].persistent_keepalive, Some(0));
+    let config = client.client_config(phone.id, false).await.unwrap();
+    assert!(!config.contains("PersistentKeepalive"), "{config}");
+}
+
+#[tokio::test]
+async fn listen_port_is_separate_from_endpoint_port() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    // Behind a NAT forwarding 51820 to 41820 on the host.
+    let server = client
+        .create_server(&CreateServerRequest {
+            network_id: network.id,
+            name: "gw".into(),
+            forwards_internet_traffic: false,
+            manage_nat: false,
+            fwmark: None,
+            route_table: None,
+            rule_priority: None,
+            mtu: None,
+            persistent_keepalive: None,
+            endpoint_host: Some("vpn.example.com".into()),
+            endpoint_port: Some(51820),
+            listen_port: Some(41820),
+            priority: None,
+            tags: Vec::new(),
+            notes: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!((server.endpoint_port, server.listen_port), (51820, Some(41820)));
+    let token = server.api_token.clone();
+    let daemon = client.daemon_config(&token).await.unwrap();
+    assert_eq!(daemon.server.listen_port, 41820);
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(config.contains("Endpoint = vpn.example.com:51820\n"), "{config}");
+
+    let cleared = UpdateServerRequest {
+        listen_port: Some(0),
+        ..Default::default()
+    };
+    let server = client.update_server(server.id, &cleared).await.unwrap();
+    assert_eq!(server.listen_port, None);
+    let daemon = client.daemon_config(&token).await.unwrap();
+    assert_eq!(daemon.server.listen_port, 51820);
+}
+
+#[tokio::test]
+async fn priority_decides_which_server_claims_overlapping_routes() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    let first = fixtures
+        .server(&network, "first")
+        .endpoint("one.example.com", 51820)
+        .create()
+        .await;
+    let second = fixtures
+        .server(&network, "second")
+        .endpoint("two.example.com", 51821)
+        .create()
+        .await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+    for server in [&first, &second] {
+        client.add_route(server.id, "192.168.50.0/24").await.unwrap();
+    }
+
+    // The older server wins ties; which peer a section belongs to follows
+    // from its comment line.
+    let allowed_ips = |config: &str, name: &str| {
+        let section = config.split("\n# ").find(|s| s.starts_with(name)).unwrap();
+        section
+            .lines()
+            .find_map(|l| l.strip_prefix("AllowedIPs = "))
+            .unwrap()
+            .to_string()
+    };
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(allowed_ips(&config, "first").contains("192.168.50.0/24"), "{config}");
+    assert!(!allowed_ips(&config, "second").contains("192.168.50.0/24"), "{config}");
+
+    let raised = UpdateServerRequest {
+        priority: Some(10),
+        ..Default::default()
+    };
+    let server = client.update_server(second.id, &raised).await.unwrap();
+    assert_eq!(server.priority, 10);
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(allowed_ips(&config, "second").contains("192.168.50.0/24"), "{config}");
+    assert!(allowed_ips(&config, "second").contains("10.0.0.0/24"), "{config}");
+    assert!(!allowed_ips(&config, "first").contains("192.168.50.0/24"), "{config}");
+}
+
+#[tokio::test]
+async fn client_routes_override_allowed_ips() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    let first = fixtures
+        .server(&network, "first")
+        .endpoint("one.example.com", 51820)
+        .create()
+        .await;
+    let second = fixtures
+        .server(&network, "second")
+        .endpoint("two.example.com", 51821)
+        .create()
+        .await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+    client.add_route(first.id, "192.168.50.0/24").await.unwrap();
+
+    let allowed_ips = |config: &str, name: &str| {
+        let section = config.split("\n# ").find(|s| s.starts_with(name)).unwrap();
+        section
+            .lines()
+            .find_map(|l| l.strip_prefix("AllowedIPs = "))
+            .unwrap()
+            .to_string()
+    };
+
+    // An extra route goes through the server it names, even one that would
+    // otherwise lose the CIDR to an older server.
+    let extra = client
+        .add_client_route(
+            laptop.id,
+            ClientRouteKind::Extra,
+            Some(second.id),
+            "192.168.50.0/24",
+        )
+        .await
+        .unwrap();
+    assert_eq!(extra.server_id, Some(second.id));
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(allowed_ips(&config, "second").contains("192.168.50.0/24"), "{config}");
+    assert!(!allowed_ips(&config, "first").contains("192.168.50.0/24"), "{config}");
+
+    // Only routes narrow every peer down to the listed CIDRs.
+    client
+        .add_client_route(laptop.id, ClientRouteKind::Only, None, "192.168.50.0/25")
+        .await
+        .unwrap();
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert_eq!(allowed_ips(&config, "first"), "10.0.0.1/32", "{config}");
+    assert_eq!(
+        allowed_ips(&config, "second"),
+        "192.168.50.0/24, 10.0.0.2/32",
+        "{config}"
+    );
+
+    let routes = client
+        .list_client_routes(laptop.id, &ListParams::default())
+        .await
+        .unwrap();
+    assert_eq!(routes.total, 2);
+
+    // Kinds and servers must agree.
+    let err = client
+        .add_client_route(laptop.id, ClientRouteKind::Extra, None, "10.9.0.0/16")
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+
+    client.delete_client_route(extra.id).await.unwrap();
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert_eq!(allowed_ips(&config, "first"), "192.168.50.0/25, 10.0.0.1/32", "{config}");
+}
+
+#[tokio::test]
+async fn excluded_cidrs_stay_off_full_tunnel_configs() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    fixtures
+        .server(&network, "gw")
+        .endpoint("vpn.example.com", 51820)
+        .forwards_internet_traffic()
+        .create()
+        .await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let update = |cidrs: &[&str]| UpdateNetworkRequest {
+        dns_servers: Vec::new(),
+        persistent_keepalive: 25,
+        enabled: None,
+        notes: None,
+        port_range: None,
+        endpoint_template: None,
+        allocation: None,
+        mtu: None,
+        excluded_cidrs: Some(cidrs.iter().map(|c| c.to_string()).collect()),
+        lan_access: None,
+        hooks: Hooks::default(),
+        search_domains: None,
+        topology: None,
+    };
+    let err = client
+        .update_network(network.id, &update(&["203.0.113.0/40"]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    let updated = client
+        .update_network(network.id, &update(&["203.0.113.7/24"]))
+        .await
+        .unwrap();
+    assert_eq!(updated.excluded_cidrs, ["203.0.113.0/24"]);
+
+    let config = client.client_config(laptop.id, true).await.unwrap();
+    assert!(config.contains("203.0.112.0/24, 203.0.114.0/23"), "{config}");
+    assert!(!config.contains("203.0.113.0/24"), "{config}");
+
+    // Excludes asked for with the config add to the network's.
+    let params = ConfigParams {
+        forward_internet: true,
+        exclude: Some("203.0.112.0/24".into()),
+        ..Default::default()
+    };
+    let config = client.client_config_with(laptop.id, &params).await.unwrap();
+    assert!(!config.contains("203.0.112.0/24"), "{config}");
+    assert!(config.contains("203.0.96.0/20, 203.0.114.0/23"), "{config}");
+
+    let params = ConfigParams {
+        forward_internet: true,
+        exclude: Some("nonsense".into()),
+        ..Default::default()
+    };
+    let err = client.client_config_with(laptop.id, &params).await.unwrap_err();
+    assert_eq!(err.status(), Some(400));
+}
+
+#[tokio::test]
+async fn lan_access_defaults_per_network_and_per_request() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    fixtures
+        .server(&network, "gw")
+        .endpoint("vpn.example.com", 51820)
+        .forwards_internet_traffic()
+        .create()
+        .await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let config = client.client_config(laptop.id, true).await.unwrap();
+    assert!(!config.contains("0.0.0.0/0"), "{config}");
+
+    let update = UpdateNetworkRequest {
+        dns_servers: Vec::new(),
+        persistent_keepalive: 25,
+        enabled: None,
+        notes: None,
+        port_range: None,
+        endpoint_template: None,
+        allocation: None,
+        mtu: None,
+        excluded_cidrs: None,
+        lan_access: Some(false),
+        hooks: Hooks::default(),
+        search_domains: None,
+        topology: None,
+    };
+    let updated = client.update_network(network.id, &update).await.unwrap();
+    assert!(!updated.lan_access);
+    let config = client.client_config(laptop.id, true).await.unwrap();
+    assert!(config.contains("AllowedIPs = 10.0.0.0/24, 0.0.0.0/0\n"), "{config}");
+
+    // A config request can still ask for LAN access back.
+    let params = ConfigParams {
+        forward_internet: true,
+        lan_access: Some(true),
+        ..Default::default()
+    };
+    let config = client.client_config_with(laptop.id, &params).await.unwrap();
+    assert!(!config.contains("0.0.0.0/0"), "{config}");
+}
+
+#[tokio::test]
+async fn hooks_reach_client_configs() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    fixtures
+        .server(&network, "gw")
+        .endpoint("vpn.example.com", 51820)
+        .create()
+        .await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let mut config = wirewarden_testing::app::config();
+    config.allow_network_hooks = true;
+    let app = TestApp::spawn_with(&db, config).await;
+    let client = app.login("alice").await;
+
+    let update = |hooks: Hooks| UpdateNetworkRequest {
+        dns_servers: Vec::new(),
+        persistent_keepalive: 25,
+        enabled: None,
+        notes: None,
+        port_range: None,
+        endpoint_template: None,
+        allocation: None,
+        mtu: None,
+        excluded_cidrs: None,
+        lan_access: None,
+        hooks,
+        search_domains: None,
+        topology: None,
+    };
+    let err = client
+        .update_network(
+            network.id,
+            &update(Hooks {
+                post_up: Some("true\nPostUp = false".into()),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    let updated = client
+        .update_network(
+            network.id,
+            &update(Hooks {
+                post_up: Some("iptables -I OUTPUT -o %i -j ACCEPT".into()),
+                pre_down: Some("iptables -D OUTPUT -o %i -j ACCEPT".into()),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.hooks.pre_down.as_deref(), Some("iptables -D OUTPUT -o %i -j ACCEPT"));
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(config.contains("PostUp = iptables -I OUTPUT -o %i -j ACCEPT\n"), "{config}");
+    assert!(config.contains("PreDown = iptables -D OUTPUT -o %i -j ACCEPT\n"), "{config}");
+
+    // A client's own hook wins; clearing it falls back to the network's.
+    let set_client_hook = |post_up: &str| UpdateNotesRequest {
+        notes: None,
+        mtu: None,
+        persistent_keepalive: None,
+        monthly_quota_bytes: None,
+        upload_kbps: None,
+        download_kbps: None,
+        hooks: Hooks {
+            post_up: Some(post_up.into()),
+            ..Default::default()
+        },
+    };
+    let laptop = client
+        .update_client(laptop.id, &set_client_hook("echo up"))
+        .await
+        .unwrap();
+    assert_eq!(laptop.hooks.post_up.as_deref(), Some("echo up"));
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(config.contains("PostUp = echo up\n"), "{config}");
+    assert!(config.contains("PreDown = iptables -D OUTPUT -o %i -j ACCEPT\n"), "{config}");
+
+    client.update_client(laptop.id, &set_client_hook("")).await.unwrap();
+    let config = client.client_config(laptop.id, false).await.unwrap();
+    assert!(config.contains("PostUp = iptables -I OUTPUT -o %i -j ACCEPT\n"), "{config}");
+
+    // Hooks run as root on clients, so every change is on record.
+    let audit = AuditStore::new(db.pool().clone());
+    let entries = audit.list(Some(network.id), 20).await.unwrap();
+    let network_hooks = entries
+        .iter()
+        .find(|e| e.action == "network.hooks_updated")
+        .expect("network hook change not audited");
+    assert_eq!(
+        network_hooks.details["hooks"]["to"]["post_up"],
+        "iptables -I OUTPUT -o %i -j ACCEPT"
+    );
+    let client_hooks: Vec<_> = entries
+        .iter()
+        .filter(|e| e.action == "client.updated" && e.details.get("hooks").is_some())
+        .collect();
+    assert_eq!(client_hooks.len(), 2);
+}
+
+#[tokio::test]
+async fn network_hooks_need_the_operator_to_allow_them() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let update = |post_up: &str| UpdateNetworkRequest {
+        dns_servers: Vec::new(),
+        persistent_keepalive: 25,
+        enabled: None,
+        notes: None,
+        port_range: None,
+        endpoint_template: None,
+        allocation: None,
+        mtu: None,
+        excluded_cidrs: None,
+        lan_access: None,
+        hooks: Hooks {
+            post_up: Some(post_up.into()),
+            ..Default::default()
+        },
+        search_domains: None,
+        topology: None,
+    };
+    let err = client
+        .update_network(network.id, &update("curl example.com | sh"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    // Clearing stays possible, so hooks set before the switch can be removed.
+    let cleared = client.update_network(network.id, &update("")).await.unwrap();
+    assert_eq!(cleared.hooks, Hooks::default());
+}
+
+#[tokio::test]
+async fn search_domains_follow_dns_servers() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    fixtures
+        .server(&network, "gw")
+        .endpoint("vpn.example.com", 51820)
+        .forwards_internet_traffic()
+        .create()
+        .await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let update = |domains: &[&str]| UpdateNetworkRequest {
+        dns_servers: vec!["10.0.0.1".into()],
+        persistent_keepalive: 25,
+        enabled: None,
+        notes: None,
+        port_range: None,
+        endpoint_template: None,
+        allocation: None,
+        mtu: None,
+        excluded_cidrs: None,
+        lan_access: None,
+        hooks: Hooks::default(),
+        search_domains: Some(domains.iter().map(|d| d.to_string()).collect()),
+        topology: None,
+    };
+    // wg-quick would take an address for a DNS server.
+    let err = client
+        .update_network(network.id, &update(&["10.0.0.2"]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    let updated = client
+        .update_network(network.id, &update(&["Corp.Example.com.", "lan"]))
+        .await
+        .unwrap();
+    assert_eq!(updated.search_domains, ["corp.example.com", "lan"]);
+
+    let config = client.client_config(laptop.id, true).await.unwrap();
+    assert!(config.contains("DNS = 10.0.0.1, corp.example.com, lan\n"), "{config}");
+}
+
+#[tokio::test]
+async fn peer_hostnames_are_exported_as_a_zone() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("Home Lab").owner(&user).create().await;
+    let gw = fixtures
+        .server(&network, "gw")
+        .endpoint("vpn.example.com", 51820)
+        .create()
+        .await;
+    let laptop = fixtures.client(&network, "Alice Laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let zone = client.network_dns(network.id).await.unwrap();
+    assert_eq!(zone.origin, "home-lab.wirewarden.internal");
+    let records: Vec<_> = zone
+        .records
+        .iter()
+        .map(|r| (r.name.as_str(), r.record_type.as_str(), r.address.as_str(), r.peer_id))
+        .collect();
+    assert_eq!(
+        records,
+        [
+            ("gw.home-lab.wirewarden.internal", "A", "10.0.0.1", gw.id),
+            ("alice-laptop.home-lab.wirewarden.internal", "A", "10.0.0.2", laptop.id),
+        ]
+    );
+
+    let file = client.network_zone_file(network.id).await.unwrap();
+    assert!(file.starts_with("$ORIGIN home-lab.wirewarden.internal.\n"), "{file}");
+    assert!(file.contains("\nalice-laptop\tIN\tA\t10.0.0.2\n"), "{file}");
+}
+
+#[tokio::test]
+async fn hub_networks_keep_servers_apart() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("sites").owner(&user).create().await;
+    let east = fixtures
+        .server(&network, "east")
+        .endpoint("east.example.com", 51820)
+        .create()
+        .await;
+    let west = fixtures
+        .server(&network, "west")
+        .endpoint("west.example.com", 51820)
+        .create()
+        .await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+    let west_key = client.get_server(west.id).await.unwrap().public_key;
+
+    let config = client.daemon_config(&east.api_token).await.unwrap();
+    assert!(config.peers.iter().any(|p| p.public_key == west_key));
+
+    let update = UpdateNetworkRequest {
+        dns_servers: Vec::new(),
+        persistent_keepalive: 25,
+        enabled: None,
+        notes: None,
+        port_range: None,
+        endpoint_template: None,
+        allocation: None,
+        mtu: None,
+        excluded_cidrs: None,
+        lan_access: None,
+        hooks: Hooks::default(),
+        search_domains: None,
+        topology: Some(Topology::Hub),
+    };
+    let updated = client.update_network(network.id, &update).await.unwrap();
+    assert_eq!(updated.topology, Topology::Hub);
+
+    let config = client.daemon_config(&east.api_token).await.unwrap();
+    let keys: Vec<_> = config.peers.iter().map(|p| p.public_key.as_str()).collect();
+    let laptop_key = client.get_client(laptop.id).await.unwrap().public_key;
+    assert_eq!(keys, [laptop_key.as_str()]);
+
+    // Clients still reach both.
+    let rendered = client.client_config(laptop.id, false).await.unwrap();
+    assert!(rendered.contains("Endpoint = west.example.com:51820"), "{rendered}");
+}
+
+#[tokio::test]
+async fn acl_rules_reach_daemon_configs() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    let gateway = fixtures.server(&network, "gateway").create().await;
+    let tv = fixtures.client(&network, "tv").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+    let tv = client.set_client_tags(tv.id, vec!["iot".into()]).await.unwrap();
+
+    let rule = |position, action, source: &str, destination: &str| CreateAclRuleRequest {
+        position,
+        action,
+        source: source.into(),
+        destination: destination.into(),
+    };
+    let deny = client
+        .add_acl_rule(network.id, &rule(None, AclAction::Deny, "tag:IoT", "*"))
+        .await
+        .unwrap();
+    assert_eq!(deny.source, "tag:iot");
+    client
+        .add_acl_rule(
+            network.id,
+            &rule(Some(1), AclAction::Allow, "tag:iot", "10.0.0.1"),
+        )
+        .await
+        .unwrap();
+    // Matches no one yet, so daemons never see it.
+    client
+        .add_acl_rule(network.id, &rule(None, AclAction::Deny, "tag:kids", "*"))
+        .await
+        .unwrap();
+    let err = client
+        .add_acl_rule(network.id, &rule(None, AclAction::Deny, "everyone", "*"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(400));
+
+    let rules = client.list_acl_rules(network.id).await.unwrap();
+    let order: Vec<_> = rules
+        .iter()
+        .map(|r| (r.position, r.action, r.destination.as_str()))
+        .collect();
+    assert_eq!(
+        order,
+        [
+            (1, AclAction::Allow, "10.0.0.1/32"),
+            (2, AclAction::Deny, "*"),
+            (3, AclAction::Deny, "*"),
+        ]
+    );
+
+    let tv_cidr = format!("{}/32", tv.address);
+    let acl = client
+        .daemon_config(&gateway.api_token)
+        .await
+        .unwrap()
+        .network
+        .acl;
+    assert_eq!(
+        acl,
+        [
+            DaemonAclRule {
+                action: DaemonAclAction::Allow,
+                sources: vec![tv_cidr.clone()],
+                destinations: vec!["10.0.0.1/32".into()],
+            },
+            DaemonAclRule {
+                action: DaemonAclAction::Deny,
+                sources: vec![tv_cidr],
+                destinations: Vec::new(),
+            },
+        ]
+    );
+
+    // A daemon that predates rules would drop the deny, so it is refused.
+    let legacy = reqwest::Client::new()
+        .get(format!("{}/api/daemon/config", app.url()))
+        .bearer_auth(&gateway.api_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(legacy.status(), 426);
+
+    // Deleting closes the gap.
+    client.delete_acl_rule(rules[0].id).await.unwrap();
+    let rules = client.list_acl_rules(network.id).await.unwrap();
+    assert_eq!(rules[0].id, deny.id);
+    assert_eq!(rules[0].position, 1);
+    let acl = client
+        .daemon_config(&gateway.api_token)
+        .await
+        .unwrap()
+        .network
+        .acl;
+    assert_eq!(acl.len(), 1);
+}
+
+#[tokio::test]
+async fn psk_rotation_replaces_every_pair_and_queues_with_its_impact() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let network = fixtures.network("home").create().await;
+    let servers = [
+        fixtures.server(&network, "gateway").create().await,
+        fixtures.server(&network, "relay").create().await,
+    ];
+    let clients = [
+        fixtures.client(&network, "laptop").create().await,
+        fixtures.client(&network, "phone").create().await,
+    ];
+    let rotate = |app: &TestApp, client: &Client, network_id: Uuid| {
+        let url = format!("{}/api/networks/{network_id}/psk/rotate", app.url());
+        reqwest::Client::new()
+            .post(url)
+            .header(COOKIE, format!("token={}", client.token().unwrap()))
+            .send()
+    };
+
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+    let psks = || async {
+        let mut psks = Vec::new();
+        for server in &servers {
+            for client in &clients {
+                let psk = app.state.vpn.ensure_psk(server.id, client.id).await;
+                psks.push(psk.unwrap());
+            }
+        }
+        psks
+    };
+    let before = psks().await;
+    let resp = rotate(&app, &client, network.id).await.unwrap();
+    assert_eq!(resp.status(), 204);
+    let after = psks().await;
+    assert!(before.iter().zip(&after).all(|(old, new)| old != new));
+
+    // Queued for approval, the change states what it breaks, and only for a
+    // network that exists.
+    let mut config = wirewarden_testing::app::config();
+    config.require_approval = true;
+    let app = TestApp::spawn_with(&db, config).await;
+    let client = app.login("alice").await;
+    let err = client.delete_network(Uuid::new_v4()).await.unwrap_err();
+    assert_eq!(err.status(), Some(404));
+    let resp = rotate(&app, &client, Uuid::new_v4()).await.unwrap();
+    assert_eq!(resp.status(), 404);
+    assert!(app.state.approvals.list(None).await.unwrap().is_empty());
+
+    let resp = rotate(&app, &client, network.id).await.unwrap();
+    assert_eq!(resp.status(), 202);
+    let pending: serde_json::Value = resp.json().await.unwrap();
+    let impact = pending["impact"].as_str().unwrap();
+    assert!(impact.contains("preshared keys"), "{impact}");
+}
+
+#[tokio::test]
+async fn ensure_psks_loads_existing_keys_and_fills_missing_ones() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let network = fixtures.network("home").create().await;
+    let server = fixtures.server(&network, "gateway").create().await;
+    let laptop = fixtures.client(&network, "laptop").create().await;
+    let phone = fixtures.client(&network, "phone").create().await;
+    sqlx::query("DELETE FROM wg_peer_psks WHERE client_id = $1")
+        .bind(phone.id)
+        .execute(db.pool())
+        .await
+        .unwrap();
+
+    let app = TestApp::spawn(&db).await;
+    let store = &app.state.vpn;
+    let existing = store.ensure_psk(server.id, laptop.id).await.unwrap();
+    let psks = store
+        .ensure_psks(server.id, &[laptop.id, phone.id])
+        .await
+        .unwrap();
+    assert_eq!(psks.len(), 2);
+    assert_eq!(psks[&laptop.id], existing);
+    let filled = store.ensure_psk(server.id, phone.id).await.unwrap();
+    assert_eq!(psks[&phone.id], filled);
+    assert!(store.ensure_psks(server.id, &[]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn events_reach_subscribers_on_other_replicas() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let user = fixtures.user("alice").await;
+    let network = fixtures.network("home").owner(&user).create().await;
+    let first = TestApp::spawn(&db).await;
+    let second = TestApp::spawn(&db).await;
+    let mut all = second.state.events.subscribe_all();
+    let mut own = second.state.events.subscribe();
+
+    let client = first.login("alice").await;
+    let server = create_server(&client, network.id, "relay", Some("vpn.example.com"), None)
+        .await
+        .unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(5), all.recv())
+        .await
+        .expect("no event relayed")
+        .unwrap();
+    assert_eq!(event.kind, EventKind::ServerCreated);
+    assert_eq!(event.resource_id, server.id);
+    // Only the replica that published an event delivers its webhooks.
+    assert!(own.try_recv().is_err());
+}
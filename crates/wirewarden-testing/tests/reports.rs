@@ -0,0 +1,167 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{TimeDelta, Utc};
+use wirewarden_testing::{Fixtures, TestApp, TestDb};
+use wirewarden_types::api::{GrowthBucket, GrowthQuery};
+use wirewarden_types::daemon::{DaemonPeerTelemetry, DaemonTelemetry};
+
+#[tokio::test]
+async fn reports_count_devices_per_network_and_owner() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let alice = fixtures.user("alice").await;
+    let home = fixtures
+        .network("home")
+        .owner(&alice)
+        .cidr("10.0.0.0/24")
+        .create()
+        .await;
+    fixtures.server(&home, "gw").create().await;
+    fixtures.client(&home, "laptop").create().await;
+    fixtures.client(&home, "phone").create().await;
+    let lab = fixtures.network("lab").cidr("10.9.0.0/30").create().await;
+    fixtures.client(&lab, "probe").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let peers = client.peers_per_network().await.unwrap();
+    let home_row = peers.iter().find(|n| n.network_id == home.id).unwrap();
+    assert_eq!((home_row.servers, home_row.clients), (1, 2));
+    assert_eq!(home_row.capacity, 254);
+    let lab_row = peers.iter().find(|n| n.network_id == lab.id).unwrap();
+    assert_eq!(
+        (lab_row.servers, lab_row.clients, lab_row.capacity),
+        (0, 1, 2)
+    );
+    assert_eq!(lab_row.utilization, 0.5);
+
+    let devices = client.devices_per_user().await.unwrap();
+    let alice_row = devices
+        .iter()
+        .find(|d| d.user_id == Some(alice.id))
+        .unwrap();
+    assert_eq!(alice_row.username.as_deref(), Some("alice"));
+    assert_eq!(
+        (alice_row.networks, alice_row.servers, alice_row.clients),
+        (1, 1, 2)
+    );
+    let unowned = devices.iter().find(|d| d.user_id.is_none()).unwrap();
+    assert_eq!(
+        (unowned.networks, unowned.servers, unowned.clients),
+        (1, 0, 1)
+    );
+
+    let growth = client
+        .growth_report(&GrowthQuery {
+            bucket: GrowthBucket::Week,
+            network_id: Some(home.id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(growth.bucket, GrowthBucket::Week);
+    let last = growth.points.last().unwrap();
+    assert_eq!((last.servers_total, last.clients_total), (1, 2));
+    let added: i64 = growth.points.iter().map(|p| p.clients_added).sum();
+    assert_eq!(added, 2);
+}
+
+#[tokio::test]
+async fn rotation_report_splits_fresh_stale_and_missing_keys() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").cidr("10.0.0.0/24").create().await;
+    let gw = fixtures.server(&home, "gw").create().await;
+    fixtures.client(&home, "laptop").create().await;
+    let phone = fixtures.client(&home, "phone").create().await;
+    let tablet = fixtures.client(&home, "tablet").create().await;
+    sqlx::query(
+        "UPDATE wg_peer_psks SET updated_at = now() - interval '100 days' WHERE client_id = $1",
+    )
+    .bind(phone.id)
+    .execute(db.pool())
+    .await
+    .unwrap();
+    sqlx::query("DELETE FROM wg_peer_psks WHERE server_id = $1 AND client_id = $2")
+        .bind(gw.id)
+        .bind(tablet.id)
+        .execute(db.pool())
+        .await
+        .unwrap();
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    let rows = client.rotation_report(90, Some(home.id)).await.unwrap();
+    assert_eq!(rows.len(), 1);
+    let row = &rows[0];
+    assert_eq!(
+        (row.pairs, row.compliant, row.stale, row.missing),
+        (3, 1, 1, 1)
+    );
+    assert!(row.oldest_rotation.is_some());
+
+    let rows = client.rotation_report(365, Some(home.id)).await.unwrap();
+    assert_eq!((rows[0].compliant, rows[0].stale), (2, 0));
+
+    let err = client.rotation_report(0, None).await.unwrap_err();
+    assert!(err.to_string().contains("max_age_days"), "{err}");
+}
+
+#[tokio::test]
+async fn summary_totals_every_network() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    let home = fixtures.network("home").cidr("10.0.0.0/24").create().await;
+    let gateway = fixtures.server(&home, "gw").create().await;
+    fixtures.server(&home, "backup").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    fixtures.client(&home, "phone").create().await;
+    let lab = fixtures.network("lab").cidr("10.9.0.0/30").create().await;
+    fixtures.client(&lab, "probe").create().await;
+    let app = TestApp::spawn(&db).await;
+    let client = app.login("alice").await;
+
+    // Checking in brings the gateway online.
+    client.daemon_config(&gateway.api_token).await.unwrap();
+    let public_key = client.get_client(laptop.id).await.unwrap().public_key;
+    let telemetry = DaemonTelemetry {
+        peers: vec![DaemonPeerTelemetry {
+            public_key,
+            rx_bytes: 0,
+            tx_bytes: 0,
+            last_handshake: Some(Utc::now() - TimeDelta::hours(1)),
+        }],
+        handshakes: true,
+    };
+    client
+        .daemon_telemetry(&gateway.api_token, &telemetry)
+        .await
+        .unwrap();
+
+    let summary = client.summary().await.unwrap();
+    assert_eq!((summary.networks, summary.servers, summary.clients), (2, 2, 3));
+    assert_eq!((summary.servers_online, summary.servers_offline), (1, 1));
+    assert_eq!(summary.clients_recently_seen, 1);
+    assert_eq!((summary.addresses_used, summary.address_capacity), (5, 256));
+    assert_eq!(summary.utilization, 5.0 / 256.0);
+}
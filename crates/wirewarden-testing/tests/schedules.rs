@@ -0,0 +1,195 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::Utc;
+use reqwest::header::COOKIE;
+use reqwest::{Response, StatusCode};
+use wirewarden_api::changes::Change;
+use wirewarden_api::db::approval::ChangeStatus;
+use wirewarden_api::db::schedule::{ScheduleStatus, ScheduleStore};
+use wirewarden_api::scheduler::Scheduler;
+use wirewarden_client::Client;
+use wirewarden_testing::{Fixtures, TestApp, TestDb};
+
+const LEASE_SECS: i64 = 600;
+
+#[tokio::test]
+async fn a_change_left_running_past_its_lease_is_claimed_again() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let alice = fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let store = ScheduleStore::new(db.pool().clone());
+    let change = Change::SetNetworkEnabled {
+        network_id: home.id,
+        enabled: false,
+    };
+    let scheduled = store
+        .create(&change, Utc::now(), alice.id, None)
+        .await
+        .unwrap();
+
+    // A scheduler claims it and dies before finishing.
+    let claimed = store.claim_due(10, LEASE_SECS, false).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].status, ScheduleStatus::Running);
+    assert!(
+        store
+            .claim_due(10, LEASE_SECS, false)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    sqlx::query("UPDATE scheduled_changes SET claimed_at = now() - interval '11 minutes'")
+        .execute(db.pool())
+        .await
+        .unwrap();
+    let reclaimed = store.claim_due(10, LEASE_SECS, false).await.unwrap();
+    assert_eq!(reclaimed.len(), 1);
+    assert_eq!(reclaimed[0].id, scheduled.id);
+    assert!(reclaimed[0].claimed_at > claimed[0].claimed_at);
+
+    store.finish(scheduled.id, None).await.unwrap();
+    let done = store.get(scheduled.id).await.unwrap().unwrap();
+    assert_eq!(done.status, ScheduleStatus::Done);
+    sqlx::query("UPDATE scheduled_changes SET claimed_at = now() - interval '11 minutes'")
+        .execute(db.pool())
+        .await
+        .unwrap();
+    assert!(
+        store
+            .claim_due(10, LEASE_SECS, false)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+async fn post(app: &TestApp, client: &Client, path: &str, body: serde_json::Value) -> Response {
+    reqwest::Client::new()
+        .post(format!("{}{path}", app.url()))
+        .header(COOKIE, format!("token={}", client.token().unwrap()))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+fn approval_app_config() -> wirewarden_api::config::Config {
+    let mut config = wirewarden_testing::app::config();
+    config.require_approval = true;
+    config
+}
+
+#[tokio::test]
+async fn a_destructive_change_scheduled_under_approval_runs_once_approved() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    fixtures.user("bob").await;
+    let home = fixtures.network("home").create().await;
+    let relay = fixtures.server(&home, "relay").create().await;
+    let app = TestApp::spawn_with(&db, approval_app_config()).await;
+    let alice = app.login("alice").await;
+    let bob = app.login("bob").await;
+    let scheduler = Scheduler::new(&app.state);
+
+    let body = serde_json::json!({
+        "change": { "kind": "delete_server", "network_id": home.id, "server_id": relay.id },
+        "run_at": Utc::now() + chrono::Duration::hours(1),
+    });
+    let resp = post(&app, &alice, "/api/scheduled-changes", body).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let scheduled: serde_json::Value = resp.json().await.unwrap();
+    let approval_id = scheduled["approval_id"].as_str().unwrap().to_owned();
+
+    // Due but unapproved: it waits.
+    sqlx::query("UPDATE scheduled_changes SET run_at = now()")
+        .execute(db.pool())
+        .await
+        .unwrap();
+    scheduler.run_due().await;
+    assert!(app.state.vpn.get_server(relay.id).await.unwrap().is_some());
+
+    // Approving releases it to the scheduler rather than applying it.
+    let resp = post(
+        &app,
+        &bob,
+        &format!("/api/approvals/{approval_id}/approve"),
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(app.state.vpn.get_server(relay.id).await.unwrap().is_some());
+
+    scheduler.run_due().await;
+    assert!(app.state.vpn.get_server(relay.id).await.unwrap().is_none());
+    let id = scheduled["id"].as_str().unwrap().parse().unwrap();
+    let done = app.state.schedules.get(id).await.unwrap().unwrap();
+    assert_eq!(done.status, ScheduleStatus::Done);
+}
+
+#[tokio::test]
+async fn a_change_scheduled_before_approval_was_required_waits_for_one() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let alice = fixtures.user("alice").await;
+    fixtures.user("bob").await;
+    let home = fixtures.network("home").create().await;
+    let relay = fixtures.server(&home, "relay").create().await;
+    let change = Change::DeleteServer {
+        network_id: home.id,
+        server_id: relay.id,
+    };
+    let store = ScheduleStore::new(db.pool().clone());
+    let scheduled = store
+        .create(&change, Utc::now(), alice.id, None)
+        .await
+        .unwrap();
+
+    let app = TestApp::spawn_with(&db, approval_app_config()).await;
+    let scheduler = Scheduler::new(&app.state);
+    scheduler.run_due().await;
+    assert!(app.state.vpn.get_server(relay.id).await.unwrap().is_some());
+    let held = store.get(scheduled.id).await.unwrap().unwrap();
+    assert_eq!(held.status, ScheduleStatus::Pending);
+    let approval_id = held.approval_id.unwrap();
+    let pending = app.state.approvals.get(approval_id).await.unwrap().unwrap();
+    assert_eq!(pending.status, ChangeStatus::Pending);
+    assert_eq!(pending.requested_by, alice.id);
+
+    // Held while the approval is open; cancelled when it is rejected.
+    scheduler.run_due().await;
+    assert!(app.state.vpn.get_server(relay.id).await.unwrap().is_some());
+    let bob = app.login("bob").await;
+    let resp = post(
+        &app,
+        &bob,
+        &format!("/api/approvals/{approval_id}/reject"),
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let cancelled = store.get(scheduled.id).await.unwrap().unwrap();
+    assert_eq!(cancelled.status, ScheduleStatus::Cancelled);
+    scheduler.run_due().await;
+    assert!(app.state.vpn.get_server(relay.id).await.unwrap().is_some());
+}
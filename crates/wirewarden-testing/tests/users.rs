@@ -0,0 +1,183 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use wirewarden_api::db::notification::{NotificationKind, NotificationStore};
+use wirewarden_api::db::user::UserStore;
+use wirewarden_testing::{Fixtures, TestApp, TestDb};
+use wirewarden_types::api::{NotificationSettings, User};
+
+#[tokio::test]
+async fn notification_settings_pick_recipients() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    fixtures.user("alice").await;
+    fixtures.user("bob").await;
+    let app = TestApp::spawn(&db).await;
+    let alice = app.login("alice").await;
+    let bob = app.login("bob").await;
+
+    let defaults = alice.notification_settings().await.unwrap();
+    assert_eq!(defaults, NotificationSettings::default());
+
+    let wanted = NotificationSettings {
+        server_offline: true,
+        client_created: false,
+        server_token_rotated: true,
+    };
+    let saved = alice.set_notification_settings(&wanted).await.unwrap();
+    assert_eq!(saved, wanted);
+    assert_eq!(alice.notification_settings().await.unwrap(), wanted);
+    bob.set_notification_settings(&NotificationSettings {
+        server_offline: true,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let store = NotificationStore::new(db.pool().clone());
+    let emails = async |kind| {
+        store
+            .recipients(kind)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.email)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        emails(NotificationKind::ServerOffline).await,
+        ["alice@example.com", "bob@example.com"]
+    );
+    assert_eq!(
+        emails(NotificationKind::ServerTokenRotated).await,
+        ["alice@example.com"]
+    );
+    assert!(emails(NotificationKind::ClientCreated).await.is_empty());
+}
+
+#[tokio::test]
+async fn forgot_password_issues_a_working_reset_token() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let alice = fixtures.user("alice").await;
+    let app = TestApp::spawn(&db).await;
+    let http = reqwest::Client::new();
+    let forgot = async |email: &str| {
+        http.post(format!("{}/api/auth/forgot-password", app.url()))
+            .json(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .unwrap()
+            .status()
+    };
+
+    // Unknown addresses look the same from outside.
+    assert_eq!(forgot("nobody@example.com").await, 200);
+    assert_eq!(forgot("alice@example.com").await, 200);
+
+    let users = UserStore::new(db.pool().clone());
+    let token = users
+        .get_by_id(alice.id)
+        .await
+        .unwrap()
+        .unwrap()
+        .reset_token
+        .expect("no reset token issued");
+    let reset = http
+        .post(format!("{}/api/auth/reset-password", app.url()))
+        .json(&serde_json::json!({ "token": token, "password": "new password" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reset.status(), 200);
+
+    let mut client = app.client();
+    client.login("alice", "new password").await.unwrap();
+}
+
+#[tokio::test]
+async fn unverified_users_can_look_but_not_change() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let fixtures = Fixtures::new(db.pool());
+    let alice = fixtures.user("alice").await;
+    let home = fixtures.network("home").create().await;
+    let laptop = fixtures.client(&home, "laptop").create().await;
+    let mut config = wirewarden_testing::app::config();
+    config.require_email_verification = true;
+    let app = TestApp::spawn_with(&db, config).await;
+    let mut client = app.login("alice").await;
+
+    assert!(!client.me().await.unwrap().email_verified);
+    client.get_client(laptop.id).await.unwrap();
+    let tag = || vec!["kids".to_string()];
+    let err = client.set_client_tags(laptop.id, tag()).await.unwrap_err();
+    assert_eq!(err.status(), Some(403));
+
+    client.resend_verification().await.unwrap();
+    let users = UserStore::new(db.pool().clone());
+    let token = users
+        .get_by_id(alice.id)
+        .await
+        .unwrap()
+        .unwrap()
+        .verify_token
+        .expect("no verification token issued");
+    let err = client.verify_email("not-a-token").await.unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    let verified = client.verify_email(&token).await.unwrap();
+    assert!(verified.email_verified);
+
+    // The refreshed session may make changes without logging in again.
+    client.set_client_tags(laptop.id, tag()).await.unwrap();
+    let err = client.resend_verification().await.unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    let err = client.verify_email(&token).await.unwrap_err();
+    assert_eq!(err.status(), Some(400));
+}
+
+#[tokio::test]
+async fn registering_issues_a_verification_token() {
+    let Some(db) = TestDb::new().await else {
+        return;
+    };
+    let app = TestApp::spawn(&db).await;
+    let resp = reqwest::Client::new()
+        .post(format!("{}/api/auth/register", app.url()))
+        .json(&serde_json::json!({
+            "username": "carol",
+            "display_name": "Carol",
+            "email": "carol@example.com",
+            "password": "hunter2 hunter2",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let user: User = resp.json().await.unwrap();
+    assert!(!user.email_verified);
+
+    let stored = UserStore::new(db.pool().clone())
+        .get_by_id(user.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(stored.verify_token.is_some());
+    assert!(stored.email_verified_at.is_none());
+}
@@ -0,0 +1,753 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Request and response bodies of the management API, as the SDK and CLI see
+//! them. The API serves `Server` and reads `VerifyEmailRequest` from here;
+//! its other bodies are its own, and `tests/contract.rs` in
+//! `wirewarden-testing` fails when a field they return is missing here.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::redact::{Redacted, redact_opt};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for LoginRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginRequest")
+            .field("username", &self.username)
+            .field("password", &Redacted)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub id: Uuid,
+    pub username: String,
+    pub display_name: String,
+    pub email: String,
+    pub locale: String,
+    /// Whether the user confirmed their address through the mailed link.
+    #[serde(default)]
+    pub email_verified: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of `POST /api/auth/verify`, with the token from the mailed link.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+impl fmt::Debug for VerifyEmailRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifyEmailRequest")
+            .field("token", &Redacted)
+            .finish()
+    }
+}
+
+/// Which events the current user is emailed about, from
+/// `/api/auth/me/notifications`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationSettings {
+    pub server_offline: bool,
+    pub client_created: bool,
+    pub server_token_rotated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Network {
+    pub id: Uuid,
+    pub name: String,
+    pub cidr: String,
+    pub dns_servers: Vec<String>,
+    pub persistent_keepalive: i32,
+    pub enabled: bool,
+    pub config_serial: i64,
+    pub notes: Option<String>,
+    /// Listen ports for the network's servers, e.g. `51820-51829`.
+    pub port_range: Option<String>,
+    /// Endpoint hostname for new servers, e.g. `{server}.vpn.example.com`.
+    pub endpoint_template: Option<String>,
+    pub allocation: Allocation,
+    /// Interface MTU for the network's peers, unless they set their own.
+    #[serde(default)]
+    pub mtu: Option<u32>,
+    /// CIDRs full-tunnel clients keep off the VPN, besides private ranges.
+    #[serde(default)]
+    pub excluded_cidrs: Vec<String>,
+    /// Whether full-tunnel clients keep private ranges off the VPN, unless
+    /// their config request says otherwise.
+    #[serde(default = "default_lan_access")]
+    pub lan_access: bool,
+    /// wg-quick hooks for client configs, unless clients set their own.
+    #[serde(flatten)]
+    pub hooks: Hooks,
+    /// Written after the DNS servers in client configs.
+    #[serde(default)]
+    pub search_domains: Vec<String>,
+    #[serde(default)]
+    pub topology: Topology,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn default_lan_access() -> bool {
+    true
+}
+
+/// `PostUp`, `PreDown`, and `PostDown` commands for client configs. In
+/// update requests, a present field replaces the current command and an
+/// empty one clears it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_up: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_down: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_down: Option<String>,
+}
+
+/// A network's peer hostnames under `<network>.wirewarden.internal`, from
+/// `GET /api/networks/{id}/dns`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DnsZone {
+    pub origin: String,
+    pub serial: u32,
+    pub records: Vec<DnsRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DnsRecord {
+    /// Fully qualified, without the trailing dot.
+    pub name: String,
+    /// `A` or `AAAA`.
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub address: String,
+    pub peer: PeerKind,
+    pub peer_id: Uuid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerKind {
+    Server,
+    Client,
+}
+
+/// How a network picks the address of each new server or client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Allocation {
+    /// The lowest free address, filling gaps left by deletions.
+    #[default]
+    LowestFree,
+    /// Any free address, so neighbours cannot be guessed by scanning.
+    RandomInRange,
+    /// The highest free address, keeping low ones for infrastructure.
+    SequentialFromHigh,
+}
+
+/// Which peers a network's servers have besides its clients.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Topology {
+    /// None; each server serves only its clients.
+    Hub,
+    /// Every other server, with the routes it advertises.
+    #[default]
+    Mesh,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateNetworkRequest {
+    pub name: String,
+    pub cidr: String,
+    pub dns_servers: Vec<String>,
+    pub persistent_keepalive: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port_range: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_template: Option<String>,
+    #[serde(default)]
+    pub allocation: Allocation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_cidrs: Vec<String>,
+    /// Defaults to keeping LAN access.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lan_access: Option<bool>,
+    #[serde(flatten)]
+    pub hooks: Hooks,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub search_domains: Vec<String>,
+    #[serde(default)]
+    pub topology: Topology,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateNetworkRequest {
+    pub dns_servers: Vec<String>,
+    pub persistent_keepalive: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Replaces the port range when present; an empty string clears it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port_range: Option<String>,
+    /// Replaces the endpoint template when present; an empty string clears
+    /// it. Servers still on the old template's hostname move to the new one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_template: Option<String>,
+    /// Applies to addresses given out from now on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocation: Option<Allocation>,
+    /// Replaces the MTU when present; zero clears it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    /// Replaces the excluded CIDRs when present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excluded_cidrs: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lan_access: Option<bool>,
+    #[serde(flatten)]
+    pub hooks: Hooks,
+    /// Replaces the search domains when present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_domains: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology: Option<Topology>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Server {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub name: String,
+    pub public_key: String,
+    /// Full token only in the create response; masked everywhere else.
+    pub api_token: String,
+    pub address_offset: i32,
+    pub address: String,
+    pub forwards_internet_traffic: bool,
+    pub manage_nat: bool,
+    #[serde(default)]
+    pub fwmark: Option<u32>,
+    #[serde(default)]
+    pub route_table: Option<u32>,
+    #[serde(default)]
+    pub rule_priority: Option<u32>,
+    /// Overrides the network's MTU.
+    #[serde(default)]
+    pub mtu: Option<u32>,
+    /// Overrides the network's keepalive on the server's links.
+    #[serde(default)]
+    pub persistent_keepalive: Option<i32>,
+    pub endpoint_host: Option<String>,
+    pub endpoint_port: i32,
+    /// Port the daemon listens on, when it differs from `endpoint_port`.
+    #[serde(default)]
+    pub listen_port: Option<u16>,
+    /// Servers with a higher priority claim overlapping AllowedIPs in client
+    /// configs first; ties go to the oldest.
+    #[serde(default)]
+    pub priority: i32,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub offline: bool,
+    /// Release the daemon last reported, if it has checked in.
+    #[serde(default)]
+    pub daemon_version: Option<String>,
+    /// The daemon reported a release older than `MIN_DAEMON_VERSION`.
+    #[serde(default)]
+    pub daemon_outdated: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub connect_command: Option<String>,
+    /// Base64 Ed25519 key the API signs daemon configs with; alongside
+    /// `connect_command` only.
+    #[serde(default)]
+    pub config_signing_key: Option<String>,
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("id", &self.id)
+            .field("network_id", &self.network_id)
+            .field("name", &self.name)
+            .field("public_key", &self.public_key)
+            .field("api_token", &Redacted)
+            .field("address_offset", &self.address_offset)
+            .field("address", &self.address)
+            .field("forwards_internet_traffic", &self.forwards_internet_traffic)
+            .field("manage_nat", &self.manage_nat)
+            .field("fwmark", &self.fwmark)
+            .field("route_table", &self.route_table)
+            .field("rule_priority", &self.rule_priority)
+            .field("mtu", &self.mtu)
+            .field("persistent_keepalive", &self.persistent_keepalive)
+            .field("endpoint_host", &self.endpoint_host)
+            .field("endpoint_port", &self.endpoint_port)
+            .field("listen_port", &self.listen_port)
+            .field("priority", &self.priority)
+            .field("tags", &self.tags)
+            .field("notes", &self.notes)
+            .field("last_seen_at", &self.last_seen_at)
+            .field("offline", &self.offline)
+            .field("daemon_version", &self.daemon_version)
+            .field("daemon_outdated", &self.daemon_outdated)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("connect_command", &redact_opt(&self.connect_command))
+            .field("config_signing_key", &self.config_signing_key)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateServerRequest {
+    pub network_id: Uuid,
+    pub name: String,
+    pub forwards_internet_traffic: bool,
+    /// Have the daemon add the masquerade rules; needs
+    /// `forwards_internet_traffic`.
+    #[serde(default)]
+    pub manage_nat: bool,
+    /// Mark WireGuard puts on the packets it sends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fwmark: Option<u32>,
+    /// Table the daemon routes the network in, with a rule looking it up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route_table: Option<u32>,
+    /// Priority of that rule; needs `route_table`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_priority: Option<u32>,
+    /// Overrides the network's MTU.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    /// Overrides the network's keepalive on the server's links, unless the
+    /// peer at the other end sets its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistent_keepalive: Option<i32>,
+    pub endpoint_host: Option<String>,
+    /// Defaults to the next free port in the network's range, or 51820.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_port: Option<i32>,
+    /// Port the daemon listens on, behind a NAT that forwards
+    /// `endpoint_port` to another; defaults to `endpoint_port`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen_port: Option<u16>,
+    /// Defaults to zero.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Client {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub name: String,
+    pub public_key: String,
+    pub address_offset: i32,
+    pub address: String,
+    pub access_schedule: Option<serde_json::Value>,
+    pub access_allowed: bool,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    /// Overrides the network's MTU in the client's config.
+    #[serde(default)]
+    pub mtu: Option<u32>,
+    /// Overrides the network's and servers' keepalive on the client's links.
+    #[serde(default)]
+    pub persistent_keepalive: Option<i32>,
+    /// Bytes the client may move per UTC month, both directions together.
+    #[serde(default)]
+    pub monthly_quota_bytes: Option<i64>,
+    /// Over this month's quota, and so cut off from its servers.
+    #[serde(default)]
+    pub quota_exceeded: bool,
+    /// Kilobits per second the client may send through a server.
+    #[serde(default)]
+    pub upload_kbps: Option<u32>,
+    /// Kilobits per second a server may send the client.
+    #[serde(default)]
+    pub download_kbps: Option<u32>,
+    /// Overrides the network's hooks in the client's config.
+    #[serde(flatten)]
+    pub hooks: Hooks,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateClientRequest {
+    pub network_id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistent_keepalive: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_quota_bytes: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_kbps: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_kbps: Option<u32>,
+    #[serde(flatten)]
+    pub hooks: Hooks,
+}
+
+/// Body of `POST /api/clients/{id}/move`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MoveClientRequest {
+    pub network_id: Uuid,
+}
+
+/// Body of `PATCH` on clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateNotesRequest {
+    /// Replaces the notes when present; an empty string clears them.
+    pub notes: Option<String>,
+    /// Replaces the client's MTU override when present; zero clears it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    /// Replaces the client's keepalive override when present; zero turns
+    /// keepalive off and a negative value clears it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistent_keepalive: Option<i32>,
+    /// Replaces the client's monthly quota when present; zero clears it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_quota_bytes: Option<i64>,
+    /// Replace the client's bandwidth caps when present; zero clears them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_kbps: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_kbps: Option<u32>,
+    #[serde(flatten)]
+    pub hooks: Hooks,
+}
+
+/// Body of `PATCH` on servers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UpdateServerRequest {
+    /// Replaces the notes when present; an empty string clears them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manage_nat: Option<bool>,
+    /// Policy routing fields replace the current value when present; zero
+    /// clears them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fwmark: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_table: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_priority: Option<u32>,
+    /// Replaces the MTU override when present; zero clears it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    /// Replaces the keepalive override when present; zero turns keepalive
+    /// off and a negative value clears it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistent_keepalive: Option<i32>,
+    /// Replaces the listen port when present; zero listens on
+    /// `endpoint_port` again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen_port: Option<u16>,
+    /// Replaces the priority when present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetTagsRequest {
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientConfig {
+    pub config: String,
+}
+
+/// Bytes a client moved over a range, from `GET /api/clients/{id}/usage`.
+/// `rx_bytes` is what its servers received from it, `tx_bytes` what they
+/// sent it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientUsage {
+    /// `hour` or `day`; points start on UTC boundaries.
+    pub step: String,
+    pub since: DateTime<Utc>,
+    pub rx_bytes: i64,
+    pub tx_bytes: i64,
+    /// Buckets with traffic, oldest first.
+    pub points: Vec<UsagePoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsagePoint {
+    pub at: DateTime<Utc>,
+    pub rx_bytes: i64,
+    pub tx_bytes: i64,
+}
+
+/// A peer connecting to or dropping off a server, from
+/// `GET /api/connections`. A peer counts as connected while its latest
+/// handshake with the server is under three minutes old.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionEvent {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub server_id: Uuid,
+    pub peer_id: Uuid,
+    pub peer_kind: PeerKind,
+    pub peer_name: String,
+    pub connected: bool,
+    pub last_handshake: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    pub id: Uuid,
+    pub server_id: Uuid,
+    pub route_cidr: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateRouteRequest {
+    pub route_cidr: String,
+}
+
+/// How a [`ClientRoute`] changes one client's AllowedIPs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientRouteKind {
+    /// Send one more CIDR through `server_id`.
+    Extra,
+    /// Route nothing but these CIDRs, and each server's own address.
+    Only,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientRoute {
+    pub id: Uuid,
+    pub client_id: Uuid,
+    pub kind: ClientRouteKind,
+    pub server_id: Option<Uuid>,
+    pub route_cidr: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateClientRouteRequest {
+    pub kind: ClientRouteKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_id: Option<Uuid>,
+    pub route_cidr: String,
+}
+
+/// What an [`AclRule`] does with the traffic it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AclAction {
+    Allow,
+    Deny,
+}
+
+/// A forwarding rule between a network's peers. Rules are checked by
+/// `position` and the first match decides; unmatched traffic is allowed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AclRule {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub position: i32,
+    pub action: AclAction,
+    /// `*`, `tag:<tag>`, or a CIDR.
+    pub source: String,
+    pub destination: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateAclRuleRequest {
+    /// Where to insert, from 1; after the last rule when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<i32>,
+    pub action: AclAction,
+    pub source: String,
+    pub destination: String,
+}
+
+/// Keys no server or client refers to, from `GET /api/tools/orphans` or,
+/// once removed, `POST /api/tools/orphans/purge`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrphanReport {
+    pub keys: Vec<Uuid>,
+}
+
+/// An entry in `GET /api/activity`, newest first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Activity {
+    Audit(AuditEntry),
+    Connection(ConnectionEvent),
+}
+
+/// An administrative action from the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub actor_id: Option<Uuid>,
+    /// What happened, e.g. `client.moved`.
+    pub action: String,
+    pub network_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Totals across every network, from `GET /api/summary`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub networks: i64,
+    pub servers: i64,
+    pub servers_online: i64,
+    /// Servers marked offline or that have never checked in.
+    pub servers_offline: i64,
+    pub clients: i64,
+    /// Clients that handshook with a server in the last 24 hours.
+    pub clients_recently_seen: i64,
+    /// Servers and clients, each holding one address.
+    pub addresses_used: i64,
+    pub address_capacity: i64,
+    /// Share of `address_capacity` in use, 0 to 1.
+    pub utilization: f64,
+}
+
+/// A row of `GET /api/reports/peers-per-network`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkPeersReport {
+    pub network_id: Uuid,
+    pub name: String,
+    pub enabled: bool,
+    pub servers: i64,
+    pub clients: i64,
+    /// Assignable addresses in the network.
+    pub capacity: i64,
+    /// Share of `capacity` in use, 0 to 1.
+    pub utilization: f64,
+}
+
+/// How finely `GET /api/reports/growth` buckets creation times.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrowthBucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+/// Query of `GET /api/reports/growth`. Without `from`, the last 30 buckets
+/// up to `to`, or now, are reported.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GrowthQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub bucket: GrowthBucket,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_id: Option<Uuid>,
+}
+
+/// Body of `GET /api/reports/growth`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrowthReport {
+    pub bucket: GrowthBucket,
+    pub points: Vec<GrowthPoint>,
+}
+
+/// Devices created in one bucket, and how many existed at its end. Deleted
+/// devices are not counted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrowthPoint {
+    pub period_start: DateTime<Utc>,
+    pub servers_added: i64,
+    pub clients_added: i64,
+    pub servers_total: i64,
+    pub clients_total: i64,
+}
+
+/// A row of `GET /api/reports/rotation`: each server-client pair's
+/// preshared key, by whether it was rotated within `max_age_days`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RotationReport {
+    pub network_id: Uuid,
+    pub name: String,
+    pub pairs: i64,
+    pub compliant: i64,
+    pub stale: i64,
+    /// Pairs that have no key yet.
+    pub missing: i64,
+    pub oldest_rotation: Option<DateTime<Utc>>,
+}
+
+/// A row of `GET /api/reports/devices-per-user`, counting devices in the
+/// networks the user owns. Unowned networks are reported without a user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserDevicesReport {
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub networks: i64,
+    pub servers: i64,
+    pub clients: i64,
+}
+
+/// Error body returned for every non-2xx response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub error: String,
+}
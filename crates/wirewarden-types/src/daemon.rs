@@ -12,17 +12,70 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+use crate::redact::{Redacted, redact_opt};
+
+/// Wire format version of [`DaemonConfig`]. Bumped only for changes an older
+/// daemon cannot safely ignore; optional additions are negotiated through
+/// [`Capabilities`] instead. Version 2 carries the negotiated features, such
+/// as forwarding rules, that a daemon must enforce rather than drop.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Request header carrying the daemon's build version.
+pub const DAEMON_VERSION_HEADER: &str = "X-Wirewarden-Daemon-Version";
+
+/// Response header carrying the oldest daemon release the API still
+/// supports, when one is configured.
+pub const MIN_DAEMON_VERSION_HEADER: &str = "X-Wirewarden-Min-Daemon-Version";
+
+/// Request header listing the optional config features the daemon handles,
+/// comma-separated.
+pub const CAPABILITIES_HEADER: &str = "X-Wirewarden-Capabilities";
+
+/// Response header carrying the network config serial a config was rendered
+/// at, to ask for a [`DaemonConfigDelta`] from next time.
+pub const CONFIG_SERIAL_HEADER: &str = "X-Wirewarden-Config-Serial";
+
+/// Response header carrying the base64 Ed25519 signature the API made, with
+/// the key it publishes for the server, over [`signed_message`] for the
+/// response body.
+pub const CONFIG_SIGNATURE_HEADER: &str = "X-Wirewarden-Config-Signature";
+
+/// Prefix of every signed config message, so a config signature can't be
+/// passed off as a signature over anything else.
+const SIGNATURE_CONTEXT: &[u8] = b"wirewarden daemon config v1\0";
+
+/// What a config signature covers: the server the config was rendered for,
+/// the serial it was rendered at and the exact bytes served. A signed config
+/// can then be neither handed to another server nor replayed over a newer
+/// one.
+pub fn signed_message(server_id: Uuid, serial: i64, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(SIGNATURE_CONTEXT.len() + 24 + body.len());
+    message.extend_from_slice(SIGNATURE_CONTEXT);
+    message.extend_from_slice(server_id.as_bytes());
+    message.extend_from_slice(&serial.to_be_bytes());
+    message.extend_from_slice(body);
+    message
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DaemonConfig {
+    /// [`CONFIG_VERSION`] of the API that rendered this. Zero from APIs that
+    /// predate versioning, whose format matches version 1.
+    #[serde(default)]
+    pub version: u32,
     pub server: DaemonServerInfo,
     pub network: DaemonNetworkInfo,
     pub peers: Vec<DaemonPeer>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct DaemonServerInfo {
     pub id: Uuid,
     pub name: String,
@@ -30,20 +83,455 @@ pub struct DaemonServerInfo {
     pub public_key: String,
     pub address: String,
     pub listen_port: i32,
+    /// Masquerade traffic the server forwards out of the network. Absent
+    /// from APIs that predate it, which left NAT to the host.
+    #[serde(default)]
+    pub manage_nat: bool,
+    /// Mark WireGuard puts on the packets it sends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fwmark: Option<u32>,
+    /// Table the interface's routes go in, instead of main.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route_table: Option<u32>,
+    /// Priority of the rule looking up `route_table`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_priority: Option<u32>,
+    /// Interface MTU, the server's own or else its network's. Unset leaves
+    /// the interface's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+impl fmt::Debug for DaemonServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DaemonServerInfo")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("private_key", &Redacted)
+            .field("public_key", &self.public_key)
+            .field("address", &self.address)
+            .field("listen_port", &self.listen_port)
+            .field("manage_nat", &self.manage_nat)
+            .field("fwmark", &self.fwmark)
+            .field("route_table", &self.route_table)
+            .field("rule_priority", &self.rule_priority)
+            .field("mtu", &self.mtu)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DaemonNetworkInfo {
     pub id: Uuid,
     pub name: String,
     pub cidr: String,
     pub persistent_keepalive: i32,
+    /// Resolvers the network hands its clients, for the daemon's DNS
+    /// forwarder to pass on queries outside the zone.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns_servers: Vec<String>,
+    /// Peer hostnames, for the daemon's DNS forwarder to answer. Absent
+    /// from older APIs, and when the network's name makes no DNS label.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_zone: Option<DaemonDnsZone>,
+    /// Forwarding policy between peers, checked in order with the first
+    /// match deciding. Traffic no rule matches is allowed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acl: Vec<DaemonAclRule>,
+}
+
+/// One forwarding rule. An empty side matches any address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonAclRule {
+    pub action: DaemonAclAction,
+    /// CIDRs the traffic comes from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    /// CIDRs the traffic is going to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destinations: Vec<String>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DaemonAclAction {
+    Allow,
+    Deny,
+}
+
+/// The network's peers under `<network>.wirewarden.internal`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonDnsZone {
+    /// Zone name, without the trailing dot.
+    pub origin: String,
+    pub records: Vec<DaemonDnsRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonDnsRecord {
+    /// Fully qualified, without the trailing dot.
+    pub name: String,
+    pub address: String,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct DaemonPeer {
     pub public_key: String,
     pub allowed_ips: Vec<String>,
     pub endpoint: Option<String>,
     pub preshared_key: Option<String>,
+    /// Seconds, overriding the network's keepalive for this peer. Absent
+    /// from older APIs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistent_keepalive: Option<i32>,
+    /// Kilobits per second the peer may send through the server, when
+    /// capped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_kbps: Option<u32>,
+    /// Kilobits per second the server may send the peer, when capped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_kbps: Option<u32>,
+}
+
+impl DaemonPeer {
+    /// The keepalive in effect for the peer, zero when off.
+    pub fn keepalive(&self, network: &DaemonNetworkInfo) -> u16 {
+        self.persistent_keepalive
+            .unwrap_or(network.persistent_keepalive)
+            .clamp(0, u16::MAX.into()) as u16
+    }
+}
+
+impl fmt::Debug for DaemonPeer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DaemonPeer")
+            .field("public_key", &self.public_key)
+            .field("allowed_ips", &self.allowed_ips)
+            .field("endpoint", &self.endpoint)
+            .field("preshared_key", &redact_opt(&self.preshared_key))
+            .field("persistent_keepalive", &self.persistent_keepalive)
+            .field("upload_kbps", &self.upload_kbps)
+            .field("download_kbps", &self.download_kbps)
+            .finish()
+    }
+}
+
+/// The peers changed since an earlier serial, from
+/// `GET /api/daemon/config/delta`. The server and network are sent whole.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonConfigDelta {
+    pub version: u32,
+    /// Serial the delta brings the config up to.
+    pub serial: i64,
+    /// The API no longer knew the config at the serial asked for, so
+    /// `upserted` holds every peer and any other peer is gone.
+    #[serde(default)]
+    pub full: bool,
+    pub server: DaemonServerInfo,
+    pub network: DaemonNetworkInfo,
+    /// Peers added or changed, in full.
+    pub upserted: Vec<DaemonPeer>,
+    /// Public keys of peers removed.
+    pub removed: Vec<String>,
+}
+
+impl DaemonConfigDelta {
+    /// What turns `old` into `new`, or with no `old`, a full delta.
+    pub fn between(old: Option<&DaemonConfig>, new: &DaemonConfig, serial: i64) -> Self {
+        let (upserted, removed) = match old {
+            Some(old) => {
+                let before: HashMap<&str, &DaemonPeer> = old
+                    .peers
+                    .iter()
+                    .map(|p| (p.public_key.as_str(), p))
+                    .collect();
+                let after: HashSet<&str> =
+                    new.peers.iter().map(|p| p.public_key.as_str()).collect();
+                let upserted = new
+                    .peers
+                    .iter()
+                    .filter(|p| before.get(p.public_key.as_str()) != Some(p))
+                    .cloned()
+                    .collect();
+                let removed = old
+                    .peers
+                    .iter()
+                    .filter(|p| !after.contains(p.public_key.as_str()))
+                    .map(|p| p.public_key.clone())
+                    .collect();
+                (upserted, removed)
+            }
+            None => (new.peers.clone(), Vec::new()),
+        };
+        Self {
+            version: new.version,
+            serial,
+            full: old.is_none(),
+            server: new.server.clone(),
+            network: new.network.clone(),
+            upserted,
+            removed,
+        }
+    }
+
+    /// The config this delta makes of `base`. Changed peers keep their
+    /// place and new ones go last.
+    pub fn apply(self, base: &DaemonConfig) -> DaemonConfig {
+        let mut peers = if self.full {
+            Vec::new()
+        } else {
+            base.peers.clone()
+        };
+        peers.retain(|p| !self.removed.contains(&p.public_key));
+        for peer in self.upserted {
+            match peers.iter_mut().find(|p| p.public_key == peer.public_key) {
+                Some(existing) => *existing = peer,
+                None => peers.push(peer),
+            }
+        }
+        DaemonConfig {
+            version: self.version,
+            server: self.server,
+            network: self.network,
+            peers,
+        }
+    }
+}
+
+/// Transfer counters and handshakes a daemon reports for its interface,
+/// with `POST /api/daemon/telemetry`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonTelemetry {
+    pub peers: Vec<DaemonPeerTelemetry>,
+    /// Whether `last_handshake` was read. Daemons that predate it, or could
+    /// not read handshakes, leave it false and connection state alone.
+    #[serde(default)]
+    pub handshakes: bool,
+}
+
+/// Bytes moved with a peer since the interface added it, as seen from the
+/// server. They drop back to zero when the peer is re-added.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonPeerTelemetry {
+    pub public_key: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    /// The latest completed handshake, if there has been one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_handshake: Option<DateTime<Utc>>,
+}
+
+/// Optional config features a daemon can handle, announced in
+/// [`CAPABILITIES_HEADER`]. The API leaves out what a daemon did not
+/// announce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    /// `psk`: peers may carry a preshared key.
+    pub preshared_keys: bool,
+    /// `nat`: the server may be asked to masquerade forwarded traffic.
+    pub nat: bool,
+    /// `routing`: the server may carry a fwmark, route table and rule
+    /// priority.
+    pub policy_routing: bool,
+    /// `mtu`: the server may carry an interface MTU.
+    pub mtu: bool,
+    /// `dns`: the network may carry resolvers and a zone for the DNS
+    /// forwarder.
+    pub dns: bool,
+    /// `acl`: the network may carry forwarding rules.
+    pub acl: bool,
+    /// `keepalive`: peers may carry their own keepalive.
+    pub peer_keepalive: bool,
+    /// `rate-limit`: peers may carry bandwidth caps.
+    pub rate_limits: bool,
+}
+
+impl Capabilities {
+    /// Everything this build understands.
+    pub const CURRENT: Self = Self {
+        preshared_keys: true,
+        nat: true,
+        policy_routing: true,
+        mtu: true,
+        dns: true,
+        acl: true,
+        peer_keepalive: true,
+        rate_limits: true,
+    };
+
+    /// Daemons that predate the header, which handle the version 1 format
+    /// with preshared keys.
+    pub const LEGACY: Self = Self {
+        preshared_keys: true,
+        ..Self::NONE
+    };
+
+    const NONE: Self = Self {
+        preshared_keys: false,
+        nat: false,
+        policy_routing: false,
+        mtu: false,
+        dns: false,
+        acl: false,
+        peer_keepalive: false,
+        rate_limits: false,
+    };
+
+    /// Each capability's header name and flag.
+    fn flags(&mut self) -> [(&'static str, &mut bool); 8] {
+        [
+            ("psk", &mut self.preshared_keys),
+            ("nat", &mut self.nat),
+            ("routing", &mut self.policy_routing),
+            ("mtu", &mut self.mtu),
+            ("dns", &mut self.dns),
+            ("acl", &mut self.acl),
+            ("keepalive", &mut self.peer_keepalive),
+            ("rate-limit", &mut self.rate_limits),
+        ]
+    }
+
+    /// Parse the header value. Daemons that predate the header get
+    /// [`LEGACY`](Self::LEGACY); unknown names are ignored.
+    pub fn from_header(value: Option<&str>) -> Self {
+        let Some(value) = value else {
+            return Self::LEGACY;
+        };
+        let mut caps = Self::NONE;
+        for name in value.split(',').map(str::trim) {
+            for (flag, set) in caps.flags() {
+                if name.eq_ignore_ascii_case(flag) {
+                    *set = true;
+                }
+            }
+        }
+        caps
+    }
+
+    pub fn to_header(mut self) -> String {
+        let names: Vec<_> = self
+            .flags()
+            .into_iter()
+            .filter(|(_, set)| **set)
+            .map(|(name, _)| name)
+            .collect();
+        names.join(",")
+    }
+
+    /// The [`DaemonConfig::version`] to render at: 1 while the daemon
+    /// handles nothing version 1 lacked, so daemons that predate version 2
+    /// keep accepting their configs.
+    pub fn config_version(self) -> u32 {
+        let v1 = Self {
+            preshared_keys: self.preshared_keys,
+            ..Self::NONE
+        };
+        if self == v1 { 1 } else { CONFIG_VERSION }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(None, true ; "legacy daemon")]
+    #[test_case(Some(""), false ; "none")]
+    #[test_case(Some("psk"), true ; "psk")]
+    #[test_case(Some("future, PSK"), true ; "unknown and mixed case")]
+    #[test_case(Some("future"), false ; "unknown only")]
+    fn test_capabilities_from_header(value: Option<&str>, psk: bool) {
+        assert_eq!(Capabilities::from_header(value).preshared_keys, psk);
+    }
+
+    #[test]
+    fn test_capabilities_round_trip() {
+        let header = Capabilities::CURRENT.to_header();
+        assert_eq!(header, "psk,nat,routing,mtu,dns,acl,keepalive,rate-limit");
+        assert_eq!(
+            Capabilities::from_header(Some(&header)),
+            Capabilities::CURRENT
+        );
+        assert_eq!(Capabilities::from_header(Some("acl")).to_header(), "acl");
+    }
+
+    #[test_case(None, 1 ; "legacy daemon")]
+    #[test_case(Some("psk"), 1 ; "psk only")]
+    #[test_case(Some(""), 1 ; "none")]
+    #[test_case(Some("psk,acl"), CONFIG_VERSION ; "newer feature")]
+    fn test_config_version(value: Option<&str>, version: u32) {
+        assert_eq!(Capabilities::from_header(value).config_version(), version);
+    }
+
+    #[test]
+    fn test_signed_message_binds_server_and_serial() {
+        let id = Uuid::from_u128(1);
+        let message = signed_message(id, 7, b"{}");
+        assert!(message.ends_with(b"{}"));
+        assert_ne!(message, signed_message(Uuid::from_u128(2), 7, b"{}"));
+        assert_ne!(message, signed_message(id, 6, b"{}"));
+        assert_ne!(message, signed_message(id, 7, b"{ }"));
+    }
+
+    fn config(peers: &[(&str, &str)]) -> DaemonConfig {
+        DaemonConfig {
+            version: CONFIG_VERSION,
+            server: DaemonServerInfo {
+                id: Uuid::nil(),
+                name: "relay".into(),
+                private_key: "priv".into(),
+                public_key: "pub".into(),
+                address: "10.0.0.1/24".into(),
+                listen_port: 51820,
+                manage_nat: false,
+                fwmark: None,
+                route_table: None,
+                rule_priority: None,
+                mtu: None,
+            },
+            network: DaemonNetworkInfo {
+                id: Uuid::nil(),
+                name: "home".into(),
+                cidr: "10.0.0.0/24".into(),
+                persistent_keepalive: 25,
+                dns_servers: Vec::new(),
+                dns_zone: None,
+                acl: Vec::new(),
+            },
+            peers: peers
+                .iter()
+                .map(|(key, ip)| DaemonPeer {
+                    public_key: (*key).into(),
+                    allowed_ips: vec![(*ip).into()],
+                    endpoint: None,
+                    preshared_key: None,
+                    persistent_keepalive: None,
+                    upload_kbps: None,
+                    download_kbps: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_delta_carries_only_changes() {
+        let old = config(&[("a", "10.0.0.2/32"), ("b", "10.0.0.3/32"), ("c", "10.0.0.4/32")]);
+        let new = config(&[("a", "10.0.0.2/32"), ("c", "10.0.0.9/32"), ("d", "10.0.0.5/32")]);
+        let delta = DaemonConfigDelta::between(Some(&old), &new, 7);
+        assert!(!delta.full);
+        assert_eq!(delta.serial, 7);
+        let upserted: Vec<_> = delta.upserted.iter().map(|p| p.public_key.as_str()).collect();
+        assert_eq!(upserted, ["c", "d"]);
+        assert_eq!(delta.removed, ["b"]);
+        assert_eq!(delta.apply(&old), new);
+    }
+
+    #[test]
+    fn test_full_delta_replaces_peers() {
+        let old = config(&[("a", "10.0.0.2/32"), ("b", "10.0.0.3/32")]);
+        let new = config(&[("b", "10.0.0.3/32")]);
+        let delta = DaemonConfigDelta::between(None, &new, 3);
+        assert!(delta.full);
+        assert_eq!(delta.apply(&old), new);
+    }
 }
@@ -0,0 +1,282 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! gRPC form of the daemon protocol, defined in `proto/daemon.proto`.
+//!
+//! The generated code is checked in so building doesn't need `protoc`.
+
+use std::fmt;
+
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::daemon;
+use crate::redact::{Redacted, redact_opt};
+
+#[allow(clippy::all)]
+#[path = "generated/wirewarden_daemon_v1.rs"]
+mod proto;
+
+pub use proto::*;
+
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    #[error("missing field: {0}")]
+    MissingField(&'static str),
+
+    #[error("invalid UUID in field: {0}")]
+    InvalidId(&'static str),
+}
+
+impl fmt::Debug for DaemonServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DaemonServerInfo")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("private_key", &Redacted)
+            .field("public_key", &self.public_key)
+            .field("address", &self.address)
+            .field("listen_port", &self.listen_port)
+            .field("manage_nat", &self.manage_nat)
+            .field("fwmark", &self.fwmark)
+            .field("route_table", &self.route_table)
+            .field("rule_priority", &self.rule_priority)
+            .field("mtu", &self.mtu)
+            .finish()
+    }
+}
+
+impl fmt::Debug for DaemonPeer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DaemonPeer")
+            .field("public_key", &self.public_key)
+            .field("allowed_ips", &self.allowed_ips)
+            .field("endpoint", &self.endpoint)
+            .field("preshared_key", &redact_opt(&self.preshared_key))
+            .field("persistent_keepalive", &self.persistent_keepalive)
+            .field("upload_kbps", &self.upload_kbps)
+            .field("download_kbps", &self.download_kbps)
+            .finish()
+    }
+}
+
+impl DaemonConfig {
+    pub fn from_config(config: daemon::DaemonConfig, config_serial: i64) -> Self {
+        let server = config.server;
+        let network = config.network;
+        Self {
+            server: Some(DaemonServerInfo {
+                id: server.id.to_string(),
+                name: server.name,
+                private_key: server.private_key,
+                public_key: server.public_key,
+                address: server.address,
+                listen_port: server.listen_port,
+                manage_nat: server.manage_nat,
+                fwmark: server.fwmark,
+                route_table: server.route_table,
+                rule_priority: server.rule_priority,
+                mtu: server.mtu,
+            }),
+            network: Some(DaemonNetworkInfo {
+                id: network.id.to_string(),
+                name: network.name,
+                cidr: network.cidr,
+                persistent_keepalive: network.persistent_keepalive,
+                dns_servers: network.dns_servers,
+                dns_zone: network.dns_zone.map(|zone| DaemonDnsZone {
+                    origin: zone.origin,
+                    records: zone
+                        .records
+                        .into_iter()
+                        .map(|r| DaemonDnsRecord {
+                            name: r.name,
+                            address: r.address,
+                        })
+                        .collect(),
+                }),
+                acl: network
+                    .acl
+                    .into_iter()
+                    .map(|r| DaemonAclRule {
+                        deny: r.action == daemon::DaemonAclAction::Deny,
+                        sources: r.sources,
+                        destinations: r.destinations,
+                    })
+                    .collect(),
+            }),
+            peers: config
+                .peers
+                .into_iter()
+                .map(|p| DaemonPeer {
+                    public_key: p.public_key,
+                    allowed_ips: p.allowed_ips,
+                    endpoint: p.endpoint,
+                    preshared_key: p.preshared_key,
+                    persistent_keepalive: p.persistent_keepalive,
+                    upload_kbps: p.upload_kbps,
+                    download_kbps: p.download_kbps,
+                })
+                .collect(),
+            config_serial,
+            version: config.version,
+            signature: None,
+            signed_config: None,
+        }
+    }
+}
+
+impl TryFrom<DaemonConfig> for daemon::DaemonConfig {
+    type Error = ConvertError;
+
+    fn try_from(msg: DaemonConfig) -> Result<Self, Self::Error> {
+        let server = msg.server.ok_or(ConvertError::MissingField("server"))?;
+        let network = msg.network.ok_or(ConvertError::MissingField("network"))?;
+        Ok(Self {
+            version: msg.version,
+            server: daemon::DaemonServerInfo {
+                id: parse_id(&server.id, "server.id")?,
+                name: server.name,
+                private_key: server.private_key,
+                public_key: server.public_key,
+                address: server.address,
+                listen_port: server.listen_port,
+                manage_nat: server.manage_nat,
+                fwmark: server.fwmark,
+                route_table: server.route_table,
+                rule_priority: server.rule_priority,
+                mtu: server.mtu,
+            },
+            network: daemon::DaemonNetworkInfo {
+                id: parse_id(&network.id, "network.id")?,
+                name: network.name,
+                cidr: network.cidr,
+                persistent_keepalive: network.persistent_keepalive,
+                dns_servers: network.dns_servers,
+                dns_zone: network.dns_zone.map(|zone| daemon::DaemonDnsZone {
+                    origin: zone.origin,
+                    records: zone
+                        .records
+                        .into_iter()
+                        .map(|r| daemon::DaemonDnsRecord {
+                            name: r.name,
+                            address: r.address,
+                        })
+                        .collect(),
+                }),
+                acl: network
+                    .acl
+                    .into_iter()
+                    .map(|r| daemon::DaemonAclRule {
+                        action: if r.deny {
+                            daemon::DaemonAclAction::Deny
+                        } else {
+                            daemon::DaemonAclAction::Allow
+                        },
+                        sources: r.sources,
+                        destinations: r.destinations,
+                    })
+                    .collect(),
+            },
+            peers: msg
+                .peers
+                .into_iter()
+                .map(|p| daemon::DaemonPeer {
+                    public_key: p.public_key,
+                    allowed_ips: p.allowed_ips,
+                    endpoint: p.endpoint,
+                    preshared_key: p.preshared_key,
+                    persistent_keepalive: p.persistent_keepalive,
+                    upload_kbps: p.upload_kbps,
+                    download_kbps: p.download_kbps,
+                })
+                .collect(),
+        })
+    }
+}
+
+fn parse_id(value: &str, field: &'static str) -> Result<Uuid, ConvertError> {
+    value.parse().map_err(|_| ConvertError::InvalidId(field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    fn sample() -> daemon::DaemonConfig {
+        daemon::DaemonConfig {
+            version: daemon::CONFIG_VERSION,
+            server: daemon::DaemonServerInfo {
+                id: Uuid::from_u128(1),
+                name: "relay".into(),
+                private_key: "priv".into(),
+                public_key: "pub".into(),
+                address: "10.0.0.1/24".into(),
+                listen_port: 51820,
+                manage_nat: true,
+                fwmark: Some(51820),
+                route_table: Some(1000),
+                rule_priority: None,
+                mtu: Some(1420),
+            },
+            network: daemon::DaemonNetworkInfo {
+                id: Uuid::from_u128(2),
+                name: "home".into(),
+                cidr: "10.0.0.0/24".into(),
+                persistent_keepalive: 25,
+                dns_servers: vec!["1.1.1.1".into()],
+                dns_zone: Some(daemon::DaemonDnsZone {
+                    origin: "home.wirewarden.internal".into(),
+                    records: vec![daemon::DaemonDnsRecord {
+                        name: "relay.home.wirewarden.internal".into(),
+                        address: "10.0.0.1".into(),
+                    }],
+                }),
+                acl: vec![daemon::DaemonAclRule {
+                    action: daemon::DaemonAclAction::Deny,
+                    sources: vec!["10.0.0.2/32".into()],
+                    destinations: Vec::new(),
+                }],
+            },
+            peers: vec![daemon::DaemonPeer {
+                public_key: "peer".into(),
+                allowed_ips: vec!["10.0.0.2/32".into()],
+                endpoint: None,
+                preshared_key: Some("psk".into()),
+                persistent_keepalive: Some(60),
+                upload_kbps: Some(1000),
+                download_kbps: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_round_trip_through_wire() {
+        let msg = DaemonConfig::from_config(sample(), 7);
+        let decoded = DaemonConfig::decode(msg.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded.config_serial, 7);
+        assert_eq!(daemon::DaemonConfig::try_from(decoded).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_missing_server_rejected() {
+        let mut msg = DaemonConfig::from_config(sample(), 1);
+        msg.server = None;
+        assert!(matches!(
+            daemon::DaemonConfig::try_from(msg),
+            Err(ConvertError::MissingField("server"))
+        ));
+    }
+}
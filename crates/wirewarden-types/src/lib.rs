@@ -14,4 +14,9 @@
 
 //! Shared type definitions for the wirewarden ecosystem.
 
+pub mod api;
 pub mod daemon;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod redact;
+pub mod version;
@@ -0,0 +1,57 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Debug output for types holding secrets: API tokens, private and
+//! preshared keys, passwords. Their `Debug` impls write [`Redacted`] in the
+//! secret's place, so a `?value` in a log line or a panic message cannot
+//! leak one.
+
+use std::fmt;
+
+/// Formats as `<redacted>`, standing in for a secret.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl fmt::Display for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// [`Redacted`] for an optional secret that is set, so Debug output still
+/// tells set from unset.
+pub fn redact_opt<T>(secret: &Option<T>) -> Option<Redacted> {
+    secret.as_ref().map(|_| Redacted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacted_hides_the_value() {
+        assert_eq!(format!("{Redacted:?}"), "<redacted>");
+        assert_eq!(
+            format!("{:?}", redact_opt(&Some("hunter2"))),
+            "Some(<redacted>)"
+        );
+        assert_eq!(format!("{:?}", redact_opt::<String>(&None)), "None");
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Release versions as found in `git describe --tags` output.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// A `MAJOR.MINOR.PATCH` release, ordered numerically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+#[derive(Debug, Error)]
+#[error("not a release version: {0}")]
+pub struct ParseVersionError(String);
+
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    /// Accepts an optional `v` prefix, a missing patch, and the
+    /// `-N-gHASH` and `-dirty` suffixes `git describe` appends. A bare commit
+    /// hash from an untagged build is an error.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.to_string());
+        let s = s.trim();
+        let release = s.strip_prefix('v').unwrap_or(s);
+        let release = release.split('-').next().unwrap_or_default();
+
+        let mut parts = release.split('.');
+        let mut next = |required| match parts.next() {
+            Some(n) => n.parse::<u64>().map_err(|_| err()),
+            None if required => Err(err()),
+            None => Ok(0),
+        };
+        let version = Self {
+            major: next(true)?,
+            minor: next(true)?,
+            patch: next(false)?,
+        };
+        if parts.next().is_some() {
+            return Err(err());
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("1.2.3", Some((1, 2, 3)) ; "plain")]
+    #[test_case("v0.4.0", Some((0, 4, 0)) ; "v prefix")]
+    #[test_case("v1.2", Some((1, 2, 0)) ; "no patch")]
+    #[test_case("v1.2.3-14-g2414721-dirty", Some((1, 2, 3)) ; "describe suffix")]
+    #[test_case("g2414721", None ; "untagged hash")]
+    #[test_case("1234567", None ; "numeric hash")]
+    #[test_case("unknown", None ; "unknown")]
+    #[test_case("1.2.3.4", None ; "too many parts")]
+    #[test_case("", None ; "empty")]
+    fn test_parse(input: &str, expected: Option<(u64, u64, u64)>) {
+        let parsed = input.parse::<Version>().ok();
+        assert_eq!(parsed.map(|v| (v.major, v.minor, v.patch)), expected);
+    }
+
+    #[test]
+    fn test_order_is_numeric() {
+        let older: Version = "v0.9.0".parse().unwrap();
+        let newer: Version = "v0.10.0".parse().unwrap();
+        assert!(older < newer);
+    }
+}
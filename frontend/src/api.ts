@@ -47,6 +47,7 @@ export interface User {
   username: string;
   display_name: string;
   email: string;
+  email_verified: boolean;
   created_at: string;
 }
 
@@ -91,6 +92,17 @@ export const authApi = {
     });
   },
 
+  verifyEmail(token: string) {
+    return api<User>('/auth/verify', {
+      method: 'POST',
+      body: JSON.stringify({ token }),
+    });
+  },
+
+  resendVerification() {
+    return api<void>('/auth/verify/resend', { method: 'POST' });
+  },
+
   resetPassword(token: string, password: string) {
     return api<{ status: string }>('/auth/reset-password', {
       method: 'POST',
@@ -0,0 +1,58 @@
+// Copyright (C) 2025 Joseph Sacchini
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+import { useEffect, useState } from 'react';
+import { Link, useSearchParams } from 'react-router';
+import { authApi, ApiError } from '../api';
+import './auth.scss';
+
+export function VerifyEmailPage() {
+  const [searchParams] = useSearchParams();
+  const token = searchParams.get('token') ?? '';
+  const [status, setStatus] = useState<'pending' | 'done' | 'failed'>('pending');
+  const [error, setError] = useState('');
+
+  useEffect(() => {
+    if (!token) return;
+    authApi
+      .verifyEmail(token)
+      .then(() => setStatus('done'))
+      .catch((err) => {
+        setError(err instanceof ApiError ? err.message : 'Verification failed');
+        setStatus('failed');
+      });
+  }, [token]);
+
+  if (!token) {
+    return (
+      <div className="auth-page">
+        <h1>Verify Email</h1>
+        <p className="error">Missing verification token.</p>
+      </div>
+    );
+  }
+
+  return (
+    <div className="auth-page">
+      <h1>Verify Email</h1>
+      {status === 'pending' && <p>Verifying…</p>}
+      {status === 'done' && (
+        <p>
+          Your email address is confirmed. <Link to="/">Continue</Link>
+        </p>
+      )}
+      {status === 'failed' && <p className="error">{error}</p>}
+    </div>
+  );
+}
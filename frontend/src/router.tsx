@@ -19,6 +19,7 @@ import { LoginPage } from './pages/LoginPage';
 import { RegisterPage } from './pages/RegisterPage';
 import { ForgotPasswordPage } from './pages/ForgotPasswordPage';
 import { ResetPasswordPage } from './pages/ResetPasswordPage';
+import { VerifyEmailPage } from './pages/VerifyEmailPage';
 import { DashboardPage } from './pages/DashboardPage';
 import { SettingsPage } from './pages/SettingsPage';
 import { NetworksPage } from './pages/NetworksPage';
@@ -64,6 +65,8 @@ export const router = createBrowserRouter([
           { path: '/reset-password', element: <ResetPasswordPage /> },
         ],
       },
+      // Opened from a mailed link, logged in or not.
+      { path: '/verify-email', element: <VerifyEmailPage /> },
     ],
   },
 ]);